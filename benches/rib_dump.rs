@@ -0,0 +1,56 @@
+//! Benchmarks decoding the routes of a full RIB dump with `RibDecoder`, whose parse time is
+//! dominated by the AS_PATH attributes of the RIB entries.
+//!
+//! Run with `cargo bench --features mrt --bench rib_dump`. The records of the dump are read
+//! into memory first, so that only the decoding of the routes is timed.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::time::{Duration, Instant};
+
+use bgp_rs::RibDecoder;
+use libflate::gzip::Decoder;
+use mrt_rs::records::tabledump::TABLE_DUMP_V2;
+use mrt_rs::Record;
+
+const DUMP: &str = "res/mrt/bview.20100101.0759.gz";
+const RUNS: u32 = 5;
+
+fn decode(records: &[TABLE_DUMP_V2]) -> usize {
+    let mut decoder = RibDecoder::new();
+    records
+        .iter()
+        .map(|record| decoder.decode(record).expect("Decoding RIB record").len())
+        .sum()
+}
+
+fn main() {
+    let file = File::open(DUMP).expect("Opening RIB dump");
+    let mut stream = Decoder::new(BufReader::new(file)).expect("Reading gzip header");
+    let mut records = vec![];
+    while let Some((_, record)) = mrt_rs::read(&mut stream).expect("Reading MRT record") {
+        if let Record::TABLE_DUMP_V2(record) = record {
+            records.push(record);
+        }
+    }
+
+    let mut routes = 0;
+    let mut best = Duration::from_secs(u64::max_value());
+    let mut total = Duration::from_secs(0);
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        routes = decode(&records);
+        let elapsed = start.elapsed();
+        best = best.min(elapsed);
+        total += elapsed;
+    }
+    println!(
+        "{}: {} records, {} routes, best {:?}, mean {:?} over {} runs",
+        DUMP,
+        records.len(),
+        routes,
+        best,
+        total / RUNS,
+        RUNS
+    );
+}
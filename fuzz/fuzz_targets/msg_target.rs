@@ -9,14 +9,14 @@ use bgp_rs::Reader;
 pub fn do_test(data: &[u8]) {
     if data.len() < 2 { return; }
     let cap_byte = data[0];
-    let _ = Reader {
-        stream: & data[1..],
-        capabilities: Capabilities {
+    let _ = Reader::with_capabilities(
+        & data[1..],
+        Capabilities {
             FOUR_OCTET_ASN_SUPPORT: (cap_byte & 0b1) == 0b1,
             EXTENDED_PATH_NLRI_SUPPORT: (cap_byte & 0b10) == 0b10,
             ..Capabilities::default()
-        }
-    }.read();
+        },
+    ).read();
 }
 
 #[cfg(feature = "afl")]
@@ -0,0 +1,34 @@
+// Generates arbitrary messages from the input, which must encode & decode without changes.
+
+extern crate arbitrary;
+extern crate bgp_rs;
+use arbitrary::{Arbitrary, Unstructured};
+use bgp_rs::Message;
+
+#[inline]
+pub fn do_test(data: &[u8]) {
+    if let Ok(message) = Message::arbitrary(&mut Unstructured::new(data)) {
+        bgp_rs::roundtrip(&message).unwrap();
+    }
+}
+
+#[cfg(feature = "afl")]
+#[macro_use] extern crate afl;
+#[cfg(feature = "afl")]
+fn main() {
+    fuzz!(|data| {
+        do_test(data);
+    });
+}
+
+#[cfg(feature = "honggfuzz")]
+#[macro_use] extern crate honggfuzz;
+#[cfg(feature = "honggfuzz")]
+fn main() {
+    loop {
+        fuzz!(|data| {
+            do_test(data);
+        });
+    }
+}
+
@@ -0,0 +1,229 @@
+//! The `aggregate` mod provides the generation of aggregate routes
+//! - Path attributes of aggregates as described in RFC4271 section 9.2.2.2: ORIGIN, AS_PATH
+//!   (with an AS_SET of the differing ASNs), ATOMIC_AGGREGATE and AGGREGATOR, of routes
+//!   sharing their MULTI_EXIT_DISC
+//! - UPDATE messages announcing the aggregates of configured prefixes
+//!
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::*;
+
+/// Generates aggregate routes, originated by the local speaker, for configured prefixes that
+/// cover more specific routes.
+///
+/// ```
+/// use bgp_rs::{ASPath, Capabilities, PathAttribute, Prefix, RouteAggregator, Segment};
+///
+/// let as_path = |asns| PathAttribute::AS_PATH(ASPath { segments: vec![Segment::AS_SEQUENCE(asns)] });
+/// let routes: Vec<(Prefix, Vec<PathAttribute>)> = vec![
+///     ("10.1.0.0/16".parse().unwrap(), vec![as_path(vec![65001, 65002])]),
+///     ("10.2.0.0/16".parse().unwrap(), vec![as_path(vec![65001, 65003])]),
+///     ("192.0.2.0/24".parse().unwrap(), vec![as_path(vec![65004])]),
+/// ];
+///
+/// let mut aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+/// aggregator.aggregates.push("10.0.0.0/8".parse().unwrap());
+/// let updates = aggregator.updates(
+///     &routes,
+///     "192.0.2.1".parse().unwrap(),
+///     4096,
+///     &Capabilities::default(),
+/// );
+/// assert_eq!(updates.len(), 1);
+/// assert_eq!(
+///     updates[0].to_string(),
+///     "10.0.0.0/8 via 192.0.2.1, origin IGP, AS path 65001 {65002,65003}, \
+///      aggregator 65000 192.0.2.1"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct RouteAggregator {
+    /// The ASN of the local speaker, used in AGGREGATOR.
+    pub local_asn: u32,
+
+    /// The BGP Identifier of the local speaker, used in AGGREGATOR.
+    pub router_id: Ipv4Addr,
+
+    /// Append the ASNs that differ between the aggregated routes as AS_SET. Otherwise only
+    /// the common leading part of the AS_PATHs is kept, and ATOMIC_AGGREGATE is added when
+    /// they differ.
+    pub as_set: bool,
+
+    /// The prefixes to aggregate more specific routes into.
+    pub aggregates: Vec<Prefix>,
+}
+
+// An ASN of an AS_PATH, along with whether it is part of an AS_SET
+type Tuple = (bool, u32);
+
+fn tuples(as_path: &ASPath) -> Vec<Tuple> {
+    as_path
+        .segments
+        .iter()
+        .flat_map(|segment| {
+            let (is_set, asns) = match segment {
+                Segment::AS_SEQUENCE(asns) => (false, asns),
+                Segment::AS_SET(asns) => (true, asns),
+            };
+            asns.iter().map(move |asn| (is_set, *asn))
+        })
+        .collect()
+}
+
+fn segments(tuples: &[Tuple]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = vec![];
+    for (is_set, asn) in tuples {
+        match (segments.last_mut(), is_set) {
+            (Some(Segment::AS_SET(asns)), true) | (Some(Segment::AS_SEQUENCE(asns)), false) => {
+                asns.push(*asn)
+            }
+            (_, true) => segments.push(Segment::AS_SET(vec![*asn])),
+            (_, false) => segments.push(Segment::AS_SEQUENCE(vec![*asn])),
+        }
+    }
+    segments
+}
+
+impl RouteAggregator {
+    /// Create an aggregator without aggregates, computing AS_SETs.
+    pub fn new(local_asn: u32, router_id: Ipv4Addr) -> RouteAggregator {
+        RouteAggregator {
+            local_asn,
+            router_id,
+            as_set: true,
+            aggregates: vec![],
+        }
+    }
+
+    /// The path attributes of the aggregate of routes, given by their path attributes.
+    /// The next hop is left to the caller. Returns None without routes, or when their
+    /// MULTI_EXIT_DISC differ, as such routes shall not be aggregated (RFC4271 section 9.2.2.2).
+    ///
+    /// ```
+    /// use bgp_rs::{ASPath, PathAttribute, RouteAggregator, Segment};
+    ///
+    /// let as_path = |asns| PathAttribute::AS_PATH(ASPath { segments: vec![Segment::AS_SEQUENCE(asns)] });
+    /// let mut aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+    /// aggregator.as_set = false;
+    /// let attributes = aggregator
+    ///     .aggregate(vec![
+    ///         &[as_path(vec![65001, 65002])][..],
+    ///         &[as_path(vec![65001, 65003])][..],
+    ///     ])
+    ///     .unwrap();
+    /// let attributes: Vec<String> = attributes.iter().map(|a| a.to_string()).collect();
+    /// assert_eq!(
+    ///     attributes,
+    ///     vec!["origin IGP", "AS path 65001", "atomic aggregate", "aggregator 65000 192.0.2.1"]
+    /// );
+    /// ```
+    pub fn aggregate<'a>(
+        &self,
+        routes: impl IntoIterator<Item = &'a [PathAttribute]>,
+    ) -> Option<Vec<PathAttribute>> {
+        let mut origin: Option<Origin> = None;
+        let mut atomic_aggregate = false;
+        let mut paths: Vec<Vec<Tuple>> = vec![];
+        let mut med: Option<Option<u32>> = None;
+        for attributes in routes {
+            let route_med = attributes.iter().find_map(|attribute| match attribute {
+                PathAttribute::MULTI_EXIT_DISC(med) => Some(*med),
+                _ => None,
+            });
+            if *med.get_or_insert(route_med) != route_med {
+                return None;
+            }
+            let route_origin = attributes
+                .iter()
+                .find_map(|attribute| match attribute {
+                    PathAttribute::ORIGIN(origin) => Some(*origin),
+                    _ => None,
+                })
+                .unwrap_or(Origin::IGP);
+            // INCOMPLETE takes precedence over EGP, which takes precedence over IGP
+            origin = Some(match (origin, route_origin) {
+                (Some(Origin::INCOMPLETE), _) | (_, Origin::INCOMPLETE) => Origin::INCOMPLETE,
+                (Some(Origin::EGP), _) | (_, Origin::EGP) => Origin::EGP,
+                _ => Origin::IGP,
+            });
+            atomic_aggregate |= attributes
+                .iter()
+                .any(|attribute| attribute.id() == Identifier::ATOMIC_AGGREGATOR);
+            paths.push(tuples(
+                &effective_as_path(attributes).unwrap_or(ASPath { segments: vec![] }),
+            ));
+        }
+        let origin = origin?;
+
+        // The longest leading sequence of tuples common to all paths
+        let first = &paths[0];
+        let common = paths[1..].iter().fold(first.len(), |common, path| {
+            first
+                .iter()
+                .zip(path)
+                .take(common)
+                .take_while(|(tuple, other)| tuple == other)
+                .count()
+        });
+        let mut as_path = segments(&first[..common]);
+        let mut rest: Vec<u32> = paths
+            .iter()
+            .flat_map(|path| path[common..].iter().map(|(_, asn)| *asn))
+            .collect();
+        rest.sort();
+        rest.dedup();
+        if !rest.is_empty() {
+            if self.as_set {
+                as_path.push(Segment::AS_SET(rest));
+            } else {
+                atomic_aggregate = true;
+            }
+        }
+
+        let mut attributes = vec![
+            PathAttribute::ORIGIN(origin),
+            PathAttribute::AS_PATH(ASPath { segments: as_path }),
+        ];
+        if atomic_aggregate {
+            attributes.push(PathAttribute::ATOMIC_AGGREGATOR);
+        }
+        attributes.push(PathAttribute::AGGREGATOR((self.local_asn, self.router_id)));
+        Some(attributes)
+    }
+
+    /// The UPDATE messages announcing the aggregates covering more specific routes, with the
+    /// given next hop, packed as described by `pack_updates`. Aggregates that cover no routes,
+    /// or routes of differing MULTI_EXIT_DISC, are not announced.
+    pub fn updates(
+        &self,
+        routes: &[(Prefix, Vec<PathAttribute>)],
+        next_hop: IpAddr,
+        max_size: usize,
+        capabilities: &Capabilities,
+    ) -> Vec<Update> {
+        let aggregates = self.aggregates.iter().filter_map(|aggregate| {
+            let covered = routes.iter().filter(|(prefix, _)| {
+                prefix.length > aggregate.length && aggregate.contains(prefix)
+            });
+            let mut attributes = self.aggregate(covered.map(|(_, attributes)| &attributes[..]))?;
+            match (aggregate.protocol, next_hop) {
+                (AFI::IPV4, IpAddr::V4(_)) => attributes.push(PathAttribute::NEXT_HOP(next_hop)),
+                (afi, next_hop) => {
+                    let next_hop = match (afi, next_hop) {
+                        (AFI::IPV6, IpAddr::V4(address)) => IpAddr::V6(address.to_ipv6_mapped()),
+                        _ => next_hop,
+                    };
+                    attributes.push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                        afi,
+                        SAFI::Unicast,
+                        next_hop,
+                        vec![],
+                    ))))
+                }
+            }
+            Some((aggregate.clone(), attributes))
+        });
+        pack_updates(aggregates, max_size, capabilities)
+    }
+}
@@ -0,0 +1,182 @@
+//! The `analysis` mod provides the summaries commonly computed over BGP data, over a stream of
+//! UPDATE messages or the routes of a RIB
+//! - The prefixes originated by every AS
+//! - The distribution of prefix lengths
+//! - The histogram of AS_PATH lengths
+//! - The usage of standard, extended & large communities
+//!
+//! Only routes holding a plain prefix (IP and labeled unicast) are counted, like in `Rib`.
+//!
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::Hash;
+
+use crate::*;
+
+/// Statistics of routes, gathered from UPDATE messages or RIBs.
+///
+/// The distributions & community usage count routes: a prefix announced multiple times (or
+/// by multiple peers of a RIB) is counted every time. The prefixes of every origin AS are
+/// distinct.
+///
+/// ```
+/// use bgp_rs::{ASPath, NLRIEncoding, PathAttribute, RouteStatistics, Segment, Update, AFI};
+///
+/// let update = Update::new(
+///     vec![],
+///     vec![
+///         PathAttribute::AS_PATH(ASPath {
+///             segments: vec![Segment::AS_SEQUENCE(vec![65000, 65001])],
+///         }),
+///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+///         PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+///     ],
+///     vec![
+///         NLRIEncoding::IP("10.0.0.0/8".parse().unwrap()),
+///         NLRIEncoding::IP("10.1.0.0/16".parse().unwrap()),
+///     ],
+/// );
+/// let mut statistics = RouteStatistics::new();
+/// statistics.add_update(&update);
+/// statistics.add_update(&update);
+///
+/// assert_eq!(statistics.routes, 4);
+/// assert_eq!(statistics.origins[&65001].len(), 2);
+/// assert_eq!(statistics.prefix_lengths[&(AFI::IPV4, 16)], 2);
+/// assert_eq!(statistics.path_lengths[&2], 4);
+/// assert_eq!(statistics.communities[&0xfde8_0064], 4);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RouteStatistics {
+    /// The number of routes counted
+    pub routes: usize,
+    /// The number of withdrawn routes seen in UPDATE messages
+    pub withdrawals: usize,
+    /// The prefixes of every origin AS. Routes without AS_PATH, or whose AS_PATH ends with an
+    /// AS_SET, have no origin.
+    pub origins: BTreeMap<u32, BTreeSet<Prefix>>,
+    /// The number of routes of every prefix length, per AFI
+    pub prefix_lengths: BTreeMap<(AFI, u8), usize>,
+    /// The number of routes of every AS_PATH length (as used in route selection, see
+    /// `ASPath::path_length`). Routes without AS_PATH are not counted.
+    pub path_lengths: BTreeMap<usize, usize>,
+    /// The number of routes carrying every community
+    pub communities: BTreeMap<u32, usize>,
+    /// The number of routes carrying every extended community
+    pub extended_communities: BTreeMap<u64, usize>,
+    /// The number of routes carrying every large community
+    pub large_communities: BTreeMap<(u32, u32, u32), usize>,
+}
+
+impl RouteStatistics {
+    /// Creates empty statistics.
+    pub fn new() -> RouteStatistics {
+        RouteStatistics::default()
+    }
+
+    /// Counts the routes announced by an UPDATE message, and its withdrawn routes. The 4-byte
+    /// ASNs of AS4_PATH are recovered, see `Update::effective_as_path`.
+    pub fn add_update(&mut self, update: &Update) {
+        self.withdrawals += update.withdrawn().count();
+        for (afi, safi, route) in update.announced() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                self.add_route(&key.prefix, &update.attributes);
+            }
+        }
+    }
+
+    /// Counts the routes of every peer of a RIB.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Rib, RouteStatistics, Update};
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+    ///     vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    /// );
+    /// let mut rib = Rib::new();
+    /// rib.apply(&update, 1);
+    /// rib.apply(&update, 2);
+    ///
+    /// let mut statistics = RouteStatistics::new();
+    /// statistics.add_rib(&rib);
+    /// assert_eq!(statistics.routes, 2);
+    /// assert!(statistics.origins.is_empty());
+    /// ```
+    pub fn add_rib<P>(&mut self, rib: &Rib<P>)
+    where
+        P: Clone + Eq + Hash,
+    {
+        for (_, key, attributes) in rib.iter() {
+            self.add_route(&key.prefix, attributes);
+        }
+    }
+
+    /// Counts a route of a prefix with its path attributes.
+    pub fn add_route(&mut self, prefix: &Prefix, attributes: &[PathAttribute]) {
+        self.routes += 1;
+        *self
+            .prefix_lengths
+            .entry((prefix.protocol, prefix.length))
+            .or_insert(0) += 1;
+
+        if let Some(as_path) = effective_as_path(attributes) {
+            *self.path_lengths.entry(as_path.path_length()).or_insert(0) += 1;
+            if let Some(origin) = as_path.origin() {
+                self.origins
+                    .entry(origin)
+                    .or_default()
+                    .insert(prefix.clone());
+            }
+        }
+
+        for attribute in attributes {
+            match attribute {
+                PathAttribute::COMMUNITY(communities) => {
+                    for community in communities {
+                        *self.communities.entry(*community).or_insert(0) += 1;
+                    }
+                }
+                PathAttribute::EXTENDED_COMMUNITIES(communities) => {
+                    for community in communities {
+                        *self.extended_communities.entry(*community).or_insert(0) += 1;
+                    }
+                }
+                PathAttribute::LARGE_COMMUNITY(communities) => {
+                    for community in communities {
+                        *self.large_communities.entry(*community).or_insert(0) += 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// The origin ASes with the most prefixes, along with their number of prefixes, ordered
+    /// by decreasing number of prefixes (and increasing ASN).
+    pub fn top_origins(&self, count: usize) -> Vec<(u32, usize)> {
+        let mut origins: Vec<(u32, usize)> = self
+            .origins
+            .iter()
+            .map(|(asn, prefixes)| (*asn, prefixes.len()))
+            .collect();
+        origins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        origins.truncate(count);
+        origins
+    }
+
+    /// The average AS_PATH length of the routes, or None when no route has an AS_PATH.
+    pub fn mean_path_length(&self) -> Option<f64> {
+        let routes: usize = self.path_lengths.values().sum();
+        if routes == 0 {
+            return None;
+        }
+        let total: usize = self
+            .path_lengths
+            .iter()
+            .map(|(length, count)| length * count)
+            .sum();
+        Some(total as f64 / routes as f64)
+    }
+}
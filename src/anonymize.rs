@@ -0,0 +1,432 @@
+//! The `anonymize` mod provides the anonymization of messages, so that captures of problems
+//! can be shared publicly
+//! - Prefixes kept, permuted preserving their common leading bits, or mapped into the
+//!   documentation ranges
+//! - ASNs replaced by private ASNs, and addresses (next hops, router IDs, ...) by addresses of
+//!   the documentation ranges
+//! - Communities kept, stripped or remapped following the ASNs
+//!
+//! The replacements are consistent across messages: an ASN, address or prefix is always
+//! replaced by the same value by an `Anonymizer`.
+//!
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::update::nlri::push_next_hop_address;
+use crate::*;
+
+const AS_TRANS: u32 = 23456;
+
+// The documentation ranges of RFC5737 & RFC3849, prefixes & addresses are taken from in turn
+const IPV4_DOCUMENTATION: [(Ipv4Addr, u8); 3] = [
+    (Ipv4Addr::new(192, 0, 2, 0), 24),
+    (Ipv4Addr::new(198, 51, 100, 0), 24),
+    (Ipv4Addr::new(203, 0, 113, 0), 24),
+];
+const IPV6_DOCUMENTATION: (u128, u8) = (0x2001_0db8 << 96, 32);
+
+/// How prefixes are anonymized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrefixAnonymization {
+    /// Prefixes are kept.
+    Keep,
+    /// Prefixes are permuted with the given key, keeping their length and the leading bits
+    /// they share with other prefixes: a more specific prefix of a prefix remains a more
+    /// specific of its replacement.
+    ///
+    /// The permutation is not cryptographically secure, keep the key secret and use a new
+    /// key for every capture.
+    PrefixPreserving(u64),
+    /// Prefixes are mapped into the documentation ranges (192.0.2.0/24, 198.51.100.0/24,
+    /// 203.0.113.0/24 & 2001:db8::/32) in the order they are seen. Prefixes shorter than the
+    /// ranges are lengthened to fit them.
+    ///
+    /// The ranges only hold a few prefixes (e.g. three IPv4 /24s), they are reused once all
+    /// of them were taken, so the prefixes of larger captures may share their replacement.
+    Documentation,
+}
+
+/// How communities are anonymized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CommunityAnonymization {
+    /// Communities are kept.
+    Keep,
+    /// The COMMUNITY, EXTENDED_COMMUNITIES & LARGE_COMMUNITY attributes are removed.
+    Strip,
+    /// The ASNs (and IPv4 addresses) of communities are replaced like those of AS_PATHs,
+    /// well-known communities are kept.
+    Remap,
+}
+
+/// Anonymizes messages, replacing the prefixes, ASNs, addresses & communities they hold.
+///
+/// ```
+/// use bgp_rs::{
+///     Anonymizer, ASPath, CommunityAnonymization, NLRIEncoding, PathAttribute,
+///     PrefixAnonymization, Segment, Update,
+/// };
+///
+/// let mut update = Update::new(
+///     vec![],
+///     vec![
+///         PathAttribute::AS_PATH(ASPath {
+///             segments: vec![Segment::AS_SEQUENCE(vec![3356, 13335])],
+///         }),
+///         PathAttribute::NEXT_HOP("4.68.1.1".parse().unwrap()),
+///         PathAttribute::COMMUNITY(vec![(3356 << 16) + 100]),
+///     ],
+///     vec![NLRIEncoding::IP("1.1.1.0/24".parse().unwrap())],
+/// );
+/// let mut anonymizer =
+///     Anonymizer::new(PrefixAnonymization::Documentation, CommunityAnonymization::Remap);
+/// anonymizer.anonymize_update(&mut update);
+/// assert_eq!(
+///     update.to_string(),
+///     "192.0.2.0/24 via 192.0.2.1, AS path 64512 64513, communities 64512:100"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct Anonymizer {
+    /// How prefixes are anonymized
+    pub prefixes: PrefixAnonymization,
+    /// How communities are anonymized
+    pub communities: CommunityAnonymization,
+
+    asns: HashMap<u32, u32>,
+    addresses: HashMap<IpAddr, IpAddr>,
+    documentation_prefixes: HashMap<Prefix, Prefix>,
+    // The number of 2-byte & 4-byte ASNs, and of IPv4 & IPv6 addresses replaced so far
+    two_byte_asns: u32,
+    four_byte_asns: u32,
+    ipv4_addresses: usize,
+    ipv6_addresses: u128,
+    // The next free offset within the IPv4 documentation ranges (spanning all three) & IPv6
+    next_ipv4_prefix: u32,
+    next_ipv6_prefix: u128,
+}
+
+impl Anonymizer {
+    /// Creates an anonymizer, replacing ASNs & addresses along with the prefixes &
+    /// communities as requested.
+    pub fn new(prefixes: PrefixAnonymization, communities: CommunityAnonymization) -> Anonymizer {
+        Anonymizer {
+            prefixes,
+            communities,
+            asns: HashMap::new(),
+            addresses: HashMap::new(),
+            documentation_prefixes: HashMap::new(),
+            two_byte_asns: 0,
+            four_byte_asns: 0,
+            ipv4_addresses: 0,
+            ipv6_addresses: 0,
+            next_ipv4_prefix: 0,
+            next_ipv6_prefix: 0,
+        }
+    }
+
+    /// Anonymizes a message: the routes & attributes of UPDATE messages, and the ASN & BGP
+    /// Identifier of OPEN messages. Other messages are kept.
+    pub fn anonymize_message(&mut self, message: &mut Message) {
+        match message {
+            Message::Update(update) => self.anonymize_update(update),
+            Message::Open(open) => self.anonymize_open(open),
+            _ => (),
+        }
+    }
+
+    /// Anonymizes the ASN (including that of the 4-octet ASN capability) & BGP Identifier
+    /// of an OPEN message.
+    pub fn anonymize_open(&mut self, open: &mut Open) {
+        open.peer_asn = self.asn(u32::from(open.peer_asn)) as u16;
+        open.identifier = u32::from(self.ipv4_address(Ipv4Addr::from(open.identifier)));
+        for parameter in &mut open.parameters {
+            if let OpenParameter::Capabilities(capabilities) = parameter {
+                for capability in capabilities {
+                    if let OpenCapability::FourByteASN(asn) = capability {
+                        *asn = self.asn(*asn);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Anonymizes the routes & attributes of an UPDATE message. Routes without a prefix
+    /// (e.g. Flowspec or EVPN routes) and unknown attributes are kept as they are.
+    pub fn anonymize_update(&mut self, update: &mut Update) {
+        for route in update
+            .withdrawn_routes
+            .iter_mut()
+            .chain(update.announced_routes.iter_mut())
+        {
+            self.anonymize_route(route);
+        }
+        if self.communities == CommunityAnonymization::Strip {
+            update.attributes.retain(|attribute| match attribute {
+                PathAttribute::COMMUNITY(_)
+                | PathAttribute::EXTENDED_COMMUNITIES(_)
+                | PathAttribute::LARGE_COMMUNITY(_) => false,
+                _ => true,
+            });
+        }
+        for attribute in &mut update.attributes {
+            self.anonymize_attribute(attribute);
+        }
+        update.clear_raw_attributes();
+    }
+
+    /// The replacement of an ASN: a private ASN of the same size (64512 and up, or
+    /// 4200000000 and up). AS_TRANS and the reserved ASNs 0, 65535 & 4294967295 are kept.
+    pub fn asn(&mut self, asn: u32) -> u32 {
+        if asn == 0 || asn == AS_TRANS || asn == 65535 || asn == std::u32::MAX {
+            return asn;
+        }
+        if let Some(replacement) = self.asns.get(&asn) {
+            return *replacement;
+        }
+        let replacement = if asn <= 65535 {
+            self.two_byte_asns += 1;
+            64512 + (self.two_byte_asns - 1) % 1023
+        } else {
+            self.four_byte_asns += 1;
+            4_200_000_000 + (self.four_byte_asns - 1) % 94_967_295
+        };
+        self.asns.insert(asn, replacement);
+        replacement
+    }
+
+    /// The replacement of an address (e.g. of a peer or next hop): an address of the
+    /// documentation ranges, or of fe80::/64 for IPv6 link-local addresses. Unspecified
+    /// addresses are kept.
+    pub fn address(&mut self, address: IpAddr) -> IpAddr {
+        if address.is_unspecified() {
+            return address;
+        }
+        if let Some(replacement) = self.addresses.get(&address) {
+            return *replacement;
+        }
+        let replacement = match address {
+            IpAddr::V4(_) => {
+                // The network & broadcast addresses of the ranges are skipped
+                let index = self.ipv4_addresses % (IPV4_DOCUMENTATION.len() * 254);
+                self.ipv4_addresses += 1;
+                let octets = IPV4_DOCUMENTATION[index / 254].0.octets();
+                IpAddr::V4(Ipv4Addr::new(
+                    octets[0],
+                    octets[1],
+                    octets[2],
+                    (index % 254) as u8 + 1,
+                ))
+            }
+            IpAddr::V6(address) => {
+                self.ipv6_addresses += 1;
+                let host = self.ipv6_addresses;
+                if address.segments()[0] & 0xffc0 == 0xfe80 {
+                    IpAddr::V6(Ipv6Addr::from((0xfe80 << 112) | host))
+                } else {
+                    IpAddr::V6(Ipv6Addr::from(IPV6_DOCUMENTATION.0 | host))
+                }
+            }
+        };
+        self.addresses.insert(address, replacement);
+        replacement
+    }
+
+    /// The replacement of a prefix, following `prefixes`.
+    pub fn prefix(&mut self, prefix: &Prefix) -> Prefix {
+        match self.prefixes {
+            PrefixAnonymization::Keep => prefix.clone(),
+            PrefixAnonymization::PrefixPreserving(key) => permute_prefix(key, prefix),
+            PrefixAnonymization::Documentation => {
+                if let Some(replacement) = self.documentation_prefixes.get(prefix) {
+                    return replacement.clone();
+                }
+                let replacement = match prefix.protocol {
+                    AFI::IPV6 => self.ipv6_documentation_prefix(prefix.length),
+                    _ => self.ipv4_documentation_prefix(prefix.length),
+                };
+                self.documentation_prefixes
+                    .insert(prefix.clone(), replacement.clone());
+                replacement
+            }
+        }
+    }
+
+    fn ipv4_address(&mut self, address: Ipv4Addr) -> Ipv4Addr {
+        match self.address(IpAddr::V4(address)) {
+            IpAddr::V4(address) => address,
+            IpAddr::V6(_) => address,
+        }
+    }
+
+    fn anonymize_route(&mut self, route: &mut NLRIEncoding) {
+        match route {
+            NLRIEncoding::IP(prefix)
+            | NLRIEncoding::IP_WITH_PATH_ID((prefix, _))
+            | NLRIEncoding::IP_MPLS((prefix, _))
+            | NLRIEncoding::IP_MPLS_WITH_PATH_ID((prefix, _, _))
+            | NLRIEncoding::IP_VPN_MPLS((_, prefix, _)) => *prefix = self.prefix(prefix),
+            _ => (),
+        }
+    }
+
+    fn anonymize_attribute(&mut self, attribute: &mut PathAttribute) {
+        match attribute {
+            PathAttribute::AS_PATH(as_path) | PathAttribute::AS4_PATH(as_path) => {
+                for segment in &mut as_path.segments {
+                    let asns = match segment {
+                        Segment::AS_SEQUENCE(asns) | Segment::AS_SET(asns) => asns,
+                    };
+                    for asn in asns {
+                        *asn = self.asn(*asn);
+                    }
+                }
+            }
+            PathAttribute::NEXT_HOP(address) => *address = self.address(*address),
+            PathAttribute::AGGREGATOR((asn, address))
+            | PathAttribute::AS4_AGGREGATOR((asn, address)) => {
+                *asn = self.asn(*asn);
+                *address = self.ipv4_address(*address);
+            }
+            PathAttribute::ORIGINATOR_ID(address) | PathAttribute::CONNECTOR(address) => {
+                *address = self.ipv4_address(*address);
+            }
+            PathAttribute::CLUSTER_LIST(addresses) => {
+                for address in addresses {
+                    *address = self.ipv4_address(*address);
+                }
+            }
+            PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                if let Ok(addresses) = mp_reach.decode_next_hop() {
+                    let mut next_hop = Vec::with_capacity(mp_reach.next_hop.len());
+                    for address in addresses {
+                        let address = self.address(address);
+                        push_next_hop_address(mp_reach.safi, address, &mut next_hop);
+                    }
+                    mp_reach.next_hop = next_hop;
+                }
+                for route in &mut mp_reach.announced_routes {
+                    self.anonymize_route(route);
+                }
+            }
+            PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+                for route in &mut mp_unreach.withdrawn_routes {
+                    self.anonymize_route(route);
+                }
+            }
+            PathAttribute::COMMUNITY(communities)
+                if self.communities == CommunityAnonymization::Remap =>
+            {
+                for community in communities {
+                    let asn = *community >> 16;
+                    // The reserved & well-known communities are kept
+                    if asn != 0 && asn != 0xffff {
+                        *community = (self.asn(asn) << 16) | (*community & 0xffff);
+                    }
+                }
+            }
+            PathAttribute::LARGE_COMMUNITY(communities)
+                if self.communities == CommunityAnonymization::Remap =>
+            {
+                for (asn, _, _) in communities {
+                    *asn = self.asn(*asn);
+                }
+            }
+            PathAttribute::EXTENDED_COMMUNITIES(communities)
+                if self.communities == CommunityAnonymization::Remap =>
+            {
+                for community in communities {
+                    *community = self.extended_community(*community);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // The Global Administrator of the AS & IPv4 address specific types (RFC4360 & RFC5668)
+    fn extended_community(&mut self, community: u64) -> u64 {
+        match community >> 56 {
+            0x00 | 0x40 => {
+                let asn = self.asn(((community >> 32) & 0xffff) as u32);
+                (community & 0xffff_0000_ffff_ffff) | (u64::from(asn) << 32)
+            }
+            0x01 | 0x41 => {
+                let address = Ipv4Addr::from(((community >> 16) & 0xffff_ffff) as u32);
+                let address = u32::from(self.ipv4_address(address));
+                (community & 0xffff_0000_0000_ffff) | (u64::from(address) << 16)
+            }
+            0x02 | 0x42 => {
+                let asn = self.asn(((community >> 16) & 0xffff_ffff) as u32);
+                (community & 0xffff_0000_0000_ffff) | (u64::from(asn) << 16)
+            }
+            _ => community,
+        }
+    }
+
+    // The next block of the IPv4 ranges, of at least /24 (taking a whole range)
+    fn ipv4_documentation_prefix(&mut self, length: u8) -> Prefix {
+        let length = length.max(24).min(32);
+        let size = 1u32 << (32 - length);
+        // Blocks are aligned to their size, and do not span two ranges
+        let mut offset = (self.next_ipv4_prefix + size - 1) / size * size;
+        if offset >= 256 * IPV4_DOCUMENTATION.len() as u32 {
+            offset = 0;
+        }
+        self.next_ipv4_prefix = offset + size;
+        let (range, _) = IPV4_DOCUMENTATION[(offset / 256) as usize];
+        let address = Ipv4Addr::from(u32::from(range) | (offset % 256));
+        (IpAddr::V4(address), length).into()
+    }
+
+    // The next block of the IPv6 range, of at least /32 (taking the whole range)
+    fn ipv6_documentation_prefix(&mut self, length: u8) -> Prefix {
+        let (range, range_length) = IPV6_DOCUMENTATION;
+        let length = length.max(range_length).min(128);
+        let size = 1u128 << (128 - u32::from(length));
+        let range_size = 1u128 << (128 - u32::from(range_length));
+        let mut offset = (self.next_ipv6_prefix + size - 1) / size * size;
+        if offset >= range_size {
+            offset = 0;
+        }
+        self.next_ipv6_prefix = offset + size;
+        (IpAddr::V6(Ipv6Addr::from(range | offset)), length).into()
+    }
+}
+
+// Flips each bit of the prefix by a pseudo-random function of the key & the bits preceding it,
+// so that prefixes sharing their first bits share the first bits of their replacements
+fn permute_prefix(key: u64, prefix: &Prefix) -> Prefix {
+    let (address, width) = match IpAddr::try_from(prefix) {
+        Ok(IpAddr::V4(address)) => (u128::from(u32::from(address)), 32),
+        Ok(IpAddr::V6(address)) => (u128::from(address), 128),
+        Err(_) => return prefix.clone(),
+    };
+    let length = u32::from(prefix.length).min(width);
+    let mut permuted = 0u128;
+    for bit in 0..length {
+        let preceding = address.checked_shr(width - bit).unwrap_or(0);
+        let flip = pseudo_random(key, u64::from(bit), preceding) & 1;
+        let value = (address >> (width - 1 - bit)) & 1;
+        permuted |= (value ^ u128::from(flip)) << (width - 1 - bit);
+    }
+    let address = if width == 32 {
+        IpAddr::V4(Ipv4Addr::from(permuted as u32))
+    } else {
+        IpAddr::V6(Ipv6Addr::from(permuted))
+    };
+    (address, prefix.length).into()
+}
+
+fn pseudo_random(key: u64, bit: u64, preceding: u128) -> u64 {
+    let value = mix(mix((preceding >> 64) as u64 ^ bit) ^ preceding as u64);
+    mix(key ^ value)
+}
+
+// The finalizer of SplitMix64
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
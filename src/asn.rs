@@ -0,0 +1,106 @@
+//! The `asn` mod provides an Autonomous System Number type
+//! - Conversion from and to the u32 values held by AS_PATHs and AGGREGATORs
+//! - asplain and asdot notations (RFC5396)
+//! - Classification of private, reserved and documentation ASNs
+//!
+
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::*;
+
+/// An Autonomous System Number, displayed in asplain notation.
+///
+/// ```
+/// use bgp_rs::Asn;
+///
+/// let asn: Asn = "1.10".parse().unwrap();
+/// assert_eq!(u32::from(asn), 65546);
+/// assert_eq!(asn.to_string(), "65546");
+/// assert!(asn.is_documentation());
+/// assert!(Asn(64512).is_private());
+/// ```
+#[derive(Debug, Copy, Clone, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Asn(pub u32);
+
+impl Asn {
+    /// The ASN in asdot notation (RFC5396): ASNs that fit in 2 bytes are written as they are,
+    /// others as their high and low order 16 bits separated by a dot.
+    pub fn to_asdot(self) -> String {
+        if self.0 > u32::from(std::u16::MAX) {
+            format!("{}.{}", self.0 >> 16, self.0 & 0xffff)
+        } else {
+            self.0.to_string()
+        }
+    }
+
+    /// Whether the ASN needs 4 bytes to be encoded.
+    pub fn is_4_byte(self) -> bool {
+        self.0 > u32::from(std::u16::MAX)
+    }
+
+    /// Checks if the ASN is in one of the private use ranges defined in RFC6996.
+    pub fn is_private(self) -> bool {
+        (self.0 >= 64512 && self.0 <= 65534) || (self.0 >= 4_200_000_000 && self.0 <= 4_294_967_294)
+    }
+
+    /// Checks if the ASN is reserved (RFC7607, RFC7300), is AS_TRANS (RFC6793) or is in the
+    /// range reserved by IANA between the 2-byte and 4-byte documentation ASNs.
+    pub fn is_reserved(self) -> bool {
+        match self.0 {
+            0 | AS_TRANS | 65535 | 4_294_967_295 => true,
+            asn => asn >= 65552 && asn <= 131_071,
+        }
+    }
+
+    /// Checks if the ASN is reserved for use in documentation (RFC5398).
+    pub fn is_documentation(self) -> bool {
+        (self.0 >= 64496 && self.0 <= 64511) || (self.0 >= 65536 && self.0 <= 65551)
+    }
+}
+
+impl From<u32> for Asn {
+    fn from(asn: u32) -> Asn {
+        Asn(asn)
+    }
+}
+
+impl From<Asn> for u32 {
+    fn from(asn: Asn) -> u32 {
+        asn.0
+    }
+}
+
+/// Display the ASN in asplain notation, see `to_asdot` for the asdot notation
+/// ```
+/// use bgp_rs::Asn;
+///
+/// assert_eq!(Asn(4200000000).to_string(), "4200000000");
+/// assert_eq!(Asn(4200000000).to_asdot(), "64086.59904");
+/// assert_eq!(Asn(65000).to_asdot(), "65000");
+/// ```
+impl Display for Asn {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Asn {
+    type Err = Error;
+
+    /// Parse an ASN in asplain ("4200000000") or asdot ("64086.59904") notation
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid ASN: {}", s));
+        let mut parts = s.splitn(2, '.');
+        let high = parts.next().ok_or_else(invalid)?;
+        match parts.next() {
+            Some(low) => {
+                let high: u16 = high.parse().map_err(|_| invalid())?;
+                let low: u16 = low.parse().map_err(|_| invalid())?;
+                Ok(Asn(u32::from(high) << 16 | u32::from(low)))
+            }
+            None => high.parse().map(Asn).map_err(|_| invalid()),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Decodes BGP messages from pcap captures, MRT archives or hex strings and prints them.
+//!
+//! ```text
+//! bgpdump-rs [--explain] [--verbose] [--as4] <pcap|mrt|hex> <FILE|HEX>
+//! ```
+
+use bgp_rs::*;
+use mrt_rs::bgp4mp::BGP4MP;
+use mrt_rs::Record;
+
+use std::fs::File;
+use std::io::{self, BufReader, Cursor};
+use std::process;
+
+const USAGE: &str = "Usage: bgpdump-rs [OPTIONS] <pcap|mrt|hex> <FILE|HEX>
+
+Decodes BGP messages from a pcap capture, an MRT archive or a hex string.
+
+Options:
+    -e, --explain   Print an annotated breakdown of the wire format of every message
+    -v, --verbose   Print every UPDATE message across multiple lines
+    -4, --as4       Decode hex strings using 4-byte ASNs
+    -h, --help      Print this help";
+
+#[derive(Default)]
+struct Options {
+    explain: bool,
+    verbose: bool,
+    four_octet_asn: bool,
+}
+
+fn main() {
+    let mut options = Options::default();
+    let mut arguments = vec![];
+    for argument in std::env::args().skip(1) {
+        match argument.as_str() {
+            "-e" | "--explain" => options.explain = true,
+            "-v" | "--verbose" => options.verbose = true,
+            "-4" | "--as4" => options.four_octet_asn = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => arguments.push(argument),
+        }
+    }
+
+    let result = match (arguments.first().map(String::as_str), arguments.get(1)) {
+        (Some("pcap"), Some(path)) if arguments.len() == 2 => dump_pcap(path, &options),
+        (Some("mrt"), Some(path)) if arguments.len() == 2 => dump_mrt(path, &options),
+        // Hex dumps are often split across multiple arguments
+        (Some("hex"), Some(_)) => dump_hex(&arguments[1..].join(" "), &options),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn dump_hex(hex: &str, options: &Options) -> io::Result<()> {
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: options.four_octet_asn,
+        ..Capabilities::default()
+    };
+    let message = Message::from_hex(hex, &capabilities);
+    // Malformed messages can still be explained, as long as the hex itself is valid
+    let bytes = if options.explain {
+        hex_bytes(hex).unwrap_or_default()
+    } else {
+        vec![]
+    };
+    print_message(None, &bytes, message, &capabilities, options);
+    Ok(())
+}
+
+// Decodes hex like Message::from_hex, ignoring separators and "0x" prefixes
+fn hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    let digits: String = hex
+        .split(|c: char| c.is_whitespace() || c == ':' || c == '-' || c == '.')
+        .map(|group| group.trim_start_matches("0x").trim_start_matches("0X"))
+        .collect();
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn dump_pcap(path: &str, options: &Options) -> io::Result<()> {
+    // OPEN messages of both peers are merged, see NegotiatedCapabilities::apply_open
+    let mut capabilities = NegotiatedCapabilities::default();
+    for message in read_bgp_messages_from_pcap(path)? {
+        let mut reader = Reader::with_capabilities(Cursor::new(&message.data), &mut capabilities);
+        let parsed = reader.read_negotiated().map(|(_, message)| message);
+        let label = format!(
+            "{} {} > {}",
+            message.timestamp.as_secs(),
+            message.source,
+            message.destination
+        );
+        print_message(
+            Some(label),
+            &message.data,
+            parsed,
+            &capabilities.capabilities,
+            options,
+        );
+    }
+    Ok(())
+}
+
+fn dump_mrt(path: &str, options: &Options) -> io::Result<()> {
+    let mut stream = BufReader::new(File::open(path)?);
+    let mut decoder = RibDecoder::new();
+    while let Some((header, record)) = mrt_rs::read(&mut stream)? {
+        match record {
+            Record::BGP4MP(BGP4MP::MESSAGE(message))
+            | Record::BGP4MP(BGP4MP::MESSAGE_LOCAL(message)) => {
+                let label = format!(
+                    "{} AS{} {}",
+                    header.timestamp, message.peer_as, message.peer_address
+                );
+                dump_mrt_message(label, &message.message, false, options);
+            }
+            Record::BGP4MP(BGP4MP::MESSAGE_AS4(message))
+            | Record::BGP4MP(BGP4MP::MESSAGE_AS4_LOCAL(message)) => {
+                let label = format!(
+                    "{} AS{} {}",
+                    header.timestamp, message.peer_as, message.peer_address
+                );
+                dump_mrt_message(label, &message.message, true, options);
+            }
+            Record::TABLE_DUMP_V2(record) => {
+                for (peer, prefix, attributes) in decoder.decode(&record)? {
+                    let attributes: Vec<String> = attributes
+                        .iter()
+                        .map(|attribute| attribute.to_string())
+                        .collect();
+                    println!(
+                        "{} AS{} {} RIB {}, {}",
+                        header.timestamp,
+                        peer.asn,
+                        peer.address,
+                        prefix,
+                        attributes.join(", ")
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn dump_mrt_message(label: String, bytes: &[u8], four_octet_asn: bool, options: &Options) {
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: four_octet_asn,
+        ..Capabilities::default()
+    };
+    let mut reader = Reader::with_capabilities(Cursor::new(bytes), &capabilities);
+    let message = reader.read().map(|(_, message)| message);
+    print_message(Some(label), bytes, message, &capabilities, options);
+}
+
+fn print_message(
+    label: Option<String>,
+    bytes: &[u8],
+    message: io::Result<Message>,
+    capabilities: &Capabilities,
+    options: &Options,
+) {
+    match message {
+        Ok(message) => match label {
+            Some(label) => println!("{} {}", label, describe(&message, options)),
+            None => println!("{}", describe(&message, options)),
+        },
+        Err(error) => match label {
+            Some(label) => println!("{} Error: {}", label, error),
+            None => println!("Error: {}", error),
+        },
+    }
+    if options.explain {
+        println!(
+            "{}",
+            Message::explain_with_capabilities(bytes, capabilities)
+        );
+    }
+}
+
+fn describe(message: &Message, options: &Options) -> String {
+    match message {
+        Message::Open(open) => format!(
+            "OPEN AS{} hold time {} identifier {}",
+            open.asn(),
+            open.hold_timer,
+            std::net::Ipv4Addr::from(open.identifier)
+        ),
+        Message::Update(update) if options.verbose => format!("UPDATE\n{:#}", update),
+        Message::Update(update) => format!("UPDATE {}", update),
+        Message::Notification(notification) => format!("NOTIFICATION {}", notification),
+        Message::KeepAlive => "KEEPALIVE".to_string(),
+        Message::RouteRefresh(refresh) => format!(
+            "ROUTE-REFRESH {} {} subtype {:?}",
+            refresh.afi, refresh.safi, refresh.subtype
+        ),
+    }
+}
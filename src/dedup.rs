@@ -0,0 +1,183 @@
+//! The `dedup` mod provides the filtering of duplicate announcements across a stream of UPDATE
+//! messages, such as the feeds of route collectors
+//! - Announcements repeating the NLRI & path attributes of the current route of a peer
+//! - Canonicalized attributes, so that their order does not matter
+//!
+//! Routes are identified by their AFI/SAFI, prefix and ADD-PATH Path Identifier like in `Rib`,
+//! others (such as VPN, Flowspec, MVPN and L2VPN routes) are never considered duplicate.
+//!
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::rib::route_attributes;
+use crate::*;
+
+/// Filters duplicate announcements: routes announced again by a peer with the same NLRI &
+/// path attributes, without having been withdrawn in between. Only a hash of every route is
+/// kept, rather than its attributes.
+///
+/// ```
+/// use bgp_rs::{DuplicateFilter, NLRIEncoding, PathAttribute, Update};
+///
+/// let update = |med: u32| {
+///     Update::new(
+///         vec![],
+///         vec![
+///             PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+///             PathAttribute::MULTI_EXIT_DISC(med),
+///         ],
+///         vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+///     )
+/// };
+/// let mut filter = DuplicateFilter::new();
+/// assert!(filter.filter(&update(10), "peer").is_some());
+/// assert!(filter.filter(&update(10), "peer").is_none());
+/// assert!(filter.filter(&update(10), "other peer").is_some());
+/// assert!(filter.filter(&update(20), "peer").is_some());
+/// assert_eq!(filter.duplicates(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DuplicateFilter<P>
+where
+    P: Clone + Eq + Hash,
+{
+    routes: HashMap<P, HashMap<RouteKey, u64>>,
+    duplicates: usize,
+}
+
+impl<P> Default for DuplicateFilter<P>
+where
+    P: Clone + Eq + Hash,
+{
+    fn default() -> Self {
+        DuplicateFilter {
+            routes: HashMap::new(),
+            duplicates: 0,
+        }
+    }
+}
+
+impl<P> DuplicateFilter<P>
+where
+    P: Clone + Eq + Hash,
+{
+    /// Creates a filter that has not seen any route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The UPDATE message received from a peer without its duplicate announcements, or None
+    /// when it only held duplicate announcements. Withdrawals are always kept, and forget the
+    /// routes they withdraw.
+    pub fn filter(&mut self, update: &Update, peer: P) -> Option<Update> {
+        let routes = self.routes.entry(peer).or_default();
+        for (afi, safi, route) in update.withdrawn() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                routes.remove(&key);
+            }
+        }
+
+        let attributes = attributes_hash(&route_attributes(update));
+        let mut duplicates = 0;
+        let mut is_new = |afi: AFI, safi: SAFI, route: &NLRIEncoding| {
+            let key = match RouteKey::new(afi, safi, route) {
+                Some(key) => key,
+                None => return true,
+            };
+            let hash = route_hash(attributes, route);
+            if routes.insert(key, hash) == Some(hash) {
+                duplicates += 1;
+                return false;
+            }
+            true
+        };
+
+        let mut filtered = update.clone();
+        filtered
+            .announced_routes
+            .retain(|route| is_new(route.afi(), route.safi(), route));
+        for attribute in &mut filtered.attributes {
+            if let PathAttribute::MP_REACH_NLRI(mp_reach) = attribute {
+                let (afi, safi) = (mp_reach.afi, mp_reach.safi);
+                mp_reach
+                    .announced_routes
+                    .retain(|route| is_new(afi, safi, route));
+            }
+        }
+        self.duplicates += duplicates;
+        if duplicates == 0 {
+            return Some(filtered);
+        }
+
+        // MP_REACH_NLRI left without routes is removed
+        filtered.attributes.retain(|attribute| match attribute {
+            PathAttribute::MP_REACH_NLRI(mp_reach) => !mp_reach.announced_routes.is_empty(),
+            _ => true,
+        });
+        if filtered.announced().next().is_none() && filtered.withdrawn().next().is_none() {
+            return None;
+        }
+        if filtered.announced().next().is_none() {
+            // Only the withdrawals remain
+            filtered.attributes.retain(|attribute| match attribute {
+                PathAttribute::MP_UNREACH_NLRI(_) => true,
+                _ => false,
+            });
+        }
+        Some(filtered)
+    }
+
+    /// Whether a route announced by a peer with the given path attributes (see `Rib::get`)
+    /// repeats its current route, remembering it otherwise.
+    pub fn is_duplicate(
+        &mut self,
+        peer: P,
+        afi: AFI,
+        safi: SAFI,
+        route: &NLRIEncoding,
+        attributes: &[PathAttribute],
+    ) -> bool {
+        let key = match RouteKey::new(afi, safi, route) {
+            Some(key) => key,
+            None => return false,
+        };
+        let hash = route_hash(attributes_hash(attributes), route);
+        let duplicate = self.routes.entry(peer).or_default().insert(key, hash) == Some(hash);
+        if duplicate {
+            self.duplicates += 1;
+        }
+        duplicate
+    }
+
+    /// Forgets the routes of a peer, such as when its session went down.
+    pub fn remove_peer(&mut self, peer: &P) {
+        self.routes.remove(peer);
+    }
+
+    /// The number of duplicate announcements seen so far.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+}
+
+// The hash of path attributes, independent of their order
+fn attributes_hash(attributes: &[PathAttribute]) -> u64 {
+    let mut attributes: Vec<String> = attributes
+        .iter()
+        .map(|attribute| format!("{:?}", attribute))
+        .collect();
+    attributes.sort();
+    let mut hasher = DefaultHasher::new();
+    attributes.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The hash of a route (keeping its MPLS label) along with the hash of its path attributes
+fn route_hash(attributes: u64, route: &NLRIEncoding) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    attributes.hash(&mut hasher);
+    format!("{:?}", route).hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,333 @@
+//! The `diff` mod provides human-readable differences between messages and RIBs, such as when
+//! comparing the behavior of two routers or of two versions of this crate
+//! - Differences between UPDATE messages, attribute by attribute and route by route
+//! - Differences between RIBs, prefix by prefix for every peer
+//!
+//! Differences are rendered one per line, prefixed with `-` for what only the first side holds,
+//! `+` for what only the second side holds, and `~` for routes whose attributes changed.
+//!
+
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+
+use crate::rib::route_attributes;
+use crate::*;
+
+/// A difference between two values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change<T> {
+    /// The value is only held by the first side
+    Removed(T),
+    /// The value is only held by the second side
+    Added(T),
+    /// The value differs between the first and the second side
+    Changed(T, T),
+}
+
+/// The differences between two UPDATE messages, see `diff`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UpdateDiff {
+    /// The path attributes that were removed, added or changed, compared by their type. The
+    /// routes of MP_REACH_NLRI & MP_UNREACH_NLRI are compared with the other routes.
+    pub attributes: Vec<Change<PathAttribute>>,
+    /// The announced routes only held by either message, with their AFI/SAFI
+    pub announced: Vec<Change<(AFI, SAFI, NLRIEncoding)>>,
+    /// The withdrawn routes only held by either message, with their AFI/SAFI
+    pub withdrawn: Vec<Change<(AFI, SAFI, NLRIEncoding)>>,
+}
+
+impl UpdateDiff {
+    /// Whether the messages hold the same routes & attributes.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty() && self.announced.is_empty() && self.withdrawn.is_empty()
+    }
+}
+
+/// The differences between two UPDATE messages: their path attributes compared by type, and
+/// the routes they announce & withdraw. The order of routes and attributes is ignored.
+///
+/// ```
+/// use bgp_rs::{diff, NLRIEncoding, PathAttribute, Update};
+///
+/// let update = |med: u32, routes: &[&str]| {
+///     Update::new(
+///         vec![],
+///         vec![
+///             PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+///             PathAttribute::MULTI_EXIT_DISC(med),
+///         ],
+///         routes.iter().map(|r| NLRIEncoding::IP(r.parse().unwrap())).collect(),
+///     )
+/// };
+/// let a = update(10, &["10.0.0.0/8", "10.1.0.0/16"]);
+/// let b = update(20, &["10.0.0.0/8", "10.2.0.0/16"]);
+/// assert_eq!(
+///     diff(&a, &b).to_string(),
+///     "- MED 10\n+ MED 20\n- announced 10.1.0.0/16\n+ announced 10.2.0.0/16"
+/// );
+/// assert!(diff(&a, &a).is_empty());
+/// ```
+pub fn diff(a: &Update, b: &Update) -> UpdateDiff {
+    UpdateDiff {
+        attributes: diff_attributes(&route_attributes(a), &route_attributes(b)),
+        announced: diff_routes(a.announced(), b.announced()),
+        withdrawn: diff_routes(a.withdrawn(), b.withdrawn()),
+    }
+}
+
+/// The differences between two sets of path attributes, compared by their type. The routes
+/// of MP_REACH_NLRI & MP_UNREACH_NLRI are part of the comparison, see `diff` to ignore them.
+pub fn diff_attributes(a: &[PathAttribute], b: &[PathAttribute]) -> Vec<Change<PathAttribute>> {
+    let mut changes = vec![];
+    for attribute in a {
+        match b.iter().find(|other| other.id() == attribute.id()) {
+            None => changes.push(Change::Removed(attribute.clone())),
+            Some(other) if other != attribute => {
+                changes.push(Change::Changed(attribute.clone(), other.clone()))
+            }
+            Some(_) => (),
+        }
+    }
+    for attribute in b {
+        if !a.iter().any(|other| other.id() == attribute.id()) {
+            changes.push(Change::Added(attribute.clone()));
+        }
+    }
+    changes
+}
+
+fn diff_routes<'a>(
+    a: impl Iterator<Item = (AFI, SAFI, &'a NLRIEncoding)>,
+    b: impl Iterator<Item = (AFI, SAFI, &'a NLRIEncoding)>,
+) -> Vec<Change<(AFI, SAFI, NLRIEncoding)>> {
+    let a: Vec<_> = a.collect();
+    let b: Vec<_> = b.collect();
+    let removed = a
+        .iter()
+        .filter(|route| !b.contains(route))
+        .map(|(afi, safi, route)| Change::Removed((*afi, *safi, (*route).clone())));
+    let added = b
+        .iter()
+        .filter(|route| !a.contains(route))
+        .map(|(afi, safi, route)| Change::Added((*afi, *safi, (*route).clone())));
+    removed.chain(added).collect()
+}
+
+/// Renders a difference per line, a changed attribute as its removal & addition.
+impl Display for UpdateDiff {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let mut lines = vec![];
+        for change in &self.attributes {
+            push_change(&mut lines, "", change, describe_attribute);
+        }
+        for (name, routes) in &[
+            ("announced", &self.announced),
+            ("withdrawn", &self.withdrawn),
+        ] {
+            for change in routes.iter() {
+                push_change(&mut lines, "", change, |(_, _, route)| {
+                    format!("{} {}", name, route)
+                });
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+/// The difference of a route between two RIBs, see `Rib::compare`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteDiff<P> {
+    /// The peer the route was received from
+    pub peer: P,
+    /// The route
+    pub key: RouteKey,
+    /// The path attributes of the route on either side
+    pub change: Change<Vec<PathAttribute>>,
+}
+
+impl<P> RouteDiff<P> {
+    /// The path attributes that differ for a changed route, attribute by attribute. All
+    /// attributes are removed (or added) for a route only held by either RIB.
+    pub fn attributes(&self) -> Vec<Change<PathAttribute>> {
+        match &self.change {
+            Change::Removed(attributes) => diff_attributes(attributes, &[]),
+            Change::Added(attributes) => diff_attributes(&[], attributes),
+            Change::Changed(old, new) => diff_attributes(old, new),
+        }
+    }
+}
+
+/// Renders the route on a single line when it was added or removed, and its attributes on
+/// the following indented lines when they changed.
+///
+/// ```
+/// use bgp_rs::{Change, PathAttribute, RouteDiff, RouteKey, AFI, SAFI};
+///
+/// let route = bgp_rs::NLRIEncoding::IP("10.0.0.0/8".parse().unwrap());
+/// let diff = RouteDiff {
+///     peer: 1,
+///     key: RouteKey::new(AFI::IPV4, SAFI::Unicast, &route).unwrap(),
+///     change: Change::Changed(
+///         vec![PathAttribute::LOCAL_PREF(100)],
+///         vec![PathAttribute::LOCAL_PREF(200)],
+///     ),
+/// };
+/// assert_eq!(diff.to_string(), "~ 1 10.0.0.0/8\n    - local pref 100\n    + local pref 200");
+/// ```
+impl<P> Display for RouteDiff<P>
+where
+    P: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let mut route = format!("{} {}", self.peer, self.key.prefix);
+        if self.key.safi != SAFI::Unicast {
+            route = format!("{} ({} {})", route, self.key.afi, self.key.safi);
+        }
+        if self.key.path_id != 0 {
+            route = format!("{} path {}", route, self.key.path_id);
+        }
+        match &self.change {
+            Change::Removed(attributes) => {
+                write!(f, "- {}: {}", route, describe_attributes(attributes))
+            }
+            Change::Added(attributes) => {
+                write!(f, "+ {}: {}", route, describe_attributes(attributes))
+            }
+            Change::Changed(..) => {
+                let mut lines = vec![format!("~ {}", route)];
+                for change in self.attributes() {
+                    push_change(&mut lines, "    ", &change, describe_attribute);
+                }
+                write!(f, "{}", lines.join("\n"))
+            }
+        }
+    }
+}
+
+/// The differences between two RIBs, see `Rib::compare`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RibDiff<P> {
+    /// The routes that were removed, added or changed, ordered by peer & route
+    pub routes: Vec<RouteDiff<P>>,
+}
+
+impl<P> RibDiff<P> {
+    /// Whether the RIBs hold the same routes & attributes.
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// Renders the difference of every route, see `RouteDiff`.
+impl<P> Display for RibDiff<P>
+where
+    P: Display,
+{
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let lines: Vec<String> = self.routes.iter().map(|route| route.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl<P> Rib<P>
+where
+    P: Clone + Eq + Hash + Ord,
+{
+    /// The differences between the routes of this RIB and those of the other RIB, prefix by
+    /// prefix for every peer. Unlike `diff`, changed routes hold both sets of attributes.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Rib, Update};
+    ///
+    /// let update = |med: u32, routes: &[&str]| {
+    ///     Update::new(
+    ///         vec![],
+    ///         vec![PathAttribute::MULTI_EXIT_DISC(med)],
+    ///         routes.iter().map(|r| NLRIEncoding::IP(r.parse().unwrap())).collect(),
+    ///     )
+    /// };
+    /// let mut a = Rib::new();
+    /// a.apply(&update(10, &["10.0.0.0/8", "10.1.0.0/16"]), 1);
+    /// let mut b = Rib::new();
+    /// b.apply(&update(20, &["10.0.0.0/8"]), 1);
+    ///
+    /// assert_eq!(
+    ///     a.compare(&b).to_string(),
+    ///     "~ 1 10.0.0.0/8\n    - MED 10\n    + MED 20\n- 1 10.1.0.0/16: MED 10"
+    /// );
+    /// ```
+    pub fn compare(&self, other: &Rib<P>) -> RibDiff<P> {
+        let mut peers: Vec<&P> = self.peers().chain(other.peers()).collect();
+        peers.sort();
+        peers.dedup();
+
+        let mut routes = vec![];
+        for peer in peers {
+            let mut old = self.routes(peer).peekable();
+            let mut new = other.routes(peer).peekable();
+            // Both iterators are ordered by key, and merged
+            loop {
+                let (key, change) = match (old.peek(), new.peek()) {
+                    (None, None) => break,
+                    (Some((old_key, _)), Some((new_key, _))) if old_key == new_key => {
+                        let (key, old_attributes) = old.next().unwrap();
+                        let (_, new_attributes) = new.next().unwrap();
+                        if old_attributes == new_attributes {
+                            continue;
+                        }
+                        let change =
+                            Change::Changed(old_attributes.to_vec(), new_attributes.to_vec());
+                        (key, change)
+                    }
+                    (Some((old_key, _)), Some((new_key, _))) if new_key < old_key => {
+                        let (key, attributes) = new.next().unwrap();
+                        (key, Change::Added(attributes.to_vec()))
+                    }
+                    (Some(_), _) => {
+                        let (key, attributes) = old.next().unwrap();
+                        (key, Change::Removed(attributes.to_vec()))
+                    }
+                    (None, Some(_)) => {
+                        let (key, attributes) = new.next().unwrap();
+                        (key, Change::Added(attributes.to_vec()))
+                    }
+                };
+                routes.push(RouteDiff {
+                    peer: peer.clone(),
+                    key: key.clone(),
+                    change,
+                });
+            }
+        }
+        RibDiff { routes }
+    }
+}
+
+fn push_change<T, F>(lines: &mut Vec<String>, indent: &str, change: &Change<T>, describe: F)
+where
+    F: Fn(&T) -> String,
+{
+    match change {
+        Change::Removed(value) => lines.push(format!("{}- {}", indent, describe(value))),
+        Change::Added(value) => lines.push(format!("{}+ {}", indent, describe(value))),
+        Change::Changed(old, new) => {
+            lines.push(format!("{}- {}", indent, describe(old)));
+            lines.push(format!("{}+ {}", indent, describe(new)));
+        }
+    }
+}
+
+// MP_REACH_NLRI is compared without its routes, and named after its AFI/SAFI
+fn describe_attribute(attribute: &PathAttribute) -> String {
+    match attribute {
+        PathAttribute::MP_REACH_NLRI(mp_reach) if mp_reach.announced_routes.is_empty() => {
+            format!("{} {} {}", mp_reach.afi, mp_reach.safi, mp_reach)
+        }
+        attribute => attribute.to_string(),
+    }
+}
+
+fn describe_attributes(attributes: &[PathAttribute]) -> String {
+    let attributes: Vec<String> = attributes.iter().map(describe_attribute).collect();
+    attributes.join(", ")
+}
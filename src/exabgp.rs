@@ -0,0 +1,578 @@
+//! The `exabgp` mod converts the JSON messages of [exabgp](https://github.com/Exa-Networks/exabgp)
+//! to and from BGP messages
+//! - UPDATE: attributes, announce & withdraw per family
+//! - OPEN: version, ASN, hold time, router ID and capabilities
+//! - NOTIFICATION & KEEPALIVE
+//!
+//! UPDATE messages can also be turned into the text commands of the exabgp API,
+//! to announce & withdraw routes through exabgp.
+//!
+
+use std::io::Error;
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde_json::{json, Map, Value};
+
+use crate::json::*;
+use crate::*;
+
+/// A BGP message exchanged with a neighbor, as written by exabgp to its API processes.
+///
+/// # Example
+/// ```
+/// use bgp_rs::{ExaBgpMessage, Message};
+///
+/// let json = r#"{"exabgp": "4.0.1", "time": 1561601637.93, "host": "lab", "type": "update",
+///     "neighbor": {
+///         "address": {"local": "192.0.2.2", "peer": "192.0.2.1"},
+///         "asn": {"local": 65000, "peer": 65001},
+///         "direction": "receive",
+///         "message": {"update": {
+///             "attribute": {"origin": "igp", "as-path": [65001], "local-preference": 100},
+///             "announce": {"ipv4 unicast": {"192.0.2.1": [{"nlri": "198.51.100.0/24"}]}}
+///         }}
+///     }}"#;
+/// let message = ExaBgpMessage::from_json(json).unwrap();
+/// assert_eq!(message.peer_asn, 65001);
+/// assert_eq!(
+///     message.to_commands().unwrap(),
+///     vec!["neighbor 192.0.2.1 announce route 198.51.100.0/24 next-hop 192.0.2.1 \
+///           origin igp as-path [ 65001 ] local-preference 100"]
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExaBgpMessage {
+    /// Time the message was exchanged, in seconds since the UNIX epoch
+    pub time: f64,
+    /// The hostname of the exabgp instance
+    pub host: String,
+    /// The IP address of the local end of the session
+    pub local_address: IpAddr,
+    /// The IP address of the neighbor
+    pub peer_address: IpAddr,
+    /// The ASN of the local end of the session
+    pub local_asn: u32,
+    /// The ASN of the neighbor
+    pub peer_asn: u32,
+    /// The BGP message
+    pub message: Message,
+}
+
+impl ExaBgpMessage {
+    /// Parses an exabgp JSON message of type update, open, notification or keepalive.
+    pub fn from_json(json: &str) -> Result<ExaBgpMessage, Error> {
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let neighbor = value
+            .get("neighbor")
+            .ok_or_else(|| invalid("Missing neighbor"))?;
+        let address = neighbor
+            .get("address")
+            .ok_or_else(|| invalid("Missing neighbor address"))?;
+        let asn = neighbor
+            .get("asn")
+            .ok_or_else(|| invalid("Missing neighbor asn"))?;
+
+        let message = match get_str(&value, "type")? {
+            "update" => {
+                let update = neighbor
+                    .get("message")
+                    .and_then(|message| message.get("update"))
+                    .ok_or_else(|| invalid("Missing update"))?;
+                Message::Update(update_from_json(update)?)
+            }
+            "open" => {
+                let open = neighbor
+                    .get("open")
+                    .ok_or_else(|| invalid("Missing open"))?;
+                Message::Open(open_from_json(open, ' ')?)
+            }
+            "notification" => {
+                let notification = neighbor
+                    .get("notification")
+                    .ok_or_else(|| invalid("Missing notification"))?;
+                Message::Notification(notification_from_json(notification)?)
+            }
+            "keepalive" => Message::KeepAlive,
+            message_type => {
+                return Err(invalid(format!(
+                    "Unsupported message type: {}",
+                    message_type
+                )));
+            }
+        };
+
+        Ok(ExaBgpMessage {
+            time: value
+                .get("time")
+                .and_then(Value::as_f64)
+                .unwrap_or_default(),
+            host: value
+                .get("host")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            local_address: parse_address(get_str(address, "local")?)?,
+            peer_address: parse_address(get_str(address, "peer")?)?,
+            local_asn: as_asn(asn.get("local").unwrap_or(&Value::Null))?,
+            peer_asn: as_asn(asn.get("peer").unwrap_or(&Value::Null))?,
+            message,
+        })
+    }
+
+    /// Writes the exabgp JSON message, as received from the neighbor.
+    ///
+    /// ROUTE-REFRESH messages are not supported.
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut neighbor = Map::new();
+        neighbor.insert(
+            "address".to_string(),
+            json!({
+                "local": self.local_address.to_string(),
+                "peer": self.peer_address.to_string(),
+            }),
+        );
+        neighbor.insert(
+            "asn".to_string(),
+            json!({"local": self.local_asn, "peer": self.peer_asn}),
+        );
+        neighbor.insert("direction".to_string(), json!("receive"));
+        let message_type = match &self.message {
+            Message::Update(update) => {
+                neighbor.insert(
+                    "message".to_string(),
+                    json!({ "update": update_to_json(update)? }),
+                );
+                "update"
+            }
+            Message::Open(open) => {
+                neighbor.insert("open".to_string(), Value::Object(open_to_json(open, ' ')));
+                "open"
+            }
+            Message::Notification(notification) => {
+                neighbor.insert(
+                    "notification".to_string(),
+                    notification_to_json(notification),
+                );
+                "notification"
+            }
+            Message::KeepAlive => "keepalive",
+            Message::RouteRefresh(_) => {
+                return Err(invalid("ROUTE-REFRESH messages are not supported"));
+            }
+        };
+
+        Ok(json!({
+            "exabgp": "4.0.1",
+            "time": self.time,
+            "host": self.host,
+            "type": message_type,
+            "neighbor": neighbor,
+        })
+        .to_string())
+    }
+
+    /// Turns an UPDATE message into exabgp API commands for the neighbor,
+    /// one command per announced or withdrawn prefix.
+    pub fn to_commands(&self) -> Result<Vec<String>, Error> {
+        let update = match &self.message {
+            Message::Update(update) => update,
+            _ => return Err(invalid("Only UPDATE messages can be turned into commands")),
+        };
+
+        let mut attributes = String::new();
+        for attribute in &update.attributes {
+            match attribute {
+                PathAttribute::ORIGIN(origin) => {
+                    attributes.push_str(&format!(" origin {}", origin_to_json(origin)));
+                }
+                PathAttribute::AS_PATH(path) => {
+                    let mut segments: Vec<String> = vec![];
+                    for segment in &path.segments {
+                        match segment {
+                            Segment::AS_SEQUENCE(asns) => {
+                                segments.extend(asns.iter().map(u32::to_string))
+                            }
+                            Segment::AS_SET(asns) => {
+                                let asns: Vec<String> = asns.iter().map(u32::to_string).collect();
+                                segments.push(format!("( {} )", asns.join(" ")));
+                            }
+                        }
+                    }
+                    attributes.push_str(&format!(" as-path [ {} ]", segments.join(" ")));
+                }
+                PathAttribute::MULTI_EXIT_DISC(med) => {
+                    attributes.push_str(&format!(" med {}", med));
+                }
+                PathAttribute::LOCAL_PREF(pref) => {
+                    attributes.push_str(&format!(" local-preference {}", pref));
+                }
+                PathAttribute::ATOMIC_AGGREGATOR => attributes.push_str(" atomic-aggregate"),
+                PathAttribute::AGGREGATOR((asn, address)) => {
+                    attributes.push_str(&format!(" aggregator ( {}:{} )", asn, address));
+                }
+                PathAttribute::COMMUNITY(communities) => {
+                    let communities: Vec<String> = communities
+                        .iter()
+                        .map(|c| format!("{}:{}", c >> 16, c & 0xffff))
+                        .collect();
+                    attributes.push_str(&format!(" community [ {} ]", communities.join(" ")));
+                }
+                PathAttribute::LARGE_COMMUNITY(communities) => {
+                    let communities: Vec<String> = communities
+                        .iter()
+                        .map(|(asn, first, second)| format!("{}:{}:{}", asn, first, second))
+                        .collect();
+                    attributes.push_str(&format!(" large-community [ {} ]", communities.join(" ")));
+                }
+                _ => (),
+            }
+        }
+
+        let neighbor = format!("neighbor {}", self.peer_address);
+        let mut commands: Vec<String> = vec![];
+        let announce = |routes: &[NLRIEncoding], next_hop: IpAddr, commands: &mut Vec<String>| {
+            for prefix in prefixes(routes) {
+                commands.push(format!(
+                    "{} announce route {} next-hop {}{}",
+                    neighbor, prefix, next_hop, attributes
+                ));
+            }
+        };
+        if let Some(PathAttribute::NEXT_HOP(next_hop)) = update.get(Identifier::NEXT_HOP) {
+            announce(&update.announced_routes, *next_hop, &mut commands);
+        }
+        if let Some(PathAttribute::MP_REACH_NLRI(mp_reach)) = update.get(Identifier::MP_REACH_NLRI)
+        {
+            if let Some(next_hop) = mp_reach.next_hop_addresses(&json_capabilities())?.first() {
+                announce(&mp_reach.announced_routes, *next_hop, &mut commands);
+            }
+        }
+
+        let mut withdrawn = prefixes(&update.withdrawn_routes);
+        if let Some(PathAttribute::MP_UNREACH_NLRI(mp_unreach)) =
+            update.get(Identifier::MP_UNREACH_NLRI)
+        {
+            withdrawn.extend(prefixes(&mp_unreach.withdrawn_routes));
+        }
+        for prefix in withdrawn {
+            commands.push(format!("{} withdraw route {}", neighbor, prefix));
+        }
+        Ok(commands)
+    }
+}
+
+// {"attribute": {...}, "announce": {"ipv4 unicast": {"<next hop>": [{"nlri": "<prefix>"}]}},
+//  "withdraw": {"ipv4 unicast": [{"nlri": "<prefix>"}]}}
+fn update_from_json(update: &Value) -> Result<Update, Error> {
+    let mut attributes: Vec<PathAttribute> = Vec::with_capacity(8);
+    if let Some(Value::Object(attribute)) = update.get("attribute") {
+        attributes = attributes_from_json(attribute)?;
+    }
+
+    let mut announced_routes: Vec<NLRIEncoding> = vec![];
+    let mut update_next_hop: Option<IpAddr> = None;
+    if let Some(Value::Object(families)) = update.get("announce") {
+        for (family, next_hops) in families {
+            let (afi, safi) = family_from_json(&json!(family), ' ')?;
+            let next_hops = match next_hops {
+                Value::Object(next_hops) => next_hops,
+                _ => return Err(invalid(format!("Invalid announce for {}", family))),
+            };
+            for (next_hop, routes) in next_hops {
+                let next_hop = parse_address(next_hop)?;
+                let routes = routes_from_json(routes)?;
+                match (afi, safi, next_hop) {
+                    (AFI::IPV4, SAFI::Unicast, IpAddr::V4(_)) => {
+                        if update_next_hop.get_or_insert(next_hop) != &next_hop {
+                            return Err(invalid("Multiple IPv4 next hops in one UPDATE"));
+                        }
+                        announced_routes.extend(routes);
+                    }
+                    _ => {
+                        let mut mp_reach = MPReachNLRI {
+                            afi,
+                            safi,
+                            next_hop: vec![],
+                            announced_routes: routes,
+                        };
+                        mp_reach.set_next_hop(&[next_hop], &json_capabilities())?;
+                        attributes.push(PathAttribute::MP_REACH_NLRI(Box::new(mp_reach)));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(next_hop) = update_next_hop {
+        attributes.push(PathAttribute::NEXT_HOP(next_hop));
+    }
+
+    let mut withdrawn_routes: Vec<NLRIEncoding> = vec![];
+    if let Some(Value::Object(families)) = update.get("withdraw") {
+        for (family, routes) in families {
+            let (afi, safi) = family_from_json(&json!(family), ' ')?;
+            let routes = routes_from_json(routes)?;
+            match (afi, safi) {
+                (AFI::IPV4, SAFI::Unicast) => withdrawn_routes.extend(routes),
+                _ => attributes.push(PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                    afi,
+                    safi,
+                    withdrawn_routes: routes,
+                }))),
+            }
+        }
+    }
+
+    // JSON objects are not ordered, attributes are encoded by type code
+    attributes.sort_by_key(|attribute| attribute.code());
+    Ok(Update::new(withdrawn_routes, attributes, announced_routes))
+}
+
+fn update_to_json(update: &Update) -> Result<Value, Error> {
+    let mut json = Map::new();
+    let attribute = attributes_to_json(&update.attributes);
+    if !attribute.is_empty() {
+        json.insert("attribute".to_string(), Value::Object(attribute));
+    }
+
+    let mut announce = Map::new();
+    let mut withdraw = Map::new();
+    let ipv4_unicast = family_to_json(AFI::IPV4, SAFI::Unicast, ' ');
+    if let Some(PathAttribute::NEXT_HOP(next_hop)) = update.get(Identifier::NEXT_HOP) {
+        if !update.announced_routes.is_empty() {
+            announce.insert(
+                ipv4_unicast.clone(),
+                json!({ (next_hop.to_string()): routes_to_json(&update.announced_routes) }),
+            );
+        }
+    }
+    if !update.withdrawn_routes.is_empty() {
+        withdraw.insert(
+            ipv4_unicast,
+            Value::Array(routes_to_json(&update.withdrawn_routes)),
+        );
+    }
+    for attribute in &update.attributes {
+        match attribute {
+            PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                let next_hop = mp_reach
+                    .next_hop_addresses(&json_capabilities())?
+                    .first()
+                    .map(IpAddr::to_string)
+                    .unwrap_or_else(|| "null".to_string());
+                announce.insert(
+                    family_to_json(mp_reach.afi, mp_reach.safi, ' '),
+                    json!({ (next_hop): routes_to_json(&mp_reach.announced_routes) }),
+                );
+            }
+            PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+                withdraw.insert(
+                    family_to_json(mp_unreach.afi, mp_unreach.safi, ' '),
+                    Value::Array(routes_to_json(&mp_unreach.withdrawn_routes)),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    if !announce.is_empty() {
+        json.insert("announce".to_string(), Value::Object(announce));
+    }
+    if !withdraw.is_empty() {
+        json.insert("withdraw".to_string(), Value::Object(withdraw));
+    }
+    Ok(Value::Object(json))
+}
+
+fn attributes_from_json(attribute: &Map<String, Value>) -> Result<Vec<PathAttribute>, Error> {
+    let mut attributes: Vec<PathAttribute> = Vec::with_capacity(attribute.len());
+    for (name, value) in attribute {
+        let invalid_attribute = || invalid(format!("Invalid {}: {}", name, value));
+        let attribute = match name.as_str() {
+            "origin" => PathAttribute::ORIGIN(match value.as_str() {
+                Some("igp") => Origin::IGP,
+                Some("egp") => Origin::EGP,
+                Some("incomplete") => Origin::INCOMPLETE,
+                _ => return Err(invalid_attribute()),
+            }),
+            "as-path" => PathAttribute::AS_PATH(as_path_from_json(value)?),
+            "med" => PathAttribute::MULTI_EXIT_DISC(as_asn(value)?),
+            "local-preference" => PathAttribute::LOCAL_PREF(as_asn(value)?),
+            "atomic-aggregate" => PathAttribute::ATOMIC_AGGREGATOR,
+            "aggregator" => {
+                let aggregator = value.as_str().unwrap_or_default();
+                let mut parts = aggregator.splitn(2, ':');
+                let asn = parts.next().and_then(|asn| asn.parse::<u32>().ok());
+                let address = parts.next().and_then(|ip| ip.parse::<Ipv4Addr>().ok());
+                match (asn, address) {
+                    (Some(asn), Some(address)) => PathAttribute::AGGREGATOR((asn, address)),
+                    _ => return Err(invalid_attribute()),
+                }
+            }
+            "community" => PathAttribute::COMMUNITY(
+                values_from_json(value, 2)?
+                    .iter()
+                    .map(|community| match community.as_slice() {
+                        [asn, value] if *asn <= 0xffff && *value <= 0xffff => Ok(asn << 16 | value),
+                        _ => Err(invalid_attribute()),
+                    })
+                    .collect::<Result<Vec<u32>, Error>>()?,
+            ),
+            "large-community" => PathAttribute::LARGE_COMMUNITY(
+                values_from_json(value, 3)?
+                    .iter()
+                    .map(|community| (community[0], community[1], community[2]))
+                    .collect(),
+            ),
+            "originator-id" => PathAttribute::ORIGINATOR_ID(
+                value
+                    .as_str()
+                    .and_then(|id| id.parse().ok())
+                    .ok_or_else(invalid_attribute)?,
+            ),
+            "cluster-list" => PathAttribute::CLUSTER_LIST(
+                value
+                    .as_array()
+                    .ok_or_else(invalid_attribute)?
+                    .iter()
+                    .map(|id| {
+                        id.as_str()
+                            .and_then(|id| id.parse().ok())
+                            .ok_or_else(invalid_attribute)
+                    })
+                    .collect::<Result<Vec<Ipv4Addr>, Error>>()?,
+            ),
+            _ => continue,
+        };
+        attributes.push(attribute);
+    }
+    Ok(attributes)
+}
+
+fn attributes_to_json(attributes: &[PathAttribute]) -> Map<String, Value> {
+    let mut json = Map::new();
+    for attribute in attributes {
+        let (name, value) = match attribute {
+            PathAttribute::ORIGIN(origin) => ("origin", json!(origin_to_json(origin))),
+            PathAttribute::AS_PATH(path) => ("as-path", as_path_to_json(path)),
+            PathAttribute::MULTI_EXIT_DISC(med) => ("med", json!(med)),
+            PathAttribute::LOCAL_PREF(pref) => ("local-preference", json!(pref)),
+            PathAttribute::ATOMIC_AGGREGATOR => ("atomic-aggregate", json!(true)),
+            PathAttribute::AGGREGATOR((asn, address)) => {
+                ("aggregator", json!(format!("{}:{}", asn, address)))
+            }
+            PathAttribute::COMMUNITY(communities) => {
+                let communities: Vec<Value> = communities
+                    .iter()
+                    .map(|community| json!([community >> 16, community & 0xffff]))
+                    .collect();
+                ("community", Value::Array(communities))
+            }
+            PathAttribute::LARGE_COMMUNITY(communities) => {
+                let communities: Vec<Value> = communities
+                    .iter()
+                    .map(|(asn, first, second)| json!([asn, first, second]))
+                    .collect();
+                ("large-community", Value::Array(communities))
+            }
+            PathAttribute::ORIGINATOR_ID(id) => ("originator-id", json!(id.to_string())),
+            PathAttribute::CLUSTER_LIST(ids) => {
+                let ids: Vec<String> = ids.iter().map(Ipv4Addr::to_string).collect();
+                ("cluster-list", json!(ids))
+            }
+            _ => continue,
+        };
+        json.insert(name.to_string(), value);
+    }
+    json
+}
+
+// Routes are listed as {"nlri": "<prefix>"}, with the "path-information" when using ADD-PATH
+fn routes_from_json(routes: &Value) -> Result<Vec<NLRIEncoding>, Error> {
+    let routes = routes
+        .as_array()
+        .ok_or_else(|| invalid(format!("Invalid routes: {}", routes)))?;
+    routes
+        .iter()
+        .map(|route| {
+            let prefix = parse_prefix(route.get("nlri").unwrap_or(route))?;
+            match route.get("path-information") {
+                Some(Value::String(id)) => id
+                    .parse::<Ipv4Addr>()
+                    .map(|id| NLRIEncoding::IP_WITH_PATH_ID((prefix, u32::from(id))))
+                    .map_err(|_| invalid(format!("Invalid path-information: {}", id))),
+                Some(id) => Ok(NLRIEncoding::IP_WITH_PATH_ID((prefix, as_asn(id)?))),
+                None => Ok(NLRIEncoding::IP(prefix)),
+            }
+        })
+        .collect()
+}
+
+fn routes_to_json(routes: &[NLRIEncoding]) -> Vec<Value> {
+    routes
+        .iter()
+        .filter_map(|route| match route {
+            NLRIEncoding::IP(prefix) => Some(json!({"nlri": prefix.to_string()})),
+            NLRIEncoding::IP_WITH_PATH_ID((prefix, id)) => Some(json!({
+                "nlri": prefix.to_string(),
+                "path-information": Ipv4Addr::from(*id).to_string(),
+            })),
+            _ => None,
+        })
+        .collect()
+}
+
+fn prefixes(routes: &[NLRIEncoding]) -> Vec<String> {
+    routes
+        .iter()
+        .filter_map(|route| match route {
+            NLRIEncoding::IP(prefix) | NLRIEncoding::IP_WITH_PATH_ID((prefix, _)) => {
+                Some(prefix.to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Communities are listed as arrays of numbers: [[65000, 100]]
+fn values_from_json(value: &Value, length: usize) -> Result<Vec<Vec<u32>>, Error> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid(format!("Invalid communities: {}", value)))?
+        .iter()
+        .map(|community| match community.as_array() {
+            Some(values) if values.len() == length => values.iter().map(as_asn).collect(),
+            _ => Err(invalid(format!("Invalid community: {}", community))),
+        })
+        .collect()
+}
+
+fn origin_to_json(origin: &Origin) -> &'static str {
+    match origin {
+        Origin::IGP => "igp",
+        Origin::EGP => "egp",
+        Origin::INCOMPLETE => "incomplete",
+    }
+}
+
+#[test]
+fn test_routes_from_json() {
+    let routes = json!([
+        {"nlri": "198.51.100.0/24"},
+        {"nlri": "203.0.113.0/24", "path-information": "0.0.0.2"},
+        "192.0.2.0/24"
+    ]);
+    let routes = routes_from_json(&routes).unwrap();
+    assert_eq!(routes.len(), 3);
+    match &routes[1] {
+        NLRIEncoding::IP_WITH_PATH_ID((prefix, id)) => {
+            assert_eq!(prefix.to_string(), "203.0.113.0/24");
+            assert_eq!(*id, 2);
+        }
+        route => panic!("Unexpected route: {:?}", route),
+    }
+    assert_eq!(
+        routes_to_json(&routes)[1],
+        json!({"nlri": "203.0.113.0/24", "path-information": "0.0.0.2"})
+    );
+}
@@ -0,0 +1,415 @@
+use std::convert::TryFrom;
+use std::io::Cursor;
+use std::net::Ipv4Addr;
+
+use crate::*;
+
+// The number of bytes shown on a single line of the dump, longer fields are abbreviated.
+const BYTES_SHOWN: usize = 8;
+
+impl Message {
+    /// Produces an annotated, field-by-field breakdown of raw BGP messages, similar to the
+    /// packet details of Wireshark. Every line holds the offset of a field, its bytes and the
+    /// decoded meaning, nested fields are indented.
+    ///
+    /// Malformed fields are annotated rather than rejected, and the dump continues wherever the
+    /// lengths in the message allow it. When `data` holds multiple messages, all of them are
+    /// explained in sequence. UPDATE messages are decoded using the default `Capabilities`,
+    /// see `explain_with_capabilities` to decode 4-byte AS paths or ADD-PATH NLRI.
+    ///
+    /// ```
+    /// use bgp_rs::Message;
+    ///
+    /// let mut data = vec![0xff; 16];
+    /// data.extend_from_slice(&[0, 23, 2, 0, 0, 0, 0]);
+    /// let dump = Message::explain(&data);
+    /// assert!(dump.contains("Type: 2 (UPDATE)"));
+    /// assert!(dump.contains("Total Path Attribute Length: 0"));
+    /// ```
+    pub fn explain(data: &[u8]) -> String {
+        Message::explain_with_capabilities(data, &Capabilities::default())
+    }
+
+    /// Produces an annotated breakdown of raw BGP messages like `explain`, decoding UPDATE
+    /// messages using the capabilities negotiated with the peer.
+    pub fn explain_with_capabilities(data: &[u8], capabilities: &Capabilities) -> String {
+        let mut lines = vec![];
+        let mut section = Section {
+            data,
+            base: 0,
+            position: 0,
+            depth: 0,
+        };
+        while section.remaining() > 0 {
+            explain_message(&mut section, &mut lines, capabilities);
+        }
+        lines.join("\n")
+    }
+}
+
+// A range of the dumped data that is annotated field by field.
+struct Section<'a> {
+    data: &'a [u8],
+    // The offset of `data` within the dumped data
+    base: usize,
+    position: usize,
+    depth: usize,
+}
+
+impl<'a> Section<'a> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn peek(&self, index: usize) -> Option<u8> {
+        self.data.get(self.position + index).cloned()
+    }
+
+    // Takes the next `length` bytes, annotating them with the meaning given by `describe`.
+    // When fewer bytes remain, they are annotated as truncated and None is returned.
+    fn field<F>(
+        &mut self,
+        lines: &mut Vec<String>,
+        length: usize,
+        name: &str,
+        describe: F,
+    ) -> Option<&'a [u8]>
+    where
+        F: FnOnce(&[u8]) -> String,
+    {
+        let offset = self.base + self.position;
+        if length > self.remaining() {
+            let bytes = &self.data[self.position..];
+            self.position = self.data.len();
+            let text = format!(
+                "{}: truncated, expected {} bytes but only {} remain",
+                name,
+                length,
+                bytes.len()
+            );
+            annotate(lines, offset, bytes, self.depth, &text);
+            return None;
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        let text = format!("{}: {}", name, describe(bytes));
+        annotate(lines, offset, bytes, self.depth, &text);
+        Some(bytes)
+    }
+
+    // Annotates the next `length` bytes (or what remains of them) without taking them.
+    fn summary(&self, lines: &mut Vec<String>, length: usize, text: &str) {
+        let end = self.data.len().min(self.position + length);
+        let bytes = &self.data[self.position..end];
+        annotate(lines, self.base + self.position, bytes, self.depth, text);
+    }
+
+    // Splits off the next `length` bytes (or what remains of them) as a separate section.
+    fn section(&mut self, length: usize, depth: usize) -> Section<'a> {
+        let length = length.min(self.remaining());
+        let section = Section {
+            data: &self.data[self.position..self.position + length],
+            base: self.base + self.position,
+            position: 0,
+            depth,
+        };
+        self.position += length;
+        section
+    }
+
+    // Annotates any bytes that were not consumed by the fields of this section.
+    fn rest(&mut self, lines: &mut Vec<String>, name: &str) {
+        let remaining = self.remaining();
+        if remaining > 0 {
+            self.field(lines, remaining, name, |bytes| {
+                format!("{} unexpected bytes", bytes.len())
+            });
+        }
+    }
+}
+
+fn annotate(lines: &mut Vec<String>, offset: usize, bytes: &[u8], depth: usize, text: &str) {
+    let mut hex: Vec<String> = bytes
+        .iter()
+        .take(BYTES_SHOWN)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if bytes.len() > BYTES_SHOWN {
+        hex.push("..".to_string());
+    }
+    lines.push(format!(
+        "{:>5}  {:<26}  {}{}",
+        offset,
+        hex.join(" "),
+        "  ".repeat(depth),
+        text
+    ));
+}
+
+fn number(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+fn explain_message(section: &mut Section, lines: &mut Vec<String>, capabilities: &Capabilities) {
+    let marker = section.field(lines, 16, "Marker", |bytes| {
+        if bytes.iter().all(|byte| *byte == 0xff) {
+            "all ones".to_string()
+        } else {
+            "invalid, expected all ones".to_string()
+        }
+    });
+    if marker.is_none() {
+        return;
+    }
+    let length = match section.field(lines, 2, "Length", |bytes| number(bytes).to_string()) {
+        Some(bytes) => number(bytes) as usize,
+        None => return,
+    };
+    let record_type = match section.field(lines, 1, "Type", |bytes| {
+        format!("{} ({})", bytes[0], message_type(bytes[0]))
+    }) {
+        Some(bytes) => MessageType::from(bytes[0]),
+        None => return,
+    };
+    if length < BGP_MIN_MESSAGE_SIZE {
+        // Without a valid length the end of the message can not be determined
+        let remaining = section.remaining();
+        section.field(lines, remaining, "Data", |_| {
+            format!("not parsed, the length is below {}", BGP_MIN_MESSAGE_SIZE)
+        });
+        return;
+    }
+
+    let body_length = length - BGP_MIN_MESSAGE_SIZE;
+    if body_length > section.remaining() {
+        section.summary(
+            lines,
+            body_length,
+            &format!(
+                "Message truncated, expected {} bytes but only {} remain",
+                body_length,
+                section.remaining()
+            ),
+        );
+    }
+    let mut body = section.section(body_length, section.depth);
+    match record_type {
+        MessageType::Open => explain_open(&mut body, lines),
+        MessageType::Update => explain_update(&mut body, lines, capabilities),
+        MessageType::Notification => explain_notification(&mut body, lines),
+        MessageType::RouteRefresh => explain_route_refresh(&mut body, lines),
+        _ => (),
+    }
+    body.rest(lines, "Data");
+}
+
+fn message_type(record_type: u8) -> String {
+    match MessageType::from(record_type) {
+        MessageType::Unknown(_) => "unknown".to_string(),
+        message_type => message_type.to_string(),
+    }
+}
+
+fn explain_open(section: &mut Section, lines: &mut Vec<String>) {
+    section.field(lines, 1, "Version", |bytes| bytes[0].to_string());
+    section.field(lines, 2, "My AS", |bytes| number(bytes).to_string());
+    section.field(lines, 2, "Hold Time", |bytes| {
+        format!("{} seconds", number(bytes))
+    });
+    section.field(lines, 4, "BGP Identifier", |bytes| {
+        Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string()
+    });
+    let length = match section.field(lines, 1, "Optional Parameters Length", |bytes| {
+        bytes[0].to_string()
+    }) {
+        Some(bytes) => bytes[0] as usize,
+        None => return,
+    };
+
+    let mut parameters = section.section(length, section.depth);
+    while parameters.remaining() > 0 {
+        let param_length = parameters.peek(1).map_or(0, usize::from);
+        parameters.summary(lines, 2 + param_length, "Optional Parameter");
+        let mut parameter = parameters.section(2 + param_length, parameters.depth + 1);
+        let param_type = parameter.field(lines, 1, "Parameter Type", |bytes| match bytes[0] {
+            2 => "2 (Capabilities)".to_string(),
+            param_type => format!("{} (unknown)", param_type),
+        });
+        parameter.field(lines, 1, "Parameter Length", |bytes| bytes[0].to_string());
+        if param_type.map(|bytes| bytes[0]) == Some(2) {
+            while parameter.remaining() > 0 {
+                explain_capability(&mut parameter, lines);
+            }
+        } else {
+            parameter.rest(lines, "Parameter Value");
+        }
+    }
+    parameters.rest(lines, "Data");
+}
+
+fn explain_capability(section: &mut Section, lines: &mut Vec<String>) {
+    let length = 2 + section.peek(1).map_or(0, usize::from);
+    let end = section.data.len().min(section.position + length);
+    let bytes = &section.data[section.position..end];
+    let text = match OpenCapability::parse(&mut Cursor::new(bytes)) {
+        Ok((_, capability)) => format!("Capability: {:?}", capability),
+        Err(error) => format!("Capability: malformed, {}", error),
+    };
+    section.summary(lines, length, &text);
+
+    let mut capability = section.section(length, section.depth + 1);
+    capability.field(lines, 1, "Capability Code", |bytes| bytes[0].to_string());
+    capability.field(lines, 1, "Capability Length", |bytes| bytes[0].to_string());
+    let remaining = capability.remaining();
+    if remaining > 0 {
+        capability.field(lines, remaining, "Capability Value", |bytes| {
+            format!("{} bytes", bytes.len())
+        });
+    }
+}
+
+fn explain_update(section: &mut Section, lines: &mut Vec<String>, capabilities: &Capabilities) {
+    let add_path = capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast);
+    let length = match section.field(lines, 2, "Withdrawn Routes Length", |bytes| {
+        number(bytes).to_string()
+    }) {
+        Some(bytes) => number(bytes) as usize,
+        None => return,
+    };
+    let mut withdrawn = section.section(length, section.depth);
+    explain_prefixes(&mut withdrawn, lines, "Withdrawn Route", add_path);
+
+    let length = match section.field(lines, 2, "Total Path Attribute Length", |bytes| {
+        number(bytes).to_string()
+    }) {
+        Some(bytes) => number(bytes) as usize,
+        None => return,
+    };
+    let mut attributes = section.section(length, section.depth);
+    while attributes.remaining() > 0 {
+        explain_attribute(&mut attributes, lines, capabilities);
+    }
+
+    explain_prefixes(section, lines, "NLRI", add_path);
+}
+
+fn explain_prefixes(section: &mut Section, lines: &mut Vec<String>, name: &str, add_path: bool) {
+    while section.remaining() > 0 {
+        if add_path
+            && section
+                .field(lines, 4, "Path Identifier", |bytes| {
+                    number(bytes).to_string()
+                })
+                .is_none()
+        {
+            return;
+        }
+        let bits = match section.peek(0) {
+            Some(bits) => bits,
+            None => return,
+        };
+        let length = 1 + (usize::from(bits) + 7) / 8;
+        section.field(lines, length, name, |bytes| {
+            if bits > 32 {
+                return format!("invalid prefix length {}", bits);
+            }
+            let mut octets = [0u8; 4];
+            octets[..bytes.len() - 1].copy_from_slice(&bytes[1..]);
+            format!("{}/{}", Ipv4Addr::from(octets), bits)
+        });
+    }
+}
+
+fn explain_attribute(section: &mut Section, lines: &mut Vec<String>, capabilities: &Capabilities) {
+    let flags = section.peek(0).unwrap_or(0);
+    let extended_length = flags & 0x10 == 0x10;
+    let length_size = if extended_length { 2 } else { 1 };
+    let length = match (section.peek(2), section.peek(3)) {
+        (Some(high), Some(low)) if extended_length => usize::from(high) << 8 | usize::from(low),
+        (Some(length), _) if !extended_length => usize::from(length),
+        _ => 0,
+    };
+    let total = 2 + length_size + length;
+    let end = section.data.len().min(section.position + total);
+    let bytes = &section.data[section.position..end];
+    let parsed = PathAttribute::parse(&mut Cursor::new(bytes), capabilities);
+    let text = match &parsed {
+        Ok(attribute) => format!("Path Attribute {:?}", attribute.id()),
+        Err(_) => "Path Attribute".to_string(),
+    };
+    section.summary(lines, total, &text);
+
+    let mut attribute = section.section(total, section.depth + 1);
+    attribute.field(lines, 1, "Flags", |bytes| {
+        format!("{:#04x} ({})", bytes[0], attribute_flags(bytes[0]))
+    });
+    attribute.field(lines, 1, "Type Code", |bytes| bytes[0].to_string());
+    attribute.field(lines, length_size, "Length", |bytes| {
+        number(bytes).to_string()
+    });
+    attribute.field(lines, length, "Value", |_| match parsed {
+        Ok(attribute) => attribute.to_string(),
+        Err(error) => format!("malformed, {}", error),
+    });
+}
+
+fn attribute_flags(flags: u8) -> String {
+    let mut names = vec![];
+    names.push(if flags & 0x80 == 0x80 {
+        "optional"
+    } else {
+        "well-known"
+    });
+    if flags & 0x40 == 0x40 {
+        names.push("transitive");
+    }
+    if flags & 0x20 == 0x20 {
+        names.push("partial");
+    }
+    if flags & 0x10 == 0x10 {
+        names.push("extended length");
+    }
+    names.join(", ")
+}
+
+fn explain_notification(section: &mut Section, lines: &mut Vec<String>) {
+    let major = match section.field(lines, 1, "Error Code", |bytes| bytes[0].to_string()) {
+        Some(bytes) => bytes[0],
+        None => return,
+    };
+    section.field(lines, 1, "Error Subcode", |bytes| {
+        format!(
+            "{} ({})",
+            bytes[0],
+            NotificationCode::from((major, bytes[0]))
+        )
+    });
+    let remaining = section.remaining();
+    if remaining > 0 {
+        section.field(lines, remaining, "Data", |bytes| {
+            format!("{} bytes", bytes.len())
+        });
+    }
+}
+
+fn explain_route_refresh(section: &mut Section, lines: &mut Vec<String>) {
+    section.field(lines, 2, "AFI", |bytes| {
+        let afi = number(bytes) as u16;
+        match AFI::try_from(afi) {
+            Ok(AFI::Other(_)) | Err(_) => format!("{} (unknown)", afi),
+            Ok(name) => format!("{} ({})", afi, name),
+        }
+    });
+    section.field(lines, 1, "Subtype", |bytes| match bytes[0] {
+        0 => "0 (Normal)".to_string(),
+        1 => "1 (Beginning of Route Refresh)".to_string(),
+        2 => "2 (End of Route Refresh)".to_string(),
+        subtype => format!("{} (unknown)", subtype),
+    });
+    section.field(lines, 1, "SAFI", |bytes| match SAFI::try_from(bytes[0]) {
+        Ok(SAFI::Other(_)) | Err(_) => format!("{} (unknown)", bytes[0]),
+        Ok(safi) => format!("{} ({})", bytes[0], safi),
+    });
+}
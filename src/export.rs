@@ -0,0 +1,313 @@
+//! The `export` mod provides the flattening of routes into rows for analytics pipelines
+//! - `RouteRow`, a route of an UPDATE message or a RIB entry with its main attributes
+//! - `CsvRouteWriter`, writing rows to CSV files with a header row
+//! - `route_batch`, converting rows into an Apache Arrow `RecordBatch`
+//! - `ParquetRouteWriter`, writing rows to Apache Parquet files
+//!
+//! Only routes with a prefix (e.g. unicast & labeled unicast routes) are exported.
+//!
+
+use std::io::{Error, ErrorKind, Write};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, BooleanArray, RecordBatch, StringArray, TimestampSecondArray, UInt32Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+
+use crate::*;
+
+/// The names of the columns of exported routes, in their order.
+pub const ROUTE_COLUMNS: [&str; 7] = [
+    "timestamp",
+    "prefix",
+    "withdrawn",
+    "origin_asn",
+    "as_path",
+    "communities",
+    "next_hop",
+];
+
+/// A route flattened into a row, with the attributes commonly used for analytics.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RouteRow {
+    /// The time (in seconds since the UNIX epoch) the route was received, e.g. of its MRT record
+    pub timestamp: u64,
+    /// The prefix of the route, e.g. "10.0.0.0/8"
+    pub prefix: String,
+    /// Whether the route was withdrawn, the attributes are empty then
+    pub withdrawn: bool,
+    /// The ASN originating the route, the last ASN of the AS_PATH
+    pub origin_asn: Option<u32>,
+    /// The AS_PATH (merged with the AS4_PATH), with AS_SETs in braces, e.g. "65000 {65001,65002}"
+    pub as_path: String,
+    /// The COMMUNITIES separated by spaces, e.g. "65000:100 65000:200"
+    pub communities: String,
+    /// The address of the next hop of the route
+    pub next_hop: Option<IpAddr>,
+}
+
+impl RouteRow {
+    /// Flattens a RIB entry, e.g. decoded from a TABLE_DUMP_V2 RIB or held by a `Rib`.
+    pub fn new(timestamp: u64, prefix: &Prefix, attributes: &[PathAttribute]) -> RouteRow {
+        let as_path = effective_as_path(attributes);
+        let next_hop = attributes.iter().find_map(|attribute| match attribute {
+            PathAttribute::NEXT_HOP(address) if prefix.protocol == AFI::IPV4 => Some(*address),
+            PathAttribute::MP_REACH_NLRI(mp_reach) => mp_reach
+                .decode_next_hop()
+                .ok()
+                .and_then(|addresses| NextHop::from_addresses(&addresses))
+                .map(|next_hop| next_hop.address()),
+            _ => None,
+        });
+        RouteRow::with_attributes(timestamp, prefix, as_path, attributes, next_hop)
+    }
+
+    /// Flattens the routes announced & withdrawn by an UPDATE message, including those of
+    /// MP_REACH_NLRI & MP_UNREACH_NLRI.
+    ///
+    /// ```
+    /// use bgp_rs::{ASPath, NLRIEncoding, PathAttribute, RouteRow, Segment, Update};
+    ///
+    /// let update = Update::new(
+    ///     vec![NLRIEncoding::IP("10.1.0.0/16".parse().unwrap())],
+    ///     vec![
+    ///         PathAttribute::AS_PATH(ASPath {
+    ///             segments: vec![Segment::AS_SEQUENCE(vec![65000, 65001])],
+    ///         }),
+    ///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+    ///         PathAttribute::COMMUNITY(vec![(65000 << 16) + 100]),
+    ///     ],
+    ///     vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    /// );
+    /// let rows = RouteRow::from_update(&update, 1_600_000_000);
+    /// assert_eq!(rows.len(), 2);
+    /// assert_eq!(rows[0].prefix, "10.0.0.0/8");
+    /// assert_eq!(rows[0].origin_asn, Some(65001));
+    /// assert_eq!(rows[0].communities, "65000:100");
+    /// assert!(rows[1].withdrawn);
+    /// ```
+    pub fn from_update(update: &Update, timestamp: u64) -> Vec<RouteRow> {
+        let as_path = update.effective_as_path();
+        let mut rows = vec![];
+        for (afi, safi, route) in update.announced() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                let next_hop = update.next_hop(route).map(|next_hop| next_hop.address());
+                rows.push(RouteRow::with_attributes(
+                    timestamp,
+                    &key.prefix,
+                    as_path.clone(),
+                    &update.attributes,
+                    next_hop,
+                ));
+            }
+        }
+        for (afi, safi, route) in update.withdrawn() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                rows.push(RouteRow {
+                    timestamp,
+                    prefix: key.prefix.to_string(),
+                    withdrawn: true,
+                    origin_asn: None,
+                    as_path: String::new(),
+                    communities: String::new(),
+                    next_hop: None,
+                });
+            }
+        }
+        rows
+    }
+
+    fn with_attributes(
+        timestamp: u64,
+        prefix: &Prefix,
+        as_path: Option<ASPath>,
+        attributes: &[PathAttribute],
+        next_hop: Option<IpAddr>,
+    ) -> RouteRow {
+        let communities = attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                PathAttribute::COMMUNITY(communities) => Some(communities),
+                _ => None,
+            })
+            .map(|communities| {
+                let communities: Vec<String> = communities
+                    .iter()
+                    .map(|community| format!("{}:{}", community >> 16, community & 0xffff))
+                    .collect();
+                communities.join(" ")
+            })
+            .unwrap_or_default();
+        RouteRow {
+            timestamp,
+            prefix: prefix.to_string(),
+            withdrawn: false,
+            origin_asn: as_path.as_ref().and_then(|as_path| as_path.origin()),
+            as_path: as_path
+                .map(|as_path| as_path.to_string())
+                .unwrap_or_default(),
+            communities,
+            next_hop,
+        }
+    }
+}
+
+/// Writes routes to a CSV file, starting with a header row of the `ROUTE_COLUMNS`.
+///
+/// ```
+/// use bgp_rs::{CsvRouteWriter, RouteRow};
+///
+/// let mut writer = CsvRouteWriter::new(vec![]);
+/// writer
+///     .write(&RouteRow::new(1_600_000_000, &"10.0.0.0/8".parse().unwrap(), &[]))
+///     .unwrap();
+/// let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+/// assert_eq!(
+///     csv,
+///     "timestamp,prefix,withdrawn,origin_asn,as_path,communities,next_hop\n\
+///      1600000000,10.0.0.0/8,false,,,,\n"
+/// );
+/// ```
+#[derive(Debug)]
+pub struct CsvRouteWriter<W: Write> {
+    writer: csv::Writer<W>,
+    header: bool,
+}
+
+impl<W: Write> CsvRouteWriter<W> {
+    /// Creates a writer, the header row is written along with the first route.
+    pub fn new(writer: W) -> CsvRouteWriter<W> {
+        CsvRouteWriter {
+            writer: csv::Writer::from_writer(writer),
+            header: false,
+        }
+    }
+
+    /// Writes a route.
+    pub fn write(&mut self, row: &RouteRow) -> Result<(), Error> {
+        if !self.header {
+            self.writer
+                .write_record(&ROUTE_COLUMNS)
+                .map_err(csv_error)?;
+            self.header = true;
+        }
+        let origin_asn = row
+            .origin_asn
+            .map(|asn| asn.to_string())
+            .unwrap_or_default();
+        let next_hop = row
+            .next_hop
+            .map(|next_hop| next_hop.to_string())
+            .unwrap_or_default();
+        self.writer
+            .write_record(&[
+                row.timestamp.to_string().as_str(),
+                &row.prefix,
+                if row.withdrawn { "true" } else { "false" },
+                &origin_asn,
+                &row.as_path,
+                &row.communities,
+                &next_hop,
+            ])
+            .map_err(csv_error)
+    }
+
+    /// Writes the buffered routes to the underlying writer.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    /// Flushes the buffered routes and returns the underlying writer.
+    pub fn into_inner(self) -> Result<W, Error> {
+        self.writer.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+/// The Apache Arrow schema of exported routes, with a column per `ROUTE_COLUMNS` entry.
+pub fn route_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new(
+            ROUTE_COLUMNS[0],
+            DataType::Timestamp(TimeUnit::Second, None),
+            false,
+        ),
+        Field::new(ROUTE_COLUMNS[1], DataType::Utf8, false),
+        Field::new(ROUTE_COLUMNS[2], DataType::Boolean, false),
+        Field::new(ROUTE_COLUMNS[3], DataType::UInt32, true),
+        Field::new(ROUTE_COLUMNS[4], DataType::Utf8, false),
+        Field::new(ROUTE_COLUMNS[5], DataType::Utf8, false),
+        Field::new(ROUTE_COLUMNS[6], DataType::Utf8, true),
+    ]))
+}
+
+/// Converts routes into an Apache Arrow record batch of the `route_schema`.
+pub fn route_batch(rows: &[RouteRow]) -> Result<RecordBatch, Error> {
+    let timestamps: Vec<i64> = rows.iter().map(|row| row.timestamp as i64).collect();
+    let prefixes: Vec<&str> = rows.iter().map(|row| row.prefix.as_str()).collect();
+    let withdrawn: Vec<bool> = rows.iter().map(|row| row.withdrawn).collect();
+    let origin_asns: Vec<Option<u32>> = rows.iter().map(|row| row.origin_asn).collect();
+    let as_paths: Vec<&str> = rows.iter().map(|row| row.as_path.as_str()).collect();
+    let communities: Vec<&str> = rows.iter().map(|row| row.communities.as_str()).collect();
+    let next_hops: Vec<Option<String>> = rows
+        .iter()
+        .map(|row| row.next_hop.map(|next_hop| next_hop.to_string()))
+        .collect();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampSecondArray::from(timestamps)),
+        Arc::new(StringArray::from(prefixes)),
+        Arc::new(BooleanArray::from(withdrawn)),
+        Arc::new(UInt32Array::from(origin_asns)),
+        Arc::new(StringArray::from(as_paths)),
+        Arc::new(StringArray::from(communities)),
+        Arc::new(StringArray::from(next_hops)),
+    ];
+    RecordBatch::try_new(route_schema(), columns).map_err(other_error)
+}
+
+/// Writes routes to an Apache Parquet file of the `route_schema`, in row groups of the
+/// routes passed to each `write`.
+///
+/// The file is only complete once the writer is closed.
+pub struct ParquetRouteWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+}
+
+impl<W: Write + Send> ParquetRouteWriter<W> {
+    /// Creates a writer, writing the header of the file.
+    pub fn new(writer: W) -> Result<ParquetRouteWriter<W>, Error> {
+        let writer = ArrowWriter::try_new(writer, route_schema(), None).map_err(other_error)?;
+        Ok(ParquetRouteWriter { writer })
+    }
+
+    /// Writes routes.
+    pub fn write(&mut self, rows: &[RouteRow]) -> Result<(), Error> {
+        let batch = route_batch(rows)?;
+        self.writer.write(&batch).map_err(other_error)?;
+        self.writer.flush().map_err(other_error)
+    }
+
+    /// Writes the footer of the file, returning the underlying writer.
+    pub fn close(self) -> Result<W, Error> {
+        self.writer.into_inner().map_err(other_error)
+    }
+}
+
+impl<W: Write + Send> std::fmt::Debug for ParquetRouteWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ParquetRouteWriter").finish()
+    }
+}
+
+fn csv_error(error: csv::Error) -> Error {
+    match error.into_kind() {
+        csv::ErrorKind::Io(error) => error,
+        kind => Error::new(ErrorKind::Other, format!("{:?}", kind)),
+    }
+}
+
+fn other_error(error: impl std::error::Error) -> Error {
+    Error::new(ErrorKind::Other, error.to_string())
+}
@@ -0,0 +1,302 @@
+//! C bindings parsing messages into opaque handles, so collectors written in C can reuse this
+//! parser. Build the crate as a library C can link against with the `ffi` feature, e.g.
+//! `cargo rustc --release --features ffi --crate-type cdylib`, and declare:
+//!
+//! ```c
+//! typedef struct BgpMessage BgpMessage;
+//!
+//! typedef struct {
+//!     uint16_t afi;
+//!     uint8_t safi;
+//!     uint8_t length;
+//!     uint8_t address[16];
+//!     uint32_t path_id;
+//! } BgpPrefix;
+//!
+//! BgpMessage *bgp_message_parse(const uint8_t *data, size_t length, bool four_octet_asn,
+//!                               char **error);
+//! void bgp_message_free(BgpMessage *message);
+//! uint8_t bgp_message_type(const BgpMessage *message);
+//! size_t bgp_message_prefix_count(const BgpMessage *message, bool withdrawn);
+//! bool bgp_message_prefix(const BgpMessage *message, bool withdrawn, size_t index,
+//!                         BgpPrefix *prefix);
+//! size_t bgp_message_as_path(const BgpMessage *message, uint32_t *asns, size_t capacity);
+//! size_t bgp_message_communities(const BgpMessage *message, uint32_t *communities,
+//!                                size_t capacity);
+//! char *bgp_message_to_json(const BgpMessage *message);
+//! void bgp_string_free(char *string);
+//! ```
+//!
+//! Strings returned by these functions must be freed with `bgp_string_free`.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+use serde_json::{Map, Value};
+
+use crate::ris_live::message_to_json;
+use crate::*;
+
+/// A route of an UPDATE message, as returned by `bgp_message_prefix`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BgpPrefix {
+    /// The Address Family Identifier of the route (1 for IPv4, 2 for IPv6)
+    pub afi: u16,
+    /// The Subsequent Address Family Identifier of the route
+    pub safi: u8,
+    /// The length of the prefix, in bits
+    pub length: u8,
+    /// The address of the prefix, IPv4 addresses fill the first 4 bytes
+    pub address: [u8; 16],
+    /// The Path Identifier of the route (RFC7911), 0 when it has none
+    pub path_id: u32,
+}
+
+impl BgpPrefix {
+    // Routes without a prefix (e.g. L2VPN, MVPN or Flowspec NLRI) are skipped
+    fn from_route(afi: AFI, safi: SAFI, route: &NLRIEncoding) -> Option<BgpPrefix> {
+        let prefix = match route {
+            NLRIEncoding::IP(prefix)
+            | NLRIEncoding::IP_WITH_PATH_ID((prefix, _))
+            | NLRIEncoding::IP_MPLS((prefix, _))
+            | NLRIEncoding::IP_MPLS_WITH_PATH_ID((prefix, _, _))
+            | NLRIEncoding::IP_VPN_MPLS((_, prefix, _)) => prefix,
+            _ => return None,
+        };
+        let mut address = [0; 16];
+        for (octet, value) in address.iter_mut().zip(&prefix.prefix) {
+            *octet = *value;
+        }
+        Some(BgpPrefix {
+            afi: u16::from(afi),
+            safi: u8::from(safi),
+            length: prefix.length,
+            address,
+            path_id: route.path_id().unwrap_or(0),
+        })
+    }
+}
+
+/// A parsed message, the opaque handle of the C bindings.
+#[derive(Debug)]
+pub struct BgpMessage {
+    message: Message,
+    announced: Vec<BgpPrefix>,
+    withdrawn: Vec<BgpPrefix>,
+}
+
+/// Parses a message, including its header, returning NULL when it can not be parsed.
+///
+/// The error is then stored in `error` unless it is NULL.
+///
+/// # Safety
+/// `data` must point to `length` readable bytes, `error` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_parse(
+    data: *const u8,
+    length: usize,
+    four_octet_asn: bool,
+    error: *mut *mut c_char,
+) -> *mut BgpMessage {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: four_octet_asn,
+        ..Capabilities::default()
+    };
+    match decode_message(slice::from_raw_parts(data, length), &capabilities) {
+        Ok(message) => {
+            let (announced, withdrawn) = match &message {
+                Message::Update(update) => {
+                    (prefixes(update.announced()), prefixes(update.withdrawn()))
+                }
+                _ => (vec![], vec![]),
+            };
+            Box::into_raw(Box::new(BgpMessage {
+                message,
+                announced,
+                withdrawn,
+            }))
+        }
+        Err(e) => {
+            if !error.is_null() {
+                *error = to_c_string(e.message);
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a message returned by `bgp_message_parse`.
+///
+/// # Safety
+/// `message` must be NULL or a handle that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_free(message: *mut BgpMessage) {
+    if !message.is_null() {
+        drop(Box::from_raw(message));
+    }
+}
+
+/// The type code of the message (e.g. 2 for UPDATE), 0 when `message` is NULL.
+///
+/// # Safety
+/// `message` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_type(message: *const BgpMessage) -> u8 {
+    match message.as_ref() {
+        Some(message) => u8::from(message.message.message_type()),
+        None => 0,
+    }
+}
+
+/// The number of announced (or withdrawn) prefixes of an UPDATE message, including those of
+/// MP_REACH_NLRI (or MP_UNREACH_NLRI).
+///
+/// # Safety
+/// `message` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_prefix_count(
+    message: *const BgpMessage,
+    withdrawn: bool,
+) -> usize {
+    match message.as_ref() {
+        Some(message) => message.prefixes(withdrawn).len(),
+        None => 0,
+    }
+}
+
+/// Copies an announced (or withdrawn) prefix into `prefix`, false when `index` is out of range.
+///
+/// # Safety
+/// `message` must be NULL or a valid handle, `prefix` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_prefix(
+    message: *const BgpMessage,
+    withdrawn: bool,
+    index: usize,
+    prefix: *mut BgpPrefix,
+) -> bool {
+    match (message.as_ref(), prefix.as_mut()) {
+        (Some(message), Some(prefix)) => match message.prefixes(withdrawn).get(index) {
+            Some(value) => {
+                *prefix = *value;
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Copies the ASNs of the AS_PATH (merged with the AS4_PATH, with the ASNs of AS_SETs in
+/// place) into `asns`, and returns their number. At most `capacity` ASNs are copied, call it
+/// again with a larger buffer when the path is longer.
+///
+/// # Safety
+/// `message` must be NULL or a valid handle, `asns` must point to `capacity` writable ASNs.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_as_path(
+    message: *const BgpMessage,
+    asns: *mut u32,
+    capacity: usize,
+) -> usize {
+    let as_path = match message.as_ref().map(|message| &message.message) {
+        Some(Message::Update(update)) => update.effective_as_path(),
+        _ => None,
+    };
+    let path: Vec<u32> = as_path
+        .iter()
+        .flat_map(|as_path| &as_path.segments)
+        .flat_map(|segment| match segment {
+            Segment::AS_SEQUENCE(asns) | Segment::AS_SET(asns) => asns,
+        })
+        .cloned()
+        .collect();
+    copy_to(&path, asns, capacity)
+}
+
+/// Copies the COMMUNITIES into `communities` like `bgp_message_as_path`.
+///
+/// # Safety
+/// `message` must be NULL or a valid handle, `communities` must point to `capacity` writable
+/// communities.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_communities(
+    message: *const BgpMessage,
+    communities: *mut u32,
+    capacity: usize,
+) -> usize {
+    match message.as_ref().map(|message| &message.message) {
+        Some(Message::Update(update)) => match update.get(Identifier::COMMUNITY) {
+            Some(PathAttribute::COMMUNITY(values)) => copy_to(values, communities, capacity),
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// The message as the `data` of a RIS Live message (see `RisLiveMessage`), NULL when it can
+/// not be converted (ROUTE-REFRESH messages).
+///
+/// # Safety
+/// `message` must be NULL or a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_message_to_json(message: *const BgpMessage) -> *mut c_char {
+    let message = match message.as_ref() {
+        Some(message) => message,
+        None => return ptr::null_mut(),
+    };
+    let mut data = Map::new();
+    match message_to_json(&message.message, &mut data) {
+        Ok(()) => to_c_string(Value::Object(data).to_string()),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Frees a string returned by these bindings.
+///
+/// # Safety
+/// `string` must be NULL or a string that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bgp_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+impl BgpMessage {
+    fn prefixes(&self, withdrawn: bool) -> &[BgpPrefix] {
+        if withdrawn {
+            &self.withdrawn
+        } else {
+            &self.announced
+        }
+    }
+}
+
+fn prefixes<'a>(routes: impl Iterator<Item = (AFI, SAFI, &'a NLRIEncoding)>) -> Vec<BgpPrefix> {
+    routes
+        .filter_map(|(afi, safi, route)| BgpPrefix::from_route(afi, safi, route))
+        .collect()
+}
+
+// Copies up to `capacity` values, returning the number of values available
+unsafe fn copy_to(values: &[u32], buffer: *mut u32, capacity: usize) -> usize {
+    if !buffer.is_null() {
+        let count = values.len().min(capacity);
+        ptr::copy_nonoverlapping(values.as_ptr(), buffer, count);
+    }
+    values.len()
+}
+
+// Strings holding a NUL byte (never produced by the parser) are returned as NULL
+fn to_c_string(string: String) -> *mut c_char {
+    CString::new(string)
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
@@ -0,0 +1,169 @@
+//! The `filter` mod provides community filter expressions
+//! - Standard communities with wildcards: `65000:*`, `*:666`
+//! - Large communities with wildcards: `65000:*:100`
+//! - Evaluation against the communities of UPDATE messages or of RIB entries
+//!
+
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+use crate::*;
+
+/// A community pattern, each part of which is either a value or a wildcard (None).
+///
+/// ```
+/// use bgp_rs::{CommunityFilter, PathAttribute};
+///
+/// let filter: CommunityFilter = "65000:*".parse().unwrap();
+/// let attributes = vec![PathAttribute::COMMUNITY(vec![0xfde8_0064])];
+/// assert!(filter.matches_attributes(&attributes));
+///
+/// let filter: CommunityFilter = "*:666".parse().unwrap();
+/// assert!(!filter.matches_attributes(&attributes));
+///
+/// let filter: CommunityFilter = "4200000000:*:100".parse().unwrap();
+/// let attributes = vec![PathAttribute::LARGE_COMMUNITY(vec![(4200000000, 1, 100)])];
+/// assert!(filter.matches_attributes(&attributes));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CommunityFilter {
+    /// Matches communities of COMMUNITY (RFC1997), written as `asn:value`.
+    Standard {
+        /// The high-order 16 bits, usually an ASN.
+        asn: Option<u16>,
+        /// The low-order 16 bits.
+        value: Option<u16>,
+    },
+
+    /// Matches large communities of LARGE_COMMUNITY (RFC8092), written as
+    /// `global:data1:data2`.
+    Large {
+        /// The Global Administrator.
+        global: Option<u32>,
+        /// The Local Data Part 1.
+        data1: Option<u32>,
+        /// The Local Data Part 2.
+        data2: Option<u32>,
+    },
+}
+
+impl CommunityFilter {
+    /// Whether a community of COMMUNITY matches.
+    pub fn matches_community(&self, community: u32) -> bool {
+        match self {
+            CommunityFilter::Standard { asn, value } => {
+                part_matches(asn.map(u32::from), community >> 16)
+                    && part_matches(value.map(u32::from), community & 0xffff)
+            }
+            CommunityFilter::Large { .. } => false,
+        }
+    }
+
+    /// Whether a large community of LARGE_COMMUNITY matches.
+    pub fn matches_large_community(&self, community: (u32, u32, u32)) -> bool {
+        match self {
+            CommunityFilter::Standard { .. } => false,
+            CommunityFilter::Large {
+                global,
+                data1,
+                data2,
+            } => {
+                part_matches(*global, community.0)
+                    && part_matches(*data1, community.1)
+                    && part_matches(*data2, community.2)
+            }
+        }
+    }
+
+    /// Whether any of the communities of the path attributes matches.
+    pub fn matches_attributes(&self, attributes: &[PathAttribute]) -> bool {
+        attributes.iter().any(|attribute| match attribute {
+            PathAttribute::COMMUNITY(communities) => communities
+                .iter()
+                .any(|community| self.matches_community(*community)),
+            PathAttribute::LARGE_COMMUNITY(communities) => communities
+                .iter()
+                .any(|community| self.matches_large_community(*community)),
+            _ => false,
+        })
+    }
+
+    /// Whether any of the communities of an UPDATE message matches.
+    pub fn matches_update(&self, update: &Update) -> bool {
+        self.matches_attributes(&update.attributes)
+    }
+}
+
+fn part_matches(part: Option<u32>, value: u32) -> bool {
+    part.map_or(true, |part| part == value)
+}
+
+impl FromStr for CommunityFilter {
+    type Err = Error;
+
+    /// Parse a filter such as "65000:*", "*:666" or "65000:*:100"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid community filter: {}", s),
+            )
+        };
+        let parts: Vec<&str> = s.split(':').collect();
+        match parts.as_slice() {
+            [asn, value] => Ok(CommunityFilter::Standard {
+                asn: parse_part(asn).ok_or_else(invalid)?,
+                value: parse_part(value).ok_or_else(invalid)?,
+            }),
+            [global, data1, data2] => Ok(CommunityFilter::Large {
+                global: parse_part(global).ok_or_else(invalid)?,
+                data1: parse_part(data1).ok_or_else(invalid)?,
+                data2: parse_part(data2).ok_or_else(invalid)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Parses a value or a wildcard, None when the part is invalid
+fn parse_part<T: FromStr>(part: &str) -> Option<Option<T>> {
+    match part {
+        "*" => Some(None),
+        part => part.parse().ok().map(Some),
+    }
+}
+
+/// Display the filter as it is parsed
+/// ```
+/// use bgp_rs::CommunityFilter;
+///
+/// let filter = CommunityFilter::Standard { asn: None, value: Some(666) };
+/// assert_eq!(&filter.to_string(), "*:666");
+/// ```
+impl Display for CommunityFilter {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            CommunityFilter::Standard { asn, value } => {
+                write!(f, "{}:{}", Part(*asn), Part(*value))
+            }
+            CommunityFilter::Large {
+                global,
+                data1,
+                data2,
+            } => write!(f, "{}:{}:{}", Part(*global), Part(*data1), Part(*data2)),
+        }
+    }
+}
+
+// A part of a filter, displayed as its value or a wildcard
+struct Part<T>(Option<T>);
+
+impl<T: Display> Display for Part<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match &self.0 {
+            Some(value) => write!(f, "{}", value),
+            None => write!(f, "*"),
+        }
+    }
+}
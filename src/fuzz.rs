@@ -0,0 +1,350 @@
+//! Generation of arbitrary messages for structured fuzzing of the encoder & decoder.
+//!
+//! Only values that can be encoded are generated (e.g. prefixes are masked, lists that may not
+//! be empty on the wire have at least one entry), so encoding errors point at actual bugs.
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use std::io::{Cursor, Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::*;
+
+const AFIS: [AFI; 4] = [AFI::IPV4, AFI::IPV6, AFI::L2VPN, AFI::BGPLS];
+const SAFIS: [SAFI; 12] = [
+    SAFI::Unicast,
+    SAFI::Multicast,
+    SAFI::Mpls,
+    SAFI::MulticastVpn,
+    SAFI::Vpls,
+    SAFI::Evpn,
+    SAFI::BgpLs,
+    SAFI::BgpLsVpn,
+    SAFI::Rtc,
+    SAFI::MplsVpn,
+    SAFI::Flowspec,
+    SAFI::FlowspecVPN,
+];
+
+///
+/// Encodes a message, decodes it again and checks that encoding the decoded message results in
+/// the same bytes. Messages are encoded & decoded with support for 4-byte ASNs.
+///
+/// ```
+/// use bgp_rs::{roundtrip, Message};
+///
+/// roundtrip(&Message::KeepAlive).unwrap();
+/// ```
+///
+/// Typically used as the property of a fuzz target, such as those in the `fuzz` directory:
+///
+/// ```ignore
+/// fuzz_target!(|message: bgp_rs::Message| {
+///     bgp_rs::roundtrip(&message).unwrap();
+/// });
+/// ```
+///
+pub fn roundtrip(message: &Message) -> Result<(), Error> {
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    let mut encoded = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+    message.encode_with_capabilities(&mut encoded, &capabilities)?;
+
+    let mut reader = Reader::with_capabilities(Cursor::new(&encoded), &capabilities);
+    let (_header, decoded) = reader.read()?;
+    if reader.stream.position() != encoded.len() as u64 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Decoding {:?} left {} bytes",
+                message,
+                encoded.len() as u64 - reader.stream.position()
+            ),
+        ));
+    }
+
+    let mut reencoded = Vec::with_capacity(encoded.len());
+    decoded.encode_with_capabilities(&mut reencoded, &capabilities)?;
+    if reencoded != encoded {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{:?} was decoded as {:?}, encoding {} instead of {}",
+                message,
+                decoded,
+                util::to_hex(&reencoded),
+                util::to_hex(&encoded)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=4)? {
+            0 => Message::Open(arbitrary_open(u)?),
+            1 => Message::Update(u.arbitrary()?),
+            2 => Message::Notification(Notification::from_data(
+                u.arbitrary()?,
+                u.arbitrary()?,
+                arbitrary_vec(u, 0, 32, |u| u.arbitrary())?,
+            )),
+            3 => Message::KeepAlive,
+            _ => Message::RouteRefresh(RouteRefresh {
+                afi: *u.choose(&AFIS)?,
+                safi: *u.choose(&SAFIS)?,
+                subtype: *u.choose(&[
+                    RouteRefreshSubtype::Normal,
+                    RouteRefreshSubtype::BeginOfRouteRefresh,
+                    RouteRefreshSubtype::EndOfRouteRefresh,
+                ])?,
+                orf: None,
+            }),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Update {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let withdrawn_routes = arbitrary_vec(u, 0, 8, |u| {
+            Ok(NLRIEncoding::IP(arbitrary_prefix(u, AFI::IPV4)?))
+        })?;
+        // Every attribute may only occur once
+        let mut attributes: Vec<PathAttribute> = Vec::with_capacity(8);
+        for attribute in arbitrary_vec(u, 0, 8, PathAttribute::arbitrary)? {
+            if attributes.iter().all(|other| other.id() != attribute.id()) {
+                attributes.push(attribute);
+            }
+        }
+        let announced_routes = arbitrary_vec(u, 0, 8, |u| {
+            Ok(NLRIEncoding::IP(arbitrary_prefix(u, AFI::IPV4)?))
+        })?;
+        Ok(Update::new(withdrawn_routes, attributes, announced_routes))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PathAttribute {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(match u.int_in_range(0..=12)? {
+            0 => {
+                PathAttribute::ORIGIN(*u.choose(&[Origin::IGP, Origin::EGP, Origin::INCOMPLETE])?)
+            }
+            1 => PathAttribute::AS_PATH(ASPath {
+                segments: arbitrary_vec(u, 0, 4, |u| {
+                    let asns = arbitrary_vec(u, 1, 8, |u| u.arbitrary())?;
+                    Ok(if u.arbitrary()? {
+                        Segment::AS_SET(asns)
+                    } else {
+                        Segment::AS_SEQUENCE(asns)
+                    })
+                })?,
+            }),
+            2 => PathAttribute::NEXT_HOP(IpAddr::V4(arbitrary_ipv4(u)?)),
+            3 => PathAttribute::MULTI_EXIT_DISC(u.arbitrary()?),
+            4 => PathAttribute::LOCAL_PREF(u.arbitrary()?),
+            5 => PathAttribute::AGGREGATOR((u.arbitrary()?, arbitrary_ipv4(u)?)),
+            6 => PathAttribute::COMMUNITY(arbitrary_vec(u, 1, 8, |u| u.arbitrary())?),
+            7 => PathAttribute::ORIGINATOR_ID(arbitrary_ipv4(u)?),
+            8 => PathAttribute::CLUSTER_LIST(arbitrary_vec(u, 1, 4, arbitrary_ipv4)?),
+            9 => PathAttribute::EXTENDED_COMMUNITIES(arbitrary_vec(u, 1, 4, |u| u.arbitrary())?),
+            10 => PathAttribute::OTC(u.arbitrary()?),
+            11 => PathAttribute::MP_REACH_NLRI(Box::new(arbitrary_mp_reach(u)?)),
+            _ => PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: arbitrary_vec(u, 0, 8, |u| {
+                    Ok(NLRIEncoding::IP(arbitrary_prefix(u, AFI::IPV6)?))
+                })?,
+            })),
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Prefix {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let protocol = if u.arbitrary()? { AFI::IPV6 } else { AFI::IPV4 };
+        arbitrary_prefix(u, protocol)
+    }
+}
+
+#[cfg(feature = "flowspec")]
+impl<'a> Arbitrary<'a> for FlowspecFilter {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let afi = if u.arbitrary()? { AFI::IPV6 } else { AFI::IPV4 };
+        arbitrary_filter(u, afi)
+    }
+}
+
+// Filters of a single Flowspec NLRI must all match the AFI of its MP_REACH_NLRI
+#[cfg(feature = "flowspec")]
+fn arbitrary_filter(u: &mut Unstructured, afi: AFI) -> arbitrary::Result<FlowspecFilter> {
+    use FlowspecFilter::*;
+
+    // The length & end-of-list bits are set while encoding
+    fn numeric(u: &mut Unstructured) -> arbitrary::Result<NumericOperator> {
+        let bits: u8 = u.arbitrary()?;
+        Ok(NumericOperator::new(bits)
+            & (NumericOperator::EQ
+                | NumericOperator::GT
+                | NumericOperator::LT
+                | NumericOperator::AND))
+    }
+    fn values<T: for<'b> Arbitrary<'b>>(
+        u: &mut Unstructured,
+    ) -> arbitrary::Result<Vec<(NumericOperator, T)>> {
+        arbitrary_vec(u, 1, 4, |u| Ok((numeric(u)?, u.arbitrary()?)))
+    }
+
+    Ok(match u.int_in_range(1..=12)? {
+        1 => DestinationPrefix(arbitrary_prefix(u, afi)?),
+        2 => SourcePrefix(arbitrary_prefix(u, afi)?),
+        3 => IpProtocol(values(u)?),
+        4 => Port(values(u)?),
+        5 => DestinationPort(values(u)?),
+        6 => SourcePort(values(u)?),
+        7 => IcmpType(values(u)?),
+        8 => IcmpCode(values(u)?),
+        9 => TcpFlags(arbitrary_vec(u, 1, 4, |u| {
+            let bits: u8 = u.arbitrary()?;
+            let operator = BinaryOperator::new(bits)
+                & (BinaryOperator::MATCH | BinaryOperator::NOT | BinaryOperator::AND);
+            Ok((operator, u.arbitrary()?))
+        })?),
+        10 => PacketLength(values(u)?),
+        11 => DSCP(values(u)?),
+        _ => Fragment(arbitrary_vec(u, 1, 4, |u| {
+            let bits: u8 = u.arbitrary()?;
+            let operator = FragmentOperator::new(bits)
+                & (FragmentOperator::DF
+                    | FragmentOperator::IF
+                    | FragmentOperator::FF
+                    | FragmentOperator::LF);
+            Ok((operator, u.arbitrary()?))
+        })?),
+    })
+}
+
+fn arbitrary_open(u: &mut Unstructured) -> arbitrary::Result<Open> {
+    let parameters = arbitrary_vec(u, 0, 2, |u| {
+        Ok(OpenParameter::Capabilities(arbitrary_vec(
+            u,
+            1,
+            4,
+            arbitrary_capability,
+        )?))
+    })?;
+    Ok(Open {
+        version: 4,
+        peer_asn: u.arbitrary()?,
+        hold_timer: u.arbitrary()?,
+        identifier: u.arbitrary()?,
+        parameters,
+    })
+}
+
+fn arbitrary_capability(u: &mut Unstructured) -> arbitrary::Result<OpenCapability> {
+    let directions = [
+        AddPathDirection::ReceivePaths,
+        AddPathDirection::SendPaths,
+        AddPathDirection::SendReceivePaths,
+    ];
+    Ok(match u.int_in_range(0..=7)? {
+        0 => OpenCapability::MultiProtocol((*u.choose(&AFIS)?, *u.choose(&SAFIS)?)),
+        1 => OpenCapability::RouteRefresh,
+        2 => OpenCapability::ExtendedNextHop(arbitrary_vec(u, 1, 3, |u| {
+            Ok((*u.choose(&AFIS)?, *u.choose(&SAFIS)?, *u.choose(&AFIS)?))
+        })?),
+        3 => OpenCapability::BGPRole(*u.choose(&[
+            BGPRole::Provider,
+            BGPRole::RouteServer,
+            BGPRole::RouteServerClient,
+            BGPRole::Customer,
+            BGPRole::Peer,
+        ])?),
+        4 => OpenCapability::FourByteASN(u.arbitrary()?),
+        5 => OpenCapability::AddPath(arbitrary_vec(u, 1, 3, |u| {
+            Ok((
+                *u.choose(&AFIS)?,
+                *u.choose(&SAFIS)?,
+                *u.choose(&directions)?,
+            ))
+        })?),
+        6 => OpenCapability::EnhancedRouteRefresh,
+        _ => {
+            // Codes reserved for private use, which are never decoded as a known capability
+            let value = arbitrary_vec(u, 0, 8, |u| u.arbitrary())?;
+            OpenCapability::Unknown {
+                cap_code: u.int_in_range(239..=254)?,
+                cap_length: value.len() as u8,
+                value,
+            }
+        }
+    })
+}
+
+fn arbitrary_mp_reach(u: &mut Unstructured) -> arbitrary::Result<MPReachNLRI> {
+    #[cfg(feature = "flowspec")]
+    {
+        if u.ratio(1, 4)? {
+            let afi = if u.arbitrary()? { AFI::IPV6 } else { AFI::IPV4 };
+            let filters = arbitrary_vec(u, 1, 4, |u| arbitrary_filter(u, afi))?;
+            return Ok(MPReachNLRI {
+                afi,
+                safi: SAFI::Flowspec,
+                next_hop: vec![],
+                announced_routes: vec![NLRIEncoding::FLOWSPEC(filters)],
+            });
+        }
+    }
+    // A global address, optionally followed by a link-local address
+    let mut next_hop = vec![0; if u.arbitrary()? { 32 } else { 16 }];
+    u.fill_buffer(&mut next_hop)?;
+    Ok(MPReachNLRI {
+        afi: AFI::IPV6,
+        safi: SAFI::Unicast,
+        next_hop,
+        announced_routes: arbitrary_vec(u, 0, 8, |u| {
+            Ok(NLRIEncoding::IP(arbitrary_prefix(u, AFI::IPV6)?))
+        })?,
+    })
+}
+
+fn arbitrary_prefix(u: &mut Unstructured, protocol: AFI) -> arbitrary::Result<Prefix> {
+    let mut prefix = protocol
+        .empty_buffer()
+        .map_err(|_| arbitrary::Error::IncorrectFormat)?;
+    let length = u.int_in_range(0..=(prefix.len() * 8) as u8)?;
+    u.fill_buffer(&mut prefix)?;
+    // Clear the bits beyond the mask, as they aren't encoded
+    for (i, octet) in prefix.iter_mut().enumerate() {
+        let bits = usize::from(length).saturating_sub(i * 8).min(8);
+        *octet &= !(0xffu8.checked_shr(bits as u32).unwrap_or(0));
+    }
+    Ok(Prefix {
+        protocol,
+        length,
+        prefix,
+    })
+}
+
+fn arbitrary_ipv4(u: &mut Unstructured) -> arbitrary::Result<Ipv4Addr> {
+    Ok(Ipv4Addr::from(u.arbitrary::<u32>()?))
+}
+
+// Generates between min and max elements, fewer when running out of data
+fn arbitrary_vec<'a, T>(
+    u: &mut Unstructured<'a>,
+    min: usize,
+    max: usize,
+    mut element: impl FnMut(&mut Unstructured<'a>) -> arbitrary::Result<T>,
+) -> arbitrary::Result<Vec<T>> {
+    let length = u.int_in_range(min..=max)?;
+    let mut elements = Vec::with_capacity(length);
+    for _ in 0..length {
+        elements.push(element(u)?);
+    }
+    Ok(elements)
+}
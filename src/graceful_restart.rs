@@ -0,0 +1,154 @@
+//! The `graceful_restart` mod provides the receiving speaker side of BGP Graceful Restart (RFC4724)
+//! - Tracking of the End-of-RIB markers received per AFI/SAFI
+//! - Retention of the routes of a restarting peer as stale routes of a `Rib`
+//! - Expiry of the stale routes after the restart and stale timers
+//!
+//! The timers are only checked when the caller invokes `expire`.
+//!
+
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// The Graceful Restart state of a peer, keeping its routes in a `Rib` as stale routes while
+/// its session is reset, until it sends them again followed by End-of-RIB markers.
+///
+/// ```
+/// use bgp_rs::{GracefulRestartState, NLRIEncoding, PathAttribute, Rib, Update, AFI, SAFI};
+/// use std::time::Duration;
+///
+/// let families = vec![(AFI::IPV4, SAFI::Unicast)].into_iter().collect();
+/// let mut state =
+///     GracefulRestartState::new(families, Duration::from_secs(120), Duration::from_secs(360));
+/// let mut rib = Rib::new();
+/// let update = Update::new(
+///     vec![],
+///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+///     vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+/// );
+/// state.apply(&mut rib, &update, 1);
+///
+/// // The routes are retained while the peer restarts
+/// state.session_down(&mut rib, &1);
+/// state.session_up();
+/// assert_eq!(rib.len(), 1);
+///
+/// // Until the peer signals it sent all its routes again
+/// state.apply(&mut rib, &Update::end_of_rib(AFI::IPV4, SAFI::Unicast), 1);
+/// assert!(state.end_of_rib_received(AFI::IPV4, SAFI::Unicast));
+/// assert!(rib.is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct GracefulRestartState {
+    /// The AFI/SAFIs for which the peer preserves its forwarding state, as negotiated with
+    /// the Graceful Restart Capability. The routes of other AFI/SAFIs are removed right away.
+    pub families: HashSet<(AFI, SAFI)>,
+
+    /// The Restart Time of the peer: how long stale routes are retained for the session to be
+    /// re-established.
+    pub restart_time: Duration,
+
+    /// How long stale routes are retained once the session is re-established, for the peer
+    /// to send End-of-RIB markers.
+    pub stale_time: Duration,
+
+    end_of_rib: HashSet<(AFI, SAFI)>,
+    stale: HashSet<(AFI, SAFI)>,
+    down_since: Option<Instant>,
+    up_since: Option<Instant>,
+}
+
+impl GracefulRestartState {
+    /// Create the state of an established session.
+    pub fn new(
+        families: HashSet<(AFI, SAFI)>,
+        restart_time: Duration,
+        stale_time: Duration,
+    ) -> GracefulRestartState {
+        GracefulRestartState {
+            families,
+            restart_time,
+            stale_time,
+            end_of_rib: HashSet::new(),
+            stale: HashSet::new(),
+            down_since: None,
+            up_since: None,
+        }
+    }
+
+    /// Applies an UPDATE message received from the peer to the RIB. An End-of-RIB marker
+    /// removes the stale routes of its AFI/SAFI that were not announced again.
+    pub fn apply<P>(&mut self, rib: &mut Rib<P>, update: &Update, peer: P)
+    where
+        P: Clone + Eq + Hash,
+    {
+        match update.end_of_rib_family() {
+            Some(family) => {
+                self.end_of_rib.insert(family);
+                if self.stale.remove(&family) {
+                    rib.remove_stale(&peer, |afi, safi| (afi, safi) == family);
+                }
+            }
+            None => rib.apply(update, peer),
+        }
+    }
+
+    /// Whether an End-of-RIB marker was received for an AFI/SAFI since the session was
+    /// (re-)established.
+    pub fn end_of_rib_received(&self, afi: AFI, safi: SAFI) -> bool {
+        self.end_of_rib.contains(&(afi, safi))
+    }
+
+    /// Whether stale routes of the peer are retained.
+    pub fn is_restarting(&self) -> bool {
+        !self.stale.is_empty()
+    }
+
+    /// Marks the routes of the peer as stale when its session is reset, and removes those of
+    /// the AFI/SAFIs it does not preserve. Starts the restart timer.
+    pub fn session_down<P>(&mut self, rib: &mut Rib<P>, peer: &P)
+    where
+        P: Clone + Eq + Hash,
+    {
+        rib.mark_stale(peer);
+        let families = &self.families;
+        rib.remove_stale(peer, |afi, safi| !families.contains(&(afi, safi)));
+        self.stale = self.families.clone();
+        self.end_of_rib.clear();
+        self.down_since = Some(Instant::now());
+        self.up_since = None;
+    }
+
+    /// Stops the restart timer once the session is re-established, and starts the stale timer.
+    pub fn session_up(&mut self) {
+        self.end_of_rib.clear();
+        if self.down_since.take().is_some() {
+            self.up_since = Some(Instant::now());
+        }
+    }
+
+    /// Removes the stale routes of the peer if the session was not re-established within the
+    /// restart time, or End-of-RIB markers were not received within the stale time.
+    /// Returns how many routes were removed.
+    pub fn expire<P>(&mut self, rib: &mut Rib<P>, peer: &P) -> usize
+    where
+        P: Clone + Eq + Hash,
+    {
+        let expired = match (self.down_since, self.up_since) {
+            (Some(down_since), _) => down_since.elapsed() >= self.restart_time,
+            (None, Some(up_since)) => up_since.elapsed() >= self.stale_time,
+            (None, None) => false,
+        };
+        if !expired {
+            return 0;
+        }
+        self.down_since = None;
+        self.up_since = None;
+        let stale = &self.stale;
+        let removed = rib.remove_stale(peer, |afi, safi| stale.contains(&(afi, safi)));
+        self.stale.clear();
+        removed
+    }
+}
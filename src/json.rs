@@ -0,0 +1,249 @@
+//! Helpers shared by the conversions to & from JSON formats (RIS Live, exabgp)
+
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde_json::{json, Map, Value};
+
+pub(crate) use crate::util::{from_hex, to_hex};
+use crate::*;
+
+// Sequences are listed as ASNs, sets as nested arrays: [64496, [64497, 64498]]
+pub(crate) fn as_path_from_json(path: &Value) -> Result<ASPath, Error> {
+    let mut segments: Vec<Segment> = vec![];
+    let mut sequence: Vec<u32> = vec![];
+    for element in path.as_array().ok_or_else(|| invalid("Invalid path"))? {
+        match element {
+            Value::Array(set) => {
+                if !sequence.is_empty() {
+                    segments.push(Segment::AS_SEQUENCE(sequence.split_off(0)));
+                }
+                let set = set
+                    .iter()
+                    .map(as_asn)
+                    .collect::<Result<Vec<u32>, Error>>()?;
+                segments.push(Segment::AS_SET(set));
+            }
+            asn => sequence.push(as_asn(asn)?),
+        }
+    }
+    if !sequence.is_empty() {
+        segments.push(Segment::AS_SEQUENCE(sequence));
+    }
+    Ok(ASPath { segments })
+}
+
+pub(crate) fn as_path_to_json(path: &ASPath) -> Value {
+    let mut segments: Vec<Value> = vec![];
+    for segment in &path.segments {
+        match segment {
+            Segment::AS_SEQUENCE(asns) => segments.extend(asns.iter().map(|asn| json!(asn))),
+            Segment::AS_SET(asns) => segments.push(json!(asns)),
+        }
+    }
+    Value::Array(segments)
+}
+
+// Capabilities are keyed by their code: {"1": {"name": "multiprotocol", "families": ["ipv4/unicast"]}}
+// with the AFI and SAFI of families joined by the separator.
+// Only the multiprotocol, route-refresh and asn4 capabilities are restored.
+pub(crate) fn open_from_json(data: &Value, separator: char) -> Result<Open, Error> {
+    let asn = data
+        .get("asn")
+        .and_then(Value::as_u64)
+        .and_then(|asn| u32::try_from(asn).ok())
+        .ok_or_else(|| invalid("Invalid asn"))?;
+    let router_id: Ipv4Addr = get_str(data, "router_id")?
+        .parse()
+        .map_err(|_| invalid("Invalid router_id"))?;
+
+    let mut capabilities: Vec<OpenCapability> = vec![];
+    if let Some(Value::Object(caps)) = data.get("capabilities") {
+        for (code, capability) in caps {
+            match code.as_str() {
+                "1" => {
+                    for family in get_array(capability, "families")? {
+                        capabilities.push(OpenCapability::MultiProtocol(family_from_json(
+                            family, separator,
+                        )?));
+                    }
+                }
+                "2" => capabilities.push(OpenCapability::RouteRefresh),
+                "65" => capabilities.push(OpenCapability::FourByteASN(asn)),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(Open {
+        version: data.get("version").and_then(Value::as_u64).unwrap_or(4) as u8,
+        peer_asn: u16::try_from(asn).unwrap_or(AS_TRANS as u16),
+        hold_timer: data
+            .get("hold_time")
+            .and_then(Value::as_u64)
+            .and_then(|hold_time| u16::try_from(hold_time).ok())
+            .ok_or_else(|| invalid("Invalid hold_time"))?,
+        identifier: u32::from(router_id),
+        parameters: vec![OpenParameter::Capabilities(capabilities)],
+    })
+}
+
+pub(crate) fn open_to_json(open: &Open, separator: char) -> Map<String, Value> {
+    let mut capabilities = Map::new();
+    let mut families: Vec<Value> = vec![];
+    for parameter in &open.parameters {
+        if let OpenParameter::Capabilities(caps) = parameter {
+            for capability in caps {
+                let (code, name) = match capability {
+                    OpenCapability::MultiProtocol((afi, safi)) => {
+                        families.push(json!(family_to_json(*afi, *safi, separator)));
+                        continue;
+                    }
+                    OpenCapability::RouteRefresh => (2, "route-refresh"),
+                    OpenCapability::OutboundRouteFiltering(_) => (3, "outbound-route-filtering"),
+                    OpenCapability::ExtendedNextHop(_) => (5, "extended-next-hop"),
+                    OpenCapability::BGPRole(_) => (9, "role"),
+                    OpenCapability::FourByteASN(_) => (65, "asn4"),
+                    OpenCapability::AddPath(_) => (69, "add-path"),
+                    OpenCapability::EnhancedRouteRefresh => (70, "enhanced-route-refresh"),
+                    OpenCapability::Unknown { cap_code, .. } => (*cap_code, "unknown"),
+                };
+                capabilities.insert(code.to_string(), json!({ "name": name }));
+            }
+        }
+    }
+    if !families.is_empty() {
+        capabilities.insert(
+            "1".to_string(),
+            json!({"name": "multiprotocol", "families": families}),
+        );
+    }
+
+    let mut data = Map::new();
+    data.insert("version".to_string(), json!(open.version));
+    data.insert("asn".to_string(), json!(open.asn()));
+    data.insert("hold_time".to_string(), json!(open.hold_timer));
+    data.insert(
+        "router_id".to_string(),
+        json!(Ipv4Addr::from(open.identifier).to_string()),
+    );
+    data.insert("capabilities".to_string(), Value::Object(capabilities));
+    data
+}
+
+// {"code": 6, "subcode": 2, "data": "0102"}
+pub(crate) fn notification_from_json(notification: &Value) -> Result<Notification, Error> {
+    let code = |key: &str| {
+        notification
+            .get(key)
+            .and_then(Value::as_u64)
+            .and_then(|code| u8::try_from(code).ok())
+            .ok_or_else(|| invalid(format!("Invalid notification {}", key)))
+    };
+    let data = match notification.get("data").and_then(Value::as_str) {
+        Some(data) => from_hex(data)?,
+        None => vec![],
+    };
+    Ok(Notification::from_data(
+        code("code")?,
+        code("subcode")?,
+        data,
+    ))
+}
+
+pub(crate) fn notification_to_json(notification: &Notification) -> Value {
+    json!({
+        "code": notification.major_err_code(),
+        "subcode": notification.minor_err_code(),
+        "data": to_hex(&notification.data),
+    })
+}
+
+pub(crate) fn family_from_json(family: &Value, separator: char) -> Result<(AFI, SAFI), Error> {
+    let family = family.as_str().unwrap_or_default();
+    let mut parts = family.splitn(2, separator);
+    let afi = match parts.next() {
+        Some("ipv4") => AFI::IPV4,
+        Some("ipv6") => AFI::IPV6,
+        _ => return Err(invalid(format!("Unsupported family: {}", family))),
+    };
+    let safi = match parts.next() {
+        Some("unicast") => SAFI::Unicast,
+        Some("multicast") => SAFI::Multicast,
+        _ => return Err(invalid(format!("Unsupported family: {}", family))),
+    };
+    Ok((afi, safi))
+}
+
+pub(crate) fn family_to_json(afi: AFI, safi: SAFI, separator: char) -> String {
+    let safi = match safi {
+        SAFI::Unicast => "unicast".to_string(),
+        SAFI::Multicast => "multicast".to_string(),
+        safi => u8::from(safi).to_string(),
+    };
+    format!("{}{}{}", afi.to_string().to_lowercase(), separator, safi)
+}
+
+// JSON messages hold 4-byte ASNs, and IPv6 next hops for IPv4 routes are accepted
+pub(crate) fn json_capabilities() -> Capabilities {
+    let mut capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    capabilities
+        .EXTENDED_NEXT_HOP_ENCODING
+        .insert((AFI::IPV4, SAFI::Unicast), AFI::IPV6);
+    capabilities
+}
+
+pub(crate) fn parse_raw(raw: &str) -> Result<Message, Error> {
+    Message::from_hex(raw, &json_capabilities())
+}
+
+pub(crate) fn parse_prefix(prefix: &Value) -> Result<Prefix, Error> {
+    let prefix = prefix.as_str().unwrap_or_default();
+    let mut parts = prefix.splitn(2, '/');
+    let address = parts.next().map(parse_address);
+    let length = parts.next().and_then(|length| length.parse::<u8>().ok());
+    match (address, length) {
+        (Some(Ok(address)), Some(length)) => Ok(Prefix::from((address, length))),
+        _ => Err(invalid(format!("Invalid prefix: {}", prefix))),
+    }
+}
+
+pub(crate) fn parse_address(address: &str) -> Result<IpAddr, Error> {
+    address
+        .parse()
+        .map_err(|_| invalid(format!("Invalid IP address: {}", address)))
+}
+
+pub(crate) fn as_asn(asn: &Value) -> Result<u32, Error> {
+    asn.as_u64()
+        .and_then(|asn| u32::try_from(asn).ok())
+        .ok_or_else(|| invalid(format!("Invalid ASN: {}", asn)))
+}
+
+pub(crate) fn as_u16(value: &Value) -> Option<u16> {
+    value.as_u64().and_then(|value| u16::try_from(value).ok())
+}
+
+pub(crate) fn get_str<'a>(value: &'a Value, key: &str) -> Result<&'a str, Error> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid(format!("Missing {}", key)))
+}
+
+// A missing key is treated as an empty array
+pub(crate) fn get_array<'a>(value: &'a Value, key: &str) -> Result<&'a [Value], Error> {
+    match value.get(key) {
+        Some(Value::Array(values)) => Ok(values),
+        Some(_) => Err(invalid(format!("Invalid {}", key))),
+        None => Ok(&[]),
+    }
+}
+
+pub(crate) fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
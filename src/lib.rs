@@ -51,16 +51,14 @@
 //!
 //! ## Reading a MRT file containing TABLE_DUMP_V2 messages
 //!
+//! Requires the `mrt` feature.
+//!
 //! ```ignore
 //! use std::fs::File;
-//! use std::io::Cursor;
-//! use std::io::Read;
 //! use std::io::BufReader;
 //! use libflate::gzip::Decoder;
-//! use bgp_rs::{Identifier, PathAttribute, Capabilities};
-//! use mrt_rs::records::tabledump::TABLE_DUMP_V2;
+//! use bgp_rs::RibDecoder;
 //! use mrt_rs::Record;
-//! use mrt_rs::bgp4mp::BGP4MP;
 //!
 //! // Download an update message.
 //! let file = File::open("res/mrt/bview.20100101.0759.gz").unwrap();
@@ -68,39 +66,164 @@
 //! // Decode the GZIP stream.
 //! let mut decoder = Decoder::new(BufReader::new(file)).unwrap();
 //!
+//! // The RibDecoder keeps track of the PEER_INDEX_TABLE.
+//! let mut rib = RibDecoder::new();
+//!
 //! // Keep reading MRT (Header, Record) tuples till the end of the file has been reached.
 //! while let Ok(Some((_, record))) = mrt_rs::read(&mut decoder) {
 //!
-//!     // Extract TABLE_DUMP_V2::RIB_IPV4_UNICAST entries.
-//!     if let Record::TABLE_DUMP_V2(TABLE_DUMP_V2::RIB_IPV4_UNICAST(x)) = record {
-//!
-//!         // Loop over each route for this particular prefix.
-//!         for mut entry in x.entries {
-//!             let length = entry.attributes.len() as u64;
-//!             let mut cursor = Cursor::new(entry.attributes);
+//!     // Extract TABLE_DUMP_V2 entries.
+//!     if let Record::TABLE_DUMP_V2(x) = record {
 //!
-//!             // Parse each PathAttribute in each route.
-//!             while cursor.position() < length {
-//!                 PathAttribute::parse(&mut cursor, &Default::default()).unwrap();
-//!             }
+//!         // Loop over each route, with the peer it was received from.
+//!         for (peer, prefix, attributes) in rib.decode(&x).unwrap() {
+//!             // Do other stuff ...
 //!         }
 //!     }
 //! }
 //! ```
+
+// Emits an event with the `tracing` feature, and nothing otherwise
+macro_rules! trace_event {
+    ($level:ident, $($arguments:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arguments)+);
+    };
+}
+
 /// Contains the OPEN Message implementation
 pub mod open;
 pub use crate::open::*;
 /// Contains the NOTIFICATION Message implementation
 pub mod notification;
 pub use crate::notification::*;
+/// Contains the Autonomous System Number type
+pub mod asn;
+pub use crate::asn::*;
 /// Contains the UPDATE Message implementation
 pub mod update;
 pub use crate::update::*;
+/// Contains the ROUTE-REFRESH Message implementation
+pub mod route_refresh;
+pub use crate::route_refresh::*;
+/// Contains the Hold and Keepalive timers of BGP sessions
+pub mod timers;
+pub use crate::timers::*;
+/// Contains a minimal BGP session implementation
+pub mod session;
+pub use crate::session::*;
+/// Contains the TCP MD5 Signature & GTSM socket options
+#[cfg(all(feature = "sockopt", target_os = "linux"))]
+pub mod sockopt;
+#[cfg(all(feature = "sockopt", target_os = "linux"))]
+pub use crate::sockopt::*;
+/// Contains a radix trie of prefixes for longest-prefix-match lookups
+pub mod trie;
+pub use crate::trie::*;
+/// Contains an in-memory RIB of the routes received from peers
+pub mod rib;
+pub use crate::rib::*;
+/// Contains the human-readable differences between messages & RIBs
+pub mod diff;
+pub use crate::diff::*;
+/// Contains the statistics of routes
+pub mod analysis;
+pub use crate::analysis::*;
+/// Contains the filtering of duplicate announcements
+pub mod dedup;
+pub use crate::dedup::*;
+/// Contains the Graceful Restart handling of the routes of restarting peers
+pub mod graceful_restart;
+pub use crate::graceful_restart::*;
+/// Contains the pacing of outbound UPDATE messages
+pub mod pacer;
+pub use crate::pacer::*;
+/// Contains the community filter expressions
+pub mod filter;
+pub use crate::filter::*;
+/// Contains the generation of aggregate routes
+pub mod aggregate;
+pub use crate::aggregate::*;
+/// Contains the per-peer capabilities of collectors
+pub mod peer_map;
+pub use crate::peer_map::*;
+/// Contains the decoding of OpenBMP message bus messages
+pub mod openbmp;
+pub use crate::openbmp::*;
+/// Contains the anonymization of messages
+pub mod anonymize;
+pub use crate::anonymize::*;
+/// Contains the metrics of readers & sessions
+pub mod metrics;
+pub use crate::metrics::*;
+/// Contains the route policy engine
+#[cfg(feature = "policy")]
+pub mod policy;
+#[cfg(feature = "policy")]
+pub use crate::policy::*;
+/// Contains the Tokio based BGP listener
+#[cfg(feature = "async")]
+pub mod listener;
+#[cfg(feature = "async")]
+pub use crate::listener::*;
+#[cfg(feature = "mrt")]
+pub mod mrt;
+#[cfg(feature = "mrt")]
+pub use crate::mrt::*;
+/// Contains the replay of recorded messages, such as those of MRT archives
+pub mod replay;
+pub use crate::replay::*;
+#[cfg(feature = "pcap")]
+pub mod pcap;
+#[cfg(feature = "pcap")]
+pub use crate::pcap::*;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "fuzz")]
+pub use crate::fuzz::*;
+/// Contains the C bindings
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "ffi")]
+pub use crate::ffi::*;
+/// Contains the Python bindings
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "python")]
+pub use crate::python::*;
+/// Contains the decoding & encoding of messages from & to byte slices
+pub mod slice;
+pub use crate::slice::*;
+/// Contains the parsing of message batches across threads
+#[cfg(feature = "parallel")]
+pub mod parallel;
+#[cfg(feature = "parallel")]
+pub use crate::parallel::*;
+/// Contains the export of routes to CSV & Apache Arrow/Parquet
+#[cfg(feature = "export")]
+pub mod export;
+#[cfg(feature = "export")]
+pub use crate::export::*;
+/// Contains the RIS Live JSON conversion
+#[cfg(feature = "json")]
+pub mod ris_live;
+#[cfg(feature = "json")]
+pub use crate::ris_live::*;
+/// Contains the exabgp JSON conversion
+#[cfg(feature = "json")]
+pub mod exabgp;
+#[cfg(feature = "json")]
+pub use crate::exabgp::*;
 
+mod explain;
+#[cfg(feature = "json")]
+mod json;
 mod util;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
+use std::borrow::BorrowMut;
+use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 use std::io::{Error, ErrorKind, Read, Write};
@@ -111,30 +234,36 @@ const BGP_MAX_MESSAGE_SIZE: usize = 4096;
 
 /// Represents an Address Family Identifier. Currently only IPv4 and IPv6 are supported.
 /// Currently only IPv4, IPv6, and L2VPN are supported.
+/// Other AFIs are carried as `Other`, to be encoded back as they were received.
+/// AFIs are ordered by their value.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[repr(u16)]
 pub enum AFI {
     /// Internet Protocol version 4 (32 bits)
-    IPV4 = 0x01,
+    IPV4,
     /// Internet Protocol version 6 (128 bits)
-    IPV6 = 0x02,
+    IPV6,
     /// L2VPN
-    L2VPN = 0x19,
+    L2VPN,
     /// BGPLS
-    BGPLS = 0x4004,
+    BGPLS,
+    /// An AFI that is not supported
+    Other(u16),
 }
 
 impl AFI {
-    fn empty_buffer(&self) -> Vec<u8> {
+    fn empty_buffer(&self) -> Result<Vec<u8>, Error> {
         match self {
-            AFI::IPV4 => vec![0u8; 4],
-            AFI::IPV6 => vec![0u8; 16],
-            _ => unimplemented!(),
+            AFI::IPV4 => Ok(vec![0u8; 4]),
+            AFI::IPV6 => Ok(vec![0u8; 16]),
+            _ => Err(Error::new(
+                ErrorKind::Other,
+                format!("{} does not hold IP prefixes", self),
+            )),
         }
     }
 }
 
-/// Convert u16 to AFI
+/// Convert u16 to AFI, unsupported values are carried as `AFI::Other`
 /// ```
 /// use std::convert::TryFrom;
 /// use bgp_rs::AFI;
@@ -143,8 +272,9 @@ impl AFI {
 /// let afi = AFI::try_from(val).unwrap();
 /// assert_eq!(afi, AFI::IPV6);
 ///
-/// let bad_afi = AFI::try_from(404);
-/// assert!(bad_afi.is_err());
+/// let other_afi = AFI::try_from(404).unwrap();
+/// assert_eq!(other_afi, AFI::Other(404));
+/// assert_eq!(u16::from(other_afi), 404);
 /// ```
 impl TryFrom<u16> for AFI {
     type Error = Error;
@@ -154,19 +284,42 @@ impl TryFrom<u16> for AFI {
             0x02 => Ok(AFI::IPV6),
             0x19 => Ok(AFI::L2VPN),
             0x4004 => Ok(AFI::BGPLS),
-            _ => Err(Error::new(
-                ErrorKind::Other,
-                format!("Not a supported AFI: '{}'", v),
-            )),
+            _ => Ok(AFI::Other(v)),
+        }
+    }
+}
+
+/// Convert AFI to u16
+impl From<AFI> for u16 {
+    fn from(afi: AFI) -> u16 {
+        match afi {
+            AFI::IPV4 => 0x01,
+            AFI::IPV6 => 0x02,
+            AFI::L2VPN => 0x19,
+            AFI::BGPLS => 0x4004,
+            AFI::Other(v) => v,
         }
     }
 }
 
+impl Ord for AFI {
+    fn cmp(&self, other: &AFI) -> Ordering {
+        u16::from(*self).cmp(&u16::from(*other))
+    }
+}
+
+impl PartialOrd for AFI {
+    fn partial_cmp(&self, other: &AFI) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Display AFI in a human-friendly format
 /// ```
 /// use bgp_rs::AFI;
 /// let afi = AFI::IPV6;
 /// assert_eq!(&afi.to_string(), "IPv6");
+/// assert_eq!(&AFI::Other(404).to_string(), "AFI 404");
 /// ```
 impl Display for AFI {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
@@ -176,43 +329,58 @@ impl Display for AFI {
             IPV6 => "IPv6",
             L2VPN => "L2VPN",
             BGPLS => "BGPLS",
+            Other(v) => return write!(f, "AFI {}", v),
         };
         write!(f, "{}", s)
     }
 }
 
 /// Represents an Subsequent Address Family Identifier. Currently only Unicast and Multicast are
-/// supported.
+/// supported. Other SAFIs are carried as `Other`, to be encoded back as they were received.
+/// SAFIs are ordered by their value.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
-#[repr(u8)]
 pub enum SAFI {
     /// Unicast Forwarding [RFC4760]
-    Unicast = 1,
+    Unicast,
     /// Multicast Forwarding [RFC4760]
-    Multicast = 2,
+    Multicast,
     /// MPLS Labels [RFC3107]
-    Mpls = 4,
-    /// Multicast VPN
-    MulticastVpn = 5,
+    Mpls,
+    /// Multicast VPN [RFC6514]
+    MulticastVpn,
+    /// Multicast VPLS [RFC7117]
+    McastVpls,
+    /// Tunnel [draft-nalawade-kapoor-tunnel-safi]
+    Tunnel,
     /// VPLS [draft-ietf-l2vpn-evpn]
-    Vpls = 65,
+    Vpls,
+    /// Multicast Distribution Tree [RFC6037]
+    Mdt,
     /// EVPN [draft-ietf-l2vpn-evpn]
-    Evpn = 70,
+    Evpn,
     /// BGP LS [RFC7752]
-    BgpLs = 71,
+    BgpLs,
     /// BGP LS VPN [RFC7752]
-    BgpLsVpn = 72,
-    /// RTC [RFC4684]
-    Rtc = 132,
+    BgpLsVpn,
+    /// SR TE Policy [draft-ietf-idr-segment-routing-te-policy]
+    SrTePolicy,
+    /// Mobile User Plane [draft-mpmz-bess-mup-safi]
+    Mup,
     /// MPLS VPN [RFC4364]
-    MplsVpn = 128,
-    /// Flowspec Unicast
-    Flowspec = 133,
+    MplsVpn,
+    /// Multicast MPLS VPN [RFC6513]
+    MulticastMplsVpn,
+    /// RTC [RFC4684]
+    Rtc,
     /// Flowspec Unicast
-    FlowspecVPN = 134,
+    Flowspec,
+    /// Flowspec VPN
+    FlowspecVPN,
+    /// A SAFI that is not supported
+    Other(u8),
 }
 
-/// Convert u8 to SAFI
+/// Convert u8 to SAFI, unsupported values are carried as `SAFI::Other`
 /// ```
 /// use std::convert::TryFrom;
 /// use bgp_rs::SAFI;
@@ -221,8 +389,9 @@ pub enum SAFI {
 /// let safi = SAFI::try_from(val).unwrap();
 /// assert_eq!(safi, SAFI::Unicast);
 ///
-/// let bad_safi = SAFI::try_from(250);
-/// assert!(bad_safi.is_err());
+/// let other_safi = SAFI::try_from(250).unwrap();
+/// assert_eq!(other_safi, SAFI::Other(250));
+/// assert_eq!(u8::from(other_safi), 250);
 /// ```
 impl TryFrom<u8> for SAFI {
     type Error = Error;
@@ -233,36 +402,85 @@ impl TryFrom<u8> for SAFI {
             2 => Ok(SAFI::Multicast),
             4 => Ok(SAFI::Mpls),
             5 => Ok(SAFI::MulticastVpn),
+            8 => Ok(SAFI::McastVpls),
+            64 => Ok(SAFI::Tunnel),
             65 => Ok(SAFI::Vpls),
+            66 => Ok(SAFI::Mdt),
             70 => Ok(SAFI::Evpn),
             71 => Ok(SAFI::BgpLs),
             72 => Ok(SAFI::BgpLsVpn),
+            73 => Ok(SAFI::SrTePolicy),
+            85 => Ok(SAFI::Mup),
             128 => Ok(SAFI::MplsVpn),
+            129 => Ok(SAFI::MulticastMplsVpn),
             132 => Ok(SAFI::Rtc),
             133 => Ok(SAFI::Flowspec),
             134 => Ok(SAFI::FlowspecVPN),
-            _ => Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Not a supported SAFI: '{}'", v),
-            )),
+            _ => Ok(SAFI::Other(v)),
         }
     }
 }
 
+/// Convert SAFI to u8
+impl From<SAFI> for u8 {
+    fn from(safi: SAFI) -> u8 {
+        match safi {
+            SAFI::Unicast => 1,
+            SAFI::Multicast => 2,
+            SAFI::Mpls => 4,
+            SAFI::MulticastVpn => 5,
+            SAFI::McastVpls => 8,
+            SAFI::Tunnel => 64,
+            SAFI::Vpls => 65,
+            SAFI::Mdt => 66,
+            SAFI::Evpn => 70,
+            SAFI::BgpLs => 71,
+            SAFI::BgpLsVpn => 72,
+            SAFI::SrTePolicy => 73,
+            SAFI::Mup => 85,
+            SAFI::MplsVpn => 128,
+            SAFI::MulticastMplsVpn => 129,
+            SAFI::Rtc => 132,
+            SAFI::Flowspec => 133,
+            SAFI::FlowspecVPN => 134,
+            SAFI::Other(v) => v,
+        }
+    }
+}
+
+impl Ord for SAFI {
+    fn cmp(&self, other: &SAFI) -> Ordering {
+        u8::from(*self).cmp(&u8::from(*other))
+    }
+}
+
+impl PartialOrd for SAFI {
+    fn partial_cmp(&self, other: &SAFI) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Display SAFI in a human-friendly format
 /// ```
 /// use bgp_rs::SAFI;
 ///
 /// assert_eq!(&(SAFI::Unicast).to_string(), "Unicast");
 /// assert_eq!(&(SAFI::Mpls).to_string(), "MPLS");
+/// assert_eq!(&(SAFI::McastVpls).to_string(), "Multicast VPLS");
+/// assert_eq!(&(SAFI::Tunnel).to_string(), "Tunnel");
 /// assert_eq!(&(SAFI::Vpls).to_string(), "VPLS");
+/// assert_eq!(&(SAFI::Mdt).to_string(), "MDT");
 /// assert_eq!(&(SAFI::Evpn).to_string(), "EVPN");
 /// assert_eq!(&(SAFI::BgpLs).to_string(), "BGPLS");
 /// assert_eq!(&(SAFI::BgpLsVpn).to_string(), "BGPLSVPN");
-/// assert_eq!(&(SAFI::Rtc).to_string(), "RTC");
+/// assert_eq!(&(SAFI::SrTePolicy).to_string(), "SR TE Policy");
+/// assert_eq!(&(SAFI::Mup).to_string(), "MUP");
 /// assert_eq!(&(SAFI::MplsVpn).to_string(), "MPLS VPN");
+/// assert_eq!(&(SAFI::MulticastMplsVpn).to_string(), "Multicast MPLS VPN");
+/// assert_eq!(&(SAFI::Rtc).to_string(), "RTC");
 /// assert_eq!(&(SAFI::Flowspec).to_string(), "Flowspec");
 /// assert_eq!(&(SAFI::FlowspecVPN).to_string(), "Flowspec VPN");
+/// assert_eq!(&(SAFI::Other(250)).to_string(), "SAFI 250");
 /// ```
 impl Display for SAFI {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
@@ -272,21 +490,107 @@ impl Display for SAFI {
             Multicast => "Multicast",
             Mpls => "MPLS",
             MulticastVpn => "Multicast VPN",
+            McastVpls => "Multicast VPLS",
+            Tunnel => "Tunnel",
             Vpls => "VPLS",
+            Mdt => "MDT",
             Evpn => "EVPN",
             BgpLs => "BGPLS",
             BgpLsVpn => "BGPLSVPN",
-            Rtc => "RTC",
+            SrTePolicy => "SR TE Policy",
+            Mup => "MUP",
             MplsVpn => "MPLS VPN",
+            MulticastMplsVpn => "Multicast MPLS VPN",
+            Rtc => "RTC",
             Flowspec => "Flowspec",
             FlowspecVPN => "Flowspec VPN",
+            Other(v) => return write!(f, "SAFI {}", v),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Represents the type of a BGP message, as found in its header.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MessageType {
+    /// OPEN message [RFC4271]
+    Open,
+    /// UPDATE message [RFC4271]
+    Update,
+    /// NOTIFICATION message [RFC4271]
+    Notification,
+    /// KEEPALIVE message [RFC4271]
+    KeepAlive,
+    /// ROUTE-REFRESH message [RFC2918]
+    RouteRefresh,
+    /// CAPABILITY message [draft-ietf-idr-dynamic-cap], not supported
+    Capability,
+    /// A message type that is not supported
+    Unknown(u8),
+}
+
+/// Convert u8 to MessageType
+/// ```
+/// use bgp_rs::MessageType;
+///
+/// assert_eq!(MessageType::from(2), MessageType::Update);
+/// assert_eq!(MessageType::from(10), MessageType::Unknown(10));
+/// assert_eq!(u8::from(MessageType::RouteRefresh), 5);
+/// ```
+impl From<u8> for MessageType {
+    fn from(v: u8) -> MessageType {
+        match v {
+            1 => MessageType::Open,
+            2 => MessageType::Update,
+            3 => MessageType::Notification,
+            4 => MessageType::KeepAlive,
+            5 => MessageType::RouteRefresh,
+            6 => MessageType::Capability,
+            _ => MessageType::Unknown(v),
+        }
+    }
+}
+
+/// Convert MessageType to u8
+impl From<MessageType> for u8 {
+    fn from(message_type: MessageType) -> u8 {
+        match message_type {
+            MessageType::Open => 1,
+            MessageType::Update => 2,
+            MessageType::Notification => 3,
+            MessageType::KeepAlive => 4,
+            MessageType::RouteRefresh => 5,
+            MessageType::Capability => 6,
+            MessageType::Unknown(v) => v,
+        }
+    }
+}
+
+/// Display MessageType in a human-friendly format
+/// ```
+/// use bgp_rs::MessageType;
+///
+/// assert_eq!(&MessageType::RouteRefresh.to_string(), "ROUTE-REFRESH");
+/// assert_eq!(&MessageType::Unknown(10).to_string(), "message type 10");
+/// ```
+impl Display for MessageType {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        use MessageType::*;
+        let s = match self {
+            Open => "OPEN",
+            Update => "UPDATE",
+            Notification => "NOTIFICATION",
+            KeepAlive => "KEEPALIVE",
+            RouteRefresh => "ROUTE-REFRESH",
+            Capability => "CAPABILITY",
+            Unknown(v) => return write!(f, "message type {}", v),
         };
         write!(f, "{}", s)
     }
 }
 
 /// Represents the BGP header accompanying every BGP message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Header {
     /// Predefined marker, must be set to all ones.
     pub marker: [u8; 16],
@@ -295,17 +599,36 @@ pub struct Header {
     pub length: u16,
 
     /// Indicates the type of message that follows the header.
-    pub record_type: u8,
+    pub record_type: MessageType,
 }
 
 impl Header {
+    /// Create a header with a marker of all ones, for a message of the given total length
+    /// (including the header) in bytes.
+    ///
+    /// ```
+    /// use bgp_rs::{Header, MessageType};
+    ///
+    /// let header = Header::new(MessageType::KeepAlive, 19);
+    /// let mut data = vec![];
+    /// header.encode(&mut data).unwrap();
+    /// assert_eq!(data[16..], [0, 19, 4]);
+    /// ```
+    pub fn new(record_type: MessageType, length: u16) -> Header {
+        Header {
+            marker: [0xff; 16],
+            length,
+            record_type,
+        }
+    }
+
     /// parse
     pub fn parse(stream: &mut impl Read) -> Result<Header, Error> {
         let mut marker = [0u8; 16];
         stream.read_exact(&mut marker)?;
 
         let length = stream.read_u16::<BigEndian>()?;
-        let record_type = stream.read_u8()?;
+        let record_type = MessageType::from(stream.read_u8()?);
 
         Ok(Header {
             marker,
@@ -318,12 +641,12 @@ impl Header {
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
         buf.write_all(&self.marker)?;
         buf.write_u16::<BigEndian>(self.length)?;
-        buf.write_u8(self.record_type)
+        buf.write_u8(u8::from(self.record_type))
     }
 }
 
 /// Represents a single BGP message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Message {
     /// Represent a BGP OPEN message.
     Open(Open),
@@ -342,10 +665,31 @@ pub enum Message {
 }
 
 impl Message {
-    fn encode_noheader(&self, buf: &mut impl Write) -> Result<(), Error> {
+    /// The type of this message, as encoded in its header.
+    ///
+    /// ```
+    /// use bgp_rs::{Message, MessageType};
+    ///
+    /// assert_eq!(Message::KeepAlive.message_type(), MessageType::KeepAlive);
+    /// ```
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Message::Open(_) => MessageType::Open,
+            Message::Update(_) => MessageType::Update,
+            Message::Notification(_) => MessageType::Notification,
+            Message::KeepAlive => MessageType::KeepAlive,
+            Message::RouteRefresh(_) => MessageType::RouteRefresh,
+        }
+    }
+
+    fn encode_noheader(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<(), Error> {
         match self {
             Message::Open(open) => open.encode(buf),
-            Message::Update(update) => update.encode(buf),
+            Message::Update(update) => update.encode_into(buf, capabilities).map(|_| ()),
             Message::Notification(notification) => notification.encode(buf),
             Message::KeepAlive => Ok(()),
             Message::RouteRefresh(refresh) => refresh.encode(buf),
@@ -354,59 +698,173 @@ impl Message {
 
     /// Writes message into the stream, including the appropriate header.
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
-        let mut message_buf: Vec<u8> = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE); // Start with minimum size
-        self.encode_noheader(&mut message_buf)?;
-        let message_length = message_buf.len();
+        let mut bytes = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+        self.encode_into(&mut bytes, None)?;
+        buf.write_all(&bytes)
+    }
+
+    /// Writes message into the stream, including the appropriate header.
+    /// UPDATE messages are encoded following the capabilities negotiated with the peer.
+    pub fn encode_with_capabilities(
+        &self,
+        buf: &mut impl Write,
+        capabilities: &Capabilities,
+    ) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+        self.encode_into(&mut bytes, Some(capabilities))?;
+        buf.write_all(&bytes)
+    }
+
+    /// Appends the message, including its header, to a buffer and returns the number of bytes
+    /// written.
+    ///
+    /// Encodes like `encode`, or like `encode_with_capabilities` when capabilities are given.
+    /// High-rate senders can reuse the buffer (after clearing it) to serialize messages without
+    /// allocating for each of them. Nothing is appended when encoding fails.
+    ///
+    /// ```
+    /// use bgp_rs::Message;
+    ///
+    /// let mut buf = Vec::with_capacity(4096);
+    /// assert_eq!(Message::KeepAlive.encode_into(&mut buf, None).unwrap(), 19);
+    /// assert_eq!(Message::KeepAlive.encode_into(&mut buf, None).unwrap(), 19);
+    /// assert_eq!(buf.len(), 38);
+    /// ```
+    pub fn encode_into(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<usize, Error> {
+        let start = buf.len();
+        self.encode_inner(buf, capabilities)
+            .map(|_| buf.len() - start)
+            .map_err(|e| {
+                buf.truncate(start);
+                e
+            })
+    }
+
+    /// Parses a single message from a hex dump, such as the output of "debug bgp packet"
+    /// commands on routers. Whitespace, ':', '-' and '.' separators and "0x" prefixes are
+    /// ignored. The dump must hold exactly one message, including its header.
+    ///
+    /// ```
+    /// use bgp_rs::{Capabilities, Message};
+    ///
+    /// let hex = "FFFFFFFF FFFFFFFF FFFFFFFF FFFFFFFF 0013 04";
+    /// let message = Message::from_hex(hex, &Capabilities::default()).unwrap();
+    /// assert_eq!(message.to_hex().unwrap(), hex.replace(" ", ""));
+    /// ```
+    pub fn from_hex(hex: &str, capabilities: &Capabilities) -> Result<Message, Error> {
+        let bytes = util::from_hex(hex)?;
+        let length = bytes.len() as u64;
+        let mut reader = Reader::with_capabilities(std::io::Cursor::new(bytes), capabilities);
+        let (_header, message) = reader.read()?;
+        if reader.stream.position() != length {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Hex dump holds {} bytes after the message",
+                    length - reader.stream.position()
+                ),
+            ));
+        }
+        Ok(message)
+    }
+
+    /// Encodes the message, including its header, as an uppercase hex string.
+    pub fn to_hex(&self) -> Result<String, Error> {
+        let mut buf = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+        self.encode(&mut buf)?;
+        Ok(util::to_hex(&buf))
+    }
+
+    fn encode_inner(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<(), Error> {
+        // The header is written once the length of the message is known
+        let start = buf.len();
+        buf.resize(start + BGP_MIN_MESSAGE_SIZE, 0);
+        self.encode_noheader(buf, capabilities)?;
+        let message_length = buf.len() - start - BGP_MIN_MESSAGE_SIZE;
         if (message_length + BGP_MIN_MESSAGE_SIZE) > BGP_MAX_MESSAGE_SIZE {
             return Err(Error::new(
                 ErrorKind::Other,
                 format!("Cannot encode message of length {}", message_length),
             ));
         }
-        let header = Header {
-            marker: [0xff; 16],
-            length: (message_length + BGP_MIN_MESSAGE_SIZE) as u16,
-            record_type: match self {
-                Message::Open(_) => 1,
-                Message::Update(_) => 2,
-                Message::Notification(_) => 3,
-                Message::KeepAlive => 4,
-                Message::RouteRefresh(_) => 5,
-            },
-        };
-        header.encode(buf)?;
-        buf.write_all(&message_buf)
+        let header = Header::new(
+            self.message_type(),
+            (message_length + BGP_MIN_MESSAGE_SIZE) as u16,
+        );
+        header.encode(&mut &mut buf[start..start + BGP_MIN_MESSAGE_SIZE])
     }
 }
 
-/// Represents a BGP Route Refresh message.
+/// The settings of the parser, which are not negotiated with peers (unlike `Capabilities`).
+///
+/// Limits protect against untrusted peers that announce excessive amounts of data, exceeding
+/// a limit results in an error. Stricter checks of the messages can be enabled as well.
 #[derive(Clone, Debug)]
-pub struct RouteRefresh {
-    /// Address Family being requested
-    pub afi: AFI,
-    /// Subsequent Address Family being requested
-    pub safi: SAFI,
-    /// This can be a subtype or RESERVED=0 for older senders
-    pub subtype: u8,
-}
+pub struct ParseOptions {
+    /// The maximum length of a message (including the header) in bytes. Any length is
+    /// accepted by default, set it to 4096 to only accept the messages of RFC4271.
+    pub max_message_size: usize,
 
-impl RouteRefresh {
-    fn parse(stream: &mut impl Read) -> Result<RouteRefresh, Error> {
-        let afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
-        let subtype = stream.read_u8()?;
-        let safi = SAFI::try_from(stream.read_u8()?)?;
+    /// The maximum number of path attributes in an UPDATE message.
+    pub max_attributes: usize,
 
-        Ok(RouteRefresh { afi, safi, subtype })
-    }
+    /// The maximum number of NLRI in a section (withdrawn, announced or an MP_(UN)REACH_NLRI)
+    /// and in an UPDATE message as a whole.
+    pub max_nlri: usize,
 
-    /// Encode RouteRefresh to bytes
-    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
-        buf.write_u16::<BigEndian>(self.afi as u16)?;
-        buf.write_u8(self.subtype)?;
-        buf.write_u8(self.safi as u8)
+    /// The maximum number of segments in an AS_PATH or AS4_PATH.
+    pub max_as_path_segments: usize,
+
+    /// Reject path attributes of which the Optional, Transitive or Partial flags don't match
+    /// the attribute type, such as an ORIGIN that is not well-known (Attribute Flags Error).
+    /// `Update::parse_lossy` skips these attributes instead.
+    pub strict_attribute_flags: bool,
+
+    /// Reject messages of which the header is malformed (Message Header Error): a marker
+    /// that is not all ones (Connection Not Synchronized), a length below the minimum of the
+    /// message type, or a length exceeding the content of the message (Bad Message Length).
+    /// Otherwise the bytes following the content of a message, up to its length, are skipped.
+    pub strict_header: bool,
+
+    /// Retain the original encoding of path attributes when parsing UPDATE messages,
+    /// so that encoding them again produces identical bytes.
+    pub preserve_wire_format: bool,
+
+    /// Decoders of (e.g. vendor-specific) path attribute types registered by the user.
+    pub attribute_decoders: AttributeDecoders,
+
+    /// Decoders of (e.g. experimental) NLRI families registered by the user.
+    pub nlri_decoders: NLRIDecoders,
+}
+
+impl Default for ParseOptions {
+    /// No limit is enforced and no stricter check is made.
+    fn default() -> Self {
+        ParseOptions {
+            max_message_size: usize::from(std::u16::MAX),
+            max_attributes: std::usize::MAX,
+            max_nlri: std::usize::MAX,
+            max_as_path_segments: std::usize::MAX,
+            strict_attribute_flags: false,
+            strict_header: false,
+            preserve_wire_format: false,
+            attribute_decoders: AttributeDecoders::default(),
+            nlri_decoders: NLRIDecoders::default(),
+        }
     }
 }
 
+/// The name of `ParseOptions` when it only held the limits enforced by a `Reader`.
+pub type ReaderOptions = ParseOptions;
+
 /// An abstract way of getting a reference to a Capabilities struct.
 /// This is used in Reader to allow use of either an owned Capabilites or a reference to one.
 pub trait CapabilitiesRef {
@@ -418,11 +876,21 @@ impl CapabilitiesRef for Capabilities {
         self
     }
 }
-impl<'a> CapabilitiesRef for &'a Capabilities {
+impl CapabilitiesRef for &Capabilities {
     fn get_ref(&self) -> &Capabilities {
         self
     }
 }
+impl CapabilitiesRef for NegotiatedCapabilities {
+    fn get_ref(&self) -> &Capabilities {
+        &self.capabilities
+    }
+}
+impl CapabilitiesRef for &mut NegotiatedCapabilities {
+    fn get_ref(&self) -> &Capabilities {
+        &self.capabilities
+    }
+}
 
 /// The BGPReader can read BGP messages from a BGP-formatted stream.
 pub struct Reader<T, C>
@@ -435,6 +903,13 @@ where
 
     /// Capability parameters that distinguish how BGP messages should be parsed.
     pub capabilities: C,
+
+    /// The settings of the parser, such as the limits enforced while reading messages.
+    pub options: ParseOptions,
+
+    // Holds the message being parsed, reused across reads to avoid allocating for each
+    // message. Starts out empty and grows to the largest message read.
+    buffer: Vec<u8>,
 }
 
 impl<T, C> Reader<T, C>
@@ -442,6 +917,19 @@ where
     T: Read,
     C: CapabilitiesRef,
 {
+    ///
+    /// Constructs a BGPReader parsing messages with the given capabilities, such as
+    /// `Capabilities`, a reference to them or `NegotiatedCapabilities`.
+    ///
+    pub fn with_capabilities(stream: T, capabilities: C) -> Self {
+        Reader {
+            stream,
+            capabilities,
+            options: ParseOptions::default(),
+            buffer: vec![],
+        }
+    }
+
     ///
     /// Reads the next BGP message in the stream.
     ///
@@ -456,6 +944,71 @@ where
     /// This function does not make use of unsafe code.
     ///
     pub fn read(&mut self) -> Result<(Header, Message), Error> {
+        let (header, message, _warnings) = self.read_inner(false)?;
+        Ok((header, message))
+    }
+
+    ///
+    /// Reads the next BGP message in the stream, continuing on recoverable problems
+    /// in UPDATE messages. See `Update::parse_lossy` for the problems that are reported
+    /// as warnings.
+    ///
+    pub fn read_lossy(&mut self) -> Result<(Header, Message, Vec<ParseWarning>), Error> {
+        self.read_inner(true)
+    }
+
+    ///
+    /// Reads the next BGP message in the stream like `read`, resynchronizing first when the
+    /// stream does not continue with a plausible header, such as after a corrupted part of a
+    /// capture. The stream is then scanned byte by byte for the next marker of all ones
+    /// followed by a length & message type that pass the `ParseOptions::strict_header`
+    /// checks, and the number of bytes skipped to reach it is returned along with the message.
+    ///
+    /// Only the header is checked while scanning: a message that is malformed past its header
+    /// is still returned as an error, and the next call resumes scanning after it. Scanning
+    /// reads single bytes, the stream should be buffered (e.g. with `BufReader`).
+    ///
+    /// ```
+    /// use bgp_rs::{Message, Reader};
+    ///
+    /// let mut data = vec![0xff; 7];
+    /// Message::KeepAlive.encode(&mut data).unwrap();
+    /// let mut reader = Reader::new(std::io::Cursor::new(data));
+    /// let (_, message, skipped) = reader.read_resync().unwrap();
+    /// assert_eq!(message, Message::KeepAlive);
+    /// assert_eq!(skipped, 7);
+    /// ```
+    ///
+    pub fn read_resync(&mut self) -> Result<(Header, Message, usize), Error> {
+        let mut window = [0u8; BGP_MIN_MESSAGE_SIZE];
+        self.stream.read_exact(&mut window)?;
+        let max_message_size = self.options.max_message_size;
+        let mut skipped = 0;
+        let header = loop {
+            let header = Header::parse(&mut &window[..])?;
+            let known_type = match header.record_type {
+                MessageType::Capability | MessageType::Unknown(_) => false,
+                _ => true,
+            };
+            if known_type
+                && validate_header(&header).is_ok()
+                && usize::from(header.length) <= max_message_size
+            {
+                break header;
+            }
+            window.rotate_left(1);
+            self.stream
+                .read_exact(&mut window[BGP_MIN_MESSAGE_SIZE - 1..])?;
+            skipped += 1;
+        };
+        if skipped > 0 {
+            trace_event!(warn, skipped, "Resynchronized with the stream");
+        }
+        let (header, message, _warnings) = self.read_message(header, false)?;
+        Ok((header, message, skipped))
+    }
+
+    fn read_inner(&mut self, lossy: bool) -> Result<(Header, Message, Vec<ParseWarning>), Error> {
         // Parse the header.
         let mut marker: [u8; 16] = [0; 16];
         self.stream.read_exact(&mut marker)?;
@@ -463,34 +1016,193 @@ where
         let header = Header {
             marker,
             length: self.stream.read_u16::<BigEndian>()?,
-            record_type: self.stream.read_u8()?,
+            record_type: MessageType::from(self.stream.read_u8()?),
         };
+        self.read_message(header, lossy)
+    }
 
-        match header.record_type {
-            1 => Ok((header, Message::Open(Open::parse(&mut self.stream)?))),
-            2 => {
-                let attribute = Message::Update(Update::parse(
-                    &header,
-                    &mut self.stream,
-                    self.capabilities.get_ref(),
-                )?);
-                Ok((header, attribute))
-            }
-            3 => {
-                let attribute =
-                    Message::Notification(Notification::parse(&header, &mut self.stream)?);
-                Ok((header, attribute))
-            }
-            4 => Ok((header, Message::KeepAlive)),
-            5 => Ok((
-                header,
-                Message::RouteRefresh(RouteRefresh::parse(&mut self.stream)?),
-            )),
-            _ => Err(Error::new(
-                ErrorKind::Other,
-                "Unknown BGP message type found in BGPHeader",
-            )),
+    // Reads & parses the message following a header
+    fn read_message(
+        &mut self,
+        header: Header,
+        lossy: bool,
+    ) -> Result<(Header, Message, Vec<ParseWarning>), Error> {
+        let max_message_size = self.options.max_message_size;
+        if usize::from(header.length) > max_message_size {
+            return Err(notification_error(
+                NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+                header.length.to_be_bytes().to_vec(),
+                format!(
+                    "Message length {} exceeds the maximum of {}",
+                    header.length, max_message_size
+                ),
+            ));
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "message",
+            message_type = %header.record_type,
+            length = header.length
+        )
+        .entered();
+
+        let strict_header = self.options.strict_header;
+        if strict_header {
+            validate_header(&header)?;
+        }
+
+        // The message is read into the buffer reused across reads, and parsed from there
+        let length = usize::from(header.length).saturating_sub(BGP_MIN_MESSAGE_SIZE);
+        self.buffer.resize(length, 0);
+        self.stream.read_exact(&mut self.buffer)?;
+        let mut cursor = std::io::Cursor::new(&self.buffer[..]);
+        let (message, warnings) = parse_message(
+            &header,
+            &mut cursor,
+            self.capabilities.get_ref(),
+            &self.options,
+            lossy,
+        )?;
+        let remaining = length as u64 - cursor.position();
+        if strict_header && remaining > 0 {
+            return Err(notification_error(
+                NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+                header.length.to_be_bytes().to_vec(),
+                format!(
+                    "Message length {} exceeds the message by {} bytes",
+                    header.length, remaining
+                ),
+            ));
+        }
+        Ok((header, message, warnings))
+    }
+}
+
+// Parses the message following a header
+fn parse_message(
+    header: &Header,
+    stream: &mut std::io::Cursor<&[u8]>,
+    capabilities: &Capabilities,
+    options: &ParseOptions,
+    lossy: bool,
+) -> Result<(Message, Vec<ParseWarning>), Error> {
+    let mut warnings = vec![];
+    let message = match header.record_type {
+        MessageType::Open => {
+            let open = Open::parse(stream).map_err(|e| {
+                with_notification(
+                    e,
+                    NotificationCode::OpenMessage(OpenMessageSubcode::Unspecific),
+                    vec![],
+                )
+            })?;
+            Message::Open(open)
+        }
+        MessageType::Update => {
+            // UPDATE messages are parsed from the buffer, taking all of it
+            let body = &stream.get_ref()[stream.position() as usize..];
+            stream.set_position(stream.get_ref().len() as u64);
+            let update = Update::parse_body(body, capabilities, options, lossy).map(
+                |(update, update_warnings)| {
+                    warnings = update_warnings;
+                    update
+                },
+            );
+            let update = update.map_err(|e| {
+                let subcode = match e.kind() {
+                    // The lengths in the message are inconsistent
+                    ErrorKind::UnexpectedEof => UpdateMessageSubcode::MalformedAttributeList,
+                    _ => UpdateMessageSubcode::Unspecific,
+                };
+                with_notification(e, NotificationCode::UpdateMessage(subcode), vec![])
+            })?;
+            Message::Update(update)
+        }
+        MessageType::Notification => Message::Notification(Notification::parse(header, stream)?),
+        MessageType::KeepAlive => Message::KeepAlive,
+        MessageType::RouteRefresh => Message::RouteRefresh(RouteRefresh::parse(header, stream)?),
+        MessageType::Capability | MessageType::Unknown(_) => {
+            return Err(notification_error(
+                NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageType),
+                vec![u8::from(header.record_type)],
+                "Unknown BGP message type found in BGPHeader".to_string(),
+            ));
+        }
+    };
+    Ok((message, warnings))
+}
+
+// Checks the marker and the length of a header (ParseOptions::strict_header)
+fn validate_header(header: &Header) -> Result<(), Error> {
+    if header.marker != [0xff; 16] {
+        return Err(notification_error(
+            NotificationCode::MessageHeader(MessageHeaderSubcode::ConnectionNotSynchronized),
+            vec![],
+            "Message marker is not all ones".to_string(),
+        ));
+    }
+    // The minimum length of each message type, KEEPALIVE and ROUTE-REFRESH have a fixed length
+    let (min_length, fixed) = match header.record_type {
+        MessageType::Open => (29, false),
+        MessageType::Update => (23, false),
+        MessageType::Notification => (21, false),
+        MessageType::KeepAlive => (BGP_MIN_MESSAGE_SIZE, true),
+        MessageType::RouteRefresh => (23, true),
+        MessageType::Capability | MessageType::Unknown(_) => (BGP_MIN_MESSAGE_SIZE, false),
+    };
+    let length = usize::from(header.length);
+    if length < min_length || (fixed && length != min_length) {
+        return Err(notification_error(
+            NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+            header.length.to_be_bytes().to_vec(),
+            format!(
+                "Message length {} is invalid for {}",
+                header.length, header.record_type
+            ),
+        ));
+    }
+    Ok(())
+}
+
+impl<T, C> Reader<T, C>
+where
+    T: Read,
+    C: CapabilitiesRef + BorrowMut<NegotiatedCapabilities>,
+{
+    ///
+    /// Merges the parameters of an OPEN message into the capabilities of this reader,
+    /// see `NegotiatedCapabilities::apply_open`.
+    ///
+    pub fn apply_open(&mut self, open: &Open) -> Result<(), Error> {
+        self.capabilities.borrow_mut().apply_open(open)
+    }
+
+    ///
+    /// Reads the next BGP message in the stream, applying any OPEN message to the capabilities
+    /// so that the UPDATE messages that follow are parsed with the negotiated session parameters.
+    ///
+    /// ```
+    /// use bgp_rs::{Message, NegotiatedCapabilities, Reader};
+    /// # use bgp_rs::{Open, PeerConfig};
+    /// # let mut capture = vec![];
+    /// # let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+    /// # Message::Open(Open::from_config(&config)).encode(&mut capture).unwrap();
+    /// # config.local_asn = 65001;
+    /// # Message::Open(Open::from_config(&config)).encode(&mut capture).unwrap();
+    /// # let stream = std::io::Cursor::new(capture);
+    ///
+    /// let mut reader = Reader::with_capabilities(stream, NegotiatedCapabilities::default());
+    /// reader.read_negotiated().unwrap();
+    /// reader.read_negotiated().unwrap();
+    /// assert!(reader.capabilities.capabilities.FOUR_OCTET_ASN_SUPPORT);
+    /// ```
+    pub fn read_negotiated(&mut self) -> Result<(Header, Message), Error> {
+        let (header, message) = self.read()?;
+        if let Message::Open(open) = &message {
+            self.apply_open(open)?;
         }
+        Ok((header, message))
     }
 }
 
@@ -516,9 +1228,42 @@ where
     where
         T: Read,
     {
-        Reader::<T, Capabilities> {
-            stream,
-            capabilities: Default::default(),
+        Reader::with_capabilities(stream, Capabilities::default())
+    }
+
+    ///
+    /// Constructs a BGPReader with default parameters, parsing with the given options (e.g.
+    /// enforcing limits).
+    ///
+    pub fn with_options(stream: T, options: ParseOptions) -> Self {
+        Reader {
+            options,
+            ..Reader::new(stream)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_reuses_buffer() {
+        let mut data = vec![];
+        let update = Message::Update(Update::new(
+            vec![],
+            vec![PathAttribute::ORIGIN(Origin::IGP)],
+            vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        ));
+        update.encode(&mut data).unwrap();
+        Message::KeepAlive.encode(&mut data).unwrap();
+        update.encode(&mut data).unwrap();
+
+        let mut reader = Reader::new(std::io::Cursor::new(data));
+        assert_eq!(reader.read().unwrap().1, update);
+        let buffer = reader.buffer.as_ptr();
+        assert_eq!(reader.read().unwrap().1, Message::KeepAlive);
+        assert_eq!(reader.read().unwrap().1, update);
+        assert_eq!(reader.buffer.as_ptr(), buffer);
+    }
+}
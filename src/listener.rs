@@ -0,0 +1,323 @@
+//! The `listener` mod provides Tokio based BGP sessions
+//! - Accepting (or initiating) TCP connections and driving the OPEN / KEEPALIVE handshake
+//! - Sending KEEPALIVE messages in the background
+//! - Hold Timer expiry, resulting in the Hold Timer Expired Notification
+//!
+//! The received UPDATE messages are yielded by `BgpPeer::next_update`.
+//!
+
+use std::io::{Cursor, Error, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+use crate::*;
+
+// The Hold Time used while the OPEN messages are exchanged, as suggested by RFC4271
+const HANDSHAKE_HOLD_TIME: Duration = Duration::from_secs(240);
+
+/// Accepts BGP sessions on a TCP socket.
+pub struct BgpListener {
+    listener: TcpListener,
+    config: PeerConfig,
+}
+
+impl BgpListener {
+    /// Listen on the given address, accepting peers with the given configuration.
+    pub async fn bind(addr: impl ToSocketAddrs, config: PeerConfig) -> Result<BgpListener, Error> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(BgpListener { listener, config })
+    }
+
+    /// The address this listener is bound to.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.listener.local_addr()
+    }
+
+    /// Sets the socket options (such as a TCP MD5 Signature key) of the connections accepted
+    /// from a peer.
+    #[cfg(all(feature = "sockopt", target_os = "linux"))]
+    pub fn set_socket_options(
+        &self,
+        peer: std::net::IpAddr,
+        options: &SocketOptions,
+    ) -> Result<(), Error> {
+        options.apply(&self.listener, peer)
+    }
+
+    /// Accepts the next TCP connection and establishes the BGP session over it.
+    pub async fn accept(&self) -> Result<(BgpPeer, SocketAddr), Error> {
+        let (stream, addr) = self.listener.accept().await?;
+        let peer = BgpPeer::establish(stream, self.config.clone()).await?;
+        Ok((peer, addr))
+    }
+}
+
+/// An established BGP session with a peer, driven by background tasks.
+pub struct BgpPeer {
+    peer_open: Open,
+    capabilities: NegotiatedCapabilities,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    updates: mpsc::Receiver<Result<Update, Error>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl BgpPeer {
+    /// Connects to the given address and establishes the BGP session.
+    pub async fn connect(addr: impl ToSocketAddrs, config: PeerConfig) -> Result<BgpPeer, Error> {
+        let stream = TcpStream::connect(addr).await?;
+        BgpPeer::establish(stream, config).await
+    }
+
+    /// Connects to a peer with the given socket options (such as a TCP MD5 Signature key) and
+    /// establishes the BGP session.
+    #[cfg(all(feature = "sockopt", target_os = "linux"))]
+    pub async fn connect_with_options(
+        addr: SocketAddr,
+        options: &SocketOptions,
+        config: PeerConfig,
+    ) -> Result<BgpPeer, Error> {
+        let socket = match addr {
+            SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+        };
+        options.apply(&socket, addr.ip())?;
+        let stream = socket.connect(addr).await?;
+        BgpPeer::establish(stream, config).await
+    }
+
+    /// Performs the OPEN / KEEPALIVE handshake over the given stream.
+    ///
+    /// When the peer OPEN is not acceptable, the corresponding Notification is sent
+    /// to the peer and the error is returned.
+    pub async fn establish(stream: TcpStream, config: PeerConfig) -> Result<BgpPeer, Error> {
+        let (mut reader, writer) = stream.into_split();
+        let writer = Arc::new(Mutex::new(writer));
+        let default_capabilities = Capabilities::default();
+
+        let local_open = Open::from_config(&config);
+        write_message(
+            &writer,
+            &Message::Open(local_open.clone()),
+            &default_capabilities,
+        )
+        .await?;
+        let peer_open = match recv_handshake(&mut reader, &default_capabilities).await {
+            Ok(Message::Open(open)) => open,
+            Ok(message) => {
+                let error = unexpected_message(
+                    FiniteStateMachineSubcode::UnexpectedMessageInOpenSent,
+                    &message,
+                );
+                return Err(abort(&writer, error).await);
+            }
+            Err(e) => return Err(abort(&writer, e).await),
+        };
+        let capabilities = match validate_open(&config, &peer_open)
+            .and_then(|_| NegotiatedCapabilities::from_opens(&local_open, &peer_open))
+        {
+            Ok(capabilities) => capabilities,
+            Err(e) => return Err(abort(&writer, e).await),
+        };
+
+        write_message(&writer, &Message::KeepAlive, &capabilities.capabilities).await?;
+        match recv_handshake(&mut reader, &capabilities.capabilities).await {
+            Ok(Message::KeepAlive) => (),
+            Ok(message) => {
+                let error = unexpected_message(
+                    FiniteStateMachineSubcode::UnexpectedMessageInOpenConfirm,
+                    &message,
+                );
+                return Err(abort(&writer, error).await);
+            }
+            Err(e) => return Err(abort(&writer, e).await),
+        }
+
+        let (sender, updates) = mpsc::channel(64);
+        let mut tasks = vec![tokio::spawn(receive_updates(
+            reader,
+            writer.clone(),
+            capabilities.clone(),
+            sender,
+        ))];
+        if let Some(interval) = capabilities.keepalive_interval() {
+            tasks.push(tokio::spawn(send_keepalives(
+                writer.clone(),
+                Duration::from_secs(u64::from(interval)),
+            )));
+        }
+
+        Ok(BgpPeer {
+            peer_open,
+            capabilities,
+            writer,
+            updates,
+            tasks,
+        })
+    }
+
+    /// The OPEN message received from the peer.
+    pub fn peer_open(&self) -> &Open {
+        &self.peer_open
+    }
+
+    /// The session parameters negotiated with the peer.
+    pub fn capabilities(&self) -> &NegotiatedCapabilities {
+        &self.capabilities
+    }
+
+    /// Waits for the next UPDATE message from the peer.
+    ///
+    /// Returns an error when the session ended (e.g. Hold Timer expiry or a Notification
+    /// from the peer), and None once that error has been returned.
+    pub async fn next_update(&mut self) -> Option<Result<Update, Error>> {
+        self.updates.recv().await
+    }
+
+    /// Sends an UPDATE message to the peer, encoded following the negotiated capabilities.
+    pub async fn send_update(&self, update: &Update) -> Result<(), Error> {
+        let message = Message::Update(update.clone());
+        write_message(&self.writer, &message, &self.capabilities.capabilities).await
+    }
+
+    /// Ends the session by sending a Cease Notification.
+    pub async fn close(self, subcode: CeaseSubcode) -> Result<(), Error> {
+        let message = Message::Notification(Notification::cease(subcode));
+        write_message(&self.writer, &message, &self.capabilities.capabilities).await
+    }
+}
+
+impl Drop for BgpPeer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Reads messages until the session ends, forwarding UPDATE messages
+async fn receive_updates(
+    mut reader: OwnedReadHalf,
+    writer: Arc<Mutex<OwnedWriteHalf>>,
+    capabilities: NegotiatedCapabilities,
+    sender: mpsc::Sender<Result<Update, Error>>,
+) {
+    let hold_timer = HoldTimer::new(capabilities.hold_time.unwrap_or_default());
+    let hold_time = hold_timer.hold_time();
+    loop {
+        let message = if hold_time > Duration::from_secs(0) {
+            match timeout(
+                hold_time,
+                read_message(&mut reader, &capabilities.capabilities),
+            )
+            .await
+            {
+                Ok(message) => message,
+                Err(_) => Err(hold_timer.expired_error()),
+            }
+        } else {
+            read_message(&mut reader, &capabilities.capabilities).await
+        };
+        let result = match message {
+            Ok(Message::Update(update)) => Ok(update),
+            Ok(Message::Notification(notification)) => Err(peer_notification(&notification)),
+            Ok(_) => continue,
+            Err(e) => Err(abort(&writer, e).await),
+        };
+        let end_of_session = result.is_err();
+        if sender.send(result).await.is_err() || end_of_session {
+            break;
+        }
+    }
+}
+
+async fn send_keepalives(writer: Arc<Mutex<OwnedWriteHalf>>, interval: Duration) {
+    let capabilities = Capabilities::default();
+    loop {
+        tokio::time::sleep(interval).await;
+        if write_message(&writer, &Message::KeepAlive, &capabilities)
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+// Receive a message while establishing the session, a Notification from the peer ends it
+async fn recv_handshake(
+    reader: &mut OwnedReadHalf,
+    capabilities: &Capabilities,
+) -> Result<Message, Error> {
+    let message = timeout(HANDSHAKE_HOLD_TIME, read_message(reader, capabilities))
+        .await
+        .unwrap_or_else(|_| {
+            Err(notification_error(
+                NotificationCode::HoldTimerExpired,
+                vec![],
+                "No message received while establishing the session".to_string(),
+            ))
+        })?;
+    match message {
+        Message::Notification(notification) => Err(peer_notification(&notification)),
+        message => Ok(message),
+    }
+}
+
+// Reads a complete message before parsing it with the (synchronous) Reader
+async fn read_message(
+    reader: &mut OwnedReadHalf,
+    capabilities: &Capabilities,
+) -> Result<Message, Error> {
+    let mut buf = vec![0u8; BGP_MIN_MESSAGE_SIZE];
+    reader.read_exact(&mut buf).await?;
+    let length = usize::from(u16::from_be_bytes([buf[16], buf[17]]));
+    if length < BGP_MIN_MESSAGE_SIZE || length > BGP_MAX_MESSAGE_SIZE {
+        return Err(notification_error(
+            NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+            buf[16..18].to_vec(),
+            format!("Invalid message length {}", length),
+        ));
+    }
+    buf.resize(length, 0);
+    reader.read_exact(&mut buf[BGP_MIN_MESSAGE_SIZE..]).await?;
+
+    let mut reader = Reader::with_capabilities(Cursor::new(buf), capabilities);
+    let (_header, message) = reader.read()?;
+    Ok(message)
+}
+
+async fn write_message(
+    writer: &Mutex<OwnedWriteHalf>,
+    message: &Message,
+    capabilities: &Capabilities,
+) -> Result<(), Error> {
+    let mut buf: Vec<u8> = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+    message.encode_with_capabilities(&mut buf, capabilities)?;
+    let mut writer = writer.lock().await;
+    writer.write_all(&buf).await?;
+    writer.flush().await
+}
+
+// Send the Notification for this error to the peer, unless it is an IO error
+async fn abort(writer: &Mutex<OwnedWriteHalf>, error: Error) -> Error {
+    if notification_of(&error).is_some() {
+        let notification = Message::Notification(error.to_notification());
+        let _ = write_message(writer, &notification, &Capabilities::default()).await;
+    }
+    error
+}
+
+fn peer_notification(notification: &Notification) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        format!("Peer sent Notification: {}", notification),
+    )
+}
@@ -0,0 +1,301 @@
+//! The `metrics` mod provides the statistics of long-running readers & sessions
+//! - Messages read by type, and the bytes they hold
+//! - Routes announced & withdrawn per AFI/SAFI (the UPDATE churn)
+//! - Parse errors by the code of the Notification they result in
+//! - The state of the session
+//!
+//! With the `prometheus` feature, `MetricsCollector` exposes them to a Prometheus registry.
+//!
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::Error;
+
+use crate::*;
+
+/// The state of a BGP session, as in the Finite State Machine of RFC4271 (Section 8.2.2).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SessionState {
+    /// No session is being established
+    Idle,
+    /// Waiting for the TCP connection to be completed
+    Connect,
+    /// Trying to establish a TCP connection after a failed attempt
+    Active,
+    /// Waiting for the OPEN message of the peer
+    OpenSent,
+    /// Waiting for the KEEPALIVE message confirming the OPEN
+    OpenConfirm,
+    /// Exchanging UPDATE messages
+    Established,
+}
+
+impl SessionState {
+    /// The value of the state in the BGP4-MIB (`bgpPeerState`), 1 for Idle up to 6 for
+    /// Established.
+    pub fn value(self) -> u8 {
+        match self {
+            SessionState::Idle => 1,
+            SessionState::Connect => 2,
+            SessionState::Active => 3,
+            SessionState::OpenSent => 4,
+            SessionState::OpenConfirm => 5,
+            SessionState::Established => 6,
+        }
+    }
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        SessionState::Idle
+    }
+}
+
+impl Display for SessionState {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let s = match self {
+            SessionState::Idle => "Idle",
+            SessionState::Connect => "Connect",
+            SessionState::Active => "Active",
+            SessionState::OpenSent => "OpenSent",
+            SessionState::OpenConfirm => "OpenConfirm",
+            SessionState::Established => "Established",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The counters & gauges of a reader or session, updated with the results of its reads.
+///
+/// ```
+/// use bgp_rs::{Message, MessageType, Metrics, Reader};
+/// # let mut capture = vec![];
+/// # Message::KeepAlive.encode(&mut capture).unwrap();
+/// # let stream = std::io::Cursor::new(capture);
+///
+/// let mut metrics = Metrics::new();
+/// let mut reader = Reader::new(stream);
+/// loop {
+///     let result = reader.read();
+///     metrics.record(&result);
+///     if result.is_err() {
+///         break;
+///     }
+/// }
+/// assert_eq!(metrics.messages[&MessageType::KeepAlive], 1);
+/// assert_eq!(metrics.error_count(), 1);
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Metrics {
+    /// The number of messages read, by type
+    pub messages: HashMap<MessageType, u64>,
+    /// The number of bytes of the messages read, including their headers
+    pub bytes: u64,
+    /// The number of routes announced, by AFI/SAFI
+    pub announced: HashMap<(AFI, SAFI), u64>,
+    /// The number of routes withdrawn, by AFI/SAFI
+    pub withdrawn: HashMap<(AFI, SAFI), u64>,
+    /// The number of errors reading messages, by the code of their Notification (see
+    /// `ToNotification`)
+    pub errors: HashMap<u8, u64>,
+    /// The state of the session, set by the owner of the session
+    pub session_state: SessionState,
+}
+
+impl Metrics {
+    /// Creates metrics with all counters at 0, for an Idle session.
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Records the result of reading a message, e.g. of `Reader::read`.
+    pub fn record(&mut self, result: &Result<(Header, Message), Error>) {
+        match result {
+            Ok((header, message)) => self.record_message(header, message),
+            Err(error) => self.record_error(error),
+        }
+    }
+
+    /// Records a message that was read, counting the routes of UPDATE messages.
+    pub fn record_message(&mut self, header: &Header, message: &Message) {
+        *self.messages.entry(header.record_type).or_insert(0) += 1;
+        self.bytes += u64::from(header.length);
+        if let Message::Update(update) = message {
+            for (afi, safi, _) in update.announced() {
+                *self.announced.entry((afi, safi)).or_insert(0) += 1;
+            }
+            for (afi, safi, _) in update.withdrawn() {
+                *self.withdrawn.entry((afi, safi)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Records an error reading a message.
+    pub fn record_error(&mut self, error: &Error) {
+        let code = error.to_notification().major_err_code();
+        *self.errors.entry(code).or_insert(0) += 1;
+    }
+
+    /// The number of messages read.
+    pub fn message_count(&self) -> u64 {
+        self.messages.values().sum()
+    }
+
+    /// The number of errors reading messages.
+    pub fn error_count(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    /// The share of reads that failed, 0 when nothing was read yet.
+    pub fn error_rate(&self) -> f64 {
+        let errors = self.error_count();
+        let reads = self.message_count() + errors;
+        if reads == 0 {
+            0.0
+        } else {
+            errors as f64 / reads as f64
+        }
+    }
+}
+
+#[cfg(feature = "prometheus")]
+pub use self::collector::MetricsCollector;
+
+#[cfg(feature = "prometheus")]
+mod collector {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use prometheus::core::{Collector, Desc};
+    use prometheus::proto::MetricFamily;
+    use prometheus::{IntCounter, IntCounterVec, IntGauge, Opts};
+
+    use super::Metrics;
+
+    /// Exposes `Metrics` to a Prometheus registry, reading them whenever the registry is
+    /// gathered:
+    /// - `bgp_messages_total`, the messages read by `type`
+    /// - `bgp_message_bytes_total`, the bytes of the messages read
+    /// - `bgp_routes_total`, the routes by `afi`, `safi` & `action` (announced or withdrawn)
+    /// - `bgp_parse_errors_total`, the errors by Notification `code`
+    /// - `bgp_session_state`, the state of the session (1 for Idle up to 6 for Established)
+    ///
+    /// ```
+    /// use bgp_rs::{Metrics, MetricsCollector, SessionState};
+    /// use std::collections::HashMap;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let metrics = Arc::new(Mutex::new(Metrics::new()));
+    /// let mut labels = HashMap::new();
+    /// labels.insert("peer".to_string(), "192.0.2.1".to_string());
+    /// let collector = MetricsCollector::new(metrics.clone(), labels).unwrap();
+    ///
+    /// let registry = prometheus::Registry::new();
+    /// registry.register(Box::new(collector)).unwrap();
+    /// metrics.lock().unwrap().session_state = SessionState::Established;
+    /// assert_eq!(registry.gather().len(), 2);
+    /// ```
+    pub struct MetricsCollector {
+        metrics: Arc<Mutex<Metrics>>,
+        messages: IntCounterVec,
+        bytes: IntCounter,
+        routes: IntCounterVec,
+        errors: IntCounterVec,
+        session_state: IntGauge,
+    }
+
+    impl MetricsCollector {
+        /// Creates a collector of shared metrics, with labels identifying them (e.g. the
+        /// peer of the session) when collecting the metrics of several readers.
+        pub fn new(
+            metrics: Arc<Mutex<Metrics>>,
+            labels: HashMap<String, String>,
+        ) -> Result<MetricsCollector, prometheus::Error> {
+            let opts = |name: &str, help: &str| Opts::new(name, help).const_labels(labels.clone());
+            Ok(MetricsCollector {
+                metrics,
+                messages: IntCounterVec::new(
+                    opts("bgp_messages_total", "The messages read, by type"),
+                    &["type"],
+                )?,
+                bytes: IntCounter::with_opts(opts(
+                    "bgp_message_bytes_total",
+                    "The bytes of the messages read",
+                ))?,
+                routes: IntCounterVec::new(
+                    opts(
+                        "bgp_routes_total",
+                        "The routes announced & withdrawn, by AFI/SAFI",
+                    ),
+                    &["afi", "safi", "action"],
+                )?,
+                errors: IntCounterVec::new(
+                    opts(
+                        "bgp_parse_errors_total",
+                        "The errors reading messages, by Notification code",
+                    ),
+                    &["code"],
+                )?,
+                session_state: IntGauge::with_opts(opts(
+                    "bgp_session_state",
+                    "The state of the session, 1 for Idle up to 6 for Established",
+                ))?,
+            })
+        }
+    }
+
+    impl Collector for MetricsCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            let mut descs = self.messages.desc();
+            descs.extend(self.bytes.desc());
+            descs.extend(self.routes.desc());
+            descs.extend(self.errors.desc());
+            descs.extend(self.session_state.desc());
+            descs
+        }
+
+        // The counters are set from the metrics, which are locked while collecting
+        fn collect(&self) -> Vec<MetricFamily> {
+            let metrics = match self.metrics.lock() {
+                Ok(metrics) => metrics,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            self.messages.reset();
+            for (message_type, count) in &metrics.messages {
+                self.messages
+                    .with_label_values(&[&message_type.to_string()])
+                    .inc_by(*count);
+            }
+            self.bytes.reset();
+            self.bytes.inc_by(metrics.bytes);
+            self.routes.reset();
+            let routes = metrics
+                .announced
+                .iter()
+                .map(|route| (route, "announced"))
+                .chain(metrics.withdrawn.iter().map(|route| (route, "withdrawn")));
+            for (((afi, safi), count), action) in routes {
+                let afi = u16::from(*afi).to_string();
+                let safi = u8::from(*safi).to_string();
+                self.routes
+                    .with_label_values(&[&afi, &safi, action])
+                    .inc_by(*count);
+            }
+            self.errors.reset();
+            for (code, count) in &metrics.errors {
+                self.errors
+                    .with_label_values(&[&code.to_string()])
+                    .inc_by(*count);
+            }
+            self.session_state
+                .set(i64::from(metrics.session_state.value()));
+
+            let mut families = self.messages.collect();
+            families.extend(self.bytes.collect());
+            families.extend(self.routes.collect());
+            families.extend(self.errors.collect());
+            families.extend(self.session_state.collect());
+            families
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! The `mrt` mod provides writers for MRT archives ([RFC6396](https://tools.ietf.org/html/rfc6396))
+//! - BGP4MP MESSAGE and MESSAGE_AS4 records, containing BGP messages exchanged with a peer
+//! - TABLE_DUMP_V2 PEER_INDEX_TABLE and RIB records, containing a snapshot of a RIB
+//!
+//! Useful to generate test fixtures and synthetic RIBs, readable by any MRT parser.
+//!
+//! TABLE_DUMP_V2 records read with `mrt_rs` are turned into routes by `RibDecoder`.
+//!
+
+use std::io::{Error, ErrorKind, Write};
+use std::net::{IpAddr, Ipv4Addr};
+
+use byteorder::{BigEndian, WriteBytesExt};
+use mrt_rs::records::tabledump::{self, RIB_AFI, TABLE_DUMP_V2};
+
+use crate::*;
+
+const MRT_TABLE_DUMP_V2: u16 = 13;
+const MRT_BGP4MP: u16 = 16;
+
+const BGP4MP_MESSAGE: u16 = 1;
+const BGP4MP_MESSAGE_AS4: u16 = 4;
+
+const PEER_INDEX_TABLE: u16 = 1;
+const RIB_IPV4_UNICAST: u16 = 2;
+const RIB_IPV6_UNICAST: u16 = 4;
+
+/// The BGP session a BGP4MP message was exchanged on.
+#[derive(Clone, Debug)]
+pub struct MrtPeering {
+    /// The ASN of the peer.
+    pub peer_as: u32,
+    /// The ASN of the local speaker (e.g. the collector).
+    pub local_as: u32,
+    /// The interface index of the session, usually zero.
+    pub interface: u16,
+    /// The IP address of the peer.
+    pub peer_address: IpAddr,
+    /// The IP address of the local speaker, of the same family as the peer address.
+    pub local_address: IpAddr,
+}
+
+/// A peer listed in the TABLE_DUMP_V2 PEER_INDEX_TABLE.
+#[derive(Clone, Debug)]
+pub struct MrtPeer {
+    /// The BGP Identifier of the peer.
+    pub bgp_id: Ipv4Addr,
+    /// The IP address of the peer.
+    pub address: IpAddr,
+    /// The ASN of the peer.
+    pub asn: u32,
+}
+
+/// A route for a prefix in a TABLE_DUMP_V2 RIB record.
+#[derive(Clone, Debug)]
+pub struct MrtRibEntry {
+    /// The index of the peer this route was received from, in the PEER_INDEX_TABLE.
+    pub peer_index: u16,
+    /// The time (in seconds since the UNIX epoch) the route was received.
+    pub originated_time: u32,
+    /// The path attributes of the route.
+    pub attributes: Vec<PathAttribute>,
+}
+
+impl MrtRibEntry {
+    /// Creates a RIB entry carrying the path attributes of an UPDATE message.
+    pub fn from_update(peer_index: u16, originated_time: u32, update: &Update) -> MrtRibEntry {
+        MrtRibEntry {
+            peer_index,
+            originated_time,
+            attributes: update.attributes.clone(),
+        }
+    }
+
+    // RFC6396 section 4.3.4: AS_PATH is always encoded with 4-byte ASNs,
+    // and MP_REACH_NLRI only holds the next hop.
+    fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        let capabilities = Capabilities {
+            FOUR_OCTET_ASN_SUPPORT: true,
+            ..Capabilities::default()
+        };
+        let mut attributes: Vec<u8> = Vec::with_capacity(64);
+        for attribute in &self.attributes {
+            match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                    attributes.write_u8(0x80)?;
+                    attributes.write_u8(Identifier::MP_REACH_NLRI as u8)?;
+                    attributes.write_u8(mp_reach.next_hop.len() as u8 + 1)?;
+                    attributes.write_u8(mp_reach.next_hop.len() as u8)?;
+                    attributes.write_all(&mp_reach.next_hop)?;
+                }
+                attribute => attribute.encode_with_capabilities(&mut attributes, &capabilities)?,
+            }
+        }
+        if attributes.len() > usize::from(std::u16::MAX) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Cannot encode attributes of length {}", attributes.len()),
+            ));
+        }
+
+        buf.write_u16::<BigEndian>(self.peer_index)?;
+        buf.write_u32::<BigEndian>(self.originated_time)?;
+        buf.write_u16::<BigEndian>(attributes.len() as u16)?;
+        buf.write_all(&attributes)
+    }
+}
+
+/// Writes MRT records into a stream.
+///
+/// # Example
+/// ```
+/// use bgp_rs::{Capabilities, Message, MrtPeering, MrtWriter};
+///
+/// let peering = MrtPeering {
+///     peer_as: 65001,
+///     local_as: 4_200_000_000,
+///     interface: 0,
+///     peer_address: "192.0.2.1".parse().unwrap(),
+///     local_address: "192.0.2.2".parse().unwrap(),
+/// };
+/// let mut writer = MrtWriter::new(Vec::new());
+/// writer
+///     .write_message_as4(1_577_836_800, &peering, &Message::KeepAlive, &Capabilities::default())
+///     .unwrap();
+/// // MRT header, BGP4MP_MESSAGE_AS4 peering & BGP header
+/// assert_eq!(writer.into_inner().len(), 12 + 20 + 19);
+/// ```
+pub struct MrtWriter<W>
+where
+    W: Write,
+{
+    stream: W,
+}
+
+impl<W> MrtWriter<W>
+where
+    W: Write,
+{
+    /// Creates a writer for the given stream.
+    pub fn new(stream: W) -> MrtWriter<W> {
+        MrtWriter { stream }
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> W {
+        self.stream
+    }
+
+    /// Writes a BGP4MP MESSAGE record, which holds 2-byte ASNs for the peering.
+    /// The message is encoded following the given capabilities.
+    pub fn write_message(
+        &mut self,
+        timestamp: u32,
+        peering: &MrtPeering,
+        message: &Message,
+        capabilities: &Capabilities,
+    ) -> Result<(), Error> {
+        if peering.peer_as > u32::from(std::u16::MAX) || peering.local_as > u32::from(std::u16::MAX)
+        {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "BGP4MP MESSAGE requires 2-byte ASNs, use MESSAGE_AS4 instead",
+            ));
+        }
+        let mut body: Vec<u8> = Vec::with_capacity(64);
+        body.write_u16::<BigEndian>(peering.peer_as as u16)?;
+        body.write_u16::<BigEndian>(peering.local_as as u16)?;
+        encode_message(&mut body, peering, message, capabilities)?;
+        self.write_record(timestamp, MRT_BGP4MP, BGP4MP_MESSAGE, &body)
+    }
+
+    /// Writes a BGP4MP MESSAGE_AS4 record, which holds 4-byte ASNs for the peering.
+    /// The message is encoded following the given capabilities.
+    pub fn write_message_as4(
+        &mut self,
+        timestamp: u32,
+        peering: &MrtPeering,
+        message: &Message,
+        capabilities: &Capabilities,
+    ) -> Result<(), Error> {
+        let mut body: Vec<u8> = Vec::with_capacity(64);
+        body.write_u32::<BigEndian>(peering.peer_as)?;
+        body.write_u32::<BigEndian>(peering.local_as)?;
+        encode_message(&mut body, peering, message, capabilities)?;
+        self.write_record(timestamp, MRT_BGP4MP, BGP4MP_MESSAGE_AS4, &body)
+    }
+
+    /// Writes a TABLE_DUMP_V2 PEER_INDEX_TABLE record, which should precede the RIB records.
+    pub fn write_peer_index_table(
+        &mut self,
+        timestamp: u32,
+        collector_id: Ipv4Addr,
+        view_name: &str,
+        peers: &[MrtPeer],
+    ) -> Result<(), Error> {
+        if view_name.len() > usize::from(std::u16::MAX) || peers.len() > usize::from(std::u16::MAX)
+        {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Too many peers (or too long view name) for PEER_INDEX_TABLE",
+            ));
+        }
+        let mut body: Vec<u8> = Vec::with_capacity(8 + view_name.len() + peers.len() * 12);
+        body.write_all(&collector_id.octets())?;
+        body.write_u16::<BigEndian>(view_name.len() as u16)?;
+        body.write_all(view_name.as_bytes())?;
+        body.write_u16::<BigEndian>(peers.len() as u16)?;
+        for peer in peers {
+            // Peer Type: bit 0 indicates an IPv6 address, bit 1 a 4-byte ASN
+            let mut peer_type = 0b10;
+            if peer.address.is_ipv6() {
+                peer_type |= 0b01;
+            }
+            body.write_u8(peer_type)?;
+            body.write_all(&peer.bgp_id.octets())?;
+            write_address(&mut body, peer.address)?;
+            body.write_u32::<BigEndian>(peer.asn)?;
+        }
+        self.write_record(timestamp, MRT_TABLE_DUMP_V2, PEER_INDEX_TABLE, &body)
+    }
+
+    /// Writes a TABLE_DUMP_V2 RIB_IPV4_UNICAST or RIB_IPV6_UNICAST record,
+    /// holding the routes for a single prefix.
+    pub fn write_rib(
+        &mut self,
+        timestamp: u32,
+        sequence_number: u32,
+        prefix: &Prefix,
+        entries: &[MrtRibEntry],
+    ) -> Result<(), Error> {
+        let sub_type = match prefix.protocol {
+            AFI::IPV4 => RIB_IPV4_UNICAST,
+            AFI::IPV6 => RIB_IPV6_UNICAST,
+            afi => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Unsupported AFI for RIB records: {:?}", afi),
+                ));
+            }
+        };
+        if entries.len() > usize::from(std::u16::MAX) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Cannot encode {} RIB entries", entries.len()),
+            ));
+        }
+        let mut body: Vec<u8> = Vec::with_capacity(64);
+        body.write_u32::<BigEndian>(sequence_number)?;
+        body.write_u8(prefix.length)?;
+        body.write_all(prefix.masked_octets())?;
+        body.write_u16::<BigEndian>(entries.len() as u16)?;
+        for entry in entries {
+            entry.encode(&mut body)?;
+        }
+        self.write_record(timestamp, MRT_TABLE_DUMP_V2, sub_type, &body)
+    }
+
+    fn write_record(
+        &mut self,
+        timestamp: u32,
+        record_type: u16,
+        sub_type: u16,
+        body: &[u8],
+    ) -> Result<(), Error> {
+        self.stream.write_u32::<BigEndian>(timestamp)?;
+        self.stream.write_u16::<BigEndian>(record_type)?;
+        self.stream.write_u16::<BigEndian>(sub_type)?;
+        self.stream.write_u32::<BigEndian>(body.len() as u32)?;
+        self.stream.write_all(body)
+    }
+}
+
+// Writes the remainder of a BGP4MP MESSAGE(_AS4) record, following the ASNs
+fn encode_message(
+    buf: &mut impl Write,
+    peering: &MrtPeering,
+    message: &Message,
+    capabilities: &Capabilities,
+) -> Result<(), Error> {
+    let afi = match (peering.peer_address, peering.local_address) {
+        (IpAddr::V4(_), IpAddr::V4(_)) => AFI::IPV4,
+        (IpAddr::V6(_), IpAddr::V6(_)) => AFI::IPV6,
+        _ => {
+            return Err(Error::new(
+                ErrorKind::Other,
+                "Peer and local address must be of the same address family",
+            ));
+        }
+    };
+    buf.write_u16::<BigEndian>(peering.interface)?;
+    buf.write_u16::<BigEndian>(u16::from(afi))?;
+    write_address(buf, peering.peer_address)?;
+    write_address(buf, peering.local_address)?;
+    message.encode_with_capabilities(buf, capabilities)
+}
+
+fn write_address(buf: &mut impl Write, address: IpAddr) -> Result<(), Error> {
+    match address {
+        IpAddr::V4(address) => buf.write_all(&address.octets()),
+        IpAddr::V6(address) => buf.write_all(&address.octets()),
+    }
+}
+
+/// Decodes TABLE_DUMP_V2 records (as read by `mrt_rs`) into routes.
+///
+/// The PEER_INDEX_TABLE record is retained, to resolve the peer of each RIB entry
+/// and to choose whether its AS_PATH holds 2-byte or 4-byte ASNs.
+///
+/// # Example
+/// ```
+/// use bgp_rs::{MrtPeer, MrtWriter, RibDecoder};
+/// use mrt_rs::Record;
+/// use std::io::Cursor;
+///
+/// let peer = MrtPeer {
+///     bgp_id: "192.0.2.1".parse().unwrap(),
+///     address: "192.0.2.1".parse().unwrap(),
+///     asn: 65001,
+/// };
+/// let mut writer = MrtWriter::new(Vec::new());
+/// writer
+///     .write_peer_index_table(0, "10.0.0.1".parse().unwrap(), "", &[peer])
+///     .unwrap();
+/// writer
+///     .write_rib(0, 0, &("10.0.0.0".parse().unwrap(), 8).into(), &[])
+///     .unwrap();
+///
+/// let mut stream = Cursor::new(writer.into_inner());
+/// let mut decoder = RibDecoder::new();
+/// while let Some((_, record)) = mrt_rs::read(&mut stream).unwrap() {
+///     if let Record::TABLE_DUMP_V2(record) = record {
+///         for (peer, prefix, attributes) in decoder.decode(&record).unwrap() {
+///             println!("{} via AS{}: {:?}", prefix, peer.asn, attributes);
+///         }
+///     }
+/// }
+/// assert_eq!(decoder.peers().len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RibDecoder {
+    peers: Vec<MrtPeer>,
+}
+
+impl RibDecoder {
+    /// Creates a decoder, awaiting the PEER_INDEX_TABLE record.
+    pub fn new() -> RibDecoder {
+        RibDecoder::default()
+    }
+
+    /// Creates a decoder for the peers in the given PEER_INDEX_TABLE.
+    pub fn from_peer_index_table(table: &tabledump::PEER_INDEX_TABLE) -> RibDecoder {
+        let mut decoder = RibDecoder::new();
+        decoder.set_peer_index_table(table);
+        decoder
+    }
+
+    /// The peers of the PEER_INDEX_TABLE.
+    pub fn peers(&self) -> &[MrtPeer] {
+        &self.peers
+    }
+
+    /// Decodes the routes in a TABLE_DUMP_V2 record, as (peer, prefix, attributes) entries.
+    ///
+    /// A PEER_INDEX_TABLE record replaces the known peers and yields no routes.
+    /// Only the RIB_IPV4/IPV6_UNICAST/MULTICAST subtypes are supported.
+    pub fn decode(
+        &mut self,
+        record: &TABLE_DUMP_V2,
+    ) -> Result<Vec<(MrtPeer, Prefix, Vec<PathAttribute>)>, Error> {
+        match record {
+            TABLE_DUMP_V2::PEER_INDEX_TABLE(table) => {
+                self.set_peer_index_table(table);
+                Ok(vec![])
+            }
+            TABLE_DUMP_V2::RIB_IPV4_UNICAST(rib) => self.decode_rib(rib, AFI::IPV4, SAFI::Unicast),
+            TABLE_DUMP_V2::RIB_IPV4_MULTICAST(rib) => {
+                self.decode_rib(rib, AFI::IPV4, SAFI::Multicast)
+            }
+            TABLE_DUMP_V2::RIB_IPV6_UNICAST(rib) => self.decode_rib(rib, AFI::IPV6, SAFI::Unicast),
+            TABLE_DUMP_V2::RIB_IPV6_MULTICAST(rib) => {
+                self.decode_rib(rib, AFI::IPV6, SAFI::Multicast)
+            }
+            _ => Err(Error::new(
+                ErrorKind::Other,
+                "Unsupported TABLE_DUMP_V2 subtype",
+            )),
+        }
+    }
+
+    fn set_peer_index_table(&mut self, table: &tabledump::PEER_INDEX_TABLE) {
+        self.peers = table
+            .peer_entries
+            .iter()
+            .map(|entry| MrtPeer {
+                bgp_id: Ipv4Addr::from(entry.peer_bgp_id),
+                address: entry.peer_ip_address,
+                asn: entry.peer_as,
+            })
+            .collect();
+    }
+
+    fn decode_rib(
+        &self,
+        rib: &RIB_AFI,
+        afi: AFI,
+        safi: SAFI,
+    ) -> Result<Vec<(MrtPeer, Prefix, Vec<PathAttribute>)>, Error> {
+        let max_length = if afi == AFI::IPV4 { 32 } else { 128 };
+        let length = usize::from(rib.prefix_length);
+        if length > max_length || rib.prefix.len() != (length + 7) / 8 {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Invalid {} RIB prefix of length {} in {} bytes",
+                    afi,
+                    rib.prefix_length,
+                    rib.prefix.len()
+                ),
+            ));
+        }
+        let prefix = Prefix {
+            protocol: afi,
+            length: rib.prefix_length,
+            prefix: rib.prefix.clone(),
+        };
+        // AS_PATHs of RIB entries always hold 4-byte ASNs (RFC6396 section 4.3.4)
+        let capabilities = Capabilities {
+            FOUR_OCTET_ASN_SUPPORT: true,
+            ..Capabilities::default()
+        };
+        let mut routes = Vec::with_capacity(rib.entries.len());
+        for entry in &rib.entries {
+            let peer = self
+                .peers
+                .get(usize::from(entry.peer_index))
+                .ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::Other,
+                        format!("Unknown peer index {}", entry.peer_index),
+                    )
+                })?;
+
+            let mut attributes = Vec::with_capacity(8);
+            let mut buf: &[u8] = &entry.attributes;
+            while !buf.is_empty() {
+                let raw = RawPathAttributeRef::parse(&mut buf)?;
+                attributes.push(decode_attribute(&raw, afi, safi, &capabilities)?);
+            }
+            routes.push((peer.clone(), prefix.clone(), attributes));
+        }
+        Ok(routes)
+    }
+}
+
+// MP_REACH_NLRI usually only holds the next hop (RFC6396 section 4.3.4)
+fn decode_attribute(
+    raw: &RawPathAttributeRef,
+    afi: AFI,
+    safi: SAFI,
+    capabilities: &Capabilities,
+) -> Result<PathAttribute, Error> {
+    match raw.value.split_first() {
+        Some((&length, next_hop))
+            if raw.code == Identifier::MP_REACH_NLRI as u8
+                && usize::from(length) == next_hop.len() =>
+        {
+            Ok(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI {
+                afi,
+                safi,
+                next_hop: next_hop.to_vec(),
+                announced_routes: vec![],
+            })))
+        }
+        _ => raw.decode(capabilities, &ParseOptions::default()),
+    }
+}
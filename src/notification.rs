@@ -1,31 +1,36 @@
+use std::convert::TryFrom;
 use std::fmt;
-use std::io::{Error, Read, Write};
+use std::io::{Error, ErrorKind, Read, Write};
 
-use byteorder::{ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::*;
 
 /// Represents a BGP Notification message.
 ///
-/// Has display for Major error codes:
+/// Has display for Major and Minor error codes:
 ///
 /// ```
 /// use bgp_rs::Notification;
-/// assert_eq!(&(Notification::new(6, 3).to_string()), "Cease / 3 ");
+/// assert_eq!(&(Notification::new(6, 3).to_string()), "Cease / Peer De-configured ");
 /// assert_eq!(&(Notification::new(9, 0).to_string()), "Major Code 9 / 0 ");
-/// assert_eq!(&(Notification::new(3, 1).to_string()), "UPDATE Message Error / 1 ");
 /// assert_eq!(
-///     &(Notification::from_data(2, 1, b"Unsupported Capability".to_vec()).to_string()),
-///     "OPEN Message Error / 1 Unsupported Capability",
+///     &(Notification::new(3, 1).to_string()),
+///     "UPDATE Message Error / Malformed Attribute List "
+/// );
+/// assert_eq!(
+///     &(Notification::from_data(2, 7, b"Unsupported Capability".to_vec()).to_string()),
+///     "OPEN Message Error / Unsupported Capability Unsupported Capability",
+/// );
+/// assert_eq!(
+///     &(Notification::new(5, 2).to_string()),
+///     "Finite State Machine / Receive Unexpected Message in OpenConfirm State "
 /// );
-/// assert_eq!(&(Notification::new(5, 2).to_string()), "Finite State Machine / 2 ");
 /// ```
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Notification {
-    /// Major Error Code [RFC4271]
-    pub major_err_code: u8,
-    /// Minor Error Code [RFC4271]
-    pub minor_err_code: u8,
+    /// Error Code and Error Subcode [RFC4271]
+    pub code: NotificationCode,
     /// Notification data as bytes (E.g. "Error Details".as_bytes())
     pub data: Vec<u8>,
 }
@@ -38,11 +43,64 @@ impl Notification {
 
     /// Create new Notification (with data)
     pub fn from_data(major: u8, minor: u8, data: Vec<u8>) -> Self {
-        Self {
-            major_err_code: major,
-            minor_err_code: minor,
+        Self::from_code(NotificationCode::from((major, minor)), data)
+    }
+
+    /// Create new Notification from a typed error code (with data)
+    ///
+    /// ```
+    /// use bgp_rs::{CeaseSubcode, Notification, NotificationCode};
+    ///
+    /// let notification = Notification::from_code(
+    ///     NotificationCode::Cease(CeaseSubcode::AdministrativeReset),
+    ///     vec![],
+    /// );
+    /// assert_eq!(notification.major_err_code(), 6);
+    /// assert_eq!(notification.minor_err_code(), 4);
+    /// ```
+    pub fn from_code(code: NotificationCode, data: Vec<u8>) -> Self {
+        Self { code, data }
+    }
+
+    /// Create new Cease Notification (without data)
+    pub fn cease(subcode: CeaseSubcode) -> Self {
+        Self::from_code(NotificationCode::Cease(subcode), vec![])
+    }
+
+    /// Create new OPEN Message Error / Unsupported Capability Notification,
+    /// carrying the capabilities that are not supported
+    pub fn unsupported_capability(capabilities: &[OpenCapability]) -> Result<Self, Error> {
+        let mut data: Vec<u8> = Vec::with_capacity(capabilities.len() * 4);
+        for capability in capabilities {
+            capability.encode_capability(&mut data)?;
+        }
+        Ok(Self::from_code(
+            NotificationCode::OpenMessage(OpenMessageSubcode::UnsupportedCapability),
             data,
+        ))
+    }
+
+    /// Create new OPEN Message Error / Bad Peer AS Notification, carrying the unacceptable ASN
+    pub fn bad_peer_as(asn: u32) -> Self {
+        let mut data: Vec<u8> = Vec::with_capacity(4);
+        if asn > u32::from(std::u16::MAX) {
+            data.extend_from_slice(&asn.to_be_bytes());
+        } else {
+            data.extend_from_slice(&(asn as u16).to_be_bytes());
         }
+        Self::from_code(
+            NotificationCode::OpenMessage(OpenMessageSubcode::BadPeerAS),
+            data,
+        )
+    }
+
+    /// Create new OPEN Message Error / Unacceptable Hold Time Notification,
+    /// carrying the unacceptable Hold Time
+    pub fn unacceptable_hold_time(hold_time: u16) -> Self {
+        Self::from_code(
+            NotificationCode::OpenMessage(OpenMessageSubcode::UnacceptableHoldTime),
+            hold_time.to_be_bytes().to_vec(),
+        )
     }
 
     /// Parse Notification message
@@ -52,48 +110,231 @@ impl Notification {
         let minor_err_code = stream.read_u8()?;
         let data = if header.length > 21 {
             let remaining_length = header.length as usize - 21;
-            let mut data = vec![0; remaining_length as usize];
+            let mut data = vec![0; remaining_length];
             stream.read_exact(&mut data)?;
             data
         } else {
             vec![]
         };
 
-        Ok(Notification {
+        Ok(Notification::from_data(
             major_err_code,
             minor_err_code,
             data,
-        })
+        ))
     }
 
     /// Encode message to bytes
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
-        buf.write_u8(self.major_err_code)?;
-        buf.write_u8(self.minor_err_code)?;
+        buf.write_u8(self.major_err_code())?;
+        buf.write_u8(self.minor_err_code())?;
         buf.write_all(&self.data)
     }
 
+    /// Major Error Code [RFC4271]
+    pub fn major_err_code(&self) -> u8 {
+        self.code.major()
+    }
+
+    /// Minor Error Code [RFC4271]
+    pub fn minor_err_code(&self) -> u8 {
+        self.code.minor()
+    }
+
     /// Major Error Code Description
     pub fn major(&self) -> String {
-        match self.major_err_code {
-            1 => "Message Header Error".to_string(),
-            2 => "OPEN Message Error".to_string(),
-            3 => "UPDATE Message Error".to_string(),
-            4 => "Hold Timer Expired".to_string(),
-            5 => "Finite State Machine".to_string(),
-            6 => "Cease".to_string(),
-            _ => format!("Major Code {}", self.major_err_code),
-        }
+        self.code.major_description()
     }
     /// Minor Error Code Description
     pub fn minor(&self) -> String {
-        format!("{}", self.minor_err_code)
+        self.code.minor_description()
     }
 
     /// Included message (if present)
     pub fn message(&self) -> Option<String> {
         String::from_utf8(self.data.clone()).ok()
     }
+
+    /// Decode the data of an OPEN Message Error, for the subcodes that carry structured data.
+    /// Returns `None` for other Notifications.
+    ///
+    /// ```
+    /// use bgp_rs::{Notification, OpenCapability, OpenErrorData};
+    ///
+    /// let notification = Notification::unsupported_capability(&[
+    ///     OpenCapability::RouteRefresh,
+    ///     OpenCapability::FourByteASN(4200000000),
+    /// ]).unwrap();
+    /// match notification.open_error_data().unwrap() {
+    ///     Some(OpenErrorData::UnsupportedCapability(capabilities)) => {
+    ///         assert_eq!(capabilities.len(), 2)
+    ///     }
+    ///     _ => panic!("Expected capabilities"),
+    /// }
+    ///
+    /// match Notification::bad_peer_as(65000).open_error_data().unwrap() {
+    ///     Some(OpenErrorData::BadPeerAS(asn)) => assert_eq!(asn, 65000),
+    ///     _ => panic!("Expected ASN"),
+    /// }
+    /// ```
+    pub fn open_error_data(&self) -> Result<Option<OpenErrorData>, Error> {
+        let subcode = match self.code {
+            NotificationCode::OpenMessage(subcode) => subcode,
+            _ => return Ok(None),
+        };
+        let mut stream = std::io::Cursor::new(&self.data);
+        let data = match subcode {
+            OpenMessageSubcode::UnsupportedCapability => {
+                let mut capabilities = Vec::with_capacity(self.data.len() / 4);
+                while (stream.position() as usize) < self.data.len() {
+                    let (_length, capability) = OpenCapability::parse(&mut stream)?;
+                    capabilities.push(capability);
+                }
+                OpenErrorData::UnsupportedCapability(capabilities)
+            }
+            OpenMessageSubcode::BadPeerAS => match self.data.len() {
+                2 => OpenErrorData::BadPeerAS(u32::from(stream.read_u16::<BigEndian>()?)),
+                4 => OpenErrorData::BadPeerAS(stream.read_u32::<BigEndian>()?),
+                length => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("Bad Peer AS data must be 2 or 4 bytes, not {}", length),
+                    ));
+                }
+            },
+            OpenMessageSubcode::UnacceptableHoldTime => match self.data.len() {
+                2 => OpenErrorData::UnacceptableHoldTime(stream.read_u16::<BigEndian>()?),
+                length => {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "Unacceptable Hold Time data must be 2 bytes, not {}",
+                            length
+                        ),
+                    ));
+                }
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(data))
+    }
+}
+
+/// A parse error that corresponds to a specific Notification.
+///
+/// Carried as the inner error of the `std::io::Error` returned while parsing,
+/// use `ToNotification` to retrieve the Notification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NotificationError {
+    /// The Notification that should be sent to the peer
+    pub notification: Notification,
+    /// Description of the error
+    pub message: String,
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for NotificationError {}
+
+impl From<Error> for NotificationError {
+    fn from(error: Error) -> NotificationError {
+        match notification_of(&error) {
+            Some(inner) => inner.clone(),
+            None => NotificationError {
+                notification: error.to_notification(),
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+// Create an error carrying the Notification to send to the peer
+pub(crate) fn notification_error(code: NotificationCode, data: Vec<u8>, message: String) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        NotificationError {
+            notification: Notification::from_code(code, data),
+            message,
+        },
+    )
+}
+
+// Attach a Notification to an error, unless it already carries a more specific one
+pub(crate) fn with_notification(error: Error, code: NotificationCode, data: Vec<u8>) -> Error {
+    if notification_of(&error).is_some() {
+        return error;
+    }
+    let message = error.to_string();
+    Error::new(
+        error.kind(),
+        NotificationError {
+            notification: Notification::from_code(code, data),
+            message,
+        },
+    )
+}
+
+// The NotificationError carried by an error, if any
+pub(crate) fn notification_of(error: &Error) -> Option<&NotificationError> {
+    error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<NotificationError>())
+}
+
+/// Conversion of parse errors into the Notification that should be sent to the peer.
+///
+/// ```
+/// use bgp_rs::{Header, MessageHeaderSubcode, NotificationCode, Reader, ToNotification};
+///
+/// // KEEPALIVE header with an unknown message type
+/// let mut data = vec![0xff; 16];
+/// data.extend_from_slice(&[0, 19, 10]);
+/// let mut reader = Reader::new(std::io::Cursor::new(data));
+/// let error = reader.read().unwrap_err();
+/// let notification = error.to_notification();
+/// assert_eq!(
+///     notification.code,
+///     NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageType)
+/// );
+/// assert_eq!(notification.data, vec![10]);
+/// ```
+pub trait ToNotification {
+    /// The Notification describing this error.
+    /// Errors without a more specific mapping result in Message Header Error / Unspecific.
+    fn to_notification(&self) -> Notification;
+}
+
+impl ToNotification for Error {
+    fn to_notification(&self) -> Notification {
+        match notification_of(self) {
+            Some(error) => error.notification.clone(),
+            None => Notification::from_code(
+                NotificationCode::MessageHeader(MessageHeaderSubcode::Unspecific),
+                vec![],
+            ),
+        }
+    }
+}
+
+impl ToNotification for NotificationError {
+    fn to_notification(&self) -> Notification {
+        self.notification.clone()
+    }
+}
+
+/// The decoded data of an OPEN Message Error Notification.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OpenErrorData {
+    /// The capabilities that are not supported by the peer [RFC5492]
+    UnsupportedCapability(Vec<OpenCapability>),
+    /// The Autonomous System number that was not accepted by the peer
+    BadPeerAS(u32),
+    /// The Hold Time that was not accepted by the peer
+    UnacceptableHoldTime(u16),
 }
 
 impl fmt::Display for Notification {
@@ -103,7 +344,465 @@ impl fmt::Display for Notification {
             "{} / {} {}",
             self.major(),
             self.minor(),
-            self.message().unwrap_or_else(|| "".to_string())
+            self.message().unwrap_or_default()
+        )
+    }
+}
+
+/// The Error Code and Error Subcode of a Notification message.
+///
+/// Codes that are not (yet) assigned by IANA are kept as `Unknown`.
+///
+/// ```
+/// use bgp_rs::{CeaseSubcode, NotificationCode};
+///
+/// let code = NotificationCode::from((6, 2));
+/// assert_eq!(code, NotificationCode::Cease(CeaseSubcode::AdministrativeShutdown));
+/// assert_eq!(code.major(), 6);
+/// assert_eq!(code.minor(), 2);
+/// assert_eq!(&code.to_string(), "Cease / Administrative Shutdown");
+///
+/// let unknown = NotificationCode::from((6, 200));
+/// assert_eq!(unknown, NotificationCode::Unknown { major: 6, minor: 200 });
+/// assert_eq!(&unknown.to_string(), "Cease / 200");
+/// ```
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum NotificationCode {
+    /// 1 - Message Header Error [RFC4271]
+    MessageHeader(MessageHeaderSubcode),
+    /// 2 - OPEN Message Error [RFC4271]
+    OpenMessage(OpenMessageSubcode),
+    /// 3 - UPDATE Message Error [RFC4271]
+    UpdateMessage(UpdateMessageSubcode),
+    /// 4 - Hold Timer Expired [RFC4271]
+    HoldTimerExpired,
+    /// 5 - Finite State Machine Error [RFC6608]
+    FiniteStateMachine(FiniteStateMachineSubcode),
+    /// 6 - Cease [RFC4486]
+    Cease(CeaseSubcode),
+    /// 7 - ROUTE-REFRESH Message Error [RFC7313]
+    RouteRefreshMessage(RouteRefreshMessageSubcode),
+    /// 8 - Send Hold Timer Expired [RFC9687]
+    SendHoldTimerExpired,
+    /// Unknown (or unsupported) error code and subcode
+    Unknown {
+        /// Major Error Code
+        major: u8,
+        /// Minor Error Code
+        minor: u8,
+    },
+}
+
+impl NotificationCode {
+    /// The (major) Error Code
+    pub fn major(&self) -> u8 {
+        use NotificationCode::*;
+        match self {
+            MessageHeader(_) => 1,
+            OpenMessage(_) => 2,
+            UpdateMessage(_) => 3,
+            HoldTimerExpired => 4,
+            FiniteStateMachine(_) => 5,
+            Cease(_) => 6,
+            RouteRefreshMessage(_) => 7,
+            SendHoldTimerExpired => 8,
+            Unknown { major, .. } => *major,
+        }
+    }
+
+    /// The (minor) Error Subcode
+    pub fn minor(&self) -> u8 {
+        use NotificationCode::*;
+        match self {
+            MessageHeader(subcode) => *subcode as u8,
+            OpenMessage(subcode) => *subcode as u8,
+            UpdateMessage(subcode) => *subcode as u8,
+            HoldTimerExpired | SendHoldTimerExpired => 0,
+            FiniteStateMachine(subcode) => *subcode as u8,
+            Cease(subcode) => *subcode as u8,
+            RouteRefreshMessage(subcode) => *subcode as u8,
+            Unknown { minor, .. } => *minor,
+        }
+    }
+
+    /// Major Error Code Description
+    pub fn major_description(&self) -> String {
+        match self.major() {
+            1 => "Message Header Error".to_string(),
+            2 => "OPEN Message Error".to_string(),
+            3 => "UPDATE Message Error".to_string(),
+            4 => "Hold Timer Expired".to_string(),
+            5 => "Finite State Machine".to_string(),
+            6 => "Cease".to_string(),
+            7 => "ROUTE-REFRESH Message Error".to_string(),
+            8 => "Send Hold Timer Expired".to_string(),
+            major => format!("Major Code {}", major),
+        }
+    }
+
+    /// Minor Error Code Description
+    pub fn minor_description(&self) -> String {
+        use NotificationCode::*;
+        match self {
+            MessageHeader(subcode) => subcode.to_string(),
+            OpenMessage(subcode) => subcode.to_string(),
+            UpdateMessage(subcode) => subcode.to_string(),
+            FiniteStateMachine(subcode) => subcode.to_string(),
+            Cease(subcode) => subcode.to_string(),
+            RouteRefreshMessage(subcode) => subcode.to_string(),
+            _ => format!("{}", self.minor()),
+        }
+    }
+}
+
+/// Convert a (major, minor) pair of error codes to a NotificationCode
+impl From<(u8, u8)> for NotificationCode {
+    fn from(codes: (u8, u8)) -> Self {
+        use NotificationCode::*;
+        let (major, minor) = codes;
+        let code = match major {
+            1 => MessageHeaderSubcode::try_from(minor).map(MessageHeader),
+            2 => OpenMessageSubcode::try_from(minor).map(OpenMessage),
+            3 => UpdateMessageSubcode::try_from(minor).map(UpdateMessage),
+            4 if minor == 0 => Ok(HoldTimerExpired),
+            5 => FiniteStateMachineSubcode::try_from(minor).map(FiniteStateMachine),
+            6 => CeaseSubcode::try_from(minor).map(Cease),
+            7 => RouteRefreshMessageSubcode::try_from(minor).map(RouteRefreshMessage),
+            8 if minor == 0 => Ok(SendHoldTimerExpired),
+            _ => Err(unknown_subcode("error code", major)),
+        };
+        code.unwrap_or(Unknown { major, minor })
+    }
+}
+
+impl fmt::Display for NotificationCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} / {}",
+            self.major_description(),
+            self.minor_description()
         )
     }
 }
+
+fn unknown_subcode(name: &str, value: u8) -> Error {
+    Error::new(
+        ErrorKind::Other,
+        format!("Number {} does not represent a valid {}.", value, name),
+    )
+}
+
+/// Error Subcodes of a Message Header Error.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum MessageHeaderSubcode {
+    /// Unspecific
+    Unspecific = 0,
+    /// Connection Not Synchronized
+    ConnectionNotSynchronized = 1,
+    /// Bad Message Length
+    BadMessageLength = 2,
+    /// Bad Message Type
+    BadMessageType = 3,
+}
+
+impl TryFrom<u8> for MessageHeaderSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use MessageHeaderSubcode::*;
+        match value {
+            0 => Ok(Unspecific),
+            1 => Ok(ConnectionNotSynchronized),
+            2 => Ok(BadMessageLength),
+            3 => Ok(BadMessageType),
+            _ => Err(unknown_subcode("Message Header Error subcode", value)),
+        }
+    }
+}
+
+impl fmt::Display for MessageHeaderSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use MessageHeaderSubcode::*;
+        let s = match self {
+            Unspecific => "Unspecific",
+            ConnectionNotSynchronized => "Connection Not Synchronized",
+            BadMessageLength => "Bad Message Length",
+            BadMessageType => "Bad Message Type",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Error Subcodes of an OPEN Message Error.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum OpenMessageSubcode {
+    /// Unspecific
+    Unspecific = 0,
+    /// Unsupported Version Number
+    UnsupportedVersionNumber = 1,
+    /// Bad Peer AS
+    BadPeerAS = 2,
+    /// Bad BGP Identifier
+    BadBGPIdentifier = 3,
+    /// Unsupported Optional Parameter
+    UnsupportedOptionalParameter = 4,
+    /// Unacceptable Hold Time
+    UnacceptableHoldTime = 6,
+    /// Unsupported Capability [RFC5492]
+    UnsupportedCapability = 7,
+    /// Role Mismatch [RFC9234]
+    RoleMismatch = 11,
+}
+
+impl TryFrom<u8> for OpenMessageSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use OpenMessageSubcode::*;
+        match value {
+            0 => Ok(Unspecific),
+            1 => Ok(UnsupportedVersionNumber),
+            2 => Ok(BadPeerAS),
+            3 => Ok(BadBGPIdentifier),
+            4 => Ok(UnsupportedOptionalParameter),
+            6 => Ok(UnacceptableHoldTime),
+            7 => Ok(UnsupportedCapability),
+            11 => Ok(RoleMismatch),
+            _ => Err(unknown_subcode("OPEN Message Error subcode", value)),
+        }
+    }
+}
+
+impl fmt::Display for OpenMessageSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use OpenMessageSubcode::*;
+        let s = match self {
+            Unspecific => "Unspecific",
+            UnsupportedVersionNumber => "Unsupported Version Number",
+            BadPeerAS => "Bad Peer AS",
+            BadBGPIdentifier => "Bad BGP Identifier",
+            UnsupportedOptionalParameter => "Unsupported Optional Parameter",
+            UnacceptableHoldTime => "Unacceptable Hold Time",
+            UnsupportedCapability => "Unsupported Capability",
+            RoleMismatch => "Role Mismatch",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Error Subcodes of an UPDATE Message Error.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum UpdateMessageSubcode {
+    /// Unspecific
+    Unspecific = 0,
+    /// Malformed Attribute List
+    MalformedAttributeList = 1,
+    /// Unrecognized Well-known Attribute
+    UnrecognizedWellKnownAttribute = 2,
+    /// Missing Well-known Attribute
+    MissingWellKnownAttribute = 3,
+    /// Attribute Flags Error
+    AttributeFlagsError = 4,
+    /// Attribute Length Error
+    AttributeLengthError = 5,
+    /// Invalid ORIGIN Attribute
+    InvalidOriginAttribute = 6,
+    /// Invalid NEXT_HOP Attribute
+    InvalidNextHopAttribute = 8,
+    /// Optional Attribute Error
+    OptionalAttributeError = 9,
+    /// Invalid Network Field
+    InvalidNetworkField = 10,
+    /// Malformed AS_PATH
+    MalformedASPath = 11,
+}
+
+impl TryFrom<u8> for UpdateMessageSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use UpdateMessageSubcode::*;
+        match value {
+            0 => Ok(Unspecific),
+            1 => Ok(MalformedAttributeList),
+            2 => Ok(UnrecognizedWellKnownAttribute),
+            3 => Ok(MissingWellKnownAttribute),
+            4 => Ok(AttributeFlagsError),
+            5 => Ok(AttributeLengthError),
+            6 => Ok(InvalidOriginAttribute),
+            8 => Ok(InvalidNextHopAttribute),
+            9 => Ok(OptionalAttributeError),
+            10 => Ok(InvalidNetworkField),
+            11 => Ok(MalformedASPath),
+            _ => Err(unknown_subcode("UPDATE Message Error subcode", value)),
+        }
+    }
+}
+
+impl fmt::Display for UpdateMessageSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use UpdateMessageSubcode::*;
+        let s = match self {
+            Unspecific => "Unspecific",
+            MalformedAttributeList => "Malformed Attribute List",
+            UnrecognizedWellKnownAttribute => "Unrecognized Well-known Attribute",
+            MissingWellKnownAttribute => "Missing Well-known Attribute",
+            AttributeFlagsError => "Attribute Flags Error",
+            AttributeLengthError => "Attribute Length Error",
+            InvalidOriginAttribute => "Invalid ORIGIN Attribute",
+            InvalidNextHopAttribute => "Invalid NEXT_HOP Attribute",
+            OptionalAttributeError => "Optional Attribute Error",
+            InvalidNetworkField => "Invalid Network Field",
+            MalformedASPath => "Malformed AS_PATH",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Error Subcodes of a Finite State Machine Error.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum FiniteStateMachineSubcode {
+    /// Unspecified Error
+    Unspecified = 0,
+    /// Receive Unexpected Message in OpenSent State
+    UnexpectedMessageInOpenSent = 1,
+    /// Receive Unexpected Message in OpenConfirm State
+    UnexpectedMessageInOpenConfirm = 2,
+    /// Receive Unexpected Message in Established State
+    UnexpectedMessageInEstablished = 3,
+}
+
+impl TryFrom<u8> for FiniteStateMachineSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use FiniteStateMachineSubcode::*;
+        match value {
+            0 => Ok(Unspecified),
+            1 => Ok(UnexpectedMessageInOpenSent),
+            2 => Ok(UnexpectedMessageInOpenConfirm),
+            3 => Ok(UnexpectedMessageInEstablished),
+            _ => Err(unknown_subcode("Finite State Machine Error subcode", value)),
+        }
+    }
+}
+
+impl fmt::Display for FiniteStateMachineSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FiniteStateMachineSubcode::*;
+        let s = match self {
+            Unspecified => "Unspecified Error",
+            UnexpectedMessageInOpenSent => "Receive Unexpected Message in OpenSent State",
+            UnexpectedMessageInOpenConfirm => "Receive Unexpected Message in OpenConfirm State",
+            UnexpectedMessageInEstablished => "Receive Unexpected Message in Established State",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Error Subcodes of a Cease.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum CeaseSubcode {
+    /// Unspecific
+    Unspecific = 0,
+    /// Maximum Number of Prefixes Reached [RFC4486]
+    MaximumPrefixesReached = 1,
+    /// Administrative Shutdown [RFC4486]
+    AdministrativeShutdown = 2,
+    /// Peer De-configured [RFC4486]
+    PeerDeconfigured = 3,
+    /// Administrative Reset [RFC4486]
+    AdministrativeReset = 4,
+    /// Connection Rejected [RFC4486]
+    ConnectionRejected = 5,
+    /// Other Configuration Change [RFC4486]
+    OtherConfigurationChange = 6,
+    /// Connection Collision Resolution [RFC4486]
+    ConnectionCollisionResolution = 7,
+    /// Out of Resources [RFC4486]
+    OutOfResources = 8,
+    /// Hard Reset [RFC8538]
+    HardReset = 9,
+    /// BFD Down [RFC9384]
+    BfdDown = 10,
+}
+
+impl TryFrom<u8> for CeaseSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use CeaseSubcode::*;
+        match value {
+            0 => Ok(Unspecific),
+            1 => Ok(MaximumPrefixesReached),
+            2 => Ok(AdministrativeShutdown),
+            3 => Ok(PeerDeconfigured),
+            4 => Ok(AdministrativeReset),
+            5 => Ok(ConnectionRejected),
+            6 => Ok(OtherConfigurationChange),
+            7 => Ok(ConnectionCollisionResolution),
+            8 => Ok(OutOfResources),
+            9 => Ok(HardReset),
+            10 => Ok(BfdDown),
+            _ => Err(unknown_subcode("Cease subcode", value)),
+        }
+    }
+}
+
+impl fmt::Display for CeaseSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CeaseSubcode::*;
+        let s = match self {
+            Unspecific => "Unspecific",
+            MaximumPrefixesReached => "Maximum Number of Prefixes Reached",
+            AdministrativeShutdown => "Administrative Shutdown",
+            PeerDeconfigured => "Peer De-configured",
+            AdministrativeReset => "Administrative Reset",
+            ConnectionRejected => "Connection Rejected",
+            OtherConfigurationChange => "Other Configuration Change",
+            ConnectionCollisionResolution => "Connection Collision Resolution",
+            OutOfResources => "Out of Resources",
+            HardReset => "Hard Reset",
+            BfdDown => "BFD Down",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Error Subcodes of a ROUTE-REFRESH Message Error.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum RouteRefreshMessageSubcode {
+    /// Invalid Message Length [RFC7313]
+    InvalidMessageLength = 1,
+}
+
+impl TryFrom<u8> for RouteRefreshMessageSubcode {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(RouteRefreshMessageSubcode::InvalidMessageLength),
+            _ => Err(unknown_subcode(
+                "ROUTE-REFRESH Message Error subcode",
+                value,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RouteRefreshMessageSubcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RouteRefreshMessageSubcode::InvalidMessageLength => {
+                write!(f, "Invalid Message Length")
+            }
+        }
+    }
+}
@@ -7,13 +7,14 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::io::{Error, ErrorKind, Read, Write};
+use std::net::Ipv4Addr;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::*;
 
 /// Represents a BGP Open message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Open {
     /// Indicates the protocol version number of the message. The current BGP version number is 4.
     pub version: u8,
@@ -63,6 +64,78 @@ impl Open {
         }
     }
 
+    /// The ASN of the sender, taken from the 4-byte ASN capability when present.
+    pub fn asn(&self) -> u32 {
+        self.parameters
+            .iter()
+            .filter_map(|parameter| match parameter {
+                OpenParameter::Capabilities(caps) => Some(caps),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|cap| match cap {
+                OpenCapability::FourByteASN(asn) => Some(*asn),
+                _ => None,
+            })
+            .next()
+            .unwrap_or_else(|| u32::from(self.peer_asn))
+    }
+
+    /// Create the OPEN message a speaker sends for the given configuration.
+    ///
+    /// The 4-byte ASN capability is always advertised, and ASNs that do not fit in
+    /// the 2-byte field are replaced with AS_TRANS.
+    /// ```
+    /// use bgp_rs::{Open, OpenCapability, OpenParameter, PeerConfig};
+    ///
+    /// let config = PeerConfig::new(4200000000, "10.0.0.1".parse().unwrap());
+    /// let open = Open::from_config(&config);
+    /// assert_eq!(open.peer_asn, 23456);
+    /// assert_eq!(open.identifier, 0x0a00_0001);
+    /// match &open.parameters[0] {
+    ///     OpenParameter::Capabilities(caps) => assert!(caps.iter().any(|cap| match cap {
+    ///         OpenCapability::FourByteASN(asn) => *asn == 4200000000,
+    ///         _ => false,
+    ///     })),
+    ///     _ => unreachable!(),
+    /// }
+    /// ```
+    pub fn from_config(config: &PeerConfig) -> Open {
+        let mut capabilities: Vec<OpenCapability> = config
+            .families
+            .iter()
+            .map(|family| OpenCapability::MultiProtocol(*family))
+            .collect();
+        if config.route_refresh {
+            capabilities.push(OpenCapability::RouteRefresh);
+        }
+        if !config.extended_next_hop.is_empty() {
+            capabilities.push(OpenCapability::ExtendedNextHop(
+                config.extended_next_hop.clone(),
+            ));
+        }
+        if let Some(role) = config.role {
+            capabilities.push(OpenCapability::BGPRole(role));
+        }
+        capabilities.push(OpenCapability::FourByteASN(config.local_asn));
+        if !config.add_path.is_empty() {
+            capabilities.push(OpenCapability::AddPath(config.add_path.clone()));
+        }
+
+        let peer_asn = if config.local_asn > u32::from(std::u16::MAX) {
+            AS_TRANS as u16
+        } else {
+            config.local_asn as u16
+        };
+        Open {
+            version: 4,
+            peer_asn,
+            hold_timer: config.hold_time,
+            identifier: u32::from(config.router_id),
+            parameters: vec![OpenParameter::Capabilities(capabilities)],
+        }
+    }
+
     /// Encode message to bytes
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
         buf.write_u8(self.version)?;
@@ -88,6 +161,54 @@ impl Open {
     }
 }
 
+/// The local settings of a BGP speaker towards a peer, used to build its OPEN message.
+#[derive(Clone, Debug)]
+pub struct PeerConfig {
+    /// The ASN of the local speaker, may be a 4-byte ASN.
+    pub local_asn: u32,
+
+    /// The ASN the peer is expected to have, any ASN is accepted when None.
+    pub peer_asn: Option<u32>,
+
+    /// The BGP Identifier of the local speaker.
+    pub router_id: Ipv4Addr,
+
+    /// The proposed Hold Time in seconds.
+    pub hold_time: u16,
+
+    /// The AFI/SAFIs to advertise Multiprotocol capabilities for.
+    pub families: Vec<(AFI, SAFI)>,
+
+    /// The AFI/SAFIs to advertise ADD-PATH support for.
+    pub add_path: Vec<(AFI, SAFI, AddPathDirection)>,
+
+    /// Advertise support for route refresh.
+    pub route_refresh: bool,
+
+    /// The AFI/SAFIs that accept a next hop of another AFI (RFC8950).
+    pub extended_next_hop: Vec<(AFI, SAFI, AFI)>,
+
+    /// The BGP Role of the local speaker (RFC9234).
+    pub role: Option<BGPRole>,
+}
+
+impl PeerConfig {
+    /// Create a configuration for IPv4 Unicast with a Hold Time of 90 seconds and route refresh.
+    pub fn new(local_asn: u32, router_id: Ipv4Addr) -> PeerConfig {
+        PeerConfig {
+            local_asn,
+            peer_asn: None,
+            router_id,
+            hold_time: 90,
+            families: vec![(AFI::IPV4, SAFI::Unicast)],
+            add_path: vec![],
+            route_refresh: true,
+            extended_next_hop: vec![],
+            role: None,
+        }
+    }
+}
+
 /// The direction which an ADD-PATH capabilty indicates a peer can provide additional paths.
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 #[repr(u8)]
@@ -102,6 +223,24 @@ pub enum AddPathDirection {
     SendReceivePaths = 3,
 }
 
+impl AddPathDirection {
+    /// The direction that remains when the peer supports the given direction.
+    /// Paths can only be received if the peer sends them and vice versa.
+    pub fn negotiate(self, peer_direction: AddPathDirection) -> Option<AddPathDirection> {
+        use AddPathDirection::*;
+        let receive = (self == ReceivePaths || self == SendReceivePaths)
+            && (peer_direction == SendPaths || peer_direction == SendReceivePaths);
+        let send = (self == SendPaths || self == SendReceivePaths)
+            && (peer_direction == ReceivePaths || peer_direction == SendReceivePaths);
+        match (receive, send) {
+            (true, true) => Some(SendReceivePaths),
+            (true, false) => Some(ReceivePaths),
+            (false, true) => Some(SendPaths),
+            (false, false) => None,
+        }
+    }
+}
+
 impl TryFrom<u8> for AddPathDirection {
     type Error = Error;
 
@@ -121,8 +260,69 @@ impl TryFrom<u8> for AddPathDirection {
     }
 }
 
+/// The role of a BGP speaker in relation to its peer. Defined in [RFC9234](http://www.iana.org/go/rfc9234).
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum BGPRole {
+    /// The local AS is a transit provider of the peer.
+    Provider = 0,
+
+    /// The local AS is a Route Server and the peer is a Route Server Client.
+    RouteServer = 1,
+
+    /// The local AS is a Route Server Client and the peer is a Route Server.
+    RouteServerClient = 2,
+
+    /// The local AS is a transit customer of the peer.
+    Customer = 3,
+
+    /// The local AS and the peer are lateral peers.
+    Peer = 4,
+}
+
+impl BGPRole {
+    /// Returns whether a peer announcing `peer_role` is a valid counterpart of this role.
+    ///
+    /// ```
+    /// use bgp_rs::BGPRole;
+    ///
+    /// assert!(BGPRole::Provider.is_compatible_with(BGPRole::Customer));
+    /// assert!(BGPRole::Peer.is_compatible_with(BGPRole::Peer));
+    /// assert!(!BGPRole::Customer.is_compatible_with(BGPRole::Peer));
+    /// ```
+    pub fn is_compatible_with(self, peer_role: BGPRole) -> bool {
+        use BGPRole::*;
+        match (self, peer_role) {
+            (Provider, Customer)
+            | (Customer, Provider)
+            | (RouteServer, RouteServerClient)
+            | (RouteServerClient, RouteServer)
+            | (Peer, Peer) => true,
+            _ => false,
+        }
+    }
+}
+
+impl TryFrom<u8> for BGPRole {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(BGPRole::Provider),
+            1 => Ok(BGPRole::RouteServer),
+            2 => Ok(BGPRole::RouteServerClient),
+            3 => Ok(BGPRole::Customer),
+            4 => Ok(BGPRole::Peer),
+            _ => {
+                let msg = format!("Number {} does not represent a valid BGP Role.", value);
+                Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+            }
+        }
+    }
+}
+
 /// Represents a known capability held in an OpenParameter
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum OpenCapability {
     /// 1 - Indicates the speaker is willing to exchange multiple protocols over this session.
     MultiProtocol((AFI, SAFI)),
@@ -130,10 +330,16 @@ pub enum OpenCapability {
     RouteRefresh,
     /// 3 - Support for Outbound Route Filtering of specified AFI/SAFIs
     OutboundRouteFiltering(HashSet<(AFI, SAFI, u8, AddPathDirection)>),
+    /// 5 - Indicates the speaker can receive routes of an AFI/SAFI with a next hop of another AFI.
+    ExtendedNextHop(Vec<(AFI, SAFI, AFI)>),
+    /// 9 - Indicates the role of the speaker in relation to its peer.
+    BGPRole(BGPRole),
     /// 65 - Indicates the speaker supports 4 byte ASNs and includes the ASN of the speaker.
     FourByteASN(u32),
     /// 69 - Indicates the speaker supports sending/receiving multiple paths for a given prefix.
     AddPath(Vec<(AFI, SAFI, AddPathDirection)>),
+    /// 70 - Indicates the speaker supports Enhanced Route Refresh.
+    EnhancedRouteRefresh,
     /// Unknown (or unsupported) capability
     Unknown {
         /// The type of the capability.
@@ -148,7 +354,7 @@ pub enum OpenCapability {
 }
 
 impl OpenCapability {
-    fn parse(stream: &mut impl Read) -> Result<(u16, OpenCapability), Error> {
+    pub(crate) fn parse(stream: &mut impl Read) -> Result<(u16, OpenCapability), Error> {
         let cap_code = stream.read_u8()?;
         let cap_length = stream.read_u8()?;
         Ok((
@@ -200,6 +406,39 @@ impl OpenCapability {
                     }
                     OpenCapability::OutboundRouteFiltering(types)
                 }
+                // EXTENDED_NEXT_HOP_ENCODING
+                5 => {
+                    if cap_length % 6 != 0 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            "Extended Next Hop Encoding capability length must be divisible by 6",
+                        ));
+                    }
+                    let mut families = Vec::with_capacity(cap_length as usize / 6);
+                    for _ in 0..(cap_length / 6) {
+                        let afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
+                        let safi = stream.read_u16::<BigEndian>()?;
+                        let safi = SAFI::try_from(u8::try_from(safi).map_err(|_| {
+                            Error::new(
+                                ErrorKind::InvalidData,
+                                format!("Not a supported SAFI: '{}'", safi),
+                            )
+                        })?)?;
+                        let next_hop_afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
+                        families.push((afi, safi, next_hop_afi));
+                    }
+                    OpenCapability::ExtendedNextHop(families)
+                }
+                // BGP_ROLE
+                9 => {
+                    if cap_length != 1 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            "BGP Role capability must be 1 byte in length",
+                        ));
+                    }
+                    OpenCapability::BGPRole(BGPRole::try_from(stream.read_u8()?)?)
+                }
                 // 4_BYTE_ASN
                 65 => {
                     if cap_length != 4 {
@@ -227,6 +466,16 @@ impl OpenCapability {
                     }
                     OpenCapability::AddPath(add_paths)
                 }
+                // ENHANCED_ROUTE_REFRESH
+                70 => {
+                    if cap_length != 0 {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            "Enhanced Route Refresh capability must be 0 bytes in length",
+                        ));
+                    }
+                    OpenCapability::EnhancedRouteRefresh
+                }
                 _ => {
                     let mut value = vec![0; cap_length as usize];
                     stream.read_exact(&mut value)?;
@@ -242,13 +491,21 @@ impl OpenCapability {
 
     fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
         let mut cap_buf: Vec<u8> = Vec::with_capacity(20);
+        self.encode_capability(&mut cap_buf)?;
+        buf.write_u8(2)?; // Parameter Type
+        buf.write_u8(cap_buf.len() as u8)?;
+        buf.write_all(&cap_buf)
+    }
+
+    // Encode the capability (type, length, value) without the Optional Parameter header
+    pub(crate) fn encode_capability(&self, cap_buf: &mut impl Write) -> Result<(), Error> {
         match self {
             OpenCapability::MultiProtocol((afi, safi)) => {
                 cap_buf.write_u8(1)?; // Capability Type
                 cap_buf.write_u8(4)?; // Capability Length
-                cap_buf.write_u16::<BigEndian>(*afi as u16)?;
+                cap_buf.write_u16::<BigEndian>(u16::from(*afi))?;
                 cap_buf.write_u8(0)?; // Reserved
-                cap_buf.write_u8(*safi as u8)?;
+                cap_buf.write_u8(u8::from(*safi))?;
             }
             OpenCapability::RouteRefresh => {
                 cap_buf.write_u8(2)?; // Capability Type
@@ -261,15 +518,38 @@ impl OpenCapability {
                 for (i, orf) in orfs.iter().enumerate() {
                     let (afi, safi, orf_type, orf_direction) = orf;
                     if i == 0 {
-                        cap_buf.write_u16::<BigEndian>(*afi as u16)?;
+                        cap_buf.write_u16::<BigEndian>(u16::from(*afi))?;
                         cap_buf.write_u8(0)?; // Reserved
-                        cap_buf.write_u8(*safi as u8)?;
+                        cap_buf.write_u8(u8::from(*safi))?;
                         cap_buf.write_u8(num_of_orfs as u8)?;
                     }
                     cap_buf.write_u8(*orf_type)?;
                     cap_buf.write_u8(*orf_direction as u8)?;
                 }
             }
+            OpenCapability::ExtendedNextHop(families) => {
+                cap_buf.write_u8(5)?; // Capability Type
+                if families.len() * 6 > std::u8::MAX as usize {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "Cannot encode Extended Next Hop with too many AFIs {}",
+                            families.len()
+                        ),
+                    ));
+                }
+                cap_buf.write_u8(families.len() as u8 * 6)?; // Capability Length
+                for (afi, safi, next_hop_afi) in families {
+                    cap_buf.write_u16::<BigEndian>(u16::from(*afi))?;
+                    cap_buf.write_u16::<BigEndian>(u16::from(u8::from(*safi)))?;
+                    cap_buf.write_u16::<BigEndian>(u16::from(*next_hop_afi))?;
+                }
+            }
+            OpenCapability::BGPRole(role) => {
+                cap_buf.write_u8(9)?; // Capability Type
+                cap_buf.write_u8(1)?; // Capability Length
+                cap_buf.write_u8(*role as u8)?;
+            }
             OpenCapability::FourByteASN(asn) => {
                 cap_buf.write_u8(65)?; // Capability Type
                 cap_buf.write_u8(4)?; // Capability Length
@@ -288,11 +568,15 @@ impl OpenCapability {
                 }
                 cap_buf.write_u8(add_paths.len() as u8 * 4)?; // Capability Length
                 for p in add_paths.iter() {
-                    cap_buf.write_u16::<BigEndian>(p.0 as u16)?;
-                    cap_buf.write_u8(p.1 as u8)?;
+                    cap_buf.write_u16::<BigEndian>(u16::from(p.0))?;
+                    cap_buf.write_u8(u8::from(p.1))?;
                     cap_buf.write_u8(p.2 as u8)?;
                 }
             }
+            OpenCapability::EnhancedRouteRefresh => {
+                cap_buf.write_u8(70)?; // Capability Type
+                cap_buf.write_u8(0)?; // Capability Length
+            }
             OpenCapability::Unknown {
                 cap_code,
                 cap_length,
@@ -300,17 +584,15 @@ impl OpenCapability {
             } => {
                 cap_buf.write_u8(*cap_code)?;
                 cap_buf.write_u8(*cap_length)?;
-                cap_buf.write_all(&value)?;
+                cap_buf.write_all(value)?;
             }
         }
-        buf.write_u8(2)?; // Parameter Type
-        buf.write_u8(cap_buf.len() as u8)?;
-        buf.write_all(&cap_buf)
+        Ok(())
     }
 }
 
 /// Represents a parameter in the optional parameter section of an Open message.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum OpenParameter {
     /// A list of capabilities supported by the sender.
     Capabilities(Vec<OpenCapability>),
@@ -388,7 +670,7 @@ impl OpenParameter {
             } => {
                 buf.write_u8(*param_type)?;
                 buf.write_u8(*param_length)?;
-                buf.write_all(&value)
+                buf.write_all(value)
             }
         }
     }
@@ -405,17 +687,20 @@ pub struct Capabilities {
     pub ROUTE_REFRESH_SUPPORT: bool,
     /// 3 - Outbound Route Filtering Capability
     pub OUTBOUND_ROUTE_FILTERING_SUPPORT: HashSet<(AFI, SAFI, u8, AddPathDirection)>,
-    /// 5 - Support for reading NLRI extended with a Path Identifier
+    /// 5 - Extended Next Hop Encoding, the next hop AFI accepted per AFI/SAFI
     pub EXTENDED_NEXT_HOP_ENCODING: HashMap<(AFI, SAFI), AFI>,
     /// 7 - BGPsec
     pub BGPSEC_SUPPORT: bool,
     /// 8 - Multiple Labels
     pub MULTIPLE_LABELS_SUPPORT: HashMap<(AFI, SAFI), u8>,
+    /// 9 - BGP Role
+    pub BGP_ROLE: Option<BGPRole>,
     /// 64 - Graceful Restart
     pub GRACEFUL_RESTART_SUPPORT: HashSet<(AFI, SAFI)>,
     /// 65 - Support for 4-octet AS number capability.
     pub FOUR_OCTET_ASN_SUPPORT: bool,
-    /// 69 - ADD_PATH
+    /// 69 - ADD_PATH, the directions in which the speaker these capabilities belong to sends
+    /// & receives Path Identifiers (see `ADVERTISED`)
     pub ADD_PATH_SUPPORT: HashMap<(AFI, SAFI), AddPathDirection>,
     /// Support for reading NLRI extended with a Path Identifier
     pub EXTENDED_PATH_NLRI_SUPPORT: bool,
@@ -423,12 +708,20 @@ pub struct Capabilities {
     pub ENHANCED_ROUTE_REFRESH_SUPPORT: bool,
     /// 71 - Long-Lived Graceful Restart
     pub LONG_LIVED_GRACEFUL_RESTART: bool,
+    /// Whether these capabilities were advertised by a speaker in its OPEN message (see
+    /// `from_parameters`), rather than being those of the local speaker (such as negotiated
+    /// ones). The ADD_PATH_SUPPORT directions are those of the advertising speaker in the
+    /// first case and those of the local speaker otherwise.
+    pub ADVERTISED: bool,
 }
 
 impl Capabilities {
     /// Convert from a collection of Open Parameters
     pub fn from_parameters(parameters: Vec<OpenParameter>) -> Self {
-        let mut capabilities = Capabilities::default();
+        let mut capabilities = Capabilities {
+            ADVERTISED: true,
+            ..Capabilities::default()
+        };
 
         for parameter in parameters {
             if let OpenParameter::Capabilities(caps) = parameter {
@@ -443,6 +736,16 @@ impl Capabilities {
                         OpenCapability::OutboundRouteFiltering(families) => {
                             capabilities.OUTBOUND_ROUTE_FILTERING_SUPPORT = families;
                         }
+                        OpenCapability::ExtendedNextHop(families) => {
+                            for (afi, safi, next_hop_afi) in families {
+                                capabilities
+                                    .EXTENDED_NEXT_HOP_ENCODING
+                                    .insert((afi, safi), next_hop_afi);
+                            }
+                        }
+                        OpenCapability::BGPRole(role) => {
+                            capabilities.BGP_ROLE = Some(role);
+                        }
                         OpenCapability::FourByteASN(_) => {
                             capabilities.FOUR_OCTET_ASN_SUPPORT = true;
                         }
@@ -454,6 +757,9 @@ impl Capabilities {
                                     .insert((path.0, path.1), path.2);
                             }
                         }
+                        OpenCapability::EnhancedRouteRefresh => {
+                            capabilities.ENHANCED_ROUTE_REFRESH_SUPPORT = true;
+                        }
                         // Ignore unimplemented capabilities
                         _ => (),
                     }
@@ -463,6 +769,352 @@ impl Capabilities {
 
         capabilities
     }
+
+    /// Whether routes of the AFI/SAFI are sent with Path Identifiers, when ADD-PATH was
+    /// negotiated for sending them (RFC7911).
+    ///
+    /// The directions advertised by a speaker (see `ADVERTISED`) are its own, so routes are
+    /// only sent to it with Path Identifiers for the families it advertised receiving them for.
+    /// Capabilities without any per-family ADD-PATH entries fall back to
+    /// `EXTENDED_PATH_NLRI_SUPPORT` for every family.
+    /// ```
+    /// use bgp_rs::{AddPathDirection, Capabilities, OpenCapability, OpenParameter, AFI, SAFI};
+    ///
+    /// let mut capabilities = Capabilities::default();
+    /// capabilities
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV4, SAFI::Mpls), AddPathDirection::SendReceivePaths);
+    /// capabilities
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV6, SAFI::Unicast), AddPathDirection::ReceivePaths);
+    /// assert!(capabilities.sends_path_ids(AFI::IPV4, SAFI::Mpls));
+    /// assert!(!capabilities.sends_path_ids(AFI::IPV6, SAFI::Unicast));
+    /// assert!(!capabilities.sends_path_ids(AFI::IPV4, SAFI::Unicast));
+    ///
+    /// // A peer that only sends Path Identifiers
+    /// let advertised = Capabilities::from_parameters(vec![OpenParameter::Capabilities(vec![
+    ///     OpenCapability::AddPath(vec![(AFI::IPV4, SAFI::Mpls, AddPathDirection::SendPaths)]),
+    /// ])]);
+    /// assert!(!advertised.sends_path_ids(AFI::IPV4, SAFI::Mpls));
+    /// ```
+    pub fn sends_path_ids(&self, afi: AFI, safi: SAFI) -> bool {
+        match self.ADD_PATH_SUPPORT.get(&(afi, safi)) {
+            Some(AddPathDirection::SendReceivePaths) => true,
+            Some(AddPathDirection::SendPaths) => !self.ADVERTISED,
+            Some(AddPathDirection::ReceivePaths) => self.ADVERTISED,
+            None => self.EXTENDED_PATH_NLRI_SUPPORT && self.ADD_PATH_SUPPORT.is_empty(),
+        }
+    }
+
+    /// Whether routes of the AFI/SAFI are received with Path Identifiers, when ADD-PATH was
+    /// negotiated for receiving them (RFC7911).
+    ///
+    /// The directions advertised by a speaker (see `ADVERTISED`) are its own, so the routes of
+    /// every family it advertised ADD-PATH for are read with Path Identifiers, whichever
+    /// speaker sent them. Capabilities without any per-family ADD-PATH entries fall back to
+    /// `EXTENDED_PATH_NLRI_SUPPORT` for every family.
+    /// ```
+    /// use bgp_rs::{AddPathDirection, Capabilities, OpenCapability, OpenParameter, AFI, SAFI};
+    ///
+    /// let mut capabilities = Capabilities::default();
+    /// capabilities.EXTENDED_PATH_NLRI_SUPPORT = true;
+    /// assert!(capabilities.receives_path_ids(AFI::IPV6, SAFI::Unicast));
+    ///
+    /// capabilities
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV4, SAFI::Unicast), AddPathDirection::ReceivePaths);
+    /// capabilities
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV4, SAFI::Mpls), AddPathDirection::SendPaths);
+    /// assert!(capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    /// assert!(!capabilities.receives_path_ids(AFI::IPV4, SAFI::Mpls));
+    /// assert!(!capabilities.receives_path_ids(AFI::IPV6, SAFI::Unicast));
+    ///
+    /// // A peer that only sends Path Identifiers
+    /// let advertised = Capabilities::from_parameters(vec![OpenParameter::Capabilities(vec![
+    ///     OpenCapability::AddPath(vec![(AFI::IPV4, SAFI::Mpls, AddPathDirection::SendPaths)]),
+    /// ])]);
+    /// assert!(advertised.receives_path_ids(AFI::IPV4, SAFI::Mpls));
+    /// assert!(!advertised.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    /// ```
+    pub fn receives_path_ids(&self, afi: AFI, safi: SAFI) -> bool {
+        match self.ADD_PATH_SUPPORT.get(&(afi, safi)) {
+            Some(_) if self.ADVERTISED => true,
+            Some(AddPathDirection::ReceivePaths) | Some(AddPathDirection::SendReceivePaths) => true,
+            Some(AddPathDirection::SendPaths) => false,
+            None => self.EXTENDED_PATH_NLRI_SUPPORT && self.ADD_PATH_SUPPORT.is_empty(),
+        }
+    }
+}
+
+/// The effective parameters of a BGP session, negotiated from the capabilities advertised
+/// by the local speaker and its peer. This is what messages of the session should be parsed with.
+#[derive(Clone, Debug, Default)]
+pub struct NegotiatedCapabilities {
+    /// The capabilities supported by both speakers, from the perspective of the local speaker
+    /// (i.e. to parse the messages received from the peer with). The capabilities advertised
+    /// by either speaker as long as only one of them is known.
+    pub capabilities: Capabilities,
+
+    /// The agreed Hold Time in seconds, when negotiated from OPEN messages.
+    pub hold_time: Option<u16>,
+
+    /// The capabilities advertised by the local speaker, once known.
+    pub local: Option<Capabilities>,
+
+    /// The capabilities advertised by the peer, once known.
+    pub remote: Option<Capabilities>,
+}
+
+impl Capabilities {
+    /// Computes the session parameters from the local and the remote capabilities.
+    ///
+    /// Only address families advertised by both speakers remain, and the ADD-PATH direction
+    /// of a family is reduced to what the peer supports in the opposite direction.
+    /// The local BGP Role is kept only if it is compatible with the role of the peer.
+    /// ```
+    /// use bgp_rs::{AddPathDirection, Capabilities, AFI, SAFI};
+    ///
+    /// let mut local = Capabilities::default();
+    /// local.FOUR_OCTET_ASN_SUPPORT = true;
+    /// local.MP_BGP_SUPPORT.insert((AFI::IPV4, SAFI::Unicast));
+    /// local.MP_BGP_SUPPORT.insert((AFI::IPV6, SAFI::Unicast));
+    /// local
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV4, SAFI::Unicast), AddPathDirection::SendReceivePaths);
+    ///
+    /// let mut remote = Capabilities::default();
+    /// remote.MP_BGP_SUPPORT.insert((AFI::IPV4, SAFI::Unicast));
+    /// remote
+    ///     .ADD_PATH_SUPPORT
+    ///     .insert((AFI::IPV4, SAFI::Unicast), AddPathDirection::SendPaths);
+    ///
+    /// let negotiated = Capabilities::negotiate(&local, &remote).capabilities;
+    /// assert!(!negotiated.FOUR_OCTET_ASN_SUPPORT);
+    /// assert_eq!(negotiated.MP_BGP_SUPPORT.len(), 1);
+    /// assert_eq!(
+    ///     negotiated.ADD_PATH_SUPPORT.get(&(AFI::IPV4, SAFI::Unicast)),
+    ///     Some(&AddPathDirection::ReceivePaths)
+    /// );
+    /// assert!(negotiated.EXTENDED_PATH_NLRI_SUPPORT);
+    /// ```
+    pub fn negotiate(local: &Capabilities, remote: &Capabilities) -> NegotiatedCapabilities {
+        let add_paths: HashMap<(AFI, SAFI), AddPathDirection> = local
+            .ADD_PATH_SUPPORT
+            .iter()
+            .filter_map(|(family, direction)| {
+                remote
+                    .ADD_PATH_SUPPORT
+                    .get(family)
+                    .and_then(|remote_direction| direction.negotiate(*remote_direction))
+                    .map(|direction| (*family, direction))
+            })
+            .collect();
+        let outbound_route_filters = local
+            .OUTBOUND_ROUTE_FILTERING_SUPPORT
+            .iter()
+            .filter_map(|(afi, safi, orf_type, direction)| {
+                remote
+                    .OUTBOUND_ROUTE_FILTERING_SUPPORT
+                    .iter()
+                    .filter(|orf| orf.0 == *afi && orf.1 == *safi && orf.2 == *orf_type)
+                    .filter_map(|orf| direction.negotiate(orf.3))
+                    .next()
+                    .map(|direction| (*afi, *safi, *orf_type, direction))
+            })
+            .collect();
+        let bgp_role = match (local.BGP_ROLE, remote.BGP_ROLE) {
+            (Some(role), Some(peer_role)) if role.is_compatible_with(peer_role) => Some(role),
+            _ => None,
+        };
+
+        let capabilities = Capabilities {
+            MP_BGP_SUPPORT: local
+                .MP_BGP_SUPPORT
+                .intersection(&remote.MP_BGP_SUPPORT)
+                .cloned()
+                .collect(),
+            ROUTE_REFRESH_SUPPORT: local.ROUTE_REFRESH_SUPPORT && remote.ROUTE_REFRESH_SUPPORT,
+            OUTBOUND_ROUTE_FILTERING_SUPPORT: outbound_route_filters,
+            EXTENDED_NEXT_HOP_ENCODING: local
+                .EXTENDED_NEXT_HOP_ENCODING
+                .iter()
+                .filter(|(family, afi)| remote.EXTENDED_NEXT_HOP_ENCODING.get(family) == Some(afi))
+                .map(|(family, afi)| (*family, *afi))
+                .collect(),
+            BGPSEC_SUPPORT: local.BGPSEC_SUPPORT && remote.BGPSEC_SUPPORT,
+            MULTIPLE_LABELS_SUPPORT: local
+                .MULTIPLE_LABELS_SUPPORT
+                .iter()
+                .filter_map(|(family, count)| {
+                    remote
+                        .MULTIPLE_LABELS_SUPPORT
+                        .get(family)
+                        .map(|remote_count| (*family, *count.min(remote_count)))
+                })
+                .collect(),
+            BGP_ROLE: bgp_role,
+            GRACEFUL_RESTART_SUPPORT: local
+                .GRACEFUL_RESTART_SUPPORT
+                .intersection(&remote.GRACEFUL_RESTART_SUPPORT)
+                .cloned()
+                .collect(),
+            FOUR_OCTET_ASN_SUPPORT: local.FOUR_OCTET_ASN_SUPPORT && remote.FOUR_OCTET_ASN_SUPPORT,
+            EXTENDED_PATH_NLRI_SUPPORT: add_paths.values().any(|direction| {
+                *direction == AddPathDirection::ReceivePaths
+                    || *direction == AddPathDirection::SendReceivePaths
+            }),
+            ADD_PATH_SUPPORT: add_paths,
+            ENHANCED_ROUTE_REFRESH_SUPPORT: local.ENHANCED_ROUTE_REFRESH_SUPPORT
+                && remote.ENHANCED_ROUTE_REFRESH_SUPPORT,
+            LONG_LIVED_GRACEFUL_RESTART: local.LONG_LIVED_GRACEFUL_RESTART
+                && remote.LONG_LIVED_GRACEFUL_RESTART,
+            ADVERTISED: false,
+        };
+
+        NegotiatedCapabilities {
+            capabilities,
+            hold_time: None,
+            local: Some(local.clone()),
+            remote: Some(remote.clone()),
+        }
+    }
+}
+
+impl NegotiatedCapabilities {
+    /// Negotiates the session parameters from the OPEN message sent by the local speaker
+    /// and the OPEN message received from the peer.
+    pub fn from_opens(local: &Open, remote: &Open) -> Result<NegotiatedCapabilities, Error> {
+        let negotiated = Capabilities::negotiate(
+            &Capabilities::from_parameters(local.parameters.clone()),
+            &Capabilities::from_parameters(remote.parameters.clone()),
+        );
+        negotiated.with_hold_time(local.hold_timer, remote.hold_timer)
+    }
+
+    /// Sets the Hold Time to the smaller of both proposals. A proposal of one or two seconds
+    /// is unacceptable (RFC4271) and results in an error carrying the Notification to send.
+    pub fn with_hold_time(
+        mut self,
+        local: u16,
+        remote: u16,
+    ) -> Result<NegotiatedCapabilities, Error> {
+        self.hold_time = Some(negotiate_hold_time(local, remote)?);
+        Ok(self)
+    }
+
+    /// Merges the parameters of an OPEN message, as seen when reading both directions of a
+    /// session without knowing which speaker sent it.
+    ///
+    /// The first OPEN of a session is taken as the one of the local speaker and the next one as
+    /// the one of the peer, see `apply_local_open` & `apply_remote_open`. An OPEN following
+    /// both starts a new session.
+    pub fn apply_open(&mut self, open: &Open) -> Result<(), Error> {
+        if self.local.is_some() && self.remote.is_none() {
+            self.apply_remote_open(open)
+        } else {
+            self.apply_local_open(open)
+        }
+    }
+
+    /// Merges the parameters of the OPEN message sent by the local speaker. The session is
+    /// negotiated once the OPEN of the peer is known as well, until then the capabilities are
+    /// the advertised ones. An OPEN of the local speaker that was already known starts a new
+    /// session, forgetting the OPEN of the peer.
+    pub fn apply_local_open(&mut self, open: &Open) -> Result<(), Error> {
+        if self.local.is_some() {
+            self.remote = None;
+        }
+        let advertised = Capabilities::from_parameters(open.parameters.clone());
+        let negotiated = match &self.remote {
+            Some(remote) => self.negotiate_with(&advertised, remote, open.hold_timer)?,
+            None => self.advertised(&advertised, open.hold_timer),
+        };
+        *self = NegotiatedCapabilities {
+            local: Some(advertised),
+            ..negotiated
+        };
+        Ok(())
+    }
+
+    /// Merges the parameters of the OPEN message received from the peer, like
+    /// `apply_local_open`.
+    pub fn apply_remote_open(&mut self, open: &Open) -> Result<(), Error> {
+        if self.remote.is_some() {
+            self.local = None;
+        }
+        let advertised = Capabilities::from_parameters(open.parameters.clone());
+        let negotiated = match &self.local {
+            Some(local) => self.negotiate_with(local, &advertised, open.hold_timer)?,
+            None => self.advertised(&advertised, open.hold_timer),
+        };
+        *self = NegotiatedCapabilities {
+            remote: Some(advertised),
+            ..negotiated
+        };
+        Ok(())
+    }
+
+    // The session parameters while only one OPEN is known
+    fn advertised(&self, advertised: &Capabilities, hold_time: u16) -> NegotiatedCapabilities {
+        NegotiatedCapabilities {
+            capabilities: advertised.clone(),
+            hold_time: Some(hold_time),
+            local: self.local.clone(),
+            remote: self.remote.clone(),
+        }
+    }
+
+    // Negotiates the session once both OPEN messages are known, the Hold Time proposed by the
+    // OPEN known first was kept until then
+    fn negotiate_with(
+        &self,
+        local: &Capabilities,
+        remote: &Capabilities,
+        hold_time: u16,
+    ) -> Result<NegotiatedCapabilities, Error> {
+        Capabilities::negotiate(local, remote)
+            .with_hold_time(self.hold_time.unwrap_or(hold_time), hold_time)
+    }
+
+    /// The capabilities to parse the messages sent by the local speaker with: the session from
+    /// the perspective of the peer, once the OPEN messages of both speakers are known.
+    /// ```
+    /// use bgp_rs::{AddPathDirection, NegotiatedCapabilities, Open, PeerConfig, AFI, SAFI};
+    ///
+    /// let mut config = PeerConfig::new(65000, "192.0.2.1".parse().unwrap());
+    /// config
+    ///     .add_path
+    ///     .push((AFI::IPV4, SAFI::Unicast, AddPathDirection::ReceivePaths));
+    /// let local = Open::from_config(&config);
+    /// config.local_asn = 65001;
+    /// config.add_path[0].2 = AddPathDirection::SendPaths;
+    /// let remote = Open::from_config(&config);
+    ///
+    /// let mut negotiated = NegotiatedCapabilities::default();
+    /// negotiated.apply_remote_open(&remote).unwrap();
+    /// assert!(negotiated.sent_capabilities().is_none());
+    /// negotiated.apply_local_open(&local).unwrap();
+    ///
+    /// // Only the peer sends Path Identifiers
+    /// assert!(negotiated.capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    /// let sent = negotiated.sent_capabilities().unwrap();
+    /// assert!(!sent.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    /// ```
+    pub fn sent_capabilities(&self) -> Option<Capabilities> {
+        match (&self.local, &self.remote) {
+            (Some(local), Some(remote)) => {
+                Some(Capabilities::negotiate(remote, local).capabilities)
+            }
+            _ => None,
+        }
+    }
+
+    /// The interval between KEEPALIVE messages, one third of the Hold Time.
+    /// None when KEEPALIVEs should not be sent (a Hold Time of zero).
+    pub fn keepalive_interval(&self) -> Option<u16> {
+        self.hold_time.and_then(keepalive_interval)
+    }
 }
 
 #[cfg(test)]
@@ -505,6 +1157,15 @@ mod tests {
                 OpenCapability::MultiProtocol((AFI::IPV6, SAFI::Unicast)),
             ]),
             OpenParameter::Capabilities(vec![OpenCapability::RouteRefresh]),
+            OpenParameter::Capabilities(vec![OpenCapability::ExtendedNextHop(vec![
+                (AFI::IPV4, SAFI::Unicast, AFI::IPV6),
+                (AFI::IPV4, SAFI::MplsVpn, AFI::IPV6),
+            ])]),
+            OpenParameter::Capabilities(vec![OpenCapability::EnhancedRouteRefresh]),
+            OpenParameter::Capabilities(vec![
+                OpenCapability::BGPRole(BGPRole::Customer),
+                OpenCapability::BGPRole(BGPRole::RouteServerClient),
+            ]),
             OpenParameter::Capabilities(vec![
                 OpenCapability::FourByteASN(3200000001),
                 OpenCapability::FourByteASN(3200000002),
@@ -530,6 +1191,128 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_open_from_config() {
+        let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+        config.families.push((AFI::IPV6, SAFI::Unicast));
+        config.add_path = vec![(AFI::IPV4, SAFI::Unicast, AddPathDirection::ReceivePaths)];
+        config.role = Some(BGPRole::Customer);
+        let open = Open::from_config(&config);
+        assert_eq!(open.version, 4);
+        assert_eq!(open.peer_asn, 65000);
+        assert_eq!(open.hold_timer, 90);
+
+        let mut bytes = vec![];
+        open.encode(&mut bytes).unwrap();
+        let open = Open::parse(&mut std::io::Cursor::new(bytes)).unwrap();
+        let caps = Capabilities::from_parameters(open.parameters);
+        assert_eq!(caps.MP_BGP_SUPPORT.len(), 2);
+        assert!(caps.ROUTE_REFRESH_SUPPORT);
+        assert!(caps.FOUR_OCTET_ASN_SUPPORT);
+        assert_eq!(caps.BGP_ROLE, Some(BGPRole::Customer));
+        assert_eq!(
+            caps.ADD_PATH_SUPPORT.get(&(AFI::IPV4, SAFI::Unicast)),
+            Some(&AddPathDirection::ReceivePaths)
+        );
+
+        config.local_asn = 65536;
+        let open = Open::from_config(&config);
+        assert_eq!(open.peer_asn, AS_TRANS as u16);
+        assert_eq!(open.asn(), 65536);
+    }
+
+    #[test]
+    fn test_negotiate_opens() {
+        let mut local = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+        local.families.push((AFI::IPV6, SAFI::Unicast));
+        local.role = Some(BGPRole::Provider);
+        let mut remote = PeerConfig::new(65001, "2.2.2.2".parse().unwrap());
+        remote.hold_time = 30;
+        remote.route_refresh = false;
+        remote.role = Some(BGPRole::Customer);
+
+        let negotiated = NegotiatedCapabilities::from_opens(
+            &Open::from_config(&local),
+            &Open::from_config(&remote),
+        )
+        .unwrap();
+        assert_eq!(negotiated.hold_time, Some(30));
+        assert_eq!(negotiated.keepalive_interval(), Some(10));
+        let caps = negotiated.capabilities;
+        assert!(caps.FOUR_OCTET_ASN_SUPPORT);
+        assert!(!caps.ROUTE_REFRESH_SUPPORT);
+        assert_eq!(caps.MP_BGP_SUPPORT.len(), 1);
+        assert_eq!(caps.BGP_ROLE, Some(BGPRole::Provider));
+
+        remote.hold_time = 2;
+        let error = NegotiatedCapabilities::from_opens(
+            &Open::from_config(&local),
+            &Open::from_config(&remote),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_notification().code,
+            NotificationCode::OpenMessage(OpenMessageSubcode::UnacceptableHoldTime)
+        );
+    }
+
+    #[test]
+    fn test_apply_open() {
+        let mut local = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+        local.families.push((AFI::IPV6, SAFI::Unicast));
+        let mut remote = PeerConfig::new(65001, "2.2.2.2".parse().unwrap());
+        remote.hold_time = 30;
+
+        let mut negotiated = NegotiatedCapabilities::default();
+        negotiated.apply_open(&Open::from_config(&local)).unwrap();
+        assert!(negotiated.local.is_some() && negotiated.remote.is_none());
+        assert_eq!(negotiated.capabilities.MP_BGP_SUPPORT.len(), 2);
+        assert_eq!(negotiated.hold_time, Some(90));
+
+        negotiated.apply_open(&Open::from_config(&remote)).unwrap();
+        assert!(negotiated.local.is_some() && negotiated.remote.is_some());
+        assert_eq!(negotiated.capabilities.MP_BGP_SUPPORT.len(), 1);
+        assert_eq!(negotiated.hold_time, Some(30));
+
+        // The OPEN of a new session isn't negotiated against the previous session
+        negotiated.apply_open(&Open::from_config(&local)).unwrap();
+        assert!(negotiated.remote.is_none());
+        assert_eq!(negotiated.capabilities.MP_BGP_SUPPORT.len(), 2);
+        assert_eq!(negotiated.hold_time, Some(90));
+
+        // The OPEN of the peer is known first
+        let mut negotiated = NegotiatedCapabilities::default();
+        negotiated
+            .apply_remote_open(&Open::from_config(&remote))
+            .unwrap();
+        negotiated
+            .apply_local_open(&Open::from_config(&local))
+            .unwrap();
+        let expected = NegotiatedCapabilities::from_opens(
+            &Open::from_config(&local),
+            &Open::from_config(&remote),
+        )
+        .unwrap();
+        assert_eq!(negotiated.hold_time, expected.hold_time);
+        assert_eq!(
+            negotiated.capabilities.MP_BGP_SUPPORT,
+            expected.capabilities.MP_BGP_SUPPORT
+        );
+    }
+
+    #[test]
+    fn test_add_path_direction_negotiate() {
+        use AddPathDirection::*;
+        assert_eq!(
+            SendReceivePaths.negotiate(SendReceivePaths),
+            Some(SendReceivePaths)
+        );
+        assert_eq!(SendReceivePaths.negotiate(ReceivePaths), Some(SendPaths));
+        assert_eq!(ReceivePaths.negotiate(SendReceivePaths), Some(ReceivePaths));
+        assert_eq!(ReceivePaths.negotiate(ReceivePaths), None);
+        assert_eq!(SendPaths.negotiate(SendPaths), None);
+    }
+
     #[test]
     fn test_from_empty_parameters() {
         let caps = Capabilities::from_parameters(vec![]);
@@ -537,20 +1320,31 @@ mod tests {
         assert!(!caps.ROUTE_REFRESH_SUPPORT);
         assert!(!caps.FOUR_OCTET_ASN_SUPPORT);
         assert!(caps.GRACEFUL_RESTART_SUPPORT.is_empty());
+        assert!(caps.BGP_ROLE.is_none());
     }
 
     #[test]
     fn test_from_parameters() {
         let params = vec![OpenParameter::Capabilities(vec![
             OpenCapability::RouteRefresh,
+            OpenCapability::EnhancedRouteRefresh,
             OpenCapability::FourByteASN(65000 * 65000),
             OpenCapability::MultiProtocol((AFI::IPV4, SAFI::Unicast)),
             OpenCapability::MultiProtocol((AFI::IPV6, SAFI::Unicast)),
+            OpenCapability::BGPRole(BGPRole::Peer),
+            OpenCapability::ExtendedNextHop(vec![(AFI::IPV4, SAFI::Unicast, AFI::IPV6)]),
         ])];
         let caps = Capabilities::from_parameters(params);
 
         assert!(caps.ROUTE_REFRESH_SUPPORT);
+        assert!(caps.ENHANCED_ROUTE_REFRESH_SUPPORT);
         assert!(caps.FOUR_OCTET_ASN_SUPPORT);
         assert_eq!(caps.MP_BGP_SUPPORT.len(), 2);
+        assert_eq!(caps.BGP_ROLE, Some(BGPRole::Peer));
+        assert_eq!(
+            caps.EXTENDED_NEXT_HOP_ENCODING
+                .get(&(AFI::IPV4, SAFI::Unicast)),
+            Some(&AFI::IPV6)
+        );
     }
 }
@@ -0,0 +1,291 @@
+//! The `openbmp` mod provides the decoding of messages consumed from the Kafka topics of
+//! OpenBMP collectors (e.g. `openbmp.bmp_raw`)
+//! - The OpenBMP binary message bus header (version 1.7), describing the collector & router
+//! - The BMP message ([RFC7854](https://tools.ietf.org/html/rfc7854)) wrapped by the header
+//! - The BGP messages wrapped by BMP Route Monitoring & Peer Up Notification messages
+//!
+//! The BGP messages are tagged with their peer, so they can be parsed with `PeerMap::parse`.
+//!
+
+use std::io::{Error, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+const OPENBMP_MAGIC: &[u8; 4] = b"OBMP";
+const OPENBMP_MAJOR_VERSION: u8 = 1;
+
+const BMP_VERSION: u8 = 3;
+const BMP_COMMON_HEADER_LENGTH: usize = 6;
+const BMP_PER_PEER_HEADER_LENGTH: usize = 42;
+const BMP_ROUTE_MONITORING: u8 = 0;
+const BMP_PEER_UP_NOTIFICATION: u8 = 3;
+
+/// The binary message bus header of OpenBMP, preceding the BMP message of a Kafka message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OpenBmpHeader {
+    /// The (major, minor) version of the header, 1.7 at the time of writing.
+    pub version: (u8, u8),
+    /// The flags, 0x80 for router messages & 0x40 for routers with an IPv6 address.
+    pub flags: u8,
+    /// The type of the wrapped message, 12 for BMP messages.
+    pub message_type: u8,
+    /// The time (since the UNIX epoch) the collector received the message.
+    pub timestamp: Duration,
+    /// The hash identifying the collector.
+    pub collector_hash: [u8; 16],
+    /// The name of the collector, as configured by its admin.
+    pub collector_admin_id: String,
+    /// The hash identifying the router.
+    pub router_hash: [u8; 16],
+    /// The IP address of the router the BMP session is established with.
+    pub router_address: IpAddr,
+    /// The group of the router, as configured on the collector.
+    pub router_group: String,
+    /// The number of rows of the message, for parsed (non raw) messages.
+    pub row_count: u32,
+}
+
+/// A message consumed from an OpenBMP Kafka topic: the OpenBMP header & the BMP message.
+///
+/// ```no_run
+/// use bgp_rs::{Capabilities, Message, OpenBmpMessage, PeerMap};
+/// # let kafka_messages: Vec<Vec<u8>> = vec![];
+///
+/// let mut peers = PeerMap::new(Capabilities::default());
+/// for data in kafka_messages {
+///     let message = OpenBmpMessage::parse(&data).unwrap();
+///     if let Some((peer, bgp_messages)) = message.bgp_messages().unwrap() {
+///         for bgp_message in bgp_messages {
+///             if let Message::Update(update) = peers.parse(peer.address, peer.asn, bgp_message).unwrap() {
+///                 println!("{} AS{}: {}", message.header.router_address, peer.asn, update);
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OpenBmpMessage<'a> {
+    /// The OpenBMP message bus header
+    pub header: OpenBmpHeader,
+    /// The BMP message, including its Common Header
+    pub bmp: &'a [u8],
+}
+
+/// The peer a BMP message is about, from its Per-Peer Header.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BmpPeer {
+    /// The type of the peer (0 for Global Instance peers).
+    pub peer_type: u8,
+    /// The flags of the peer (e.g. 0x80 for IPv6 peers, 0x20 for 2-byte AS_PATHs).
+    pub flags: u8,
+    /// The Peer Distinguisher, the Route Distinguisher of the VRF of the peer.
+    pub distinguisher: u64,
+    /// The IP address of the peer.
+    pub address: IpAddr,
+    /// The ASN of the peer.
+    pub asn: u32,
+    /// The BGP Identifier of the peer.
+    pub bgp_id: Ipv4Addr,
+    /// The time (since the UNIX epoch) the router received the routes.
+    pub timestamp: Duration,
+}
+
+// The peer of a BMP message & the BGP messages it wraps
+type PeerMessages<'a> = (BmpPeer, Vec<&'a [u8]>);
+
+impl OpenBmpHeader {
+    /// Reads an OpenBMP message bus header, including the fields of newer minor versions
+    /// (which are skipped).
+    pub fn parse(stream: &mut impl Read) -> Result<OpenBmpHeader, Error> {
+        let mut magic = [0; 4];
+        stream.read_exact(&mut magic)?;
+        if &magic != OPENBMP_MAGIC {
+            return Err(invalid("Not an OpenBMP message".to_string()));
+        }
+        let version = (stream.read_u8()?, stream.read_u8()?);
+        if version.0 != OPENBMP_MAJOR_VERSION {
+            return Err(invalid(format!(
+                "Unsupported OpenBMP version {}.{}",
+                version.0, version.1
+            )));
+        }
+        let header_length = usize::from(stream.read_u16::<BigEndian>()?);
+        let mut buf = vec![0; header_length.saturating_sub(8)];
+        stream.read_exact(&mut buf)?;
+
+        let mut buf: &[u8] = &buf;
+        let _message_length = buf.read_u32::<BigEndian>()?;
+        let flags = buf.read_u8()?;
+        let message_type = buf.read_u8()?;
+        let seconds = buf.read_u32::<BigEndian>()?;
+        let micros = buf.read_u32::<BigEndian>()?;
+        let mut collector_hash = [0; 16];
+        buf.read_exact(&mut collector_hash)?;
+        let collector_admin_id = read_string(&mut buf)?;
+        let mut router_hash = [0; 16];
+        buf.read_exact(&mut router_hash)?;
+        let mut address = [0; 16];
+        buf.read_exact(&mut address)?;
+        let router_address = if flags & 0x40 == 0x40 {
+            IpAddr::V6(Ipv6Addr::from(address))
+        } else {
+            IpAddr::V4(Ipv4Addr::new(
+                address[12],
+                address[13],
+                address[14],
+                address[15],
+            ))
+        };
+        let router_group = read_string(&mut buf)?;
+        let row_count = buf.read_u32::<BigEndian>()?;
+
+        Ok(OpenBmpHeader {
+            version,
+            flags,
+            message_type,
+            timestamp: Duration::new(u64::from(seconds), micros.min(999_999) * 1000),
+            collector_hash,
+            collector_admin_id,
+            router_hash,
+            router_address,
+            router_group,
+            row_count,
+        })
+    }
+}
+
+impl<'a> OpenBmpMessage<'a> {
+    /// Decodes the value of a Kafka message, holding an OpenBMP header followed by its message.
+    pub fn parse(data: &'a [u8]) -> Result<OpenBmpMessage<'a>, Error> {
+        let mut buf = data;
+        let header = OpenBmpHeader::parse(&mut buf)?;
+        // The message length follows the magic, version & header length
+        let length = (&data[8..]).read_u32::<BigEndian>()? as usize;
+        if buf.len() < length {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                format!(
+                    "OpenBMP message of {} bytes holds {} bytes",
+                    length,
+                    buf.len()
+                ),
+            ));
+        }
+        Ok(OpenBmpMessage {
+            header,
+            bmp: &buf[..length],
+        })
+    }
+
+    /// The type of the BMP message (e.g. 0 for Route Monitoring), from its Common Header.
+    pub fn bmp_type(&self) -> Result<u8, Error> {
+        if self.bmp.len() < BMP_COMMON_HEADER_LENGTH {
+            return Err(invalid(format!(
+                "BMP message of {} bytes lacks its Common Header",
+                self.bmp.len()
+            )));
+        }
+        if self.bmp[0] != BMP_VERSION {
+            return Err(invalid(format!("Unsupported BMP version {}", self.bmp[0])));
+        }
+        Ok(self.bmp[5])
+    }
+
+    /// The peer & BGP messages (including their headers) wrapped by the BMP message: the UPDATE
+    /// of a Route Monitoring message, or the OPEN messages sent & received by the router of a
+    /// Peer Up Notification. None for other BMP messages.
+    pub fn bgp_messages(&self) -> Result<Option<PeerMessages<'a>>, Error> {
+        let bmp_type = self.bmp_type()?;
+        if bmp_type != BMP_ROUTE_MONITORING && bmp_type != BMP_PEER_UP_NOTIFICATION {
+            return Ok(None);
+        }
+        let mut buf = &self.bmp[BMP_COMMON_HEADER_LENGTH..];
+        if buf.len() < BMP_PER_PEER_HEADER_LENGTH {
+            return Err(invalid("BMP message lacks its Per-Peer Header".to_string()));
+        }
+        let peer = BmpPeer::parse(&mut buf)?;
+        let mut messages = Vec::with_capacity(2);
+        if bmp_type == BMP_ROUTE_MONITORING {
+            messages.push(split_bgp_message(&mut buf)?);
+        } else {
+            // Local address & the local & remote ports
+            if buf.len() < 20 {
+                return Err(invalid("Truncated BMP Peer Up Notification".to_string()));
+            }
+            buf = &buf[20..];
+            messages.push(split_bgp_message(&mut buf)?);
+            messages.push(split_bgp_message(&mut buf)?);
+        }
+        Ok(Some((peer, messages)))
+    }
+}
+
+impl BmpPeer {
+    fn parse(buf: &mut &[u8]) -> Result<BmpPeer, Error> {
+        let peer_type = buf.read_u8()?;
+        let flags = buf.read_u8()?;
+        let distinguisher = buf.read_u64::<BigEndian>()?;
+        let mut address = [0; 16];
+        buf.read_exact(&mut address)?;
+        let address = if flags & 0x80 == 0x80 {
+            IpAddr::V6(Ipv6Addr::from(address))
+        } else {
+            IpAddr::V4(Ipv4Addr::new(
+                address[12],
+                address[13],
+                address[14],
+                address[15],
+            ))
+        };
+        let asn = buf.read_u32::<BigEndian>()?;
+        let bgp_id = Ipv4Addr::from(buf.read_u32::<BigEndian>()?);
+        let seconds = buf.read_u32::<BigEndian>()?;
+        let micros = buf.read_u32::<BigEndian>()?;
+        Ok(BmpPeer {
+            peer_type,
+            flags,
+            distinguisher,
+            address,
+            asn,
+            bgp_id,
+            timestamp: Duration::new(u64::from(seconds), micros.min(999_999) * 1000),
+        })
+    }
+}
+
+// Splits the BGP message at the start of the buffer off, by the length in its header
+fn split_bgp_message<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let length = match buf.get(16..18) {
+        Some(length) => usize::from(u16::from_be_bytes([length[0], length[1]])),
+        None => return Err(invalid("Truncated BGP message in BMP message".to_string())),
+    };
+    if length < 19 || buf.len() < length {
+        return Err(invalid(format!(
+            "Bogus length {} of BGP message in BMP message",
+            length
+        )));
+    }
+    let (message, rest) = buf.split_at(length);
+    *buf = rest;
+    Ok(message)
+}
+
+// Reads a string preceded by its 2-byte length
+fn read_string(buf: &mut &[u8]) -> Result<String, Error> {
+    let length = usize::from(buf.read_u16::<BigEndian>()?);
+    if buf.len() < length {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "failed to fill whole buffer",
+        ));
+    }
+    let (value, rest) = buf.split_at(length);
+    *buf = rest;
+    Ok(String::from_utf8_lossy(value).into_owned())
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
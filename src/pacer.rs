@@ -0,0 +1,209 @@
+//! The `pacer` mod provides the pacing of outbound UPDATE messages (RFC4271 section 9.2.1.1)
+//! - Queueing of the UPDATE messages to send to each peer
+//! - Coalescing of the changes of a route, only its last announcement or withdrawal is sent
+//! - Release of the queued routes in batches, at most once per MinRouteAdvertisementInterval
+//!
+//! The interval is only checked when the caller invokes `poll` or `release`.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::rib::{announcement_updates, route_attributes};
+use crate::*;
+
+/// Paces the UPDATE messages sent to peers following the MinRouteAdvertisementInterval (MRAI).
+/// Peers are identified by any type, such as their address.
+///
+/// Only routes holding a plain prefix (see `RouteKey`) are coalesced. UPDATE messages holding
+/// other routes, or none (such as End-of-RIB markers), are sent as they are after the routes of
+/// their batch.
+///
+/// ```
+/// use bgp_rs::{NLRIEncoding, Pacer, PathAttribute, Update};
+/// use std::time::Duration;
+///
+/// let update = |next_hop: &str| {
+///     Update::new(
+///         vec![],
+///         vec![PathAttribute::NEXT_HOP(next_hop.parse().unwrap())],
+///         vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+///     )
+/// };
+/// let mut pacer = Pacer::new(Duration::from_secs(30));
+/// pacer.queue(1, &update("192.0.2.1"));
+/// assert_eq!(pacer.poll().len(), 1);
+///
+/// // Within the interval, only the last announcement of the route is kept
+/// pacer.queue(1, &update("192.0.2.2"));
+/// pacer.queue(1, &update("192.0.2.3"));
+/// assert!(pacer.poll().is_empty());
+/// let updates = pacer.flush(&1);
+/// assert_eq!(updates.len(), 1);
+/// assert_eq!(updates[0].to_string(), "10.0.0.0/8 via 192.0.2.3");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Pacer<P>
+where
+    P: Clone + Eq + Hash,
+{
+    /// The MinRouteAdvertisementInterval: the minimum time between two batches sent to a peer.
+    pub interval: Duration,
+
+    peers: HashMap<P, Queue>,
+}
+
+// The UPDATE messages waiting to be sent to a peer
+#[derive(Clone, Debug, Default)]
+struct Queue {
+    // The last change of each route
+    routes: BTreeMap<RouteKey, Change>,
+    // UPDATE messages sent as they are
+    others: Vec<Update>,
+    last_release: Option<Instant>,
+}
+
+// An announcement of a route, or its withdrawal without path attributes
+#[derive(Clone, Debug)]
+struct Change {
+    route: NLRIEncoding,
+    attributes: Option<Arc<Vec<PathAttribute>>>,
+}
+
+impl Queue {
+    fn is_empty(&self) -> bool {
+        self.routes.is_empty() && self.others.is_empty()
+    }
+
+    fn is_due(&self, interval: Duration) -> bool {
+        !self.is_empty()
+            && self
+                .last_release
+                .map_or(true, |last_release| last_release.elapsed() >= interval)
+    }
+
+    fn release(&mut self) -> Vec<Update> {
+        if self.is_empty() {
+            return vec![];
+        }
+        let routes = std::mem::replace(&mut self.routes, BTreeMap::new());
+        let withdrawn = routes
+            .iter()
+            .filter(|(_, change)| change.attributes.is_none())
+            .map(|(key, change)| (key.afi, key.safi, change.route.clone()));
+        let mut updates = withdrawal_updates(withdrawn);
+        let announced = routes.iter().filter_map(|(key, change)| {
+            change
+                .attributes
+                .as_ref()
+                .map(|attributes| (key.afi, key.safi, &change.route, attributes))
+        });
+        updates.extend(announcement_updates(announced));
+        updates.append(&mut self.others);
+        self.last_release = Some(Instant::now());
+        updates
+    }
+}
+
+impl<P> Pacer<P>
+where
+    P: Clone + Eq + Hash,
+{
+    /// Create a pacer without queued messages.
+    pub fn new(interval: Duration) -> Self {
+        Pacer {
+            interval,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Queues an UPDATE message to send to a peer. Its routes replace the queued changes of
+    /// the same routes.
+    pub fn queue(&mut self, peer: P, update: &Update) {
+        let queue = self.peers.entry(peer).or_default();
+        let mut routes = update.withdrawn().chain(update.announced()).peekable();
+        let coalesced = routes.peek().is_some()
+            && routes.all(|(afi, safi, route)| RouteKey::new(afi, safi, route).is_some());
+        if !coalesced {
+            queue.others.push(update.clone());
+            return;
+        }
+
+        for (afi, safi, route) in update.withdrawn() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                let change = Change {
+                    route: route.clone(),
+                    attributes: None,
+                };
+                queue.routes.insert(key, change);
+            }
+        }
+        let attributes = Arc::new(route_attributes(update));
+        for (afi, safi, route) in update.announced() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                let change = Change {
+                    route: route.clone(),
+                    attributes: Some(Arc::clone(&attributes)),
+                };
+                queue.routes.insert(key, change);
+            }
+        }
+    }
+
+    /// The number of routes and UPDATE messages waiting to be sent to a peer.
+    pub fn pending(&self, peer: &P) -> usize {
+        self.peers
+            .get(peer)
+            .map_or(0, |queue| queue.routes.len() + queue.others.len())
+    }
+
+    /// When the next batch is due, None without queued messages. This may be in the past.
+    pub fn next_release(&self) -> Option<Instant> {
+        let now = Instant::now();
+        self.peers
+            .values()
+            .filter(|queue| !queue.is_empty())
+            .map(|queue| {
+                queue
+                    .last_release
+                    .map_or(now, |last_release| last_release + self.interval)
+            })
+            .min()
+    }
+
+    /// The UPDATE messages to send to a peer, when the interval elapsed since its last batch.
+    /// Withdrawals come first, followed by announcements grouped by their path attributes.
+    /// The messages are not limited in size, see `Update::split`.
+    pub fn release(&mut self, peer: &P) -> Vec<Update> {
+        let interval = self.interval;
+        match self.peers.get_mut(peer) {
+            Some(queue) if queue.is_due(interval) => queue.release(),
+            _ => vec![],
+        }
+    }
+
+    /// The UPDATE messages to send to a peer regardless of the interval, see `release`.
+    pub fn flush(&mut self, peer: &P) -> Vec<Update> {
+        self.peers
+            .get_mut(peer)
+            .map(Queue::release)
+            .unwrap_or_default()
+    }
+
+    /// The batches of UPDATE messages due for every peer, see `release`.
+    pub fn poll(&mut self) -> Vec<(P, Vec<Update>)> {
+        let interval = self.interval;
+        self.peers
+            .iter_mut()
+            .filter(|(_, queue)| queue.is_due(interval))
+            .map(|(peer, queue)| (peer.clone(), queue.release()))
+            .collect()
+    }
+
+    /// Drops the messages queued for a peer, such as when its session went down.
+    pub fn remove_peer(&mut self, peer: &P) {
+        self.peers.remove(peer);
+    }
+}
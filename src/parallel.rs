@@ -0,0 +1,36 @@
+//! Parsing of framed messages across threads, for batches such as the messages of MRT archives
+//! or pcap captures.
+
+use rayon::prelude::*;
+
+use std::io::Error;
+
+use crate::slice::parse_framed;
+use crate::*;
+
+/// Parses messages across threads, each with the capabilities of the peer that sent it.
+///
+/// Each chunk must hold exactly one message, including its header. The results are returned
+/// in the order of the messages.
+///
+/// ```
+/// use bgp_rs::{parse_parallel, Capabilities, Message};
+///
+/// let mut keepalive = vec![];
+/// Message::KeepAlive.encode(&mut keepalive).unwrap();
+/// let capabilities = Capabilities::default();
+/// let messages = vec![(keepalive.clone(), &capabilities), (vec![0xff; 16], &capabilities)];
+/// let results = parse_parallel(&messages);
+/// assert_eq!(results[0].as_ref().unwrap().1, Message::KeepAlive);
+/// assert!(results[1].is_err());
+/// ```
+pub fn parse_parallel<B>(messages: &[(B, &Capabilities)]) -> Vec<Result<(Header, Message), Error>>
+where
+    B: AsRef<[u8]> + Sync,
+{
+    let options = ParseOptions::default();
+    messages
+        .par_iter()
+        .map(|(data, capabilities)| parse_framed(data.as_ref(), capabilities, &options))
+        .collect()
+}
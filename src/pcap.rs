@@ -0,0 +1,314 @@
+//! Extraction of BGP messages from pcap captures, reassembling the TCP streams they were sent on.
+
+use etherparse::{IpHeader, PacketHeaders, TransportHeader};
+use pcap_file::{DataLink, PcapReader};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::*;
+
+/// A BGP message found in a pcap capture.
+#[derive(Clone, Debug)]
+pub struct PcapMessage {
+    /// The capture time of the packet that completed the message, since the UNIX epoch.
+    pub timestamp: Duration,
+
+    /// The address of the speaker that sent the message.
+    pub source: SocketAddr,
+
+    /// The address of the speaker that received the message.
+    pub destination: SocketAddr,
+
+    /// The message, including its header.
+    pub data: Vec<u8>,
+}
+
+impl PcapMessage {
+    /// Parses the message, see `Reader::read`.
+    pub fn parse(&self, capabilities: &Capabilities) -> Result<Message, Error> {
+        let mut reader = Reader::with_capabilities(std::io::Cursor::new(&self.data), capabilities);
+        let (_header, message) = reader.read()?;
+        Ok(message)
+    }
+}
+
+///
+/// Reads all BGP messages from a pcap capture of Ethernet, raw IP, Linux cooked (SLL), Cisco HDLC
+/// or BSD loopback frames.
+///
+/// The payloads of the TCP segments are reassembled into a stream per direction of every
+/// connection before splitting them into messages, so messages spanning multiple segments,
+/// retransmissions and reordered segments are handled. When a capture starts in the middle of a
+/// connection, or segments are missing from it, the stream is resynchronized on the next BGP
+/// marker.
+///
+/// ```
+/// use bgp_rs::{Capabilities, Message, read_bgp_messages_from_pcap};
+///
+/// let messages = read_bgp_messages_from_pcap("res/pcap/BGP_MP_NLRI.cap").unwrap();
+/// match messages[0].parse(&Capabilities::default()).unwrap() {
+///     Message::Open(open) => assert_eq!(open.peer_asn, 65001),
+///     message => panic!("Unexpected message {:?}", message),
+/// }
+/// ```
+///
+pub fn read_bgp_messages_from_pcap(path: impl AsRef<Path>) -> Result<Vec<PcapMessage>, Error> {
+    let reader = PcapReader::new(File::open(path)?).map_err(pcap_error)?;
+    let datalink = reader.header.datalink;
+
+    let mut streams: HashMap<(SocketAddr, SocketAddr), TcpStream> = HashMap::new();
+    let mut messages = vec![];
+    for packet in reader {
+        let packet = packet.map_err(pcap_error)?;
+        let headers = match datalink {
+            DataLink::ETHERNET => PacketHeaders::from_ethernet_slice(&packet.data),
+            DataLink::RAW => PacketHeaders::from_ip_slice(&packet.data),
+            DataLink::LINUX_SLL if packet.data.len() >= 16 => {
+                PacketHeaders::from_ip_slice(&packet.data[16..])
+            }
+            DataLink::NULL | DataLink::C_HDLC if packet.data.len() >= 4 => {
+                PacketHeaders::from_ip_slice(&packet.data[4..])
+            }
+            DataLink::LINUX_SLL | DataLink::NULL | DataLink::C_HDLC => continue,
+            datalink => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Unsupported pcap link type: {:?}", datalink),
+                ));
+            }
+        };
+        // Packets that aren't TCP/IP (or are truncated) can't hold BGP messages
+        let headers = match headers {
+            Ok(headers) => headers,
+            Err(_) => continue,
+        };
+        let (source, destination) = match &headers.ip {
+            Some(IpHeader::Version4(ip)) => (
+                IpAddr::V4(Ipv4Addr::from(ip.source)),
+                IpAddr::V4(Ipv4Addr::from(ip.destination)),
+            ),
+            Some(IpHeader::Version6(ip)) => (
+                IpAddr::V6(Ipv6Addr::from(ip.source)),
+                IpAddr::V6(Ipv6Addr::from(ip.destination)),
+            ),
+            None => continue,
+        };
+        let tcp = match &headers.transport {
+            Some(TransportHeader::Tcp(tcp)) => tcp,
+            _ => continue,
+        };
+        let source = SocketAddr::new(source, tcp.source_port);
+        let destination = SocketAddr::new(destination, tcp.destination_port);
+
+        let stream = streams.entry((source, destination)).or_default();
+        if tcp.syn || tcp.rst {
+            // A new connection, or the end of the current one
+            *stream = TcpStream::default();
+            if tcp.syn {
+                stream.next_sequence = Some(tcp.sequence_number.wrapping_add(1));
+                stream.synchronized = true;
+            }
+            continue;
+        }
+        stream.receive(tcp.sequence_number, headers.payload);
+        let timestamp = Duration::new(u64::from(packet.header.ts_sec), packet.header.ts_nsec);
+        while let Some(data) = stream.next_message() {
+            messages.push(PcapMessage {
+                timestamp,
+                source,
+                destination,
+                data,
+            });
+        }
+    }
+    Ok(messages)
+}
+
+fn pcap_error(error: pcap_file::PcapError) -> Error {
+    Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+// The maximum number of out-of-order segments buffered for a stream
+const MAX_PENDING_SEGMENTS: usize = 1024;
+
+// One direction of a TCP connection
+#[derive(Default)]
+struct TcpStream {
+    // The sequence number of the next byte in the stream, if known
+    next_sequence: Option<u32>,
+    buffer: Vec<u8>,
+    // Segments received ahead of `next_sequence`
+    pending: BTreeMap<u32, Vec<u8>>,
+    // Whether the buffer starts at a message boundary
+    synchronized: bool,
+}
+
+impl TcpStream {
+    fn receive(&mut self, sequence: u32, payload: &[u8]) {
+        if payload.is_empty() {
+            return;
+        }
+        let next = match self.next_sequence {
+            Some(next) => next,
+            None => {
+                // The capture started after the handshake
+                self.next_sequence = Some(sequence);
+                sequence
+            }
+        };
+        // Relative to the next expected byte, accounting for wrapping sequence numbers
+        let offset = sequence.wrapping_sub(next) as i32;
+        if offset > 0 {
+            if self.pending.len() >= MAX_PENDING_SEGMENTS {
+                // The missing segment was not captured, skip ahead to the buffered ones
+                self.skip_to_pending();
+                return self.receive(sequence, payload);
+            }
+            self.pending.insert(sequence, payload.to_vec());
+            return;
+        }
+        // Retransmitted bytes are dropped
+        let overlap = offset.wrapping_neg() as usize;
+        if overlap >= payload.len() {
+            return;
+        }
+        self.append(&payload[overlap..]);
+        self.append_pending();
+    }
+
+    fn append(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+        self.next_sequence = self
+            .next_sequence
+            .map(|next| next.wrapping_add(bytes.len() as u32));
+    }
+
+    // Appends the pending segments that have become contiguous with the stream
+    fn append_pending(&mut self) {
+        while let Some(next) = self.next_sequence {
+            let sequence = match self
+                .pending
+                .keys()
+                .find(|sequence| (sequence.wrapping_sub(next) as i32) <= 0)
+            {
+                Some(sequence) => *sequence,
+                None => return,
+            };
+            let payload = self.pending.remove(&sequence).unwrap_or_default();
+            let overlap = next.wrapping_sub(sequence) as usize;
+            if overlap < payload.len() {
+                self.append(&payload[overlap..]);
+            }
+        }
+    }
+
+    fn skip_to_pending(&mut self) {
+        let next = self.next_sequence.unwrap_or(0);
+        let sequence = self
+            .pending
+            .keys()
+            .min_by_key(|sequence| sequence.wrapping_sub(next))
+            .cloned();
+        if let Some(sequence) = sequence {
+            self.buffer.clear();
+            self.synchronized = false;
+            self.next_sequence = Some(sequence);
+            self.append_pending();
+        }
+    }
+
+    // Takes the next complete message from the buffer
+    fn next_message(&mut self) -> Option<Vec<u8>> {
+        loop {
+            if !self.synchronized {
+                match self
+                    .buffer
+                    .windows(16)
+                    .position(|window| window == [0xff; 16])
+                {
+                    Some(mut start) => {
+                        // Longer runs of 0xff end with the marker
+                        while self.buffer.get(start + 16) == Some(&0xff) {
+                            start += 1;
+                        }
+                        self.buffer.drain(..start);
+                        if self.buffer.len() == 16 {
+                            return None;
+                        }
+                        self.synchronized = true;
+                    }
+                    None => {
+                        // Keep the bytes that could be the start of a marker
+                        let keep = self.buffer.len().min(15);
+                        let start = self.buffer.len() - keep;
+                        self.buffer.drain(..start);
+                        return None;
+                    }
+                }
+            }
+            if self.buffer.len() < BGP_MIN_MESSAGE_SIZE {
+                return None;
+            }
+            let length = usize::from(self.buffer[16]) << 8 | usize::from(self.buffer[17]);
+            if self.buffer[..16] != [0xff; 16] || length < BGP_MIN_MESSAGE_SIZE {
+                // Not a message boundary, look for the next marker
+                self.buffer.remove(0);
+                self.synchronized = false;
+                continue;
+            }
+            if self.buffer.len() < length {
+                return None;
+            }
+            let rest = self.buffer.split_off(length);
+            return Some(std::mem::replace(&mut self.buffer, rest));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keepalive() -> Vec<u8> {
+        let mut data = vec![0xff; 16];
+        data.extend_from_slice(&[0, 19, 4]);
+        data
+    }
+
+    #[test]
+    fn test_reassembly() {
+        let mut data = keepalive();
+        data.extend(keepalive());
+        let mut stream = TcpStream::default();
+        // Split across segments, reordered & partially retransmitted
+        stream.receive(u32::max_value() - 9, &data[..10]);
+        stream.receive(15, &data[25..]);
+        assert!(stream.next_message().is_none());
+        stream.receive(u32::max_value() - 4, &data[5..25]);
+        assert_eq!(stream.next_message(), Some(keepalive()));
+        assert_eq!(stream.next_message(), Some(keepalive()));
+        assert!(stream.next_message().is_none());
+    }
+
+    #[test]
+    fn test_resynchronization() {
+        let mut data = vec![0xff, 0, 0xff, 0xff];
+        data.extend(keepalive());
+        let mut stream = TcpStream::default();
+        stream.receive(1000, &data);
+        assert_eq!(stream.next_message(), Some(keepalive()));
+
+        // A bogus length within a marker is skipped
+        let mut data = vec![0xff; 16];
+        data.extend_from_slice(&[0, 3]);
+        data.extend(keepalive());
+        stream.receive(1000 + 23, &data);
+        assert_eq!(stream.next_message(), Some(keepalive()));
+        assert!(stream.buffer.is_empty());
+    }
+}
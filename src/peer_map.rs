@@ -0,0 +1,143 @@
+//! The `peer_map` mod provides the parsing of messages of many peers, as done by collectors
+//! - Capabilities kept per peer, identified by its IP address & ASN
+//! - Learning the capabilities of a peer from the OPEN messages exchanged with it
+//!
+//! Messages must be tagged with the peer they were exchanged with, as done by MRT (BGP4MP)
+//! records and BMP Per-Peer Headers.
+//!
+
+use std::collections::HashMap;
+use std::io::Error;
+use std::net::IpAddr;
+
+use crate::slice::parse_framed;
+use crate::*;
+
+/// The capabilities of many peers, parsing the messages of each peer with its own capabilities
+/// instead of applying one set of capabilities to all of them.
+///
+/// Peers start out with the capabilities the map was created with, and are updated from the
+/// OPEN messages exchanged with them like `NegotiatedCapabilities::apply_open`.
+///
+/// ```
+/// use bgp_rs::{Capabilities, Message, PeerMap};
+/// # use bgp_rs::{Open, PeerConfig};
+/// # let mut config = PeerConfig::new(65001, "192.0.2.1".parse().unwrap());
+/// # let mut open = vec![];
+/// # Message::Open(Open::from_config(&config)).encode(&mut open).unwrap();
+/// # config.local_asn = 65000;
+/// # let mut local_open = vec![];
+/// # Message::Open(Open::from_config(&config)).encode(&mut local_open).unwrap();
+///
+/// let mut peers = PeerMap::new(Capabilities::default());
+/// let peer = "192.0.2.1".parse().unwrap();
+///
+/// // The OPEN messages sent & received by the collector, e.g. of BGP4MP records
+/// peers.parse(peer, 65001, &open).unwrap();
+/// peers.parse(peer, 65001, &local_open).unwrap();
+/// assert!(peers.get(peer, 65001).FOUR_OCTET_ASN_SUPPORT);
+///
+/// // Other peers are still parsed with the initial capabilities
+/// assert!(!peers.get("192.0.2.2".parse().unwrap(), 65002).FOUR_OCTET_ASN_SUPPORT);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PeerMap {
+    template: Capabilities,
+    options: ParseOptions,
+    peers: HashMap<(IpAddr, u32), NegotiatedCapabilities>,
+}
+
+impl PeerMap {
+    /// Creates a map in which peers start out with the given capabilities.
+    pub fn new(template: Capabilities) -> PeerMap {
+        PeerMap::with_options(template, ParseOptions::default())
+    }
+
+    /// Creates a map parsing the messages of every peer with the given settings (e.g. the
+    /// registered decoders).
+    pub fn with_options(template: Capabilities, options: ParseOptions) -> PeerMap {
+        PeerMap {
+            template,
+            options,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Sets the capabilities of a peer (e.g. when known from its configuration), returning
+    /// the previous ones (if any).
+    pub fn insert(
+        &mut self,
+        address: IpAddr,
+        asn: u32,
+        capabilities: Capabilities,
+    ) -> Option<NegotiatedCapabilities> {
+        let negotiated = NegotiatedCapabilities {
+            capabilities,
+            ..NegotiatedCapabilities::default()
+        };
+        self.peers.insert((address, asn), negotiated)
+    }
+
+    /// The capabilities the messages of a peer are parsed with, those of new peers when the
+    /// peer is unknown.
+    pub fn get(&self, address: IpAddr, asn: u32) -> &Capabilities {
+        match self.peers.get(&(address, asn)) {
+            Some(negotiated) => &negotiated.capabilities,
+            None => &self.template,
+        }
+    }
+
+    /// The session parameters of a peer for modification, adding the peer when unknown.
+    pub fn get_mut(&mut self, address: IpAddr, asn: u32) -> &mut NegotiatedCapabilities {
+        let template = &self.template;
+        self.peers
+            .entry((address, asn))
+            .or_insert_with(|| NegotiatedCapabilities {
+                capabilities: template.clone(),
+                ..NegotiatedCapabilities::default()
+            })
+    }
+
+    /// Removes a peer (e.g. when its session went down), returning its session parameters.
+    pub fn remove(&mut self, address: IpAddr, asn: u32) -> Option<NegotiatedCapabilities> {
+        self.peers.remove(&(address, asn))
+    }
+
+    /// The IP addresses & ASNs of the known peers.
+    pub fn peers(&self) -> impl Iterator<Item = (IpAddr, u32)> + '_ {
+        self.peers.keys().cloned()
+    }
+
+    /// The number of known peers.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Whether no peer is known.
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Parses a message (including its header) exchanged with a peer, in either direction.
+    ///
+    /// The data must hold exactly one message. OPEN messages update the capabilities of the
+    /// peer, see `NegotiatedCapabilities::apply_open`.
+    pub fn parse(&mut self, address: IpAddr, asn: u32, data: &[u8]) -> Result<Message, Error> {
+        let PeerMap {
+            template,
+            options,
+            peers,
+        } = self;
+        let peer = peers
+            .entry((address, asn))
+            .or_insert_with(|| NegotiatedCapabilities {
+                capabilities: template.clone(),
+                ..NegotiatedCapabilities::default()
+            });
+        let (_header, message) = parse_framed(data, &peer.capabilities, options)?;
+        if let Message::Open(open) = &message {
+            peer.apply_open(open)?;
+        }
+        Ok(message)
+    }
+}
@@ -0,0 +1,484 @@
+//! The `policy` mod provides a route policy engine, configured programmatically
+//! - Match conditions: prefix lists (with ge/le), AS_PATH regular expressions, communities
+//!   (optionally with wildcards), next hop & origin
+//! - Actions: set, strip & prepend attributes, add & remove communities, accept & reject
+//! - Applicable to the routes of UPDATE messages, or to RIB entries
+//!
+//! A policy is a list of terms evaluated in order. The actions of every term whose conditions
+//! all match are applied, until a term accepts or rejects the route. Routes reaching the end of
+//! the policy get its default verdict.
+//!
+
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::*;
+
+/// A route policy, see the module documentation for its evaluation.
+///
+/// ```
+/// use bgp_rs::policy::{Action, Community, Condition, Policy, Term};
+/// use bgp_rs::{ASPath, PathAttribute, RouteKey, Segment, NLRIEncoding, AFI, SAFI};
+///
+/// let policy = Policy {
+///     terms: vec![
+///         Term {
+///             conditions: vec![Condition::Prefix(vec!["10.0.0.0/8 le 24".parse().unwrap()])],
+///             actions: vec![Action::Set(PathAttribute::LOCAL_PREF(200))],
+///         },
+///         Term {
+///             conditions: vec![Condition::AsPath("^65001( |$)".parse().unwrap())],
+///             actions: vec![
+///                 Action::AddCommunity(Community::Standard(0xfde9_0001)),
+///                 Action::Accept,
+///             ],
+///         },
+///     ],
+///     accept_by_default: false,
+/// };
+///
+/// let route = NLRIEncoding::IP("10.1.0.0/16".parse().unwrap());
+/// let key = RouteKey::new(AFI::IPV4, SAFI::Unicast, &route).unwrap();
+/// let mut attributes = vec![PathAttribute::AS_PATH(ASPath {
+///     segments: vec![Segment::AS_SEQUENCE(vec![65001, 65002])],
+/// })];
+/// assert!(policy.apply_route(&key, &mut attributes));
+/// assert_eq!(attributes.len(), 3);
+///
+/// attributes[0] = PathAttribute::AS_PATH(ASPath { segments: vec![] });
+/// assert!(!policy.apply_route(&key, &mut attributes));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    /// The terms, evaluated in order.
+    pub terms: Vec<Term>,
+
+    /// The verdict for routes that were neither accepted nor rejected by a term.
+    pub accept_by_default: bool,
+}
+
+/// A term of a policy: actions applied to the routes matching all its conditions.
+#[derive(Clone, Debug, Default)]
+pub struct Term {
+    /// The conditions a route has to match, a term without conditions matches all routes.
+    pub conditions: Vec<Condition>,
+
+    /// The actions applied in order to matching routes.
+    pub actions: Vec<Action>,
+}
+
+/// A condition matching routes.
+#[derive(Clone, Debug)]
+pub enum Condition {
+    /// The prefix of the route matches an entry of the prefix list.
+    /// Routes without a plain prefix (such as VPN or Flowspec routes) never match.
+    Prefix(Vec<PrefixListEntry>),
+
+    /// The AS_PATH (merged with AS4_PATH) matches the regular expression. The AS_PATH is
+    /// written as ASNs separated by spaces, with AS_SETs in braces: `65001 65002 {65003,65004}`.
+    AsPath(Regex),
+
+    /// The route carries the community.
+    Community(Community),
+
+    /// The route carries a community matching the filter, e.g. `65000:*`.
+    CommunityFilter(CommunityFilter),
+
+    /// The next hop of the route, from NEXT_HOP or MP_REACH_NLRI, is the address.
+    NextHop(IpAddr),
+
+    /// The ORIGIN of the route.
+    Origin(Origin),
+
+    /// The condition does not match.
+    Not(Box<Condition>),
+}
+
+/// An action applied to matching routes.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Accept the route, ending the evaluation.
+    Accept,
+
+    /// Reject the route, ending the evaluation.
+    Reject,
+
+    /// Set a path attribute, replacing the attribute of the same type.
+    Set(PathAttribute),
+
+    /// Remove the path attributes of a type.
+    Strip(Identifier),
+
+    /// Prepend an ASN to the AS_PATH a number of times.
+    Prepend {
+        /// The ASN to prepend.
+        asn: u32,
+        /// The number of times to prepend it.
+        count: usize,
+    },
+
+    /// Set the next hop of the route, in NEXT_HOP and MP_REACH_NLRI (see `Update::set_next_hop`).
+    SetNextHop(IpAddr),
+
+    /// Add a community, unless the route already carries it.
+    AddCommunity(Community),
+
+    /// Remove a community, removing the attribute when no communities remain.
+    RemoveCommunity(Community),
+}
+
+/// A community of any of the community path attributes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Community {
+    /// A community of COMMUNITY (RFC1997).
+    Standard(u32),
+
+    /// An extended community of EXTENDED_COMMUNITIES (RFC4360).
+    Extended(u64),
+
+    /// A large community of LARGE_COMMUNITY (RFC8092), as (Global Administrator, Local Data 1,
+    /// Local Data 2).
+    Large((u32, u32, u32)),
+}
+
+/// An entry of a prefix list: prefixes covered by `prefix` with a length between `ge` and `le`.
+/// Without `ge` or `le` only the prefix itself matches, with `ge` alone the longest length is
+/// that of addresses.
+///
+/// ```
+/// use bgp_rs::policy::PrefixListEntry;
+///
+/// let entry: PrefixListEntry = "10.0.0.0/8 ge 16 le 24".parse().unwrap();
+/// assert!(entry.matches(&"10.1.0.0/16".parse().unwrap()));
+/// assert!(entry.matches(&"10.1.1.0/24".parse().unwrap()));
+/// assert!(!entry.matches(&"10.0.0.0/8".parse().unwrap()));
+/// assert!(!entry.matches(&"11.1.0.0/16".parse().unwrap()));
+///
+/// let entry: PrefixListEntry = "10.0.0.0/8".parse().unwrap();
+/// assert!(!entry.matches(&"10.1.0.0/16".parse().unwrap()));
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrefixListEntry {
+    /// The prefix covering the matching prefixes.
+    pub prefix: Prefix,
+
+    /// The shortest length of matching prefixes.
+    pub ge: Option<u8>,
+
+    /// The longest length of matching prefixes.
+    pub le: Option<u8>,
+}
+
+impl PrefixListEntry {
+    /// Whether the prefix matches the entry.
+    pub fn matches(&self, prefix: &Prefix) -> bool {
+        let max_length = match self.prefix.protocol {
+            AFI::IPV4 => 32,
+            _ => 128,
+        };
+        let (min, max) = match (self.ge, self.le) {
+            (None, None) => (self.prefix.length, self.prefix.length),
+            (ge, le) => (ge.unwrap_or(self.prefix.length), le.unwrap_or(max_length)),
+        };
+        self.prefix.contains(prefix) && prefix.length >= min && prefix.length <= max
+    }
+}
+
+impl FromStr for PrefixListEntry {
+    type Err = Error;
+
+    /// Parse an entry as written in router configurations, e.g. "10.0.0.0/8 ge 16 le 24"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid prefix list entry: {}", s),
+            )
+        };
+        let mut words = s.split_whitespace();
+        let prefix: Prefix = words.next().ok_or_else(invalid)?.parse()?;
+        let mut entry = PrefixListEntry {
+            prefix,
+            ge: None,
+            le: None,
+        };
+        while let Some(word) = words.next() {
+            let length = words
+                .next()
+                .and_then(|length| length.parse().ok())
+                .ok_or_else(invalid)?;
+            match word {
+                "ge" if entry.ge.is_none() => entry.ge = Some(length),
+                "le" if entry.le.is_none() => entry.le = Some(length),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(entry)
+    }
+}
+
+impl Condition {
+    fn matches(&self, prefix: Option<&Prefix>, attributes: &[PathAttribute]) -> bool {
+        match self {
+            Condition::Prefix(entries) => prefix
+                .map(|prefix| entries.iter().any(|entry| entry.matches(prefix)))
+                .unwrap_or(false),
+            Condition::AsPath(regex) => {
+                let as_path = effective_as_path(attributes)
+                    .map(|as_path| as_path.to_string())
+                    .unwrap_or_default();
+                regex.is_match(&as_path)
+            }
+            Condition::Community(community) => {
+                attributes
+                    .iter()
+                    .any(|attribute| match (attribute, community) {
+                        (PathAttribute::COMMUNITY(values), Community::Standard(value)) => {
+                            values.contains(value)
+                        }
+                        (
+                            PathAttribute::EXTENDED_COMMUNITIES(values),
+                            Community::Extended(value),
+                        ) => values.contains(value),
+                        (PathAttribute::LARGE_COMMUNITY(values), Community::Large(value)) => {
+                            values.contains(value)
+                        }
+                        _ => false,
+                    })
+            }
+            Condition::CommunityFilter(filter) => filter.matches_attributes(attributes),
+            Condition::NextHop(next_hop) => attributes.iter().any(|attribute| match attribute {
+                PathAttribute::NEXT_HOP(address) => address == next_hop,
+                PathAttribute::MP_REACH_NLRI(mp_reach) => mp_reach
+                    .decode_next_hop()
+                    .map(|addresses| addresses.contains(next_hop))
+                    .unwrap_or(false),
+                _ => false,
+            }),
+            Condition::Origin(origin) => attributes.iter().any(|attribute| match attribute {
+                PathAttribute::ORIGIN(value) => value == origin,
+                _ => false,
+            }),
+            Condition::Not(condition) => !condition.matches(prefix, attributes),
+        }
+    }
+}
+
+impl Action {
+    // Applies the action, returning the verdict of Accept & Reject
+    fn apply(&self, attributes: &mut Vec<PathAttribute>) -> Option<bool> {
+        match self {
+            Action::Accept => return Some(true),
+            Action::Reject => return Some(false),
+            Action::Set(attribute) => {
+                let code = attribute.code();
+                match attributes.iter_mut().find(|a| a.code() == code) {
+                    Some(existing) => *existing = attribute.clone(),
+                    None => attributes.push(attribute.clone()),
+                }
+            }
+            Action::Strip(identifier) => attributes.retain(|a| a.id() != *identifier),
+            Action::Prepend { asn, count } => {
+                let as_path = attributes.iter_mut().find_map(|attribute| match attribute {
+                    PathAttribute::AS_PATH(as_path) => Some(as_path),
+                    _ => None,
+                });
+                match as_path {
+                    Some(as_path) => as_path.prepend(*asn, *count),
+                    None => {
+                        let mut as_path = ASPath { segments: vec![] };
+                        as_path.prepend(*asn, *count);
+                        attributes.push(PathAttribute::AS_PATH(as_path));
+                    }
+                }
+            }
+            Action::SetNextHop(next_hop) => {
+                let mut update = Update::new(vec![], std::mem::replace(attributes, vec![]), vec![]);
+                update.set_next_hop(*next_hop);
+                *attributes = update.attributes;
+            }
+            Action::AddCommunity(community) => add_community(attributes, *community),
+            Action::RemoveCommunity(community) => remove_community(attributes, *community),
+        }
+        None
+    }
+}
+
+fn add_community(attributes: &mut Vec<PathAttribute>, community: Community) {
+    fn add<T: PartialEq>(values: &mut Vec<T>, value: T) {
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    for attribute in attributes.iter_mut() {
+        match (attribute, community) {
+            (PathAttribute::COMMUNITY(values), Community::Standard(value)) => {
+                return add(values, value)
+            }
+            (PathAttribute::EXTENDED_COMMUNITIES(values), Community::Extended(value)) => {
+                return add(values, value)
+            }
+            (PathAttribute::LARGE_COMMUNITY(values), Community::Large(value)) => {
+                return add(values, value)
+            }
+            _ => {}
+        }
+    }
+    attributes.push(match community {
+        Community::Standard(value) => PathAttribute::COMMUNITY(vec![value]),
+        Community::Extended(value) => PathAttribute::EXTENDED_COMMUNITIES(vec![value]),
+        Community::Large(value) => PathAttribute::LARGE_COMMUNITY(vec![value]),
+    });
+}
+
+fn remove_community(attributes: &mut Vec<PathAttribute>, community: Community) {
+    attributes.retain(|attribute| match (attribute, community) {
+        (PathAttribute::COMMUNITY(values), Community::Standard(value)) => {
+            values.iter().any(|v| *v != value)
+        }
+        (PathAttribute::EXTENDED_COMMUNITIES(values), Community::Extended(value)) => {
+            values.iter().any(|v| *v != value)
+        }
+        (PathAttribute::LARGE_COMMUNITY(values), Community::Large(value)) => {
+            values.iter().any(|v| *v != value)
+        }
+        _ => true,
+    });
+    for attribute in attributes.iter_mut() {
+        match (attribute, community) {
+            (PathAttribute::COMMUNITY(values), Community::Standard(value)) => {
+                values.retain(|v| *v != value)
+            }
+            (PathAttribute::EXTENDED_COMMUNITIES(values), Community::Extended(value)) => {
+                values.retain(|v| *v != value)
+            }
+            (PathAttribute::LARGE_COMMUNITY(values), Community::Large(value)) => {
+                values.retain(|v| *v != value)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Policy {
+    // Evaluates the policy for a route, modifying its attributes
+    fn evaluate(&self, prefix: Option<&Prefix>, attributes: &mut Vec<PathAttribute>) -> bool {
+        for term in &self.terms {
+            let matches = term
+                .conditions
+                .iter()
+                .all(|condition| condition.matches(prefix, attributes));
+            if !matches {
+                continue;
+            }
+            for action in &term.actions {
+                if let Some(accepted) = action.apply(attributes) {
+                    return accepted;
+                }
+            }
+        }
+        self.accept_by_default
+    }
+
+    /// Applies the policy to a route, such as a RIB entry, modifying its attributes.
+    /// Returns whether the route is accepted.
+    pub fn apply_route(&self, key: &RouteKey, attributes: &mut Vec<PathAttribute>) -> bool {
+        self.evaluate(Some(&key.prefix), attributes)
+    }
+
+    /// Applies the policy to the routes announced by an UPDATE message. Returns the UPDATE
+    /// messages holding the accepted routes, grouped by their resulting attributes, preceded by
+    /// the withdrawn routes. Rejected routes are withdrawn, replacing any route previously
+    /// accepted for the same prefix.
+    ///
+    /// ```
+    /// use bgp_rs::policy::{Action, Condition, Policy, Term};
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Update};
+    ///
+    /// let policy = Policy {
+    ///     terms: vec![Term {
+    ///         conditions: vec![Condition::Prefix(vec!["10.0.0.0/8 le 16".parse().unwrap()])],
+    ///         actions: vec![Action::Set(PathAttribute::MULTI_EXIT_DISC(10)), Action::Accept],
+    ///     }],
+    ///     accept_by_default: true,
+    /// };
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+    ///     vec![
+    ///         NLRIEncoding::IP("10.1.0.0/16".parse().unwrap()),
+    ///         NLRIEncoding::IP("10.1.1.0/24".parse().unwrap()),
+    ///     ],
+    /// );
+    /// let updates = policy.apply_update(&update);
+    /// assert_eq!(updates.len(), 2);
+    /// assert_eq!(updates[0].to_string(), "10.1.0.0/16 via 192.0.2.1, MED 10");
+    /// assert_eq!(updates[1].to_string(), "10.1.1.0/24 via 192.0.2.1");
+    /// ```
+    pub fn apply_update(&self, update: &Update) -> Vec<Update> {
+        let mut base = update.attributes.clone();
+        base.retain(|attribute| match attribute {
+            PathAttribute::MP_REACH_NLRI(_) | PathAttribute::MP_UNREACH_NLRI(_) => false,
+            _ => true,
+        });
+
+        // The accepted routes, grouped by their attributes
+        let mut groups: Vec<(String, Vec<PathAttribute>, Vec<NLRIEncoding>)> = vec![];
+        let mut withdrawn: Vec<(AFI, SAFI, NLRIEncoding)> = update
+            .withdrawn()
+            .map(|(afi, safi, route)| (afi, safi, route.clone()))
+            .collect();
+
+        let mut evaluate = |attributes: &[PathAttribute], afi, safi, route: &NLRIEncoding| {
+            let mut attributes = attributes.to_vec();
+            let prefix = RouteKey::new(afi, safi, route).map(|key| key.prefix);
+            if !self.evaluate(prefix.as_ref(), &mut attributes) {
+                withdrawn.push((afi, safi, route.clone()));
+                return;
+            }
+            let key = format!("{:?}", attributes);
+            match groups.iter_mut().find(|(group, _, _)| *group == key) {
+                Some((_, _, routes)) => routes.push(route.clone()),
+                None => groups.push((key, attributes, vec![route.clone()])),
+            }
+        };
+        for route in &update.announced_routes {
+            let (afi, safi) = route.family();
+            evaluate(&base, afi, safi, route);
+        }
+        for attribute in &update.attributes {
+            if let PathAttribute::MP_REACH_NLRI(mp_reach) = attribute {
+                // The next hop of MP routes is that of MP_REACH_NLRI
+                let mut attributes: Vec<PathAttribute> = base
+                    .iter()
+                    .filter(|attribute| attribute.id() != Identifier::NEXT_HOP)
+                    .cloned()
+                    .collect();
+                attributes.push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI {
+                    announced_routes: vec![],
+                    ..(**mp_reach).clone()
+                })));
+                for route in &mp_reach.announced_routes {
+                    evaluate(&attributes, mp_reach.afi, mp_reach.safi, route);
+                }
+            }
+        }
+
+        let mut updates = withdrawal_updates(withdrawn);
+        for (_, mut attributes, routes) in groups {
+            let mut announced_routes = vec![];
+            match attributes.iter_mut().find_map(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach) => Some(mp_reach),
+                _ => None,
+            }) {
+                Some(mp_reach) => mp_reach.announced_routes = routes,
+                None => announced_routes = routes,
+            }
+            updates.push(Update::new(vec![], attributes, announced_routes));
+        }
+        updates
+    }
+}
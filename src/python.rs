@@ -0,0 +1,330 @@
+//! Python bindings, for analysing BGP messages (e.g. of MRT archives) from Python with this
+//! parser. Build the `bgp_rs` extension module with the `python` feature, e.g.
+//! `cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib`, and
+//! rename the library to `bgp_rs.so`:
+//!
+//! ```python
+//! import bgp_rs
+//!
+//! capabilities = bgp_rs.Capabilities(four_octet_asn=True)
+//! message = bgp_rs.parse_message(data, capabilities)
+//! if message["type"] == "UPDATE":
+//!     update = bgp_rs.Update.from_bytes(data, capabilities)
+//!     print(update.as_path, update.announced)
+//! ```
+//!
+//! Messages are converted to dicts in the format of the `data` of RIS Live messages (see
+//! `RisLiveMessage`), parse errors are raised as `ValueError`.
+
+// The code generated by the pyo3 macros trips these lints
+#![allow(clippy::incompatible_msrv, clippy::useless_conversion)]
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use serde_json::{json, Map, Value};
+
+use std::net::Ipv4Addr;
+
+use crate::json::as_path_to_json;
+use crate::ris_live::message_to_json;
+use crate::*;
+
+/// The capabilities messages are parsed with, `Capabilities` in Python.
+#[pyclass(name = "Capabilities", module = "bgp_rs")]
+#[derive(Clone, Debug, Default)]
+pub struct PyCapabilities {
+    capabilities: Capabilities,
+}
+
+#[pymethods]
+impl PyCapabilities {
+    #[new]
+    #[pyo3(signature = (four_octet_asn = false, add_path = false))]
+    fn new(four_octet_asn: bool, add_path: bool) -> PyCapabilities {
+        PyCapabilities {
+            capabilities: Capabilities {
+                FOUR_OCTET_ASN_SUPPORT: four_octet_asn,
+                EXTENDED_PATH_NLRI_SUPPORT: add_path,
+                ..Capabilities::default()
+            },
+        }
+    }
+
+    #[getter]
+    fn four_octet_asn(&self) -> bool {
+        self.capabilities.FOUR_OCTET_ASN_SUPPORT
+    }
+
+    #[getter]
+    fn add_path(&self) -> bool {
+        self.capabilities.EXTENDED_PATH_NLRI_SUPPORT
+    }
+}
+
+/// An UPDATE message, `Update` in Python.
+#[pyclass(name = "Update", module = "bgp_rs")]
+#[derive(Clone, Debug)]
+pub struct PyUpdate {
+    update: Update,
+}
+
+#[pymethods]
+impl PyUpdate {
+    /// Parses an UPDATE message, including its header.
+    #[staticmethod]
+    #[pyo3(signature = (data, capabilities = None))]
+    fn from_bytes(data: &[u8], capabilities: Option<&PyCapabilities>) -> PyResult<PyUpdate> {
+        match decode(data, capabilities)? {
+            Message::Update(update) => Ok(PyUpdate { update }),
+            message => Err(unexpected_type(&message)),
+        }
+    }
+
+    /// The announced routes, including those of MP_REACH_NLRI.
+    #[getter]
+    fn announced(&self) -> Vec<String> {
+        self.update
+            .announced()
+            .map(|(_, _, route)| route.to_string())
+            .collect()
+    }
+
+    /// The withdrawn routes, including those of MP_UNREACH_NLRI.
+    #[getter]
+    fn withdrawn(&self) -> Vec<String> {
+        self.update
+            .withdrawn()
+            .map(|(_, _, route)| route.to_string())
+            .collect()
+    }
+
+    /// The AS_PATH merged with the AS4_PATH, with AS_SETs as nested lists.
+    #[getter]
+    fn as_path(&self, py: Python<'_>) -> PyObject {
+        match self.update.effective_as_path() {
+            Some(as_path) => to_python(py, &as_path_to_json(&as_path)),
+            None => py.None(),
+        }
+    }
+
+    #[getter]
+    fn origin(&self) -> Option<String> {
+        match self.update.get(Identifier::ORIGIN) {
+            Some(PathAttribute::ORIGIN(origin)) => Some(format!("{:?}", origin)),
+            _ => None,
+        }
+    }
+
+    #[getter]
+    fn next_hop(&self) -> Option<String> {
+        match self.update.get(Identifier::NEXT_HOP) {
+            Some(PathAttribute::NEXT_HOP(next_hop)) => Some(next_hop.to_string()),
+            _ => None,
+        }
+    }
+
+    #[getter]
+    fn local_pref(&self) -> Option<u32> {
+        match self.update.get(Identifier::LOCAL_PREF) {
+            Some(PathAttribute::LOCAL_PREF(local_pref)) => Some(*local_pref),
+            _ => None,
+        }
+    }
+
+    #[getter]
+    fn med(&self) -> Option<u32> {
+        match self.update.get(Identifier::MULTI_EXIT_DISC) {
+            Some(PathAttribute::MULTI_EXIT_DISC(med)) => Some(*med),
+            _ => None,
+        }
+    }
+
+    /// The COMMUNITIES as (ASN, value) tuples.
+    #[getter]
+    fn communities(&self) -> Vec<(u16, u16)> {
+        match self.update.get(Identifier::COMMUNITY) {
+            Some(PathAttribute::COMMUNITY(communities)) => communities
+                .iter()
+                .map(|community| ((community >> 16) as u16, *community as u16))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The message as a dict, like `parse_message`.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        message_dict(py, &Message::Update(self.update.clone()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Update({})", self.update)
+    }
+}
+
+/// An OPEN message, `Open` in Python.
+#[pyclass(name = "Open", module = "bgp_rs")]
+#[derive(Clone, Debug)]
+pub struct PyOpen {
+    open: Open,
+}
+
+#[pymethods]
+impl PyOpen {
+    /// Parses an OPEN message, including its header.
+    #[staticmethod]
+    #[pyo3(signature = (data, capabilities = None))]
+    fn from_bytes(data: &[u8], capabilities: Option<&PyCapabilities>) -> PyResult<PyOpen> {
+        match decode(data, capabilities)? {
+            Message::Open(open) => Ok(PyOpen { open }),
+            message => Err(unexpected_type(&message)),
+        }
+    }
+
+    #[getter]
+    fn version(&self) -> u8 {
+        self.open.version
+    }
+
+    /// The ASN of the speaker, from the 4-octet ASN capability when present.
+    #[getter]
+    fn asn(&self) -> u32 {
+        self.open.asn()
+    }
+
+    #[getter]
+    fn hold_time(&self) -> u16 {
+        self.open.hold_timer
+    }
+
+    #[getter]
+    fn router_id(&self) -> String {
+        Ipv4Addr::from(self.open.identifier).to_string()
+    }
+
+    /// The message as a dict, like `parse_message`.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        message_dict(py, &Message::Open(self.open.clone()))
+    }
+}
+
+/// A NOTIFICATION message, `Notification` in Python.
+#[pyclass(name = "Notification", module = "bgp_rs")]
+#[derive(Clone, Debug)]
+pub struct PyNotification {
+    notification: Notification,
+}
+
+#[pymethods]
+impl PyNotification {
+    /// Parses a NOTIFICATION message, including its header.
+    #[staticmethod]
+    #[pyo3(signature = (data, capabilities = None))]
+    fn from_bytes(data: &[u8], capabilities: Option<&PyCapabilities>) -> PyResult<PyNotification> {
+        match decode(data, capabilities)? {
+            Message::Notification(notification) => Ok(PyNotification { notification }),
+            message => Err(unexpected_type(&message)),
+        }
+    }
+
+    #[getter]
+    fn code(&self) -> u8 {
+        self.notification.major_err_code()
+    }
+
+    #[getter]
+    fn subcode(&self) -> u8 {
+        self.notification.minor_err_code()
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new_bound(py, &self.notification.data)
+    }
+
+    /// The message as a dict, like `parse_message`.
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        message_dict(py, &Message::Notification(self.notification.clone()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Notification({})", self.notification)
+    }
+}
+
+/// Parses a message, including its header, into a dict.
+#[pyfunction]
+#[pyo3(name = "parse_message", signature = (data, capabilities = None))]
+fn py_parse_message(
+    py: Python<'_>,
+    data: &[u8],
+    capabilities: Option<&PyCapabilities>,
+) -> PyResult<PyObject> {
+    message_dict(py, &decode(data, capabilities)?)
+}
+
+/// The `bgp_rs` Python module.
+#[pymodule]
+#[pyo3(name = "bgp_rs")]
+pub fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyCapabilities>()?;
+    module.add_class::<PyUpdate>()?;
+    module.add_class::<PyOpen>()?;
+    module.add_class::<PyNotification>()?;
+    module.add_function(wrap_pyfunction!(py_parse_message, module)?)?;
+    Ok(())
+}
+
+fn decode(data: &[u8], capabilities: Option<&PyCapabilities>) -> PyResult<Message> {
+    let capabilities = capabilities
+        .map(|capabilities| capabilities.capabilities.clone())
+        .unwrap_or_default();
+    decode_message(data, &capabilities).map_err(|e| PyValueError::new_err(e.message))
+}
+
+fn unexpected_type(message: &Message) -> PyErr {
+    PyValueError::new_err(format!("Unexpected {} message", message.message_type()))
+}
+
+// ROUTE-REFRESH messages are not published by RIS Live, they only hold their family
+fn message_dict(py: Python<'_>, message: &Message) -> PyResult<PyObject> {
+    let mut data = Map::new();
+    match message {
+        Message::RouteRefresh(refresh) => {
+            data.insert("type".to_string(), json!("ROUTE-REFRESH"));
+            data.insert("afi".to_string(), json!(u16::from(refresh.afi)));
+            data.insert("safi".to_string(), json!(u8::from(refresh.safi)));
+        }
+        message => {
+            message_to_json(message, &mut data).map_err(|e| PyValueError::new_err(e.to_string()))?
+        }
+    }
+    Ok(to_python(py, &Value::Object(data)))
+}
+
+fn to_python(py: Python<'_>, value: &Value) -> PyObject {
+    match value {
+        Value::Null => py.None(),
+        Value::Bool(value) => value.into_py(py),
+        Value::Number(number) => match number.as_u64() {
+            Some(number) => number.into_py(py),
+            None => number.as_f64().unwrap_or_default().into_py(py),
+        },
+        Value::String(value) => value.into_py(py),
+        Value::Array(values) => {
+            let list = PyList::empty_bound(py);
+            for value in values {
+                // Appending to a new list does not fail
+                let _ = list.append(to_python(py, value));
+            }
+            list.into_py(py)
+        }
+        Value::Object(values) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in values {
+                let _ = dict.set_item(key, to_python(py, value));
+            }
+            dict.into_py(py)
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! The `replay` mod provides the replay of recorded BGP messages, e.g. to load-test BGP daemons
+//! with real-world feeds
+//! - `TimedMessage`, a message along with the time it was exchanged & the peer it was
+//!   exchanged with
+//! - `MrtReplay`, re-emitting the messages of a BGP4MP archive with their original timing (or
+//!   at a multiple of their original speed)
+//!
+//! Messages are parsed with the capabilities of their peer, see `PeerMap`.
+//!
+
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::*;
+
+/// A message along with the time it was exchanged & the peer it was exchanged with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedMessage {
+    /// The time (since the UNIX epoch) the message was exchanged.
+    pub timestamp: Duration,
+    /// The IP address & ASN of the peer, as used by `PeerMap`.
+    pub peer: (IpAddr, u32),
+    /// The message.
+    pub message: Message,
+}
+
+#[cfg(feature = "mrt")]
+pub use self::mrt_replay::MrtReplay;
+
+#[cfg(feature = "mrt")]
+mod mrt_replay {
+    use std::io::{Error, Read};
+    use std::net::IpAddr;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use mrt_rs::bgp4mp::BGP4MP;
+    use mrt_rs::Record;
+
+    use super::TimedMessage;
+    use crate::*;
+
+    /// Replays the BGP messages of the BGP4MP (and BGP4MP_ET) records of an MRT archive,
+    /// waiting between messages for the time that passed between them when they were recorded.
+    /// Other records are skipped.
+    ///
+    /// The AS_PATHs & NLRI of messages are parsed following the subtype of their record
+    /// (MESSAGE_AS4, MESSAGE_ADDPATH, ...), other capabilities are learned from the OPEN
+    /// messages of the archive.
+    ///
+    /// ```no_run
+    /// use bgp_rs::{BgpSession, Message, MrtReplay, PeerConfig};
+    /// use std::fs::File;
+    /// use std::io::BufReader;
+    /// use std::net::TcpStream;
+    ///
+    /// let stream = TcpStream::connect("192.0.2.1:179").unwrap();
+    /// let config = PeerConfig::new(65000, "192.0.2.2".parse().unwrap());
+    /// let mut session = BgpSession::connect(stream, config).unwrap();
+    ///
+    /// let archive = BufReader::new(File::open("updates.20200101.0000").unwrap());
+    /// let mut replay = MrtReplay::new(archive);
+    /// // Replay the archive at 10 times its original speed
+    /// replay.speed = Some(10.0);
+    /// for message in replay {
+    ///     if let Message::Update(update) = message.unwrap().message {
+    ///         session.send_update(&update).unwrap();
+    ///     }
+    /// }
+    /// ```
+    #[derive(Debug)]
+    pub struct MrtReplay<R>
+    where
+        R: Read,
+    {
+        /// The multiple of the original speed messages are replayed at (e.g. 2.0 to wait half
+        /// the time between messages), or None to replay them without waiting.
+        pub speed: Option<f64>,
+
+        stream: R,
+        peers: PeerMap,
+        // The time the first message was replayed at, and its timestamp
+        start: Option<(Instant, Duration)>,
+    }
+
+    impl<R> MrtReplay<R>
+    where
+        R: Read,
+    {
+        /// Creates a replay of the archive read from the stream, at its original speed.
+        pub fn new(stream: R) -> MrtReplay<R> {
+            MrtReplay {
+                speed: Some(1.0),
+                stream,
+                peers: PeerMap::new(Capabilities::default()),
+                start: None,
+            }
+        }
+
+        /// The capabilities of the peers of the messages replayed so far.
+        pub fn peers(&self) -> &PeerMap {
+            &self.peers
+        }
+
+        /// Reads the next message of the archive without waiting, None at the end of the
+        /// archive.
+        pub fn read(&mut self) -> Result<Option<TimedMessage>, Error> {
+            while let Some((header, record)) = mrt_rs::read(&mut self.stream)? {
+                let bgp4mp = match record {
+                    Record::BGP4MP(bgp4mp) | Record::BGP4MP_ET(bgp4mp) => bgp4mp,
+                    _ => continue,
+                };
+                // (peer address, peer ASN, data, 4-byte ASNs, ADD-PATH)
+                let (address, asn, data, as4, add_path) = match bgp4mp {
+                    BGP4MP::MESSAGE(message) | BGP4MP::MESSAGE_LOCAL(message) => (
+                        message.peer_address,
+                        u32::from(message.peer_as),
+                        message.message,
+                        false,
+                        false,
+                    ),
+                    BGP4MP::MESSAGE_ADDPATH(message) | BGP4MP::MESSAGE_LOCAL_ADDPATH(message) => (
+                        message.peer_address,
+                        u32::from(message.peer_as),
+                        message.message,
+                        false,
+                        true,
+                    ),
+                    BGP4MP::MESSAGE_AS4(message) | BGP4MP::MESSAGE_AS4_LOCAL(message) => (
+                        message.peer_address,
+                        message.peer_as,
+                        message.message,
+                        true,
+                        false,
+                    ),
+                    BGP4MP::MESSAGE_AS4_ADDPATH(message)
+                    | BGP4MP::MESSAGE_AS4_LOCAL_ADDPATH(message) => (
+                        message.peer_address,
+                        message.peer_as,
+                        message.message,
+                        true,
+                        true,
+                    ),
+                    _ => continue,
+                };
+                let message = self.parse(address, asn, &data, as4, add_path)?;
+                let micros = header.extended.min(999_999);
+                return Ok(Some(TimedMessage {
+                    timestamp: Duration::new(u64::from(header.timestamp), micros * 1000),
+                    peer: (address, asn),
+                    message,
+                }));
+            }
+            Ok(None)
+        }
+
+        fn parse(
+            &mut self,
+            address: IpAddr,
+            asn: u32,
+            data: &[u8],
+            as4: bool,
+            add_path: bool,
+        ) -> Result<Message, Error> {
+            let capabilities = &mut self.peers.get_mut(address, asn).capabilities;
+            capabilities.FOUR_OCTET_ASN_SUPPORT = as4;
+            capabilities.EXTENDED_PATH_NLRI_SUPPORT = add_path;
+            self.peers.parse(address, asn, data)
+        }
+
+        // Waits until the message is due, relative to the first message replayed
+        fn wait(&mut self, timestamp: Duration) {
+            let speed = match self.speed {
+                Some(speed) if speed > 0.0 => speed,
+                _ => return,
+            };
+            let (started, first) = *self
+                .start
+                .get_or_insert_with(|| (Instant::now(), timestamp));
+            let offset = timestamp.checked_sub(first).unwrap_or_default();
+            let due = Duration::from_nanos((offset.as_nanos() as f64 / speed) as u64);
+            let elapsed = started.elapsed();
+            if due > elapsed {
+                thread::sleep(due - elapsed);
+            }
+        }
+    }
+
+    impl<R> Iterator for MrtReplay<R>
+    where
+        R: Read,
+    {
+        type Item = Result<TimedMessage, Error>;
+
+        /// Reads the next message of the archive, waiting until it is due.
+        fn next(&mut self) -> Option<Self::Item> {
+            match self.read() {
+                Ok(Some(message)) => {
+                    self.wait(message.timestamp);
+                    Some(Ok(message))
+                }
+                Ok(None) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }
+    }
+}
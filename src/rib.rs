@@ -0,0 +1,459 @@
+//! The `rib` mod provides an in-memory RIB holding the Adj-RIB-In of every peer
+//! - Incremental maintenance from UPDATE messages
+//! - Lookup of the routes of a prefix across peers, exact or longest-prefix-match
+//! - Iteration over the routes of a peer, or of all peers
+//! - Stale routes retained across a Graceful Restart of a peer
+//!
+//! Routes are identified by their AFI/SAFI, prefix and ADD-PATH Path Identifier. Only routes
+//! holding a plain prefix (IP and labeled unicast) are stored, others (such as VPN, Flowspec,
+//! MVPN and L2VPN routes) are ignored.
+//!
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::*;
+
+/// Identifies a route in the RIB.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RouteKey {
+    /// The Address Family Identifier of the route
+    pub afi: AFI,
+    /// The Subsequent Address Family Identifier of the route
+    pub safi: SAFI,
+    /// The prefix of the route
+    pub prefix: Prefix,
+    /// The ADD-PATH Path Identifier of the route, 0 without ADD-PATH
+    pub path_id: u32,
+}
+
+impl RouteKey {
+    /// The key of a route of the given AFI/SAFI, or None for routes without a plain prefix.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, RouteKey, AFI, SAFI};
+    ///
+    /// let route = NLRIEncoding::IP_WITH_PATH_ID((("10.0.0.0".parse().unwrap(), 8).into(), 7));
+    /// let key = RouteKey::new(AFI::IPV4, SAFI::Unicast, &route).unwrap();
+    /// assert_eq!(key.path_id, 7);
+    /// ```
+    pub fn new(afi: AFI, safi: SAFI, route: &NLRIEncoding) -> Option<RouteKey> {
+        let (prefix, path_id) = match route {
+            NLRIEncoding::IP(prefix) | NLRIEncoding::IP_MPLS((prefix, _)) => (prefix, 0),
+            NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id))
+            | NLRIEncoding::IP_MPLS_WITH_PATH_ID((prefix, _, path_id)) => (prefix, *path_id),
+            _ => return None,
+        };
+        Some(RouteKey {
+            afi,
+            safi,
+            prefix: prefix.clone(),
+            path_id,
+        })
+    }
+}
+
+/// A RIB holding the routes received from every peer (their Adj-RIB-In), with the path
+/// attributes of each route. Peers are identified by any type, such as their address.
+///
+/// The path attributes are shared between the routes announced by the same UPDATE message.
+/// They include MP_REACH_NLRI (without routes) to retain the next hop of MP routes.
+///
+/// ```
+/// use bgp_rs::{NLRIEncoding, PathAttribute, Rib, RouteKey, Update, AFI, SAFI};
+/// use std::net::IpAddr;
+///
+/// let route = NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into());
+/// let update = Update::new(
+///     vec![],
+///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+///     vec![route.clone()],
+/// );
+/// let peer: IpAddr = "192.0.2.1".parse().unwrap();
+/// let mut rib = Rib::new();
+/// rib.apply(&update, peer);
+///
+/// let key = RouteKey::new(AFI::IPV4, SAFI::Unicast, &route).unwrap();
+/// assert_eq!(rib.lookup(&key).count(), 1);
+/// assert_eq!(rib.get(&peer, &key).unwrap().len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rib<P>
+where
+    P: Clone + Eq + Hash,
+{
+    peers: HashMap<P, BTreeMap<RouteKey, Entry>>,
+    // The number of routes of each prefix, for longest-prefix-match lookups
+    index: HashMap<(AFI, SAFI), PrefixTrie<usize>>,
+}
+
+// A route of the RIB, keeping its NLRI to announce it again (such as with its MPLS label)
+#[derive(Clone, Debug)]
+struct Entry {
+    route: NLRIEncoding,
+    attributes: Arc<Vec<PathAttribute>>,
+    stale: bool,
+}
+
+impl<P> Default for Rib<P>
+where
+    P: Clone + Eq + Hash,
+{
+    fn default() -> Self {
+        Rib {
+            peers: HashMap::new(),
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl<P> Rib<P>
+where
+    P: Clone + Eq + Hash,
+{
+    /// Creates an empty RIB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the routes withdrawn and announced by an UPDATE message received from a peer.
+    /// Announced routes replace the routes with the same key.
+    pub fn apply(&mut self, update: &Update, peer: P) {
+        let routes = self.peers.entry(peer).or_default();
+        for (afi, safi, route) in update.withdrawn() {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                if routes.remove(&key).is_some() {
+                    unindex(&mut self.index, &key);
+                }
+            }
+        }
+
+        let mut announced = update.announced().peekable();
+        if announced.peek().is_none() {
+            return;
+        }
+        let attributes = Arc::new(route_attributes(update));
+        for (afi, safi, route) in announced {
+            if let Some(key) = RouteKey::new(afi, safi, route) {
+                let entry = Entry {
+                    route: route.clone(),
+                    attributes: Arc::clone(&attributes),
+                    stale: false,
+                };
+                if routes.insert(key.clone(), entry).is_none() {
+                    index(&mut self.index, &key);
+                }
+            }
+        }
+    }
+
+    /// Removes all routes of a peer, such as when its session went down.
+    pub fn remove_peer(&mut self, peer: &P) {
+        for key in self.peers.remove(peer).iter().flat_map(BTreeMap::keys) {
+            unindex(&mut self.index, key);
+        }
+    }
+
+    /// Marks all routes of a peer as stale, such as when its session went down while it is
+    /// restarting gracefully (RFC4724). Stale routes are kept until they are announced again,
+    /// withdrawn, or removed with `remove_stale`.
+    pub fn mark_stale(&mut self, peer: &P) {
+        for entry in self
+            .peers
+            .get_mut(peer)
+            .into_iter()
+            .flat_map(BTreeMap::values_mut)
+        {
+            entry.stale = true;
+        }
+    }
+
+    /// Removes the stale routes of a peer in the AFI/SAFIs accepted by the filter, and returns
+    /// how many were removed.
+    pub fn remove_stale<F>(&mut self, peer: &P, filter: F) -> usize
+    where
+        F: Fn(AFI, SAFI) -> bool,
+    {
+        let routes = match self.peers.get_mut(peer) {
+            Some(routes) => routes,
+            None => return 0,
+        };
+        let stale: Vec<RouteKey> = routes
+            .iter()
+            .filter(|(key, entry)| entry.stale && filter(key.afi, key.safi))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &stale {
+            routes.remove(key);
+            unindex(&mut self.index, key);
+        }
+        stale.len()
+    }
+
+    /// Whether a route received from a peer is stale, see `mark_stale`.
+    pub fn is_stale(&self, peer: &P, key: &RouteKey) -> bool {
+        self.peers
+            .get(peer)
+            .and_then(|routes| routes.get(key))
+            .map_or(false, |entry| entry.stale)
+    }
+
+    /// The UPDATE messages turning this RIB into the other RIB: withdrawals of the routes the
+    /// other RIB lacks, followed by announcements of the routes it adds or changes, grouped by
+    /// their path attributes. The messages are not limited in size, see `Update::split`.
+    ///
+    /// This is meant for RIBs of a single peer, such as snapshots of a table being replayed or
+    /// re-advertised: the messages of every peer are concatenated, see `peer_diff`.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Rib, Update};
+    ///
+    /// let update = |next_hop: &str, routes: &[&str]| {
+    ///     Update::new(
+    ///         vec![],
+    ///         vec![PathAttribute::NEXT_HOP(next_hop.parse().unwrap())],
+    ///         routes.iter().map(|r| NLRIEncoding::IP(r.parse().unwrap())).collect(),
+    ///     )
+    /// };
+    /// let mut old = Rib::new();
+    /// old.apply(&update("192.0.2.1", &["10.0.0.0/8", "10.1.0.0/16"]), ());
+    /// let mut new = Rib::new();
+    /// new.apply(&update("192.0.2.1", &["10.0.0.0/8"]), ());
+    /// new.apply(&update("192.0.2.2", &["10.2.0.0/16"]), ());
+    ///
+    /// let updates: Vec<String> = old.diff(&new).iter().map(|u| u.to_string()).collect();
+    /// assert_eq!(updates, vec!["withdrawn 10.1.0.0/16", "10.2.0.0/16 via 192.0.2.2"]);
+    /// ```
+    pub fn diff(&self, other: &Rib<P>) -> Vec<Update> {
+        let added = other
+            .peers
+            .keys()
+            .filter(|peer| !self.peers.contains_key(peer));
+        self.peers
+            .keys()
+            .chain(added)
+            .flat_map(|peer| self.peer_diff(other, peer))
+            .collect()
+    }
+
+    /// The UPDATE messages turning the routes of a peer in this RIB into its routes in the
+    /// other RIB, see `diff`.
+    pub fn peer_diff(&self, other: &Rib<P>, peer: &P) -> Vec<Update> {
+        let empty = BTreeMap::new();
+        let old = self.peers.get(peer).unwrap_or(&empty);
+        let new = other.peers.get(peer).unwrap_or(&empty);
+
+        let withdrawn = old
+            .iter()
+            .filter(|(key, _)| !new.contains_key(key))
+            .map(|(key, entry)| (key.afi, key.safi, entry.route.clone()));
+        let mut updates = withdrawal_updates(withdrawn);
+
+        // The new and changed routes
+        let announced = new.iter().filter(|(key, entry)| {
+            !old.get(key).map_or(false, |old| {
+                old.route == entry.route
+                    && (Arc::ptr_eq(&old.attributes, &entry.attributes)
+                        || format!("{:?}", old.attributes) == format!("{:?}", entry.attributes))
+            })
+        });
+        updates.extend(announcement_updates(announced.map(|(key, entry)| {
+            (key.afi, key.safi, &entry.route, &entry.attributes)
+        })));
+        updates
+    }
+
+    /// The path attributes of a route received from a peer.
+    pub fn get(&self, peer: &P, key: &RouteKey) -> Option<&[PathAttribute]> {
+        self.peers
+            .get(peer)
+            .and_then(|routes| routes.get(key))
+            .map(|entry| entry.attributes.as_slice())
+    }
+
+    /// The peers that announced a route, along with its path attributes.
+    pub fn lookup<'a>(
+        &'a self,
+        key: &'a RouteKey,
+    ) -> impl Iterator<Item = (&'a P, &'a [PathAttribute])> + 'a {
+        self.peers.iter().filter_map(move |(peer, routes)| {
+            routes
+                .get(key)
+                .map(|entry| (peer, entry.attributes.as_slice()))
+        })
+    }
+
+    /// The routes of the most specific prefix covering the given prefix (or address, as a host
+    /// prefix) in an AFI/SAFI, along with the peers that announced them.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Rib, Update, AFI, SAFI};
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+    ///     vec![
+    ///         NLRIEncoding::IP("10.0.0.0/8".parse().unwrap()),
+    ///         NLRIEncoding::IP("10.1.0.0/16".parse().unwrap()),
+    ///     ],
+    /// );
+    /// let mut rib = Rib::new();
+    /// rib.apply(&update, 1);
+    ///
+    /// let address = "10.1.2.3/32".parse().unwrap();
+    /// let routes: Vec<_> = rib.longest_match(AFI::IPV4, SAFI::Unicast, &address).collect();
+    /// assert_eq!(routes.len(), 1);
+    /// assert_eq!(routes[0].1.prefix.to_string(), "10.1.0.0/16");
+    /// ```
+    pub fn longest_match(
+        &self,
+        afi: AFI,
+        safi: SAFI,
+        prefix: &Prefix,
+    ) -> impl Iterator<Item = (&P, &RouteKey, &[PathAttribute])> {
+        let mut routes = vec![];
+        let matched = self
+            .index
+            .get(&(afi, safi))
+            .and_then(|trie| trie.longest_match(prefix));
+        if let Some((prefix, _)) = matched {
+            let start = RouteKey {
+                afi,
+                safi,
+                prefix: prefix.clone(),
+                path_id: 0,
+            };
+            let end = RouteKey {
+                path_id: u32::max_value(),
+                ..start.clone()
+            };
+            for (peer, peer_routes) in &self.peers {
+                for (key, entry) in peer_routes.range(start.clone()..=end.clone()) {
+                    routes.push((peer, key, entry.attributes.as_slice()));
+                }
+            }
+        }
+        routes.into_iter()
+    }
+
+    /// The routes received from a peer, ordered by their key.
+    pub fn routes<'a>(
+        &'a self,
+        peer: &P,
+    ) -> impl Iterator<Item = (&'a RouteKey, &'a [PathAttribute])> + 'a {
+        self.peers
+            .get(peer)
+            .into_iter()
+            .flat_map(|routes| routes.iter())
+            .map(|(key, entry)| (key, entry.attributes.as_slice()))
+    }
+
+    /// The routes of all peers.
+    pub fn iter(&self) -> impl Iterator<Item = (&P, &RouteKey, &[PathAttribute])> {
+        self.peers.iter().flat_map(|(peer, routes)| {
+            routes
+                .iter()
+                .map(move |(key, entry)| (peer, key, entry.attributes.as_slice()))
+        })
+    }
+
+    /// The peers that UPDATE messages were applied for.
+    pub fn peers(&self) -> impl Iterator<Item = &P> {
+        self.peers.keys()
+    }
+
+    /// The number of routes of all peers.
+    pub fn len(&self) -> usize {
+        self.peers.values().map(BTreeMap::len).sum()
+    }
+
+    /// Whether no routes are held.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// The path attributes of the routes announced by an UPDATE message, keeping MP_REACH_NLRI
+// without routes for the next hop of MP routes
+pub(crate) fn route_attributes(update: &Update) -> Vec<PathAttribute> {
+    update
+        .attributes
+        .iter()
+        .filter_map(|attribute| match attribute {
+            PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                Some(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI {
+                    announced_routes: vec![],
+                    ..(**mp_reach).clone()
+                })))
+            }
+            PathAttribute::MP_UNREACH_NLRI(_) => None,
+            attribute => Some(attribute.clone()),
+        })
+        .collect()
+}
+
+// The UPDATE messages announcing routes with their path attributes (see `route_attributes`),
+// one per distinct set of path attributes. MP routes are added to the MP_REACH_NLRI of their
+// AFI/SAFI, others to the announced routes.
+pub(crate) fn announcement_updates<'a>(
+    routes: impl IntoIterator<Item = (AFI, SAFI, &'a NLRIEncoding, &'a Arc<Vec<PathAttribute>>)>,
+) -> Vec<Update> {
+    let mut groups: Vec<Update> = vec![];
+    let mut shared: HashMap<*const Vec<PathAttribute>, usize> = HashMap::new();
+    let mut contents: HashMap<String, usize> = HashMap::new();
+    for (afi, safi, route, attributes) in routes {
+        let pointer: *const Vec<PathAttribute> = &**attributes;
+        let group = match shared.get(&pointer) {
+            Some(group) => *group,
+            None => {
+                let group = *contents
+                    .entry(format!("{:?}", attributes))
+                    .or_insert_with(|| {
+                        groups.push(Update::new(vec![], attributes.to_vec(), vec![]));
+                        groups.len() - 1
+                    });
+                shared.insert(pointer, group);
+                group
+            }
+        };
+        let update = &mut groups[group];
+        let mp_reach = update
+            .attributes
+            .iter_mut()
+            .find_map(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach)
+                    if mp_reach.afi == afi && mp_reach.safi == safi =>
+                {
+                    Some(mp_reach)
+                }
+                _ => None,
+            });
+        match mp_reach {
+            Some(mp_reach) => mp_reach.announced_routes.push(route.clone()),
+            None => update.announced_routes.push(route.clone()),
+        }
+    }
+    groups
+}
+
+fn index(index: &mut HashMap<(AFI, SAFI), PrefixTrie<usize>>, key: &RouteKey) {
+    let trie = index.entry((key.afi, key.safi)).or_default();
+    match trie.get_mut(&key.prefix) {
+        Some(count) => *count += 1,
+        None => {
+            trie.insert(&key.prefix, 1);
+        }
+    }
+}
+
+fn unindex(index: &mut HashMap<(AFI, SAFI), PrefixTrie<usize>>, key: &RouteKey) {
+    if let Some(trie) = index.get_mut(&(key.afi, key.safi)) {
+        if let Some(count) = trie.get_mut(&key.prefix) {
+            *count -= 1;
+            if *count == 0 {
+                trie.remove(&key.prefix);
+            }
+        }
+    }
+}
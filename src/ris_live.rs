@@ -0,0 +1,348 @@
+//! The `ris_live` mod converts [RIS Live](https://ris-live.ripe.net) JSON messages
+//! to and from BGP messages
+//! - UPDATE: AS path, origin, MED, aggregator, communities, announcements & withdrawals
+//! - OPEN: version, ASN, hold time, router ID and capabilities
+//! - NOTIFICATION & KEEPALIVE
+//!
+//! When a message includes the `raw` BGP message (the `includeRaw` option of RIS Live),
+//! the raw message is parsed instead of the JSON fields, as it is a lossless representation.
+//!
+
+use std::convert::TryFrom;
+use std::io::Error;
+use std::net::{IpAddr, Ipv4Addr};
+
+use serde_json::{json, Map, Value};
+
+use crate::json::*;
+use crate::*;
+
+/// A BGP message as published by RIS Live, along with the peer it was received from.
+///
+/// # Example
+/// ```
+/// use bgp_rs::{Message, RisLiveMessage};
+///
+/// let json = r#"{"type": "ris_message", "data": {
+///     "timestamp": 1568279796.63, "peer": "192.0.2.0", "peer_asn": "64496", "host": "rrc21",
+///     "type": "UPDATE", "path": [64496, 64497], "origin": "igp",
+///     "announcements": [{"next_hop": "192.0.2.0", "prefixes": ["198.51.100.0/24"]}]
+/// }}"#;
+/// let message = RisLiveMessage::from_json(json).unwrap();
+/// assert_eq!(message.peer_asn, 64496);
+/// match &message.message {
+///     Message::Update(update) => assert_eq!(update.announced_routes.len(), 1),
+///     _ => unreachable!(),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RisLiveMessage {
+    /// Time the message was received, in seconds since the UNIX epoch
+    pub timestamp: f64,
+    /// The IP address of the peer the message was received from
+    pub peer: IpAddr,
+    /// The ASN of the peer the message was received from
+    pub peer_asn: u32,
+    /// The RIS collector that received the message (e.g. "rrc21")
+    pub host: String,
+    /// The BGP message
+    pub message: Message,
+}
+
+impl RisLiveMessage {
+    /// Parses a RIS Live message, either the `ris_message` envelope or its `data`.
+    pub fn from_json(json: &str) -> Result<RisLiveMessage, Error> {
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        let data = match value.get("type").and_then(Value::as_str) {
+            Some("ris_message") => value.get("data").ok_or_else(|| invalid("Missing data"))?,
+            _ => &value,
+        };
+
+        let peer_asn = match data.get("peer_asn") {
+            Some(Value::String(asn)) => asn.parse().ok(),
+            Some(asn) => asn.as_u64().and_then(|asn| u32::try_from(asn).ok()),
+            None => None,
+        }
+        .ok_or_else(|| invalid("Invalid peer_asn"))?;
+        let message = match data.get("raw").and_then(Value::as_str) {
+            Some(raw) => parse_raw(raw)?,
+            None => match get_str(data, "type")? {
+                "UPDATE" => Message::Update(update_from_json(data)?),
+                "OPEN" => Message::Open(open_from_json(data, '/')?),
+                "NOTIFICATION" => {
+                    let notification = data
+                        .get("notification")
+                        .ok_or_else(|| invalid("Missing notification"))?;
+                    Message::Notification(notification_from_json(notification)?)
+                }
+                "KEEPALIVE" => Message::KeepAlive,
+                message_type => {
+                    return Err(invalid(format!(
+                        "Unsupported message type: {}",
+                        message_type
+                    )));
+                }
+            },
+        };
+
+        Ok(RisLiveMessage {
+            timestamp: data
+                .get("timestamp")
+                .and_then(Value::as_f64)
+                .unwrap_or_default(),
+            peer: parse_address(get_str(data, "peer")?)?,
+            peer_asn,
+            host: data
+                .get("host")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            message,
+        })
+    }
+
+    /// Writes the `ris_message` envelope, including the `raw` BGP message.
+    ///
+    /// ROUTE-REFRESH messages are not published by RIS Live, and can not be converted.
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut data = Map::new();
+        data.insert("timestamp".to_string(), json!(self.timestamp));
+        data.insert("peer".to_string(), json!(self.peer.to_string()));
+        data.insert("peer_asn".to_string(), json!(self.peer_asn.to_string()));
+        data.insert("host".to_string(), json!(self.host));
+        message_to_json(&self.message, &mut data)?;
+
+        let mut raw: Vec<u8> = Vec::with_capacity(64);
+        self.message
+            .encode_with_capabilities(&mut raw, &json_capabilities())?;
+        data.insert("raw".to_string(), json!(to_hex(&raw)));
+
+        Ok(json!({"type": "ris_message", "data": data}).to_string())
+    }
+}
+
+// Adds the type & fields of the message to the `data` of a RIS Live message
+pub(crate) fn message_to_json(
+    message: &Message,
+    data: &mut Map<String, Value>,
+) -> Result<(), Error> {
+    match message {
+        Message::Update(update) => {
+            data.insert("type".to_string(), json!("UPDATE"));
+            update_to_json(update, data)?;
+        }
+        Message::Open(open) => {
+            data.insert("type".to_string(), json!("OPEN"));
+            data.extend(open_to_json(open, '/'));
+        }
+        Message::Notification(notification) => {
+            data.insert("type".to_string(), json!("NOTIFICATION"));
+            data.insert(
+                "notification".to_string(),
+                notification_to_json(notification),
+            );
+        }
+        Message::KeepAlive => {
+            data.insert("type".to_string(), json!("KEEPALIVE"));
+        }
+        Message::RouteRefresh(_) => {
+            return Err(invalid("RIS Live does not publish ROUTE-REFRESH messages"));
+        }
+    }
+    Ok(())
+}
+
+fn update_from_json(data: &Value) -> Result<Update, Error> {
+    let mut attributes: Vec<PathAttribute> = Vec::with_capacity(8);
+    if let Some(origin) = data.get("origin") {
+        attributes.push(PathAttribute::ORIGIN(match origin.as_str() {
+            Some("igp") | Some("IGP") => Origin::IGP,
+            Some("egp") | Some("EGP") => Origin::EGP,
+            Some("incomplete") | Some("INCOMPLETE") => Origin::INCOMPLETE,
+            _ => return Err(invalid(format!("Invalid origin: {}", origin))),
+        }));
+    }
+    if let Some(path) = data.get("path") {
+        attributes.push(PathAttribute::AS_PATH(as_path_from_json(path)?));
+    }
+
+    let mut announced_routes: Vec<NLRIEncoding> = vec![];
+    let mut next_hop: Option<IpAddr> = None;
+    let mut mp_reach: Option<MPReachNLRI> = None;
+    for announcement in get_array(data, "announcements")? {
+        let next_hops = get_str(announcement, "next_hop")?
+            .split(',')
+            .map(parse_address)
+            .collect::<Result<Vec<IpAddr>, Error>>()?;
+        for prefix in get_array(announcement, "prefixes")? {
+            let prefix = parse_prefix(prefix)?;
+            match (prefix.protocol, next_hops.as_slice()) {
+                (AFI::IPV4, [address @ IpAddr::V4(_)]) => {
+                    if *next_hop.get_or_insert(*address) != *address {
+                        return Err(invalid("Multiple IPv4 next hops in one UPDATE"));
+                    }
+                    announced_routes.push(NLRIEncoding::IP(prefix));
+                }
+                (afi, addresses) => {
+                    let mp_reach = mp_reach.get_or_insert_with(|| MPReachNLRI {
+                        afi,
+                        safi: SAFI::Unicast,
+                        next_hop: vec![],
+                        announced_routes: vec![],
+                    });
+                    if mp_reach.afi != afi {
+                        return Err(invalid("Multiple address families in one UPDATE"));
+                    }
+                    if mp_reach.next_hop.is_empty() {
+                        mp_reach.set_next_hop(addresses, &json_capabilities())?;
+                    } else if mp_reach.next_hop_addresses(&json_capabilities())? != addresses {
+                        return Err(invalid("Multiple next hops in one UPDATE"));
+                    }
+                    mp_reach.announced_routes.push(NLRIEncoding::IP(prefix));
+                }
+            }
+        }
+    }
+    if let Some(next_hop) = next_hop {
+        attributes.push(PathAttribute::NEXT_HOP(next_hop));
+    }
+
+    if let Some(med) = data.get("med") {
+        let med = med
+            .as_u64()
+            .and_then(|med| u32::try_from(med).ok())
+            .ok_or_else(|| invalid(format!("Invalid med: {}", med)))?;
+        attributes.push(PathAttribute::MULTI_EXIT_DISC(med));
+    }
+    if let Some(aggregator) = data.get("aggregator").and_then(Value::as_str) {
+        let mut parts = aggregator.splitn(2, ':');
+        let asn = parts.next().and_then(|asn| asn.parse::<u32>().ok());
+        let address = parts.next().and_then(|ip| ip.parse::<Ipv4Addr>().ok());
+        match (asn, address) {
+            (Some(asn), Some(address)) => {
+                attributes.push(PathAttribute::AGGREGATOR((asn, address)));
+            }
+            _ => return Err(invalid(format!("Invalid aggregator: {}", aggregator))),
+        }
+    }
+    if let Some(communities) = data.get("community") {
+        let communities = communities
+            .as_array()
+            .ok_or_else(|| invalid("Invalid community"))?
+            .iter()
+            .map(|community| match community.as_array().map(Vec::as_slice) {
+                Some([asn, value]) => match (as_u16(asn), as_u16(value)) {
+                    (Some(asn), Some(value)) => Ok(u32::from(asn) << 16 | u32::from(value)),
+                    _ => Err(invalid(format!("Invalid community: {}", community))),
+                },
+                _ => Err(invalid(format!("Invalid community: {}", community))),
+            })
+            .collect::<Result<Vec<u32>, Error>>()?;
+        attributes.push(PathAttribute::COMMUNITY(communities));
+    }
+    if let Some(mp_reach) = mp_reach {
+        attributes.push(PathAttribute::MP_REACH_NLRI(Box::new(mp_reach)));
+    }
+
+    let mut withdrawn_routes: Vec<NLRIEncoding> = vec![];
+    let mut mp_unreach: Option<MPUnreachNLRI> = None;
+    for prefix in get_array(data, "withdrawals")? {
+        let prefix = parse_prefix(prefix)?;
+        match prefix.protocol {
+            AFI::IPV4 => withdrawn_routes.push(NLRIEncoding::IP(prefix)),
+            afi => {
+                let mp_unreach = mp_unreach.get_or_insert_with(|| MPUnreachNLRI {
+                    afi,
+                    safi: SAFI::Unicast,
+                    withdrawn_routes: vec![],
+                });
+                mp_unreach.withdrawn_routes.push(NLRIEncoding::IP(prefix));
+            }
+        }
+    }
+    if let Some(mp_unreach) = mp_unreach {
+        attributes.push(PathAttribute::MP_UNREACH_NLRI(Box::new(mp_unreach)));
+    }
+
+    Ok(Update::new(withdrawn_routes, attributes, announced_routes))
+}
+
+fn update_to_json(update: &Update, data: &mut Map<String, Value>) -> Result<(), Error> {
+    let mut announcements: Vec<Value> = vec![];
+    let mut withdrawals: Vec<Value> = prefixes_to_json(&update.withdrawn_routes);
+    if !update.announced_routes.is_empty() {
+        if let Some(PathAttribute::NEXT_HOP(next_hop)) = update.get(Identifier::NEXT_HOP) {
+            announcements.push(json!({
+                "next_hop": next_hop.to_string(),
+                "prefixes": prefixes_to_json(&update.announced_routes),
+            }));
+        }
+    }
+
+    for attribute in &update.attributes {
+        match attribute {
+            PathAttribute::ORIGIN(origin) => {
+                let origin = match origin {
+                    Origin::IGP => "igp",
+                    Origin::EGP => "egp",
+                    Origin::INCOMPLETE => "incomplete",
+                };
+                data.insert("origin".to_string(), json!(origin));
+            }
+            PathAttribute::AS_PATH(path) => {
+                data.insert("path".to_string(), as_path_to_json(path));
+            }
+            PathAttribute::MULTI_EXIT_DISC(med) => {
+                data.insert("med".to_string(), json!(med));
+            }
+            PathAttribute::AGGREGATOR((asn, address)) => {
+                data.insert(
+                    "aggregator".to_string(),
+                    json!(format!("{}:{}", asn, address)),
+                );
+            }
+            PathAttribute::COMMUNITY(communities) => {
+                let communities: Vec<Value> = communities
+                    .iter()
+                    .map(|community| json!([community >> 16, community & 0xffff]))
+                    .collect();
+                data.insert("community".to_string(), Value::Array(communities));
+            }
+            PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                let next_hops: Vec<String> = mp_reach
+                    .next_hop_addresses(&json_capabilities())?
+                    .iter()
+                    .map(IpAddr::to_string)
+                    .collect();
+                announcements.push(json!({
+                    "next_hop": next_hops.join(","),
+                    "prefixes": prefixes_to_json(&mp_reach.announced_routes),
+                }));
+            }
+            PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+                withdrawals.extend(prefixes_to_json(&mp_unreach.withdrawn_routes));
+            }
+            _ => (),
+        }
+    }
+
+    if !announcements.is_empty() {
+        data.insert("announcements".to_string(), Value::Array(announcements));
+    }
+    if !withdrawals.is_empty() {
+        data.insert("withdrawals".to_string(), Value::Array(withdrawals));
+    }
+    Ok(())
+}
+
+fn prefixes_to_json(routes: &[NLRIEncoding]) -> Vec<Value> {
+    routes
+        .iter()
+        .filter_map(|route| match route {
+            NLRIEncoding::IP(prefix) | NLRIEncoding::IP_WITH_PATH_ID((prefix, _)) => {
+                Some(json!(prefix.to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,377 @@
+//! The `route_refresh` mod provides structs and implementation for ROUTE-REFRESH messages
+//! - Enhanced Route Refresh subtypes [RFC7313]
+//! - Outbound Route Filtering entries [RFC5291]
+//!   - Address Prefix ORF [RFC5292]
+//!
+
+use std::convert::TryFrom;
+use std::io::{Cursor, Error, ErrorKind, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::*;
+
+/// Represents a BGP Route Refresh message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RouteRefresh {
+    /// Address Family being requested
+    pub afi: AFI,
+    /// Subsequent Address Family being requested
+    pub safi: SAFI,
+    /// Message subtype, always Normal for senders without Enhanced Route Refresh support
+    pub subtype: RouteRefreshSubtype,
+    /// Outbound Route Filtering entries to apply before refreshing (if present)
+    pub orf: Option<RouteRefreshORF>,
+}
+
+impl RouteRefresh {
+    /// Parse RouteRefresh message, including ORF entries when present
+    pub fn parse(header: &Header, stream: &mut impl Read) -> Result<RouteRefresh, Error> {
+        let afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
+        let subtype = RouteRefreshSubtype::from(stream.read_u8()?);
+        let safi = SAFI::try_from(stream.read_u8()?)?;
+
+        let is_demarcation = match subtype {
+            RouteRefreshSubtype::BeginOfRouteRefresh | RouteRefreshSubtype::EndOfRouteRefresh => {
+                true
+            }
+            _ => false,
+        };
+        if is_demarcation && header.length != 23 {
+            // The complete message is included in the Notification data
+            let mut data = Vec::with_capacity(header.length as usize);
+            header.encode(&mut data)?;
+            data.write_u16::<BigEndian>(u16::from(afi))?;
+            data.write_u8(subtype.into())?;
+            data.write_u8(u8::from(safi))?;
+            if header.length > 23 {
+                let mut remaining = vec![0; header.length as usize - 23];
+                stream.read_exact(&mut remaining)?;
+                data.extend_from_slice(&remaining);
+            }
+            return Err(notification_error(
+                NotificationCode::RouteRefreshMessage(
+                    RouteRefreshMessageSubcode::InvalidMessageLength,
+                ),
+                data,
+                format!(
+                    "Invalid {:?} ROUTE-REFRESH length: {}",
+                    subtype, header.length
+                ),
+            ));
+        }
+
+        let orf = if header.length > 23 {
+            let mut buffer = vec![0; header.length as usize - 23];
+            stream.read_exact(&mut buffer)?;
+            Some(RouteRefreshORF::parse(&buffer, afi)?)
+        } else {
+            None
+        };
+
+        Ok(RouteRefresh {
+            afi,
+            safi,
+            subtype,
+            orf,
+        })
+    }
+
+    /// Encode RouteRefresh to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        buf.write_u16::<BigEndian>(u16::from(self.afi))?;
+        buf.write_u8(self.subtype.into())?;
+        buf.write_u8(u8::from(self.safi))?;
+        if let Some(orf) = &self.orf {
+            orf.encode(buf)?;
+        }
+        Ok(())
+    }
+}
+
+/// The subtype of a ROUTE-REFRESH message. Defined in [RFC7313](http://www.iana.org/go/rfc7313).
+///
+/// ```
+/// use bgp_rs::RouteRefreshSubtype;
+///
+/// assert_eq!(RouteRefreshSubtype::from(1), RouteRefreshSubtype::BeginOfRouteRefresh);
+/// assert_eq!(RouteRefreshSubtype::from(255), RouteRefreshSubtype::Reserved(255));
+/// assert_eq!(u8::from(RouteRefreshSubtype::EndOfRouteRefresh), 2);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+pub enum RouteRefreshSubtype {
+    /// 0 - Normal route refresh request [RFC2918]
+    Normal,
+    /// 1 - Demarcation of the beginning of a route refresh (BoRR)
+    BeginOfRouteRefresh,
+    /// 2 - Demarcation of the ending of a route refresh (EoRR)
+    EndOfRouteRefresh,
+    /// Reserved (or unassigned) subtype, such messages should be ignored
+    Reserved(u8),
+}
+
+impl From<u8> for RouteRefreshSubtype {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => RouteRefreshSubtype::Normal,
+            1 => RouteRefreshSubtype::BeginOfRouteRefresh,
+            2 => RouteRefreshSubtype::EndOfRouteRefresh,
+            x => RouteRefreshSubtype::Reserved(x),
+        }
+    }
+}
+
+impl From<RouteRefreshSubtype> for u8 {
+    fn from(subtype: RouteRefreshSubtype) -> Self {
+        match subtype {
+            RouteRefreshSubtype::Normal => 0,
+            RouteRefreshSubtype::BeginOfRouteRefresh => 1,
+            RouteRefreshSubtype::EndOfRouteRefresh => 2,
+            RouteRefreshSubtype::Reserved(x) => x,
+        }
+    }
+}
+
+/// Indicates when the peer should re-advertise routes after applying the ORF entries.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum WhenToRefresh {
+    /// Re-advertise immediately
+    Immediate = 1,
+    /// Defer re-advertisement until a subsequent ROUTE-REFRESH with IMMEDIATE is received
+    Defer = 2,
+}
+
+impl TryFrom<u8> for WhenToRefresh {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(WhenToRefresh::Immediate),
+            2 => Ok(WhenToRefresh::Defer),
+            _ => Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Number {} does not represent a valid When-to-refresh.",
+                    value
+                ),
+            )),
+        }
+    }
+}
+
+/// The Outbound Route Filtering part of a ROUTE-REFRESH message. Defined in [RFC5291](http://www.iana.org/go/rfc5291).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RouteRefreshORF {
+    /// When the peer should re-advertise routes
+    pub when_to_refresh: WhenToRefresh,
+    /// ORF entries grouped by ORF type
+    pub orfs: Vec<ORF>,
+}
+
+impl RouteRefreshORF {
+    fn parse(buffer: &[u8], afi: AFI) -> Result<RouteRefreshORF, Error> {
+        let mut cursor = Cursor::new(buffer);
+        let when_to_refresh = WhenToRefresh::try_from(cursor.read_u8()?)?;
+        let mut orfs = Vec::with_capacity(1);
+        while (cursor.position() as usize) < buffer.len() {
+            let orf_type = cursor.read_u8()?;
+            let length = cursor.read_u16::<BigEndian>()?;
+            let mut value = vec![0; usize::from(length)];
+            cursor.read_exact(&mut value)?;
+            orfs.push(ORF::parse(orf_type, value, afi)?);
+        }
+        Ok(RouteRefreshORF {
+            when_to_refresh,
+            orfs,
+        })
+    }
+
+    /// Encode ORF entries to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        buf.write_u8(self.when_to_refresh as u8)?;
+        for orf in &self.orfs {
+            let mut orf_buf: Vec<u8> = Vec::with_capacity(16);
+            orf.encode_entries(&mut orf_buf)?;
+            if orf_buf.len() > std::u16::MAX as usize {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Cannot encode ORF entries with length {}", orf_buf.len()),
+                ));
+            }
+            buf.write_u8(orf.orf_type())?;
+            buf.write_u16::<BigEndian>(orf_buf.len() as u16)?;
+            buf.write_all(&orf_buf)?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of ORF entries of a single ORF type.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ORF {
+    /// 64 - Address Prefix ORF [RFC5292]
+    AddressPrefix(Vec<AddressPrefixORFEntry>),
+    /// Unknown (or unsupported) ORF type
+    Unknown {
+        /// The ORF type
+        orf_type: u8,
+        /// The ORF entries as bytes
+        value: Vec<u8>,
+    },
+}
+
+impl ORF {
+    fn parse(orf_type: u8, value: Vec<u8>, afi: AFI) -> Result<ORF, Error> {
+        match (orf_type, afi) {
+            (64, AFI::IPV4) | (64, AFI::IPV6) => {
+                let mut cursor = Cursor::new(&value);
+                let mut entries = Vec::with_capacity(value.len() / 8);
+                while (cursor.position() as usize) < value.len() {
+                    entries.push(AddressPrefixORFEntry::parse(&mut cursor, afi)?);
+                }
+                Ok(ORF::AddressPrefix(entries))
+            }
+            _ => Ok(ORF::Unknown { orf_type, value }),
+        }
+    }
+
+    fn encode_entries(&self, buf: &mut impl Write) -> Result<(), Error> {
+        match self {
+            ORF::AddressPrefix(entries) => {
+                for entry in entries {
+                    entry.encode(buf)?;
+                }
+                Ok(())
+            }
+            ORF::Unknown { value, .. } => buf.write_all(value),
+        }
+    }
+
+    /// The ORF type code
+    pub fn orf_type(&self) -> u8 {
+        match self {
+            ORF::AddressPrefix(_) => 64,
+            ORF::Unknown { orf_type, .. } => *orf_type,
+        }
+    }
+}
+
+/// The action to take for an ORF entry.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum ORFAction {
+    /// Add the entry
+    Add = 0,
+    /// Remove the entry
+    Remove = 1,
+    /// Remove all previously received entries
+    RemoveAll = 2,
+}
+
+/// Whether routes matching an ORF entry are permitted or denied.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum ORFMatch {
+    /// Permit matching routes
+    Permit = 0,
+    /// Deny matching routes
+    Deny = 1,
+}
+
+/// An Address Prefix ORF entry. Defined in [RFC5292](http://www.iana.org/go/rfc5292).
+///
+/// ```
+/// use bgp_rs::{AddressPrefixORFEntry, ORFAction, ORFMatch};
+///
+/// let entry = AddressPrefixORFEntry::Entry {
+///     action: ORFAction::Add,
+///     match_type: ORFMatch::Permit,
+///     sequence: 10,
+///     min_length: 24,
+///     max_length: 32,
+///     prefix: ("10.0.0.0".parse().unwrap(), 8).into(),
+/// };
+/// let mut bytes = vec![];
+/// entry.encode(&mut bytes).unwrap();
+/// assert_eq!(bytes, vec![0, 0, 0, 0, 10, 24, 32, 8, 10]);
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddressPrefixORFEntry {
+    /// Add or remove a single entry
+    Entry {
+        /// Add or Remove (Remove-All is represented by `RemoveAll`)
+        action: ORFAction,
+        /// Permit or Deny
+        match_type: ORFMatch,
+        /// Relative ordering of the entry
+        sequence: u32,
+        /// Minimum length of matching prefixes
+        min_length: u8,
+        /// Maximum length of matching prefixes
+        max_length: u8,
+        /// The prefix to match
+        prefix: Prefix,
+    },
+    /// Remove all previously received entries
+    RemoveAll,
+}
+
+impl AddressPrefixORFEntry {
+    fn parse(stream: &mut impl Read, afi: AFI) -> Result<AddressPrefixORFEntry, Error> {
+        let common = stream.read_u8()?;
+        let action = match common >> 6 {
+            0 => ORFAction::Add,
+            1 => ORFAction::Remove,
+            2 => return Ok(AddressPrefixORFEntry::RemoveAll),
+            x => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Number {} does not represent a valid ORF action.", x),
+                ));
+            }
+        };
+        let match_type = if common & 0x20 == 0x20 {
+            ORFMatch::Deny
+        } else {
+            ORFMatch::Permit
+        };
+        let sequence = stream.read_u32::<BigEndian>()?;
+        let min_length = stream.read_u8()?;
+        let max_length = stream.read_u8()?;
+        let prefix = Prefix::parse(stream, afi)?;
+        Ok(AddressPrefixORFEntry::Entry {
+            action,
+            match_type,
+            sequence,
+            min_length,
+            max_length,
+            prefix,
+        })
+    }
+
+    /// Encode ORF entry to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        match self {
+            AddressPrefixORFEntry::Entry {
+                action,
+                match_type,
+                sequence,
+                min_length,
+                max_length,
+                prefix,
+            } => {
+                if *action == ORFAction::RemoveAll {
+                    return buf.write_u8((ORFAction::RemoveAll as u8) << 6);
+                }
+                buf.write_u8((*action as u8) << 6 | (*match_type as u8) << 5)?;
+                buf.write_u32::<BigEndian>(*sequence)?;
+                buf.write_u8(*min_length)?;
+                buf.write_u8(*max_length)?;
+                buf.write_u8(prefix.length)?;
+                buf.write_all(prefix.masked_octets())
+            }
+            AddressPrefixORFEntry::RemoveAll => buf.write_u8((ORFAction::RemoveAll as u8) << 6),
+        }
+    }
+}
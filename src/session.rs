@@ -0,0 +1,246 @@
+//! The `session` mod provides a minimal BGP speaker on top of any `Read + Write` stream
+//! - OPEN / KEEPALIVE handshake and validation of the peer OPEN
+//! - Capability negotiation
+//! - Hold and Keepalive timers
+//!
+//! Reads are blocking, so the timers are only checked when the caller invokes `tick`.
+//! Use a stream with a read timeout (e.g. `TcpStream::set_read_timeout`) to do so periodically.
+//!
+
+use std::io::{Error, ErrorKind, Read, Write};
+use std::time::Duration;
+
+use crate::*;
+
+/// An established BGP session with a peer.
+pub struct BgpSession<S>
+where
+    S: Read + Write,
+{
+    reader: Reader<S, NegotiatedCapabilities>,
+    config: PeerConfig,
+    peer_open: Open,
+    hold_timer: HoldTimer,
+    keepalive_timer: KeepaliveTimer,
+    metrics: Metrics,
+}
+
+impl<S> BgpSession<S>
+where
+    S: Read + Write,
+{
+    /// Performs the OPEN / KEEPALIVE handshake over the given stream.
+    ///
+    /// When the peer OPEN is not acceptable, the corresponding Notification is sent
+    /// to the peer and the error is returned.
+    pub fn connect(stream: S, config: PeerConfig) -> Result<BgpSession<S>, Error> {
+        let local_open = Open::from_config(&config);
+        let mut session = BgpSession {
+            reader: Reader::with_capabilities(stream, NegotiatedCapabilities::default()),
+            config,
+            peer_open: local_open.clone(),
+            // Disabled until the Hold Time is negotiated
+            hold_timer: HoldTimer::new(0),
+            keepalive_timer: KeepaliveTimer::new(0),
+            metrics: Metrics::new(),
+        };
+        session.send(&Message::Open(local_open.clone()))?;
+        session.metrics.session_state = SessionState::OpenSent;
+
+        let peer_open = match session.recv_handshake()? {
+            Message::Open(open) => open,
+            message => {
+                return Err(session.abort(unexpected_message(
+                    FiniteStateMachineSubcode::UnexpectedMessageInOpenSent,
+                    &message,
+                )));
+            }
+        };
+        let negotiated = validate_open(&session.config, &peer_open)
+            .and_then(|_| NegotiatedCapabilities::from_opens(&local_open, &peer_open))
+            .map_err(|e| session.abort(e))?;
+        let hold_time = negotiated.hold_time.unwrap_or_default();
+        session.hold_timer = HoldTimer::new(hold_time);
+        session.keepalive_timer = KeepaliveTimer::new(hold_time);
+        session.reader.capabilities = negotiated;
+        session.peer_open = peer_open;
+        session.metrics.session_state = SessionState::OpenConfirm;
+
+        session.send(&Message::KeepAlive)?;
+        match session.recv_handshake()? {
+            Message::KeepAlive => {
+                session.metrics.session_state = SessionState::Established;
+                Ok(session)
+            }
+            message => Err(session.abort(unexpected_message(
+                FiniteStateMachineSubcode::UnexpectedMessageInOpenConfirm,
+                &message,
+            ))),
+        }
+    }
+
+    /// The OPEN message received from the peer.
+    pub fn peer_open(&self) -> &Open {
+        &self.peer_open
+    }
+
+    /// The session parameters negotiated with the peer.
+    pub fn capabilities(&self) -> &NegotiatedCapabilities {
+        &self.reader.capabilities
+    }
+
+    /// The statistics of the messages received from the peer & the state of the session.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Sends a message to the peer, encoded following the negotiated capabilities.
+    pub fn send(&mut self, message: &Message) -> Result<(), Error> {
+        let mut buf: Vec<u8> = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+        message.encode_with_capabilities(&mut buf, &self.reader.capabilities.capabilities)?;
+        self.reader.stream.write_all(&buf)?;
+        self.reader.stream.flush()?;
+        self.keepalive_timer.reset();
+        Ok(())
+    }
+
+    /// Sends an UPDATE message to the peer.
+    pub fn send_update(&mut self, update: &Update) -> Result<(), Error> {
+        self.send(&Message::Update(update.clone()))
+    }
+
+    /// Receives the next message from the peer.
+    ///
+    /// When the message can not be parsed, the corresponding Notification
+    /// is sent to the peer before the error is returned.
+    pub fn recv(&mut self) -> Result<Message, Error> {
+        let result = self.reader.read();
+        self.metrics.record(&result);
+        match result {
+            Ok((_header, message)) => {
+                self.hold_timer.reset();
+                Ok(message)
+            }
+            Err(e) => {
+                if notification_of(&e).is_some() {
+                    Err(self.abort(e))
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    /// Ends the session by sending a Cease Notification.
+    pub fn close(mut self, subcode: CeaseSubcode) -> Result<(), Error> {
+        self.send(&Message::Notification(Notification::cease(subcode)))
+    }
+
+    /// The negotiated Hold Time, zero when the Hold Timer is disabled.
+    pub fn hold_time(&self) -> Duration {
+        self.hold_timer.hold_time()
+    }
+
+    /// Checks if no message was received from the peer within the Hold Time.
+    pub fn hold_timer_expired(&self) -> bool {
+        self.hold_timer.is_expired()
+    }
+
+    /// Checks if a KEEPALIVE should be sent to the peer.
+    pub fn keepalive_due(&self) -> bool {
+        self.keepalive_timer.is_due()
+    }
+
+    /// Runs the timers: sends a KEEPALIVE when one is due, and when the Hold Timer
+    /// expired sends the Hold Timer Expired Notification and returns an error.
+    pub fn tick(&mut self) -> Result<(), Error> {
+        if self.hold_timer_expired() {
+            let error = self.hold_timer.expired_error();
+            return Err(self.abort(error));
+        }
+        if self.keepalive_due() {
+            self.send(&Message::KeepAlive)?;
+        }
+        Ok(())
+    }
+
+    // Receive a message while establishing the session, a Notification from the peer ends it
+    fn recv_handshake(&mut self) -> Result<Message, Error> {
+        match self.recv()? {
+            Message::Notification(notification) => Err(Error::new(
+                ErrorKind::Other,
+                format!("Peer sent Notification: {}", notification),
+            )),
+            message => Ok(message),
+        }
+    }
+
+    // Send the Notification for this error to the peer, failures to do so are ignored
+    fn abort(&mut self, error: Error) -> Error {
+        let notification = error.to_notification();
+        let _ = self.send(&Message::Notification(notification));
+        self.metrics.session_state = SessionState::Idle;
+        error
+    }
+}
+
+#[cfg(all(feature = "sockopt", target_os = "linux"))]
+impl BgpSession<std::net::TcpStream> {
+    /// Connects to a peer with the given socket options (such as a TCP MD5 Signature key) and
+    /// establishes the session, see `connect`.
+    pub fn connect_with_options(
+        addr: std::net::SocketAddr,
+        options: &SocketOptions,
+        config: PeerConfig,
+    ) -> Result<Self, Error> {
+        let stream = options.connect(addr)?;
+        BgpSession::connect(stream, config)
+    }
+}
+
+pub(crate) fn unexpected_message(subcode: FiniteStateMachineSubcode, message: &Message) -> Error {
+    notification_error(
+        NotificationCode::FiniteStateMachine(subcode),
+        vec![],
+        format!("Unexpected message: {:?}", message),
+    )
+}
+
+// Checks the peer OPEN against the local configuration
+pub(crate) fn validate_open(config: &PeerConfig, open: &Open) -> Result<(), Error> {
+    if open.version != 4 {
+        return Err(notification_error(
+            NotificationCode::OpenMessage(OpenMessageSubcode::UnsupportedVersionNumber),
+            4u16.to_be_bytes().to_vec(),
+            format!("Unsupported BGP version {}", open.version),
+        ));
+    }
+    if let Some(asn) = config.peer_asn {
+        if open.asn() != asn {
+            let notification = Notification::bad_peer_as(open.asn());
+            return Err(notification_error(
+                notification.code,
+                notification.data,
+                format!("Expected peer AS {}, got {}", asn, open.asn()),
+            ));
+        }
+    }
+    if open.identifier == 0 || open.identifier == u32::from(config.router_id) {
+        return Err(notification_error(
+            NotificationCode::OpenMessage(OpenMessageSubcode::BadBGPIdentifier),
+            vec![],
+            format!("Bad BGP Identifier {}", open.identifier),
+        ));
+    }
+    let peer_role = Capabilities::from_parameters(open.parameters.clone()).BGP_ROLE;
+    if let (Some(role), Some(peer_role)) = (config.role, peer_role) {
+        if !role.is_compatible_with(peer_role) {
+            return Err(notification_error(
+                NotificationCode::OpenMessage(OpenMessageSubcode::RoleMismatch),
+                vec![],
+                format!("Role {:?} does not match peer role {:?}", role, peer_role),
+            ));
+        }
+    }
+    Ok(())
+}
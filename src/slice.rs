@@ -0,0 +1,72 @@
+//! Decoding & encoding of messages from & to byte slices, for targets without sockets or files
+//! such as `wasm32-unknown-unknown` (e.g. in-browser message inspectors).
+
+use std::io::{Cursor, Error, ErrorKind};
+
+use crate::*;
+
+/// Decodes a message from a slice holding exactly one message, including its header.
+///
+/// Errors are returned as a `NotificationError`, which unlike the `std::io::Error` of the other
+/// APIs can be cloned, compared and converted (e.g. into a JavaScript value) without downcasting.
+///
+/// ```
+/// use bgp_rs::{decode_message, encode_message, Capabilities, Message};
+///
+/// let capabilities = Capabilities::default();
+/// let bytes = encode_message(&Message::KeepAlive, Some(&capabilities)).unwrap();
+/// assert_eq!(decode_message(&bytes, &capabilities).unwrap(), Message::KeepAlive);
+///
+/// let error = decode_message(&bytes[..18], &capabilities).unwrap_err();
+/// assert_eq!(error.message, "failed to fill whole buffer");
+/// ```
+pub fn decode_message(
+    data: &[u8],
+    capabilities: &Capabilities,
+) -> Result<Message, NotificationError> {
+    let (_header, message) = parse_framed(data, capabilities, &ParseOptions::default())?;
+    Ok(message)
+}
+
+/// Decodes the consecutive messages of a slice, such as a captured stream of a session.
+pub fn decode_messages(
+    data: &[u8],
+    capabilities: &Capabilities,
+) -> Result<Vec<Message>, NotificationError> {
+    let mut reader = Reader::with_capabilities(Cursor::new(data), capabilities);
+    let mut messages = vec![];
+    while reader.stream.position() < data.len() as u64 {
+        let (_header, message) = reader.read()?;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+/// Encodes a message, including its header, like `Message::encode_into`.
+pub fn encode_message(
+    message: &Message,
+    capabilities: Option<&Capabilities>,
+) -> Result<Vec<u8>, NotificationError> {
+    let mut buf = Vec::with_capacity(BGP_MIN_MESSAGE_SIZE);
+    message.encode_into(&mut buf, capabilities)?;
+    Ok(buf)
+}
+
+// Parses a chunk holding exactly one message
+pub(crate) fn parse_framed(
+    data: &[u8],
+    capabilities: &Capabilities,
+    options: &ParseOptions,
+) -> Result<(Header, Message), Error> {
+    let mut reader = Reader::with_capabilities(Cursor::new(data), capabilities);
+    reader.options = options.clone();
+    let (header, message) = reader.read()?;
+    let remaining = data.len() as u64 - reader.stream.position();
+    if remaining > 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Chunk holds {} bytes after the message", remaining),
+        ));
+    }
+    Ok((header, message))
+}
@@ -0,0 +1,190 @@
+//! The `sockopt` mod provides the TCP socket options most BGP deployments require (Linux only)
+//! - TCP MD5 Signatures of the segments exchanged with a peer (RFC2385)
+//! - The Generalized TTL Security Mechanism (GTSM, RFC5082)
+//! - Blocking TCP connections with these options set before the SYN is sent
+//!
+//! The options apply to any socket (`std` or Tokio) through `AsRawFd`. The address family of
+//! the socket must match the one of the peer address.
+//!
+
+use std::io::Error;
+use std::mem;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+// The longest TCP MD5 key supported by Linux
+const TCP_MD5SIG_MAXKEYLEN: usize = 80;
+
+// struct tcp_md5sig of linux/tcp.h
+#[repr(C)]
+struct TcpMd5Sig {
+    tcpm_addr: libc::sockaddr_storage,
+    tcpm_flags: u8,
+    tcpm_prefixlen: u8,
+    tcpm_keylen: u16,
+    tcpm_ifindex: u32,
+    tcpm_key: [u8; TCP_MD5SIG_MAXKEYLEN],
+}
+
+/// The socket options of the TCP connections with a peer.
+///
+/// ```no_run
+/// use bgp_rs::SocketOptions;
+///
+/// let mut options = SocketOptions::default();
+/// options.md5_key = Some(b"secret".to_vec());
+/// // The peer is directly connected
+/// options.gtsm_hops = Some(1);
+/// let stream = options.connect("192.0.2.1:179".parse().unwrap()).unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SocketOptions {
+    /// The TCP MD5 Signature key shared with the peer, up to 80 bytes.
+    pub md5_key: Option<Vec<u8>>,
+
+    /// The number of hops to the peer (1 when directly connected) for GTSM: packets are sent
+    /// with a TTL of 255, and only packets received with a TTL of at least 256 - hops are
+    /// accepted.
+    pub gtsm_hops: Option<u8>,
+}
+
+impl SocketOptions {
+    /// Sets the options on a socket for the given peer. On a listening socket, they apply to
+    /// the connections accepted from the peer.
+    pub fn apply(&self, socket: &impl AsRawFd, peer: IpAddr) -> Result<(), Error> {
+        if let Some(key) = &self.md5_key {
+            set_md5_key(socket, peer, key)?;
+        }
+        if let Some(hops) = self.gtsm_hops {
+            set_gtsm(socket, peer, hops)?;
+        }
+        Ok(())
+    }
+
+    /// Opens a TCP connection to a peer with the options set, blocking until it is established.
+    pub fn connect(&self, addr: SocketAddr) -> Result<TcpStream, Error> {
+        let domain = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6,
+        };
+        let fd = unsafe { libc::socket(domain, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(Error::last_os_error());
+        }
+        // Closes the socket on errors
+        let stream = unsafe { TcpStream::from_raw_fd(fd) };
+        self.apply(&stream, addr.ip())?;
+
+        let (storage, length) = socket_addr(addr);
+        let result = unsafe {
+            libc::connect(
+                fd,
+                &storage as *const libc::sockaddr_storage as *const libc::sockaddr,
+                length,
+            )
+        };
+        if result < 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(stream)
+    }
+}
+
+/// Sets the TCP MD5 Signature key (RFC2385) of the segments exchanged with a peer. An empty key
+/// removes it.
+pub fn set_md5_key(socket: &impl AsRawFd, peer: IpAddr, key: &[u8]) -> Result<(), Error> {
+    if key.len() > TCP_MD5SIG_MAXKEYLEN {
+        return Err(Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("TCP MD5 key longer than {} bytes", TCP_MD5SIG_MAXKEYLEN),
+        ));
+    }
+    let (tcpm_addr, _) = socket_addr(SocketAddr::new(peer, 0));
+    let mut md5sig = TcpMd5Sig {
+        tcpm_addr,
+        tcpm_flags: 0,
+        tcpm_prefixlen: 0,
+        tcpm_keylen: key.len() as u16,
+        tcpm_ifindex: 0,
+        tcpm_key: [0; TCP_MD5SIG_MAXKEYLEN],
+    };
+    md5sig.tcpm_key[..key.len()].copy_from_slice(key);
+    set_option(
+        socket.as_raw_fd(),
+        libc::IPPROTO_TCP,
+        libc::TCP_MD5SIG,
+        &md5sig,
+    )
+}
+
+/// Enables the Generalized TTL Security Mechanism (RFC5082) for a peer the given number of hops
+/// away: sends packets with a TTL (or Hop Limit) of 255, and drops packets received with a TTL
+/// below 256 - hops.
+pub fn set_gtsm(socket: &impl AsRawFd, peer: IpAddr, hops: u8) -> Result<(), Error> {
+    let fd = socket.as_raw_fd();
+    let min_ttl: i32 = 256 - i32::from(hops.max(1));
+    match peer {
+        IpAddr::V4(_) => {
+            set_option(fd, libc::IPPROTO_IP, libc::IP_TTL, &255)?;
+            set_option(fd, libc::IPPROTO_IP, libc::IP_MINTTL, &min_ttl)
+        }
+        IpAddr::V6(_) => {
+            set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, &255)?;
+            set_option(fd, libc::IPPROTO_IPV6, libc::IPV6_MINHOPCOUNT, &min_ttl)
+        }
+    }
+}
+
+fn set_option<T>(fd: RawFd, level: i32, name: i32, value: &T) -> Result<(), Error> {
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn socket_addr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let length = match addr {
+        SocketAddr::V4(addr) => {
+            let sockaddr = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from(*addr.ip()).to_be(),
+                },
+                sin_zero: [0; 8],
+            };
+            unsafe {
+                (&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in)
+                    .write(sockaddr)
+            };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let sockaddr = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            unsafe {
+                (&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6)
+                    .write(sockaddr)
+            };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, length as libc::socklen_t)
+}
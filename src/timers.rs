@@ -0,0 +1,168 @@
+//! The `timers` mod provides the Hold and Keepalive timers of a BGP session (RFC4271)
+//! - Negotiation of the Hold Time from the proposals of both speakers
+//! - Keepalive interval of one third of the Hold Time
+//! - Expiry detection and the Hold Timer Expired Notification
+//!
+//! The timers do not run on their own: they are checked when the caller asks for them.
+//!
+
+use std::io::Error;
+use std::time::{Duration, Instant};
+
+use crate::*;
+
+/// Negotiates the Hold Time of a session: the smaller of both proposals. A proposal of one or two
+/// seconds is unacceptable (RFC4271) and results in an error carrying the Notification to send.
+///
+/// ```
+/// use bgp_rs::negotiate_hold_time;
+///
+/// assert_eq!(negotiate_hold_time(90, 30).unwrap(), 30);
+/// assert_eq!(negotiate_hold_time(90, 0).unwrap(), 0);
+/// assert!(negotiate_hold_time(90, 2).is_err());
+/// ```
+pub fn negotiate_hold_time(local: u16, remote: u16) -> Result<u16, Error> {
+    if remote == 1 || remote == 2 {
+        return Err(notification_error(
+            NotificationCode::OpenMessage(OpenMessageSubcode::UnacceptableHoldTime),
+            vec![],
+            format!("Unacceptable Hold Time: {}", remote),
+        ));
+    }
+    Ok(local.min(remote))
+}
+
+/// The interval between KEEPALIVE messages for a Hold Time, one third of it.
+/// None when KEEPALIVEs should not be sent (a Hold Time of zero).
+pub fn keepalive_interval(hold_time: u16) -> Option<u16> {
+    if hold_time > 0 {
+        Some(hold_time / 3)
+    } else {
+        None
+    }
+}
+
+/// Expires when no message was received from the peer within the Hold Time.
+/// A Hold Time of zero disables it.
+///
+/// ```
+/// use bgp_rs::{HoldTimer, NotificationCode};
+/// use std::time::Duration;
+///
+/// let mut timer = HoldTimer::negotiate(90, 30).unwrap();
+/// assert_eq!(timer.hold_time(), Duration::from_secs(30));
+/// assert!(!timer.is_expired());
+/// // Restarted whenever a message is received
+/// timer.reset();
+///
+/// let keepalive = timer.keepalive_timer();
+/// assert_eq!(keepalive.interval(), Some(Duration::from_secs(10)));
+/// let notification = HoldTimer::expired_notification();
+/// assert_eq!(notification.major_err_code(), NotificationCode::HoldTimerExpired.major());
+/// ```
+#[derive(Clone, Debug)]
+pub struct HoldTimer {
+    hold_time: u16,
+    last_received: Instant,
+}
+
+impl HoldTimer {
+    /// Create a timer with a Hold Time in seconds, started now.
+    pub fn new(hold_time: u16) -> HoldTimer {
+        HoldTimer {
+            hold_time,
+            last_received: Instant::now(),
+        }
+    }
+
+    /// Create a timer with the Hold Time negotiated from both proposals, see `negotiate_hold_time`.
+    pub fn negotiate(local: u16, remote: u16) -> Result<HoldTimer, Error> {
+        negotiate_hold_time(local, remote).map(HoldTimer::new)
+    }
+
+    /// The Hold Time, zero when the timer is disabled.
+    pub fn hold_time(&self) -> Duration {
+        Duration::from_secs(u64::from(self.hold_time))
+    }
+
+    /// Restarts the timer, when a message was received from the peer.
+    pub fn reset(&mut self) {
+        self.last_received = Instant::now();
+    }
+
+    /// When the timer expires, None when it is disabled.
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.hold_time > 0 {
+            Some(self.last_received + self.hold_time())
+        } else {
+            None
+        }
+    }
+
+    /// Checks if no message was received from the peer within the Hold Time.
+    pub fn is_expired(&self) -> bool {
+        self.hold_time > 0 && self.last_received.elapsed() > self.hold_time()
+    }
+
+    /// The Keepalive timer for this Hold Time, started now.
+    pub fn keepalive_timer(&self) -> KeepaliveTimer {
+        KeepaliveTimer::new(self.hold_time)
+    }
+
+    /// The Hold Timer Expired Notification to send to the peer.
+    pub fn expired_notification() -> Notification {
+        Notification::from_code(NotificationCode::HoldTimerExpired, vec![])
+    }
+
+    /// The error ending the session when the timer expired, carrying the Hold Timer Expired
+    /// Notification (see `ToNotification`).
+    pub fn expired_error(&self) -> Error {
+        notification_error(
+            NotificationCode::HoldTimerExpired,
+            vec![],
+            format!("No message received within {:?}", self.hold_time()),
+        )
+    }
+}
+
+/// Tells when a KEEPALIVE should be sent to the peer: one third of the Hold Time after the last
+/// message sent. Never with a Hold Time of zero.
+#[derive(Clone, Debug)]
+pub struct KeepaliveTimer {
+    interval: Option<u16>,
+    last_sent: Instant,
+}
+
+impl KeepaliveTimer {
+    /// Create a timer for a Hold Time in seconds, started now.
+    pub fn new(hold_time: u16) -> KeepaliveTimer {
+        KeepaliveTimer {
+            interval: keepalive_interval(hold_time),
+            last_sent: Instant::now(),
+        }
+    }
+
+    /// The interval between KEEPALIVE messages, None when they should not be sent.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+            .map(|interval| Duration::from_secs(u64::from(interval)))
+    }
+
+    /// Restarts the timer, when a message was sent to the peer.
+    pub fn reset(&mut self) {
+        self.last_sent = Instant::now();
+    }
+
+    /// When the next KEEPALIVE is due, None when they should not be sent.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.interval().map(|interval| self.last_sent + interval)
+    }
+
+    /// Checks if a KEEPALIVE should be sent to the peer.
+    pub fn is_due(&self) -> bool {
+        match self.interval() {
+            Some(interval) => self.last_sent.elapsed() >= interval,
+            None => false,
+        }
+    }
+}
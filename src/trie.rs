@@ -0,0 +1,362 @@
+//! The `trie` mod provides a radix trie of IPv4 and IPv6 prefixes
+//! - Insertion, removal and exact lookup of prefixes
+//! - Longest-prefix-match lookups
+//! - Iteration over all prefixes, or over the prefixes covered by a prefix
+//!
+//! Prefixes are compared on the bits covered by their length only, so host bits are ignored.
+//!
+
+use crate::*;
+
+/// A path-compressed binary trie mapping IPv4 and IPv6 prefixes to values.
+///
+/// ```
+/// use bgp_rs::{Prefix, PrefixTrie};
+///
+/// let mut trie = PrefixTrie::new();
+/// trie.insert(&"10.0.0.0/8".parse().unwrap(), "eight");
+/// trie.insert(&"10.1.0.0/16".parse().unwrap(), "sixteen");
+///
+/// let address: Prefix = "10.1.2.3/32".parse().unwrap();
+/// let (prefix, value) = trie.longest_match(&address).unwrap();
+/// assert_eq!(prefix.to_string(), "10.1.0.0/16");
+/// assert_eq!(*value, "sixteen");
+/// assert!(trie.longest_match(&"11.0.0.0/8".parse().unwrap()).is_none());
+/// ```
+#[derive(Clone, Debug)]
+pub struct PrefixTrie<T> {
+    ipv4: Option<Box<Node<T>>>,
+    ipv6: Option<Box<Node<T>>>,
+    len: usize,
+}
+
+// The bits of a node are left-aligned in a u128, IPv4 addresses using the 32 highest bits
+#[derive(Clone, Debug)]
+struct Node<T> {
+    bits: u128,
+    length: u8,
+    entry: Option<(Prefix, T)>,
+    children: [Option<Box<Node<T>>>; 2],
+}
+
+impl<T> Node<T> {
+    fn new(bits: u128, length: u8, entry: Option<(Prefix, T)>) -> Box<Node<T>> {
+        Box::new(Node {
+            bits,
+            length,
+            entry,
+            children: [None, None],
+        })
+    }
+}
+
+// The bits of the prefix, masked to its length (capped to the length of addresses)
+fn prefix_bits(prefix: &Prefix) -> (u128, u8) {
+    let max_length = match prefix.protocol {
+        AFI::IPV4 => 32,
+        _ => 128,
+    };
+    let mut octets = [0; 16];
+    for (octet, value) in octets.iter_mut().zip(&prefix.prefix) {
+        *octet = *value;
+    }
+    let length = prefix.length.min(max_length);
+    (mask(u128::from_be_bytes(octets), length), length)
+}
+
+fn mask(bits: u128, length: u8) -> u128 {
+    if length == 0 {
+        0
+    } else {
+        bits & (!0u128 << (128 - u32::from(length)))
+    }
+}
+
+// The bit following the first `index` bits
+fn bit(bits: u128, index: u8) -> usize {
+    ((bits >> (127 - u32::from(index))) & 1) as usize
+}
+
+fn common_length(bits: u128, length: u8, other_bits: u128, other_length: u8) -> u8 {
+    let differing = (bits ^ other_bits).leading_zeros() as u8;
+    differing.min(length).min(other_length)
+}
+
+fn insert<T>(
+    slot: &mut Option<Box<Node<T>>>,
+    bits: u128,
+    length: u8,
+    entry: (Prefix, T),
+) -> Option<T> {
+    let (common, node_length) = match slot {
+        Some(node) => (
+            common_length(node.bits, node.length, bits, length),
+            node.length,
+        ),
+        None => {
+            *slot = Some(Node::new(bits, length, Some(entry)));
+            return None;
+        }
+    };
+    if common == node_length {
+        let node = slot.as_mut().unwrap();
+        if length == node_length {
+            return node.entry.replace(entry).map(|(_, value)| value);
+        }
+        return insert(
+            &mut node.children[bit(bits, node_length)],
+            bits,
+            length,
+            entry,
+        );
+    }
+
+    // The node is not covered by the prefix, insert a node for their common bits
+    let node = slot.take().unwrap();
+    let mut parent = Node::new(mask(bits, common), common, None);
+    let node_bit = bit(node.bits, common);
+    parent.children[node_bit] = Some(node);
+    if common == length {
+        parent.entry = Some(entry);
+    } else {
+        parent.children[1 - node_bit] = Some(Node::new(bits, length, Some(entry)));
+    }
+    *slot = Some(parent);
+    None
+}
+
+fn remove<T>(slot: &mut Option<Box<Node<T>>>, bits: u128, length: u8) -> Option<T> {
+    let node = slot.as_mut()?;
+    if common_length(node.bits, node.length, bits, length) < node.length {
+        return None;
+    }
+    let removed = if node.length == length {
+        node.entry.take().map(|(_, value)| value)
+    } else {
+        let child = bit(bits, node.length);
+        remove(&mut node.children[child], bits, length)
+    };
+    if removed.is_some() {
+        compact(slot);
+    }
+    removed
+}
+
+// Removes a node without entry that no longer branches
+fn compact<T>(slot: &mut Option<Box<Node<T>>>) {
+    let node = match slot {
+        Some(node) if node.entry.is_none() => node,
+        _ => return,
+    };
+    match (node.children[0].take(), node.children[1].take()) {
+        (Some(child), None) | (None, Some(child)) => *slot = Some(child),
+        (None, None) => *slot = None,
+        children => node.children = [children.0, children.1],
+    }
+}
+
+impl<T> Default for PrefixTrie<T> {
+    fn default() -> Self {
+        PrefixTrie {
+            ipv4: None,
+            ipv6: None,
+            len: 0,
+        }
+    }
+}
+
+impl<T> PrefixTrie<T> {
+    /// Creates an empty trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn root(&self, afi: AFI) -> Option<&Node<T>> {
+        match afi {
+            AFI::IPV4 => self.ipv4.as_ref().map(|node| &**node),
+            AFI::IPV6 => self.ipv6.as_ref().map(|node| &**node),
+            _ => None,
+        }
+    }
+
+    fn root_mut(&mut self, afi: AFI) -> Option<&mut Option<Box<Node<T>>>> {
+        match afi {
+            AFI::IPV4 => Some(&mut self.ipv4),
+            AFI::IPV6 => Some(&mut self.ipv6),
+            _ => None,
+        }
+    }
+
+    // The node of the prefix, if any
+    fn find(&self, prefix: &Prefix) -> Option<&Node<T>> {
+        let (bits, length) = prefix_bits(prefix);
+        let mut node = self.root(prefix.protocol)?;
+        loop {
+            if common_length(node.bits, node.length, bits, length) < node.length {
+                return None;
+            }
+            if node.length == length {
+                return Some(node);
+            }
+            node = node.children[bit(bits, node.length)].as_ref()?;
+        }
+    }
+
+    /// Inserts a value for a prefix, returning the value it replaced.
+    /// Prefixes that are not IPv4 or IPv6 are not stored.
+    pub fn insert(&mut self, prefix: &Prefix, value: T) -> Option<T> {
+        let (bits, length) = prefix_bits(prefix);
+        let entry = (prefix.clone(), value);
+        let replaced = insert(self.root_mut(prefix.protocol)?, bits, length, entry);
+        if replaced.is_none() {
+            self.len += 1;
+        }
+        replaced
+    }
+
+    /// Removes a prefix, returning its value.
+    pub fn remove(&mut self, prefix: &Prefix) -> Option<T> {
+        let (bits, length) = prefix_bits(prefix);
+        let removed = remove(self.root_mut(prefix.protocol)?, bits, length);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    /// The value of a prefix.
+    pub fn get(&self, prefix: &Prefix) -> Option<&T> {
+        self.find(prefix)
+            .and_then(|node| node.entry.as_ref())
+            .map(|(_, value)| value)
+    }
+
+    /// The value of a prefix, mutably.
+    pub fn get_mut(&mut self, prefix: &Prefix) -> Option<&mut T> {
+        let (bits, length) = prefix_bits(prefix);
+        let mut node = self.root_mut(prefix.protocol)?.as_mut()?;
+        loop {
+            if common_length(node.bits, node.length, bits, length) < node.length {
+                return None;
+            }
+            if node.length == length {
+                return node.entry.as_mut().map(|(_, value)| value);
+            }
+            node = node.children[bit(bits, node.length)].as_mut()?;
+        }
+    }
+
+    /// The most specific prefix covering the given prefix (or address, as a host prefix),
+    /// along with its value.
+    pub fn longest_match(&self, prefix: &Prefix) -> Option<(&Prefix, &T)> {
+        let (bits, length) = prefix_bits(prefix);
+        let mut matched = None;
+        let mut next = self.root(prefix.protocol);
+        while let Some(node) = next {
+            if node.length > length
+                || common_length(node.bits, node.length, bits, length) < node.length
+            {
+                break;
+            }
+            if let Some((prefix, value)) = &node.entry {
+                matched = Some((prefix, value));
+            }
+            if node.length == length {
+                break;
+            }
+            next = node.children[bit(bits, node.length)]
+                .as_ref()
+                .map(|node| &**node);
+        }
+        matched
+    }
+
+    /// The prefixes covered by the given prefix (including itself) with their values,
+    /// ordered like `Prefix`.
+    pub fn subtree(&self, prefix: &Prefix) -> PrefixTrieIter<'_, T> {
+        let (bits, length) = prefix_bits(prefix);
+        let mut next = self.root(prefix.protocol);
+        while let Some(node) = next {
+            if node.length >= length {
+                // Only the bits of the given prefix need to match
+                if common_length(node.bits, node.length, bits, length) < length {
+                    next = None;
+                }
+                break;
+            }
+            if common_length(node.bits, node.length, bits, length) < node.length {
+                next = None;
+                break;
+            }
+            next = node.children[bit(bits, node.length)]
+                .as_ref()
+                .map(|node| &**node);
+        }
+        PrefixTrieIter {
+            stack: next.into_iter().collect(),
+        }
+    }
+
+    /// All prefixes with their values, IPv4 prefixes first, ordered like `Prefix`.
+    pub fn iter(&self) -> PrefixTrieIter<'_, T> {
+        PrefixTrieIter {
+            stack: self
+                .root(AFI::IPV6)
+                .into_iter()
+                .chain(self.root(AFI::IPV4))
+                .collect(),
+        }
+    }
+
+    /// The number of prefixes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the trie holds no prefixes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a, T> IntoIterator for &'a PrefixTrie<T> {
+    type Item = (&'a Prefix, &'a T);
+    type IntoIter = PrefixTrieIter<'a, T>;
+
+    fn into_iter(self) -> PrefixTrieIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> std::iter::FromIterator<(Prefix, T)> for PrefixTrie<T> {
+    fn from_iter<I: IntoIterator<Item = (Prefix, T)>>(iter: I) -> Self {
+        let mut trie = PrefixTrie::new();
+        for (prefix, value) in iter {
+            trie.insert(&prefix, value);
+        }
+        trie
+    }
+}
+
+/// An iterator over the prefixes of a `PrefixTrie`, see `PrefixTrie::iter`.
+#[derive(Clone, Debug)]
+pub struct PrefixTrieIter<'a, T> {
+    stack: Vec<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for PrefixTrieIter<'a, T> {
+    type Item = (&'a Prefix, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            // Visit the node before its children, lower addresses first
+            for child in node.children.iter().rev().flatten() {
+                self.stack.push(child);
+            }
+            if let Some((prefix, value)) = &node.entry {
+                return Some((prefix, value));
+            }
+        }
+        None
+    }
+}
@@ -2,9 +2,13 @@ use crate::Capabilities;
 
 use byteorder::{BigEndian, ReadBytesExt};
 
-use std::fmt::{Display, Formatter};
+use std::any::Any;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
 use std::io::{Cursor, Error, ErrorKind, Read};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
 
 use crate::*;
 
@@ -44,11 +48,93 @@ pub enum Identifier {
     LARGE_COMMUNITY = 32,
     BGPSEC_PATH = 33,
     BGP_PREFIX_SID = 34,
+    OTC = 35,
     ATTR_SET = 128,
+    /// Attributes decoded by a decoder registered in `AttributeDecoders`, their type code is
+    /// given by `PathAttribute::code` (0 is a reserved type code).
+    CUSTOM = 0,
+}
+
+/// Convert u8 to Identifier
+/// ```
+/// use std::convert::TryFrom;
+/// use bgp_rs::Identifier;
+///
+/// let identifier = Identifier::try_from(16).unwrap();
+/// assert_eq!(identifier, Identifier::EXTENDED_COMMUNITIES);
+/// assert_eq!(
+///     format!("attribute {} = {}", u8::from(identifier), identifier),
+///     "attribute 16 = EXTENDED_COMMUNITIES"
+/// );
+///
+/// assert!(Identifier::try_from(250).is_err());
+/// ```
+impl TryFrom<u8> for Identifier {
+    type Error = Error;
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            1 => Ok(Identifier::ORIGIN),
+            2 => Ok(Identifier::AS_PATH),
+            3 => Ok(Identifier::NEXT_HOP),
+            4 => Ok(Identifier::MULTI_EXIT_DISC),
+            5 => Ok(Identifier::LOCAL_PREF),
+            6 => Ok(Identifier::ATOMIC_AGGREGATOR),
+            7 => Ok(Identifier::AGGREGATOR),
+            8 => Ok(Identifier::COMMUNITY),
+            9 => Ok(Identifier::ORIGINATOR_ID),
+            10 => Ok(Identifier::CLUSTER_LIST),
+            11 => Ok(Identifier::DPA),
+            12 => Ok(Identifier::ADVERTISER),
+            13 => Ok(Identifier::CLUSTER_ID),
+            14 => Ok(Identifier::MP_REACH_NLRI),
+            15 => Ok(Identifier::MP_UNREACH_NLRI),
+            16 => Ok(Identifier::EXTENDED_COMMUNITIES),
+            17 => Ok(Identifier::AS4_PATH),
+            18 => Ok(Identifier::AS4_AGGREGATOR),
+            19 => Ok(Identifier::SSA),
+            20 => Ok(Identifier::CONNECTOR),
+            21 => Ok(Identifier::AS_PATHLIMIT),
+            22 => Ok(Identifier::PMSI_TUNNEL),
+            23 => Ok(Identifier::TUNNEL_ENCAPSULATION),
+            24 => Ok(Identifier::TRAFFIC_ENGINEERING),
+            25 => Ok(Identifier::IPV6_SPECIFIC_EXTENDED_COMMUNITY),
+            26 => Ok(Identifier::AIGP),
+            27 => Ok(Identifier::PE_DISTINGUISHER_LABELS),
+            28 => Ok(Identifier::ENTROPY_LABEL_CAPABILITY),
+            29 => Ok(Identifier::BGP_LS),
+            32 => Ok(Identifier::LARGE_COMMUNITY),
+            33 => Ok(Identifier::BGPSEC_PATH),
+            34 => Ok(Identifier::BGP_PREFIX_SID),
+            35 => Ok(Identifier::OTC),
+            128 => Ok(Identifier::ATTR_SET),
+            _ => Err(Error::new(
+                ErrorKind::Other,
+                format!("Not a supported path attribute type: '{}'", v),
+            )),
+        }
+    }
+}
+
+/// Convert Identifier to u8
+impl From<Identifier> for u8 {
+    fn from(identifier: Identifier) -> u8 {
+        identifier as u8
+    }
+}
+
+/// Display the Identifier by its name
+impl Display for Identifier {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{:?}", self)
+    }
 }
 
 /// Represents a path attribute that described meta data of a specific route.
-#[derive(Debug, Clone)]
+///
+/// Variants holding large values are boxed, keeping the enum at 32 bytes (instead of 56) on
+/// 64-bit targets, as routes hold many attributes.
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(non_camel_case_types)]
 pub enum PathAttribute {
     /// Indicates how an UPDATE message has been generated. Defined in [RFC4271](http://www.iana.org/go/rfc4271).
@@ -76,11 +162,12 @@ pub enum PathAttribute {
     COMMUNITY(Vec<u32>),
 
     /// Defined in [RFC4456](http://www.iana.org/go/rfc4456).
-    ORIGINATOR_ID(u32),
+    /// Holds the Router ID of the originator of the route.
+    ORIGINATOR_ID(Ipv4Addr),
 
     /// Defined in [RFC4456](http://www.iana.org/go/rfc4456).
     /// Holds a list of CLUSTER_IDs.
-    CLUSTER_LIST(Vec<u32>),
+    CLUSTER_LIST(Vec<Ipv4Addr>),
 
     /// Defined in [RFC6938](http://www.iana.org/go/rfc6938). **(deprecated)**
     /// Tuple represents the (ASN specifying the preference, DPA value).
@@ -93,10 +180,10 @@ pub enum PathAttribute {
     CLUSTER_ID,
 
     /// Multi-protocol extensions. Defined in [RFC4760](http://www.iana.org/go/rfc4760).
-    MP_REACH_NLRI(MPReachNLRI),
+    MP_REACH_NLRI(Box<MPReachNLRI>),
 
     /// Multi-protocol extensions. Defined in [RFC4760](http://www.iana.org/go/rfc4760).
-    MP_UNREACH_NLRI(MPUnreachNLRI),
+    MP_UNREACH_NLRI(Box<MPUnreachNLRI>),
 
     /// Defined in [RFC4360](http://www.iana.org/go/rfc4360).
     EXTENDED_COMMUNITIES(Vec<u64>),
@@ -117,12 +204,11 @@ pub enum PathAttribute {
     AS_PATHLIMIT((u8, u32)),
 
     /// Defined in [RFC6514](http://www.iana.org/go/rfc6514).
-    /// Specifies the (Flags, Tunnel Type + MPLS Label, Tunnel Identifier) fields.
-    PMSI_TUNNEL((u8, u32, Vec<u8>)),
+    PMSI_TUNNEL(Box<PMSITunnel>),
 
     /// Defined in [RFC5512](http://www.iana.org/go/rfc5512).
     /// Specifies the (Tunnel Type, Value) fields.
-    TUNNEL_ENCAPSULATION((u16, Vec<u8>)),
+    TUNNEL_ENCAPSULATION(Box<(u16, Vec<u8>)>),
 
     /// Defined in [RFC5543](http://www.iana.org/go/rfc5543).
     TRAFFIC_ENGINEERING,
@@ -131,9 +217,8 @@ pub enum PathAttribute {
     /// Specifies the (Transitive, Sub-type, Global Administrator, Local Administrator) fields.
     IPV6_SPECIFIC_EXTENDED_COMMUNITY((u8, u8, Ipv6Addr, u16)),
 
-    /// Defined in [RFC7311](http://www.iana.org/go/rfc7311).
-    /// Specifies the (Type, Value) fields.
-    AIGP((u8, Vec<u8>)),
+    /// Accumulated IGP Metric. Defined in [RFC7311](http://www.iana.org/go/rfc7311).
+    AIGP(AccumulatedIGP),
 
     /// Defined in [RFC6514](http://www.iana.org/go/rfc6514).
     PE_DISTINGUISHER_LABELS,
@@ -141,8 +226,8 @@ pub enum PathAttribute {
     /// Defined in [RFC6790](http://www.iana.org/go/rfc6790).
     ENTROPY_LABEL_CAPABILITY,
 
-    /// Defined in [RFC7752](http://www.iana.org/go/rfc7752).  **(deprecated)**
-    BGP_LS,
+    /// Defined in [RFC7752](http://www.iana.org/go/rfc7752).
+    BGP_LS(LinkStateAttribute),
 
     /// Defined in [RFC8092](http://www.iana.org/go/rfc8092).
     LARGE_COMMUNITY(Vec<(u32, u32, u32)>),
@@ -153,8 +238,352 @@ pub enum PathAttribute {
     /// Defined [here](http://www.iana.org/go/draft-ietf-idr-bgp-prefix-sid-27).
     BGP_PREFIX_SID,
 
+    /// Only to Customer, holds the ASN that marked the route. Defined in [RFC9234](http://www.iana.org/go/rfc9234).
+    OTC(u32),
+
     /// Defined in [RFC6368](http://www.iana.org/go/rfc6368).
-    ATTR_SET((u32, Vec<PathAttribute>)),
+    ATTR_SET(Box<(u32, Vec<PathAttribute>)>),
+
+    /// An attribute decoded by a decoder registered in `ParseOptions::attribute_decoders`,
+    /// such as a vendor-specific or experimental attribute.
+    CUSTOM(Box<dyn CustomAttribute>),
+}
+
+/// A path attribute of a type that is decoded by a decoder registered in `AttributeDecoders`,
+/// so that private attributes can be handled without changes to this crate.
+///
+/// ```
+/// use std::any::Any;
+/// use std::io::Error;
+/// use bgp_rs::{Capabilities, CustomAttribute, ParseOptions, PathAttribute};
+///
+/// #[derive(Clone, Debug)]
+/// struct Color(u32);
+///
+/// impl CustomAttribute for Color {
+///     fn code(&self) -> u8 {
+///         241
+///     }
+///     fn flags(&self) -> u8 {
+///         0xc0
+///     }
+///     fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+///         buf.extend_from_slice(&self.0.to_be_bytes());
+///         Ok(())
+///     }
+///     fn clone_box(&self) -> Box<dyn CustomAttribute> {
+///         Box::new(self.clone())
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// let mut options = ParseOptions::default();
+/// options
+///     .attribute_decoders
+///     .register(241, |_flags, _code, value: &[u8]| {
+///         let mut color = [0; 4];
+///         color.copy_from_slice(value);
+///         Ok(Box::new(Color(u32::from_be_bytes(color))) as Box<dyn CustomAttribute>)
+///     });
+///
+/// let bytes = [0xc0, 241, 4, 0, 0, 0, 7];
+/// let capabilities = Capabilities::default();
+/// let attribute =
+///     PathAttribute::parse_with_options(&mut &bytes[..], &capabilities, &options).unwrap();
+/// match &attribute {
+///     PathAttribute::CUSTOM(custom) => {
+///         let color = custom.as_any().downcast_ref::<Color>().unwrap();
+///         assert_eq!(color.0, 7);
+///     }
+///     _ => panic!("Color not decoded"),
+/// }
+/// assert_eq!(attribute.code(), 241);
+///
+/// let mut encoded = vec![];
+/// attribute.encode(&mut encoded).unwrap();
+/// assert_eq!(encoded, bytes);
+/// ```
+pub trait CustomAttribute: Debug + Send + Sync {
+    /// The attribute type code
+    fn code(&self) -> u8;
+
+    /// The attribute flags (Optional, Transitive & Partial bits), the Extended Length bit is
+    /// set when encoding a value exceeding 255 bytes.
+    fn flags(&self) -> u8;
+
+    /// Appends the attribute value, without the flags, type code & length.
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Clones the attribute, as `PathAttribute` implements `Clone`.
+    fn clone_box(&self) -> Box<dyn CustomAttribute>;
+
+    /// The attribute as `Any`, for downcasting it to its type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Clone for Box<dyn CustomAttribute> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Custom attributes are equal when their flags, type code & encoded value are.
+impl PartialEq for Box<dyn CustomAttribute> {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut value, mut other_value) = (vec![], vec![]);
+        self.code() == other.code()
+            && self.flags() == other.flags()
+            && self.encode(&mut value).is_ok()
+            && other.encode(&mut other_value).is_ok()
+            && value == other_value
+    }
+}
+
+impl Eq for Box<dyn CustomAttribute> {}
+
+/// Decodes the value of a path attribute, given its flags, type code & value.
+pub type AttributeDecoder =
+    Arc<dyn Fn(u8, u8, &[u8]) -> Result<Box<dyn CustomAttribute>, Error> + Send + Sync>;
+
+/// The decoders of path attribute types registered by the user, see `CustomAttribute`.
+///
+/// A registered decoder takes precedence over the decoding of this crate, attributes of the
+/// type are parsed into a `PathAttribute::CUSTOM`. Errors of the decoder are handled like
+/// other malformed attributes.
+#[derive(Clone, Default)]
+pub struct AttributeDecoders {
+    decoders: HashMap<u8, AttributeDecoder>,
+}
+
+impl AttributeDecoders {
+    /// Registers the decoder of an attribute type code, replacing the previous one (if any).
+    pub fn register<F>(&mut self, code: u8, decoder: F)
+    where
+        F: Fn(u8, u8, &[u8]) -> Result<Box<dyn CustomAttribute>, Error> + Send + Sync + 'static,
+    {
+        self.decoders.insert(code, Arc::new(decoder));
+    }
+
+    /// Removes the decoder of an attribute type code, returning whether it was registered.
+    pub fn unregister(&mut self, code: u8) -> bool {
+        self.decoders.remove(&code).is_some()
+    }
+
+    /// Whether a decoder is registered for the attribute type code.
+    pub fn contains(&self, code: u8) -> bool {
+        self.decoders.contains_key(&code)
+    }
+
+    pub(crate) fn get(&self, code: u8) -> Option<&AttributeDecoder> {
+        self.decoders.get(&code)
+    }
+}
+
+/// Lists the type codes decoders are registered for.
+impl Debug for AttributeDecoders {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let mut codes: Vec<&u8> = self.decoders.keys().collect();
+        codes.sort();
+        f.debug_set().entries(codes).finish()
+    }
+}
+
+/// The original encoding of a path attribute, as it was received on the wire.
+///
+/// Retained by `Update::parse` when `ParseOptions::preserve_wire_format` is set, including for
+/// attributes that could not be decoded.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RawPathAttribute {
+    /// Attribute flags (Optional, Transitive, Partial and Extended Length bits)
+    pub flags: u8,
+
+    /// Attribute type code
+    pub code: u8,
+
+    /// Attribute value
+    pub value: Vec<u8>,
+}
+
+impl RawPathAttribute {
+    /// Reads a Path Attribute without decoding its value.
+    pub fn parse(stream: &mut impl Read) -> Result<RawPathAttribute, Error> {
+        let flags = stream.read_u8()?;
+        let code = stream.read_u8()?;
+        let length: u16 = if flags & (1 << 4) == 0 {
+            u16::from(stream.read_u8()?)
+        } else {
+            stream.read_u16::<BigEndian>()?
+        };
+        let mut value = vec![0; usize::from(length)];
+        stream.read_exact(&mut value)?;
+        Ok(RawPathAttribute { flags, code, value })
+    }
+
+    /// Decode the value of this Path Attribute.
+    ///
+    /// Errors carry the UPDATE Message Error Notification for the attribute, see `ToNotification`.
+    pub fn decode(&self, capabilities: &Capabilities) -> Result<PathAttribute, Error> {
+        self.borrowed()
+            .decode(capabilities, &ParseOptions::default())
+    }
+
+    fn borrowed(&self) -> RawPathAttributeRef<'_> {
+        RawPathAttributeRef {
+            flags: self.flags,
+            code: self.code,
+            value: &self.value,
+        }
+    }
+
+    // Include this attribute as the data of an UPDATE Message Error Notification
+    fn echo_in_notification(&self, error: Error) -> Error {
+        let code = match notification_of(&error) {
+            Some(NotificationError { notification, .. })
+                if notification.data.is_empty() && notification.major_err_code() == 3 =>
+            {
+                notification.code
+            }
+            _ => return error,
+        };
+        let mut data = Vec::with_capacity(self.value.len() + 4);
+        if self.encode(&mut data).is_err() {
+            return error;
+        }
+        let message = error.to_string();
+        Error::new(
+            error.kind(),
+            NotificationError {
+                notification: Notification::from_code(code, data),
+                message,
+            },
+        )
+    }
+
+    /// Encode path attribute to bytes, exactly as it was received.
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        buf.write_u8(self.flags)?;
+        buf.write_u8(self.code)?;
+        if self.flags & (1 << 4) == 0 {
+            buf.write_u8(self.value.len() as u8)?;
+        } else {
+            buf.write_u16::<BigEndian>(self.value.len() as u16)?;
+        }
+        buf.write_all(&self.value)
+    }
+}
+
+// A path attribute borrowing its value from the buffer of a message, see `RawPathAttribute`
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct RawPathAttributeRef<'a> {
+    pub(crate) flags: u8,
+    pub(crate) code: u8,
+    pub(crate) value: &'a [u8],
+}
+
+impl<'a> RawPathAttributeRef<'a> {
+    // Reads a Path Attribute from the start of the buffer, which is advanced past it
+    pub(crate) fn parse(buf: &mut &'a [u8]) -> Result<RawPathAttributeRef<'a>, Error> {
+        let flags = buf.read_u8()?;
+        let code = buf.read_u8()?;
+        let length: u16 = if flags & (1 << 4) == 0 {
+            u16::from(buf.read_u8()?)
+        } else {
+            buf.read_u16::<BigEndian>()?
+        };
+        if buf.len() < usize::from(length) {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "failed to fill whole buffer",
+            ));
+        }
+        let (value, rest) = buf.split_at(usize::from(length));
+        *buf = rest;
+        Ok(RawPathAttributeRef { flags, code, value })
+    }
+
+    pub(crate) fn to_raw(self) -> RawPathAttribute {
+        RawPathAttribute {
+            flags: self.flags,
+            code: self.code,
+            value: self.value.to_vec(),
+        }
+    }
+
+    pub(crate) fn decode(
+        &self,
+        capabilities: &Capabilities,
+        options: &ParseOptions,
+    ) -> Result<PathAttribute, Error> {
+        let length = self.value.len() as u16;
+        let mut cursor = Cursor::new(self.value);
+        let mut count_stream = ReadCountingStream {
+            stream: &mut cursor,
+            remaining: self.value.len(),
+        };
+        PathAttribute::parse_limited(
+            &mut count_stream,
+            capabilities,
+            options,
+            self.flags,
+            self.code,
+            length,
+        )
+        .and_then(|attribute| {
+            check_consumed(count_stream.remaining)?;
+            Ok(attribute)
+        })
+        .map_err(|e| {
+            let subcode = match self.code {
+                _ if notification_of(&e).is_some() => UpdateMessageSubcode::Unspecific,
+                1 => UpdateMessageSubcode::InvalidOriginAttribute,
+                2 => UpdateMessageSubcode::MalformedASPath,
+                3 => UpdateMessageSubcode::InvalidNextHopAttribute,
+                _ if self.flags & 0x80 == 0x80 => UpdateMessageSubcode::OptionalAttributeError,
+                _ => UpdateMessageSubcode::MalformedAttributeList,
+            };
+            let e = with_notification(e, NotificationCode::UpdateMessage(subcode), vec![]);
+            self.to_raw().echo_in_notification(e)
+        })
+    }
+}
+
+// The Optional & Transitive flags of the known attribute types
+fn attribute_flags(code: u8) -> Option<u8> {
+    match code {
+        // Well-known
+        1 | 2 | 3 | 5 | 6 => Some(0x40),
+        // Optional non-transitive
+        4 | 9 | 10 | 14 | 15 | 26 | 29 => Some(0x80),
+        // Optional transitive
+        7 | 8 | 16 | 17 | 18 | 22 | 23 | 25 | 32 | 35 | 128 => Some(0xc0),
+        _ => None,
+    }
+}
+
+// Whether the flags match the attribute type, attributes of unknown types are accepted.
+// The Extended Length bit only describes the encoding of the length, the unused bits
+// are ignored (RFC4271 4.3).
+pub(crate) fn has_valid_flags(flags: u8, code: u8) -> bool {
+    match attribute_flags(code) {
+        // Only optional transitive attributes may be marked Partial
+        Some(0xc0) => flags & 0xc0 == 0xc0,
+        Some(expected) => flags & 0xe0 == expected,
+        None => true,
+    }
+}
+
+// Errors when an attribute holds bytes after its value
+fn check_consumed(remaining: usize) -> Result<(), Error> {
+    if remaining > 0 {
+        return Err(notification_error(
+            NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError),
+            vec![],
+            format!("Attribute holds {} bytes after its value", remaining),
+        ));
+    }
+    Ok(())
 }
 
 struct ReadCountingStream<'a, R: Read> {
@@ -165,9 +594,10 @@ struct ReadCountingStream<'a, R: Read> {
 impl<'a, R: Read> Read for ReadCountingStream<'a, R> {
     fn read(&mut self, buff: &mut [u8]) -> Result<usize, Error> {
         if buff.len() > self.remaining {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Attribute decode tried to read more than its length",
+            return Err(notification_error(
+                NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError),
+                vec![],
+                "Attribute decode tried to read more than its length".to_string(),
             ));
         }
         let res = self.stream.read(buff)?;
@@ -185,7 +615,8 @@ impl PathAttribute {
     ///
     /// # Errors
     /// Any IO error will be returned while reading from the stream.
-    /// Behavior is undefined when an ill-formatted stream is provided.
+    /// Errors decoding the attribute carry the corresponding UPDATE Message Error Notification,
+    /// see `ToNotification`.
     ///
     /// # Safety
     /// This function does not make use of unsafe code.
@@ -194,46 +625,49 @@ impl PathAttribute {
         stream: &mut impl Read,
         capabilities: &Capabilities,
     ) -> Result<PathAttribute, Error> {
-        let flags = stream.read_u8()?;
-        let code = stream.read_u8()?;
-
-        // Check if the Extended Length bit is set.
-        let length: u16 = if flags & (1 << 4) == 0 {
-            u16::from(stream.read_u8()?)
-        } else {
-            stream.read_u16::<BigEndian>()?
-        };
-
-        let mut count_stream = ReadCountingStream {
-            stream,
-            remaining: length as usize,
-        };
-
-        let res =
-            PathAttribute::parse_limited(&mut count_stream, capabilities, flags, code, length);
+        PathAttribute::parse_with_options(stream, capabilities, &ParseOptions::default())
+    }
 
-        // Some routes include bogus attributes, which we attempt to parse, but if they're supposed
-        // to be longer than we parsed, just ignore the remaining bytes.
-        if count_stream.remaining != 0 {
-            let mut dummy_buff = vec![0; count_stream.remaining];
-            stream.read_exact(&mut dummy_buff)?;
-        }
-        res
+    /// Reads a Path Attribute like `parse`, with the given settings of the parser (e.g. its
+    /// limits or registered decoders).
+    pub fn parse_with_options(
+        stream: &mut impl Read,
+        capabilities: &Capabilities,
+        options: &ParseOptions,
+    ) -> Result<PathAttribute, Error> {
+        // The attribute is consumed entirely, even when its value can not be decoded
+        RawPathAttribute::parse(stream)?
+            .borrowed()
+            .decode(capabilities, options)
     }
 
     fn parse_limited(
         stream: &mut impl Read,
         capabilities: &Capabilities,
-        _flags: u8,
+        options: &ParseOptions,
+        flags: u8,
         code: u8,
         length: u16,
     ) -> Result<PathAttribute, Error> {
+        if options.strict_attribute_flags && !has_valid_flags(flags, code) {
+            return Err(notification_error(
+                NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeFlagsError),
+                vec![],
+                format!("Invalid flags {:#04x} for path attribute {}", flags, code),
+            ));
+        }
+        if let Some(decoder) = options.attribute_decoders.get(code) {
+            let mut value = vec![0; usize::from(length)];
+            stream.read_exact(&mut value)?;
+            return Ok(PathAttribute::CUSTOM(decoder(flags, code, &value)?));
+        }
         match code {
             1 => Ok(PathAttribute::ORIGIN(Origin::parse(stream)?)),
             2 => Ok(PathAttribute::AS_PATH(ASPath::parse(
                 stream,
                 length,
-                capabilities,
+                capabilities.FOUR_OCTET_ASN_SUPPORT,
+                options,
             )?)),
             3 => {
                 let ip: IpAddr = if length == 4 {
@@ -267,13 +701,13 @@ impl PathAttribute {
 
                 Ok(PathAttribute::COMMUNITY(communities))
             }
-            9 => Ok(PathAttribute::ORIGINATOR_ID(
+            9 => Ok(PathAttribute::ORIGINATOR_ID(Ipv4Addr::from(
                 stream.read_u32::<BigEndian>()?,
-            )),
+            ))),
             10 => {
                 let mut ids = Vec::with_capacity(usize::from(length / 4));
                 for _ in 0..(length / 4) {
-                    ids.push(stream.read_u32::<BigEndian>()?)
+                    ids.push(Ipv4Addr::from(stream.read_u32::<BigEndian>()?))
                 }
 
                 Ok(PathAttribute::CLUSTER_LIST(ids))
@@ -282,16 +716,15 @@ impl PathAttribute {
                 stream.read_u16::<BigEndian>()?,
                 stream.read_u32::<BigEndian>()?,
             ))),
-            14 => Ok(PathAttribute::MP_REACH_NLRI(MPReachNLRI::parse(
-                stream,
-                length,
-                capabilities,
-            )?)),
-            15 => Ok(PathAttribute::MP_UNREACH_NLRI(MPUnreachNLRI::parse(
+            14 => Ok(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::parse(
                 stream,
                 length,
                 capabilities,
-            )?)),
+                options,
+            )?))),
+            15 => Ok(PathAttribute::MP_UNREACH_NLRI(Box::new(
+                MPUnreachNLRI::parse(stream, length, capabilities, options)?,
+            ))),
             16 => {
                 let mut communities = Vec::with_capacity(usize::from(length / 8));
                 for _ in 0..(length / 8) {
@@ -301,9 +734,7 @@ impl PathAttribute {
                 Ok(PathAttribute::EXTENDED_COMMUNITIES(communities))
             }
             17 => Ok(PathAttribute::AS4_PATH(ASPath::parse(
-                stream,
-                length,
-                capabilities,
+                stream, length, true, options,
             )?)),
             18 => {
                 let asn = stream.read_u32::<BigEndian>()?;
@@ -329,21 +760,19 @@ impl PathAttribute {
 
                 Ok(PathAttribute::AS_PATHLIMIT((limit, asn)))
             }
-            22 => {
-                let flags = stream.read_u8()?;
-                let label = stream.read_u32::<BigEndian>()?;
-                let mut identifier = vec![0; usize::from(length - 4)];
-                stream.read_exact(&mut identifier)?;
-
-                Ok(PathAttribute::PMSI_TUNNEL((flags, label, identifier)))
-            }
+            22 => Ok(PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel::parse(
+                stream, length,
+            )?))),
             23 => {
                 let tunnel_type = stream.read_u16::<BigEndian>()?;
                 let length = stream.read_u16::<BigEndian>()?;
                 let mut value = vec![0; usize::from(length)];
                 stream.read_exact(&mut value)?;
 
-                Ok(PathAttribute::TUNNEL_ENCAPSULATION((tunnel_type, value)))
+                Ok(PathAttribute::TUNNEL_ENCAPSULATION(Box::new((
+                    tunnel_type,
+                    value,
+                ))))
             }
             25 => {
                 let transitive = stream.read_u8()?;
@@ -358,26 +787,15 @@ impl PathAttribute {
                     local_admin,
                 )))
             }
-            26 => {
-                let aigp_type = stream.read_u8()?;
-                let length = stream.read_u16::<BigEndian>()?;
-                if length < 3 {
-                    Err(Error::new(
-                        ErrorKind::Other,
-                        format!("Bogus AIGP length: {} < 3", length),
-                    ))
-                } else {
-                    let mut value = vec![0; usize::from(length - 3)];
-                    stream.read_exact(&mut value)?;
-
-                    Ok(PathAttribute::AIGP((aigp_type, value)))
-                }
-            }
+            26 => Ok(PathAttribute::AIGP(AccumulatedIGP::parse(stream, length)?)),
             28 => {
                 stream.read_exact(&mut vec![0u8; length as usize])?;
 
                 Ok(PathAttribute::ENTROPY_LABEL_CAPABILITY)
             }
+            29 => Ok(PathAttribute::BGP_LS(LinkStateAttribute::parse(
+                stream, length,
+            )?)),
             32 => {
                 let mut communities: Vec<(u32, u32, u32)> =
                     Vec::with_capacity(usize::from(length / 12));
@@ -390,31 +808,64 @@ impl PathAttribute {
 
                 Ok(PathAttribute::LARGE_COMMUNITY(communities))
             }
+            35 => {
+                if length != 4 {
+                    return Err(notification_error(
+                        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError),
+                        vec![],
+                        format!("Bogus OTC length: {} != 4", length),
+                    ));
+                }
+                Ok(PathAttribute::OTC(stream.read_u32::<BigEndian>()?))
+            }
             128 => {
+                if length < 4 {
+                    return Err(notification_error(
+                        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError),
+                        vec![],
+                        format!("Bogus ATTR_SET length: {} < 4", length),
+                    ));
+                }
                 let asn = stream.read_u32::<BigEndian>()?;
 
-                let mut buffer = vec![0; length as usize - 4];
+                let mut buffer = vec![0; usize::from(length - 4)];
                 stream.read_exact(&mut buffer)?;
 
                 let mut cursor = Cursor::new(buffer);
 
                 let mut attributes = Vec::with_capacity(5);
                 while cursor.position() < (length - 4).into() {
-                    let result = PathAttribute::parse(&mut cursor, capabilities);
+                    let result =
+                        PathAttribute::parse_with_options(&mut cursor, capabilities, options);
                     match result {
-                        Err(x) => println!("Error: {}", x),
+                        // A truncated attribute can't be skipped
+                        Err(ref x) if x.kind() == ErrorKind::UnexpectedEof => {
+                            return Err(Error::new(
+                                ErrorKind::Other,
+                                format!("Truncated attribute in ATTR_SET: {}", x),
+                            ));
+                        }
+                        Err(_x) => {
+                            trace_event!(debug, error = %_x, "Skipped attribute in ATTR_SET");
+                        }
                         Ok(x) => attributes.push(x),
                     }
                 }
 
-                Ok(PathAttribute::ATTR_SET((asn, attributes)))
+                Ok(PathAttribute::ATTR_SET(Box::new((asn, attributes))))
             }
             x => {
                 let mut buffer = vec![0; usize::from(length)];
                 stream.read_exact(&mut buffer)?;
 
-                Err(Error::new(
-                    ErrorKind::Other,
+                let subcode = if flags & 0x80 == 0x80 {
+                    UpdateMessageSubcode::OptionalAttributeError
+                } else {
+                    UpdateMessageSubcode::UnrecognizedWellKnownAttribute
+                };
+                Err(notification_error(
+                    NotificationCode::UpdateMessage(subcode),
+                    vec![],
                     format!("Unknown path attribute type found: {}", x),
                 ))
             }
@@ -454,18 +905,77 @@ impl PathAttribute {
             PathAttribute::AIGP(_) => Identifier::AIGP,
             PathAttribute::PE_DISTINGUISHER_LABELS => Identifier::PE_DISTINGUISHER_LABELS,
             PathAttribute::ENTROPY_LABEL_CAPABILITY => Identifier::ENTROPY_LABEL_CAPABILITY,
-            PathAttribute::BGP_LS => Identifier::BGP_LS,
+            PathAttribute::BGP_LS(_) => Identifier::BGP_LS,
             PathAttribute::LARGE_COMMUNITY(_) => Identifier::LARGE_COMMUNITY,
             PathAttribute::BGPSEC_PATH => Identifier::BGPSEC_PATH,
             PathAttribute::BGP_PREFIX_SID => Identifier::BGP_PREFIX_SID,
+            PathAttribute::OTC(_) => Identifier::OTC,
             PathAttribute::ATTR_SET(_) => Identifier::ATTR_SET,
+            PathAttribute::CUSTOM(_) => Identifier::CUSTOM,
+        }
+    }
+
+    /// The type code of this PathAttribute, which is also known for `CUSTOM` attributes.
+    pub fn code(&self) -> u8 {
+        match self {
+            PathAttribute::CUSTOM(attribute) => attribute.code(),
+            attribute => attribute.id() as u8,
         }
     }
 
     /// Encode path attribute to bytes
+    ///
+    /// The ASN width of AS_PATH is derived from the ASNs it contains.
+    /// Use `encode_with_capabilities` to follow the capabilities negotiated with a peer.
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(16);
+        self.encode_into(&mut bytes, None)?;
+        buf.write_all(&bytes)
+    }
+
+    /// Encode path attribute to bytes, using the ASN width & ADD-PATH families negotiated
+    /// with a peer.
+    ///
+    /// When the peer does not support 4-byte ASNs, any 4-byte ASN in AS_PATH and AGGREGATOR
+    /// is replaced with AS_TRANS.
+    pub fn encode_with_capabilities(
+        &self,
+        buf: &mut impl Write,
+        capabilities: &Capabilities,
+    ) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(16);
+        self.encode_into(&mut bytes, Some(capabilities))?;
+        buf.write_all(&bytes)
+    }
+
+    /// Appends the path attribute to a buffer, returning the number of bytes written.
+    ///
+    /// Encodes like `encode`, or like `encode_with_capabilities` when capabilities are given,
+    /// without allocating temporary buffers. Nothing is appended when encoding fails.
+    pub fn encode_into(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<usize, Error> {
+        let start = buf.len();
+        self.encode_inner(buf, capabilities)
+            .map(|_| buf.len() - start)
+            .map_err(|e| {
+                buf.truncate(start);
+                e
+            })
+    }
+
+    fn encode_inner(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<(), Error> {
         use PathAttribute::*;
-        let mut bytes = Vec::with_capacity(8);
+        let four_octet_asn = capabilities.map(|capabilities| capabilities.FOUR_OCTET_ASN_SUPPORT);
+        // The flags, type code & extended length are written once the value is known
+        let start = buf.len();
+        buf.extend_from_slice(&[0; 4]);
         let (mut flags, identifier) = match self {
             ORIGIN(origin) => {
                 let value: u8 = match origin {
@@ -473,85 +983,206 @@ impl PathAttribute {
                     Origin::EGP => 1,
                     Origin::INCOMPLETE => 2,
                 };
-                bytes.write_u8(value)?;
+                buf.write_u8(value)?;
                 (0x40, Identifier::ORIGIN)
             }
             AS_PATH(as_path) => {
-                as_path.encode(&mut bytes)?;
+                match four_octet_asn {
+                    Some(four_octet_asn) => as_path.encode_with_asn_width(buf, four_octet_asn)?,
+                    None => as_path.encode(buf)?,
+                }
                 (0x40, Identifier::AS_PATH)
             }
             COMMUNITY(communities) => {
                 for comm in communities {
-                    bytes.write_u32::<BigEndian>(*comm)?;
+                    buf.write_u32::<BigEndian>(*comm)?;
                 }
                 (0xc0, Identifier::COMMUNITY)
             }
             NEXT_HOP(next_hop) => {
                 match next_hop {
-                    IpAddr::V4(addr) => bytes.write_all(&addr.octets())?,
-                    IpAddr::V6(addr) => bytes.write_all(&addr.octets())?,
+                    IpAddr::V4(addr) => buf.write_all(&addr.octets())?,
+                    IpAddr::V6(addr) => buf.write_all(&addr.octets())?,
                 }
                 (0x40, Identifier::NEXT_HOP)
             }
             MULTI_EXIT_DISC(med) => {
-                bytes.write_u32::<BigEndian>(*med)?;
+                buf.write_u32::<BigEndian>(*med)?;
                 (0x80, Identifier::MULTI_EXIT_DISC)
             }
             LOCAL_PREF(pref) => {
-                bytes.write_u32::<BigEndian>(*pref)?;
+                buf.write_u32::<BigEndian>(*pref)?;
                 (0x40, Identifier::LOCAL_PREF)
             }
             MP_REACH_NLRI(mp_reach) => {
-                mp_reach.encode(&mut bytes)?;
+                match capabilities {
+                    Some(capabilities) => mp_reach.encode_with_capabilities(buf, capabilities)?,
+                    None => mp_reach.encode(buf)?,
+                }
                 (0x80, Identifier::MP_REACH_NLRI)
             }
             MP_UNREACH_NLRI(mp_unreach) => {
-                mp_unreach.encode(&mut bytes)?;
+                match capabilities {
+                    Some(capabilities) => mp_unreach.encode_with_capabilities(buf, capabilities)?,
+                    None => mp_unreach.encode(buf)?,
+                }
                 (0x80, Identifier::MP_UNREACH_NLRI)
             }
             EXTENDED_COMMUNITIES(ext_communities) => {
                 for comm in ext_communities {
-                    bytes.write_u64::<BigEndian>(*comm)?;
+                    buf.write_u64::<BigEndian>(*comm)?;
                 }
                 (0xc0, Identifier::EXTENDED_COMMUNITIES)
             }
             CLUSTER_LIST(clusters) => {
                 for cluster in clusters {
-                    bytes.write_u32::<BigEndian>(*cluster)?;
+                    buf.write_all(&cluster.octets())?;
                 }
                 (0x80, Identifier::CLUSTER_LIST)
             }
             ORIGINATOR_ID(origin_id) => {
-                bytes.write_u32::<BigEndian>(*origin_id)?;
+                buf.write_all(&origin_id.octets())?;
                 (0x80, Identifier::ORIGINATOR_ID)
             }
             AS4_PATH(as_path) => {
-                as_path.encode(&mut bytes)?;
+                match four_octet_asn {
+                    // AS4_PATH always carries 4-byte ASNs
+                    Some(_) => as_path.encode_with_asn_width(buf, true)?,
+                    None => as_path.encode(buf)?,
+                }
                 (0xc0, Identifier::AS4_PATH)
             }
             AGGREGATOR((asn, ip)) => {
-                bytes.write_u16::<BigEndian>(*asn as u16)?;
-                bytes.write_u32::<BigEndian>((*ip).into())?;
+                match four_octet_asn {
+                    Some(true) => buf.write_u32::<BigEndian>(*asn)?,
+                    Some(false) => buf.write_u16::<BigEndian>(as_trans(*asn) as u16)?,
+                    None => buf.write_u16::<BigEndian>(*asn as u16)?,
+                }
+                buf.write_u32::<BigEndian>((*ip).into())?;
                 (0xc0, Identifier::AGGREGATOR)
             }
+            AS4_AGGREGATOR((asn, ip)) => {
+                buf.write_u32::<BigEndian>(*asn)?;
+                buf.write_u32::<BigEndian>((*ip).into())?;
+                (0xc0, Identifier::AS4_AGGREGATOR)
+            }
+            OTC(asn) => {
+                buf.write_u32::<BigEndian>(*asn)?;
+                (0xc0, Identifier::OTC)
+            }
+            PMSI_TUNNEL(pmsi_tunnel) => {
+                pmsi_tunnel.encode(buf)?;
+                (0xc0, Identifier::PMSI_TUNNEL)
+            }
+            AIGP(aigp) => {
+                aigp.encode(buf)?;
+                (0x80, Identifier::AIGP)
+            }
+            BGP_LS(link_state) => {
+                link_state.encode(buf)?;
+                (0x80, Identifier::BGP_LS)
+            }
+            ATTR_SET(attribute_set) => {
+                let (asn, attributes) = &**attribute_set;
+                buf.write_u32::<BigEndian>(*asn)?;
+                for attribute in attributes {
+                    attribute.encode_inner(buf, capabilities)?;
+                }
+                (0xc0, Identifier::ATTR_SET)
+            }
+            CUSTOM(attribute) => {
+                attribute.encode(buf)?;
+                (attribute.flags(), Identifier::CUSTOM)
+            }
             _ => {
-                unimplemented!("{:?}", self);
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Encoding {:?} is not supported", self.id()),
+                ));
             }
         };
+        let length = buf.len() - start - 4;
         // Use extended length if the attribute bytes are greater than 255
         // Or if a PathAttribute has explicitly set the ext-length bit (0x10)
-        let is_extended_length = bytes.len() > std::u8::MAX as usize || (flags & 0x10) == 0x10;
+        let is_extended_length = length > std::u8::MAX as usize || (flags & 0x10) == 0x10;
         if is_extended_length {
             flags |= 0x10; // Set extended length bit
-        }
-        buf.write_u8(flags)?;
-        buf.write_u8(identifier as u8)?;
-        if is_extended_length {
-            buf.write_u16::<BigEndian>(bytes.len() as u16)?;
+            buf[start + 2..start + 4].copy_from_slice(&(length as u16).to_be_bytes());
         } else {
-            buf.write_u8(bytes.len() as u8)?;
+            // The value moves into the second byte reserved for the length
+            buf.remove(start + 3);
+            buf[start + 2] = length as u8;
+        }
+        buf[start] = flags;
+        buf[start + 1] = match self {
+            CUSTOM(attribute) => attribute.code(),
+            _ => identifier as u8,
+        };
+        Ok(())
+    }
+}
+
+/// Renders the attribute the way it is shown by `show ip bgp` style commands.
+///
+/// ```
+/// use bgp_rs::{ASPath, PathAttribute, Segment};
+///
+/// let as_path = PathAttribute::AS_PATH(ASPath {
+///     segments: vec![Segment::AS_SEQUENCE(vec![65000, 65001])],
+/// });
+/// assert_eq!(as_path.to_string(), "AS path 65000 65001");
+/// assert_eq!(
+///     PathAttribute::COMMUNITY(vec![0xfde8_0064]).to_string(),
+///     "communities 65000:100"
+/// );
+/// ```
+impl Display for PathAttribute {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            PathAttribute::ORIGIN(origin) => write!(f, "origin {}", origin),
+            PathAttribute::AS_PATH(as_path) if as_path.segments.is_empty() => {
+                write!(f, "AS path (empty)")
+            }
+            PathAttribute::AS_PATH(as_path) => write!(f, "AS path {}", as_path),
+            PathAttribute::NEXT_HOP(next_hop) => write!(f, "next hop {}", next_hop),
+            PathAttribute::MULTI_EXIT_DISC(med) => write!(f, "MED {}", med),
+            PathAttribute::LOCAL_PREF(local_pref) => write!(f, "local pref {}", local_pref),
+            PathAttribute::ATOMIC_AGGREGATOR => write!(f, "atomic aggregate"),
+            PathAttribute::AGGREGATOR((asn, ip)) => write!(f, "aggregator {} {}", asn, ip),
+            PathAttribute::COMMUNITY(communities) => {
+                write!(f, "communities")?;
+                for community in communities {
+                    write!(f, " {}:{}", community >> 16, community & 0xffff)?;
+                }
+                Ok(())
+            }
+            PathAttribute::ORIGINATOR_ID(originator) => write!(f, "originator {}", originator),
+            PathAttribute::CLUSTER_LIST(clusters) => {
+                write!(f, "cluster list")?;
+                for cluster in clusters {
+                    write!(f, " {}", cluster)?;
+                }
+                Ok(())
+            }
+            PathAttribute::MP_REACH_NLRI(mp_reach) => write!(f, "{}", mp_reach),
+            PathAttribute::MP_UNREACH_NLRI(mp_unreach) => write!(f, "withdrawn {}", mp_unreach),
+            PathAttribute::EXTENDED_COMMUNITIES(communities) => {
+                write!(f, "extended communities")?;
+                for community in communities {
+                    write!(f, " {:#018x}", community)?;
+                }
+                Ok(())
+            }
+            PathAttribute::LARGE_COMMUNITY(communities) => {
+                write!(f, "large communities")?;
+                for (global, local1, local2) in communities {
+                    write!(f, " {}:{}:{}", global, local1, local2)?;
+                }
+                Ok(())
+            }
+            PathAttribute::OTC(asn) => write!(f, "only to customer {}", asn),
+            attribute => write!(f, "{:?}", attribute),
         }
-        buf.write_all(&bytes)
     }
 }
 
@@ -564,7 +1195,7 @@ impl PathAttribute {
 /// assert_eq!(&(Origin::EGP).to_string(), "EGP");
 /// assert_eq!(&(Origin::INCOMPLETE).to_string(), "Incomplete");
 /// ```
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Origin {
     /// Generated by an Interior Gateway Protocol
     IGP,
@@ -598,15 +1229,50 @@ impl Display for Origin {
 }
 
 /// Represents the path that an announcement has traveled.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ASPath {
     /// A collection of segments that together form the path that a message has traveled.
     pub segments: Vec<Segment>,
 }
 
 impl ASPath {
-    fn parse(stream: &mut impl Read, length: u16, _: &Capabilities) -> Result<ASPath, Error> {
-        let segments = Segment::parse_unknown_segments(stream, length)?;
+    // The ASNs are expected to have the width negotiated for AS_PATH, or 4 bytes for AS4_PATH.
+    // As this is not always followed (or known, e.g. in MRT dumps), the other width is
+    // only attempted when the segments do not fit the attribute.
+    fn parse(
+        stream: &mut impl Read,
+        length: u16,
+        four_byte_asns: bool,
+        options: &ParseOptions,
+    ) -> Result<ASPath, Error> {
+        // Most AS_PATHs fit the inline buffer, avoiding an allocation for each of them
+        let mut inline = [0u8; AS_PATH_INLINE_LENGTH];
+        let mut allocated = vec![];
+        let buf: &mut [u8] = if usize::from(length) <= AS_PATH_INLINE_LENGTH {
+            &mut inline[..usize::from(length)]
+        } else {
+            allocated.resize(usize::from(length), 0);
+            &mut allocated
+        };
+        stream.read_exact(buf)?;
+        let (width, other_width) = if four_byte_asns { (4, 2) } else { (2, 4) };
+        let max_segments = options.max_as_path_segments;
+        let segments = match Segment::parse_segments(buf, width, max_segments)? {
+            Some(segments) => segments,
+            None => {
+                let segments = Segment::parse_segments(buf, other_width, max_segments)?
+                    .ok_or_else(|| {
+                        Error::new(ErrorKind::Other, "Invalid AS_PATH length detected")
+                    })?;
+                trace_event!(
+                    debug,
+                    expected = width,
+                    detected = other_width,
+                    "AS_PATH holds ASNs of the other width"
+                );
+                segments
+            }
+        };
         Ok(ASPath { segments })
     }
 
@@ -682,173 +1348,1049 @@ impl ASPath {
         Some(sequence)
     }
 
-    /// Encode AS Path to bytes
-    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
-        for segment in &self.segments {
-            let (path_type, seq) = match segment {
-                Segment::AS_SET(set) => (1u8, set),
-                Segment::AS_SEQUENCE(seq) => (2u8, seq),
-            };
-            buf.write_u8(path_type)?;
-            buf.write_u8(seq.len() as u8)?;
-            let is_4_byte_aspath = self.has_4_byte_asns();
-            for asn in seq.iter() {
-                if is_4_byte_aspath {
-                    buf.write_u32::<BigEndian>(*asn)?;
-                } else {
-                    buf.write_u16::<BigEndian>(*asn as u16)?;
+    /// Prepends an ASN to the path `count` times.
+    /// The ASN is added to the leading AS_SEQUENCE, or a new one is created when the path
+    /// starts with an AS_SET or the leading sequence is full (255 ASNs).
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let mut aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 200]),
+    /// ]};
+    /// aspath.prepend(300, 2);
+    /// assert_eq!(aspath.sequence(), Some(vec![300, 300, 100, 200]));
+    ///
+    /// let mut aspath = ASPath { segments: vec![
+    ///     Segment::AS_SET(vec![100, 200]),
+    /// ]};
+    /// aspath.prepend(300, 1);
+    /// assert_eq!(aspath.segments.len(), 2);
+    /// ```
+    pub fn prepend(&mut self, asn: u32, count: usize) {
+        for _ in 0..count {
+            match self.segments.first_mut() {
+                Some(Segment::AS_SEQUENCE(seq)) if seq.len() < std::u8::MAX as usize => {
+                    seq.insert(0, asn);
                 }
+                _ => self.segments.insert(0, Segment::AS_SEQUENCE(vec![asn])),
             }
         }
-        Ok(())
     }
-}
 
-/// Represents the segment type of an AS_PATH. Can be either AS_SEQUENCE or AS_SET.
-#[derive(Debug, Clone)]
-#[allow(non_camel_case_types)]
-pub enum Segment {
-    /// Represents a sequence of ASN that an announcement traveled through.
+    /// Returns the length of the AS_PATH as used in route selection.
+    /// Each ASN in an AS_SEQUENCE counts as 1, each AS_SET counts as 1 regardless of its size.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 200, 300]),
+    ///     Segment::AS_SET(vec![400, 500]),
+    /// ]};
+    /// assert_eq!(aspath.path_length(), 4);
+    /// ```
+    pub fn path_length(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::AS_SEQUENCE(seq) => seq.len(),
+                Segment::AS_SET(_) => 1,
+            })
+            .sum()
+    }
+
+    /// Checks if the ASN is present anywhere in the AS_PATH.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 200]),
+    ///     Segment::AS_SET(vec![300, 400]),
+    /// ]};
+    /// assert!(aspath.contains(200));
+    /// assert!(aspath.contains(400));
+    /// assert!(!aspath.contains(500));
+    /// ```
+    pub fn contains(&self, asn: u32) -> bool {
+        self.segments.iter().any(|s| s.asns().contains(&asn))
+    }
+
+    /// Removes all private ASNs (RFC6996) from the AS_PATH.
+    /// Segments left empty are removed.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let mut aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 64512, 200]),
+    ///     Segment::AS_SET(vec![65000, 4200000000]),
+    /// ]};
+    /// aspath.remove_private_asns();
+    /// assert_eq!(aspath.sequence(), Some(vec![100, 200]));
+    /// ```
+    pub fn remove_private_asns(&mut self) {
+        for segment in self.segments.iter_mut() {
+            segment.asns_mut().retain(|asn| !Asn(*asn).is_private());
+        }
+        self.segments.retain(|s| !s.asns().is_empty());
+    }
+
+    /// Replaces all private ASNs (RFC6996) in the AS_PATH with the given local ASN.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let mut aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 64512, 200]),
+    /// ]};
+    /// aspath.replace_private_asns(300);
+    /// assert_eq!(aspath.sequence(), Some(vec![100, 300, 200]));
+    /// ```
+    pub fn replace_private_asns(&mut self, local_asn: u32) {
+        for segment in self.segments.iter_mut() {
+            for asn in segment.asns_mut().iter_mut() {
+                if Asn(*asn).is_private() {
+                    *asn = local_asn;
+                }
+            }
+        }
+    }
+
+    /// Reconstructs the full AS path from an AS_PATH and AS4_PATH as received from a speaker
+    /// that does not support 4-byte ASNs, following the rules of RFC6793 section 4.2.3.
+    ///
+    /// If the AS_PATH is shorter than the AS4_PATH, the AS4_PATH is ignored. Otherwise the
+    /// leading ASNs of the AS_PATH that are not covered by the AS4_PATH are prepended to it.
+    /// Whether the AS4_PATH is to be used at all depends on the AGGREGATOR, which
+    /// `Update::effective_as_path` checks.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let as_path = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![2, 23456, 23456]),
+    /// ]};
+    /// let as4_path = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![655361, 2621441]),
+    /// ]};
+    /// let merged = ASPath::merge_as4(&as_path, &as4_path);
+    /// assert_eq!(merged.sequence(), Some(vec![2, 655361, 2621441]));
+    ///
+    /// // AS4_PATH is longer than AS_PATH, so it is ignored
+    /// let as_path = ASPath { segments: vec![Segment::AS_SEQUENCE(vec![23456])] };
+    /// let merged = ASPath::merge_as4(&as_path, &as4_path);
+    /// assert_eq!(merged.sequence(), Some(vec![23456]));
+    /// ```
+    pub fn merge_as4(as_path: &ASPath, as4_path: &ASPath) -> ASPath {
+        let as_path_length = as_path.path_length();
+        let as4_path_length = as4_path.path_length();
+        if as_path_length < as4_path_length {
+            return as_path.clone();
+        }
+
+        // Take the leading ASNs from AS_PATH that are not present in AS4_PATH
+        let mut remaining = as_path_length - as4_path_length;
+        let mut segments: Vec<Segment> = Vec::with_capacity(as_path.segments.len());
+        for segment in &as_path.segments {
+            if remaining == 0 {
+                break;
+            }
+            match segment {
+                Segment::AS_SEQUENCE(seq) => {
+                    let count = std::cmp::min(remaining, seq.len());
+                    segments.push(Segment::AS_SEQUENCE(seq[..count].to_vec()));
+                    remaining -= count;
+                }
+                Segment::AS_SET(set) => {
+                    segments.push(Segment::AS_SET(set.clone()));
+                    remaining -= 1;
+                }
+            }
+        }
+
+        for segment in &as4_path.segments {
+            match (segments.last_mut(), segment) {
+                (Some(Segment::AS_SEQUENCE(last)), Segment::AS_SEQUENCE(seq))
+                    if last.len() + seq.len() <= std::u8::MAX as usize =>
+                {
+                    last.extend(seq)
+                }
+                _ => segments.push(segment.clone()),
+            }
+        }
+
+        ASPath { segments }
+    }
+
+    /// Encode AS Path to bytes
+    /// Uses 4-byte ASNs only if the AS_PATH contains ASNs that do not fit in 2 bytes.
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        self.encode_with_asn_width(buf, self.has_4_byte_asns())
+    }
+
+    /// Encode AS Path to bytes, using either 2-byte or 4-byte ASNs.
+    /// When encoding 2-byte ASNs, any 4-byte ASN is replaced with AS_TRANS.
+    /// ```
+    /// use bgp_rs::{ASPath, Segment};
+    ///
+    /// let aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100, 4200000000]),
+    /// ]};
+    /// let mut bytes = vec![];
+    /// aspath.encode_with_asn_width(&mut bytes, false).unwrap();
+    /// assert_eq!(bytes, vec![2, 2, 0, 100, 0x5b, 0xa0]);
+    ///
+    /// let aspath = ASPath { segments: vec![
+    ///     Segment::AS_SEQUENCE(vec![100]),
+    /// ]};
+    /// let mut bytes = vec![];
+    /// aspath.encode_with_asn_width(&mut bytes, true).unwrap();
+    /// assert_eq!(bytes, vec![2, 1, 0, 0, 0, 100]);
+    /// ```
+    pub fn encode_with_asn_width(
+        &self,
+        buf: &mut impl Write,
+        four_octet_asn: bool,
+    ) -> Result<(), Error> {
+        for segment in &self.segments {
+            let (path_type, seq) = match segment {
+                Segment::AS_SET(set) => (1u8, set),
+                Segment::AS_SEQUENCE(seq) => (2u8, seq),
+            };
+            buf.write_u8(path_type)?;
+            buf.write_u8(seq.len() as u8)?;
+            for asn in seq.iter() {
+                if four_octet_asn {
+                    buf.write_u32::<BigEndian>(*asn)?;
+                } else {
+                    buf.write_u16::<BigEndian>(as_trans(*asn) as u16)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Renders the path as a space-separated list of ASNs, with AS_SETs in braces.
+///
+/// ```
+/// use bgp_rs::{ASPath, Segment};
+///
+/// let as_path = ASPath {
+///     segments: vec![
+///         Segment::AS_SEQUENCE(vec![65000, 65001]),
+///         Segment::AS_SET(vec![65002, 65003]),
+///     ],
+/// };
+/// assert_eq!(as_path.to_string(), "65000 65001 {65002,65003}");
+/// ```
+impl Display for ASPath {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let mut first = true;
+        for segment in &self.segments {
+            match segment {
+                Segment::AS_SEQUENCE(asns) => {
+                    for asn in asns {
+                        if !first {
+                            write!(f, " ")?;
+                        }
+                        write!(f, "{}", asn)?;
+                        first = false;
+                    }
+                }
+                Segment::AS_SET(asns) => {
+                    if !first {
+                        write!(f, " ")?;
+                    }
+                    let asns: Vec<String> = asns.iter().map(|asn| asn.to_string()).collect();
+                    write!(f, "{{{}}}", asns.join(","))?;
+                    first = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Represents the segment type of an AS_PATH. Can be either AS_SEQUENCE or AS_SET.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum Segment {
+    /// Represents a sequence of ASN that an announcement traveled through.
     AS_SEQUENCE(Vec<u32>),
 
     /// Represents a set of ASN through which a BGP message traveled.
     AS_SET(Vec<u32>),
 }
 
+// The length of AS_PATHs read without allocating, any attribute without the Extended Length flag
+const AS_PATH_INLINE_LENGTH: usize = 255;
+
+/// Reserved 2-byte ASN used in place of 4-byte ASNs when communicating with
+/// speakers that do not support 4-byte ASNs. Defined in [RFC6793](http://www.iana.org/go/rfc6793).
+pub const AS_TRANS: u32 = 23456;
+
+/// The GRACEFUL_SHUTDOWN well-known community (65535:0), tagging routes that are about to be
+/// withdrawn for maintenance. Defined in [RFC8326](http://www.iana.org/go/rfc8326).
+pub const GRACEFUL_SHUTDOWN: u32 = 0xffff_0000;
+
+/// Substitutes AS_TRANS for ASNs that can not be represented in 2 bytes.
+fn as_trans(asn: u32) -> u32 {
+    if asn > u32::from(std::u16::MAX) {
+        AS_TRANS
+    } else {
+        asn
+    }
+}
+
 impl Segment {
     /// Are there any 4-byte ASNs in the Segment
     pub fn has_4_byte_asns(&self) -> bool {
-        let asns = match &self {
+        self.asns().iter().any(|a| a > &(std::u16::MAX as u32))
+    }
+
+    /// The ASNs contained in this Segment, regardless of the segment type
+    pub fn asns(&self) -> &[u32] {
+        match self {
             Segment::AS_SEQUENCE(asns) => asns,
             Segment::AS_SET(asns) => asns,
-        };
-        asns.iter().any(|a| a > &(std::u16::MAX as u32))
+        }
     }
 
-    fn parse_unknown_segments(stream: &mut impl Read, length: u16) -> Result<Vec<Segment>, Error> {
-        // Read in everything so we can touch the buffer multiple times in order to
-        // work out what we have
-        let mut buf = vec![0u8; length as usize];
-        stream.read_exact(&mut buf)?;
-        let size = buf.len();
-        let mut cur = Cursor::new(buf);
+    fn asns_mut(&mut self) -> &mut Vec<u32> {
+        match self {
+            Segment::AS_SEQUENCE(asns) => asns,
+            Segment::AS_SET(asns) => asns,
+        }
+    }
 
-        'as_len: for i in 1..=2u64 {
-            cur.set_position(0);
+    // Parses segments with ASNs of the given width (2 or 4 bytes) in a single pass.
+    // None when they do not fill the buffer exactly, as ASNs of the other width usually don't.
+    // Stops as soon as the segments exceed `max_segments`, instead of decoding all of them.
+    fn parse_segments(
+        buf: &[u8],
+        width: usize,
+        max_segments: usize,
+    ) -> Result<Option<Vec<Segment>>, Error> {
+        let mut segments: Vec<Segment> = Vec::with_capacity(1);
+        let mut position = 0;
+        while position < buf.len() {
+            if segments.len() == max_segments {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("AS_PATH exceeds the maximum of {} segments", max_segments),
+                ));
+            }
+            // The type of a segment (AS_SET or AS_SEQUENCE) and the amount of ASNs inside it.
+            let (segment_type, count) = match buf.get(position..position + 2) {
+                Some(&[segment_type, count]) if segment_type == 1 || segment_type == 2 => {
+                    (segment_type, usize::from(count))
+                }
+                _ => return Ok(None),
+            };
+            let start = position + 2;
+            position = start + count * width;
+            let asns = match buf.get(start..position) {
+                Some(asns) => asns,
+                None => return Ok(None),
+            };
+            // Construct a Vec<u32> such that one interface be used when handling AS_PATHs.
+            let mut elements: Vec<u32> = Vec::with_capacity(count);
+            if width == 2 {
+                for asn in asns.chunks_exact(2) {
+                    elements.push(u32::from(u16::from_be_bytes([asn[0], asn[1]])));
+                }
+            } else {
+                for asn in asns.chunks_exact(4) {
+                    elements.push(u32::from_be_bytes([asn[0], asn[1], asn[2], asn[3]]));
+                }
+            }
+            if segment_type == 1 {
+                segments.push(Segment::AS_SET(elements));
+            } else {
+                segments.push(Segment::AS_SEQUENCE(elements));
+            }
+        }
+        Ok(Some(segments))
+    }
+}
 
-            // Now attempt to work out whether the first segment is 2 byte or 4 byte
-            let assumed_as_len = i * 2;
-            let mut total_segments = 0u64;
+/// The TLVs of an AIGP attribute. Defined in [RFC7311](http://www.iana.org/go/rfc7311).
+///
+/// ```
+/// use bgp_rs::{AccumulatedIGP, AIGPTlv};
+///
+/// let mut aigp = AccumulatedIGP { tlvs: vec![AIGPTlv::Metric(100)] };
+/// aigp.increment(20);
+/// assert_eq!(aigp.metric(), Some(120));
+///
+/// // The metric does not wrap around
+/// aigp.increment(std::u64::MAX);
+/// assert_eq!(aigp.metric(), Some(std::u64::MAX));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AccumulatedIGP {
+    /// The TLVs contained in the attribute, in the order they were received.
+    pub tlvs: Vec<AIGPTlv>,
+}
 
-            while cur.position() < size as u64 {
-                let segment_type = cur.read_u8()?;
-                let segment_len = cur.read_u8()?;
+impl AccumulatedIGP {
+    fn parse(stream: &mut impl Read, length: u16) -> Result<AccumulatedIGP, Error> {
+        let mut tlvs = Vec::with_capacity(1);
+        let mut remaining = length;
+        while remaining > 0 {
+            if remaining < 3 {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Bogus AIGP TLV header: {} bytes remaining", remaining),
+                ));
+            }
+            let tlv_type = stream.read_u8()?;
+            let tlv_length = stream.read_u16::<BigEndian>()?;
+            if tlv_length < 3 || tlv_length > remaining {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Bogus AIGP TLV length: {}", tlv_length),
+                ));
+            }
+            let tlv = match (tlv_type, tlv_length) {
+                (1, 11) => AIGPTlv::Metric(stream.read_u64::<BigEndian>()?),
+                _ => {
+                    let mut value = vec![0; usize::from(tlv_length - 3)];
+                    stream.read_exact(&mut value)?;
+                    AIGPTlv::Unknown { tlv_type, value }
+                }
+            };
+            tlvs.push(tlv);
+            remaining -= tlv_length;
+        }
+        Ok(AccumulatedIGP { tlvs })
+    }
 
-                // If the second segment type isn't valid, pretty sure this isn't 2 byte
-                if (assumed_as_len == 2 && total_segments >= 1)
-                    && (segment_type < 1 || segment_type > 2)
-                {
-                    continue 'as_len;
+    /// Encode AIGP attribute value to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        for tlv in &self.tlvs {
+            match tlv {
+                AIGPTlv::Metric(metric) => {
+                    buf.write_u8(1)?;
+                    buf.write_u16::<BigEndian>(11)?;
+                    buf.write_u64::<BigEndian>(*metric)?;
+                }
+                AIGPTlv::Unknown { tlv_type, value } => {
+                    buf.write_u8(*tlv_type)?;
+                    buf.write_u16::<BigEndian>(value.len() as u16 + 3)?;
+                    buf.write_all(value)?;
                 }
+            }
+        }
+        Ok(())
+    }
 
-                cur.set_position(cur.position() + (u64::from(segment_len) * assumed_as_len));
-                total_segments += 1;
+    /// Returns the accumulated IGP metric of the first AIGP TLV, if any.
+    pub fn metric(&self) -> Option<u64> {
+        self.tlvs.iter().find_map(|tlv| match tlv {
+            AIGPTlv::Metric(metric) => Some(*metric),
+            _ => None,
+        })
+    }
+
+    /// Adds the metric towards the next hop to the accumulated IGP metric, as done when
+    /// propagating the attribute. The result saturates at the maximum value.
+    pub fn increment(&mut self, metric: u64) {
+        for tlv in &mut self.tlvs {
+            if let AIGPTlv::Metric(accumulated) = tlv {
+                *accumulated = accumulated.saturating_add(metric);
+                // Only the first AIGP TLV is used, others are ignored
+                break;
             }
+        }
+    }
+}
 
-            if cur.position() == u64::from(length) {
-                cur.set_position(0);
+/// A TLV held in an AIGP attribute.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AIGPTlv {
+    /// 1 - The accumulated IGP metric.
+    Metric(u64),
 
-                match i {
-                    1 => {
-                        return Segment::parse_u16_segments(&mut cur, length);
-                    }
-                    2 => {
-                        return Segment::parse_u32_segments(&mut cur, length);
+    /// Unknown (or unsupported) TLV
+    Unknown {
+        /// The type of the TLV.
+        tlv_type: u8,
+
+        /// The value of the TLV.
+        value: Vec<u8>,
+    },
+}
+
+/// Identifies the provider tunnel used to deliver multicast (or BUM) traffic.
+/// Defined in [RFC6514](http://www.iana.org/go/rfc6514).
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PMSITunnel {
+    /// Flags, where the least significant bit is Leaf Information Required.
+    pub flags: u8,
+
+    /// The 3-octet MPLS Label field, the label value is held in the high-order 20 bits.
+    pub label: u32,
+
+    /// The type of tunnel and the identifier of the tunnel of that type.
+    pub identifier: PMSITunnelIdentifier,
+}
+
+impl PMSITunnel {
+    fn parse(stream: &mut impl Read, length: u16) -> Result<PMSITunnel, Error> {
+        if length < 5 {
+            return Err(notification_error(
+                NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError),
+                vec![],
+                format!("Bogus PMSI Tunnel length: {} < 5", length),
+            ));
+        }
+        let flags = stream.read_u8()?;
+        let tunnel_type = stream.read_u8()?;
+        let label = stream.read_u24::<BigEndian>()?;
+        let identifier = PMSITunnelIdentifier::parse(stream, tunnel_type, length - 5)?;
+
+        Ok(PMSITunnel {
+            flags,
+            label,
+            identifier,
+        })
+    }
+
+    /// Encode PMSI Tunnel attribute value to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        buf.write_u8(self.flags)?;
+        buf.write_u8(self.identifier.tunnel_type())?;
+        buf.write_u24::<BigEndian>(self.label)?;
+        self.identifier.encode(buf)
+    }
+
+    /// Returns whether the Leaf Information Required flag is set.
+    pub fn leaf_information_required(&self) -> bool {
+        self.flags & 0x01 == 0x01
+    }
+
+    /// Returns the 20-bit MPLS label value.
+    ///
+    /// ```
+    /// use bgp_rs::{PMSITunnel, PMSITunnelIdentifier};
+    ///
+    /// let tunnel = PMSITunnel {
+    ///     flags: 0,
+    ///     label: 0x00_3e_81, // Label 1000, bottom of stack
+    ///     identifier: PMSITunnelIdentifier::IngressReplication("10.0.0.1".parse().unwrap()),
+    /// };
+    /// assert_eq!(tunnel.mpls_label(), 1000);
+    /// ```
+    pub fn mpls_label(&self) -> u32 {
+        self.label >> 4
+    }
+}
+
+/// The Tunnel Identifier of a PMSI Tunnel attribute, by Tunnel Type.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PMSITunnelIdentifier {
+    /// 0 - No tunnel information present
+    NoTunnelInformation,
+
+    /// 1 - RSVP-TE P2MP LSP, identified by its SESSION Object
+    RsvpTeP2mp {
+        /// Extended Tunnel ID
+        extended_tunnel_id: Ipv4Addr,
+        /// Tunnel ID
+        tunnel_id: u16,
+        /// P2MP ID
+        p2mp_id: u32,
+    },
+
+    /// 2 - mLDP P2MP LSP, identified by its P2MP FEC Element
+    MldpP2mp(Vec<u8>),
+
+    /// 3 - PIM-SSM Tree, identified by (Sender Address, P-Multicast Group)
+    PimSsm((IpAddr, IpAddr)),
+
+    /// 4 - PIM-SM Tree, identified by (Sender Address, P-Multicast Group)
+    PimSm((IpAddr, IpAddr)),
+
+    /// 5 - BIDIR-PIM Tree, identified by (Sender Address, P-Multicast Group)
+    BidirPim((IpAddr, IpAddr)),
+
+    /// 6 - Ingress Replication, identified by the unicast tunnel endpoint
+    IngressReplication(IpAddr),
+
+    /// 7 - mLDP MP2MP LSP, identified by its MP2MP FEC Element
+    MldpMp2mp(Vec<u8>),
+
+    /// Unknown (or unsupported) tunnel type
+    Unknown {
+        /// The Tunnel Type
+        tunnel_type: u8,
+
+        /// The Tunnel Identifier
+        value: Vec<u8>,
+    },
+}
+
+impl PMSITunnelIdentifier {
+    fn parse(
+        stream: &mut impl Read,
+        tunnel_type: u8,
+        length: u16,
+    ) -> Result<PMSITunnelIdentifier, Error> {
+        let mut value = vec![0; usize::from(length)];
+        stream.read_exact(&mut value)?;
+
+        let mut cursor = Cursor::new(&value);
+        let identifier = match (tunnel_type, length) {
+            (0, 0) => PMSITunnelIdentifier::NoTunnelInformation,
+            (1, 12) => PMSITunnelIdentifier::RsvpTeP2mp {
+                extended_tunnel_id: Ipv4Addr::from(cursor.read_u32::<BigEndian>()?),
+                tunnel_id: {
+                    let _reserved = cursor.read_u16::<BigEndian>()?;
+                    cursor.read_u16::<BigEndian>()?
+                },
+                p2mp_id: cursor.read_u32::<BigEndian>()?,
+            },
+            (2, _) => PMSITunnelIdentifier::MldpP2mp(value),
+            (3, 8) | (3, 32) => PMSITunnelIdentifier::PimSsm(read_address_pair(&mut cursor)?),
+            (4, 8) | (4, 32) => PMSITunnelIdentifier::PimSm(read_address_pair(&mut cursor)?),
+            (5, 8) | (5, 32) => PMSITunnelIdentifier::BidirPim(read_address_pair(&mut cursor)?),
+            (6, 4) => PMSITunnelIdentifier::IngressReplication(IpAddr::V4(Ipv4Addr::from(
+                cursor.read_u32::<BigEndian>()?,
+            ))),
+            (6, 16) => PMSITunnelIdentifier::IngressReplication(IpAddr::V6(Ipv6Addr::from(
+                cursor.read_u128::<BigEndian>()?,
+            ))),
+            (7, _) => PMSITunnelIdentifier::MldpMp2mp(value),
+            (tunnel_type, _) => PMSITunnelIdentifier::Unknown { tunnel_type, value },
+        };
+        Ok(identifier)
+    }
+
+    /// Encode the Tunnel Identifier to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        match self {
+            PMSITunnelIdentifier::NoTunnelInformation => Ok(()),
+            PMSITunnelIdentifier::RsvpTeP2mp {
+                extended_tunnel_id,
+                tunnel_id,
+                p2mp_id,
+            } => {
+                buf.write_all(&extended_tunnel_id.octets())?;
+                buf.write_u16::<BigEndian>(0)?; // Reserved
+                buf.write_u16::<BigEndian>(*tunnel_id)?;
+                buf.write_u32::<BigEndian>(*p2mp_id)
+            }
+            PMSITunnelIdentifier::PimSsm((sender, group))
+            | PMSITunnelIdentifier::PimSm((sender, group))
+            | PMSITunnelIdentifier::BidirPim((sender, group)) => {
+                match (sender, group) {
+                    (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => (),
+                    _ => {
+                        return Err(Error::new(
+                            ErrorKind::Other,
+                            format!(
+                                "PMSI Tunnel sender {} and group {} must be of the same family",
+                                sender, group
+                            ),
+                        ));
                     }
-                    _ => {}
-                };
+                }
+                write_address(buf, sender)?;
+                write_address(buf, group)
             }
+            PMSITunnelIdentifier::IngressReplication(endpoint) => write_address(buf, endpoint),
+            PMSITunnelIdentifier::MldpP2mp(value)
+            | PMSITunnelIdentifier::MldpMp2mp(value)
+            | PMSITunnelIdentifier::Unknown { value, .. } => buf.write_all(value),
+        }
+    }
+
+    /// Returns the Tunnel Type code for this identifier.
+    pub fn tunnel_type(&self) -> u8 {
+        match self {
+            PMSITunnelIdentifier::NoTunnelInformation => 0,
+            PMSITunnelIdentifier::RsvpTeP2mp { .. } => 1,
+            PMSITunnelIdentifier::MldpP2mp(_) => 2,
+            PMSITunnelIdentifier::PimSsm(_) => 3,
+            PMSITunnelIdentifier::PimSm(_) => 4,
+            PMSITunnelIdentifier::BidirPim(_) => 5,
+            PMSITunnelIdentifier::IngressReplication(_) => 6,
+            PMSITunnelIdentifier::MldpMp2mp(_) => 7,
+            PMSITunnelIdentifier::Unknown { tunnel_type, .. } => *tunnel_type,
         }
+    }
+}
 
-        Err(Error::new(
-            ErrorKind::Other,
-            "Invalid AS_PATH length detected",
+// Read a (Sender Address, P-Multicast Group) pair of either IPv4 or IPv6 addresses
+fn read_address_pair(cursor: &mut Cursor<&Vec<u8>>) -> Result<(IpAddr, IpAddr), Error> {
+    if cursor.get_ref().len() == 8 {
+        Ok((
+            IpAddr::V4(Ipv4Addr::from(cursor.read_u32::<BigEndian>()?)),
+            IpAddr::V4(Ipv4Addr::from(cursor.read_u32::<BigEndian>()?)),
+        ))
+    } else {
+        Ok((
+            IpAddr::V6(Ipv6Addr::from(cursor.read_u128::<BigEndian>()?)),
+            IpAddr::V6(Ipv6Addr::from(cursor.read_u128::<BigEndian>()?)),
         ))
     }
+}
 
-    fn parse_u16_segments(stream: &mut impl Read, length: u16) -> Result<Vec<Segment>, Error> {
-        let mut segments: Vec<Segment> = Vec::with_capacity(1);
+fn write_address(buf: &mut impl Write, address: &IpAddr) -> Result<(), Error> {
+    match address {
+        IpAddr::V4(addr) => buf.write_all(&addr.octets()),
+        IpAddr::V6(addr) => buf.write_all(&addr.octets()),
+    }
+}
 
-        // While there are multiple AS_PATH segments, parse the segments.
-        let mut size = length;
-        while size != 0 {
-            // The type of a segment, either AS_SET or AS_SEQUENCE.
-            let segment_type = stream.read_u8()?;
+/// The TLVs of a BGP-LS attribute, describing the node, link or prefix of BGP-LS NLRI.
+/// Defined in [RFC7752](http://www.iana.org/go/rfc7752).
+///
+/// ```
+/// use bgp_rs::{LinkStateAttribute, LinkStateTlv, SegmentId};
+///
+/// let link_state = LinkStateAttribute {
+///     tlvs: vec![
+///         LinkStateTlv::AdjacencySid { flags: 0x30, weight: 0, sid: SegmentId::Label(24001) },
+///         LinkStateTlv::LinkDelay { anomalous: false, delay: 1500 },
+///     ],
+/// };
+/// let mut bytes = vec![];
+/// link_state.encode(&mut bytes).unwrap();
+/// assert_eq!(bytes.len(), 11 + 8);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkStateAttribute {
+    /// The TLVs contained in the attribute, in the order they were received.
+    pub tlvs: Vec<LinkStateTlv>,
+}
 
-            // The amount of ASN inside a segment.
-            let segment_length = stream.read_u8()?;
+impl LinkStateAttribute {
+    fn parse(stream: &mut impl Read, length: u16) -> Result<LinkStateAttribute, Error> {
+        let mut tlvs = Vec::with_capacity(4);
+        let mut remaining = length;
+        while remaining > 0 {
+            if remaining < 4 {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Bogus BGP-LS TLV header: {} bytes remaining", remaining),
+                ));
+            }
+            let tlv_type = stream.read_u16::<BigEndian>()?;
+            let tlv_length = stream.read_u16::<BigEndian>()?;
+            if tlv_length > remaining - 4 {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Bogus BGP-LS TLV length: {}", tlv_length),
+                ));
+            }
+            let mut value = vec![0; usize::from(tlv_length)];
+            stream.read_exact(&mut value)?;
+            // TLVs that can't be decoded are kept as they are
+            let tlv = LinkStateTlv::decode(tlv_type, &value)
+                .unwrap_or(LinkStateTlv::Unknown { tlv_type, value });
+            tlvs.push(tlv);
+            remaining -= 4 + tlv_length;
+        }
+        Ok(LinkStateAttribute { tlvs })
+    }
 
-            // Construct a Vec<u32> such that one interface be used when handling AS_PATHs.
-            let mut elements: Vec<u32> = Vec::with_capacity(usize::from(segment_length));
+    /// Encode BGP-LS attribute value to bytes
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        for tlv in &self.tlvs {
+            let mut value = Vec::with_capacity(8);
+            tlv.encode_value(&mut value)?;
+            buf.write_u16::<BigEndian>(tlv.tlv_type())?;
+            buf.write_u16::<BigEndian>(value.len() as u16)?;
+            buf.write_all(&value)?;
+        }
+        Ok(())
+    }
+}
 
-            // Parse the ASN as 16-bit ASN.
-            for _ in 0..segment_length {
-                elements.push(u32::from(stream.read_u16::<BigEndian>()?));
-            }
+/// A TLV held in a BGP-LS attribute.
+///
+/// Delays are in microseconds, loss in units of 0.000003% and bandwidths in bytes per second.
+/// Bandwidths are kept as the bits of their IEEE 754 floating point value, see `bandwidth()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStateTlv {
+    /// 1034 - SR Capabilities of a node, its SRGB ranges as (range size, first SID).
+    /// Defined in [RFC9085](http://www.iana.org/go/rfc9085).
+    SrCapabilities {
+        /// The MPLS-IPv4 (I) & MPLS-IPv6 (V) flags.
+        flags: u8,
+        /// The Segment Routing Global Blocks.
+        ranges: Vec<(u32, SegmentId)>,
+    },
+
+    /// 1099 - Adjacency SID of a link. Defined in [RFC9085](http://www.iana.org/go/rfc9085).
+    AdjacencySid {
+        /// The IGP specific flags.
+        flags: u8,
+        /// The weight used for load-balancing.
+        weight: u8,
+        /// The label or index of the SID.
+        sid: SegmentId,
+    },
+
+    /// 1114 - Unidirectional Link Delay. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    LinkDelay {
+        /// Whether the measured value exceeds its configured threshold.
+        anomalous: bool,
+        /// The average delay.
+        delay: u32,
+    },
+
+    /// 1115 - Min/Max Unidirectional Link Delay.
+    /// Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    MinMaxLinkDelay {
+        /// Whether the measured values exceed their configured threshold.
+        anomalous: bool,
+        /// The minimum delay.
+        min_delay: u32,
+        /// The maximum delay.
+        max_delay: u32,
+    },
+
+    /// 1116 - Unidirectional Delay Variation. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    DelayVariation(u32),
+
+    /// 1117 - Unidirectional Link Loss. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    LinkLoss {
+        /// Whether the measured value exceeds its configured threshold.
+        anomalous: bool,
+        /// The packet loss.
+        loss: u32,
+    },
+
+    /// 1118 - Unidirectional Residual Bandwidth. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    ResidualBandwidth(u32),
+
+    /// 1119 - Unidirectional Available Bandwidth. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    AvailableBandwidth(u32),
+
+    /// 1120 - Unidirectional Utilized Bandwidth. Defined in [RFC8571](http://www.iana.org/go/rfc8571).
+    UtilizedBandwidth(u32),
+
+    /// 1158 - Prefix SID of a prefix. Defined in [RFC9085](http://www.iana.org/go/rfc9085).
+    PrefixSid {
+        /// The IGP specific flags.
+        flags: u8,
+        /// The algorithm the SID is associated with.
+        algorithm: u8,
+        /// The label or index of the SID.
+        sid: SegmentId,
+    },
+
+    /// Unknown (or unsupported) TLV
+    Unknown {
+        /// The type of the TLV.
+        tlv_type: u16,
+
+        /// The value of the TLV.
+        value: Vec<u8>,
+    },
+}
 
-            match segment_type {
-                1 => segments.push(Segment::AS_SET(elements)),
-                2 => segments.push(Segment::AS_SEQUENCE(elements)),
-                x => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        format!("Unknown AS_PATH (2 byte) segment type found: {}", x),
-                    ));
-                }
-            }
+/// The SID of a Segment Routing TLV, an MPLS label or an index into the SRGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentId {
+    /// A 20-bit MPLS label, encoded in 3 bytes.
+    Label(u32),
 
-            size -= 2 + (u16::from(segment_length) * 2);
+    /// An index, encoded in 4 bytes.
+    Index(u32),
+}
+
+impl SegmentId {
+    fn parse(stream: &mut impl Read, length: usize) -> Result<SegmentId, Error> {
+        match length {
+            3 => Ok(SegmentId::Label(stream.read_u24::<BigEndian>()? & 0xf_ffff)),
+            4 => Ok(SegmentId::Index(stream.read_u32::<BigEndian>()?)),
+            _ => Err(Error::new(
+                ErrorKind::Other,
+                format!("Bogus SID length: {}", length),
+            )),
         }
+    }
 
-        Ok(segments)
+    fn encode(self, buf: &mut impl Write) -> Result<(), Error> {
+        match self {
+            SegmentId::Label(label) => buf.write_u24::<BigEndian>(label),
+            SegmentId::Index(index) => buf.write_u32::<BigEndian>(index),
+        }
     }
 
-    fn parse_u32_segments(stream: &mut impl Read, length: u16) -> Result<Vec<Segment>, Error> {
-        let mut segments: Vec<Segment> = Vec::with_capacity(1);
+    fn length(self) -> u16 {
+        match self {
+            SegmentId::Label(_) => 3,
+            SegmentId::Index(_) => 4,
+        }
+    }
+}
 
-        // While there are multiple AS_PATH segments, parse the segments.
-        let mut size: i32 = i32::from(length);
+// The SID/Label sub-TLV of the SR Capabilities TLV
+const SID_LABEL_SUB_TLV: u16 = 1161;
 
-        while size != 0 {
-            // The type of a segment, either AS_SET or AS_SEQUENCE.
-            let segment_type = stream.read_u8()?;
+// Delays & loss are 24-bit values, following a byte holding the Anomalous flag
+fn read_flagged_u24(cursor: &mut Cursor<&[u8]>) -> Result<(bool, u32), Error> {
+    let value = cursor.read_u32::<BigEndian>()?;
+    Ok((value & 0x8000_0000 != 0, value & 0xff_ffff))
+}
 
-            // The amount of ASN inside a segment.
-            let segment_length = stream.read_u8()?;
+fn write_flagged_u24(buf: &mut impl Write, anomalous: bool, value: u32) -> Result<(), Error> {
+    let flag = if anomalous { 0x8000_0000 } else { 0 };
+    buf.write_u32::<BigEndian>(flag | (value & 0xff_ffff))
+}
 
-            // Construct a Vec<u32> such that one interface be used when handling AS_PATHs.
-            let mut elements: Vec<u32> = Vec::with_capacity(usize::from(segment_length));
+impl LinkStateTlv {
+    /// The type code of the TLV.
+    pub fn tlv_type(&self) -> u16 {
+        match self {
+            LinkStateTlv::SrCapabilities { .. } => 1034,
+            LinkStateTlv::AdjacencySid { .. } => 1099,
+            LinkStateTlv::LinkDelay { .. } => 1114,
+            LinkStateTlv::MinMaxLinkDelay { .. } => 1115,
+            LinkStateTlv::DelayVariation(_) => 1116,
+            LinkStateTlv::LinkLoss { .. } => 1117,
+            LinkStateTlv::ResidualBandwidth(_) => 1118,
+            LinkStateTlv::AvailableBandwidth(_) => 1119,
+            LinkStateTlv::UtilizedBandwidth(_) => 1120,
+            LinkStateTlv::PrefixSid { .. } => 1158,
+            LinkStateTlv::Unknown { tlv_type, .. } => *tlv_type,
+        }
+    }
 
-            // Parse the ASN as 32-bit ASN.
-            for _ in 0..segment_length {
-                elements.push(stream.read_u32::<BigEndian>()?);
-            }
+    /// The bandwidth in bytes per second of the Residual, Available & Utilized Bandwidth TLVs.
+    pub fn bandwidth(&self) -> Option<f32> {
+        match self {
+            LinkStateTlv::ResidualBandwidth(bandwidth)
+            | LinkStateTlv::AvailableBandwidth(bandwidth)
+            | LinkStateTlv::UtilizedBandwidth(bandwidth) => Some(f32::from_bits(*bandwidth)),
+            _ => None,
+        }
+    }
 
-            match segment_type {
-                1 => segments.push(Segment::AS_SET(elements)),
-                2 => segments.push(Segment::AS_SEQUENCE(elements)),
-                x => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        format!("Unknown AS_PATH (4 byte) segment type found: {}", x),
+    fn decode(tlv_type: u16, value: &[u8]) -> Result<LinkStateTlv, Error> {
+        let mut cursor = Cursor::new(value);
+        let tlv = match (tlv_type, value.len()) {
+            (1034, _) => {
+                let flags = cursor.read_u8()?;
+                let _reserved = cursor.read_u8()?;
+                let mut ranges = vec![];
+                while (cursor.position() as usize) < value.len() {
+                    let range_size = cursor.read_u24::<BigEndian>()?;
+                    if cursor.read_u16::<BigEndian>()? != SID_LABEL_SUB_TLV {
+                        return Err(Error::new(ErrorKind::Other, "Missing SID/Label sub-TLV"));
+                    }
+                    let length = cursor.read_u16::<BigEndian>()?;
+                    ranges.push((
+                        range_size,
+                        SegmentId::parse(&mut cursor, usize::from(length))?,
                     ));
                 }
+                LinkStateTlv::SrCapabilities { flags, ranges }
             }
-
-            size -= 2 + i32::from(u16::from(segment_length) * 4);
+            (1099, length) | (1158, length) if length >= 4 => {
+                let flags = cursor.read_u8()?;
+                let second = cursor.read_u8()?;
+                let _reserved = cursor.read_u16::<BigEndian>()?;
+                let sid = SegmentId::parse(&mut cursor, length - 4)?;
+                if tlv_type == 1099 {
+                    LinkStateTlv::AdjacencySid {
+                        flags,
+                        weight: second,
+                        sid,
+                    }
+                } else {
+                    LinkStateTlv::PrefixSid {
+                        flags,
+                        algorithm: second,
+                        sid,
+                    }
+                }
+            }
+            (1114, 4) => {
+                let (anomalous, delay) = read_flagged_u24(&mut cursor)?;
+                LinkStateTlv::LinkDelay { anomalous, delay }
+            }
+            (1115, 8) => {
+                let (anomalous, min_delay) = read_flagged_u24(&mut cursor)?;
+                let (_, max_delay) = read_flagged_u24(&mut cursor)?;
+                LinkStateTlv::MinMaxLinkDelay {
+                    anomalous,
+                    min_delay,
+                    max_delay,
+                }
+            }
+            (1116, 4) => LinkStateTlv::DelayVariation(read_flagged_u24(&mut cursor)?.1),
+            (1117, 4) => {
+                let (anomalous, loss) = read_flagged_u24(&mut cursor)?;
+                LinkStateTlv::LinkLoss { anomalous, loss }
+            }
+            (1118, 4) => LinkStateTlv::ResidualBandwidth(cursor.read_u32::<BigEndian>()?),
+            (1119, 4) => LinkStateTlv::AvailableBandwidth(cursor.read_u32::<BigEndian>()?),
+            (1120, 4) => LinkStateTlv::UtilizedBandwidth(cursor.read_u32::<BigEndian>()?),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Unsupported BGP-LS TLV {}", tlv_type),
+                ));
+            }
+        };
+        if cursor.position() as usize != value.len() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Bogus BGP-LS TLV {} length: {}", tlv_type, value.len()),
+            ));
         }
+        Ok(tlv)
+    }
 
-        Ok(segments)
+    fn encode_value(&self, buf: &mut impl Write) -> Result<(), Error> {
+        match self {
+            LinkStateTlv::SrCapabilities { flags, ranges } => {
+                buf.write_u8(*flags)?;
+                buf.write_u8(0)?;
+                for (range_size, sid) in ranges {
+                    buf.write_u24::<BigEndian>(*range_size)?;
+                    buf.write_u16::<BigEndian>(SID_LABEL_SUB_TLV)?;
+                    buf.write_u16::<BigEndian>(sid.length())?;
+                    sid.encode(buf)?;
+                }
+                Ok(())
+            }
+            LinkStateTlv::AdjacencySid {
+                flags,
+                weight: second,
+                sid,
+            }
+            | LinkStateTlv::PrefixSid {
+                flags,
+                algorithm: second,
+                sid,
+            } => {
+                buf.write_u8(*flags)?;
+                buf.write_u8(*second)?;
+                buf.write_u16::<BigEndian>(0)?;
+                sid.encode(buf)
+            }
+            LinkStateTlv::LinkDelay { anomalous, delay } => {
+                write_flagged_u24(buf, *anomalous, *delay)
+            }
+            LinkStateTlv::MinMaxLinkDelay {
+                anomalous,
+                min_delay,
+                max_delay,
+            } => {
+                write_flagged_u24(buf, *anomalous, *min_delay)?;
+                write_flagged_u24(buf, false, *max_delay)
+            }
+            LinkStateTlv::DelayVariation(variation) => write_flagged_u24(buf, false, *variation),
+            LinkStateTlv::LinkLoss { anomalous, loss } => write_flagged_u24(buf, *anomalous, *loss),
+            LinkStateTlv::ResidualBandwidth(bandwidth)
+            | LinkStateTlv::AvailableBandwidth(bandwidth)
+            | LinkStateTlv::UtilizedBandwidth(bandwidth) => buf.write_u32::<BigEndian>(*bandwidth),
+            LinkStateTlv::Unknown { value, .. } => buf.write_all(value),
+        }
     }
 }
 
@@ -869,7 +2411,6 @@ mod tests {
     // assert_eq!(addr, make_prefix!("10.10.10.10"));
     // ```
     #[allow(unused_macros)]
-    #[macro_use]
     macro_rules! make_prefix {
         ($prefix:tt, $mask_len:expr) => {{
             let _prefix: Prefix = ($prefix.parse().unwrap(), $mask_len).into();
@@ -934,47 +2475,39 @@ mod tests {
             ),
             (PathAttribute::COMMUNITY(vec![100, 9000008]), None),
             (
-                PathAttribute::MP_REACH_NLRI(MPReachNLRI {
-                    afi: AFI::IPV4,
-                    safi: SAFI::Unicast,
-                    next_hop: vec![1, 1, 1, 1],
-                    announced_routes: vec![
+                PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                    AFI::IPV4,
+                    SAFI::Unicast,
+                    "1.1.1.1".parse().unwrap(),
+                    vec![
                         NLRIEncoding::IP(make_prefix!("10.10.10.0", 24)),
                         NLRIEncoding::IP(make_prefix!("20.20.20.0", 24)),
                     ],
-                }),
+                ))),
                 None,
             ),
             (
-                PathAttribute::MP_REACH_NLRI(MPReachNLRI {
-                    afi: AFI::IPV6,
-                    safi: SAFI::Unicast,
-                    next_hop: "3001::1"
-                        .parse::<std::net::Ipv6Addr>()
-                        .unwrap()
-                        .octets()
-                        .to_vec(),
-                    announced_routes: vec![
+                PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                    AFI::IPV6,
+                    SAFI::Unicast,
+                    "3001::1".parse().unwrap(),
+                    vec![
                         NLRIEncoding::IP(make_prefix!("2620:10:20::", 64)),
                         NLRIEncoding::IP(make_prefix!("2620:10:20::", 64)),
                     ],
-                }),
+                ))),
                 None,
             ),
             (
-                PathAttribute::MP_REACH_NLRI(MPReachNLRI {
-                    afi: AFI::IPV6,
-                    safi: SAFI::Unicast,
-                    next_hop: "3001::1"
-                        .parse::<std::net::Ipv6Addr>()
-                        .unwrap()
-                        .octets()
-                        .to_vec(),
-                    announced_routes: vec![
+                PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                    AFI::IPV6,
+                    SAFI::Unicast,
+                    "3001::1".parse().unwrap(),
+                    vec![
                         NLRIEncoding::IP_WITH_PATH_ID((make_prefix!("2620:10:20::", 64), 10)),
                         NLRIEncoding::IP_WITH_PATH_ID((make_prefix!("2620:10:20::", 64), 20)),
                     ],
-                }),
+                ))),
                 Some(Capabilities::from_parameters(vec![
                     OpenParameter::Capabilities(vec![OpenCapability::AddPath(vec![(
                         AFI::IPV4,
@@ -984,25 +2517,25 @@ mod tests {
                 ])),
             ),
             (
-                PathAttribute::MP_UNREACH_NLRI(MPUnreachNLRI {
+                PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
                     afi: AFI::IPV4,
                     safi: SAFI::Unicast,
                     withdrawn_routes: vec![
                         NLRIEncoding::IP(make_prefix!("10.10.10.0", 24)),
                         NLRIEncoding::IP(make_prefix!("20.20.20.0", 24)),
                     ],
-                }),
+                })),
                 None,
             ),
             (
-                PathAttribute::MP_UNREACH_NLRI(MPUnreachNLRI {
+                PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
                     afi: AFI::IPV6,
                     safi: SAFI::Unicast,
                     withdrawn_routes: vec![
                         NLRIEncoding::IP(make_prefix!("2620:10:20::", 64)),
                         NLRIEncoding::IP(make_prefix!("2620:10:20::", 64)),
                     ],
-                }),
+                })),
                 None,
             ),
             (
@@ -1024,6 +2557,102 @@ mod tests {
                 }),
                 None,
             ),
+            (
+                PathAttribute::ORIGINATOR_ID("10.0.15.1".parse().unwrap()),
+                None,
+            ),
+            (
+                PathAttribute::CLUSTER_LIST(vec![
+                    "10.0.15.4".parse().unwrap(),
+                    "192.168.0.1".parse().unwrap(),
+                ]),
+                None,
+            ),
+            (PathAttribute::OTC(65000), None),
+            (PathAttribute::OTC(4200000000), None),
+            (
+                PathAttribute::AIGP(AccumulatedIGP {
+                    tlvs: vec![AIGPTlv::Metric(3200000000)],
+                }),
+                None,
+            ),
+            (
+                PathAttribute::ATTR_SET(Box::new((
+                    65000,
+                    vec![
+                        PathAttribute::ORIGIN(Origin::IGP),
+                        PathAttribute::AS_PATH(ASPath {
+                            segments: vec![Segment::AS_SEQUENCE(vec![100, 200])],
+                        }),
+                        PathAttribute::LOCAL_PREF(100),
+                        PathAttribute::COMMUNITY(vec![100, 9000008]),
+                    ],
+                ))),
+                None,
+            ),
+            (
+                PathAttribute::ATTR_SET(Box::new((4200000000, vec![]))),
+                None,
+            ),
+            (
+                PathAttribute::AIGP(AccumulatedIGP {
+                    tlvs: vec![
+                        AIGPTlv::Metric(10),
+                        AIGPTlv::Unknown {
+                            tlv_type: 200,
+                            value: vec![1, 2, 3],
+                        },
+                    ],
+                }),
+                None,
+            ),
+            (
+                PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel {
+                    flags: 0,
+                    label: 1000 << 4,
+                    identifier: PMSITunnelIdentifier::IngressReplication(make_prefix!("10.0.0.1")),
+                })),
+                None,
+            ),
+            (
+                PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel {
+                    flags: 1,
+                    label: 0,
+                    identifier: PMSITunnelIdentifier::RsvpTeP2mp {
+                        extended_tunnel_id: "10.0.0.1".parse().unwrap(),
+                        tunnel_id: 100,
+                        p2mp_id: 200,
+                    },
+                })),
+                None,
+            ),
+            (
+                PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel {
+                    flags: 0,
+                    label: 0,
+                    identifier: PMSITunnelIdentifier::PimSsm((
+                        make_prefix!("3001::1"),
+                        make_prefix!("ff3e::1"),
+                    )),
+                })),
+                None,
+            ),
+            (
+                PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel {
+                    flags: 0,
+                    label: 0,
+                    identifier: PMSITunnelIdentifier::MldpP2mp(vec![6, 0, 1, 4, 10, 0, 0, 1]),
+                })),
+                None,
+            ),
+            (
+                PathAttribute::PMSI_TUNNEL(Box::new(PMSITunnel {
+                    flags: 0,
+                    label: 0,
+                    identifier: PMSITunnelIdentifier::NoTunnelInformation,
+                })),
+                None,
+            ),
             // Not yet implemented
             // (PathAttribute::AS_PATHLIMIT((6, 65000)), None),
             // (
@@ -1042,6 +2671,12 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_path_attribute_size() {
+        assert_eq!(std::mem::size_of::<PathAttribute>(), 32);
+    }
+
     #[test]
     fn test_unknown_attribute() {
         let attr_data: Vec<u8> = vec![0x80, 190 /* not valid */, 4, 0, 0, 0, 0];
@@ -1050,6 +2685,168 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_pmsi_tunnel_ingress_replication() {
+        let attr_data: Vec<u8> = vec![0xc0, 22, 9, 0, 6, 0x00, 0x3e, 0x81, 10, 0, 0, 1];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap();
+        match res {
+            PathAttribute::PMSI_TUNNEL(tunnel) => {
+                assert!(!tunnel.leaf_information_required());
+                assert_eq!(tunnel.mpls_label(), 1000);
+                assert_eq!(tunnel.identifier.tunnel_type(), 6);
+                match tunnel.identifier {
+                    PMSITunnelIdentifier::IngressReplication(endpoint) => {
+                        assert_eq!(endpoint, make_prefix!("10.0.0.1"))
+                    }
+                    _ => panic!("Expected Ingress Replication"),
+                }
+            }
+            _ => panic!("Expected PMSI_TUNNEL"),
+        }
+    }
+
+    #[test]
+    fn test_pmsi_tunnel_bad_length() {
+        let attr_data: Vec<u8> = vec![0xc0, 22, 3, 0, 6, 0];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_aigp_bad_tlv_length() {
+        // TLV length extends past the attribute
+        let attr_data: Vec<u8> = vec![0x80, 26, 11, 1, 0, 12, 0, 0, 0, 0, 0, 0, 0, 10];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_attr_set_bad_length() {
+        let attr_data: Vec<u8> = vec![0xc0, 128, 2, 0, 1];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default());
+        assert!(res.is_err());
+
+        // The nested attribute claims more bytes than ATTR_SET holds
+        let attr_data: Vec<u8> = vec![0xc0, 128, 7, 0, 0, 0xfd, 0xe8, 0x40, 5, 4];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_bgp_ls_sr_and_te_tlvs() {
+        #[rustfmt::skip]
+        let attr_data: Vec<u8> = vec![
+            0x80, 29, 75,
+            // SR Capabilities: MPLS-IPv4, SRGB 16000-23999
+            0x04, 0x0a, 0, 12, 0x80, 0, 0, 0x1f, 0x40, 0x04, 0x89, 0, 3, 0, 0x3e, 0x80,
+            // Prefix SID: index 100
+            0x04, 0x86, 0, 8, 0, 0, 0, 0, 0, 0, 0, 100,
+            // Adjacency SID: label 24001
+            0x04, 0x4b, 0, 7, 0x30, 0, 0, 0, 0, 0x5d, 0xc1,
+            // Link delay 1500us, anomalous
+            0x04, 0x5a, 0, 4, 0x80, 0, 0x05, 0xdc,
+            // Min/Max link delay 1000us - 2000us
+            0x04, 0x5b, 0, 8, 0, 0, 0x03, 0xe8, 0, 0, 0x07, 0xd0,
+            // Link loss
+            0x04, 0x5d, 0, 4, 0, 0, 0, 10,
+            // Utilized bandwidth 1.0 bytes/s
+            0x04, 0x60, 0, 4, 0x3f, 0x80, 0, 0,
+        ];
+        let mut buf = std::io::Cursor::new(attr_data.clone());
+        let link_state = match PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap() {
+            PathAttribute::BGP_LS(link_state) => link_state,
+            attribute => panic!("Unexpected {:?}", attribute),
+        };
+        assert_eq!(
+            link_state.tlvs,
+            vec![
+                LinkStateTlv::SrCapabilities {
+                    flags: 0x80,
+                    ranges: vec![(8000, SegmentId::Label(16000))],
+                },
+                LinkStateTlv::PrefixSid {
+                    flags: 0,
+                    algorithm: 0,
+                    sid: SegmentId::Index(100),
+                },
+                LinkStateTlv::AdjacencySid {
+                    flags: 0x30,
+                    weight: 0,
+                    sid: SegmentId::Label(24001),
+                },
+                LinkStateTlv::LinkDelay {
+                    anomalous: true,
+                    delay: 1500,
+                },
+                LinkStateTlv::MinMaxLinkDelay {
+                    anomalous: false,
+                    min_delay: 1000,
+                    max_delay: 2000,
+                },
+                LinkStateTlv::LinkLoss {
+                    anomalous: false,
+                    loss: 10,
+                },
+                LinkStateTlv::UtilizedBandwidth(1.0f32.to_bits()),
+            ]
+        );
+
+        let mut encoded = vec![];
+        PathAttribute::BGP_LS(link_state)
+            .encode(&mut encoded)
+            .unwrap();
+        assert_eq!(encoded, attr_data);
+    }
+
+    #[test]
+    fn test_bgp_ls_unknown_tlvs() {
+        // An unknown TLV & a link delay TLV of the wrong length are kept as they are
+        let attr_data: Vec<u8> = vec![
+            0x80, 29, 13, 0x04, 0x00, 0, 2, 0xab, 0xcd, 0x04, 0x5a, 0, 3, 0, 0x05, 0xdc,
+        ];
+        let mut buf = std::io::Cursor::new(attr_data.clone());
+        let attribute = PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap();
+        match &attribute {
+            PathAttribute::BGP_LS(link_state) => assert_eq!(
+                link_state.tlvs,
+                vec![
+                    LinkStateTlv::Unknown {
+                        tlv_type: 1024,
+                        value: vec![0xab, 0xcd],
+                    },
+                    LinkStateTlv::Unknown {
+                        tlv_type: 1114,
+                        value: vec![0, 0x05, 0xdc],
+                    },
+                ]
+            ),
+            attribute => panic!("Unexpected {:?}", attribute),
+        }
+        let mut encoded = vec![];
+        attribute.encode(&mut encoded).unwrap();
+        assert_eq!(encoded, attr_data);
+
+        // TLV length extends past the attribute
+        let attr_data: Vec<u8> = vec![0x80, 29, 6, 0x04, 0x00, 0, 3, 0xab, 0xcd];
+        let mut buf = std::io::Cursor::new(attr_data);
+        assert!(PathAttribute::parse(&mut buf, &Capabilities::default()).is_err());
+    }
+
+    #[test]
+    fn test_otc_bad_length() {
+        let attr_data: Vec<u8> = vec![0xc0, 35, 2, 0xfd, 0xe8];
+        let mut buf = std::io::Cursor::new(attr_data);
+        let res = PathAttribute::parse(&mut buf, &Capabilities::default());
+        assert!(res.is_err());
+        // The attribute is still consumed entirely
+        assert_eq!(buf.position(), 5);
+    }
+
     #[test]
     fn test_read_counter_overflow() {
         let data: Vec<u8> = (0..10).collect();
@@ -286,6 +286,12 @@ impl FlowspecFilter {
             // Prefix-based filters
             1 | 2 => {
                 let prefix_length = stream.read_u8()?;
+                if usize::from(prefix_length) > afi.empty_buffer()?.len() * 8 {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("Bogus Flowspec prefix length {}", prefix_length),
+                    ));
+                }
                 if afi == AFI::IPV6 {
                     let _prefix_offset = stream.read_u8()?;
                 }
@@ -309,7 +315,12 @@ impl FlowspecFilter {
                         1 => u32::from(stream.read_u8()?),
                         2 => u32::from(stream.read_u16::<BigEndian>()?),
                         4 => stream.read_u32::<BigEndian>()?,
-                        _ => unreachable!(),
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::Other,
+                                format!("Unsupported Flowspec value length {}", length),
+                            ))
+                        }
                     };
                     values.push((operator, value));
                     // Check for end-of-list bit
@@ -376,7 +387,7 @@ impl FlowspecFilter {
                 if prefix.protocol == AFI::IPV6 {
                     buf.write_u8(0)?; // Ipv6 Offset
                 }
-                buf.write_all(&prefix.masked_octets())?;
+                buf.write_all(prefix.masked_octets())?;
             }
             IpProtocol(values)
             | DestinationPort(values)
@@ -417,7 +428,7 @@ impl FlowspecFilter {
                     }
                     oper.set_length(1);
                     buf.write_u8(oper.bits())?;
-                    buf.write_u8(*value as u8)?;
+                    buf.write_u8(*value)?;
                 }
             }
             TcpFlags(values) => {
@@ -449,7 +460,7 @@ impl FlowspecFilter {
                         oper.unset_eol();
                     }
                     buf.write_u8(oper.bits())?;
-                    buf.write_u8(*value as u8)?;
+                    buf.write_u8(*value)?;
                 }
             }
         }
@@ -488,7 +499,7 @@ where
         name,
         value
             .iter()
-            .map(|(op, v)| format!("{}{}", op.to_string(), v))
+            .map(|(op, v)| format!("{}{}", op, v))
             .collect::<Vec<_>>()
             .join(", ")
     )
@@ -0,0 +1,138 @@
+use crate::*;
+
+use std::io::{Cursor, Error};
+
+use super::{
+    is_fatal_attribute_error, malformed_attribute_list, parse_announced_routes,
+    parse_withdrawn_routes, split_sections, too_many_attributes,
+};
+
+/// An UPDATE message of which the path attributes are only decoded when they are accessed.
+///
+/// Parsing only delimits the path attributes, borrowing their values from the message. This
+/// saves decoding the attributes (and NLRI) a consumer is not interested in, e.g. MP_REACH_NLRI
+/// when only the AS_PATHs of a full table are needed. Attributes are decoded again on every
+/// access, see `decode` to decode the whole message.
+///
+/// ```
+/// use bgp_rs::{Capabilities, Identifier, LazyUpdate, ParseOptions, PathAttribute};
+///
+/// #[rustfmt::skip]
+/// let message = vec![
+///     0, 0, // Withdrawn Routes Length
+///     0, 11, // Path Attribute Length
+///     0x40, 1, 1, 0, // ORIGIN IGP
+///     0x40, 2, 4, 2, 1, 0xfd, 0xe8, // AS_PATH 65000
+///     24, 10, 0, 0, // 10.0.0.0/24
+/// ];
+/// let (capabilities, options) = (Capabilities::default(), ParseOptions::default());
+/// let update = LazyUpdate::parse(&message, &capabilities, &options).unwrap();
+/// assert_eq!(update.codes().collect::<Vec<u8>>(), vec![1, 2]);
+/// match update.attribute(Identifier::AS_PATH) {
+///     Some(Ok(PathAttribute::AS_PATH(as_path))) => assert_eq!(as_path.origin(), Some(65000)),
+///     attribute => panic!("Unexpected attribute {:?}", attribute),
+/// }
+/// assert_eq!(update.announced_routes().unwrap().len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LazyUpdate<'a> {
+    message: &'a [u8],
+    withdrawn: &'a [u8],
+    attributes: Vec<RawPathAttributeRef<'a>>,
+    nlri: &'a [u8],
+    capabilities: &'a Capabilities,
+    options: &'a ParseOptions,
+}
+
+impl<'a> LazyUpdate<'a> {
+    /// Delimits the sections and the path attributes of the UPDATE message following its header.
+    ///
+    /// The message must be consistent with its lengths like for `Update::parse`, errors
+    /// decoding the attributes and the NLRI are only returned when they are accessed.
+    pub fn parse(
+        message: &'a [u8],
+        capabilities: &'a Capabilities,
+        options: &'a ParseOptions,
+    ) -> Result<LazyUpdate<'a>, Error> {
+        let (withdrawn, mut buffer, nlri) = split_sections(message)?;
+        let max_attributes = options.max_attributes;
+        let mut attributes = Vec::with_capacity(8);
+        while !buffer.is_empty() {
+            if attributes.len() >= max_attributes {
+                return Err(too_many_attributes(max_attributes));
+            }
+            attributes
+                .push(RawPathAttributeRef::parse(&mut buffer).map_err(malformed_attribute_list)?);
+        }
+        Ok(LazyUpdate {
+            message,
+            withdrawn,
+            attributes,
+            nlri,
+            capabilities,
+            options,
+        })
+    }
+
+    /// The type codes of the path attributes, in the order they were received.
+    pub fn codes<'b>(&'b self) -> impl Iterator<Item = u8> + 'b {
+        self.attributes.iter().map(|attribute| attribute.code)
+    }
+
+    /// Decodes the first path attribute of the given type, None if the message holds none.
+    pub fn attribute(&self, identifier: Identifier) -> Option<Result<PathAttribute, Error>> {
+        let code = u8::from(identifier);
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.code == code)
+            .map(|attribute| attribute.decode(self.capabilities, self.options))
+    }
+
+    /// Decodes all path attributes, skipping those that can not be decoded like `Update::parse`.
+    pub fn attributes(&self) -> Result<Vec<PathAttribute>, Error> {
+        let mut attributes = Vec::with_capacity(self.attributes.len());
+        for attribute in &self.attributes {
+            match attribute.decode(self.capabilities, self.options) {
+                Ok(attribute) => attributes.push(attribute),
+                Err(e) if is_fatal_attribute_error(&e) => return Err(e),
+                Err(_e) => {
+                    trace_event!(debug, code = attribute.code, error = %_e, "Skipped attribute");
+                }
+            }
+        }
+        Ok(attributes)
+    }
+
+    /// Decodes the routes of the Withdrawn Routes section.
+    pub fn withdrawn_routes(&self) -> Result<Vec<NLRIEncoding>, Error> {
+        parse_withdrawn_routes(
+            self.withdrawn,
+            self.capabilities,
+            self.options,
+            false,
+            &mut vec![],
+        )
+    }
+
+    /// Decodes the routes of the NLRI section.
+    pub fn announced_routes(&self) -> Result<Vec<NLRIEncoding>, Error> {
+        parse_announced_routes(
+            self.nlri,
+            self.capabilities,
+            self.options,
+            false,
+            &mut vec![],
+        )
+    }
+
+    /// Decodes the whole message, see `Update::parse`.
+    pub fn decode(&self) -> Result<Update, Error> {
+        let header = Header::new(MessageType::Update, self.message.len() as u16 + 19);
+        Update::parse_with_options(
+            &header,
+            &mut Cursor::new(self.message),
+            self.capabilities,
+            self.options,
+        )
+    }
+}
@@ -4,6 +4,12 @@ pub use crate::attributes::*;
 /// Contains the implementation of BGP NLRI.
 pub mod nlri;
 pub use crate::nlri::*;
+/// Contains the implementation of MVPN NLRI.
+pub mod mvpn;
+pub use crate::mvpn::*;
+/// Contains the lazily decoded UPDATE message.
+pub mod lazy;
+pub use crate::lazy::*;
 #[cfg(feature = "flowspec")]
 /// Contains the implementation of Flowspec attributes
 pub mod flowspec;
@@ -12,9 +18,13 @@ pub use crate::flowspec::*;
 
 use crate::*;
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
 use std::io::{Cursor, Error, Read};
 use std::net::IpAddr;
+use std::str::FromStr;
 
 /// Represents a BGP Update message.
 #[derive(Clone, Debug)]
@@ -27,203 +37,2047 @@ pub struct Update {
 
     /// A collection of routes that are announced by the peer.
     pub announced_routes: Vec<NLRIEncoding>,
+
+    // The path attributes as they were received when parsing with
+    // `ParseOptions::preserve_wire_format`, and the attributes decoded from them
+    raw_attributes: Vec<RawPathAttribute>,
+    received_attributes: Vec<PathAttribute>,
+}
+
+// The encoding of the received attributes is not part of the message
+impl PartialEq for Update {
+    fn eq(&self, other: &Update) -> bool {
+        self.withdrawn_routes == other.withdrawn_routes
+            && self.attributes == other.attributes
+            && self.announced_routes == other.announced_routes
+    }
 }
 
+impl Eq for Update {}
+
 impl Update {
-    /// docs
+    /// Creates an UPDATE message withdrawing & announcing the given routes.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, Origin, PathAttribute, Update};
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![
+    ///         PathAttribute::ORIGIN(Origin::IGP),
+    ///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+    ///     ],
+    ///     vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    /// );
+    /// assert!(update.is_announcement());
+    /// ```
+    pub fn new(
+        withdrawn_routes: Vec<NLRIEncoding>,
+        attributes: Vec<PathAttribute>,
+        announced_routes: Vec<NLRIEncoding>,
+    ) -> Update {
+        Update {
+            withdrawn_routes,
+            attributes,
+            announced_routes,
+            raw_attributes: vec![],
+            received_attributes: vec![],
+        }
+    }
+
+    /// Parses an UPDATE message following its header.
+    ///
+    /// The message must be consistent with the length of the header: sections that exceed it
+    /// are a Malformed Attribute List, NLRI that do not fill their section exactly an Invalid
+    /// Network Field, and path attributes holding bytes after their value an Attribute Length
+    /// Error (see `ToNotification`).
     pub fn parse(
         header: &Header,
         stream: &mut impl Read,
         capabilities: &Capabilities,
     ) -> Result<Update, Error> {
+        Update::parse_with_options(header, stream, capabilities, &ParseOptions::default())
+    }
+
+    /// Parses an UPDATE message like `parse`, with the given settings of the parser (e.g. its
+    /// limits or registered decoders).
+    pub fn parse_with_options(
+        header: &Header,
+        stream: &mut impl Read,
+        capabilities: &Capabilities,
+        options: &ParseOptions,
+    ) -> Result<Update, Error> {
+        let (update, _warnings) =
+            Update::parse_inner(header, stream, capabilities, options, false)?;
+        Ok(update)
+    }
+
+    /// Parses an UPDATE message like `parse`, but continues on recoverable problems.
+    ///
+    /// Malformed path attributes are skipped and NLRI sections are truncated at the first
+    /// malformed prefix, each reported as a `ParseWarning`. Errors are still returned when
+    /// the message itself can not be delimited (e.g. bogus lengths). See `Reader::read_lossy`
+    /// to parse with other `ParseOptions`.
+    ///
+    /// ```
+    /// use bgp_rs::{Capabilities, Header, MessageType, ParseWarning, Update};
+    ///
+    /// #[rustfmt::skip]
+    /// let data = vec![
+    ///     0, 2, // Withdrawn Routes Length
+    ///     24, 10, // A truncated prefix
+    ///     0, 4, // Path Attribute Length
+    ///     0x40, 1, 1, 5, // ORIGIN with an invalid value
+    ///     24, 10, 0, 0, // 10.0.0.0/24
+    /// ];
+    /// let header = Header::new(MessageType::Update, 19 + data.len() as u16);
+    /// let mut stream = std::io::Cursor::new(data);
+    /// let (update, warnings) =
+    ///     Update::parse_lossy(&header, &mut stream, &Capabilities::default()).unwrap();
+    /// assert!(update.withdrawn_routes.is_empty());
+    /// assert!(update.attributes.is_empty());
+    /// assert_eq!(update.announced_routes.len(), 1);
+    /// assert_eq!(warnings.len(), 2);
+    /// match &warnings[1] {
+    ///     ParseWarning::SkippedAttribute { code, .. } => assert_eq!(*code, 1),
+    ///     warning => panic!("Unexpected warning {}", warning),
+    /// }
+    /// ```
+    pub fn parse_lossy(
+        header: &Header,
+        stream: &mut impl Read,
+        capabilities: &Capabilities,
+    ) -> Result<(Update, Vec<ParseWarning>), Error> {
+        Update::parse_inner(header, stream, capabilities, &ParseOptions::default(), true)
+    }
+
+    /// Decodes the announced routes of an UPDATE message following its header one at a time,
+    /// without collecting them. See `NLRIIter`.
+    ///
+    /// ```
+    /// use bgp_rs::{Capabilities, ParseOptions, Update};
+    ///
+    /// #[rustfmt::skip]
+    /// let message = vec![
+    ///     0, 0, // Withdrawn Routes Length
+    ///     0, 4, // Path Attribute Length
+    ///     0x40, 1, 1, 0, // ORIGIN IGP
+    ///     24, 10, 0, 0, // 10.0.0.0/24
+    ///     16, 10, 1, // 10.1.0.0/16
+    /// ];
+    /// let (capabilities, options) = (Capabilities::default(), ParseOptions::default());
+    /// let routes = Update::iter_nlri(&message, &capabilities, &options).unwrap();
+    /// assert_eq!(routes.filter(|route| route.is_ok()).count(), 2);
+    /// ```
+    pub fn iter_nlri<'a>(
+        message: &'a [u8],
+        capabilities: &'a Capabilities,
+        options: &'a ParseOptions,
+    ) -> Result<NLRIIter<'a>, Error> {
+        NLRIIter::new(message, capabilities, options, false)
+    }
+
+    /// Decodes the withdrawn routes of an UPDATE message following its header one at a time,
+    /// like `iter_nlri`.
+    pub fn iter_withdrawn<'a>(
+        message: &'a [u8],
+        capabilities: &'a Capabilities,
+        options: &'a ParseOptions,
+    ) -> Result<NLRIIter<'a>, Error> {
+        NLRIIter::new(message, capabilities, options, true)
+    }
+
+    fn parse_inner(
+        header: &Header,
+        stream: &mut impl Read,
+        capabilities: &Capabilities,
+        options: &ParseOptions,
+        lossy: bool,
+    ) -> Result<(Update, Vec<ParseWarning>), Error> {
         if header.length < 23 {
-            return Err(Error::new(
-                ErrorKind::Other,
+            return Err(notification_error(
+                NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+                header.length.to_be_bytes().to_vec(),
                 format!("Header had bogus length {} < 23", header.length),
             ));
         }
-        let mut nlri_length: usize = header.length as usize - 23;
+
+        // The message is read at once, its sections are parsed from slices of it
+        let mut message = vec![0; header.length as usize - 19];
+        stream.read_exact(&mut message)?;
+        Update::parse_body(&message, capabilities, options, lossy)
+    }
+
+    // Parses the message following an UPDATE header, see `parse` and `parse_lossy`
+    pub(crate) fn parse_body(
+        message: &[u8],
+        capabilities: &Capabilities,
+        options: &ParseOptions,
+        lossy: bool,
+    ) -> Result<(Update, Vec<ParseWarning>), Error> {
+        let mut warnings: Vec<ParseWarning> = vec![];
+        let (withdrawn, mut buffer, nlri) = split_sections(message)?;
+
+        let withdrawn_routes =
+            parse_withdrawn_routes(withdrawn, capabilities, options, lossy, &mut warnings)?;
 
         // ----------------------------
-        // Read withdrawn routes.
+        // Read path attributes
         // ----------------------------
-        let withdraw_len = stream.read_u16::<BigEndian>()? as usize;
-        if withdraw_len > nlri_length {
-            return Err(Error::new(
+        let mut attributes: Vec<PathAttribute> = Vec::with_capacity(8);
+        let mut raw_attributes: Vec<RawPathAttribute> = Vec::with_capacity(0);
+        let max_attributes = options.max_attributes;
+        let mut attribute_count = 0;
+        while !buffer.is_empty() {
+            if attribute_count >= max_attributes {
+                return Err(too_many_attributes(max_attributes));
+            }
+            attribute_count += 1;
+            let raw_attribute = match RawPathAttributeRef::parse(&mut buffer) {
+                Ok(raw_attribute) => raw_attribute,
+                Err(e) if lossy => {
+                    trace_event!(debug, error = %e, "Truncated path attributes");
+                    warnings.push(ParseWarning::TruncatedAttributes {
+                        error: e.to_string(),
+                    });
+                    break;
+                }
+                Err(e) => return Err(malformed_attribute_list(e)),
+            };
+            match raw_attribute.decode(capabilities, options) {
+                Ok(attribute) => attributes.push(attribute),
+                // Flags errors are only raised with ParseOptions::strict_attribute_flags
+                Err(e) if !lossy && is_fatal_attribute_error(&e) => return Err(e),
+                Err(e) => {
+                    trace_event!(debug, code = raw_attribute.code, error = %e, "Skipped attribute");
+                    warnings.push(ParseWarning::SkippedAttribute {
+                        code: raw_attribute.code,
+                        error: e.to_string(),
+                    })
+                }
+            }
+            if options.preserve_wire_format {
+                raw_attributes.push(raw_attribute.to_raw());
+            }
+        }
+
+        let announced_routes =
+            parse_announced_routes(nlri, capabilities, options, lossy, &mut warnings)?;
+
+        let mp_nlri_count: usize = attributes
+            .iter()
+            .map(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach) => mp_reach.announced_routes.len(),
+                PathAttribute::MP_UNREACH_NLRI(mp_unreach) => mp_unreach.withdrawn_routes.len(),
+                _ => 0,
+            })
+            .sum();
+        let nlri_count = withdrawn_routes.len() + announced_routes.len() + mp_nlri_count;
+        if nlri_count > options.max_nlri {
+            return Err(invalid_network_field(Error::new(
                 ErrorKind::Other,
                 format!(
-                    "Got bogus withdraw length {} < msg len {}",
-                    withdraw_len, nlri_length
+                    "UPDATE has {} NLRI, exceeding the maximum of {}",
+                    nlri_count, options.max_nlri
                 ),
-            ));
+            )));
+        }
+
+        let received_attributes = if options.preserve_wire_format {
+            attributes.clone()
+        } else {
+            vec![]
+        };
+        let update = Update {
+            withdrawn_routes,
+            attributes,
+            announced_routes,
+            raw_attributes,
+            received_attributes,
+        };
+        Ok((update, warnings))
+    }
+
+    /// Update message to bytes
+    ///
+    /// Path attributes are written ordered by their type code, holding the same type more than
+    /// once is an error (see `dedup_attributes`).
+    /// The ASN width of AS_PATH is derived from the ASNs it contains.
+    /// Use `encode_with_capabilities` to follow the capabilities negotiated with a peer.
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(64);
+        self.encode_into(&mut bytes, None)?;
+        buf.write_all(&bytes)
+    }
+
+    /// Update message to bytes, following the capabilities negotiated with a peer.
+    ///
+    /// When the peer does not support 4-byte ASNs, 4-byte ASNs in AS_PATH and AGGREGATOR are
+    /// replaced with AS_TRANS and the AS4_PATH and AS4_AGGREGATOR attributes are added
+    /// (unless already present) to carry the original values, as described in RFC6793.
+    pub fn encode_with_capabilities(
+        &self,
+        buf: &mut impl Write,
+        capabilities: &Capabilities,
+    ) -> Result<(), Error> {
+        let mut bytes = Vec::with_capacity(64);
+        self.encode_into(&mut bytes, Some(capabilities))?;
+        buf.write_all(&bytes)
+    }
+
+    /// Appends the Update message (without header) to a buffer, returning the number of bytes
+    /// written.
+    ///
+    /// Encodes like `encode`, or like `encode_with_capabilities` when capabilities are given.
+    /// Reusing the buffer (after clearing it) avoids allocating for every message, as the
+    /// sections are written in place. Nothing is appended when encoding fails.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, Origin, PathAttribute, Update};
+    ///
+    /// let update = Update::new(
+    ///     vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    ///     vec![PathAttribute::ORIGIN(Origin::IGP)],
+    ///     vec![],
+    /// );
+    /// let mut buf = Vec::with_capacity(4096);
+    /// for _ in 0..2 {
+    ///     buf.clear();
+    ///     assert_eq!(update.encode_into(&mut buf, None).unwrap(), 10);
+    ///     assert_eq!(buf, vec![0, 2, 8, 10, 0, 4, 0x40, 1, 1, 0]);
+    /// }
+    /// ```
+    pub fn encode_into(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<usize, Error> {
+        let start = buf.len();
+        self.encode_inner(buf, capabilities)
+            .map(|_| buf.len() - start)
+            .map_err(|e| {
+                buf.truncate(start);
+                e
+            })
+    }
+
+    fn encode_inner(
+        &self,
+        buf: &mut Vec<u8>,
+        capabilities: Option<&Capabilities>,
+    ) -> Result<(), Error> {
+        // NLRI holding a Path Identifier keep it
+        let ipv4_add_path = capabilities.map_or(false, |capabilities| {
+            capabilities.sends_path_ids(AFI::IPV4, SAFI::Unicast)
+        });
+        let encode_ipv4 = |route: &NLRIEncoding, buf: &mut Vec<u8>| {
+            route.encode_with_add_path(buf, ipv4_add_path || route.path_id().is_some())
+        };
+
+        let mut unreach_nlri: HashMap<(AFI, SAFI), Vec<NLRIEncoding>> = HashMap::new();
+        let section = start_section(buf);
+        for withdrawal in &self.withdrawn_routes {
+            if withdrawal.is_ipv4() {
+                encode_ipv4(withdrawal, buf)?;
+            } else {
+                // Encode into MP_UNREACH_NLRI
+                let nlris = unreach_nlri
+                    .entry((withdrawal.afi(), withdrawal.safi()))
+                    .or_default();
+                nlris.push(withdrawal.clone());
+            }
+        }
+        end_section(buf, section);
+
+        // Path Attributes
+        let section = start_section(buf);
+        // The received MP_UNREACH_NLRI (if any) doesn't hold the added withdrawals
+        let raw_attributes = if unreach_nlri.is_empty() {
+            self.raw_attributes()
+        } else {
+            None
+        };
+        if let Some(raw_attributes) = raw_attributes {
+            // Re-use the attributes exactly as they were received
+            for attribute in raw_attributes {
+                attribute.encode(buf)?;
+            }
+        }
+        let mut generated: Vec<PathAttribute> = Vec::with_capacity(0);
+        if let Some(capabilities) = capabilities {
+            if !capabilities.FOUR_OCTET_ASN_SUPPORT && raw_attributes.is_none() {
+                generated.extend(self.as4_attributes());
+            }
+        }
+        // Withdrawals are added to the MP_UNREACH_NLRI of their family, if present
+        let mut merged: Option<usize> = None;
+        for (index, attribute) in self.attributes.iter().enumerate() {
+            if let PathAttribute::MP_UNREACH_NLRI(mp_unreach) = attribute {
+                if let Some(withdrawals) = unreach_nlri.remove(&(mp_unreach.afi, mp_unreach.safi)) {
+                    let mut mp_unreach = mp_unreach.clone();
+                    mp_unreach.withdrawn_routes.extend(withdrawals);
+                    generated.push(PathAttribute::MP_UNREACH_NLRI(mp_unreach));
+                    merged = Some(index);
+                }
+            }
+        }
+        for ((afi, safi), unreach_nlris) in unreach_nlri.into_iter() {
+            generated.push(PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi,
+                safi,
+                withdrawn_routes: unreach_nlris,
+            })));
+        }
+        // Attributes are written ordered by their type code, which strict peers may expect.
+        // Attributes that are already ordered (without duplicates) are written as they are.
+        let ordered = self
+            .attributes
+            .windows(2)
+            .all(|pair| pair[0].code() < pair[1].code());
+        if raw_attributes.is_none() && generated.is_empty() && ordered {
+            for attribute in &self.attributes {
+                attribute.encode_into(buf, capabilities)?;
+            }
+        } else {
+            let mut attributes: Vec<&PathAttribute> = if raw_attributes.is_none() {
+                self.attributes
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| Some(*index) != merged)
+                    .map(|(_, attribute)| attribute)
+                    .collect()
+            } else {
+                vec![]
+            };
+            // Checked once merged, withdrawals of another family can't get an MP_UNREACH_NLRI
+            // of their own either
+            attributes.extend(generated.iter());
+            attributes.sort_by_key(|attribute| attribute.code());
+            if let Some(pair) = attributes
+                .windows(2)
+                .find(|pair| pair[0].code() == pair[1].code())
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Duplicate {:?} path attribute, see Update::dedup_attributes",
+                        pair[0].id()
+                    ),
+                ));
+            }
+            for attribute in attributes {
+                attribute.encode_into(buf, capabilities)?;
+            }
+        }
+        end_section(buf, section);
+
+        // NLRI
+        for route in &self.announced_routes {
+            encode_ipv4(route, buf)?;
+        }
+        Ok(())
+    }
+
+    // Build the AS4_PATH and AS4_AGGREGATOR attributes needed to carry 4-byte ASNs
+    // through a peer that does not support them, if not already present.
+    fn as4_attributes(&self) -> Vec<PathAttribute> {
+        let mut attributes = Vec::with_capacity(0);
+        if let Some(PathAttribute::AS_PATH(as_path)) = self.get(Identifier::AS_PATH) {
+            if as_path.has_4_byte_asns() && self.get(Identifier::AS4_PATH).is_none() {
+                attributes.push(PathAttribute::AS4_PATH(as_path.clone()));
+            }
+        }
+        if let Some(PathAttribute::AGGREGATOR((asn, ip))) = self.get(Identifier::AGGREGATOR) {
+            if *asn > u32::from(std::u16::MAX) && self.get(Identifier::AS4_AGGREGATOR).is_none() {
+                attributes.push(PathAttribute::AS4_AGGREGATOR((*asn, *ip)));
+            }
+        }
+        attributes
+    }
+
+    /// The path attributes as they were received, when parsed with
+    /// `ParseOptions::preserve_wire_format`. These are encoded instead of `attributes` as long
+    /// as those are unchanged, any modification of `attributes` is encoded as usual.
+    pub fn raw_attributes(&self) -> Option<&[RawPathAttribute]> {
+        if self.raw_attributes.is_empty() || self.attributes != self.received_attributes {
+            None
+        } else {
+            Some(&self.raw_attributes)
+        }
+    }
+
+    // Drops the received encoding, such as of attributes that could not be decoded
+    pub(crate) fn clear_raw_attributes(&mut self) {
+        self.raw_attributes.clear();
+        self.received_attributes.clear();
+    }
+
+    /// Retrieves the first PathAttribute that matches the given identifier.
+    pub fn get(&self, identifier: Identifier) -> Option<&PathAttribute> {
+        self.attributes.iter().find(|a| a.id() == identifier)
+    }
+
+    /// Retrieves all PathAttributes that match the given identifier.
+    pub fn get_all(&self, identifier: Identifier) -> impl Iterator<Item = &PathAttribute> {
+        self.attributes.iter().filter(move |a| a.id() == identifier)
+    }
+
+    /// Retrieves the first PathAttribute that matches the given identifier for modification.
+    ///
+    /// ```
+    /// use bgp_rs::{ASPath, Identifier, PathAttribute, Segment, Update};
+    ///
+    /// let mut update = Update::new(
+    ///     vec![],
+    ///     vec![
+    ///         PathAttribute::AS_PATH(ASPath {
+    ///             segments: vec![Segment::AS_SEQUENCE(vec![65001])],
+    ///         }),
+    ///         PathAttribute::MULTI_EXIT_DISC(10),
+    ///         PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+    ///     ],
+    ///     vec![],
+    /// );
+    /// if let Some(PathAttribute::AS_PATH(as_path)) = update.get_mut(Identifier::AS_PATH) {
+    ///     as_path.prepend(65000, 2);
+    /// }
+    /// update.insert(PathAttribute::MULTI_EXIT_DISC(20));
+    /// update.remove(Identifier::COMMUNITY);
+    /// assert_eq!(
+    ///     update.to_string(),
+    ///     "AS path 65000 65000 65001, MED 20"
+    /// );
+    /// ```
+    pub fn get_mut(&mut self, identifier: Identifier) -> Option<&mut PathAttribute> {
+        self.attributes.iter_mut().find(|a| a.id() == identifier)
+    }
+
+    /// Removes all PathAttributes that match the given identifier, returning them.
+    pub fn remove(&mut self, identifier: Identifier) -> Vec<PathAttribute> {
+        let (removed, kept) = self
+            .attributes
+            .drain(..)
+            .partition(|a| a.id() == identifier);
+        self.attributes = kept;
+        removed
+    }
+
+    /// Adds a PathAttribute, replacing the first PathAttribute of the same type (if any),
+    /// which is returned.
+    pub fn insert(&mut self, attribute: PathAttribute) -> Option<PathAttribute> {
+        let code = attribute.code();
+        match self.attributes.iter_mut().find(|a| a.code() == code) {
+            Some(existing) => Some(std::mem::replace(existing, attribute)),
+            None => {
+                self.attributes.push(attribute);
+                None
+            }
+        }
+    }
+
+    /// Retrieves the AS_PATH of this UPDATE, merged with the AS4_PATH (if present) as
+    /// described in RFC6793. This recovers the 4-byte ASNs that were replaced by AS_TRANS
+    /// when the UPDATE passed through a speaker that does not support 4-byte ASNs.
+    ///
+    /// The AS4_PATH is ignored when the AGGREGATOR holds an ASN other than AS_TRANS, as the
+    /// route was aggregated by a speaker that does not support 4-byte ASNs (RFC6793 4.2.3).
+    pub fn effective_as_path(&self) -> Option<ASPath> {
+        effective_as_path(&self.attributes)
+    }
+
+    /// Tags the announced routes for a graceful shutdown of the session (RFC8326): adds the
+    /// GRACEFUL_SHUTDOWN community and sets LOCAL_PREF to 0, so that peers prefer alternate
+    /// paths before the routes are withdrawn. UPDATE messages only withdrawing routes are left
+    /// as they are.
+    ///
+    /// LOCAL_PREF is only sent to internal peers, it should be removed for external peers.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, PathAttribute, Update};
+    ///
+    /// let mut update = Update::new(
+    ///     vec![],
+    ///     vec![
+    ///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+    ///         PathAttribute::LOCAL_PREF(100),
+    ///     ],
+    ///     vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    /// );
+    /// update.apply_graceful_shutdown();
+    /// assert!(update.is_graceful_shutdown());
+    /// assert_eq!(
+    ///     update.to_string(),
+    ///     "10.0.0.0/8 via 192.0.2.1, local pref 0, communities 65535:0"
+    /// );
+    /// ```
+    pub fn apply_graceful_shutdown(&mut self) {
+        if !self.is_announcement() {
+            return;
+        }
+        self.insert(PathAttribute::LOCAL_PREF(0));
+        match self.get_mut(Identifier::COMMUNITY) {
+            Some(PathAttribute::COMMUNITY(communities)) => {
+                if !communities.contains(&GRACEFUL_SHUTDOWN) {
+                    communities.push(GRACEFUL_SHUTDOWN);
+                }
+            }
+            _ => {
+                self.insert(PathAttribute::COMMUNITY(vec![GRACEFUL_SHUTDOWN]));
+            }
+        }
+    }
+
+    /// Checks if the routes are tagged with the GRACEFUL_SHUTDOWN community (RFC8326): the
+    /// peer is about to withdraw them, the receiver should lower their preference.
+    pub fn is_graceful_shutdown(&self) -> bool {
+        self.get_all(Identifier::COMMUNITY)
+            .any(|attribute| match attribute {
+                PathAttribute::COMMUNITY(communities) => communities.contains(&GRACEFUL_SHUTDOWN),
+                _ => false,
+            })
+    }
+
+    /// Creates the End-of-RIB marker for the given address family, as described in RFC4724.
+    ///
+    /// For IPv4 Unicast this is an empty UPDATE, other address families use an UPDATE
+    /// that only holds an empty MP_UNREACH_NLRI attribute.
+    ///
+    /// ```
+    /// use bgp_rs::{Update, AFI, SAFI};
+    ///
+    /// let eor = Update::end_of_rib(AFI::IPV4, SAFI::Unicast);
+    /// let mut bytes = vec![];
+    /// eor.encode(&mut bytes).unwrap();
+    /// assert_eq!(bytes, vec![0, 0, 0, 0]);
+    ///
+    /// let eor = Update::end_of_rib(AFI::IPV6, SAFI::Unicast);
+    /// assert!(eor.is_end_of_rib());
+    /// assert_eq!(eor.end_of_rib_family(), Some((AFI::IPV6, SAFI::Unicast)));
+    /// ```
+    pub fn end_of_rib(afi: AFI, safi: SAFI) -> Update {
+        let attributes = match (afi, safi) {
+            (AFI::IPV4, SAFI::Unicast) => vec![],
+            _ => vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi,
+                safi,
+                withdrawn_routes: vec![],
+            }))],
+        };
+        Update::new(vec![], attributes, vec![])
+    }
+
+    /// Checks if this UPDATE message is an End-of-RIB marker.
+    pub fn is_end_of_rib(&self) -> bool {
+        self.end_of_rib_family().is_some()
+    }
+
+    /// Retrieves the address family this End-of-RIB marker applies to,
+    /// or None if this UPDATE message is not an End-of-RIB marker.
+    pub fn end_of_rib_family(&self) -> Option<(AFI, SAFI)> {
+        if !self.withdrawn_routes.is_empty() || !self.announced_routes.is_empty() {
+            return None;
+        }
+        match self.attributes.as_slice() {
+            [] => Some((AFI::IPV4, SAFI::Unicast)),
+            [PathAttribute::MP_UNREACH_NLRI(mp_unreach)]
+                if mp_unreach.withdrawn_routes.is_empty() =>
+            {
+                Some((mp_unreach.afi, mp_unreach.safi))
+            }
+            _ => None,
+        }
+    }
+
+    /// Checks if this UPDATE message contains announced prefixes.
+    pub fn is_announcement(&self) -> bool {
+        if !self.announced_routes.is_empty() || self.get(Identifier::MP_REACH_NLRI).is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Checks if this UPDATE message contains withdrawn routes..
+    pub fn is_withdrawal(&self) -> bool {
+        if !self.withdrawn_routes.is_empty() || self.get(Identifier::MP_UNREACH_NLRI).is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Resolves the next hop of an announced route: the NEXT_HOP attribute for routes in the
+    /// NLRI, or the next hop of the MP_REACH_NLRI holding the route. Returns None when the route
+    /// isn't announced by this UPDATE or its next hop is missing or can't be decoded.
+    ///
+    /// ```
+    /// use bgp_rs::{MPReachNLRI, NLRIEncoding, NextHop, PathAttribute, Update, AFI, SAFI};
+    ///
+    /// let route = NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into());
+    /// let mut mp_reach = MPReachNLRI::new(
+    ///     AFI::IPV6,
+    ///     SAFI::Unicast,
+    ///     "2001:db8::1".parse().unwrap(),
+    ///     vec![route.clone()],
+    /// );
+    /// let link_local: std::net::Ipv6Addr = "fe80::1".parse().unwrap();
+    /// mp_reach.next_hop.extend_from_slice(&link_local.octets());
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::MP_REACH_NLRI(Box::new(mp_reach))],
+    ///     vec![],
+    /// );
+    /// assert_eq!(
+    ///     update.next_hop(&route),
+    ///     Some(NextHop::LinkLocal {
+    ///         global: "2001:db8::1".parse().unwrap(),
+    ///         link_local,
+    ///     })
+    /// );
+    /// ```
+    pub fn next_hop(&self, route: &NLRIEncoding) -> Option<NextHop> {
+        if self.announced_routes.contains(route) {
+            return match self.get(Identifier::NEXT_HOP) {
+                Some(PathAttribute::NEXT_HOP(address)) => Some(NextHop::Address(*address)),
+                _ => None,
+            };
+        }
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach)
+                    if mp_reach.announced_routes.contains(route) =>
+                {
+                    let addresses = mp_reach.decode_next_hop().ok()?;
+                    NextHop::from_addresses(&addresses)
+                }
+                _ => None,
+            })
+    }
+
+    /// Sets the next hop of all announced routes, such as for next-hop-self: the NEXT_HOP
+    /// attribute (added when routes are announced in the NLRI) and the next hop of
+    /// MP_REACH_NLRI, encoded the way its AFI/SAFI requires. An IPv4 next hop of IPv6 routes
+    /// is encoded as IPv4-mapped IPv6 address, a link-local next hop is dropped.
+    ///
+    /// An IPv6 next hop for IPv4 routes requires the Extended Next Hop Encoding capability,
+    /// which isn't checked here (see `MPReachNLRI::set_next_hop`).
+    ///
+    /// ```
+    /// use bgp_rs::{MPReachNLRI, NLRIEncoding, PathAttribute, Update, AFI, SAFI};
+    ///
+    /// let route = NLRIEncoding::IP_VPN_MPLS((100, ("10.1.0.0".parse().unwrap(), 16).into(), 16));
+    /// let mut update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+    ///         AFI::IPV4,
+    ///         SAFI::MplsVpn,
+    ///         "192.0.2.1".parse().unwrap(),
+    ///         vec![route.clone()],
+    ///     )))],
+    ///     vec![],
+    /// );
+    /// update.set_next_hop("198.51.100.1".parse().unwrap());
+    /// assert_eq!(update.next_hop(&route).unwrap().to_string(), "198.51.100.1");
+    /// ```
+    pub fn set_next_hop(&mut self, next_hop: IpAddr) {
+        if !self.announced_routes.is_empty() || self.get(Identifier::NEXT_HOP).is_some() {
+            self.insert(PathAttribute::NEXT_HOP(next_hop));
+        }
+        for attribute in &mut self.attributes {
+            if let PathAttribute::MP_REACH_NLRI(mp_reach) = attribute {
+                let address = match (mp_reach.afi, next_hop) {
+                    (AFI::IPV6, IpAddr::V4(address)) => IpAddr::V6(address.to_ipv6_mapped()),
+                    _ => next_hop,
+                };
+                mp_reach.next_hop.clear();
+                push_next_hop_address(mp_reach.safi, address, &mut mp_reach.next_hop);
+            }
+        }
+    }
+
+    /// Iterates over the announced routes with their AFI/SAFI, those of the NLRI followed by
+    /// those of MP_REACH_NLRI.
+    ///
+    /// ```
+    /// use bgp_rs::{MPReachNLRI, NLRIEncoding, PathAttribute, Update, AFI, SAFI};
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+    ///         AFI::IPV6,
+    ///         SAFI::Multicast,
+    ///         "2001:db8::1".parse().unwrap(),
+    ///         vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+    ///     )))],
+    ///     vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    /// );
+    /// let routes: Vec<_> = update
+    ///     .announced()
+    ///     .map(|(afi, safi, route)| format!("{} {} {}", afi, safi, route))
+    ///     .collect();
+    /// assert_eq!(routes.len(), 2);
+    /// assert_eq!(update.announced().nth(1).unwrap().1, SAFI::Multicast);
+    /// ```
+    pub fn announced(&self) -> impl Iterator<Item = (AFI, SAFI, &NLRIEncoding)> {
+        let mp_routes = self.attributes.iter().flat_map(|attribute| {
+            let (afi, safi, routes) = match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                    (mp_reach.afi, mp_reach.safi, &mp_reach.announced_routes[..])
+                }
+                _ => (AFI::IPV4, SAFI::Unicast, &[][..]),
+            };
+            routes.iter().map(move |route| (afi, safi, route))
+        });
+        with_family(&self.announced_routes).chain(mp_routes)
+    }
+
+    /// Iterates over the withdrawn routes with their AFI/SAFI, those of the Withdrawn Routes
+    /// followed by those of MP_UNREACH_NLRI.
+    pub fn withdrawn(&self) -> impl Iterator<Item = (AFI, SAFI, &NLRIEncoding)> {
+        let mp_routes = self.attributes.iter().flat_map(|attribute| {
+            let (afi, safi, routes) = match attribute {
+                PathAttribute::MP_UNREACH_NLRI(mp_unreach) => (
+                    mp_unreach.afi,
+                    mp_unreach.safi,
+                    &mp_unreach.withdrawn_routes[..],
+                ),
+                _ => (AFI::IPV4, SAFI::Unicast, &[][..]),
+            };
+            routes.iter().map(move |route| (afi, safi, route))
+        });
+        with_family(&self.withdrawn_routes).chain(mp_routes)
+    }
+
+    /// Moves the MP_REACH and MP_UNREACH NLRI into the NLRI.
+    /// Use `announced` and `withdrawn` to go over the routes along with their AFI/SAFI instead.
+    ///
+    /// Routes whose AFI/SAFI can't be derived from their encoding (see `NLRIEncoding::family`),
+    /// such as the prefixes of Multicast SAFIs, are only kept in their MP attribute.
+    pub fn normalize(&mut self) {
+        // Move the MP_REACH_NLRI attribute in the NLRI.
+        let identifier = match self.get(Identifier::MP_REACH_NLRI) {
+            Some(PathAttribute::MP_REACH_NLRI(routes)) => Some(of_family(
+                &routes.announced_routes,
+                (routes.afi, routes.safi),
+            )),
+            _ => None,
+        };
+        if let Some(routes) = identifier {
+            self.announced_routes.extend(routes)
+        }
+
+        // Move the MP_REACH_NLRI attribute in the NLRI.
+        let identifier = match self.get(Identifier::MP_UNREACH_NLRI) {
+            Some(PathAttribute::MP_UNREACH_NLRI(routes)) => Some(of_family(
+                &routes.withdrawn_routes,
+                (routes.afi, routes.safi),
+            )),
+            _ => None,
+        };
+        if let Some(routes) = identifier {
+            self.withdrawn_routes.extend(routes)
+        }
+    }
+
+    /// Moves the IPv4 Unicast routes of the NLRI into MP_REACH_NLRI and MP_UNREACH_NLRI, the
+    /// inverse of `normalize`, when MP-BGP was negotiated for IPv4 Unicast. The NEXT_HOP
+    /// attribute becomes the next hop of MP_REACH_NLRI, which may then be replaced by an IPv6
+    /// next hop (RFC8950) with `MPReachNLRI::set_next_hop`.
+    ///
+    /// Fails when routes are announced without a NEXT_HOP attribute.
+    ///
+    /// ```
+    /// use bgp_rs::{Capabilities, Identifier, NLRIEncoding, PathAttribute, Update, AFI, SAFI};
+    ///
+    /// let mut update = Update::new(
+    ///     vec![NLRIEncoding::IP(("10.1.0.0".parse().unwrap(), 16).into())],
+    ///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+    ///     vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    /// );
+    /// let mut capabilities = Capabilities::default();
+    /// capabilities.MP_BGP_SUPPORT.insert((AFI::IPV4, SAFI::Unicast));
+    /// update.to_mp(&capabilities).unwrap();
+    /// assert!(update.announced_routes.is_empty() && update.withdrawn_routes.is_empty());
+    /// assert!(update.get(Identifier::NEXT_HOP).is_none());
+    /// match update.get(Identifier::MP_REACH_NLRI) {
+    ///     Some(PathAttribute::MP_REACH_NLRI(mp_reach)) => {
+    ///         assert_eq!(mp_reach.next_hop, vec![192, 0, 2, 1]);
+    ///         assert_eq!(mp_reach.announced_routes.len(), 1);
+    ///     }
+    ///     _ => panic!("MP_REACH_NLRI not present"),
+    /// }
+    /// assert_eq!(update.withdrawn().count(), 1);
+    /// ```
+    pub fn to_mp(&mut self, capabilities: &Capabilities) -> Result<(), Error> {
+        let family = (AFI::IPV4, SAFI::Unicast);
+        if !capabilities.MP_BGP_SUPPORT.contains(&family) {
+            return Ok(());
+        }
+        let (announced, others): (Vec<_>, Vec<_>) = self
+            .announced_routes
+            .drain(..)
+            .partition(|route| route.family() == family);
+        self.announced_routes = others;
+        let (withdrawn, others): (Vec<_>, Vec<_>) = self
+            .withdrawn_routes
+            .drain(..)
+            .partition(|route| route.family() == family);
+        self.withdrawn_routes = others;
+
+        if !announced.is_empty() {
+            let next_hop = match self.get(Identifier::NEXT_HOP) {
+                Some(PathAttribute::NEXT_HOP(next_hop)) => *next_hop,
+                _ => {
+                    self.announced_routes.extend(announced);
+                    self.withdrawn_routes.extend(withdrawn);
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Routes are announced without a NEXT_HOP attribute",
+                    ));
+                }
+            };
+            self.remove(Identifier::NEXT_HOP);
+            let mp_reach = self
+                .attributes
+                .iter_mut()
+                .find_map(|attribute| match attribute {
+                    PathAttribute::MP_REACH_NLRI(mp_reach)
+                        if (mp_reach.afi, mp_reach.safi) == family =>
+                    {
+                        Some(mp_reach)
+                    }
+                    _ => None,
+                });
+            match mp_reach {
+                Some(mp_reach) => mp_reach.announced_routes.extend(announced),
+                None => {
+                    self.attributes
+                        .push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                            family.0, family.1, next_hop, announced,
+                        ))))
+                }
+            }
+        }
+        if !withdrawn.is_empty() {
+            let mp_unreach = self
+                .attributes
+                .iter_mut()
+                .find_map(|attribute| match attribute {
+                    PathAttribute::MP_UNREACH_NLRI(mp_unreach)
+                        if (mp_unreach.afi, mp_unreach.safi) == family =>
+                    {
+                        Some(mp_unreach)
+                    }
+                    _ => None,
+                });
+            match mp_unreach {
+                Some(mp_unreach) => mp_unreach.withdrawn_routes.extend(withdrawn),
+                None => self
+                    .attributes
+                    .push(PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                        afi: family.0,
+                        safi: family.1,
+                        withdrawn_routes: withdrawn,
+                    }))),
+            }
+        }
+        Ok(())
+    }
+
+    /// The routes announced or withdrawn more than once by this UPDATE (in the NLRI or the MP
+    /// attributes), with their AFI/SAFI: every repetition after the first. Routes are compared
+    /// by their NLRI, the same prefix with distinct Path Identifiers or labels is no duplicate.
+    ///
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, Update};
+    ///
+    /// let route = |prefix: &str| NLRIEncoding::IP(prefix.parse().unwrap());
+    /// let mut update = Update::new(
+    ///     vec![route("10.9.0.0/16")],
+    ///     vec![],
+    ///     vec![route("10.0.0.0/8"), route("10.1.0.0/16"), route("10.0.0.0/8")],
+    /// );
+    /// let duplicates: Vec<String> = update
+    ///     .duplicate_routes()
+    ///     .iter()
+    ///     .map(|(_, _, route)| route.to_string())
+    ///     .collect();
+    /// assert_eq!(duplicates, vec!["10.0.0.0/8"]);
+    ///
+    /// update.dedup_routes();
+    /// assert_eq!(update.announced_routes.len(), 2);
+    /// assert!(update.duplicate_routes().is_empty());
+    /// ```
+    pub fn duplicate_routes(&self) -> Vec<(AFI, SAFI, &NLRIEncoding)> {
+        let mut duplicates = vec![];
+        for routes in &[
+            self.announced().collect::<Vec<_>>(),
+            self.withdrawn().collect::<Vec<_>>(),
+        ] {
+            for (index, (afi, safi, route)) in routes.iter().enumerate() {
+                if routes[..index].iter().any(|(_, _, other)| other == route) {
+                    duplicates.push((*afi, *safi, *route));
+                }
+            }
+        }
+        duplicates
+    }
+
+    /// Removes the routes announced or withdrawn more than once, keeping their first
+    /// occurrence, see `duplicate_routes`.
+    pub fn dedup_routes(&mut self) {
+        let mut announced = vec![];
+        let mut withdrawn = vec![];
+        retain_first(&mut self.announced_routes, &mut announced);
+        retain_first(&mut self.withdrawn_routes, &mut withdrawn);
+        for attribute in &mut self.attributes {
+            match attribute {
+                PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                    retain_first(&mut mp_reach.announced_routes, &mut announced)
+                }
+                PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+                    retain_first(&mut mp_unreach.withdrawn_routes, &mut withdrawn)
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Removes duplicate path attributes, keeping the last attribute of every type.
+    /// UPDATE messages holding duplicate path attributes fail to encode.
+    ///
+    /// ```
+    /// use bgp_rs::{Identifier, PathAttribute, Update};
+    ///
+    /// let mut update = Update::new(
+    ///     vec![],
+    ///     vec![
+    ///         PathAttribute::LOCAL_PREF(100),
+    ///         PathAttribute::MULTI_EXIT_DISC(10),
+    ///         PathAttribute::LOCAL_PREF(200),
+    ///     ],
+    ///     vec![],
+    /// );
+    /// assert!(update.encode(&mut vec![]).is_err());
+    ///
+    /// update.dedup_attributes();
+    /// assert_eq!(update.attributes.len(), 2);
+    /// match update.get(Identifier::LOCAL_PREF) {
+    ///     Some(PathAttribute::LOCAL_PREF(local_pref)) => assert_eq!(*local_pref, 200),
+    ///     _ => panic!("LOCAL_PREF not present"),
+    /// }
+    /// ```
+    pub fn dedup_attributes(&mut self) {
+        let mut attributes: Vec<PathAttribute> = Vec::with_capacity(self.attributes.len());
+        for attribute in self.attributes.drain(..).rev() {
+            if attributes
+                .iter()
+                .all(|other| other.code() != attribute.code())
+            {
+                attributes.push(attribute);
+            }
+        }
+        attributes.reverse();
+        self.attributes = attributes;
+    }
+
+    /// Checks the path attributes of this UPDATE against the rules of RFC4271 and RFC4760,
+    /// returning the violations found (none for a valid UPDATE). Every violation maps to the
+    /// UPDATE Message Error Notification a receiver would send, see `ToNotification`.
+    ///
+    /// The flags of the attributes are only checked for `raw_attributes()`, as they are derived
+    /// from the attribute type when encoding.
+    ///
+    /// ```
+    /// use bgp_rs::*;
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![
+    ///         PathAttribute::ORIGIN(Origin::IGP),
+    ///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+    ///     ],
+    ///     vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    /// );
+    /// let violations = update.validate(&Capabilities::default());
+    /// assert_eq!(
+    ///     violations,
+    ///     vec![UpdateViolation::MissingWellKnownAttribute(Identifier::AS_PATH)]
+    /// );
+    /// let notification = violations[0].to_notification();
+    /// assert_eq!(
+    ///     notification.code,
+    ///     NotificationCode::UpdateMessage(UpdateMessageSubcode::MissingWellKnownAttribute)
+    /// );
+    /// assert_eq!(notification.data, vec![2]);
+    /// ```
+    pub fn validate(&self, capabilities: &Capabilities) -> Vec<UpdateViolation> {
+        let mut violations: Vec<UpdateViolation> = vec![];
+
+        // Every attribute (including MP_REACH_NLRI & MP_UNREACH_NLRI) may only appear once
+        let mut seen: Vec<u8> = Vec::with_capacity(self.attributes.len());
+        for attribute in &self.attributes {
+            let violation = UpdateViolation::DuplicateAttribute(attribute.id());
+            if !seen.contains(&attribute.code()) {
+                seen.push(attribute.code());
+            } else if !violations.contains(&violation) {
+                violations.push(violation);
+            }
+        }
+
+        for attribute in self.raw_attributes().unwrap_or_default() {
+            if !has_valid_flags(attribute.flags, attribute.code) {
+                let mut bytes = vec![];
+                let _ = attribute.encode(&mut bytes);
+                violations.push(UpdateViolation::AttributeFlags {
+                    code: attribute.code,
+                    flags: attribute.flags,
+                    attribute: bytes,
+                });
+            }
+        }
+
+        let mp_announced = self.attributes.iter().any(|attribute| match attribute {
+            PathAttribute::MP_REACH_NLRI(mp_reach) => !mp_reach.announced_routes.is_empty(),
+            _ => false,
+        });
+        if !self.announced_routes.is_empty() || mp_announced {
+            for identifier in &[Identifier::ORIGIN, Identifier::AS_PATH] {
+                if self.get(*identifier).is_none() {
+                    violations.push(UpdateViolation::MissingWellKnownAttribute(*identifier));
+                }
+            }
+            // Routes of MP_REACH_NLRI take the next hop of the attribute
+            if !self.announced_routes.is_empty() && self.get(Identifier::NEXT_HOP).is_none() {
+                violations.push(UpdateViolation::MissingWellKnownAttribute(
+                    Identifier::NEXT_HOP,
+                ));
+            }
+        } else if self.get(Identifier::NEXT_HOP).is_some() {
+            violations.push(UpdateViolation::UnexpectedNextHop);
+        }
+
+        if !capabilities.MP_BGP_SUPPORT.is_empty() {
+            for attribute in &self.attributes {
+                let family = match attribute {
+                    PathAttribute::MP_REACH_NLRI(mp_reach) => (mp_reach.afi, mp_reach.safi),
+                    PathAttribute::MP_UNREACH_NLRI(mp_unreach) => (mp_unreach.afi, mp_unreach.safi),
+                    _ => continue,
+                };
+                if !capabilities.MP_BGP_SUPPORT.contains(&family) {
+                    violations.push(UpdateViolation::UnsupportedFamily {
+                        afi: family.0,
+                        safi: family.1,
+                    });
+                }
+            }
+        }
+        violations
+    }
+
+    /// Splits this UPDATE into messages of at most `max_size` bytes (including the header),
+    /// such as the 4096 bytes allowed by RFC4271, when encoded with the given capabilities.
+    ///
+    /// The withdrawn routes come first, in messages without path attributes. The announced
+    /// routes follow, every message repeating the path attributes shared by the routes.
+    /// An UPDATE that already fits is returned as is, otherwise `raw_attributes()` are not kept.
+    /// A route that doesn't fit along with the path attributes still gets a message of its own,
+    /// which will fail to encode.
+    ///
+    /// ```
+    /// use bgp_rs::{Capabilities, Message, NLRIEncoding, PathAttribute, Update};
+    /// use std::net::Ipv4Addr;
+    ///
+    /// let update = Update::new(
+    ///     vec![],
+    ///     vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+    ///     (0..2000u32)
+    ///         .map(|i| NLRIEncoding::IP((Ipv4Addr::from(i << 8).into(), 24).into()))
+    ///         .collect(),
+    /// );
+    /// let capabilities = Capabilities::default();
+    /// let updates = update.split(4096, &capabilities);
+    /// assert_eq!(updates.len(), 2);
+    /// for update in updates {
+    ///     let mut bytes = vec![];
+    ///     Message::Update(update)
+    ///         .encode_with_capabilities(&mut bytes, &capabilities)
+    ///         .unwrap();
+    ///     assert!(bytes.len() <= 4096);
+    /// }
+    /// ```
+    pub fn split(&self, max_size: usize, capabilities: &Capabilities) -> Vec<Update> {
+        let mut bytes = vec![];
+        if self
+            .encode_with_capabilities(&mut bytes, capabilities)
+            .is_ok()
+            && bytes.len() + BGP_MIN_MESSAGE_SIZE <= max_size
+        {
+            return vec![self.clone()];
+        }
+
+        let mut withdrawals = UpdatePacker::new(vec![], max_size, capabilities);
+        for route in &self.withdrawn_routes {
+            if route.is_ipv4() {
+                withdrawals.push(route, Placement::Withdrawn);
+            } else {
+                withdrawals.push(route, Placement::Unreach(route.afi(), route.safi()));
+            }
+        }
+        for attribute in &self.attributes {
+            if let PathAttribute::MP_UNREACH_NLRI(mp_unreach) = attribute {
+                for route in &mp_unreach.withdrawn_routes {
+                    withdrawals.push(route, Placement::Unreach(mp_unreach.afi, mp_unreach.safi));
+                }
+            }
+        }
+
+        let shared: Vec<PathAttribute> = self
+            .attributes
+            .iter()
+            .filter(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(_) | PathAttribute::MP_UNREACH_NLRI(_) => false,
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        let mut announcements = UpdatePacker::new(shared, max_size, capabilities);
+        for attribute in &self.attributes {
+            if let PathAttribute::MP_REACH_NLRI(mp_reach) = attribute {
+                for route in &mp_reach.announced_routes {
+                    announcements.push(route, Placement::Reach(mp_reach));
+                }
+            }
+        }
+        for route in &self.announced_routes {
+            announcements.push(route, Placement::Announced);
+        }
+
+        let mut updates = withdrawals.finish();
+        updates.extend(announcements.finish());
+        if updates.is_empty() {
+            // Nothing to split, such as an UPDATE holding only path attributes
+            updates.push(self.clone());
+        }
+        updates
+    }
+}
+
+// The AS_PATH merged with AS4_PATH, see `Update::effective_as_path`
+pub(crate) fn effective_as_path(attributes: &[PathAttribute]) -> Option<ASPath> {
+    let mut as_path = None;
+    let mut as4_path = None;
+    let mut aggregator_asn = None;
+    for attribute in attributes {
+        match attribute {
+            PathAttribute::AS_PATH(path) if as_path.is_none() => as_path = Some(path),
+            PathAttribute::AS4_PATH(path) if as4_path.is_none() => as4_path = Some(path),
+            PathAttribute::AGGREGATOR((asn, _)) if aggregator_asn.is_none() => {
+                aggregator_asn = Some(*asn)
+            }
+            _ => (),
+        }
+    }
+    match (as_path?, as4_path) {
+        (as_path, Some(as4_path)) if aggregator_asn.map_or(true, |asn| asn == AS_TRANS) => {
+            Some(ASPath::merge_as4(as_path, as4_path))
+        }
+        (as_path, _) => Some(as_path.clone()),
+    }
+}
+
+// UPDATE messages withdrawing routes, one per AFI/SAFI in the order they are first seen.
+// IPv4 Unicast routes are withdrawn in the Withdrawn Routes, others in MP_UNREACH_NLRI.
+pub(crate) fn withdrawal_updates(
+    routes: impl IntoIterator<Item = (AFI, SAFI, NLRIEncoding)>,
+) -> Vec<Update> {
+    let mut families: Vec<(AFI, SAFI, Vec<NLRIEncoding>)> = vec![];
+    for (afi, safi, route) in routes {
+        match families
+            .iter_mut()
+            .find(|family| family.0 == afi && family.1 == safi)
+        {
+            Some((_, _, routes)) => routes.push(route),
+            None => families.push((afi, safi, vec![route])),
         }
-        let mut buffer = vec![0; withdraw_len];
-        stream.read_exact(&mut buffer)?;
-        nlri_length -= withdraw_len;
+    }
+    families
+        .into_iter()
+        .map(|(afi, safi, routes)| match (afi, safi) {
+            (AFI::IPV4, SAFI::Unicast) => Update::new(routes, vec![], vec![]),
+            _ => Update::new(
+                vec![],
+                vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                    afi,
+                    safi,
+                    withdrawn_routes: routes,
+                }))],
+                vec![],
+            ),
+        })
+        .collect()
+}
 
-        let mut withdrawn_routes: Vec<NLRIEncoding> = Vec::with_capacity(0);
-        let mut cursor = Cursor::new(buffer);
+/// Packs routes into as few UPDATE messages as possible, the way BGP speakers send their RIB:
+/// prefixes with identical path attributes (regardless of their order) share messages, which
+/// are filled up to `max_size` bytes (including the header) as described by `Update::split`.
+///
+/// IPv4 prefixes are announced in the NLRI of the UPDATE, unless the attributes hold an
+/// MP_REACH_NLRI of the IPv4 AFI and the Unicast or Multicast SAFI. Prefixes of other AFIs are
+/// added to such an MP_REACH_NLRI of their attributes, providing the next hop, and are left out
+/// when there is none. The messages follow the order in which the attribute sets were first seen.
+///
+/// ```
+/// use bgp_rs::{pack_updates, Capabilities, MPReachNLRI, PathAttribute, Prefix, AFI, SAFI};
+///
+/// let ipv4 = vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())];
+/// let ipv6 = vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+///     AFI::IPV6,
+///     SAFI::Unicast,
+///     "2001:db8::1".parse().unwrap(),
+///     vec![],
+/// )))];
+/// let routes: Vec<(Prefix, Vec<PathAttribute>)> = vec![
+///     ("10.0.0.0/8".parse().unwrap(), ipv4.clone()),
+///     ("2001:db8::/32".parse().unwrap(), ipv6),
+///     ("10.1.0.0/16".parse().unwrap(), ipv4),
+/// ];
+/// let updates = pack_updates(routes, 4096, &Capabilities::default());
+/// assert_eq!(updates.len(), 2);
+/// assert_eq!(updates[0].announced_routes.len(), 2);
+/// ```
+pub fn pack_updates(
+    routes: impl IntoIterator<Item = (Prefix, Vec<PathAttribute>)>,
+    max_size: usize,
+    capabilities: &Capabilities,
+) -> Vec<Update> {
+    // The attribute sets of the groups, without the routes of their MP_REACH_NLRI
+    let mut keys: Vec<Vec<PathAttribute>> = vec![];
+    let mut groups: Vec<Update> = vec![];
+    let mut last: Option<usize> = None;
+    for (prefix, attributes) in routes {
+        // Prefixes are announced in an MP_REACH_NLRI of their AFI, holding plain prefixes
+        let afi = prefix.protocol;
+        let holds_prefix = |attribute: &PathAttribute| match attribute {
+            PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                mp_reach.afi == afi
+                    && (mp_reach.safi == SAFI::Unicast || mp_reach.safi == SAFI::Multicast)
+            }
+            _ => false,
+        };
+        // Only IPv4 prefixes can be announced in the NLRI of the UPDATE
+        if afi != AFI::IPV4 && !attributes.iter().any(holds_prefix) {
+            continue;
+        }
 
-        if capabilities.EXTENDED_PATH_NLRI_SUPPORT {
-            while cursor.position() < withdraw_len as u64 {
-                let path_id = cursor.read_u32::<BigEndian>()?;
-                let prefix = Prefix::parse(&mut cursor, AFI::IPV4)?;
-                withdrawn_routes.push(NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)));
+        let key: Vec<PathAttribute> = attributes
+            .into_iter()
+            .map(|attribute| match attribute {
+                PathAttribute::MP_REACH_NLRI(mut mp_reach) => {
+                    mp_reach.announced_routes = vec![];
+                    PathAttribute::MP_REACH_NLRI(mp_reach)
+                }
+                attribute => attribute,
+            })
+            .collect();
+        // Routes sharing their attributes usually follow each other, try the last group first
+        let group = match last
+            .filter(|&group| same_attributes(&keys[group], &key))
+            .or_else(|| keys.iter().position(|other| same_attributes(other, &key)))
+        {
+            Some(group) => group,
+            None => {
+                groups.push(Update::new(vec![], key.clone(), vec![]));
+                keys.push(key);
+                groups.len() - 1
+            }
+        };
+        last = Some(group);
+
+        let update = &mut groups[group];
+        let mp_reach = update
+            .attributes
+            .iter_mut()
+            .find(|attribute| holds_prefix(attribute));
+        match mp_reach {
+            Some(PathAttribute::MP_REACH_NLRI(mp_reach)) => {
+                mp_reach.announced_routes.push(NLRIEncoding::IP(prefix))
+            }
+            _ => update.announced_routes.push(NLRIEncoding::IP(prefix)),
+        }
+    }
+    groups
+        .iter()
+        .flat_map(|update| update.split(max_size, capabilities))
+        .collect()
+}
+
+// Whether the attribute sets hold the same attributes, regardless of their order
+fn same_attributes(attributes: &[PathAttribute], other: &[PathAttribute]) -> bool {
+    attributes.len() == other.len()
+        && attributes.iter().all(|attribute| other.contains(attribute))
+        && other.iter().all(|attribute| attributes.contains(attribute))
+}
+
+// Where a route goes in the UPDATE messages built by `UpdatePacker`
+enum Placement<'a> {
+    Withdrawn,
+    Announced,
+    Unreach(AFI, SAFI),
+    Reach(&'a MPReachNLRI),
+}
+
+// Fills UPDATE messages holding the same path attributes with routes, up to a maximum size.
+// Sizes are tracked as routes are added, assuming extended lengths for MP attributes.
+struct UpdatePacker<'a> {
+    template: Update,
+    template_size: usize,
+    max_size: usize,
+    capabilities: &'a Capabilities,
+    current: Update,
+    current_size: usize,
+    updates: Vec<Update>,
+}
+
+impl<'a> UpdatePacker<'a> {
+    fn new(
+        attributes: Vec<PathAttribute>,
+        max_size: usize,
+        capabilities: &'a Capabilities,
+    ) -> UpdatePacker<'a> {
+        let template = Update::new(vec![], attributes, vec![]);
+        let mut bytes = vec![];
+        // Encoding errors resurface when encoding the messages
+        let _ = template.encode_with_capabilities(&mut bytes, capabilities);
+        let template_size = bytes.len() + BGP_MIN_MESSAGE_SIZE;
+        UpdatePacker {
+            current: template.clone(),
+            current_size: template_size,
+            template,
+            template_size,
+            max_size,
+            capabilities,
+            updates: vec![],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.current.withdrawn_routes.is_empty()
+            && self.current.announced_routes.is_empty()
+            && self.current.attributes.len() == self.template.attributes.len()
+    }
+
+    // The size of the MP attribute that has to be added to the current message for the route
+    fn overhead(&self, placement: &Placement) -> usize {
+        let attributes = &self.current.attributes[self.template.attributes.len()..];
+        match placement {
+            Placement::Withdrawn | Placement::Announced => 0,
+            Placement::Unreach(afi, safi) => {
+                let present = attributes.iter().any(|attribute| match attribute {
+                    PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+                        mp_unreach.afi == *afi && mp_unreach.safi == *safi
+                    }
+                    _ => false,
+                });
+                // Attribute header, AFI & SAFI
+                if present {
+                    0
+                } else {
+                    4 + 3
+                }
+            }
+            Placement::Reach(reach) => {
+                let present = attributes.iter().any(|attribute| match attribute {
+                    PathAttribute::MP_REACH_NLRI(mp_reach) => {
+                        mp_reach.afi == reach.afi
+                            && mp_reach.safi == reach.safi
+                            && mp_reach.next_hop == reach.next_hop
+                    }
+                    _ => false,
+                });
+                // Attribute header, AFI, SAFI, next hop & reserved byte
+                if present {
+                    0
+                } else {
+                    4 + 3 + 1 + reach.next_hop.len() + 1
+                }
+            }
+        }
+    }
+
+    // Whether the current message holds an MP attribute of the kind needed for the route,
+    // but of another family (or next hop), which can't be added to
+    fn holds_other_mp_attribute(&self, placement: &Placement) -> bool {
+        let attributes = &self.current.attributes[self.template.attributes.len()..];
+        attributes
+            .iter()
+            .any(|attribute| match (placement, attribute) {
+                (Placement::Unreach(afi, safi), PathAttribute::MP_UNREACH_NLRI(mp_unreach)) => {
+                    (mp_unreach.afi, mp_unreach.safi) != (*afi, *safi)
+                }
+                (Placement::Reach(reach), PathAttribute::MP_REACH_NLRI(mp_reach)) => {
+                    (mp_reach.afi, mp_reach.safi, &mp_reach.next_hop)
+                        != (reach.afi, reach.safi, &reach.next_hop)
+                }
+                _ => false,
+            })
+    }
+
+    fn push(&mut self, route: &NLRIEncoding, placement: Placement) {
+        let (route, add_path) = match placement {
+            Placement::Withdrawn | Placement::Announced => (
+                route.clone(),
+                self.capabilities.sends_path_ids(AFI::IPV4, SAFI::Unicast),
+            ),
+            Placement::Unreach(afi, safi) => (
+                route.as_withdrawal(),
+                self.capabilities.sends_path_ids(afi, safi),
+            ),
+            Placement::Reach(mp_reach) => (
+                route.clone(),
+                self.capabilities
+                    .sends_path_ids(mp_reach.afi, mp_reach.safi),
+            ),
+        };
+        let mut bytes = vec![];
+        let _ = route.encode_with_add_path(&mut bytes, add_path || route.path_id().is_some());
+
+        let mut overhead = self.overhead(&placement);
+        // A message holds a single MP attribute of each kind, whose family can't change
+        let conflicts = overhead > 0 && self.holds_other_mp_attribute(&placement);
+        if !self.is_empty()
+            && (conflicts || self.current_size + overhead + bytes.len() > self.max_size)
+        {
+            self.flush();
+            overhead = self.overhead(&placement);
+        }
+        self.current_size += overhead + bytes.len();
+
+        match placement {
+            Placement::Withdrawn => self.current.withdrawn_routes.push(route),
+            Placement::Announced => self.current.announced_routes.push(route),
+            Placement::Unreach(afi, safi) => {
+                if overhead > 0 {
+                    self.current
+                        .attributes
+                        .push(PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                            afi,
+                            safi,
+                            withdrawn_routes: vec![],
+                        })));
+                }
+                for attribute in self.current.attributes.iter_mut().rev() {
+                    match attribute {
+                        PathAttribute::MP_UNREACH_NLRI(mp_unreach)
+                            if mp_unreach.afi == afi && mp_unreach.safi == safi =>
+                        {
+                            mp_unreach.withdrawn_routes.push(route);
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Placement::Reach(reach) => {
+                if overhead > 0 {
+                    self.current
+                        .attributes
+                        .push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI {
+                            announced_routes: vec![],
+                            ..reach.clone()
+                        })));
+                }
+                for attribute in self.current.attributes.iter_mut().rev() {
+                    match attribute {
+                        PathAttribute::MP_REACH_NLRI(mp_reach)
+                            if mp_reach.afi == reach.afi
+                                && mp_reach.safi == reach.safi
+                                && mp_reach.next_hop == reach.next_hop =>
+                        {
+                            mp_reach.announced_routes.push(route);
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.is_empty() {
+            let update = std::mem::replace(&mut self.current, self.template.clone());
+            self.updates.push(update);
+        }
+        self.current_size = self.template_size;
+    }
+
+    fn finish(mut self) -> Vec<Update> {
+        self.flush();
+        self.updates
+    }
+}
+
+/// Renders the update similar to `show ip bgp`: announced routes with their next hop, followed
+/// by the other path attributes and the withdrawn routes. The alternate flag (`{:#}`) puts every
+/// part on its own line, indenting the path attributes. End-of-RIB markers are shown as such.
+///
+/// ```
+/// use bgp_rs::{ASPath, NLRIEncoding, PathAttribute, Segment, Update};
+///
+/// let update = Update::new(
+///     vec![],
+///     vec![
+///         PathAttribute::AS_PATH(ASPath {
+///             segments: vec![Segment::AS_SEQUENCE(vec![65000, 65001])],
+///         }),
+///         PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+///         PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+///     ],
+///     vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 24).into())],
+/// );
+/// assert_eq!(
+///     update.to_string(),
+///     "10.0.0.0/24 via 192.0.2.1, AS path 65000 65001, communities 65000:100"
+/// );
+/// assert_eq!(
+///     format!("{:#}", update),
+///     "10.0.0.0/24 via 192.0.2.1\n    AS path 65000 65001\n    communities 65000:100"
+/// );
+/// ```
+impl Display for Update {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        if let Some((afi, safi)) = self.end_of_rib_family() {
+            return write!(f, "End-of-RIB {} {}", afi, safi);
+        }
+        let mut routes: Vec<String> = vec![];
+        let mut attributes: Vec<String> = vec![];
+        let mut withdrawn: Vec<String> = vec![];
+
+        let next_hop = self
+            .attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                PathAttribute::NEXT_HOP(next_hop) => Some(next_hop),
+                _ => None,
+            });
+        if !self.announced_routes.is_empty() {
+            let announced: Vec<String> = self
+                .announced_routes
+                .iter()
+                .map(|route| route.to_string())
+                .collect();
+            match next_hop {
+                Some(next_hop) => routes.push(format!("{} via {}", announced.join(" "), next_hop)),
+                None => routes.push(announced.join(" ")),
+            }
+        }
+        if !self.withdrawn_routes.is_empty() {
+            let routes: Vec<String> = self
+                .withdrawn_routes
+                .iter()
+                .map(|route| route.to_string())
+                .collect();
+            withdrawn.push(format!("withdrawn {}", routes.join(" ")));
+        }
+        for attribute in &self.attributes {
+            match attribute {
+                PathAttribute::NEXT_HOP(_) if !self.announced_routes.is_empty() => (),
+                PathAttribute::MP_REACH_NLRI(_) => routes.push(attribute.to_string()),
+                PathAttribute::MP_UNREACH_NLRI(_) => withdrawn.push(attribute.to_string()),
+                _ => attributes.push(attribute.to_string()),
+            }
+        }
+        if f.alternate() {
+            let attributes = attributes
+                .iter()
+                .map(|attribute| format!("    {}", attribute));
+            let lines: Vec<String> = routes
+                .into_iter()
+                .chain(attributes)
+                .chain(withdrawn)
+                .collect();
+            write!(f, "{}", lines.join("\n"))
+        } else {
+            let parts: Vec<String> = routes
+                .into_iter()
+                .chain(attributes)
+                .chain(withdrawn)
+                .collect();
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// A recoverable problem encountered by `Update::parse_lossy`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseWarning {
+    /// A path attribute that could not be decoded was skipped.
+    SkippedAttribute {
+        /// The type code of the attribute
+        code: u8,
+        /// Description of the error
+        error: String,
+    },
+    /// The remaining path attributes could not be delimited and were skipped.
+    TruncatedAttributes {
+        /// Description of the error
+        error: String,
+    },
+    /// The NLRI were (true) or were not (false) detected to carry ADD-PATH Path Identifiers,
+    /// contrary to the capabilities used for parsing.
+    AddPathDetected(bool),
+    /// The remaining NLRI of a section could not be parsed and were skipped.
+    TruncatedNLRI {
+        /// The section of the UPDATE message holding the NLRI
+        section: NLRISection,
+        /// Description of the error
+        error: String,
+    },
+}
+
+/// The sections of an UPDATE message holding NLRI.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NLRISection {
+    /// Withdrawn Routes
+    Withdrawn,
+    /// Network Layer Reachability Information
+    Announced,
+}
+
+impl Display for ParseWarning {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ParseWarning::SkippedAttribute { code, error } => {
+                write!(f, "Skipped path attribute {}: {}", code, error)
+            }
+            ParseWarning::TruncatedAttributes { error } => {
+                write!(f, "Skipped remaining path attributes: {}", error)
+            }
+            ParseWarning::AddPathDetected(true) => write!(f, "Detected ADD-PATH Path Identifiers"),
+            ParseWarning::AddPathDetected(false) => {
+                write!(f, "Detected NLRI without ADD-PATH Path Identifiers")
+            }
+            ParseWarning::TruncatedNLRI { section, error } => {
+                write!(f, "Skipped remaining {:?} NLRI: {}", section, error)
+            }
+        }
+    }
+}
+
+/// A rule broken by the path attributes of an UPDATE message, found by `Update::validate`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UpdateViolation {
+    /// ORIGIN or AS_PATH is missing from an UPDATE announcing routes,
+    /// or NEXT_HOP is missing from an UPDATE announcing routes in its NLRI.
+    MissingWellKnownAttribute(Identifier),
+    /// The attribute appears more than once, such as a second MP_REACH_NLRI.
+    DuplicateAttribute(Identifier),
+    /// The Optional, Transitive or Partial flag doesn't match the attribute type.
+    AttributeFlags {
+        /// The type code of the attribute
+        code: u8,
+        /// The flags of the attribute
+        flags: u8,
+        /// The attribute as it was received
+        attribute: Vec<u8>,
+    },
+    /// NEXT_HOP is present in an UPDATE that doesn't announce any routes.
+    UnexpectedNextHop,
+    /// MP_REACH_NLRI or MP_UNREACH_NLRI hold an AFI/SAFI that was not negotiated.
+    UnsupportedFamily {
+        /// The Address Family Identifier of the attribute
+        afi: AFI,
+        /// The Subsequent Address Family Identifier of the attribute
+        safi: SAFI,
+    },
+}
+
+impl UpdateViolation {
+    /// The UPDATE Message Error subcode of this violation.
+    pub fn subcode(&self) -> UpdateMessageSubcode {
+        match self {
+            UpdateViolation::MissingWellKnownAttribute(_) => {
+                UpdateMessageSubcode::MissingWellKnownAttribute
+            }
+            UpdateViolation::DuplicateAttribute(_) | UpdateViolation::UnexpectedNextHop => {
+                UpdateMessageSubcode::MalformedAttributeList
+            }
+            UpdateViolation::AttributeFlags { .. } => UpdateMessageSubcode::AttributeFlagsError,
+            UpdateViolation::UnsupportedFamily { .. } => {
+                UpdateMessageSubcode::OptionalAttributeError
+            }
+        }
+    }
+}
+
+impl ToNotification for UpdateViolation {
+    fn to_notification(&self) -> Notification {
+        // RFC4271 6.3: the type code of the missing attribute, or the erroneous attribute
+        let data = match self {
+            UpdateViolation::MissingWellKnownAttribute(identifier) => vec![*identifier as u8],
+            UpdateViolation::AttributeFlags { attribute, .. } => attribute.clone(),
+            _ => vec![],
+        };
+        Notification::from_code(NotificationCode::UpdateMessage(self.subcode()), data)
+    }
+}
+
+impl Display for UpdateViolation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            UpdateViolation::MissingWellKnownAttribute(identifier) => {
+                write!(f, "Missing well-known attribute {:?}", identifier)
             }
-        } else {
-            while cursor.position() < withdraw_len as u64 {
-                withdrawn_routes.push(NLRIEncoding::IP(Prefix::parse(&mut cursor, AFI::IPV4)?));
+            UpdateViolation::DuplicateAttribute(identifier) => {
+                write!(f, "Path attribute {:?} appears more than once", identifier)
+            }
+            UpdateViolation::AttributeFlags { code, flags, .. } => {
+                write!(
+                    f,
+                    "Invalid flags {:#04x} for path attribute {}",
+                    flags, code
+                )
+            }
+            UpdateViolation::UnexpectedNextHop => {
+                write!(f, "NEXT_HOP in an UPDATE without announced routes")
+            }
+            UpdateViolation::UnsupportedFamily { afi, safi } => {
+                write!(
+                    f,
+                    "MP attribute of {} {}, which was not negotiated",
+                    afi, safi
+                )
             }
         }
+    }
+}
 
-        // ----------------------------
-        // Read path attributes
-        // ----------------------------
-        let length = stream.read_u16::<BigEndian>()? as usize;
-        if length > nlri_length {
-            return Err(Error::new(
-                ErrorKind::Other,
-                format!(
-                    "Got bogus attributes length {} < msg len {} - withdraw len {}",
-                    length, nlri_length, withdraw_len
-                ),
-            ));
-        }
-        let mut buffer = vec![0; length];
-        stream.read_exact(&mut buffer)?;
-        nlri_length -= length;
-
-        let mut attributes: Vec<PathAttribute> = Vec::with_capacity(8);
-        let mut cursor = Cursor::new(buffer);
-        while cursor.position() < length as u64 {
-            let attribute = match PathAttribute::parse(&mut cursor, capabilities) {
-                Ok(a) => a,
-                Err(e) => match e.kind() {
-                    ErrorKind::UnexpectedEof => return Err(e),
-                    _ => continue,
-                },
-            };
-            attributes.push(attribute);
-        }
+// Reserves the length of a section, returning where it starts
+fn start_section(buf: &mut Vec<u8>) -> usize {
+    buf.extend_from_slice(&[0, 0]);
+    buf.len()
+}
 
-        // ----------------------------
-        // Read NLRI
-        // ----------------------------
-        let mut buffer = vec![0; nlri_length as usize];
+// Writes the length of a section started with start_section
+fn end_section(buf: &mut [u8], start: usize) {
+    let length = (buf.len() - start) as u16;
+    buf[start - 2..start].copy_from_slice(&length.to_be_bytes());
+}
 
-        stream.read_exact(&mut buffer)?;
-        let mut cursor = Cursor::new(buffer);
-        let mut announced_routes: Vec<NLRIEncoding> = Vec::with_capacity(4);
+// The withdrawn routes, path attributes and NLRI sections of an UPDATE message
+type Sections<'a> = (&'a [u8], &'a [u8], &'a [u8]);
 
-        while cursor.position() < nlri_length as u64 {
-            if util::detect_add_path_prefix(&mut cursor, 32)? {
-                let path_id = cursor.read_u32::<BigEndian>()?;
-                let prefix = Prefix::parse(&mut cursor, AFI::IPV4)?;
-                announced_routes.push(NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)));
-            } else {
-                announced_routes.push(NLRIEncoding::IP(Prefix::parse(&mut cursor, AFI::IPV4)?));
-            }
-        }
+// Splits the message following an UPDATE header into its sections
+fn split_sections(message: &[u8]) -> Result<Sections<'_>, Error> {
+    if message.len() < 4 {
+        let length = message.len() as u16 + 19;
+        return Err(notification_error(
+            NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength),
+            length.to_be_bytes().to_vec(),
+            format!("Header had bogus length {} < 23", length),
+        ));
+    }
+    let mut nlri_length = message.len() - 4;
+    let mut body = message;
 
-        Ok(Update {
-            withdrawn_routes,
-            attributes,
-            announced_routes,
-        })
+    let withdraw_len = body.read_u16::<BigEndian>()? as usize;
+    if withdraw_len > nlri_length {
+        return Err(notification_error(
+            NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList),
+            vec![],
+            format!(
+                "Got bogus withdraw length {} < msg len {}",
+                withdraw_len, nlri_length
+            ),
+        ));
     }
+    let (withdrawn, mut body) = body.split_at(withdraw_len);
+    nlri_length -= withdraw_len;
 
-    /// Update message to bytes
-    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
-        // Create one buf to reuse for each Update attribute
-        let mut temp_buf: Vec<u8> = Vec::with_capacity(8);
+    let length = body.read_u16::<BigEndian>()? as usize;
+    if length > nlri_length {
+        return Err(notification_error(
+            NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList),
+            vec![],
+            format!(
+                "Got bogus attributes length {} < msg len {} - withdraw len {}",
+                length, nlri_length, withdraw_len
+            ),
+        ));
+    }
+    let (attributes, nlri) = body.split_at(length);
+    Ok((withdrawn, attributes, nlri))
+}
 
-        let mut unreach_nlri: HashMap<(AFI, SAFI), Vec<NLRIEncoding>> = HashMap::new();
-        for withdrawal in &self.withdrawn_routes {
-            if withdrawal.is_ipv4() {
-                withdrawal.encode(&mut temp_buf)?;
-            } else {
-                // Encode into MP_UNREACH_NLRI
-                let nlris = unreach_nlri
-                    .entry((withdrawal.afi(), withdrawal.safi()))
-                    .or_insert_with(Vec::new);
-                nlris.push(withdrawal.clone());
+// Parses the Withdrawn Routes section, which holds IPv4 Unicast routes
+fn parse_withdrawn_routes(
+    buffer: &[u8],
+    capabilities: &Capabilities,
+    options: &ParseOptions,
+    lossy: bool,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<Vec<NLRIEncoding>, Error> {
+    let ipv4_add_path = capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast);
+    let mut withdrawn_routes: Vec<NLRIEncoding> = Vec::with_capacity(0);
+    let mut cursor = Cursor::new(buffer);
+    while cursor.position() < buffer.len() as u64 {
+        check_nlri_limit(withdrawn_routes.len(), options).map_err(invalid_network_field)?;
+        match parse_ipv4_route(&mut cursor, ipv4_add_path) {
+            Ok(route) => withdrawn_routes.push(route),
+            Err(e) if lossy => {
+                trace_event!(debug, error = %e, "Truncated withdrawn routes");
+                warnings.push(ParseWarning::TruncatedNLRI {
+                    section: NLRISection::Withdrawn,
+                    error: e.to_string(),
+                });
+                break;
             }
+            Err(e) => return Err(invalid_network_field(e)),
         }
-        buf.write_u16::<BigEndian>(temp_buf.len() as u16)?;
-        buf.write_all(&temp_buf)?;
-        temp_buf.clear();
-
-        // Path Attributes
-        for attribute in &self.attributes {
-            attribute.encode(&mut temp_buf)?;
-        }
-        for ((afi, safi), unreach_nlris) in unreach_nlri.into_iter() {
-            let pa = PathAttribute::MP_UNREACH_NLRI(MPUnreachNLRI {
-                afi,
-                safi,
-                withdrawn_routes: unreach_nlris,
-            });
-            pa.encode(&mut temp_buf)?;
-        }
-        buf.write_u16::<BigEndian>(temp_buf.len() as u16)?;
-        buf.write_all(&temp_buf)?;
-        temp_buf.clear();
-
-        // NLRI
-        for route in &self.announced_routes {
-            route.encode(&mut temp_buf)?;
-        }
-        buf.write_all(&temp_buf)
     }
+    Ok(withdrawn_routes)
+}
 
-    /// Retrieves the first PathAttribute that matches the given identifier.
-    pub fn get(&self, identifier: Identifier) -> Option<&PathAttribute> {
-        for a in &self.attributes {
-            if a.id() == identifier {
-                return Some(a);
+// Parses the NLRI section, which holds IPv4 Unicast routes
+fn parse_announced_routes(
+    buffer: &[u8],
+    capabilities: &Capabilities,
+    options: &ParseOptions,
+    lossy: bool,
+    warnings: &mut Vec<ParseWarning>,
+) -> Result<Vec<NLRIEncoding>, Error> {
+    let ipv4_add_path = capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast);
+    let mut announced_routes: Vec<NLRIEncoding> = Vec::with_capacity(4);
+    let mut cursor = Cursor::new(buffer);
+    while cursor.position() < buffer.len() as u64 {
+        check_nlri_limit(announced_routes.len(), options).map_err(invalid_network_field)?;
+        let route = util::detect_add_path_prefix(&mut cursor, 32).and_then(|add_path| {
+            if add_path != ipv4_add_path
+                && !warnings.contains(&ParseWarning::AddPathDetected(add_path))
+            {
+                trace_event!(
+                    debug,
+                    add_path,
+                    "ADD-PATH detected contrary to the capabilities"
+                );
+                warnings.push(ParseWarning::AddPathDetected(add_path));
+            }
+            parse_ipv4_route(&mut cursor, add_path)
+        });
+        match route {
+            Ok(route) => announced_routes.push(route),
+            Err(e) if lossy => {
+                trace_event!(debug, error = %e, "Truncated announced routes");
+                warnings.push(ParseWarning::TruncatedNLRI {
+                    section: NLRISection::Announced,
+                    error: e.to_string(),
+                });
+                break;
             }
+            Err(e) => return Err(invalid_network_field(e)),
         }
-        None
     }
+    Ok(announced_routes)
+}
 
-    /// Checks if this UPDATE message contains announced prefixes.
-    pub fn is_announcement(&self) -> bool {
-        if !self.announced_routes.is_empty() || self.get(Identifier::MP_REACH_NLRI).is_some() {
-            return true;
-        }
-        false
+// Parses a route of the Withdrawn Routes or NLRI section, preceded by a Path Identifier
+// with ADD-PATH
+fn parse_ipv4_route<T: AsRef<[u8]>>(
+    cursor: &mut Cursor<T>,
+    add_path: bool,
+) -> Result<NLRIEncoding, Error> {
+    if add_path {
+        let path_id = cursor.read_u32::<BigEndian>()?;
+        let prefix = Prefix::parse(cursor, AFI::IPV4)?;
+        Ok(NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)))
+    } else {
+        Prefix::parse(cursor, AFI::IPV4).map(NLRIEncoding::IP)
     }
+}
 
-    /// Checks if this UPDATE message contains withdrawn routes..
-    pub fn is_withdrawal(&self) -> bool {
-        if !self.withdrawn_routes.is_empty() || self.get(Identifier::MP_UNREACH_NLRI).is_some() {
-            return true;
-        }
-        false
+// Errors when an UPDATE message holds more attributes than allowed by ParseOptions
+fn too_many_attributes(max_attributes: usize) -> Error {
+    notification_error(
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList),
+        vec![],
+        format!(
+            "Number of attributes exceeds the maximum of {}",
+            max_attributes
+        ),
+    )
+}
+
+// Errors delimiting the path attributes of an UPDATE message are a Malformed Attribute List
+fn malformed_attribute_list(error: Error) -> Error {
+    with_notification(
+        error,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList),
+        vec![],
+    )
+}
+
+// Fails when another NLRI would exceed the limit of ParseOptions
+pub(crate) fn check_nlri_limit(count: usize, options: &ParseOptions) -> Result<(), Error> {
+    let max_nlri = options.max_nlri;
+    if count >= max_nlri {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("Number of NLRI exceeds the maximum of {}", max_nlri),
+        ));
     }
+    Ok(())
+}
 
-    /// Moves the MP_REACH and MP_UNREACH NLRI into the NLRI.
-    pub fn normalize(&mut self) {
-        // Move the MP_REACH_NLRI attribute in the NLRI.
-        let identifier = match self.get(Identifier::MP_REACH_NLRI) {
-            Some(PathAttribute::MP_REACH_NLRI(routes)) => Some(routes.announced_routes.clone()),
-            _ => None,
-        };
-        if let Some(routes) = identifier {
-            self.announced_routes.extend(routes)
-        }
+// The routes of the NLRI sections, which are IPv4 Unicast unless built otherwise
+fn with_family(routes: &[NLRIEncoding]) -> impl Iterator<Item = (AFI, SAFI, &NLRIEncoding)> {
+    routes.iter().map(|route| {
+        let (afi, safi) = route.family();
+        (afi, safi, route)
+    })
+}
 
-        // Move the MP_REACH_NLRI attribute in the NLRI.
-        let identifier = match self.get(Identifier::MP_UNREACH_NLRI) {
-            Some(PathAttribute::MP_UNREACH_NLRI(routes)) => Some(routes.withdrawn_routes.clone()),
-            _ => None,
-        };
-        if let Some(routes) = identifier {
-            self.withdrawn_routes.extend(routes)
+// The routes whose AFI/SAFI derived from their encoding is the given family
+fn of_family(routes: &[NLRIEncoding], family: (AFI, SAFI)) -> Vec<NLRIEncoding> {
+    routes
+        .iter()
+        .filter(|route| route.family() == family)
+        .cloned()
+        .collect()
+}
+
+// Keeps the routes not seen yet, adding them to those seen
+fn retain_first(routes: &mut Vec<NLRIEncoding>, seen: &mut Vec<NLRIEncoding>) {
+    routes.retain(|route| {
+        if seen.contains(route) {
+            return false;
         }
-    }
+        seen.push(route.clone());
+        true
+    });
+}
+
+// Attribute errors ending the parsing of an UPDATE message, unless it is parsed lossily
+fn is_fatal_attribute_error(error: &Error) -> bool {
+    notification_of(error).map_or(false, |error| match error.notification.code {
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeFlagsError)
+        | NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError) => true,
+        _ => false,
+    })
+}
+
+// Errors in the NLRI sections of an UPDATE message are reported as Invalid Network Field
+fn invalid_network_field(error: Error) -> Error {
+    with_notification(
+        error,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField),
+        vec![],
+    )
 }
 
+/// The label of withdrawn labeled routes. Withdrawals carry a placeholder instead of the label
+/// (0x800000 for compatibility with RFC3107, or implicit-null), which is ignored on receipt (RFC8277).
+pub const WITHDRAWN_LABEL: u32 = 0x8_0000;
+
 /// Represents NLRIEncodings present in the NRLI section of an UPDATE message.
 #[derive(Debug, Clone, Eq, PartialEq)]
 #[allow(non_camel_case_types)]
@@ -244,11 +2098,24 @@ pub enum NLRIEncoding {
     IP_VPN_MPLS((u64, Prefix, u32)),
 
     /// Encodings that specify a VPLS endpoint as specified in RFC4761. (RD, VE ID, Label Block Offset, Label Block Size, Label Base)
+    /// The Label Base holds the 3-byte field, with the label in its high-order 20 bits.
     L2VPN((u64, u16, u16, u16, u32)),
 
+    /// Multicast VPN routes as specified in RFC6514.
+    MVPN(MvpnRoute),
+
     /// Flowspec Traffic Filter Specification - RFC5575
     #[cfg(feature = "flowspec")]
     FLOWSPEC(Vec<FlowspecFilter>),
+
+    /// A route decoded by a decoder registered in `ParseOptions::nlri_decoders`, such as a
+    /// route of an experimental SAFI.
+    CUSTOM(Box<dyn CustomNLRI>),
+
+    /// The routes of a family that is not supported, undecoded. (AFI, SAFI, NLRI)
+    /// As the boundaries of its routes are unknown, it holds the NLRI of the MP_REACH_NLRI or
+    /// MP_UNREACH_NLRI attribute as a whole.
+    OPAQUE((AFI, SAFI, Vec<u8>)),
 }
 
 impl NLRIEncoding {
@@ -261,46 +2128,165 @@ impl NLRIEncoding {
         }
     }
 
-    /// Derive the AFI for this NLRI
+    /// Derive the AFI for this NLRI, from its prefix. Flowspec NLRI take the AFI of their
+    /// prefix filters, and are assumed to be IPv4 when they don't filter on prefixes. MVPN NLRI
+    /// take the AFI of their addresses the same way. The AFI of the MP attribute holding a route
+    /// takes precedence, see `Update::announced` & `Update::withdrawn`.
     pub fn afi(&self) -> AFI {
         use NLRIEncoding::*;
         match &self {
-            IP(prefix) => prefix.protocol,
+            IP(prefix)
+            | IP_WITH_PATH_ID((prefix, _))
+            | IP_MPLS((prefix, _))
+            | IP_MPLS_WITH_PATH_ID((prefix, _, _))
+            | IP_VPN_MPLS((_, prefix, _)) => prefix.protocol,
+            L2VPN(_) => AFI::L2VPN,
+            MVPN(route) => route.afi(),
+            CUSTOM(route) => route.afi(),
+            OPAQUE((afi, _, _)) => *afi,
             #[cfg(feature = "flowspec")]
-            FLOWSPEC(_) => AFI::IPV4, // TODO: match ipv6 from filters
-            _ => unimplemented!(),
+            FLOWSPEC(filters) => filters
+                .iter()
+                .filter_map(|filter| match filter {
+                    FlowspecFilter::DestinationPrefix(prefix)
+                    | FlowspecFilter::SourcePrefix(prefix) => Some(prefix.protocol),
+                    _ => None,
+                })
+                .next()
+                .unwrap_or(AFI::IPV4),
         }
     }
 
-    /// Derive the SAFI for this NLRI
+    /// Derive the SAFI for this NLRI, from its encoding. IP prefixes are assumed to be unicast,
+    /// as those of the Multicast SAFI are encoded the same way. The SAFI of the MP attribute
+    /// holding a route takes precedence, see `Update::announced` & `Update::withdrawn`.
     pub fn safi(&self) -> SAFI {
         use NLRIEncoding::*;
         match &self {
-            IP(_) => SAFI::Unicast,
+            IP(_) | IP_WITH_PATH_ID(_) => SAFI::Unicast,
+            IP_MPLS(_) | IP_MPLS_WITH_PATH_ID(_) => SAFI::Mpls,
+            IP_VPN_MPLS(_) => SAFI::MplsVpn,
+            L2VPN(_) => SAFI::Vpls,
+            MVPN(_) => SAFI::MulticastVpn,
+            CUSTOM(route) => route.safi(),
+            OPAQUE((_, safi, _)) => *safi,
             #[cfg(feature = "flowspec")]
             FLOWSPEC(_) => SAFI::Flowspec,
-            _ => unimplemented!(),
         }
     }
 
-    /// Encode NLRI to bytes
+    /// This NLRI as carried by MP_UNREACH_NLRI, with any label replaced by `WITHDRAWN_LABEL`.
+    /// A withdrawn labeled route is equal to the withdrawal of its announcement.
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, WITHDRAWN_LABEL};
+    ///
+    /// let announced = NLRIEncoding::IP_MPLS((("10.0.0.0".parse().unwrap(), 8).into(), 100));
+    /// let withdrawn = NLRIEncoding::IP_MPLS((("10.0.0.0".parse().unwrap(), 8).into(), WITHDRAWN_LABEL));
+    /// assert_eq!(announced.as_withdrawal(), withdrawn);
+    /// ```
+    pub fn as_withdrawal(&self) -> NLRIEncoding {
+        use NLRIEncoding::*;
+        match self {
+            IP_MPLS((prefix, _)) => IP_MPLS((prefix.clone(), WITHDRAWN_LABEL)),
+            IP_MPLS_WITH_PATH_ID((prefix, _, path_id)) => {
+                IP_MPLS_WITH_PATH_ID((prefix.clone(), WITHDRAWN_LABEL, *path_id))
+            }
+            IP_VPN_MPLS((rd, prefix, _)) => IP_VPN_MPLS((*rd, prefix.clone(), WITHDRAWN_LABEL)),
+            nlri => nlri.clone(),
+        }
+    }
+
+    /// The address family of this NLRI, as carried by MP_REACH_NLRI & MP_UNREACH_NLRI.
+    /// ```
+    /// use bgp_rs::{NLRIEncoding, AFI, SAFI};
+    ///
+    /// let route = NLRIEncoding::IP_MPLS((("2001:db8::".parse().unwrap(), 32).into(), 100));
+    /// assert_eq!(route.family(), (AFI::IPV6, SAFI::Mpls));
+    /// ```
+    pub fn family(&self) -> (AFI, SAFI) {
+        (self.afi(), self.safi())
+    }
+
+    /// The Path Identifier of this NLRI (RFC7911), if any.
+    pub fn path_id(&self) -> Option<u32> {
+        match self {
+            NLRIEncoding::IP_WITH_PATH_ID((_, path_id))
+            | NLRIEncoding::IP_MPLS_WITH_PATH_ID((_, _, path_id)) => Some(*path_id),
+            _ => None,
+        }
+    }
+
+    /// Encode NLRI to bytes, including the Path Identifier of NLRI that carry one.
     pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        self.encode_with_add_path(buf, self.path_id().is_some())
+    }
+
+    /// Encode NLRI to bytes for an AFI/SAFI with or without ADD-PATH (RFC7911), see
+    /// `Capabilities::sends_path_ids`. With ADD-PATH every NLRI is preceded by a Path Identifier,
+    /// which is 0 for NLRI without one. Without ADD-PATH the Path Identifier is left out.
+    /// ```
+    /// use bgp_rs::NLRIEncoding;
+    ///
+    /// let route = NLRIEncoding::IP_MPLS((("10.0.0.0".parse().unwrap(), 8).into(), 16));
+    /// let mut bytes = vec![];
+    /// route.encode_with_add_path(&mut bytes, true).unwrap();
+    /// assert_eq!(bytes, vec![0, 0, 0, 0, 32, 0, 1, 1, 10]);
+    ///
+    /// let route = NLRIEncoding::IP_MPLS_WITH_PATH_ID((("10.0.0.0".parse().unwrap(), 8).into(), 16, 7));
+    /// let mut bytes = vec![];
+    /// route.encode_with_add_path(&mut bytes, true).unwrap();
+    /// assert_eq!(bytes, vec![0, 0, 0, 7, 32, 0, 1, 1, 10]);
+    /// ```
+    pub fn encode_with_add_path(&self, buf: &mut impl Write, add_path: bool) -> Result<(), Error> {
+        // Custom & opaque routes hold their Path Identifier (if any) themselves
+        let undecoded = match self {
+            NLRIEncoding::CUSTOM(_) | NLRIEncoding::OPAQUE(_) => true,
+            _ => false,
+        };
+        if add_path && !undecoded {
+            buf.write_u32::<BigEndian>(self.path_id().unwrap_or(0))?;
+        }
         match self {
-            NLRIEncoding::IP(prefix) => {
+            NLRIEncoding::IP(prefix) | NLRIEncoding::IP_WITH_PATH_ID((prefix, _)) => {
                 buf.write_u8(prefix.length)?;
-                buf.write_all(&prefix.masked_octets())
+                buf.write_all(prefix.masked_octets())
             }
-            NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)) => {
-                buf.write_u32::<BigEndian>(*path_id)?;
-                buf.write_u8(prefix.length)?;
-                buf.write_all(&prefix.masked_octets())
+            NLRIEncoding::IP_MPLS((prefix, label))
+            | NLRIEncoding::IP_MPLS_WITH_PATH_ID((prefix, label, _)) => {
+                // The length includes the label
+                buf.write_u8(labeled_length(prefix, 24)?)?;
+                encode_label(buf, *label)?;
+                buf.write_all(prefix.masked_octets())
             }
             NLRIEncoding::IP_VPN_MPLS((rd, prefix, label)) => {
-                // TODO: the parsing in nlri.rs may not be correct
-                buf.write_u32::<BigEndian>(*label)?;
+                // The length includes the label & route distinguisher
+                buf.write_u8(labeled_length(prefix, 24 + 64)?)?;
+                encode_label(buf, *label)?;
+                buf.write_u64::<BigEndian>(*rd)?;
+                buf.write_all(prefix.masked_octets())
+            }
+            NLRIEncoding::L2VPN((rd, ve_id, label_block_offset, label_block_size, label_base)) => {
+                if *label_base > 0xff_ffff {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("Label base {:#x} does not fit in 3 bytes", label_base),
+                    ));
+                }
+                // The length of the fields below, in bytes
+                buf.write_u16::<BigEndian>(17)?;
                 buf.write_u64::<BigEndian>(*rd)?;
-                buf.write_all(&prefix.prefix)
+                buf.write_u16::<BigEndian>(*ve_id)?;
+                buf.write_u16::<BigEndian>(*label_block_offset)?;
+                buf.write_u16::<BigEndian>(*label_block_size)?;
+                buf.write_u24::<BigEndian>(*label_base)
             }
+            NLRIEncoding::MVPN(route) => route.encode(buf),
+            NLRIEncoding::CUSTOM(route) => {
+                let mut bytes = Vec::with_capacity(16);
+                route.encode(&mut bytes)?;
+                buf.write_all(&bytes)
+            }
+            NLRIEncoding::OPAQUE((_, _, routes)) => buf.write_all(routes),
             #[cfg(feature = "flowspec")]
             NLRIEncoding::FLOWSPEC(filters) => {
                 let mut bytes: Vec<u8> = Vec::with_capacity(16);
@@ -310,13 +2296,79 @@ impl NLRIEncoding {
                 buf.write_u8(bytes.len() as u8)?;
                 buf.write_all(&bytes)
             }
-            _ => unimplemented!("{:?}", self),
+        }
+    }
+}
+
+fn labeled_length(prefix: &Prefix, label_bits: u8) -> Result<u8, Error> {
+    prefix.length.checked_add(label_bits).ok_or_else(|| {
+        Error::new(
+            ErrorKind::Other,
+            format!("Bogus labeled prefix length {}", prefix.length),
+        )
+    })
+}
+
+// A label is 20 bits, followed by 3 reserved bits & the bottom-of-stack bit
+fn encode_label(buf: &mut impl Write, label: u32) -> Result<(), Error> {
+    if label > 0xf_ffff {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("Label {} does not fit in 20 bits", label),
+        ));
+    }
+    if label == WITHDRAWN_LABEL {
+        // The placeholder is sent without the bottom-of-stack bit
+        return buf.write_u24::<BigEndian>(0x80_0000);
+    }
+    buf.write_u24::<BigEndian>(label << 4 | 1)
+}
+
+impl Display for NLRIEncoding {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            NLRIEncoding::IP(prefix) => write!(f, "{}", prefix),
+            NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)) => {
+                write!(f, "{} path-id {}", prefix, path_id)
+            }
+            NLRIEncoding::IP_MPLS((prefix, label)) => write!(f, "{} label {}", prefix, label),
+            NLRIEncoding::IP_MPLS_WITH_PATH_ID((prefix, label, path_id)) => {
+                write!(f, "{} label {} path-id {}", prefix, label, path_id)
+            }
+            NLRIEncoding::IP_VPN_MPLS((rd, prefix, label)) => {
+                write!(f, "RD {} {} label {}", rd, prefix, label)
+            }
+            NLRIEncoding::MVPN(route) => write!(f, "mvpn {}", route),
+            NLRIEncoding::OPAQUE((afi, safi, routes)) => {
+                write!(f, "{} {} NLRI 0x{}", afi, safi, util::to_hex(routes))
+            }
+            #[cfg(feature = "flowspec")]
+            NLRIEncoding::FLOWSPEC(filters) => {
+                let filters: Vec<String> =
+                    filters.iter().map(|filter| filter.to_string()).collect();
+                write!(f, "flowspec {}", filters.join(" "))
+            }
+            encoding => write!(f, "{:?}", encoding),
         }
     }
 }
 
 /// Represents a generic prefix. For example an IPv4 prefix or IPv6 prefix.
-#[derive(Clone, Eq, PartialEq)]
+///
+/// Prefixes are ordered by AFI, then address, then length. Octets missing from `prefix` are
+/// treated as zero, so a prefix parsed from NLRI equals the same prefix built from an address.
+/// ```
+/// use bgp_rs::Prefix;
+/// let mut prefixes: Vec<Prefix> = vec![
+///     "10.0.0.0/24".parse().unwrap(),
+///     "2001:db8::/32".parse().unwrap(),
+///     "10.0.0.0/8".parse().unwrap(),
+/// ];
+/// prefixes.sort();
+/// assert_eq!(prefixes[0].to_string(), "10.0.0.0/8");
+/// assert_eq!(prefixes[2].to_string(), "2001:db8::/32");
+/// ```
+#[derive(Clone)]
 pub struct Prefix {
     /// IP version for prefix (v4|v6)
     pub protocol: AFI,
@@ -326,37 +2378,49 @@ pub struct Prefix {
     pub prefix: Vec<u8>,
 }
 
-impl From<&Prefix> for IpAddr {
-    fn from(prefix: &Prefix) -> Self {
+impl TryFrom<&Prefix> for IpAddr {
+    type Error = Error;
+
+    /// The address of an IPv4 or IPv6 prefix, failing for other AFIs or too many octets.
+    fn try_from(prefix: &Prefix) -> Result<Self, Self::Error> {
         match prefix.protocol {
-            AFI::IPV4 => {
+            AFI::IPV4 if prefix.prefix.len() <= 4 => {
                 let mut buffer: [u8; 4] = [0; 4];
                 buffer[..prefix.prefix.len()].clone_from_slice(&prefix.prefix[..]);
-                IpAddr::from(buffer)
+                Ok(IpAddr::from(buffer))
             }
-            AFI::IPV6 => {
+            AFI::IPV6 if prefix.prefix.len() <= 16 => {
                 let mut buffer: [u8; 16] = [0; 16];
                 buffer[..prefix.prefix.len()].clone_from_slice(&prefix.prefix[..]);
-                IpAddr::from(buffer)
+                Ok(IpAddr::from(buffer))
             }
-            AFI::L2VPN => unimplemented!(),
-            AFI::BGPLS => unimplemented!(),
+            afi => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "{} prefix of {} octets has no IP address",
+                    afi,
+                    prefix.prefix.len()
+                ),
+            )),
         }
     }
 }
 
-impl From<&Prefix> for (IpAddr, u8) {
-    /// Convert from IpAddr/CIDR to Prefix
+impl TryFrom<&Prefix> for (IpAddr, u8) {
+    type Error = Error;
+
+    /// Convert from Prefix to IpAddr/CIDR
     /// ```
+    /// use std::convert::TryFrom;
     /// use std::net::{IpAddr, Ipv4Addr};
     /// use bgp_rs::Prefix;
     /// let prefix: Prefix = ("5.5.5.5".parse().unwrap(), 32).into();
-    /// let (addr, length) = (&prefix).into();
+    /// let (addr, length) = <(IpAddr, u8)>::try_from(&prefix).unwrap();
     /// assert_eq!(addr, IpAddr::from(Ipv4Addr::new(5, 5, 5, 5)));
     /// assert_eq!(length, 32);
     /// ```
-    fn from(prefix: &Prefix) -> (IpAddr, u8) {
-        (IpAddr::from(prefix), prefix.length)
+    fn try_from(prefix: &Prefix) -> Result<(IpAddr, u8), Self::Error> {
+        Ok((IpAddr::try_from(prefix)?, prefix.length))
     }
 }
 
@@ -381,15 +2445,131 @@ impl From<(IpAddr, u8)> for Prefix {
     }
 }
 
+#[cfg(feature = "ipnet")]
+impl From<ipnet::Ipv4Net> for Prefix {
+    fn from(net: ipnet::Ipv4Net) -> Prefix {
+        (IpAddr::V4(net.addr()), net.prefix_len()).into()
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl From<ipnet::Ipv6Net> for Prefix {
+    fn from(net: ipnet::Ipv6Net) -> Prefix {
+        (IpAddr::V6(net.addr()), net.prefix_len()).into()
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl From<ipnet::IpNet> for Prefix {
+    fn from(net: ipnet::IpNet) -> Prefix {
+        (net.addr(), net.prefix_len()).into()
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl std::convert::TryFrom<&Prefix> for ipnet::IpNet {
+    type Error = Error;
+
+    /// Convert from Prefix to IpNet, failing for prefixes that aren't IPv4 or IPv6
+    /// ```
+    /// use std::convert::TryInto;
+    /// use bgp_rs::Prefix;
+    /// use ipnet::IpNet;
+    ///
+    /// let prefix: Prefix = "10.0.0.0/24".parse().unwrap();
+    /// let net: IpNet = (&prefix).try_into().unwrap();
+    /// assert_eq!(net, "10.0.0.0/24".parse::<IpNet>().unwrap());
+    /// assert_eq!(Prefix::from(net), prefix);
+    /// ```
+    fn try_from(prefix: &Prefix) -> Result<Self, Self::Error> {
+        let invalid = |_| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Bogus prefix length {}", prefix.length),
+            )
+        };
+        ipnet::IpNet::new(IpAddr::try_from(prefix)?, prefix.length).map_err(invalid)
+    }
+}
+
+#[cfg(feature = "ipnet")]
+impl std::convert::TryFrom<Prefix> for ipnet::IpNet {
+    type Error = Error;
+
+    fn try_from(prefix: Prefix) -> Result<Self, Self::Error> {
+        std::convert::TryFrom::try_from(&prefix)
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = Error;
+
+    /// Parse a prefix in CIDR notation, e.g. "10.0.0.0/24" or "2001:db8::/32"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid prefix: {}", s));
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = parts
+            .next()
+            .and_then(|addr| addr.parse().ok())
+            .ok_or_else(invalid)?;
+        let length: u8 = parts
+            .next()
+            .and_then(|length| length.parse().ok())
+            .ok_or_else(invalid)?;
+        let max_length = if addr.is_ipv4() { 32 } else { 128 };
+        if length > max_length {
+            return Err(invalid());
+        }
+        Ok((addr, length).into())
+    }
+}
+
+impl PartialEq for Prefix {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Prefix {}
+
+impl PartialOrd for Prefix {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Prefix {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for Prefix {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state)
+    }
+}
+
+/// Prefixes without an IP address, of other AFIs or holding too many octets, are shown with
+/// their AFI and octets in hexadecimal instead.
 impl Display for Prefix {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        write!(f, "{}/{}", IpAddr::from(self), self.length)
+        match IpAddr::try_from(self) {
+            Ok(address) => write!(f, "{}/{}", address, self.length),
+            Err(_) => write!(
+                f,
+                "{} 0x{}/{}",
+                self.protocol,
+                util::to_hex(&self.prefix),
+                self.length
+            ),
+        }
     }
 }
 
 impl Debug for Prefix {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        write!(f, "{}/{}", IpAddr::from(self), self.length)
+        Display::fmt(self, f)
     }
 }
 
@@ -406,23 +2586,52 @@ impl Prefix {
         (self.length as usize + 7) / 8
     }
 
+    // The fields compared by Eq, Ord & Hash, with the octets padded to the longest address
+    fn key(&self) -> (AFI, [u8; 16], u8) {
+        let mut octets = [0; 16];
+        for (octet, value) in octets.iter_mut().zip(&self.prefix) {
+            *octet = *value;
+        }
+        (self.protocol, octets, self.length)
+    }
+
+    /// Whether the prefix covers the other prefix: both are of the same AFI, the other is as
+    /// specific or more specific, and their addresses match over the length of this prefix.
+    /// ```
+    /// use bgp_rs::Prefix;
+    /// let prefix: Prefix = "10.0.0.0/8".parse().unwrap();
+    /// assert!(prefix.contains(&"10.1.0.0/16".parse().unwrap()));
+    /// assert!(prefix.contains(&prefix));
+    /// assert!(!prefix.contains(&"11.0.0.0/16".parse().unwrap()));
+    /// assert!(!prefix.contains(&"10.0.0.0/7".parse().unwrap()));
+    /// ```
+    pub fn contains(&self, other: &Prefix) -> bool {
+        if self.protocol != other.protocol || self.length > other.length {
+            return false;
+        }
+        let (_, octets, _) = self.key();
+        let (_, other_octets, _) = other.key();
+        let whole = usize::from(self.length / 8).min(octets.len());
+        if octets[..whole] != other_octets[..whole] {
+            return false;
+        }
+        let bits = self.length % 8;
+        bits == 0 || whole == octets.len() || {
+            let mask = !0u8 << (8 - bits);
+            octets[whole] & mask == other_octets[whole] & mask
+        }
+    }
+
     /// Get a slice of the prefix octets covered by the prefix mask
     /// Useful for encoding the prefix in NLRI
     pub fn masked_octets(&self) -> &[u8] {
         &self.prefix[..self.octet_length()]
     }
 
-    fn parse(stream: &mut impl Read, protocol: AFI) -> Result<Prefix, Error> {
+    pub(crate) fn parse(stream: &mut impl Read, protocol: AFI) -> Result<Prefix, Error> {
         let length = stream.read_u8()?;
 
-        if length
-            > match protocol {
-                AFI::IPV4 => 32,
-                AFI::IPV6 => 128,
-                AFI::L2VPN => unimplemented!(),
-                AFI::BGPLS => unimplemented!(),
-            }
-        {
+        if usize::from(length) > protocol.empty_buffer()?.len() * 8 {
             return Err(Error::new(
                 ErrorKind::Other,
                 format!("Bogus prefix length {}", length),
@@ -455,6 +2664,66 @@ fn test_prefix_masked_octets() {
     assert_eq!(&prefix.to_string(), "1.1.1.1/18");
 }
 
+#[test]
+fn test_prefix_from_str() {
+    let prefix: Prefix = "10.0.0.0/24".parse().unwrap();
+    assert_eq!(prefix.protocol, AFI::IPV4);
+    assert_eq!(prefix.length, 24);
+    assert_eq!(prefix.masked_octets(), &[10, 0, 0]);
+    let prefix: Prefix = "2001:db8::/32".parse().unwrap();
+    assert_eq!(prefix.protocol, AFI::IPV6);
+    assert_eq!(&prefix.to_string(), "2001:db8::/32");
+
+    assert!("10.0.0.0".parse::<Prefix>().is_err());
+    assert!("10.0.0.0/33".parse::<Prefix>().is_err());
+    assert!("2001:db8::/129".parse::<Prefix>().is_err());
+    assert!("10.0.0/8".parse::<Prefix>().is_err());
+    assert!("10.0.0.0/8/8".parse::<Prefix>().is_err());
+}
+
+#[test]
+fn test_prefix_ordering() {
+    use std::collections::HashSet;
+
+    // Parsed NLRI only holds the masked octets
+    let parsed = Prefix::parse(&mut std::io::Cursor::new(vec![16, 10, 1]), AFI::IPV4).unwrap();
+    let prefix: Prefix = "10.1.0.0/16".parse().unwrap();
+    assert_eq!(parsed, prefix);
+    let set: HashSet<Prefix> = vec![parsed, prefix].into_iter().collect();
+    assert_eq!(set.len(), 1);
+
+    let mut prefixes: Vec<Prefix> = vec!["::/0", "10.1.0.0/24", "10.1.0.0/16", "9.0.0.0/8"]
+        .into_iter()
+        .map(|prefix| prefix.parse().unwrap())
+        .collect();
+    prefixes.sort();
+    let sorted: Vec<String> = prefixes.iter().map(|prefix| prefix.to_string()).collect();
+    assert_eq!(
+        sorted,
+        vec!["9.0.0.0/8", "10.1.0.0/16", "10.1.0.0/24", "::/0"]
+    );
+}
+
+#[test]
+#[cfg(feature = "ipnet")]
+fn test_prefix_ipnet() {
+    use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+    use std::convert::TryInto;
+
+    let prefix = Prefix::from("10.1.0.0/16".parse::<Ipv4Net>().unwrap());
+    assert_eq!(&prefix.to_string(), "10.1.0.0/16");
+    let prefix = Prefix::from("2001:db8::/32".parse::<Ipv6Net>().unwrap());
+    assert_eq!(&prefix.to_string(), "2001:db8::/32");
+
+    // Parsed NLRI only holds the masked octets
+    let parsed = Prefix::parse(&mut std::io::Cursor::new(vec![16, 10, 1]), AFI::IPV4).unwrap();
+    let net: IpNet = parsed.try_into().unwrap();
+    assert_eq!(net, "10.1.0.0/16".parse::<IpNet>().unwrap());
+
+    let bogus = Prefix::new(AFI::IPV4, 33, vec![10, 1, 0, 0]);
+    assert!(TryInto::<IpNet>::try_into(bogus).is_err());
+}
+
 #[test]
 fn test_prefix_bad_length() {
     let mut buf = std::io::Cursor::new(vec![35, 5, 5, 5, 5]);
@@ -462,3 +2731,178 @@ fn test_prefix_bad_length() {
     let mut buf = std::io::Cursor::new(vec![145, 48, 1, 0, 16, 0, 16, 0]);
     assert!(Prefix::parse(&mut buf, AFI::IPV6).is_err());
 }
+
+#[test]
+fn test_prefix_without_address() {
+    let bogus = Prefix::new(AFI::IPV4, 40, vec![10, 0, 0, 0, 1]);
+    assert!(IpAddr::try_from(&bogus).is_err());
+    assert_eq!(bogus.to_string(), "IPv4 0x0A00000001/40");
+    assert_eq!(format!("{:?}", bogus), "IPv4 0x0A00000001/40");
+
+    let l2vpn = Prefix::new(AFI::L2VPN, 8, vec![1]);
+    assert!(<(IpAddr, u8)>::try_from(&l2vpn).is_err());
+    assert_eq!(l2vpn.to_string(), "L2VPN 0x01/8");
+}
+
+#[test]
+fn test_update_attribute_mutation() {
+    let attributes = vec![
+        PathAttribute::COMMUNITY(vec![1]),
+        PathAttribute::LOCAL_PREF(100),
+        PathAttribute::COMMUNITY(vec![2]),
+    ];
+    let mut update = Update {
+        withdrawn_routes: vec![],
+        attributes: attributes.clone(),
+        announced_routes: vec![],
+        raw_attributes: vec![RawPathAttribute {
+            flags: 0x40,
+            code: 5,
+            value: vec![0, 0, 0, 100],
+        }],
+        received_attributes: attributes,
+    };
+    assert_eq!(update.get_all(Identifier::COMMUNITY).count(), 2);
+    assert!(update.raw_attributes().is_some());
+
+    match update.insert(PathAttribute::LOCAL_PREF(200)) {
+        Some(PathAttribute::LOCAL_PREF(100)) => (),
+        attribute => panic!("Unexpected {:?}", attribute),
+    }
+    // The modification is encoded instead of the received attributes
+    assert!(update.raw_attributes().is_none());
+    assert!(update.insert(PathAttribute::MULTI_EXIT_DISC(5)).is_none());
+
+    assert_eq!(update.remove(Identifier::COMMUNITY).len(), 2);
+    assert!(update.remove(Identifier::COMMUNITY).is_empty());
+    let identifiers: Vec<_> = update.attributes.iter().map(|a| a.id()).collect();
+    assert_eq!(
+        identifiers,
+        vec![Identifier::LOCAL_PREF, Identifier::MULTI_EXIT_DISC]
+    );
+}
+
+#[test]
+fn test_update_withdrawn_families() {
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV4,
+                safi: SAFI::Mpls,
+                withdrawn_routes: vec![NLRIEncoding::IP_MPLS((
+                    ("10.1.0.0".parse().unwrap(), 16).into(),
+                    WITHDRAWN_LABEL,
+                ))],
+            })),
+        ],
+        vec![],
+    );
+    let families: Vec<_> = update
+        .withdrawn()
+        .map(|(afi, safi, _route)| (afi, safi))
+        .collect();
+    assert_eq!(
+        families,
+        vec![(AFI::IPV4, SAFI::Unicast), (AFI::IPV4, SAFI::Mpls)]
+    );
+    assert_eq!(update.announced().count(), 0);
+}
+
+#[test]
+fn test_update_next_hop() {
+    let route = NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into());
+    let vpn_route = NLRIEncoding::IP_VPN_MPLS((100, ("10.1.0.0".parse().unwrap(), 16).into(), 16));
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV4,
+                SAFI::MplsVpn,
+                "192.0.2.2".parse().unwrap(),
+                vec![vpn_route.clone()],
+            ))),
+        ],
+        vec![route.clone()],
+    );
+    assert_eq!(
+        update.next_hop(&route),
+        Some(NextHop::Address("192.0.2.1".parse().unwrap()))
+    );
+    assert_eq!(
+        update
+            .next_hop(&vpn_route)
+            .map(|next_hop| next_hop.to_string()),
+        Some("192.0.2.2".to_string())
+    );
+    let other = NLRIEncoding::IP(("10.2.0.0".parse().unwrap(), 16).into());
+    assert_eq!(update.next_hop(&other), None);
+}
+
+#[test]
+fn test_update_to_mp() {
+    let route = NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into());
+    let mut update = Update::new(
+        vec![],
+        vec![PathAttribute::ORIGIN(Origin::IGP)],
+        vec![route.clone()],
+    );
+    // Left alone unless MP-BGP was negotiated for IPv4 Unicast
+    update.to_mp(&Capabilities::default()).unwrap();
+    assert_eq!(update.announced_routes.len(), 1);
+
+    let mut capabilities = Capabilities::default();
+    capabilities
+        .MP_BGP_SUPPORT
+        .insert((AFI::IPV4, SAFI::Unicast));
+    assert!(update.to_mp(&capabilities).is_err());
+    assert_eq!(update.announced_routes, vec![route.clone()]);
+
+    update.insert(PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()));
+    update.to_mp(&capabilities).unwrap();
+    let mut bytes = vec![];
+    update.encode(&mut bytes).unwrap();
+    let header = Header::new(MessageType::Update, 19 + bytes.len() as u16);
+    let mut parsed = Update::parse(&header, &mut Cursor::new(bytes), &capabilities).unwrap();
+    assert!(parsed.announced_routes.is_empty());
+    assert_eq!(
+        parsed.next_hop(&route),
+        Some(NextHop::Address("192.0.2.1".parse().unwrap()))
+    );
+    parsed.normalize();
+    assert_eq!(parsed.announced_routes, vec![route]);
+}
+
+#[test]
+fn test_update_set_next_hop() {
+    let route = NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into());
+    let ipv6_route = NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into());
+    let mut mp_reach = MPReachNLRI::new(
+        AFI::IPV6,
+        SAFI::Unicast,
+        "2001:db8::1".parse().unwrap(),
+        vec![ipv6_route.clone()],
+    );
+    mp_reach
+        .set_next_hop(
+            &["2001:db8::1".parse().unwrap(), "fe80::1".parse().unwrap()],
+            &Capabilities::default(),
+        )
+        .unwrap();
+    let mut update = Update::new(
+        vec![],
+        vec![PathAttribute::MP_REACH_NLRI(Box::new(mp_reach))],
+        vec![route.clone()],
+    );
+    update.set_next_hop("192.0.2.1".parse().unwrap());
+    assert_eq!(
+        update.next_hop(&route),
+        Some(NextHop::Address("192.0.2.1".parse().unwrap()))
+    );
+    assert_eq!(
+        update.next_hop(&ipv6_route),
+        Some(NextHop::Address("::ffff:192.0.2.1".parse().unwrap()))
+    );
+}
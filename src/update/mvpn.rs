@@ -0,0 +1,458 @@
+use crate::AFI;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use std::fmt;
+use std::io::{Cursor, Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// A route of the MCAST-VPN NLRI (SAFI 5), as specified in RFC6514.
+///
+/// Multicast sources & groups are `None` for the wildcards of RFC6625.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MvpnRoute {
+    /// Intra-AS I-PMSI A-D route (type 1), advertising membership of an MVPN
+    IntraAsIPmsiAd {
+        /// Route Distinguisher
+        rd: u64,
+        /// The IP address of the originating PE
+        originating_router: IpAddr,
+    },
+    /// Inter-AS I-PMSI A-D route (type 2), advertising membership of an MVPN for an AS
+    InterAsIPmsiAd {
+        /// Route Distinguisher
+        rd: u64,
+        /// The AS of the originating ASBR
+        source_as: u32,
+    },
+    /// S-PMSI A-D route (type 3), binding a customer multicast flow to a provider tunnel
+    SPmsiAd {
+        /// Route Distinguisher
+        rd: u64,
+        /// Customer multicast source
+        source: Option<IpAddr>,
+        /// Customer multicast group
+        group: Option<IpAddr>,
+        /// The IP address of the originating PE
+        originating_router: IpAddr,
+    },
+    /// Leaf A-D route (type 4), joining the provider tunnel of another A-D route
+    LeafAd {
+        /// The A-D route being responded to
+        route_key: Box<MvpnRoute>,
+        /// The IP address of the originating PE
+        originating_router: IpAddr,
+    },
+    /// Source Active A-D route (type 5), advertising an active customer multicast source
+    SourceActiveAd {
+        /// Route Distinguisher
+        rd: u64,
+        /// Customer multicast source
+        source: Option<IpAddr>,
+        /// Customer multicast group
+        group: Option<IpAddr>,
+    },
+    /// Shared Tree Join C-multicast route (type 6), a customer (*, G) join
+    SharedTreeJoin {
+        /// Route Distinguisher of the upstream PE
+        rd: u64,
+        /// The AS of the upstream PE
+        source_as: u32,
+        /// Customer rendezvous point
+        source: Option<IpAddr>,
+        /// Customer multicast group
+        group: Option<IpAddr>,
+    },
+    /// Source Tree Join C-multicast route (type 7), a customer (S, G) join
+    SourceTreeJoin {
+        /// Route Distinguisher of the upstream PE
+        rd: u64,
+        /// The AS of the upstream PE
+        source_as: u32,
+        /// Customer multicast source
+        source: Option<IpAddr>,
+        /// Customer multicast group
+        group: Option<IpAddr>,
+    },
+}
+
+impl MvpnRoute {
+    /// The route type code.
+    pub fn route_type(&self) -> u8 {
+        match self {
+            MvpnRoute::IntraAsIPmsiAd { .. } => 1,
+            MvpnRoute::InterAsIPmsiAd { .. } => 2,
+            MvpnRoute::SPmsiAd { .. } => 3,
+            MvpnRoute::LeafAd { .. } => 4,
+            MvpnRoute::SourceActiveAd { .. } => 5,
+            MvpnRoute::SharedTreeJoin { .. } => 6,
+            MvpnRoute::SourceTreeJoin { .. } => 7,
+        }
+    }
+
+    /// The AFI of the first address of this route, routes without addresses are IPv4.
+    pub fn afi(&self) -> AFI {
+        let address = match self {
+            MvpnRoute::IntraAsIPmsiAd {
+                originating_router, ..
+            } => Some(*originating_router),
+            MvpnRoute::InterAsIPmsiAd { .. } => None,
+            MvpnRoute::SPmsiAd {
+                source,
+                group,
+                originating_router,
+                ..
+            } => source.or(*group).or(Some(*originating_router)),
+            MvpnRoute::LeafAd { route_key, .. } => return route_key.afi(),
+            MvpnRoute::SourceActiveAd { source, group, .. }
+            | MvpnRoute::SharedTreeJoin { source, group, .. }
+            | MvpnRoute::SourceTreeJoin { source, group, .. } => source.or(*group),
+        };
+        match address {
+            Some(IpAddr::V6(_)) => AFI::IPV6,
+            _ => AFI::IPV4,
+        }
+    }
+
+    /// Parse a route, including its type & length.
+    pub fn parse(stream: &mut impl Read) -> Result<MvpnRoute, Error> {
+        let route_type = stream.read_u8()?;
+        let length = stream.read_u8()?;
+        let mut value = vec![0; usize::from(length)];
+        stream.read_exact(&mut value)?;
+
+        let mut cursor = Cursor::new(value);
+        let route = match route_type {
+            1 => MvpnRoute::IntraAsIPmsiAd {
+                rd: cursor.read_u64::<BigEndian>()?,
+                originating_router: parse_router(&mut cursor)?,
+            },
+            2 => MvpnRoute::InterAsIPmsiAd {
+                rd: cursor.read_u64::<BigEndian>()?,
+                source_as: cursor.read_u32::<BigEndian>()?,
+            },
+            3 => MvpnRoute::SPmsiAd {
+                rd: cursor.read_u64::<BigEndian>()?,
+                source: parse_multicast_address(&mut cursor)?,
+                group: parse_multicast_address(&mut cursor)?,
+                originating_router: parse_router(&mut cursor)?,
+            },
+            4 => MvpnRoute::LeafAd {
+                route_key: Box::new(MvpnRoute::parse(&mut cursor)?),
+                originating_router: parse_router(&mut cursor)?,
+            },
+            5 => MvpnRoute::SourceActiveAd {
+                rd: cursor.read_u64::<BigEndian>()?,
+                source: parse_multicast_address(&mut cursor)?,
+                group: parse_multicast_address(&mut cursor)?,
+            },
+            6 | 7 => {
+                let rd = cursor.read_u64::<BigEndian>()?;
+                let source_as = cursor.read_u32::<BigEndian>()?;
+                let source = parse_multicast_address(&mut cursor)?;
+                let group = parse_multicast_address(&mut cursor)?;
+                if route_type == 6 {
+                    MvpnRoute::SharedTreeJoin {
+                        rd,
+                        source_as,
+                        source,
+                        group,
+                    }
+                } else {
+                    MvpnRoute::SourceTreeJoin {
+                        rd,
+                        source_as,
+                        source,
+                        group,
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Unsupported MVPN route type {}", route_type),
+                ));
+            }
+        };
+        if cursor.position() != u64::from(length) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Bogus length {} for MVPN route type {}", length, route_type),
+            ));
+        }
+        Ok(route)
+    }
+
+    /// Encode a route, including its type & length.
+    pub fn encode(&self, buf: &mut impl Write) -> Result<(), Error> {
+        let mut value: Vec<u8> = Vec::with_capacity(32);
+        match self {
+            MvpnRoute::IntraAsIPmsiAd {
+                rd,
+                originating_router,
+            } => {
+                value.write_u64::<BigEndian>(*rd)?;
+                encode_address(&mut value, *originating_router)?;
+            }
+            MvpnRoute::InterAsIPmsiAd { rd, source_as } => {
+                value.write_u64::<BigEndian>(*rd)?;
+                value.write_u32::<BigEndian>(*source_as)?;
+            }
+            MvpnRoute::SPmsiAd {
+                rd,
+                source,
+                group,
+                originating_router,
+            } => {
+                value.write_u64::<BigEndian>(*rd)?;
+                encode_multicast_address(&mut value, *source)?;
+                encode_multicast_address(&mut value, *group)?;
+                encode_address(&mut value, *originating_router)?;
+            }
+            MvpnRoute::LeafAd {
+                route_key,
+                originating_router,
+            } => {
+                route_key.encode(&mut value)?;
+                encode_address(&mut value, *originating_router)?;
+            }
+            MvpnRoute::SourceActiveAd { rd, source, group } => {
+                value.write_u64::<BigEndian>(*rd)?;
+                encode_multicast_address(&mut value, *source)?;
+                encode_multicast_address(&mut value, *group)?;
+            }
+            MvpnRoute::SharedTreeJoin {
+                rd,
+                source_as,
+                source,
+                group,
+            }
+            | MvpnRoute::SourceTreeJoin {
+                rd,
+                source_as,
+                source,
+                group,
+            } => {
+                value.write_u64::<BigEndian>(*rd)?;
+                value.write_u32::<BigEndian>(*source_as)?;
+                encode_multicast_address(&mut value, *source)?;
+                encode_multicast_address(&mut value, *group)?;
+            }
+        }
+        if value.len() > usize::from(std::u8::MAX) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("MVPN route of {} bytes is too long", value.len()),
+            ));
+        }
+        buf.write_u8(self.route_type())?;
+        buf.write_u8(value.len() as u8)?;
+        buf.write_all(&value)
+    }
+}
+
+// The originating router is the remainder of the route, either an IPv4 or IPv6 address
+fn parse_router(cursor: &mut Cursor<Vec<u8>>) -> Result<IpAddr, Error> {
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    match remaining {
+        4 => Ok(IpAddr::V4(Ipv4Addr::from(cursor.read_u32::<BigEndian>()?))),
+        16 => Ok(IpAddr::V6(Ipv6Addr::from(cursor.read_u128::<BigEndian>()?))),
+        _ => Err(Error::new(
+            ErrorKind::Other,
+            format!("Bogus originating router address of {} bytes", remaining),
+        )),
+    }
+}
+
+// Multicast sources & groups are prefixed with their length in bits, 0 being a wildcard
+fn parse_multicast_address(stream: &mut impl Read) -> Result<Option<IpAddr>, Error> {
+    match stream.read_u8()? {
+        0 => Ok(None),
+        32 => Ok(Some(IpAddr::V4(Ipv4Addr::from(
+            stream.read_u32::<BigEndian>()?,
+        )))),
+        128 => Ok(Some(IpAddr::V6(Ipv6Addr::from(
+            stream.read_u128::<BigEndian>()?,
+        )))),
+        length => Err(Error::new(
+            ErrorKind::Other,
+            format!("Bogus multicast address length {}", length),
+        )),
+    }
+}
+
+fn encode_address(buf: &mut impl Write, address: IpAddr) -> Result<(), Error> {
+    match address {
+        IpAddr::V4(address) => buf.write_all(&address.octets()),
+        IpAddr::V6(address) => buf.write_all(&address.octets()),
+    }
+}
+
+fn encode_multicast_address(buf: &mut impl Write, address: Option<IpAddr>) -> Result<(), Error> {
+    match address {
+        None => buf.write_u8(0),
+        Some(address) => {
+            buf.write_u8(if address.is_ipv4() { 32 } else { 128 })?;
+            encode_address(buf, address)
+        }
+    }
+}
+
+struct Wildcard(Option<IpAddr>);
+
+impl fmt::Display for Wildcard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(address) => write!(f, "{}", address),
+            None => write!(f, "*"),
+        }
+    }
+}
+
+impl fmt::Display for MvpnRoute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MvpnRoute::IntraAsIPmsiAd {
+                rd,
+                originating_router,
+            } => write!(
+                f,
+                "intra-as-i-pmsi-ad RD {} originator {}",
+                rd, originating_router
+            ),
+            MvpnRoute::InterAsIPmsiAd { rd, source_as } => {
+                write!(f, "inter-as-i-pmsi-ad RD {} AS{}", rd, source_as)
+            }
+            MvpnRoute::SPmsiAd {
+                rd,
+                source,
+                group,
+                originating_router,
+            } => write!(
+                f,
+                "s-pmsi-ad RD {} ({}, {}) originator {}",
+                rd,
+                Wildcard(*source),
+                Wildcard(*group),
+                originating_router
+            ),
+            MvpnRoute::LeafAd {
+                route_key,
+                originating_router,
+            } => write!(
+                f,
+                "leaf-ad [{}] originator {}",
+                route_key, originating_router
+            ),
+            MvpnRoute::SourceActiveAd { rd, source, group } => write!(
+                f,
+                "source-active-ad RD {} ({}, {})",
+                rd,
+                Wildcard(*source),
+                Wildcard(*group)
+            ),
+            MvpnRoute::SharedTreeJoin {
+                rd,
+                source_as,
+                source,
+                group,
+            } => write!(
+                f,
+                "shared-tree-join RD {} AS{} ({}, {})",
+                rd,
+                source_as,
+                Wildcard(*source),
+                Wildcard(*group)
+            ),
+            MvpnRoute::SourceTreeJoin {
+                rd,
+                source_as,
+                source,
+                group,
+            } => write!(
+                f,
+                "source-tree-join RD {} AS{} ({}, {})",
+                rd,
+                source_as,
+                Wildcard(*source),
+                Wildcard(*group)
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_mvpn_routes_roundtrip() {
+    let s_pmsi_ad = MvpnRoute::SPmsiAd {
+        rd: 100,
+        source: Some("10.0.0.1".parse().unwrap()),
+        group: Some("232.1.1.1".parse().unwrap()),
+        originating_router: "192.0.2.1".parse().unwrap(),
+    };
+    let routes = vec![
+        MvpnRoute::IntraAsIPmsiAd {
+            rd: 100,
+            originating_router: "2001:db8::1".parse().unwrap(),
+        },
+        MvpnRoute::InterAsIPmsiAd {
+            rd: 100,
+            source_as: 65000,
+        },
+        s_pmsi_ad.clone(),
+        MvpnRoute::LeafAd {
+            route_key: Box::new(s_pmsi_ad),
+            originating_router: "192.0.2.2".parse().unwrap(),
+        },
+        MvpnRoute::SourceActiveAd {
+            rd: 100,
+            source: Some("2001:db8::2".parse().unwrap()),
+            group: Some("ff3e::1".parse().unwrap()),
+        },
+        MvpnRoute::SharedTreeJoin {
+            rd: 100,
+            source_as: 65000,
+            source: Some("10.0.0.254".parse().unwrap()),
+            group: Some("239.1.1.1".parse().unwrap()),
+        },
+        MvpnRoute::SourceTreeJoin {
+            rd: 100,
+            source_as: 65000,
+            source: None,
+            group: None,
+        },
+    ];
+    for route in routes {
+        let mut encoded = vec![];
+        route.encode(&mut encoded).unwrap();
+        assert_eq!(usize::from(encoded[1]) + 2, encoded.len());
+        let parsed = MvpnRoute::parse(&mut Cursor::new(&encoded)).unwrap();
+        assert_eq!(parsed, route);
+    }
+}
+
+#[test]
+fn test_mvpn_source_tree_join() {
+    #[rustfmt::skip]
+    let data = vec![
+        7, 22, // type, length
+        0, 0, 0xfd, 0xe8, 0, 0, 0, 100, // RD 65000:100
+        0, 0, 0xfd, 0xe8, // AS65000
+        32, 10, 0, 0, 1, // source
+        32, 232, 1, 1, 1, // group
+    ];
+    let route = MvpnRoute::parse(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(
+        &route.to_string(),
+        "source-tree-join RD 279172874240100 AS65000 (10.0.0.1, 232.1.1.1)"
+    );
+    assert_eq!(route.afi(), AFI::IPV4);
+
+    // The length must cover the route exactly
+    let mut bad_length = data.clone();
+    bad_length[1] = 23;
+    bad_length.push(0);
+    assert!(MvpnRoute::parse(&mut Cursor::new(&bad_length)).is_err());
+    let mut bad_address = data;
+    bad_address[14] = 24;
+    assert!(MvpnRoute::parse(&mut Cursor::new(&bad_address)).is_err());
+}
@@ -1,12 +1,22 @@
 use byteorder::{BigEndian, ReadBytesExt};
 
+use std::any::Any;
+use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Formatter};
 use std::io::{self, Cursor, Error, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
 
 use crate::*;
 
+use super::{
+    invalid_network_field, malformed_attribute_list, parse_ipv4_route, split_sections,
+    too_many_attributes,
+};
+
 /// Used when announcing routes to non-IPv4 addresses.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MPReachNLRI {
     /// The Address Family Identifier of the routes being announced.
     pub afi: AFI,
@@ -22,16 +32,59 @@ pub struct MPReachNLRI {
 }
 
 impl MPReachNLRI {
+    /// Create a new MP_REACH_NLRI, encoding the next hop as required by the AFI/SAFI
+    /// (VPN SAFIs prefix the address with a zeroed Route Distinguisher).
+    /// ```
+    /// use bgp_rs::{MPReachNLRI, NLRIEncoding, AFI, SAFI};
+    ///
+    /// let mp_reach = MPReachNLRI::new(
+    ///     AFI::IPV6,
+    ///     SAFI::Unicast,
+    ///     "2001:db8::1".parse().unwrap(),
+    ///     vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+    /// );
+    /// assert_eq!(mp_reach.next_hop.len(), 16);
+    ///
+    /// let mp_reach = MPReachNLRI::new(AFI::IPV4, SAFI::MplsVpn, "10.0.0.1".parse().unwrap(), vec![]);
+    /// assert_eq!(mp_reach.next_hop, vec![0, 0, 0, 0, 0, 0, 0, 0, 10, 0, 0, 1]);
+    /// ```
+    pub fn new(
+        afi: AFI,
+        safi: SAFI,
+        next_hop: IpAddr,
+        announced_routes: Vec<NLRIEncoding>,
+    ) -> MPReachNLRI {
+        let mut next_hop_bytes = Vec::with_capacity(24);
+        push_next_hop_address(safi, next_hop, &mut next_hop_bytes);
+        MPReachNLRI {
+            afi,
+            safi,
+            next_hop: next_hop_bytes,
+            announced_routes,
+        }
+    }
+
     /// Parse MPUnreachNLRI information
     pub(crate) fn parse(
         stream: &mut impl Read,
         length: u16,
         capabilities: &Capabilities,
+        options: &ParseOptions,
     ) -> io::Result<MPReachNLRI> {
         let afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
         let safi = SAFI::try_from(stream.read_u8()?)?;
 
         let next_hop_length = stream.read_u8()?;
+        // AFI, SAFI, next hop length & reserved byte
+        if length < 5 + u16::from(next_hop_length) {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "Bogus MP_REACH_NLRI length {} for a next hop of {} bytes",
+                    length, next_hop_length
+                ),
+            ));
+        }
         let mut next_hop = vec![0; usize::from(next_hop_length)];
         stream.read_exact(&mut next_hop)?;
 
@@ -40,17 +93,13 @@ impl MPReachNLRI {
         // ----------------------------
         // Read NLRI
         // ----------------------------
-        let size = length - u16::from(5 + next_hop_length);
+        let size = length - (5 + u16::from(next_hop_length));
 
         let mut buffer = vec![0; usize::from(size)];
         stream.read_exact(&mut buffer)?;
         let mut cursor = Cursor::new(buffer);
 
-        let announced_routes = match afi {
-            AFI::IPV4 | AFI::IPV6 => parse_nlri(afi, safi, &capabilities, &mut cursor, size)?,
-            AFI::L2VPN => parse_l2vpn(&mut cursor)?,
-            AFI::BGPLS => unimplemented!(),
-        };
+        let announced_routes = parse_nlri(afi, safi, capabilities, options, &mut cursor, size)?;
 
         Ok(MPReachNLRI {
             afi,
@@ -61,21 +110,253 @@ impl MPReachNLRI {
     }
 
     /// Encode Multiprotocol Reach NLRI to bytes
-    pub fn encode(&self, mut buf: &mut impl Write) -> io::Result<()> {
-        buf.write_u16::<BigEndian>(self.afi as u16)?;
-        buf.write_u8(self.safi as u8)?;
+    pub fn encode(&self, buf: &mut impl Write) -> io::Result<()> {
+        self.encode_inner(buf, false)
+    }
+
+    /// Encode Multiprotocol Reach NLRI to bytes, with Path Identifiers when ADD-PATH was
+    /// negotiated for sending routes of this AFI/SAFI.
+    pub fn encode_with_capabilities(
+        &self,
+        buf: &mut impl Write,
+        capabilities: &Capabilities,
+    ) -> io::Result<()> {
+        self.encode_inner(buf, capabilities.sends_path_ids(self.afi, self.safi))
+    }
+
+    fn encode_inner(&self, buf: &mut impl Write, add_path: bool) -> io::Result<()> {
+        buf.write_u16::<BigEndian>(u16::from(self.afi))?;
+        buf.write_u8(u8::from(self.safi))?;
         buf.write_u8(self.next_hop.len() as u8)?;
         buf.write_all(&self.next_hop)?;
         buf.write_u8(0u8)?; // Reserved
         for nlri in &self.announced_routes {
-            nlri.encode(&mut buf)?;
+            // NLRI holding a Path Identifier keep it
+            nlri.encode_with_add_path(buf, add_path || nlri.path_id().is_some())?;
         }
         Ok(())
     }
+
+    /// Interprets the next hop as one or two IP addresses (an IPv6 next hop may also
+    /// carry a link-local address). For MPLS VPN the zeroed Route Distinguishers are skipped.
+    ///
+    /// An IPv6 next hop for IPv4 routes (RFC8950) is only accepted when the Extended Next Hop
+    /// Encoding capability was negotiated for this AFI/SAFI.
+    /// ```
+    /// use bgp_rs::{Capabilities, MPReachNLRI, AFI, SAFI};
+    /// use std::net::IpAddr;
+    ///
+    /// let mut mp_reach = MPReachNLRI {
+    ///     afi: AFI::IPV4,
+    ///     safi: SAFI::Unicast,
+    ///     next_hop: vec![],
+    ///     announced_routes: vec![],
+    /// };
+    /// let next_hop: IpAddr = "2001:db8::1".parse().unwrap();
+    /// assert!(mp_reach.set_next_hop(&[next_hop], &Capabilities::default()).is_err());
+    ///
+    /// let mut capabilities = Capabilities::default();
+    /// capabilities
+    ///     .EXTENDED_NEXT_HOP_ENCODING
+    ///     .insert((AFI::IPV4, SAFI::Unicast), AFI::IPV6);
+    /// mp_reach.set_next_hop(&[next_hop], &capabilities).unwrap();
+    /// assert_eq!(mp_reach.next_hop.len(), 16);
+    /// assert_eq!(mp_reach.next_hop_addresses(&capabilities).unwrap(), vec![next_hop]);
+    /// ```
+    pub fn next_hop_addresses(&self, capabilities: &Capabilities) -> io::Result<Vec<IpAddr>> {
+        let addresses = self.decode_next_hop()?;
+        check_next_hop_family(self.afi, self.safi, &addresses, capabilities)?;
+        Ok(addresses)
+    }
+
+    /// Splits the next hop into its addresses without checking them against the AFI.
+    pub(crate) fn decode_next_hop(&self) -> io::Result<Vec<IpAddr>> {
+        let is_vpn = self.safi == SAFI::MplsVpn;
+        let ranges: &[(usize, usize)] = match (is_vpn, self.next_hop.len()) {
+            (false, 4) => &[(0, 4)],
+            (false, 16) => &[(0, 16)],
+            (false, 32) => &[(0, 16), (16, 32)],
+            (true, 12) => &[(8, 12)],
+            (true, 24) => &[(8, 24)],
+            (true, 48) => &[(8, 24), (32, 48)],
+            (_, length) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Invalid next hop length for {}/{}: {}",
+                        self.afi, self.safi, length
+                    ),
+                ));
+            }
+        };
+        Ok(ranges
+            .iter()
+            .map(|&(start, end)| ip_from_bytes(&self.next_hop[start..end]))
+            .collect())
+    }
+
+    /// Sets the next hop from one or two IP addresses, using the encoding of this AFI/SAFI.
+    /// See `next_hop_addresses` for the accepted address families.
+    pub fn set_next_hop(
+        &mut self,
+        addresses: &[IpAddr],
+        capabilities: &Capabilities,
+    ) -> io::Result<()> {
+        check_next_hop_family(self.afi, self.safi, addresses, capabilities)?;
+        self.next_hop = encode_next_hop(self.safi, addresses)?;
+        Ok(())
+    }
+}
+
+/// The next hop of announced routes, as resolved by `Update::next_hop`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NextHop {
+    /// A single address, from NEXT_HOP or MP_REACH_NLRI
+    Address(IpAddr),
+    /// An IPv6 global address along with a link-local address (RFC2545)
+    LinkLocal {
+        /// The global address
+        global: Ipv6Addr,
+        /// The link-local address
+        link_local: Ipv6Addr,
+    },
+}
+
+impl NextHop {
+    /// The (global) address of the next hop.
+    pub fn address(&self) -> IpAddr {
+        match self {
+            NextHop::Address(address) => *address,
+            NextHop::LinkLocal { global, .. } => IpAddr::V6(*global),
+        }
+    }
+
+    pub(crate) fn from_addresses(addresses: &[IpAddr]) -> Option<NextHop> {
+        match addresses {
+            [address] => Some(NextHop::Address(*address)),
+            [IpAddr::V6(global), IpAddr::V6(link_local)] => Some(NextHop::LinkLocal {
+                global: *global,
+                link_local: *link_local,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Display for NextHop {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            NextHop::Address(address) => write!(f, "{}", address),
+            NextHop::LinkLocal { global, link_local } => write!(f, "{} ({})", global, link_local),
+        }
+    }
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> IpAddr {
+    if bytes.len() == 4 {
+        IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]))
+    } else {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(bytes);
+        IpAddr::V6(Ipv6Addr::from(octets))
+    }
+}
+
+/// An IPv6 next hop for IPv4 routes requires the Extended Next Hop Encoding capability
+fn check_next_hop_family(
+    afi: AFI,
+    safi: SAFI,
+    addresses: &[IpAddr],
+    capabilities: &Capabilities,
+) -> io::Result<()> {
+    if afi == AFI::IPV4
+        && addresses.iter().any(IpAddr::is_ipv6)
+        && capabilities.EXTENDED_NEXT_HOP_ENCODING.get(&(afi, safi)) != Some(&AFI::IPV6)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "IPv6 next hop for {}/{} requires the Extended Next Hop Encoding capability",
+                afi, safi
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Encodes next hop addresses, prefixing each with a zero Route Distinguisher for MPLS VPN
+fn encode_next_hop(safi: SAFI, addresses: &[IpAddr]) -> io::Result<Vec<u8>> {
+    match addresses {
+        [_] | [IpAddr::V6(_), IpAddr::V6(_)] => (),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Next hop must be a single address or an IPv6 global and link-local address",
+            ));
+        }
+    }
+    let mut next_hop = Vec::with_capacity(48);
+    for address in addresses {
+        push_next_hop_address(safi, *address, &mut next_hop);
+    }
+    Ok(next_hop)
+}
+
+pub(crate) fn push_next_hop_address(safi: SAFI, address: IpAddr, next_hop: &mut Vec<u8>) {
+    if safi == SAFI::MplsVpn {
+        next_hop.extend_from_slice(&[0u8; 8]);
+    }
+    match address {
+        IpAddr::V4(addr) => next_hop.extend_from_slice(&addr.octets()),
+        IpAddr::V6(addr) => next_hop.extend_from_slice(&addr.octets()),
+    }
+}
+
+/// Renders the announced routes followed by their next hop(s).
+///
+/// ```
+/// use bgp_rs::{MPReachNLRI, NLRIEncoding, AFI, SAFI};
+///
+/// let mp_reach = MPReachNLRI::new(
+///     AFI::IPV6,
+///     SAFI::Unicast,
+///     "2001:db8::1".parse().unwrap(),
+///     vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+/// );
+/// assert_eq!(mp_reach.to_string(), "2001:db8::/32 via 2001:db8::1");
+/// ```
+impl Display for MPReachNLRI {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let routes: Vec<String> = self
+            .announced_routes
+            .iter()
+            .map(|route| route.to_string())
+            .collect();
+        write!(f, "{}", routes.join(" "))?;
+        if self.next_hop.is_empty() {
+            return Ok(());
+        }
+        if !routes.is_empty() {
+            write!(f, " ")?;
+        }
+        match self.decode_next_hop() {
+            Ok(addresses) => {
+                let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+                write!(f, "via {}", addresses.join(" "))
+            }
+            Err(_) => {
+                write!(f, "via 0x")?;
+                for byte in &self.next_hop {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 /// Used when withdrawing routes to non-IPv4 addresses.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct MPUnreachNLRI {
     /// The Address Family Identifier of the routes being withdrawn.
     pub afi: AFI,
@@ -93,7 +374,14 @@ impl MPUnreachNLRI {
         stream: &mut impl Read,
         length: u16,
         capabilities: &Capabilities,
+        options: &ParseOptions,
     ) -> io::Result<MPUnreachNLRI> {
+        if length < 3 {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("Bogus MP_UNREACH_NLRI length {} < 3", length),
+            ));
+        }
         let afi = AFI::try_from(stream.read_u16::<BigEndian>()?)?;
         let safi = SAFI::try_from(stream.read_u8()?)?;
 
@@ -105,7 +393,10 @@ impl MPUnreachNLRI {
         let mut buffer = vec![0; usize::from(size)];
         stream.read_exact(&mut buffer)?;
         let mut cursor = Cursor::new(buffer);
-        let withdrawn_routes = parse_nlri(afi, safi, &capabilities, &mut cursor, size)?;
+        let withdrawn_routes = parse_nlri(afi, safi, capabilities, options, &mut cursor, size)?
+            .iter()
+            .map(NLRIEncoding::as_withdrawal)
+            .collect();
 
         Ok(MPUnreachNLRI {
             afi,
@@ -114,101 +405,514 @@ impl MPUnreachNLRI {
         })
     }
 
-    /// Encode Multiprotocol Reach NLRI to bytes
+    /// Encode Multiprotocol Unreach NLRI to bytes, labeled routes are withdrawn with the
+    /// `WITHDRAWN_LABEL` placeholder
     pub fn encode(&self, buf: &mut impl Write) -> io::Result<()> {
-        buf.write_u16::<BigEndian>(self.afi as u16)?;
-        buf.write_u8(self.safi as u8)?;
+        self.encode_inner(buf, false)
+    }
+
+    /// Encode Multiprotocol Unreach NLRI to bytes, with Path Identifiers when ADD-PATH was
+    /// negotiated for sending routes of this AFI/SAFI.
+    pub fn encode_with_capabilities(
+        &self,
+        buf: &mut impl Write,
+        capabilities: &Capabilities,
+    ) -> io::Result<()> {
+        self.encode_inner(buf, capabilities.sends_path_ids(self.afi, self.safi))
+    }
+
+    fn encode_inner(&self, buf: &mut impl Write, add_path: bool) -> io::Result<()> {
+        buf.write_u16::<BigEndian>(u16::from(self.afi))?;
+        buf.write_u8(u8::from(self.safi))?;
         for nlri in &self.withdrawn_routes {
-            nlri.encode(buf)?;
+            let nlri = nlri.as_withdrawal();
+            // NLRI holding a Path Identifier keep it
+            nlri.encode_with_add_path(buf, add_path || nlri.path_id().is_some())?;
         }
         Ok(())
     }
 }
 
-fn parse_l2vpn(buf: &mut impl Read) -> io::Result<Vec<NLRIEncoding>> {
-    let _len = buf.read_u16::<BigEndian>()?;
+impl Display for MPUnreachNLRI {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let routes: Vec<String> = self
+            .withdrawn_routes
+            .iter()
+            .map(|route| route.to_string())
+            .collect();
+        write!(f, "{}", routes.join(" "))
+    }
+}
+
+/// A route of a family that is decoded by a decoder registered in `NLRIDecoders`, so that
+/// experimental families (e.g. of the MUP or CAR SAFIs) can be handled without changes to this
+/// crate.
+///
+/// ```
+/// use std::any::Any;
+/// use std::io::Error;
+/// use bgp_rs::{Capabilities, CustomNLRI, NLRIEncoding, ParseOptions, PathAttribute, AFI, SAFI};
+///
+/// // A route holding an opaque key of 4 bytes
+/// #[derive(Clone, Debug)]
+/// struct KeyRoute(u32);
+///
+/// impl CustomNLRI for KeyRoute {
+///     fn afi(&self) -> AFI {
+///         AFI::IPV4
+///     }
+///     fn safi(&self) -> SAFI {
+///         SAFI::Other(250)
+///     }
+///     fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Error> {
+///         buf.extend_from_slice(&self.0.to_be_bytes());
+///         Ok(())
+///     }
+///     fn clone_box(&self) -> Box<dyn CustomNLRI> {
+///         Box::new(self.clone())
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// let mut options = ParseOptions::default();
+/// options
+///     .nlri_decoders
+///     .register(AFI::IPV4, SAFI::Other(250), |_afi, _safi, routes: &mut &[u8]| {
+///         let mut key = [0; 4];
+///         key.copy_from_slice(&routes[..4]);
+///         *routes = &routes[4..];
+///         Ok(Box::new(KeyRoute(u32::from_be_bytes(key))) as Box<dyn CustomNLRI>)
+///     });
+///
+/// // MP_UNREACH_NLRI withdrawing 2 routes
+/// let bytes = [0x80, 15, 11, 0, 1, 250, 0, 0, 0, 1, 0, 0, 0, 2];
+/// let attribute =
+///     PathAttribute::parse_with_options(&mut &bytes[..], &Capabilities::default(), &options)
+///         .unwrap();
+/// match &attribute {
+///     PathAttribute::MP_UNREACH_NLRI(mp_unreach) => {
+///         assert_eq!(mp_unreach.withdrawn_routes.len(), 2);
+///         match &mp_unreach.withdrawn_routes[1] {
+///             NLRIEncoding::CUSTOM(route) => {
+///                 assert_eq!(route.as_any().downcast_ref::<KeyRoute>().unwrap().0, 2);
+///             }
+///             route => panic!("Unexpected {:?}", route),
+///         }
+///     }
+///     _ => panic!("MP_UNREACH_NLRI not decoded"),
+/// }
+///
+/// let mut encoded = vec![];
+/// attribute.encode(&mut encoded).unwrap();
+/// assert_eq!(encoded, bytes);
+/// ```
+pub trait CustomNLRI: Debug + Send + Sync {
+    /// The Address Family Identifier of the route
+    fn afi(&self) -> AFI;
+
+    /// The Subsequent Address Family Identifier of the route
+    fn safi(&self) -> SAFI;
+
+    /// Appends the route as carried by MP_REACH_NLRI & MP_UNREACH_NLRI, including its Path
+    /// Identifier when ADD-PATH is used.
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Clones the route, as `NLRIEncoding` implements `Clone`.
+    fn clone_box(&self) -> Box<dyn CustomNLRI>;
+
+    /// The route as `Any`, for downcasting it to its type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl Clone for Box<dyn CustomNLRI> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Custom routes are equal when their family & encoding are.
+impl PartialEq for Box<dyn CustomNLRI> {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut route, mut other_route) = (vec![], vec![]);
+        self.afi() == other.afi()
+            && self.safi() == other.safi()
+            && self.encode(&mut route).is_ok()
+            && other.encode(&mut other_route).is_ok()
+            && route == other_route
+    }
+}
+
+impl Eq for Box<dyn CustomNLRI> {}
+
+/// Decodes a route from the start of the NLRI of an MP_REACH_NLRI or MP_UNREACH_NLRI
+/// attribute, advancing the buffer past it. Receives the AFI & SAFI of the attribute.
+pub type NLRIDecoder =
+    Arc<dyn Fn(AFI, SAFI, &mut &[u8]) -> Result<Box<dyn CustomNLRI>, Error> + Send + Sync>;
+
+/// The decoders of NLRI families registered by the user, see `CustomNLRI`.
+///
+/// A registered decoder takes precedence over the decoding of this crate, the routes of the
+/// family are parsed into `NLRIEncoding::CUSTOM` routes. Errors of the decoder are handled
+/// like other malformed MP_REACH_NLRI & MP_UNREACH_NLRI attributes.
+#[derive(Clone, Default)]
+pub struct NLRIDecoders {
+    decoders: HashMap<(AFI, SAFI), NLRIDecoder>,
+}
+
+impl NLRIDecoders {
+    /// Registers the decoder of an AFI/SAFI, replacing the previous one (if any).
+    pub fn register<F>(&mut self, afi: AFI, safi: SAFI, decoder: F)
+    where
+        F: Fn(AFI, SAFI, &mut &[u8]) -> Result<Box<dyn CustomNLRI>, Error> + Send + Sync + 'static,
+    {
+        self.decoders.insert((afi, safi), Arc::new(decoder));
+    }
+
+    /// Removes the decoder of an AFI/SAFI, returning whether it was registered.
+    pub fn unregister(&mut self, afi: AFI, safi: SAFI) -> bool {
+        self.decoders.remove(&(afi, safi)).is_some()
+    }
+
+    /// Whether a decoder is registered for the AFI/SAFI.
+    pub fn contains(&self, afi: AFI, safi: SAFI) -> bool {
+        self.decoders.contains_key(&(afi, safi))
+    }
+
+    pub(crate) fn get(&self, afi: AFI, safi: SAFI) -> Option<&NLRIDecoder> {
+        self.decoders.get(&(afi, safi))
+    }
+}
+
+/// Lists the families decoders are registered for.
+impl Debug for NLRIDecoders {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        let mut families: Vec<(u16, u8)> = self
+            .decoders
+            .keys()
+            .map(|(afi, safi)| (u16::from(*afi), u8::from(*safi)))
+            .collect();
+        families.sort();
+        f.debug_set().entries(families).finish()
+    }
+}
+
+/// Decodes the routes of an UPDATE message one at a time, see `Update::iter_nlri` and
+/// `Update::iter_withdrawn`.
+///
+/// The routes of the NLRI (or Withdrawn Routes) section come first, followed by those of the
+/// MP_REACH_NLRI (or MP_UNREACH_NLRI) attribute. No other path attribute is decoded, and
+/// MP_UNREACH_NLRI routes are returned as withdrawals like in `MPUnreachNLRI`. Errors carry an
+/// Invalid Network Field or Optional Attribute Error Notification (see `ToNotification`), the
+/// iteration ends after the first one.
+#[derive(Clone, Debug)]
+pub struct NLRIIter<'a> {
+    capabilities: &'a Capabilities,
+    options: &'a ParseOptions,
+    withdrawn: bool,
+    // The IPv4 Unicast routes of the Withdrawn Routes or NLRI section
+    routes: &'a [u8],
+    // The family & routes of the MP_UNREACH_NLRI or MP_REACH_NLRI attribute
+    mp_routes: Option<(AFI, SAFI, &'a [u8])>,
+    add_path: Option<bool>,
+    count: usize,
+}
+
+impl<'a> NLRIIter<'a> {
+    pub(crate) fn new(
+        message: &'a [u8],
+        capabilities: &'a Capabilities,
+        options: &'a ParseOptions,
+        withdrawn: bool,
+    ) -> Result<NLRIIter<'a>, Error> {
+        let (withdrawn_routes, mut buffer, nlri) = split_sections(message)?;
+        let code = if withdrawn {
+            Identifier::MP_UNREACH_NLRI
+        } else {
+            Identifier::MP_REACH_NLRI
+        };
+        let max_attributes = options.max_attributes;
+        let mut attribute_count = 0;
+        let mut mp_routes = None;
+        while !buffer.is_empty() {
+            if attribute_count >= max_attributes {
+                return Err(too_many_attributes(max_attributes));
+            }
+            attribute_count += 1;
+            let attribute =
+                RawPathAttributeRef::parse(&mut buffer).map_err(malformed_attribute_list)?;
+            if attribute.code == u8::from(code) {
+                mp_routes = Some(
+                    split_mp_routes(attribute.value, withdrawn)
+                        .map_err(optional_attribute_error)?,
+                );
+                break;
+            }
+        }
+        Ok(NLRIIter {
+            capabilities,
+            options,
+            withdrawn,
+            routes: if withdrawn { withdrawn_routes } else { nlri },
+            mp_routes,
+            add_path: mp_routes
+                .and_then(|(afi, safi, _)| negotiated_add_path(afi, safi, capabilities)),
+            count: 0,
+        })
+    }
+
+    // Decodes the next route of the Withdrawn Routes or NLRI section
+    fn next_route(&mut self) -> io::Result<NLRIEncoding> {
+        check_nlri_limit(self.count, self.options)?;
+        let mut cursor = Cursor::new(self.routes);
+        let add_path = if self.withdrawn {
+            self.capabilities
+                .receives_path_ids(AFI::IPV4, SAFI::Unicast)
+        } else {
+            util::detect_add_path_prefix(&mut cursor, 32)?
+        };
+        let route = parse_ipv4_route(&mut cursor, add_path)?;
+        self.routes = &self.routes[cursor.position() as usize..];
+        self.count += 1;
+        Ok(route)
+    }
+
+    // Decodes the next route of the MP_UNREACH_NLRI or MP_REACH_NLRI attribute
+    fn next_mp_route(
+        &mut self,
+        afi: AFI,
+        safi: SAFI,
+        routes: &'a [u8],
+    ) -> io::Result<NLRIEncoding> {
+        check_nlri_limit(self.count, self.options)?;
+        let mut cursor = Cursor::new(routes);
+        let route = parse_route(
+            afi,
+            safi,
+            self.capabilities,
+            self.options,
+            &mut cursor,
+            self.add_path,
+        )?;
+        self.mp_routes = Some((afi, safi, &routes[cursor.position() as usize..]));
+        self.count += 1;
+        if self.withdrawn {
+            Ok(route.as_withdrawal())
+        } else {
+            Ok(route)
+        }
+    }
+}
+
+impl<'a> Iterator for NLRIIter<'a> {
+    type Item = Result<NLRIEncoding, Error>;
+
+    fn next(&mut self) -> Option<Result<NLRIEncoding, Error>> {
+        let route = match self.mp_routes {
+            _ if !self.routes.is_empty() => self.next_route().map_err(invalid_network_field),
+            Some((afi, safi, routes)) if !routes.is_empty() => self
+                .next_mp_route(afi, safi, routes)
+                .map_err(optional_attribute_error),
+            _ => return None,
+        };
+        if route.is_err() {
+            self.routes = &[];
+            self.mp_routes = None;
+        }
+        Some(route)
+    }
+}
+
+// The family & routes of an MP_UNREACH_NLRI value, or of an MP_REACH_NLRI value following its
+// next hop
+fn split_mp_routes(mut value: &[u8], withdrawn: bool) -> io::Result<(AFI, SAFI, &[u8])> {
+    let afi = AFI::try_from(value.read_u16::<BigEndian>()?)?;
+    let safi = SAFI::try_from(value.read_u8()?)?;
+    if withdrawn {
+        return Ok((afi, safi, value));
+    }
+    let next_hop_length = usize::from(value.read_u8()?);
+    // Next hop & reserved byte
+    if value.len() < next_hop_length + 1 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "Bogus MP_REACH_NLRI length {} for a next hop of {} bytes",
+                value.len() + 4,
+                next_hop_length
+            ),
+        ));
+    }
+    Ok((afi, safi, &value[next_hop_length + 1..]))
+}
+
+// Errors decoding the routes of an MP_REACH_NLRI or MP_UNREACH_NLRI attribute
+fn optional_attribute_error(error: Error) -> Error {
+    with_notification(
+        error,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::OptionalAttributeError),
+        vec![],
+    )
+}
+
+// Parse AFI::L2VPN SAFI::Vpls into NLRIEncoding, as specified in RFC4761
+fn parse_l2vpn(buf: &mut impl Read) -> io::Result<NLRIEncoding> {
+    let len = buf.read_u16::<BigEndian>()?;
+    if len != L2VPN_NLRI_LENGTH {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("Bogus VPLS NLRI length {}", len),
+        ));
+    }
     let rd = buf.read_u64::<BigEndian>()?;
     let ve_id = buf.read_u16::<BigEndian>()?;
     let label_block_offset = buf.read_u16::<BigEndian>()?;
     let label_block_size = buf.read_u16::<BigEndian>()?;
     let label_base = buf.read_u24::<BigEndian>()?;
 
-    Ok(vec![NLRIEncoding::L2VPN((
+    Ok(NLRIEncoding::L2VPN((
         rd,
         ve_id,
         label_block_offset,
         label_block_size,
         label_base,
-    ))])
+    )))
+}
+
+// RD, VE ID, Label Block Offset, Label Block Size & Label Base
+const L2VPN_NLRI_LENGTH: u16 = 17;
+
+// The families of which the routes are decoded, the routes of others are kept as OPAQUE
+fn is_supported_family(afi: AFI, safi: SAFI) -> bool {
+    match (afi, safi) {
+        (AFI::L2VPN, SAFI::Vpls) => true,
+        (AFI::IPV4, safi) | (AFI::IPV6, safi) => match safi {
+            SAFI::Unicast | SAFI::Multicast | SAFI::Mpls | SAFI::MplsVpn | SAFI::MulticastVpn => {
+                true
+            }
+            #[cfg(feature = "flowspec")]
+            SAFI::Flowspec => true,
+            _ => false,
+        },
+        _ => false,
+    }
 }
 
-// Parse AFI::IPV4/IPv6 NLRI, based on the MP SAFI
-// Common across MPReach and MPUnreach
 fn parse_nlri(
     afi: AFI,
     safi: SAFI,
     capabilities: &Capabilities,
+    options: &ParseOptions,
     buf: &mut Cursor<Vec<u8>>,
     size: u16,
 ) -> io::Result<Vec<NLRIEncoding>> {
+    let add_path = negotiated_add_path(afi, safi, capabilities);
     let mut nlri: Vec<NLRIEncoding> = Vec::with_capacity(4);
     while buf.position() < u64::from(size) {
-        match safi {
-            // Labelled nexthop
-            // TODO Add label parsing and support capabilities.MULTIPLE_LABELS
-            SAFI::Mpls => {
-                nlri.push(parse_mpls(afi, buf)?);
-            }
-            SAFI::MplsVpn => {
-                nlri.push(parse_mplsvpn(afi, buf)?);
-            }
-            #[cfg(feature = "flowspec")]
-            SAFI::Flowspec => {
-                nlri.push(parse_flowspec(afi, buf)?);
-            }
-            #[cfg(feature = "flowspec")]
-            SAFI::FlowspecVPN => {
-                unimplemented!();
-            }
-            // DEFAULT
-            _ => {
-                if capabilities.EXTENDED_PATH_NLRI_SUPPORT {
-                    while buf.position() < u64::from(size) {
-                        let path_id = buf.read_u32::<BigEndian>()?;
-                        let prefix = Prefix::parse(buf, afi)?;
-                        nlri.push(NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)));
-                    }
-                } else {
-                    while buf.position() < u64::from(size) {
-                        let prefix = Prefix::parse(buf, afi)?;
-                        nlri.push(NLRIEncoding::IP(prefix));
-                    }
-                }
-            }
-        };
+        check_nlri_limit(nlri.len(), options)?;
+        nlri.push(parse_route(
+            afi,
+            safi,
+            capabilities,
+            options,
+            buf,
+            add_path,
+        )?);
     }
     Ok(nlri)
 }
 
+// Families with ADD-PATH negotiated are parsed as such, others may fall back to detection
+fn negotiated_add_path(afi: AFI, safi: SAFI, capabilities: &Capabilities) -> Option<bool> {
+    if capabilities.ADD_PATH_SUPPORT.contains_key(&(afi, safi)) {
+        Some(capabilities.receives_path_ids(afi, safi))
+    } else {
+        None
+    }
+}
+
+// Parses a single route of an MP_REACH_NLRI or MP_UNREACH_NLRI attribute
+fn parse_route<T: AsRef<[u8]>>(
+    afi: AFI,
+    safi: SAFI,
+    capabilities: &Capabilities,
+    options: &ParseOptions,
+    buf: &mut Cursor<T>,
+    add_path: Option<bool>,
+) -> io::Result<NLRIEncoding> {
+    if let Some(decoder) = options.nlri_decoders.get(afi, safi) {
+        let routes = &buf.get_ref().as_ref()[buf.position() as usize..];
+        let mut rest = routes;
+        let route = decoder(afi, safi, &mut rest)?;
+        // Protect against decoders looping forever on the same bytes
+        if rest.len() >= routes.len() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("The {} {} NLRI decoder consumed no bytes", afi, safi),
+            ));
+        }
+        buf.set_position(buf.position() + (routes.len() - rest.len()) as u64);
+        return Ok(NLRIEncoding::CUSTOM(route));
+    }
+    if !is_supported_family(afi, safi) {
+        let mut routes = vec![];
+        buf.read_to_end(&mut routes)?;
+        return Ok(NLRIEncoding::OPAQUE((afi, safi, routes)));
+    }
+    match safi {
+        // Labelled nexthop
+        // TODO Support capabilities.MULTIPLE_LABELS
+        SAFI::Mpls => parse_mpls(afi, buf, add_path),
+        SAFI::MplsVpn => parse_mplsvpn(afi, buf),
+        SAFI::MulticastVpn => Ok(NLRIEncoding::MVPN(MvpnRoute::parse(buf)?)),
+        SAFI::Vpls => parse_l2vpn(buf),
+        #[cfg(feature = "flowspec")]
+        SAFI::Flowspec => parse_flowspec(afi, buf),
+        // DEFAULT
+        _ => {
+            if capabilities.receives_path_ids(afi, safi) {
+                let path_id = buf.read_u32::<BigEndian>()?;
+                let prefix = Prefix::parse(buf, afi)?;
+                Ok(NLRIEncoding::IP_WITH_PATH_ID((prefix, path_id)))
+            } else {
+                Ok(NLRIEncoding::IP(Prefix::parse(buf, afi)?))
+            }
+        }
+    }
+}
+
 // Parse SAFI::Mpls into NLRIEncoding
-fn parse_mpls(afi: AFI, buf: &mut Cursor<Vec<u8>>) -> io::Result<NLRIEncoding> {
-    let path_id = if util::detect_add_path_prefix(buf, 255)? {
+fn parse_mpls<T: AsRef<[u8]>>(
+    afi: AFI,
+    buf: &mut Cursor<T>,
+    add_path: Option<bool>,
+) -> io::Result<NLRIEncoding> {
+    let add_path = match add_path {
+        Some(add_path) => add_path,
+        // The prefix length includes the label
+        None => util::detect_add_path_prefix(buf, 24 + afi.empty_buffer()?.len() as u32 * 8)?,
+    };
+    let path_id = if add_path {
         Some(buf.read_u32::<BigEndian>()?)
     } else {
         None
     };
     let len_bits = buf.read_u8()?;
-    // Protect against malformed messages
-    if len_bits == 0 {
-        return Err(Error::new(ErrorKind::Other, "Invalid prefix length 0"));
+    let mut pfx_buf = afi.empty_buffer()?;
+    // Protect against malformed messages, the length includes the label
+    if len_bits < 24 || usize::from(len_bits - 24) > pfx_buf.len() * 8 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("Invalid prefix length {}", len_bits),
+        ));
     }
 
     let len_bytes = (f32::from(len_bits) / 8.0).ceil() as u8;
-    // discard label, resv and s-bit for now
-    buf.read_exact(&mut [0u8; 3])?;
+    // Only the first label is kept, discarding the bottom-of-stack bit
+    let label = buf.read_u24::<BigEndian>()? >> 4;
     let remaining = (len_bytes - 3) as usize;
 
-    let mut pfx_buf = afi.empty_buffer();
     buf.read_exact(&mut pfx_buf[..remaining])?;
 
     // len_bits - MPLS info
@@ -216,41 +920,51 @@ fn parse_mpls(afi: AFI, buf: &mut Cursor<Vec<u8>>) -> io::Result<NLRIEncoding> {
 
     let nlri = match path_id {
         Some(path_id) => {
-            NLRIEncoding::IP_MPLS_WITH_PATH_ID((Prefix::new(afi, pfx_len, pfx_buf), 0, path_id))
+            NLRIEncoding::IP_MPLS_WITH_PATH_ID((Prefix::new(afi, pfx_len, pfx_buf), label, path_id))
         }
-        None => NLRIEncoding::IP_MPLS((Prefix::new(afi, pfx_len, pfx_buf), 0)),
+        None => NLRIEncoding::IP_MPLS((Prefix::new(afi, pfx_len, pfx_buf), label)),
     };
     Ok(nlri)
 }
 
 // Parse SAFI::MplsVpn into NLRIEncoding
-fn parse_mplsvpn(afi: AFI, buf: &mut Cursor<Vec<u8>>) -> io::Result<NLRIEncoding> {
+fn parse_mplsvpn(afi: AFI, buf: &mut impl Read) -> io::Result<NLRIEncoding> {
     let len_bits = buf.read_u8()?;
+    let mut pfx_buf = afi.empty_buffer()?;
+    // Protect against malformed messages, the length includes the label & route distinguisher
+    if len_bits < 88 || usize::from(len_bits - 88) > pfx_buf.len() * 8 {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!("Invalid prefix length {}", len_bits),
+        ));
+    }
+
     let len_bytes = (f32::from(len_bits) / 8.0).ceil() as u8;
-    // discard label, resv and s-bit for now
-    buf.read_exact(&mut [0u8; 3])?;
+    // Only the first label is kept, discarding the bottom-of-stack bit
+    let label = buf.read_u24::<BigEndian>()? >> 4;
     let remaining = (len_bytes - 3) as usize;
 
     let rd = buf.read_u64::<BigEndian>()?;
-    let mut pfx_buf = afi.empty_buffer();
     buf.read_exact(&mut pfx_buf[..(remaining - 8)])?;
 
     // len_bits - MPLS info - Route Distinguisher
     let pfx_len = len_bits - 24 - 64;
     let prefix = Prefix::new(afi, pfx_len, pfx_buf);
 
-    Ok(NLRIEncoding::IP_VPN_MPLS((rd, prefix, 0u32)))
+    Ok(NLRIEncoding::IP_VPN_MPLS((rd, prefix, label)))
 }
 
 #[cfg(feature = "flowspec")]
 // Parse SAFI::Flowspec into NLRIEncoding
-fn parse_flowspec(afi: AFI, buf: &mut Cursor<Vec<u8>>) -> io::Result<NLRIEncoding> {
-    let mut nlri_length = buf.read_u8()?;
+fn parse_flowspec<T: AsRef<[u8]>>(afi: AFI, buf: &mut Cursor<T>) -> io::Result<NLRIEncoding> {
+    let mut nlri_length = u64::from(buf.read_u8()?);
     let mut filters: Vec<FlowspecFilter> = vec![];
     while nlri_length > 0 {
         let cur_position = buf.position();
         filters.push(FlowspecFilter::parse(buf, afi)?);
-        nlri_length -= (buf.position() - cur_position) as u8;
+        nlri_length = nlri_length
+            .checked_sub(buf.position() - cur_position)
+            .ok_or_else(|| Error::new(ErrorKind::Other, "Flowspec filter exceeds NLRI length"))?;
     }
     Ok(NLRIEncoding::FLOWSPEC(filters))
 }
@@ -263,7 +977,15 @@ fn test_parse_nlri_ip_add_path() {
         EXTENDED_PATH_NLRI_SUPPORT: true,
         ..Capabilities::default()
     };
-    let result = parse_nlri(AFI::IPV4, SAFI::Unicast, &capabilities, &mut nlri_data, 8).unwrap();
+    let result = parse_nlri(
+        AFI::IPV4,
+        SAFI::Unicast,
+        &capabilities,
+        &ParseOptions::default(),
+        &mut nlri_data,
+        8,
+    )
+    .unwrap();
 
     match &result[0] {
         NLRIEncoding::IP_WITH_PATH_ID((_prefix, _pathid)) => (),
@@ -279,7 +1001,15 @@ fn test_parse_nlri_mpls_add_path() {
         EXTENDED_PATH_NLRI_SUPPORT: true,
         ..Capabilities::default()
     };
-    let result = parse_nlri(AFI::IPV4, SAFI::Mpls, &capabilities, &mut nlri_data, 11).unwrap();
+    let result = parse_nlri(
+        AFI::IPV4,
+        SAFI::Mpls,
+        &capabilities,
+        &ParseOptions::default(),
+        &mut nlri_data,
+        11,
+    )
+    .unwrap();
 
     match &result[0] {
         NLRIEncoding::IP_MPLS_WITH_PATH_ID((_prefix, _label, _pathid)) => (),
@@ -295,7 +1025,15 @@ fn test_parse_nlri_mpls() {
         EXTENDED_PATH_NLRI_SUPPORT: true,
         ..Capabilities::default()
     };
-    let result = parse_nlri(AFI::IPV4, SAFI::Mpls, &capabilities, &mut nlri_data, 7).unwrap();
+    let result = parse_nlri(
+        AFI::IPV4,
+        SAFI::Mpls,
+        &capabilities,
+        &ParseOptions::default(),
+        &mut nlri_data,
+        7,
+    )
+    .unwrap();
 
     match &result[0] {
         NLRIEncoding::IP_MPLS((_prefix, _label)) => (),
@@ -303,17 +1041,225 @@ fn test_parse_nlri_mpls() {
     }
 }
 
+#[test]
+fn test_parse_nlri_mpls_vpn_roundtrip() {
+    let data = vec![
+        112, 0x00, 0xc8, 0x01, 0, 1, 0, 0, 0xfd, 0xe8, 0, 100, 10, 10, 128,
+    ];
+    let mut nlri_data = std::io::Cursor::new(data.clone());
+    let result = parse_nlri(
+        AFI::IPV4,
+        SAFI::MplsVpn,
+        &Capabilities::default(),
+        &ParseOptions::default(),
+        &mut nlri_data,
+        15,
+    )
+    .unwrap();
+
+    match &result[0] {
+        NLRIEncoding::IP_VPN_MPLS((rd, prefix, label)) => {
+            assert_eq!(*rd, 0x0001_0000_fde8_0064);
+            assert_eq!(prefix.to_string(), "10.10.128.0/24");
+            assert_eq!(*label, 3200);
+        }
+        _ => panic!(),
+    }
+    let mut encoded = vec![];
+    result[0].encode(&mut encoded).unwrap();
+    assert_eq!(encoded, data);
+}
+
+#[test]
+fn test_mp_unreach_labeled_withdrawal() {
+    let announced = NLRIEncoding::IP_MPLS((("10.10.128.0".parse().unwrap(), 17).into(), 16));
+    for placeholder in &[[0x80, 0x00, 0x00], [0x00, 0x00, 0x00], [0x00, 0x00, 0x31]] {
+        let mut data = vec![0, 1, 4, 41];
+        data.extend_from_slice(placeholder);
+        data.extend_from_slice(&[10, 10, 128]);
+        let length = data.len() as u16;
+        let mp_unreach = MPUnreachNLRI::parse(
+            &mut Cursor::new(&data),
+            length,
+            &Capabilities::default(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(mp_unreach.withdrawn_routes, vec![announced.as_withdrawal()]);
+
+        let mut encoded = vec![];
+        mp_unreach.encode(&mut encoded).unwrap();
+        assert_eq!(encoded, vec![0, 1, 4, 41, 0x80, 0x00, 0x00, 10, 10, 128]);
+    }
+
+    // The announced label isn't leaked into the withdrawal
+    let mp_unreach = MPUnreachNLRI {
+        afi: AFI::IPV4,
+        safi: SAFI::Mpls,
+        withdrawn_routes: vec![announced],
+    };
+    let mut encoded = vec![];
+    mp_unreach.encode(&mut encoded).unwrap();
+    assert_eq!(encoded, vec![0, 1, 4, 41, 0x80, 0x00, 0x00, 10, 10, 128]);
+}
+
+#[test]
+fn test_mp_unreach_add_path_roundtrip() {
+    let mut capabilities = Capabilities::default();
+    capabilities.ADD_PATH_SUPPORT.insert(
+        (AFI::IPV6, SAFI::Unicast),
+        AddPathDirection::SendReceivePaths,
+    );
+    capabilities
+        .ADD_PATH_SUPPORT
+        .insert((AFI::IPV4, SAFI::Mpls), AddPathDirection::SendPaths);
+
+    // 2001:db8::/32 with Path ID 7
+    let data = vec![0, 2, 1, 0, 0, 0, 7, 32, 0x20, 0x01, 0x0d, 0xb8];
+    let mp_unreach = MPUnreachNLRI::parse(
+        &mut Cursor::new(&data),
+        data.len() as u16,
+        &capabilities,
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        mp_unreach.withdrawn_routes,
+        vec![NLRIEncoding::IP_WITH_PATH_ID((
+            ("2001:db8::".parse().unwrap(), 32).into(),
+            7
+        ))]
+    );
+    let mut encoded = vec![];
+    mp_unreach
+        .encode_with_capabilities(&mut encoded, &capabilities)
+        .unwrap();
+    assert_eq!(encoded, data);
+
+    // Path IDs are only read for families negotiated for receiving them, without detection
+    let data = vec![0, 1, 4, 41, 0x80, 0x00, 0x00, 10, 10, 128];
+    let mp_unreach = MPUnreachNLRI::parse(
+        &mut Cursor::new(&data),
+        data.len() as u16,
+        &capabilities,
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    match &mp_unreach.withdrawn_routes[0] {
+        NLRIEncoding::IP_MPLS(_) => (),
+        route => panic!("Unexpected route {:?}", route),
+    }
+}
+
+#[test]
+fn test_mp_reach_mvpn() {
+    #[rustfmt::skip]
+    let data = vec![
+        0, 1, 5, // IPv4 MVPN
+        4, 192, 0, 2, 1, 0, // next hop
+        1, 12, 0, 0, 0, 0, 0, 0, 0, 100, 192, 0, 2, 1, // Intra-AS I-PMSI A-D
+        2, 12, 0, 0, 0, 0, 0, 0, 0, 100, 0, 0, 0xfd, 0xe8, // Inter-AS I-PMSI A-D
+    ];
+    let mp_reach = MPReachNLRI::parse(
+        &mut Cursor::new(&data),
+        data.len() as u16,
+        &Capabilities::default(),
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        mp_reach.announced_routes,
+        vec![
+            NLRIEncoding::MVPN(MvpnRoute::IntraAsIPmsiAd {
+                rd: 100,
+                originating_router: "192.0.2.1".parse().unwrap(),
+            }),
+            NLRIEncoding::MVPN(MvpnRoute::InterAsIPmsiAd {
+                rd: 100,
+                source_as: 65000,
+            }),
+        ]
+    );
+    assert_eq!(
+        mp_reach.to_string(),
+        "mvpn intra-as-i-pmsi-ad RD 100 originator 192.0.2.1 mvpn inter-as-i-pmsi-ad RD 100 AS65000 via 192.0.2.1"
+    );
+    assert_eq!(
+        mp_reach.announced_routes[0].family(),
+        (AFI::IPV4, SAFI::MulticastVpn)
+    );
+
+    let mut encoded = vec![];
+    mp_reach.encode(&mut encoded).unwrap();
+    assert_eq!(encoded, data);
+}
+
 #[test]
 fn test_parse_l2vpn() {
     let mut nlri_data = std::io::Cursor::new(vec![
-        19, 0, 0, 0, 0, 0, 0, 0, 100, 0, 10, 0, 10, 0, 10, 0, 0, 0, 0,
+        0, 17, 0, 0, 0, 0, 0, 0, 0, 100, 0, 10, 0, 10, 0, 10, 0, 0, 0,
     ]);
 
     let result = parse_l2vpn(&mut nlri_data).unwrap();
-    match &result[0] {
+    match &result {
         NLRIEncoding::L2VPN(_) => (),
         _ => panic!(),
     }
+
+    let mut nlri_data = std::io::Cursor::new(vec![
+        0, 19, 0, 0, 0, 0, 0, 0, 0, 100, 0, 10, 0, 10, 0, 10, 0, 0, 0, 0, 0,
+    ]);
+    assert!(parse_l2vpn(&mut nlri_data).is_err());
+}
+
+#[test]
+fn test_mp_reach_l2vpn_roundtrip() {
+    #[rustfmt::skip]
+    let data = vec![
+        0, 25, 65, // L2VPN VPLS
+        4, 192, 0, 2, 1, 0, // next hop
+        0, 17, 0, 1, 0, 0, 0xfd, 0xe8, 0, 100, 0, 1, 0, 1, 0, 8, 0x0f, 0xa0, 0x01,
+        0, 17, 0, 1, 0, 0, 0xfd, 0xe8, 0, 100, 0, 2, 0, 1, 0, 8, 0x0f, 0xa0, 0x81,
+    ];
+    let mp_reach = MPReachNLRI::parse(
+        &mut Cursor::new(&data),
+        data.len() as u16,
+        &Capabilities::default(),
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        mp_reach.announced_routes,
+        vec![
+            NLRIEncoding::L2VPN((0x0001_0000_fde8_0064, 1, 1, 8, 0x0f_a001)),
+            NLRIEncoding::L2VPN((0x0001_0000_fde8_0064, 2, 1, 8, 0x0f_a081)),
+        ]
+    );
+    assert_eq!(
+        mp_reach.announced_routes[0].family(),
+        (AFI::L2VPN, SAFI::Vpls)
+    );
+
+    let mut encoded = vec![];
+    mp_reach.encode(&mut encoded).unwrap();
+    assert_eq!(encoded, data);
+
+    let mp_unreach = MPUnreachNLRI {
+        afi: AFI::L2VPN,
+        safi: SAFI::Vpls,
+        withdrawn_routes: mp_reach.announced_routes,
+    };
+    let mut encoded = vec![];
+    mp_unreach.encode(&mut encoded).unwrap();
+    let length = encoded.len() as u16;
+    let parsed = MPUnreachNLRI::parse(
+        &mut Cursor::new(&encoded),
+        length,
+        &Capabilities::default(),
+        &ParseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(parsed.withdrawn_routes, mp_unreach.withdrawn_routes);
 }
 
 #[cfg(feature = "flowspec")]
@@ -327,10 +1273,57 @@ fn test_parse_nlri_flowspec() {
     ]);
 
     let capabilities = Capabilities::default();
-    let result = parse_nlri(AFI::IPV6, SAFI::Flowspec, &capabilities, &mut nlri_data, 39).unwrap();
+    let result = parse_nlri(
+        AFI::IPV6,
+        SAFI::Flowspec,
+        &capabilities,
+        &ParseOptions::default(),
+        &mut nlri_data,
+        39,
+    )
+    .unwrap();
 
     match &result[0] {
         NLRIEncoding::FLOWSPEC(_filters) => (),
         _ => panic!(),
     }
 }
+
+#[test]
+fn test_next_hop_addresses() {
+    let mut capabilities = Capabilities::default();
+    let mut mp_reach = MPReachNLRI {
+        afi: AFI::IPV6,
+        safi: SAFI::Unicast,
+        next_hop: vec![],
+        announced_routes: vec![],
+    };
+    let global: IpAddr = "2001:db8::1".parse().unwrap();
+    let link_local: IpAddr = "fe80::1".parse().unwrap();
+    mp_reach
+        .set_next_hop(&[global, link_local], &capabilities)
+        .unwrap();
+    assert_eq!(mp_reach.next_hop.len(), 32);
+    assert_eq!(
+        mp_reach.next_hop_addresses(&capabilities).unwrap(),
+        vec![global, link_local]
+    );
+
+    // VPN-IPv4 routes over an IPv6 next hop
+    mp_reach.afi = AFI::IPV4;
+    mp_reach.safi = SAFI::MplsVpn;
+    mp_reach.next_hop = vec![0; 24];
+    mp_reach.next_hop[23] = 1;
+    assert!(mp_reach.next_hop_addresses(&capabilities).is_err());
+    capabilities
+        .EXTENDED_NEXT_HOP_ENCODING
+        .insert((AFI::IPV4, SAFI::MplsVpn), AFI::IPV6);
+    assert_eq!(
+        mp_reach.next_hop_addresses(&capabilities).unwrap(),
+        vec!["::1".parse::<IpAddr>().unwrap()]
+    );
+
+    mp_reach.next_hop = vec![0; 5];
+    assert!(mp_reach.next_hop_addresses(&capabilities).is_err());
+    assert!(mp_reach.set_next_hop(&[], &capabilities).is_err());
+}
@@ -1,15 +1,18 @@
 use byteorder::ReadBytesExt;
 
-use std::io::{Cursor, Result};
+use std::io::{Cursor, Error, ErrorKind, Result};
 
 // Attempt to detect whether the prefix has a path ID or not.
 // Modelled heavily on the Wireshark code - https://github.com/wireshark/wireshark/blob/24e43bf542d65f5b802b65355caacfba2c7b00d0/epan/dissectors/packet-bgp.c#L2336
 //
 // This is used because whilst we *do* look at the OPEN messages, some BMP implementations
 // don't send OPENs as part of the Peer Up messages. •`_´•  Looking at you XR 6.4.2
-pub(crate) fn detect_add_path_prefix(cur: &mut Cursor<Vec<u8>>, max_bit_len: u32) -> Result<bool> {
+pub(crate) fn detect_add_path_prefix<T: AsRef<[u8]>>(
+    cur: &mut Cursor<T>,
+    max_bit_len: u32,
+) -> Result<bool> {
     let cursor_init = cur.position();
-    let cursor_end = cur.get_ref().len() as u64;
+    let cursor_end = cur.get_ref().as_ref().len() as u64;
 
     let mut i = cur.position() + 4;
     while i < cursor_end {
@@ -42,6 +45,11 @@ pub(crate) fn detect_add_path_prefix(cur: &mut Cursor<Vec<u8>>, max_bit_len: u32
 
         i += 4;
     }
+    if i != cursor_end + 4 {
+        // Trailing bytes that can't hold a path ID & prefix length
+        cur.set_position(cursor_init);
+        return Ok(false);
+    }
 
     cur.set_position(cursor_init);
     let mut j = cur.position();
@@ -83,6 +91,34 @@ pub(crate) fn detect_add_path_prefix(cur: &mut Cursor<Vec<u8>>, max_bit_len: u32
     Ok(false)
 }
 
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+// Accepts the formats found in packet dumps of routers & other tools: digits may be grouped by
+// whitespace, ':', '-' or '.', and groups may be prefixed with "0x".
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid hex string: {}", hex),
+        )
+    };
+    let digits: Vec<u8> = hex
+        .split(|c: char| c.is_whitespace() || c == ':' || c == '-' || c == '.')
+        .map(|group| group.trim_start_matches("0x").trim_start_matches("0X"))
+        .flat_map(|group| group.chars())
+        .map(|c| c.to_digit(16).map(|digit| digit as u8).ok_or_else(invalid))
+        .collect::<Result<_>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect())
+}
+
 #[test]
 fn test_with_path_id() {
     #[rustfmt::skip]
@@ -128,3 +164,37 @@ fn test_without_path_id2() {
     let add_path = detect_add_path_prefix(&mut buf, 16).expect("detecting add_path");
     assert!(!add_path);
 }
+
+#[test]
+fn test_without_path_id_default_route() {
+    #[rustfmt::skip]
+    let nlri_data = vec![
+        // 26.147.144.0/22
+        0x16, 0x1a, 0x93, 0x90,
+        // 209.203.40.0/25
+        0x19, 0xd1, 0xcb, 0x28, 0x00,
+        // 0.0.0.0/0
+        0x00,
+        // 13.160.0.0/11
+        0x0b, 0x0d, 0xa0,
+    ];
+    let mut buf = std::io::Cursor::new(nlri_data);
+    let add_path = detect_add_path_prefix(&mut buf, 32).expect("detecting add_path");
+    assert!(!add_path);
+}
+
+#[test]
+fn test_from_hex() {
+    assert_eq!(from_hex("FFff0013").unwrap(), vec![0xff, 0xff, 0x00, 0x13]);
+    assert_eq!(
+        from_hex("0xffff 0x0013\n04").unwrap(),
+        vec![0xff, 0xff, 0x00, 0x13, 0x04]
+    );
+    assert_eq!(
+        from_hex("ff:ff-00.13").unwrap(),
+        vec![0xff, 0xff, 0x00, 0x13]
+    );
+    assert!(from_hex("fff").is_err());
+    assert!(from_hex("fg").is_err());
+    assert_eq!(to_hex(&[0xff, 0x00, 0x13]), "FF0013");
+}
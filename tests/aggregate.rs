@@ -0,0 +1,164 @@
+use bgp_rs::*;
+
+fn as_path(segments: Vec<Segment>) -> PathAttribute {
+    PathAttribute::AS_PATH(ASPath { segments })
+}
+
+fn strings(attributes: &[PathAttribute]) -> Vec<String> {
+    attributes.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn test_aggregate_attributes() {
+    let aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+    let routes = [
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            as_path(vec![
+                Segment::AS_SEQUENCE(vec![65001, 65002]),
+                Segment::AS_SET(vec![65010, 65011]),
+            ]),
+        ],
+        vec![
+            PathAttribute::ORIGIN(Origin::EGP),
+            as_path(vec![Segment::AS_SEQUENCE(vec![65001, 65002, 65003])]),
+            PathAttribute::ATOMIC_AGGREGATOR,
+        ],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            as_path(vec![Segment::AS_SEQUENCE(vec![65001, 65004, 65003])]),
+        ],
+    ];
+    let attributes = aggregator
+        .aggregate(routes.iter().map(|attributes| &attributes[..]))
+        .unwrap();
+    assert_eq!(
+        strings(&attributes),
+        vec![
+            "origin EGP",
+            "AS path 65001 {65002,65003,65004,65010,65011}",
+            "atomic aggregate",
+            "aggregator 65000 192.0.2.1",
+        ]
+    );
+
+    // INCOMPLETE wins over EGP
+    let routes = [
+        vec![PathAttribute::ORIGIN(Origin::INCOMPLETE)],
+        vec![PathAttribute::ORIGIN(Origin::EGP)],
+    ];
+    let attributes = aggregator
+        .aggregate(routes.iter().map(|attributes| &attributes[..]))
+        .unwrap();
+    assert_eq!(attributes[0].to_string(), "origin Incomplete");
+
+    // Identical paths are kept as they are
+    let routes = [
+        vec![as_path(vec![Segment::AS_SEQUENCE(vec![65001, 65002])])],
+        vec![as_path(vec![Segment::AS_SEQUENCE(vec![65001, 65002])])],
+    ];
+    let attributes = aggregator
+        .aggregate(routes.iter().map(|attributes| &attributes[..]))
+        .unwrap();
+    assert_eq!(
+        strings(&attributes),
+        vec![
+            "origin IGP",
+            "AS path 65001 65002",
+            "aggregator 65000 192.0.2.1"
+        ]
+    );
+
+    assert!(aggregator.aggregate(vec![]).is_none());
+}
+
+#[test]
+fn test_aggregate_multi_exit_disc() {
+    let aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+    let route = |med: Option<u32>| {
+        let mut attributes = vec![as_path(vec![Segment::AS_SEQUENCE(vec![65001])])];
+        attributes.extend(med.map(PathAttribute::MULTI_EXIT_DISC));
+        attributes
+    };
+    let aggregate = |routes: &[Vec<PathAttribute>]| {
+        aggregator.aggregate(routes.iter().map(|attributes| &attributes[..]))
+    };
+    assert!(aggregate(&[route(Some(10)), route(Some(10))]).is_some());
+    assert!(aggregate(&[route(None), route(None)]).is_some());
+    // Routes of differing MULTI_EXIT_DISC shall not be aggregated
+    assert!(aggregate(&[route(Some(10)), route(Some(20))]).is_none());
+    assert!(aggregate(&[route(Some(10)), route(None)]).is_none());
+
+    let mut aggregator = aggregator.clone();
+    aggregator.aggregates.push("10.0.0.0/8".parse().unwrap());
+    let routes: Vec<(Prefix, Vec<PathAttribute>)> = vec![
+        ("10.1.0.0/16".parse().unwrap(), route(Some(10))),
+        ("10.2.0.0/16".parse().unwrap(), route(Some(20))),
+    ];
+    let updates = aggregator.updates(
+        &routes,
+        "192.0.2.1".parse().unwrap(),
+        4096,
+        &Capabilities::default(),
+    );
+    assert!(updates.is_empty());
+}
+
+#[test]
+fn test_aggregate_as4_path() {
+    let aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+    let routes = [
+        vec![
+            as_path(vec![Segment::AS_SEQUENCE(vec![65001, AS_TRANS])]),
+            PathAttribute::AS4_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![4_200_000_000])],
+            }),
+        ],
+        vec![as_path(vec![Segment::AS_SEQUENCE(vec![65001])])],
+    ];
+    let attributes = aggregator
+        .aggregate(routes.iter().map(|attributes| &attributes[..]))
+        .unwrap();
+    assert_eq!(attributes[1].to_string(), "AS path 65001 {4200000000}");
+}
+
+#[test]
+fn test_aggregate_updates() {
+    let attributes = vec![
+        PathAttribute::ORIGIN(Origin::IGP),
+        as_path(vec![Segment::AS_SEQUENCE(vec![65001])]),
+    ];
+    let routes: Vec<(Prefix, Vec<PathAttribute>)> = vec![
+        "10.1.0.0/16",
+        "10.2.0.0/16",
+        "2001:db8:1::/48",
+        "198.51.100.0/24",
+    ]
+    .into_iter()
+    .map(|prefix| (prefix.parse().unwrap(), attributes.clone()))
+    .collect();
+
+    let mut aggregator = RouteAggregator::new(65000, "192.0.2.1".parse().unwrap());
+    aggregator.aggregates = vec![
+        "10.0.0.0/8".parse().unwrap(),
+        "2001:db8::/32".parse().unwrap(),
+        // Only covers a route of the same length
+        "198.51.100.0/24".parse().unwrap(),
+        "172.16.0.0/12".parse().unwrap(),
+    ];
+    let updates = aggregator.updates(
+        &routes,
+        "192.0.2.1".parse().unwrap(),
+        4096,
+        &Capabilities::default(),
+    );
+    let updates: Vec<String> = updates.iter().map(|update| update.to_string()).collect();
+    assert_eq!(
+        updates,
+        vec![
+            "10.0.0.0/8 via 192.0.2.1, origin IGP, AS path 65001, aggregator 65000 192.0.2.1",
+            "2001:db8::/32 via ::ffff:192.0.2.1, origin IGP, AS path 65001, \
+             aggregator 65000 192.0.2.1",
+        ]
+    );
+}
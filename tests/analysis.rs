@@ -0,0 +1,118 @@
+use bgp_rs::*;
+
+fn route(prefix: &str) -> NLRIEncoding {
+    NLRIEncoding::IP(prefix.parse().unwrap())
+}
+
+fn as_path(segments: Vec<Segment>) -> PathAttribute {
+    PathAttribute::AS_PATH(ASPath { segments })
+}
+
+#[test]
+fn test_statistics_of_updates() {
+    let mut statistics = RouteStatistics::new();
+    statistics.add_update(&Update::new(
+        vec![route("10.9.0.0/16")],
+        vec![
+            as_path(vec![Segment::AS_SEQUENCE(vec![65000, 23456])]),
+            PathAttribute::AS4_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000, 4_200_000_001])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::COMMUNITY(vec![0xfde8_0064, 0xfde8_00c8]),
+            PathAttribute::LARGE_COMMUNITY(vec![(65000, 1, 2)]),
+        ],
+        vec![route("10.0.0.0/8"), route("10.1.0.0/16")],
+    ));
+    statistics.add_update(&Update::new(
+        vec![],
+        vec![
+            as_path(vec![
+                Segment::AS_SEQUENCE(vec![65000, 65001, 65002]),
+                Segment::AS_SET(vec![65003, 65004]),
+            ]),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![route("2001:db8::/32")],
+            ))),
+            PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+            PathAttribute::EXTENDED_COMMUNITIES(vec![0x0002_fde8_0000_0064]),
+        ],
+        vec![],
+    ));
+    statistics.add_update(&Update::new(
+        vec![],
+        vec![
+            as_path(vec![Segment::AS_SEQUENCE(vec![65005, 4_200_000_001])]),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        ],
+        vec![route("10.0.0.0/8")],
+    ));
+
+    assert_eq!(statistics.routes, 4);
+    assert_eq!(statistics.withdrawals, 1);
+    // The origin of AS4_PATH is recovered, AS_SETs have no origin
+    assert_eq!(statistics.origins.len(), 1);
+    assert_eq!(
+        statistics.origins[&4_200_000_001],
+        vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "10.1.0.0/16".parse().unwrap()
+        ]
+        .into_iter()
+        .collect()
+    );
+    assert_eq!(statistics.top_origins(5), vec![(4_200_000_001, 2)]);
+    assert_eq!(
+        statistics.prefix_lengths.iter().collect::<Vec<_>>(),
+        vec![
+            (&(AFI::IPV4, 8), &2),
+            (&(AFI::IPV4, 16), &1),
+            (&(AFI::IPV6, 32), &1)
+        ]
+    );
+    assert_eq!(
+        statistics.path_lengths.iter().collect::<Vec<_>>(),
+        vec![(&2, &3), (&4, &1)]
+    );
+    assert_eq!(statistics.mean_path_length(), Some(2.5));
+    assert_eq!(statistics.communities[&0xfde8_0064], 3);
+    assert_eq!(statistics.communities[&0xfde8_00c8], 2);
+    assert_eq!(statistics.extended_communities[&0x0002_fde8_0000_0064], 1);
+    assert_eq!(statistics.large_communities[&(65000, 1, 2)], 2);
+}
+
+#[test]
+fn test_statistics_of_rib() {
+    let update = |origin: u32, routes: Vec<NLRIEncoding>| {
+        Update::new(
+            vec![],
+            vec![
+                as_path(vec![Segment::AS_SEQUENCE(vec![65000, origin])]),
+                PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            ],
+            routes,
+        )
+    };
+    let mut rib = Rib::new();
+    rib.apply(&update(65001, vec![route("10.0.0.0/8")]), 1);
+    rib.apply(&update(65001, vec![route("10.0.0.0/8")]), 2);
+    rib.apply(
+        &update(65002, vec![route("10.1.0.0/16"), route("10.2.0.0/16")]),
+        2,
+    );
+
+    let mut statistics = RouteStatistics::new();
+    statistics.add_rib(&rib);
+    assert_eq!(statistics.routes, 4);
+    assert_eq!(statistics.withdrawals, 0);
+    // The prefix of both peers is counted once for its origin
+    assert_eq!(statistics.top_origins(5), vec![(65002, 2), (65001, 1)]);
+    assert_eq!(statistics.top_origins(1), vec![(65002, 2)]);
+    assert_eq!(statistics.prefix_lengths[&(AFI::IPV4, 8)], 2);
+    assert_eq!(statistics.path_lengths[&2], 4);
+    assert!(statistics.communities.is_empty());
+    assert_eq!(RouteStatistics::new().mean_path_length(), None);
+}
@@ -0,0 +1,146 @@
+use bgp_rs::*;
+use std::io::Cursor;
+
+fn update() -> Update {
+    Update::new(
+        vec![NLRIEncoding::IP("10.1.0.0/16".parse().unwrap())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![3356, 4_200_000_001, 23456]),
+                    Segment::AS_SET(vec![13335]),
+                ],
+            }),
+            PathAttribute::NEXT_HOP("4.68.1.1".parse().unwrap()),
+            PathAttribute::AGGREGATOR((13335, "1.1.1.1".parse().unwrap())),
+            PathAttribute::COMMUNITY(vec![(3356 << 16) + 100, 0xffff_ff01]),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                next_hop: [
+                    "2001:4860::1"
+                        .parse::<std::net::Ipv6Addr>()
+                        .unwrap()
+                        .octets(),
+                    "fe80::1".parse::<std::net::Ipv6Addr>().unwrap().octets(),
+                ]
+                .concat(),
+                announced_routes: vec![NLRIEncoding::IP("2606:4700::/32".parse().unwrap())],
+            })),
+            PathAttribute::EXTENDED_COMMUNITIES(vec![0x0002_0d1c_0000_0064]),
+            PathAttribute::LARGE_COMMUNITY(vec![(4_200_000_001, 1, 2)]),
+        ],
+        vec![
+            NLRIEncoding::IP("10.0.0.0/8".parse().unwrap()),
+            NLRIEncoding::IP("1.1.1.0/24".parse().unwrap()),
+        ],
+    )
+}
+
+#[test]
+fn test_anonymize_documentation() {
+    let mut anonymizer = Anonymizer::new(
+        PrefixAnonymization::Documentation,
+        CommunityAnonymization::Remap,
+    );
+    let mut update = update();
+    anonymizer.anonymize_update(&mut update);
+    assert_eq!(
+        update.to_string(),
+        "198.51.100.0/24 203.0.113.0/24 via 192.0.2.1, \
+         2001:db8::/32 via 2001:db8::1 fe80::2, origin IGP, \
+         AS path 64512 4200000000 23456 {64513}, aggregator 64513 192.0.2.2, \
+         communities 64512:100 65535:65281, extended communities 0x0002fc0000000064, \
+         large communities 4200000000:1:2, withdrawn 192.0.2.0/24"
+    );
+
+    // The replacements are consistent across messages
+    let mut withdrawal = Update::new(
+        vec![NLRIEncoding::IP("1.1.1.0/24".parse().unwrap())],
+        vec![],
+        vec![],
+    );
+    anonymizer.anonymize_update(&mut withdrawal);
+    assert_eq!(withdrawal.to_string(), "withdrawn 203.0.113.0/24");
+    assert_eq!(anonymizer.asn(3356), 64512);
+    assert_eq!(
+        anonymizer.address("4.68.1.1".parse().unwrap()),
+        "192.0.2.1".parse::<std::net::IpAddr>().unwrap()
+    );
+
+    // The anonymized message remains valid (LARGE_COMMUNITY can not be encoded)
+    update
+        .attributes
+        .retain(|attribute| attribute.id() != Identifier::LARGE_COMMUNITY);
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    let mut data = vec![];
+    Message::Update(update.clone())
+        .encode_with_capabilities(&mut data, &capabilities)
+        .unwrap();
+    let mut reader = Reader::with_capabilities(Cursor::new(data), capabilities);
+    assert_eq!(reader.read().unwrap().1, Message::Update(update));
+}
+
+#[test]
+fn test_anonymize_prefix_preserving() {
+    let mut anonymizer = Anonymizer::new(
+        PrefixAnonymization::PrefixPreserving(0x5eed),
+        CommunityAnonymization::Strip,
+    );
+    let prefix =
+        |anonymizer: &mut Anonymizer, prefix: &str| anonymizer.prefix(&prefix.parse().unwrap());
+    let short = prefix(&mut anonymizer, "10.0.0.0/8");
+    let long = prefix(&mut anonymizer, "10.1.0.0/16");
+    let other = prefix(&mut anonymizer, "10.2.0.0/16");
+    assert_eq!(short.length, 8);
+    assert_ne!(short, "10.0.0.0/8".parse().unwrap());
+    assert!(short.contains(&long));
+    assert!(short.contains(&other));
+    assert_ne!(long, other);
+    let v6 = prefix(&mut anonymizer, "2001:db8:1::/48");
+    assert_eq!(v6.protocol, AFI::IPV6);
+    assert_eq!(v6.length, 48);
+
+    // The permutation only depends on the key
+    let mut again = Anonymizer::new(
+        PrefixAnonymization::PrefixPreserving(0x5eed),
+        CommunityAnonymization::Strip,
+    );
+    assert_eq!(prefix(&mut again, "10.1.0.0/16"), long);
+    again.prefixes = PrefixAnonymization::PrefixPreserving(0xbeef);
+    assert_ne!(prefix(&mut again, "10.1.0.0/16"), long);
+
+    let mut update = update();
+    anonymizer.anonymize_update(&mut update);
+    assert!(update.get(Identifier::COMMUNITY).is_none());
+    assert!(update.get(Identifier::EXTENDED_COMMUNITIES).is_none());
+    assert!(update.get(Identifier::LARGE_COMMUNITY).is_none());
+    assert_eq!(
+        update.announced_routes[0],
+        NLRIEncoding::IP(anonymizer.prefix(&"10.0.0.0/8".parse().unwrap()))
+    );
+}
+
+#[test]
+fn test_anonymize_open() {
+    let mut config = PeerConfig::new(4_200_000_001, "1.1.1.1".parse().unwrap());
+    config.hold_time = 90;
+    let mut message = Message::Open(Open::from_config(&config));
+    let mut anonymizer = Anonymizer::new(PrefixAnonymization::Keep, CommunityAnonymization::Keep);
+    anonymizer.anonymize_message(&mut message);
+    match message {
+        Message::Open(open) => {
+            assert_eq!(open.peer_asn, 23456);
+            assert_eq!(open.asn(), 4_200_000_000);
+            assert_eq!(
+                std::net::Ipv4Addr::from(open.identifier),
+                "192.0.2.1".parse::<std::net::Ipv4Addr>().unwrap()
+            );
+        }
+        message => panic!("Unexpected message: {:?}", message),
+    }
+}
@@ -0,0 +1,51 @@
+use bgp_rs::*;
+
+#[test]
+fn test_asn_notation() {
+    for (asplain, asdot) in &[
+        ("0", "0"),
+        ("65535", "65535"),
+        ("65536", "1.0"),
+        ("4200000000", "64086.59904"),
+        ("4294967295", "65535.65535"),
+    ] {
+        let asn: Asn = asplain.parse().unwrap();
+        assert_eq!(&asn.to_asdot(), asdot);
+        assert_eq!(asdot.parse::<Asn>().unwrap(), asn);
+        assert_eq!(&asn.to_string(), asplain);
+    }
+    assert!("65536.0".parse::<Asn>().is_err());
+    assert!("4294967296".parse::<Asn>().is_err());
+    assert!("1.2.3".parse::<Asn>().is_err());
+    assert!("AS65000".parse::<Asn>().is_err());
+}
+
+#[test]
+fn test_asn_classification() {
+    let asns: Vec<Asn> = vec![
+        0, 13335, 23456, 64496, 64512, 65534, 65535, 65536, 65552, 131072, 4200000000,
+    ]
+    .into_iter()
+    .map(Asn::from)
+    .collect();
+    let private: Vec<u32> = asns
+        .iter()
+        .filter(|asn| asn.is_private())
+        .map(|asn| asn.0)
+        .collect();
+    assert_eq!(private, vec![64512, 65534, 4200000000]);
+    let reserved: Vec<u32> = asns
+        .iter()
+        .filter(|asn| asn.is_reserved())
+        .map(|asn| asn.0)
+        .collect();
+    assert_eq!(reserved, vec![0, 23456, 65535, 65552]);
+    let documentation: Vec<u32> = asns
+        .iter()
+        .filter(|asn| asn.is_documentation())
+        .map(|asn| asn.0)
+        .collect();
+    assert_eq!(documentation, vec![64496, 65536]);
+    assert!(!Asn(65535).is_4_byte());
+    assert!(Asn(65536).is_4_byte());
+}
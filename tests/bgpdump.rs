@@ -0,0 +1,54 @@
+#![cfg(feature = "cli")]
+use std::process::Command;
+
+fn bgpdump(arguments: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_bgpdump-rs"))
+        .args(arguments)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_bgpdump_pcap() {
+    let output = bgpdump(&["pcap", "res/pcap/BGP_MP_NLRI.cap"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines[0],
+        "1277757397 [2001:db8::1]:42037 > [2001:db8::2]:179 OPEN AS65001 hold time 180 \
+         identifier 1.1.1.1"
+    );
+    assert!(lines.contains(
+        &"1277757397 10.0.0.2:179 > 10.0.0.1:15110 UPDATE 172.17.2.0/24 172.17.1.0/24 \
+          172.17.0.0/24 via 10.0.0.2, origin IGP, AS path 65002, MED 0"
+    ));
+}
+
+#[test]
+fn test_bgpdump_hex() {
+    let keepalive = "ffffffff ffffffff ffffffff ffffffff 0013 04";
+    assert_eq!(bgpdump(&["hex", keepalive]), "KEEPALIVE\n");
+
+    let output = bgpdump(&["--explain", "hex", keepalive]);
+    assert_eq!(output.lines().count(), 4);
+    assert!(output.contains("Type: 4 (KEEPALIVE)"));
+
+    // Malformed messages are still explained
+    let output = bgpdump(&[
+        "-e",
+        "hex",
+        "ffffffff ffffffff ffffffff ffffffff 0017 02 0000",
+    ]);
+    assert!(output.starts_with("Error: "));
+    assert!(output.contains("Total Path Attribute Length: truncated"));
+}
+
+#[test]
+fn test_bgpdump_usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bgpdump-rs"))
+        .args(&["json", "file"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
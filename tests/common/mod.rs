@@ -1,7 +1,7 @@
 #[allow(dead_code)]
 #[cfg(test)]
 pub mod parse {
-    use bgp_rs::{Capabilities, Message, Reader};
+    use bgp_rs::{Capabilities, Message, ParseOptions, Reader};
     use etherparse::PacketHeaders;
     use pcap_file::PcapReader;
     use std::fs::File;
@@ -30,16 +30,12 @@ pub mod parse {
                 Err(value) => println!("Err {:?}", value),
                 Ok(value) => {
                     let mut pos: usize = 0;
-                    loop {
-                        if let Some(i) = find_bytes(&value.payload[pos..], &[255; 16]) {
-                            pos += i;
-                            let length: usize = value.payload[pos + 17] as usize;
-                            let stream = &value.payload[pos..pos + length];
-                            message_chunks.push(stream.to_owned());
-                            pos += length as usize;
-                        } else {
-                            break;
-                        }
+                    while let Some(i) = find_bytes(&value.payload[pos..], &[255; 16]) {
+                        pos += i;
+                        let length: usize = value.payload[pos + 17] as usize;
+                        let stream = &value.payload[pos..pos + length];
+                        message_chunks.push(stream.to_owned());
+                        pos += length;
                         if pos >= value.payload.len() {
                             break;
                         }
@@ -52,14 +48,12 @@ pub mod parse {
 
     /// Parse and return Messages from a given pcap file
     pub fn parse_pcap_messages(filename: &str) -> Result<Vec<Message>, io::Error> {
-        let message_bytes = parse_pcap_message_bytes(&filename)?;
+        let message_bytes = parse_pcap_message_bytes(filename)?;
 
         let mut messages: Vec<Message> = vec![];
         for message_chunk in message_bytes {
-            let mut reader = Reader {
-                stream: Cursor::new(message_chunk),
-                capabilities: Capabilities::default(),
-            };
+            let mut reader =
+                Reader::with_capabilities(Cursor::new(message_chunk), Capabilities::default());
             let (_header, message) = reader.read()?;
             messages.push(message);
         }
@@ -69,15 +63,13 @@ pub mod parse {
     /// For a given message as bytes,
     /// make sure that the parsed and re-encoded message is the same
     pub fn test_message_roundtrip(message_bytes: &[u8]) -> Result<(), io::Error> {
-        let mut reader = Reader {
-            stream: Cursor::new(message_bytes),
-            capabilities: Capabilities::default(),
-        };
+        let mut reader =
+            Reader::with_capabilities(Cursor::new(message_bytes), Capabilities::default());
         let (_header, message) = reader.read()?;
         let mut encoded: Vec<u8> = vec![];
         message.encode(&mut encoded)?;
         assert_eq!(
-            message_bytes.to_vec(),
+            canonical_attribute_order(message_bytes),
             encoded,
             "Parsed message: {:?}",
             &message
@@ -85,8 +77,69 @@ pub mod parse {
         Ok(())
     }
 
+    /// For a given message as bytes, make sure that the parsed and re-encoded message
+    /// is the same when following the given session capabilities
+    pub fn test_message_roundtrip_with_capabilities(
+        message_bytes: &[u8],
+        capabilities: &Capabilities,
+    ) -> Result<(), io::Error> {
+        test_message_roundtrip_with_options(message_bytes, capabilities, ParseOptions::default())
+    }
+
+    /// Like `test_message_roundtrip_with_capabilities`, parsing with the given options
+    pub fn test_message_roundtrip_with_options(
+        message_bytes: &[u8],
+        capabilities: &Capabilities,
+        options: ParseOptions,
+    ) -> Result<(), io::Error> {
+        let preserve_wire_format = options.preserve_wire_format;
+        let mut reader = Reader::with_capabilities(Cursor::new(message_bytes), capabilities);
+        reader.options = options;
+        let (_header, message) = reader.read()?;
+        let mut encoded: Vec<u8> = vec![];
+        message.encode_with_capabilities(&mut encoded, capabilities)?;
+        let expected = if preserve_wire_format {
+            message_bytes.to_vec()
+        } else {
+            canonical_attribute_order(message_bytes)
+        };
+        assert_eq!(expected, encoded, "Parsed message: {:?}", &message);
+        Ok(())
+    }
+
+    /// Path attributes are encoded ordered by their type code,
+    /// reorder those of a captured UPDATE message the same way
+    pub fn canonical_attribute_order(message_bytes: &[u8]) -> Vec<u8> {
+        if message_bytes[18] != 2 {
+            return message_bytes.to_vec();
+        }
+        let read_u16 =
+            |i: usize| usize::from(message_bytes[i]) << 8 | usize::from(message_bytes[i + 1]);
+        let start = 19 + 2 + read_u16(19) + 2;
+        let end = start + read_u16(start - 2);
+        let mut attributes: Vec<&[u8]> = vec![];
+        let mut i = start;
+        while i < end {
+            let length = if message_bytes[i] & 0x10 == 0x10 {
+                4 + read_u16(i + 2)
+            } else {
+                3 + usize::from(message_bytes[i + 2])
+            };
+            attributes.push(&message_bytes[i..i + length]);
+            i += length;
+        }
+        attributes.sort_by_key(|attribute| attribute[1]);
+
+        let mut bytes = message_bytes[..start].to_vec();
+        for attribute in attributes {
+            bytes.extend_from_slice(attribute);
+        }
+        bytes.extend_from_slice(&message_bytes[end..]);
+        bytes
+    }
+
     pub fn test_pcap_roundtrip(filename: &str) -> Result<(), io::Error> {
-        let messages = parse_pcap_message_bytes(&filename)?;
+        let messages = parse_pcap_message_bytes(filename)?;
         for message in messages {
             test_message_roundtrip(&message)?;
         }
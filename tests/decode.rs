@@ -19,7 +19,7 @@ fn test_header_type() {
     let header = Header::parse(&mut buffer).unwrap();
     assert_eq!(header.marker.len(), 16);
     assert_eq!(header.length, 19);
-    assert_eq!(header.record_type, 4);
+    assert_eq!(header.record_type, MessageType::KeepAlive);
 }
 
 #[test]
@@ -78,6 +78,47 @@ fn test_open_decode() {
     }
 }
 
+#[test]
+fn test_open_decode_safis() {
+    #[rustfmt::skip]
+    let data = vec![
+        0x4, // Version
+        0xfd, 0xe8, // ASN
+        0, 0x3c, // Hold Timer
+        0x01, 0x01, 0x01, 0x01, // Identifier
+        24, // Parameter Length
+        0x02, 0x06, 0x01, 0x04, 0x00, 0x01, 0x00, 0x49, // IPv4 - SR TE Policy
+        0x02, 0x06, 0x01, 0x04, 0x00, 0x02, 0x00, 0x55, // IPv6 - MUP
+        0x02, 0x06, 0x01, 0x04, 0x00, 0x01, 0x00, 0xc8, // IPv4 - SAFI 200
+    ];
+    let mut buf = std::io::Cursor::new(data.clone());
+    let open = Open::parse(&mut buf).expect("Decoding OPEN");
+    let families: Vec<(AFI, SAFI)> = open
+        .parameters
+        .iter()
+        .flat_map(|parameter| match parameter {
+            OpenParameter::Capabilities(caps) => caps.clone(),
+            _ => vec![],
+        })
+        .filter_map(|capability| match capability {
+            OpenCapability::MultiProtocol(family) => Some(family),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        families,
+        vec![
+            (AFI::IPV4, SAFI::SrTePolicy),
+            (AFI::IPV6, SAFI::Mup),
+            (AFI::IPV4, SAFI::Other(200)),
+        ]
+    );
+
+    let mut encoded = vec![];
+    open.encode(&mut encoded).expect("Encoding OPEN");
+    assert_eq!(encoded, data);
+}
+
 #[test]
 fn test_bad_open_length() {
     #[rustfmt::skip]
@@ -98,15 +139,15 @@ fn test_bad_open_length() {
 
 #[test]
 fn test_notification_parse_no_data() {
-    let header = Header {
-        marker: [0xff; 16],
-        length: 19,
-        record_type: 4,
-    };
+    let header = Header::new(MessageType::KeepAlive, 19);
     let mut buf = std::io::Cursor::new(vec![6, 3]);
     let notification = Notification::parse(&header, &mut buf).expect("Parsing Notification");
-    assert_eq!(notification.major_err_code, 6);
-    assert_eq!(notification.minor_err_code, 3);
+    assert_eq!(notification.major_err_code(), 6);
+    assert_eq!(notification.minor_err_code(), 3);
+    assert_eq!(
+        notification.code,
+        NotificationCode::Cease(CeaseSubcode::PeerDeconfigured)
+    );
     assert!(notification.data.is_empty());
 }
 
@@ -114,18 +155,67 @@ fn test_notification_parse_no_data() {
 fn test_notification_parse_with_data() {
     let mut data = vec![4, 0];
     data.extend_from_slice(b"Hold Timer Expired");
-    let header = Header {
-        marker: [0xff; 16],
-        length: data.len() as u16 + 19,
-        record_type: 4,
-    };
+    let header = Header::new(MessageType::KeepAlive, data.len() as u16 + 19);
     let mut buf = std::io::Cursor::new(data);
     let notification = Notification::parse(&header, &mut buf).expect("Parsing Notification");
-    assert_eq!(notification.major_err_code, 4);
-    assert_eq!(notification.minor_err_code, 0);
+    assert_eq!(notification.major_err_code(), 4);
+    assert_eq!(notification.minor_err_code(), 0);
+    assert_eq!(notification.code, NotificationCode::HoldTimerExpired);
     assert_eq!(&notification.message().unwrap(), "Hold Timer Expired");
 }
 
+#[test]
+fn test_notification_parse_unsupported_capability() {
+    #[rustfmt::skip]
+    let data = vec![
+        2, 7, // OPEN Message Error / Unsupported Capability
+        1, 4, 0, 2, 0, 1, // MultiProtocol IPv6 Unicast
+        65, 4, 0, 0, 253, 232, // 4-byte ASN 65000
+    ];
+    let header = Header::new(MessageType::KeepAlive, data.len() as u16 + 19);
+    let mut buf = std::io::Cursor::new(data);
+    let notification = Notification::parse(&header, &mut buf).expect("Parsing Notification");
+    match notification.open_error_data().unwrap() {
+        Some(OpenErrorData::UnsupportedCapability(capabilities)) => {
+            assert_eq!(capabilities.len(), 2);
+            match capabilities[0] {
+                OpenCapability::MultiProtocol((AFI::IPV6, SAFI::Unicast)) => (),
+                _ => panic!("Expected MultiProtocol"),
+            }
+            match capabilities[1] {
+                OpenCapability::FourByteASN(65000) => (),
+                _ => panic!("Expected FourByteASN"),
+            }
+        }
+        _ => panic!("Expected Unsupported Capability data"),
+    }
+}
+
+#[test]
+fn test_notification_parse_open_error_data() {
+    let notification = Notification::from_data(2, 6, vec![0, 3]);
+    match notification.open_error_data().unwrap() {
+        Some(OpenErrorData::UnacceptableHoldTime(3)) => (),
+        _ => panic!("Expected Unacceptable Hold Time"),
+    }
+
+    let notification = Notification::from_data(2, 2, vec![250, 86, 234, 0]);
+    match notification.open_error_data().unwrap() {
+        Some(OpenErrorData::BadPeerAS(4200000000)) => (),
+        _ => panic!("Expected Bad Peer AS"),
+    }
+    assert_eq!(
+        Notification::bad_peer_as(4200000000).data,
+        vec![250, 86, 234, 0]
+    );
+
+    let notification = Notification::from_data(2, 2, vec![1, 2, 3]);
+    assert!(notification.open_error_data().is_err());
+
+    let notification = Notification::new(6, 2);
+    assert!(notification.open_error_data().unwrap().is_none());
+}
+
 #[test]
 fn test_update_bogus_withdraw_length() {
     #[rustfmt::skip]
@@ -145,13 +235,12 @@ fn test_update_bogus_withdraw_length() {
     ];
     let header_length = 19 + update_data.len();
     let mut buf = std::io::Cursor::new(update_data);
-    let header = Header {
-        marker: [0xff; 16],
-        length: header_length as u16,
-        record_type: 2,
-    };
+    let header = Header::new(MessageType::Update, header_length as u16);
     let res = Update::parse(&header, &mut buf, &Capabilities::default());
-    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err().to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList)
+    );
 }
 
 #[test]
@@ -170,15 +259,201 @@ fn test_update_bogus_attributes_length() {
     ];
     let header_length = 19 + update_data.len();
     let mut buf = std::io::Cursor::new(update_data);
-    let header = Header {
-        marker: [0xff; 16],
-        length: header_length as u16,
-        record_type: 2,
-    };
+    let header = Header::new(MessageType::Update, header_length as u16);
     let res = Update::parse(&header, &mut buf, &Capabilities::default());
     assert!(res.is_err());
 }
 
+#[test]
+fn test_update_length_consistency() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 13, // Path Attribute Length
+        64, 1, 1, 0, // ORIGIN
+        64, 5, 6, 0, 0, 0, 100, 0, 0, // LOCAL_PREF (holds 2 extra bytes)
+        8, 10, // 10.0.0.0/8
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let res = Update::parse(&header, &mut buf, &Capabilities::default());
+    assert_eq!(
+        res.unwrap_err().to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError)
+    );
+    // The attribute is skipped when parsing lossily
+    let mut buf = std::io::Cursor::new(update_data);
+    let (update, warnings) =
+        Update::parse_lossy(&header, &mut buf, &Capabilities::default()).unwrap();
+    assert_eq!(update.attributes.len(), 1);
+    assert_eq!(update.announced_routes.len(), 1);
+    assert_eq!(warnings.len(), 1);
+
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 4, // Path Attribute Length
+        64, 1, 1, 0, // ORIGIN
+        8, 10, // 10.0.0.0/8
+        16, 172, // Truncated prefix
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data);
+    let res = Update::parse(&header, &mut buf, &Capabilities::default());
+    assert_eq!(
+        res.unwrap_err().to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField)
+    );
+}
+
+#[test]
+fn test_update_other_family() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 6, // Path Attribute Length
+        128, 15, 3, 0, 3, 7, // MP_UNREACH_NLRI for AFI 3 SAFI 7
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let update = Update::parse(&header, &mut buf, &Capabilities::default()).unwrap();
+    assert_eq!(
+        update.end_of_rib_family(),
+        Some((AFI::Other(3), SAFI::Other(7)))
+    );
+    let mut data = vec![];
+    update.encode(&mut data).unwrap();
+    assert_eq!(data, update_data);
+
+    // The NLRI of unsupported families are kept undecoded
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 16, // Path Attribute Length
+        64, 1, 1, 0, // ORIGIN
+        128, 14, 9, 0, 3, 7, 0, 0, 16, 1, 2, 3, // MP_REACH_NLRI for AFI 3 SAFI 7
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let (update, warnings) =
+        Update::parse_lossy(&header, &mut buf, &Capabilities::default()).unwrap();
+    assert!(warnings.is_empty());
+    let routes: Vec<_> = update.announced().collect();
+    assert_eq!(
+        routes,
+        vec![(
+            AFI::Other(3),
+            SAFI::Other(7),
+            &NLRIEncoding::OPAQUE((AFI::Other(3), SAFI::Other(7), vec![16, 1, 2, 3]))
+        )]
+    );
+    let mut data = vec![];
+    update.encode(&mut data).unwrap();
+    assert_eq!(data, update_data);
+}
+
+#[test]
+fn test_lazy_update() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 2, // Withdrawn Routes Length
+        8, 10, // 10.0.0.0/8
+        0, 18, // Path Attribute Length
+        64, 1, 1, 5, // ORIGIN with an invalid value
+        64, 2, 4, 2, 1, 0xfd, 0xe8, // AS_PATH 65000
+        64, 5, 4, 0, 0, 0, 100, // LOCAL_PREF 100
+        24, 10, 1, 0, // 10.1.0.0/24
+    ];
+    let capabilities = Capabilities::default();
+    let options = ParseOptions::default();
+    let lazy = LazyUpdate::parse(&update_data, &capabilities, &options).unwrap();
+    assert_eq!(lazy.codes().collect::<Vec<u8>>(), vec![1, 2, 5]);
+    assert!(lazy.attribute(Identifier::ORIGIN).unwrap().is_err());
+    assert_eq!(
+        lazy.attribute(Identifier::LOCAL_PREF).unwrap().unwrap(),
+        PathAttribute::LOCAL_PREF(100)
+    );
+    assert!(lazy.attribute(Identifier::COMMUNITY).is_none());
+    assert_eq!(lazy.withdrawn_routes().unwrap().len(), 1);
+    assert_eq!(lazy.announced_routes().unwrap().len(), 1);
+
+    // Decoding all attributes skips those that can not be decoded, like Update::parse
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let update = Update::parse(&header, &mut buf, &capabilities).unwrap();
+    assert_eq!(lazy.attributes().unwrap(), update.attributes);
+    assert_eq!(lazy.decode().unwrap(), update);
+
+    // Inconsistent lengths are detected when parsing
+    assert!(LazyUpdate::parse(
+        &update_data[..update_data.len() - 5],
+        &capabilities,
+        &options
+    )
+    .is_err());
+    assert!(LazyUpdate::parse(&[0, 0, 0], &capabilities, &options).is_err());
+}
+
+#[test]
+fn test_iter_nlri() {
+    let mp_announced = vec![
+        NLRIEncoding::IP(("2001:db8:1::".parse().unwrap(), 48).into()),
+        NLRIEncoding::IP(("2001:db8:2::".parse().unwrap(), 48).into()),
+    ];
+    let mp_withdrawn = vec![NLRIEncoding::IP(
+        ("2001:db8:3::".parse().unwrap(), 48).into(),
+    )];
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                mp_announced.clone(),
+            ))),
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: mp_withdrawn.clone(),
+            })),
+        ],
+        vec![NLRIEncoding::IP(("10.1.0.0".parse().unwrap(), 24).into())],
+    );
+    let mut data = vec![];
+    update.encode(&mut data).unwrap();
+    let capabilities = Capabilities::default();
+    let options = ParseOptions::default();
+
+    let announced: Vec<NLRIEncoding> = Update::iter_nlri(&data, &capabilities, &options)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut expected = update.announced_routes.clone();
+    expected.extend(mp_announced);
+    assert_eq!(announced, expected);
+
+    let withdrawn: Vec<NLRIEncoding> = Update::iter_withdrawn(&data, &capabilities, &options)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut expected = update.withdrawn_routes.clone();
+    expected.extend(mp_withdrawn);
+    assert_eq!(withdrawn, expected);
+
+    // The iteration ends after the first malformed route
+    data.push(24);
+    let mut routes = Update::iter_nlri(&data, &capabilities, &options).unwrap();
+    assert!(routes.next().unwrap().is_ok());
+    let error = routes.next().unwrap().unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField)
+    );
+    assert!(routes.next().is_none());
+}
+
 #[test]
 fn test_update_extended_path_support() {
     #[rustfmt::skip]
@@ -198,11 +473,7 @@ fn test_update_extended_path_support() {
     ];
     let header_length = 19 + update_data.len();
     let mut buf = std::io::Cursor::new(update_data);
-    let header = Header {
-        marker: [0xff; 16],
-        length: header_length as u16,
-        record_type: 2,
-    };
+    let header = Header::new(MessageType::Update, header_length as u16);
     let capabilities = Capabilities::from_parameters(vec![OpenParameter::Capabilities(vec![
         OpenCapability::AddPath(vec![(
             AFI::IPV4,
@@ -218,4 +489,829 @@ fn test_update_extended_path_support() {
             _ => panic!("Expected Path ID"),
         }
     }
+    match update.get(Identifier::ORIGINATOR_ID) {
+        Some(PathAttribute::ORIGINATOR_ID(originator_id)) => {
+            assert_eq!(&originator_id.to_string(), "10.0.15.1")
+        }
+        _ => panic!("Expected ORIGINATOR_ID"),
+    }
+    match update.get(Identifier::CLUSTER_LIST) {
+        Some(PathAttribute::CLUSTER_LIST(cluster_list)) => {
+            assert_eq!(
+                cluster_list,
+                &vec!["10.0.34.4".parse::<Ipv4Addr>().unwrap()]
+            )
+        }
+        _ => panic!("Expected CLUSTER_LIST"),
+    }
+}
+
+#[test]
+fn test_update_withdrawn_add_path_per_family() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 8, // Withdrawn Routes Length
+        24, 172, 17, 2, // 172.17.2.0/24
+        24, 172, 17, 1, // 172.17.1.0/24
+        0, 0, // Path Attribute Length
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    // ADD-PATH is only negotiated for IPv6, classic IPv4 withdrawals carry no Path IDs
+    let capabilities = Capabilities::from_parameters(vec![OpenParameter::Capabilities(vec![
+        OpenCapability::AddPath(vec![(
+            AFI::IPV6,
+            SAFI::Unicast,
+            AddPathDirection::SendReceivePaths,
+        )]),
+    ])]);
+    assert!(capabilities.EXTENDED_PATH_NLRI_SUPPORT);
+    let mut buf = std::io::Cursor::new(update_data);
+    let update = Update::parse(&header, &mut buf, &capabilities).unwrap();
+    assert_eq!(
+        update.withdrawn_routes,
+        vec![
+            NLRIEncoding::IP(("172.17.2.0".parse().unwrap(), 24).into()),
+            NLRIEncoding::IP(("172.17.1.0".parse().unwrap(), 24).into()),
+        ]
+    );
+}
+
+#[test]
+fn test_update_withdrawn_add_path_send_only_peer() {
+    // The OPEN of a peer that only sends Path Identifiers for IPv4 unicast
+    let mut config = PeerConfig::new(65001, "192.0.2.1".parse().unwrap());
+    config
+        .add_path
+        .push((AFI::IPV4, SAFI::Unicast, AddPathDirection::SendPaths));
+    let open = Open::from_config(&config);
+    let capabilities = Capabilities::from_parameters(open.parameters.clone());
+    assert!(capabilities.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    assert!(!capabilities.sends_path_ids(AFI::IPV4, SAFI::Unicast));
+
+    // Withdrawing 10.0.0.0/8 with Path ID 1
+    let update_data = vec![0, 6, 0, 0, 0, 1, 8, 10, 0, 0];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let update = Update::parse(&header, &mut buf, &capabilities).unwrap();
+    assert_eq!(
+        update.withdrawn_routes,
+        vec![NLRIEncoding::IP_WITH_PATH_ID((
+            ("10.0.0.0".parse().unwrap(), 8).into(),
+            1
+        ))]
+    );
+
+    // Withdrawals sent to the peer don't carry Path Identifiers
+    let withdrawal = Update::new(
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        vec![],
+        vec![],
+    );
+    let mut data = vec![];
+    Message::Update(withdrawal)
+        .encode_with_capabilities(&mut data, &capabilities)
+        .unwrap();
+    assert_eq!(&data[19..], &[0, 2, 8, 10, 0, 0]);
+
+    // Negotiated with a local speaker receiving Path Identifiers, from its own perspective
+    let mut local = Capabilities::default();
+    local
+        .ADD_PATH_SUPPORT
+        .insert((AFI::IPV4, SAFI::Unicast), AddPathDirection::ReceivePaths);
+    let negotiated = Capabilities::negotiate(&local, &capabilities).capabilities;
+    assert!(negotiated.receives_path_ids(AFI::IPV4, SAFI::Unicast));
+    assert!(!negotiated.sends_path_ids(AFI::IPV4, SAFI::Unicast));
+    let mut buf = std::io::Cursor::new(update_data);
+    let parsed = Update::parse(&header, &mut buf, &negotiated).unwrap();
+    assert_eq!(parsed, update);
+}
+
+#[test]
+fn test_attribute_error_notification() {
+    // ORIGIN with an invalid value
+    let mut buf = std::io::Cursor::new(vec![0x40, 1, 1, 5]);
+    let error = PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap_err();
+    let notification = error.to_notification();
+    assert_eq!(
+        notification.code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidOriginAttribute)
+    );
+    // The attribute is echoed in the data
+    assert_eq!(notification.data, vec![0x40, 1, 1, 5]);
+
+    // OTC with an invalid length
+    let mut buf = std::io::Cursor::new(vec![0xc0, 35, 2, 0xfd, 0xe8]);
+    let error = PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap_err();
+    let notification = error.to_notification();
+    assert_eq!(
+        notification.code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeLengthError)
+    );
+    assert_eq!(notification.data, vec![0xc0, 35, 2, 0xfd, 0xe8]);
+
+    // Unrecognized well-known attribute
+    let mut buf = std::io::Cursor::new(vec![0x40, 190, 1, 0]);
+    let error = PathAttribute::parse(&mut buf, &Capabilities::default()).unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::UnrecognizedWellKnownAttribute)
+    );
+}
+
+#[test]
+fn test_update_error_notification() {
+    #[rustfmt::skip]
+    let mut message = vec![
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0, 29, 2, // Header
+        0, 0, // Withdrawn Routes Length
+        0, 4, // Path Attribute Length
+        64, 1, 1, 0, // ORIGIN
+        33, 10, // NLRI with a bogus prefix length
+    ];
+    let mut reader = Reader::new(std::io::Cursor::new(message.clone()));
+    let error = reader.read().unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField)
+    );
+
+    // The attributes claim to be longer than the message
+    message[22] = 10;
+    let mut reader = Reader::new(std::io::Cursor::new(message));
+    let error = reader.read().unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList)
+    );
+}
+
+#[test]
+fn test_route_refresh_subtypes() {
+    #[rustfmt::skip]
+    let mut message = vec![
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0, 23, 5, // Header
+        0, 2, 2, 1, // IPv6 Unicast, EoRR
+    ];
+    let mut reader = Reader::new(std::io::Cursor::new(message.clone()));
+    match reader.read().unwrap() {
+        (_, Message::RouteRefresh(refresh)) => {
+            assert_eq!(refresh.subtype, RouteRefreshSubtype::EndOfRouteRefresh);
+            assert_eq!(refresh.afi, AFI::IPV6);
+        }
+        _ => panic!("Expected Route Refresh"),
+    }
+
+    // BoRR/EoRR messages must not carry anything else
+    message[17] = 24;
+    message.push(1);
+    let mut reader = Reader::new(std::io::Cursor::new(message.clone()));
+    let notification = reader.read().unwrap_err().to_notification();
+    assert_eq!(
+        notification.code,
+        NotificationCode::RouteRefreshMessage(RouteRefreshMessageSubcode::InvalidMessageLength)
+    );
+    assert_eq!(notification.data, message);
+}
+
+#[test]
+fn test_update_end_of_rib() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 6, // Path Attribute Length
+        128, 15, 3, 0, 2, 1, // MP_UNREACH_NLRI IPv6 Unicast
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data);
+    let update = Update::parse(&header, &mut buf, &Capabilities::default()).unwrap();
+    assert!(update.is_end_of_rib());
+    assert_eq!(update.end_of_rib_family(), Some((AFI::IPV6, SAFI::Unicast)));
+
+    let header = Header::new(MessageType::Update, 23);
+    let mut buf = std::io::Cursor::new(vec![0, 0, 0, 0]);
+    let update = Update::parse(&header, &mut buf, &Capabilities::default()).unwrap();
+    assert_eq!(update.end_of_rib_family(), Some((AFI::IPV4, SAFI::Unicast)));
+
+    // A regular UPDATE is not an End-of-RIB
+    let mut update = Update::end_of_rib(AFI::IPV4, SAFI::Unicast);
+    update
+        .announced_routes
+        .push(NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into()));
+    assert!(!update.is_end_of_rib());
+}
+
+#[test]
+fn test_update_display() {
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("10.1.0.0".parse().unwrap(), 16).into())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![65000]),
+                    Segment::AS_SET(vec![65001, 65002]),
+                ],
+            }),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+            ))),
+            PathAttribute::LARGE_COMMUNITY(vec![(65000, 1, 2)]),
+        ],
+        vec![],
+    );
+    assert_eq!(
+        update.to_string(),
+        "2001:db8::/32 via 2001:db8::1, origin IGP, AS path 65000 {65001,65002}, \
+         large communities 65000:1:2, withdrawn 10.1.0.0/16"
+    );
+    assert_eq!(
+        format!("{:#}", update),
+        "2001:db8::/32 via 2001:db8::1\n    origin IGP\n    AS path 65000 {65001,65002}\n    \
+         large communities 65000:1:2\nwithdrawn 10.1.0.0/16"
+    );
+    assert_eq!(
+        Update::end_of_rib(AFI::IPV6, SAFI::Unicast).to_string(),
+        format!("End-of-RIB {} {}", AFI::IPV6, SAFI::Unicast)
+    );
+}
+
+#[test]
+fn test_reader_options_limits() {
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![100, 200]),
+                    Segment::AS_SET(vec![300, 400]),
+                ],
+            }),
+            PathAttribute::NEXT_HOP("10.0.0.1".parse().unwrap()),
+        ],
+        vec![
+            NLRIEncoding::IP(("10.1.0.0".parse().unwrap(), 16).into()),
+            NLRIEncoding::IP(("10.2.0.0".parse().unwrap(), 16).into()),
+        ],
+    );
+    let mut data = vec![];
+    Message::Update(update).encode(&mut data).unwrap();
+
+    let read = |options: ParseOptions| {
+        let mut reader = Reader::with_options(std::io::Cursor::new(data.clone()), options);
+        reader.read()
+    };
+    assert!(read(ParseOptions::default()).is_ok());
+
+    let error = read(ParseOptions {
+        max_message_size: 40,
+        ..ParseOptions::default()
+    })
+    .unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength)
+    );
+
+    let error = read(ParseOptions {
+        max_attributes: 2,
+        ..ParseOptions::default()
+    })
+    .unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::MalformedAttributeList)
+    );
+
+    let error = read(ParseOptions {
+        max_nlri: 1,
+        ..ParseOptions::default()
+    })
+    .unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField)
+    );
+
+    // AS_PATH with 2 segments
+    let as_path = vec![0x40, 2, 12, 2, 2, 0, 100, 0, 200, 1, 2, 1, 44, 1, 144];
+    let options = ParseOptions {
+        max_as_path_segments: 1,
+        ..ParseOptions::default()
+    };
+    let capabilities = Capabilities::default();
+    assert!(PathAttribute::parse_with_options(
+        &mut std::io::Cursor::new(as_path.clone()),
+        &capabilities,
+        &options
+    )
+    .is_err());
+    assert!(
+        PathAttribute::parse(&mut std::io::Cursor::new(as_path), &Capabilities::default()).is_ok()
+    );
+}
+
+#[test]
+fn test_read_large_message() {
+    // An UPDATE of 4423 bytes withdrawing 1100 routes, as received with extended messages
+    let mut body = vec![0x11, 0x30];
+    for i in 0..1100u16 {
+        body.push(24);
+        body.push(10);
+        body.extend_from_slice(&i.to_be_bytes());
+    }
+    body.extend_from_slice(&[0, 0]);
+    let mut data = vec![0xff; 16];
+    data.extend_from_slice(&(19 + body.len() as u16).to_be_bytes());
+    data.push(2);
+    data.extend_from_slice(&body);
+
+    let mut reader = Reader::new(std::io::Cursor::new(data.clone()));
+    match reader.read().unwrap().1 {
+        Message::Update(update) => assert_eq!(update.withdrawn_routes.len(), 1100),
+        message => panic!("Unexpected {:?}", message),
+    }
+
+    // The limit of RFC4271 is opt-in
+    let options = ParseOptions {
+        max_message_size: 4096,
+        ..ParseOptions::default()
+    };
+    let mut reader = Reader::with_options(std::io::Cursor::new(data), options);
+    assert!(reader.read().is_err());
+}
+
+#[test]
+fn test_read_lossy() {
+    #[rustfmt::skip]
+    let mut data = vec![
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0, 37, 2,
+        0, 2, // Withdrawn Routes Length
+        40, 10, // A truncated withdrawn prefix
+        0, 10, // Path Attribute Length
+        0x40, 1, 1, 0, // ORIGIN
+        0x40, 3, 3, 10, 0, 0, // NEXT_HOP with a bad length
+        8, 10, // 10.0.0.0/8
+    ];
+    // Followed by a KEEPALIVE
+    data.extend_from_slice(&[0xff; 16]);
+    data.extend_from_slice(&[0, 19, 4]);
+
+    let mut reader = Reader::new(std::io::Cursor::new(data.clone()));
+    assert!(reader.read().is_err());
+
+    let mut reader = Reader::new(std::io::Cursor::new(data));
+    let (_header, message, warnings) = reader.read_lossy().unwrap();
+    match message {
+        Message::Update(update) => {
+            assert!(update.withdrawn_routes.is_empty());
+            assert_eq!(update.attributes.len(), 1);
+            assert_eq!(update.announced_routes.len(), 1);
+        }
+        _ => panic!("Expected UPDATE"),
+    }
+    assert_eq!(warnings.len(), 2);
+    match &warnings[0] {
+        ParseWarning::TruncatedNLRI { section, .. } => assert_eq!(*section, NLRISection::Withdrawn),
+        warning => panic!("Unexpected warning {}", warning),
+    }
+    match &warnings[1] {
+        ParseWarning::SkippedAttribute { code, .. } => assert_eq!(*code, 3),
+        warning => panic!("Unexpected warning {}", warning),
+    }
+
+    // The stream continues with the next message
+    let (_header, message, warnings) = reader.read_lossy().unwrap();
+    assert!(warnings.is_empty());
+    match message {
+        Message::KeepAlive => (),
+        _ => panic!("Expected KEEPALIVE"),
+    }
+}
+
+#[test]
+fn test_explain() {
+    let mut data = vec![0xff; 16];
+    #[rustfmt::skip]
+    data.extend_from_slice(&[
+        0, 34, 2, // Length, Type
+        0, 0, // Withdrawn Routes Length
+        0, 7, // Total Path Attribute Length
+        0x40, 1, 1, 0, // ORIGIN IGP
+        0x40, 1, 0, // ORIGIN without a value
+        24, 10, 0, 0, // 10.0.0.0/24
+        0xff, 0xff, // Start of a truncated message
+    ]);
+    let dump = Message::explain(&data);
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines[1], "   16  00 22                       Length: 34");
+    assert!(lines[5].ends_with("Path Attribute ORIGIN"));
+    assert!(lines[9].ends_with("    Value: origin IGP"));
+    assert!(lines[10].ends_with("Path Attribute"));
+    assert!(lines[14].contains("Value: malformed"));
+    assert!(lines[15].ends_with("NLRI: 10.0.0.0/24"));
+    assert!(lines[16].ends_with("Marker: truncated, expected 16 bytes but only 2 remain"));
+    assert_eq!(lines.len(), 17);
+}
+
+#[test]
+fn test_message_hex() {
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    // As printed by "debug bgp updates" on a router
+    let hex = "0xffffffffffffffffffffffffffffffff 0x002f02 0x00000014400101004002060202fde8fde9 \
+               0x400304c0000201 0x180a0000";
+    let update = match Message::from_hex(hex, &Capabilities::default()).unwrap() {
+        Message::Update(update) => update,
+        message => panic!("Unexpected message: {:?}", message),
+    };
+    assert_eq!(
+        update.to_string(),
+        "10.0.0.0/24 via 192.0.2.1, origin IGP, AS path 65000 65001"
+    );
+    let message = Message::Update(update);
+    assert_eq!(
+        message.to_hex().unwrap(),
+        hex.replace("0x", "").replace(" ", "").to_uppercase()
+    );
+
+    // A trailing KEEPALIVE is not part of the message
+    let keepalive = Message::KeepAlive.to_hex().unwrap();
+    assert!(Message::from_hex(&(keepalive.clone() + &keepalive), &capabilities).is_err());
+    assert!(Message::from_hex("ffff", &capabilities).is_err());
+    assert!(Message::from_hex("not hex", &capabilities).is_err());
+}
+
+#[test]
+fn test_update_validate() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 15, // Path Attribute Length
+        0xc0, 1, 1, 0, // ORIGIN, flagged as optional
+        64, 3, 4, 10, 0, 14, 1,  // NEXT_HOP
+        64, 1, 1, 0, // ORIGIN, once more
+        // NLRI
+        8, 10, // 10.0.0.0/8
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let capabilities = Capabilities::default();
+    let options = ParseOptions {
+        preserve_wire_format: true,
+        ..ParseOptions::default()
+    };
+    let mut buf = std::io::Cursor::new(update_data);
+    let update = Update::parse_with_options(&header, &mut buf, &capabilities, &options).unwrap();
+    let violations = update.validate(&capabilities);
+    assert_eq!(
+        violations,
+        vec![
+            UpdateViolation::DuplicateAttribute(Identifier::ORIGIN),
+            UpdateViolation::AttributeFlags {
+                code: 1,
+                flags: 0xc0,
+                attribute: vec![0xc0, 1, 1, 0],
+            },
+            UpdateViolation::MissingWellKnownAttribute(Identifier::AS_PATH),
+        ]
+    );
+    assert_eq!(
+        violations[1].to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeFlagsError)
+    );
+
+    // Withdrawals without NEXT_HOP, for negotiated families only
+    let mut capabilities = Capabilities::default();
+    capabilities
+        .MP_BGP_SUPPORT
+        .insert((AFI::IPV4, SAFI::Unicast));
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        vec![
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: vec![],
+            })),
+        ],
+        vec![],
+    );
+    assert_eq!(
+        update.validate(&capabilities),
+        vec![
+            UpdateViolation::UnexpectedNextHop,
+            UpdateViolation::UnsupportedFamily {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+            },
+        ]
+    );
+    assert!(Update::end_of_rib(AFI::IPV4, SAFI::Unicast)
+        .validate(&capabilities)
+        .is_empty());
+}
+
+#[test]
+fn test_strict_attribute_flags() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 15, // Path Attribute Length
+        0xc0, 1, 1, 0, // ORIGIN, flagged as optional
+        64, 2, 0, // AS_PATH
+        0x50, 3, 0, 4, 10, 0, 14, 1,  // NEXT_HOP, with an extended length
+        // NLRI
+        8, 10, // 10.0.0.0/8
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let parse = |strict_attribute_flags: bool| {
+        let options = ParseOptions {
+            strict_attribute_flags,
+            ..ParseOptions::default()
+        };
+        let mut buf = std::io::Cursor::new(update_data.clone());
+        Update::parse_with_options(&header, &mut buf, &Capabilities::default(), &options)
+    };
+    assert!(parse(false).is_ok());
+
+    let notification = parse(true).unwrap_err().to_notification();
+    assert_eq!(
+        notification.code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::AttributeFlagsError)
+    );
+    assert_eq!(notification.data, vec![0xc0, 1, 1, 0]);
+}
+
+#[test]
+fn test_strict_header() {
+    let read = |data: &[u8], strict_header: bool| {
+        let options = ParseOptions {
+            strict_header,
+            ..ParseOptions::default()
+        };
+        let mut reader = Reader::with_options(std::io::Cursor::new(data.to_vec()), options);
+        reader.read().map(|(_, message)| message)
+    };
+    let code = |data: &[u8]| read(data, true).unwrap_err().to_notification().code;
+    let keepalive = |marker: u8, length: u16| {
+        let mut data = vec![marker; 16];
+        data.extend_from_slice(&length.to_be_bytes());
+        data.push(4);
+        data.resize(usize::from(length.max(19)), 0);
+        data
+    };
+
+    assert!(read(&keepalive(0xff, 19), true).is_ok());
+    assert!(read(&keepalive(0, 19), false).is_ok());
+    assert_eq!(
+        code(&keepalive(0, 19)),
+        NotificationCode::MessageHeader(MessageHeaderSubcode::ConnectionNotSynchronized)
+    );
+    assert!(read(&keepalive(0xff, 20), false).is_ok());
+    assert_eq!(
+        code(&keepalive(0xff, 20)),
+        NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength)
+    );
+    assert_eq!(
+        code(&keepalive(0xff, 18)),
+        NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength)
+    );
+
+    // A NOTIFICATION without data, followed by bytes counted in its length
+    let mut data = vec![];
+    Message::Notification(Notification::cease(CeaseSubcode::AdministrativeShutdown))
+        .encode(&mut data)
+        .unwrap();
+    assert!(read(&data, true).is_ok());
+    data[17] = 23;
+    data.extend_from_slice(&[0, 0]);
+    assert!(read(&data, true).is_ok());
+
+    // An OPEN holding less than its length
+    let mut data = vec![];
+    let open = Open::from_config(&PeerConfig::new(65000, "192.0.2.1".parse().unwrap()));
+    Message::Open(open).encode(&mut data).unwrap();
+    data[17] += 2;
+    data.extend_from_slice(&[0, 0]);
+    assert!(read(&data, false).is_ok());
+    assert_eq!(
+        code(&data),
+        NotificationCode::MessageHeader(MessageHeaderSubcode::BadMessageLength)
+    );
+}
+
+#[test]
+fn test_reader_skips_trailing_bytes() {
+    // An OPEN holding less than its length, followed by a KEEPALIVE
+    let mut data = vec![];
+    let open = Open::from_config(&PeerConfig::new(65000, "192.0.2.1".parse().unwrap()));
+    Message::Open(open).encode(&mut data).unwrap();
+    data[17] += 2;
+    data.extend_from_slice(&[0xff, 0xff]);
+    Message::KeepAlive.encode(&mut data).unwrap();
+
+    // The trailing bytes are not read as the header of the next message
+    let mut reader = Reader::new(std::io::Cursor::new(data));
+    match reader.read().unwrap().1 {
+        Message::Open(open) => assert_eq!(open.peer_asn, 65000),
+        _ => panic!("Expected OPEN"),
+    }
+    assert_eq!(reader.read().unwrap().1, Message::KeepAlive);
+}
+
+#[derive(Clone, Debug)]
+struct Color(u32);
+
+impl CustomAttribute for Color {
+    fn code(&self) -> u8 {
+        241
+    }
+    fn flags(&self) -> u8 {
+        0xc0
+    }
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), std::io::Error> {
+        buf.extend_from_slice(&self.0.to_be_bytes());
+        Ok(())
+    }
+    fn clone_box(&self) -> Box<dyn CustomAttribute> {
+        Box::new(self.clone())
+    }
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[test]
+fn test_read_resync() {
+    let mut data = vec![];
+    Message::KeepAlive.encode(&mut data).unwrap();
+    // A truncated message, followed by a marker with an implausible length
+    data.extend_from_slice(&[0xff; 10]);
+    data.extend_from_slice(&[0xff; 16]);
+    data.extend_from_slice(&[0, 5, 4]);
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath { segments: vec![] }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    );
+    Message::Update(update.clone()).encode(&mut data).unwrap();
+    // A marker of an unknown message type
+    data.extend_from_slice(&[0xff; 16]);
+    data.extend_from_slice(&[0, 19, 9]);
+    Message::KeepAlive.encode(&mut data).unwrap();
+
+    let mut reader = Reader::new(std::io::Cursor::new(data.clone()));
+    let (_, message, skipped) = reader.read_resync().unwrap();
+    assert_eq!((message, skipped), (Message::KeepAlive, 0));
+    let (header, message, skipped) = reader.read_resync().unwrap();
+    assert_eq!(header.record_type, MessageType::Update);
+    assert_eq!((message, skipped), (Message::Update(update), 29));
+    let (_, message, skipped) = reader.read_resync().unwrap();
+    assert_eq!((message, skipped), (Message::KeepAlive, 19));
+    assert_eq!(
+        reader.read_resync().unwrap_err().kind(),
+        std::io::ErrorKind::UnexpectedEof
+    );
+
+    // Reading without resynchronizing fails on the truncated message
+    let mut reader = Reader::new(std::io::Cursor::new(data));
+    reader.read().unwrap();
+    assert!(reader.read().is_err());
+
+    // Headers exceeding the maximum message size are skipped
+    let mut data = vec![0xff; 16];
+    data.extend_from_slice(&[0x10, 0x00, 2]);
+    Message::KeepAlive.encode(&mut data).unwrap();
+    let options = ParseOptions {
+        max_message_size: 1024,
+        ..ParseOptions::default()
+    };
+    let mut reader = Reader::with_options(std::io::Cursor::new(data), options);
+    let (_, message, skipped) = reader.read_resync().unwrap();
+    assert_eq!((message, skipped), (Message::KeepAlive, 19));
+}
+
+#[test]
+fn test_attribute_decoders() {
+    let capabilities = Capabilities::default();
+    let mut options = ParseOptions::default();
+    options
+        .attribute_decoders
+        .register(241, |_flags, code, value: &[u8]| {
+            if value.len() != 4 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Bogus length of attribute {}", code),
+                ));
+            }
+            let mut color = [0; 4];
+            color.copy_from_slice(value);
+            Ok(Box::new(Color(u32::from_be_bytes(color))) as Box<dyn CustomAttribute>)
+        });
+    assert!(options.attribute_decoders.contains(241));
+    assert_eq!(format!("{:?}", options.attribute_decoders), "{241}");
+
+    // ORIGIN, the custom attribute & MED, 10.0.0.0/8
+    let body = [
+        0, 0, 0, 18, 0x40, 1, 1, 0, 0xc0, 241, 4, 0, 0, 0, 7, 0x80, 4, 4, 0, 0, 0, 10, 8, 10,
+    ];
+    let mut data = vec![0xff; 16];
+    data.extend_from_slice(&[0, 19 + body.len() as u8, 2]);
+    data.extend_from_slice(&body);
+
+    let mut reader = Reader::with_options(std::io::Cursor::new(data.clone()), options.clone());
+    let update = match reader.read().unwrap().1 {
+        Message::Update(update) => update,
+        message => panic!("Unexpected {:?}", message),
+    };
+    let custom = update
+        .attributes
+        .iter()
+        .find(|attribute| attribute.code() == 241)
+        .unwrap();
+    match custom {
+        PathAttribute::CUSTOM(color) => {
+            assert_eq!(color.as_any().downcast_ref::<Color>().unwrap().0, 7);
+        }
+        attribute => panic!("Unexpected {:?}", attribute),
+    }
+    assert_eq!(custom.id(), Identifier::CUSTOM);
+    assert_eq!(custom.clone(), *custom);
+
+    // Attributes are encoded ordered by their type code
+    let mut update = update;
+    update.insert(PathAttribute::CUSTOM(Box::new(Color(8))));
+    assert_eq!(update.attributes.len(), 3);
+    let bytes = encode_message(&Message::Update(update), Some(&capabilities)).unwrap();
+    let mut expected = data.clone();
+    expected[19 + 4..19 + 22].copy_from_slice(&[
+        0x40, 1, 1, 0, 0x80, 4, 4, 0, 0, 0, 10, 0xc0, 241, 4, 0, 0, 0, 8,
+    ]);
+    assert_eq!(bytes, expected);
+
+    // Errors of the decoder are handled like other malformed optional attributes
+    let error =
+        PathAttribute::parse_with_options(&mut &[0xc0, 241, 1, 0][..], &capabilities, &options)
+            .unwrap_err();
+    assert_eq!(error.to_string(), "Bogus length of attribute 241");
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::OptionalAttributeError)
+    );
+
+    // Without the decoder the attribute is unknown
+    assert!(PathAttribute::parse(&mut &data[19 + 12..19 + 19], &Capabilities::default()).is_err());
+    assert!(options.attribute_decoders.unregister(241));
+}
+
+#[test]
+fn test_opaque_nlri() {
+    #[rustfmt::skip]
+    let update_data = vec![
+        0, 0, // Withdrawn Routes Length
+        0, 33, // Path Attribute Length
+        64, 1, 1, 0, // ORIGIN
+        // MP_REACH_NLRI for BGP-LS, next hop 192.0.2.1 & a Node NLRI
+        128, 14, 18, 0x40, 0x04, 71, 4, 192, 0, 2, 1, 0, 0, 1, 0, 5, 2, 0, 0, 0, 0,
+        // MP_UNREACH_NLRI for IPv4 Flowspec VPN
+        128, 15, 5, 0, 1, 134, 1, 2,
+    ];
+    let header = Header::new(MessageType::Update, 19 + update_data.len() as u16);
+    let mut buf = std::io::Cursor::new(update_data.clone());
+    let update = Update::parse(&header, &mut buf, &Capabilities::default()).unwrap();
+
+    let announced: Vec<String> = update
+        .announced()
+        .map(|(_, _, route)| route.to_string())
+        .collect();
+    assert_eq!(announced, vec!["BGPLS BGPLS NLRI 0x000100050200000000"]);
+    let withdrawn: Vec<_> = update.withdrawn().collect();
+    assert_eq!(
+        withdrawn,
+        vec![(
+            AFI::IPV4,
+            SAFI::FlowspecVPN,
+            &NLRIEncoding::OPAQUE((AFI::IPV4, SAFI::FlowspecVPN, vec![1, 2]))
+        )]
+    );
+
+    let mut data = vec![];
+    update.encode(&mut data).unwrap();
+    assert_eq!(data, update_data);
 }
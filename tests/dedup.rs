@@ -0,0 +1,143 @@
+use bgp_rs::*;
+
+fn route(prefix: &str) -> NLRIEncoding {
+    NLRIEncoding::IP(prefix.parse().unwrap())
+}
+
+fn mp_reach(routes: Vec<NLRIEncoding>) -> PathAttribute {
+    PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+        AFI::IPV6,
+        SAFI::Unicast,
+        "2001:db8::1".parse().unwrap(),
+        routes,
+    )))
+}
+
+#[test]
+fn test_duplicate_routes_of_update() {
+    let mut update = Update::new(
+        vec![route("10.9.0.0/16"), route("10.9.0.0/16")],
+        vec![
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            mp_reach(vec![
+                route("2001:db8:1::/48"),
+                route("2001:db8:2::/48"),
+                route("2001:db8:1::/48"),
+            ]),
+        ],
+        vec![
+            route("10.0.0.0/8"),
+            NLRIEncoding::IP_WITH_PATH_ID(("10.0.0.0/8".parse().unwrap(), 1)),
+        ],
+    );
+    let duplicates: Vec<String> = update
+        .duplicate_routes()
+        .iter()
+        .map(|(afi, safi, route)| format!("{} {} {}", afi, safi, route))
+        .collect();
+    assert_eq!(
+        duplicates,
+        vec!["IPv6 Unicast 2001:db8:1::/48", "IPv4 Unicast 10.9.0.0/16"]
+    );
+
+    update.dedup_routes();
+    assert!(update.duplicate_routes().is_empty());
+    assert_eq!(update.withdrawn_routes, vec![route("10.9.0.0/16")]);
+    assert_eq!(update.announced().count(), 4);
+}
+
+#[test]
+fn test_duplicate_filter() {
+    let update = |med: u32, routes: Vec<NLRIEncoding>, mp_routes: Vec<NLRIEncoding>| {
+        Update::new(
+            vec![],
+            vec![
+                PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+                PathAttribute::MULTI_EXIT_DISC(med),
+                mp_reach(mp_routes),
+            ],
+            routes,
+        )
+    };
+    let mut filter = DuplicateFilter::new();
+    let first = update(
+        10,
+        vec![route("10.0.0.0/8"), route("10.1.0.0/16")],
+        vec![route("2001:db8::/32")],
+    );
+    assert_eq!(filter.filter(&first, 1), Some(first.clone()));
+
+    // The order of the attributes does not matter
+    let mut reordered = first.clone();
+    reordered.attributes.reverse();
+    assert_eq!(filter.filter(&reordered, 1), None);
+    assert_eq!(filter.duplicates(), 3);
+
+    // Only the new & changed routes are kept
+    let second = update(
+        10,
+        vec![route("10.0.0.0/8"), route("10.2.0.0/16")],
+        vec![route("2001:db8::/32"), route("2001:db8:1::/48")],
+    );
+    let filtered = filter.filter(&second, 1).unwrap();
+    assert_eq!(filtered.announced_routes, vec![route("10.2.0.0/16")]);
+    assert_eq!(
+        filtered
+            .announced()
+            .map(|(_, _, route)| route.clone())
+            .last(),
+        Some(route("2001:db8:1::/48"))
+    );
+    assert_eq!(filtered.announced().count(), 2);
+    let filtered = filter
+        .filter(
+            &update(20, vec![route("10.0.0.0/8")], vec![route("2001:db8::/32")]),
+            1,
+        )
+        .unwrap();
+    assert_eq!(filtered.announced().count(), 2);
+    let filtered = filter
+        .filter(
+            &update(20, vec![route("10.3.0.0/16")], vec![route("2001:db8::/32")]),
+            1,
+        )
+        .unwrap();
+    assert_eq!(filtered.announced_routes, vec![route("10.3.0.0/16")]);
+    // MP_REACH_NLRI is removed once it lacks routes
+    assert!(filtered.get(Identifier::MP_REACH_NLRI).is_none());
+
+    // A withdrawn route is new once announced again
+    let withdrawal = Update::new(vec![route("10.1.0.0/16")], vec![], vec![]);
+    assert_eq!(filter.filter(&withdrawal, 1), Some(withdrawal.clone()));
+    let mut mixed = update(10, vec![route("10.1.0.0/16"), route("10.2.0.0/16")], vec![]);
+    mixed.withdrawn_routes.push(route("10.3.0.0/16"));
+    let filtered = filter.filter(&mixed, 1).unwrap();
+    assert_eq!(filtered.announced_routes, vec![route("10.1.0.0/16")]);
+
+    // Withdrawals are kept without the attributes of duplicate announcements
+    let mut mixed = update(10, vec![route("10.1.0.0/16")], vec![]);
+    mixed.withdrawn_routes.push(route("10.2.0.0/16"));
+    let filtered = filter.filter(&mixed, 1).unwrap();
+    assert_eq!(filtered.to_string(), "withdrawn 10.2.0.0/16");
+
+    // Every peer has its own routes
+    assert!(filter.filter(&first, 2).is_some());
+    filter.remove_peer(&2);
+    assert!(filter.filter(&first, 2).is_some());
+
+    // The routes of a RIB can be checked as well
+    let attributes = [PathAttribute::MULTI_EXIT_DISC(30)];
+    let mut filter = DuplicateFilter::new();
+    let check = |filter: &mut DuplicateFilter<u32>| {
+        filter.is_duplicate(
+            1,
+            AFI::IPV4,
+            SAFI::Unicast,
+            &route("10.0.0.0/8"),
+            &attributes,
+        )
+    };
+    assert!(!check(&mut filter));
+    assert!(check(&mut filter));
+    assert_eq!(filter.duplicates(), 1);
+}
@@ -0,0 +1,153 @@
+use bgp_rs::*;
+use std::net::IpAddr;
+
+fn route(prefix: &str) -> NLRIEncoding {
+    NLRIEncoding::IP(prefix.parse().unwrap())
+}
+
+fn mp_reach(next_hop: &str, routes: Vec<NLRIEncoding>) -> PathAttribute {
+    PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+        AFI::IPV6,
+        SAFI::Unicast,
+        next_hop.parse().unwrap(),
+        routes,
+    )))
+}
+
+#[test]
+fn test_diff_updates() {
+    let a = Update::new(
+        vec![route("10.9.0.0/16")],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000, 65001])],
+            }),
+            PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+            mp_reach("2001:db8::1", vec![route("2001:db8:1::/48")]),
+        ],
+        vec![route("10.0.0.0/8")],
+    );
+    let b = Update::new(
+        vec![],
+        vec![
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000, 65002])],
+            }),
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::LOCAL_PREF(100),
+            mp_reach(
+                "2001:db8::2",
+                vec![route("2001:db8:1::/48"), route("2001:db8:2::/48")],
+            ),
+        ],
+        vec![route("10.0.0.0/8")],
+    );
+
+    let changes = diff(&a, &b);
+    assert_eq!(
+        changes.attributes,
+        vec![
+            Change::Changed(
+                a.attributes[1].clone(),
+                PathAttribute::AS_PATH(ASPath {
+                    segments: vec![Segment::AS_SEQUENCE(vec![65000, 65002])],
+                })
+            ),
+            Change::Removed(PathAttribute::COMMUNITY(vec![0xfde8_0064])),
+            Change::Changed(
+                mp_reach("2001:db8::1", vec![]),
+                mp_reach("2001:db8::2", vec![])
+            ),
+            Change::Added(PathAttribute::LOCAL_PREF(100)),
+        ]
+    );
+    assert_eq!(
+        changes.to_string(),
+        "- AS path 65000 65001\n+ AS path 65000 65002\n- communities 65000:100\n\
+         - IPv6 Unicast via 2001:db8::1\n+ IPv6 Unicast via 2001:db8::2\n+ local pref 100\n\
+         + announced 2001:db8:2::/48\n- withdrawn 10.9.0.0/16"
+    );
+    assert!(!changes.is_empty());
+
+    // The order of routes and attributes is ignored
+    let mut reordered = b.clone();
+    reordered.attributes.reverse();
+    assert!(diff(&b, &reordered).is_empty());
+    assert_eq!(diff(&b, &reordered).to_string(), "");
+}
+
+#[test]
+fn test_compare_ribs() {
+    let peer_a: IpAddr = "192.0.2.1".parse().unwrap();
+    let peer_b: IpAddr = "192.0.2.2".parse().unwrap();
+    let attributes = |med: u32| {
+        vec![
+            PathAttribute::NEXT_HOP("192.0.2.254".parse().unwrap()),
+            PathAttribute::MULTI_EXIT_DISC(med),
+        ]
+    };
+
+    let mut old = Rib::new();
+    old.apply(
+        &Update::new(
+            vec![],
+            attributes(10),
+            vec![route("10.0.0.0/8"), route("10.1.0.0/16")],
+        ),
+        peer_a,
+    );
+    old.apply(
+        &Update::new(vec![], attributes(10), vec![route("10.3.0.0/16")]),
+        peer_b,
+    );
+    let mut new = Rib::new();
+    new.apply(
+        &Update::new(vec![], attributes(20), vec![route("10.0.0.0/8")]),
+        peer_a,
+    );
+    new.apply(
+        &Update::new(
+            vec![],
+            attributes(10),
+            vec![route("10.1.0.0/16"), route("10.2.0.0/16")],
+        ),
+        peer_a,
+    );
+    new.apply(
+        &Update::new(vec![], attributes(10), vec![route("10.3.0.0/16")]),
+        peer_b,
+    );
+
+    let changes = old.compare(&new);
+    assert_eq!(changes.routes.len(), 2);
+    assert_eq!(changes.routes[0].peer, peer_a);
+    assert_eq!(
+        changes.routes[0].attributes(),
+        vec![Change::Changed(
+            PathAttribute::MULTI_EXIT_DISC(10),
+            PathAttribute::MULTI_EXIT_DISC(20)
+        )]
+    );
+    assert_eq!(
+        changes.to_string(),
+        "~ 192.0.2.1 10.0.0.0/8\n    - MED 10\n    + MED 20\n\
+         + 192.0.2.1 10.2.0.0/16: next hop 192.0.2.254, MED 10"
+    );
+    assert!(old.compare(&old).is_empty());
+
+    // A peer only held by one RIB has all of its routes removed (or added)
+    new.remove_peer(&peer_b);
+    let changes = old.compare(&new);
+    assert_eq!(
+        changes.routes.last().unwrap().to_string(),
+        "- 192.0.2.2 10.3.0.0/16: next hop 192.0.2.254, MED 10"
+    );
+    assert_eq!(
+        changes.routes.last().unwrap().attributes(),
+        attributes(10)
+            .into_iter()
+            .map(Change::Removed)
+            .collect::<Vec<_>>()
+    );
+}
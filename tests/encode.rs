@@ -16,9 +16,9 @@ fn test_message_too_large() {
             routes.push(NLRIEncoding::IP((addr, 128).into()));
         }
     }
-    let message = Message::Update(Update {
-        withdrawn_routes: vec![],
-        attributes: vec![
+    let message = Message::Update(Update::new(
+        vec![],
+        vec![
             PathAttribute::ORIGIN(Origin::IGP),
             PathAttribute::AS_PATH(ASPath {
                 segments: vec![Segment::AS_SEQUENCE(vec![64511])],
@@ -26,11 +26,11 @@ fn test_message_too_large() {
             PathAttribute::NEXT_HOP("10.0.14.1".parse().unwrap()),
             PathAttribute::MULTI_EXIT_DISC(0),
             PathAttribute::LOCAL_PREF(100),
-            PathAttribute::CLUSTER_LIST(vec![167780868]),
-            PathAttribute::ORIGINATOR_ID(167776001),
+            PathAttribute::CLUSTER_LIST(vec!["10.0.34.4".parse().unwrap()]),
+            PathAttribute::ORIGINATOR_ID("10.0.15.1".parse().unwrap()),
         ],
-        announced_routes: routes,
-    });
+        routes,
+    ));
     let mut buf = vec![];
     let res = message.encode(&mut buf);
     assert!(res.is_err());
@@ -161,7 +161,8 @@ fn test_encode_route_refresh() {
     let refresh = RouteRefresh {
         afi: AFI::IPV4,
         safi: SAFI::Unicast,
-        subtype: 1u8,
+        subtype: RouteRefreshSubtype::BeginOfRouteRefresh,
+        orf: None,
     };
     let mut data: Vec<u8> = vec![];
     refresh.encode(&mut data).expect("Encoding Route Refresh");
@@ -175,11 +176,54 @@ fn test_encode_route_refresh() {
     );
 }
 
+#[test]
+fn test_encode_route_refresh_orf() {
+    let refresh = RouteRefresh {
+        afi: AFI::IPV4,
+        safi: SAFI::Unicast,
+        subtype: RouteRefreshSubtype::Normal,
+        orf: Some(RouteRefreshORF {
+            when_to_refresh: WhenToRefresh::Immediate,
+            orfs: vec![ORF::AddressPrefix(vec![
+                AddressPrefixORFEntry::RemoveAll,
+                AddressPrefixORFEntry::Entry {
+                    action: ORFAction::Add,
+                    match_type: ORFMatch::Deny,
+                    sequence: 20,
+                    min_length: 16,
+                    max_length: 24,
+                    prefix: ("192.168.0.0".parse().unwrap(), 16).into(),
+                },
+            ])],
+        }),
+    };
+    let mut data: Vec<u8> = vec![];
+    refresh.encode(&mut data).expect("Encoding Route Refresh");
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        vec![
+            0, 1, 0, 1, // AFI, Reserved, SAFI
+            1, // When-to-refresh: IMMEDIATE
+            64, 0, 11, // Address Prefix ORF, Length
+            0x80, // REMOVE-ALL
+            0x20, 0, 0, 0, 20, 16, 24, 16, 192, 168, // ADD DENY 192.168.0.0/16 ge 16 le 24
+        ]
+    );
+
+    // Roundtrip through the Reader
+    let message = Message::RouteRefresh(refresh);
+    let message_data = encode_as_message(message.clone());
+    let mut reader = Reader::new(std::io::Cursor::new(message_data));
+    let (_, parsed) = reader.read().expect("Reading Route Refresh");
+    assert_eq!(parsed, message);
+}
+
 #[test]
 fn test_encode_update_add_path() {
-    let update = Update {
-        withdrawn_routes: vec![],
-        attributes: vec![
+    let update = Update::new(
+        vec![],
+        vec![
             PathAttribute::ORIGIN(Origin::IGP),
             PathAttribute::AS_PATH(ASPath {
                 segments: vec![Segment::AS_SEQUENCE(vec![64511])],
@@ -187,14 +231,14 @@ fn test_encode_update_add_path() {
             PathAttribute::NEXT_HOP("10.0.14.1".parse().unwrap()),
             PathAttribute::MULTI_EXIT_DISC(0),
             PathAttribute::LOCAL_PREF(100),
-            PathAttribute::CLUSTER_LIST(vec![167780868]),
-            PathAttribute::ORIGINATOR_ID(167776001),
+            PathAttribute::CLUSTER_LIST(vec!["10.0.34.4".parse().unwrap()]),
+            PathAttribute::ORIGINATOR_ID("10.0.15.1".parse().unwrap()),
         ],
-        announced_routes: vec![
+        vec![
             NLRIEncoding::IP_WITH_PATH_ID((("5.5.5.5".parse().unwrap(), 32).into(), 1)),
             NLRIEncoding::IP_WITH_PATH_ID((("192.168.1.5".parse().unwrap(), 32).into(), 1)),
         ],
-    };
+    );
 
     let mut data: Vec<u8> = vec![];
     update.encode(&mut data).expect("Encoding Update");
@@ -209,8 +253,8 @@ fn test_encode_update_add_path() {
             64, 3, 4, 10, 0, 14, 1,  // NEXT_HOP
             128, 4, 4, 0, 0, 0, 0, // MED
             64, 5, 4, 0, 0, 0, 100, // LOCAL_PREF
+            128, 9, 4, 10, 0, 15, 1, // ORIGINATOR_ID, ordered by type code
             128, 10, 4, 10, 0, 34, 4, // CLUSTER LIST
-            128, 9, 4, 10, 0, 15, 1, // ORIGINATOR_ID
             // NLRI
             0, 0, 0, 1, 32, 5, 5, 5, 5, // 5.5.5.5/32 w/ Path ID 1
             0, 0, 0, 1, 32, 192, 168, 1, 5   // 192.168.1.5/32 w/ Path ID 1
@@ -225,14 +269,194 @@ fn test_encode_update_add_path() {
     );
 }
 
+#[test]
+fn test_message_equality() {
+    let update = Update::new(
+        vec![NLRIEncoding::IP("10.2.0.0/16".parse().unwrap())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65001, 65002])],
+            }),
+            // Ordered by type code, as they are encoded
+            PathAttribute::COMMUNITY(vec![0xffff_0000]),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![NLRIEncoding::IP("2001:db8:1::/48".parse().unwrap())],
+            ))),
+        ],
+        vec![],
+    );
+    let message = Message::Update(update.clone());
+    let mut reader = Reader::new(std::io::Cursor::new(encode_as_message(message.clone())));
+    let (header, parsed) = reader.read().expect("Reading Update");
+    assert_eq!(header.record_type, MessageType::Update);
+    assert_eq!(parsed, message);
+
+    let mut other = update;
+    other.attributes[0] = PathAttribute::ORIGIN(Origin::EGP);
+    assert_ne!(parsed, Message::Update(other));
+    assert_ne!(parsed, Message::KeepAlive);
+
+    // Messages are Eq, BGP-LS bandwidths being kept as the bits of their value
+    fn assert_eq_impl<T: Eq>(_: &T) {}
+    assert_eq_impl(&parsed);
+    let bandwidth = LinkStateTlv::UtilizedBandwidth(f32::NAN.to_bits());
+    assert_eq!(bandwidth, bandwidth.clone());
+    assert!(bandwidth.bandwidth().unwrap().is_nan());
+}
+
+#[test]
+fn test_encode_update_as4_path() {
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![100, 4200000000])],
+            }),
+        ],
+        vec![],
+    );
+
+    // Peer supports 4-byte ASNs
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    let mut data: Vec<u8> = vec![];
+    update
+        .encode_with_capabilities(&mut data, &capabilities)
+        .expect("Encoding Update");
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        vec![
+            0, 0, // Withdrawn Routes Length
+            0, 17, // Path Attribute Length
+            64, 1, 1, 0, // ORIGIN
+            64, 2, 10, 2, 2, 0, 0, 0, 100, 250, 86, 234, 0, // AS_PATH
+        ]
+    );
+
+    // Peer only supports 2-byte ASNs, AS4_PATH is added
+    let mut data: Vec<u8> = vec![];
+    update
+        .encode_with_capabilities(&mut data, &Capabilities::default())
+        .expect("Encoding Update");
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        vec![
+            0, 0, // Withdrawn Routes Length
+            0, 26, // Path Attribute Length
+            64, 1, 1, 0, // ORIGIN
+            64, 2, 6, 2, 2, 0, 100, 0x5b, 0xa0, // AS_PATH
+            192, 17, 10, 2, 2, 0, 0, 0, 100, 250, 86, 234, 0, // AS4_PATH
+        ]
+    );
+
+    // Reconstruct the original AS_PATH
+    let mut message_data = vec![];
+    Message::Update(update)
+        .encode_with_capabilities(&mut message_data, &Capabilities::default())
+        .expect("Encoding Update");
+    let mut reader = Reader::new(std::io::Cursor::new(message_data));
+    match reader.read().expect("Decoding Update") {
+        (_, Message::Update(update)) => {
+            let as_path = update.effective_as_path().unwrap();
+            assert_eq!(as_path.sequence(), Some(vec![100, 4200000000]));
+        }
+        _ => panic!("Should be an UPDATE"),
+    }
+}
+
+#[test]
+fn test_effective_as_path_aggregator() {
+    let attributes = |aggregator_asn: Option<u32>| {
+        let mut attributes = vec![
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![100, AS_TRANS])],
+            }),
+            PathAttribute::AS4_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![4200000000])],
+            }),
+        ];
+        if let Some(asn) = aggregator_asn {
+            attributes.push(PathAttribute::AGGREGATOR((
+                asn,
+                "192.0.2.1".parse().unwrap(),
+            )));
+            attributes.push(PathAttribute::AS4_AGGREGATOR((
+                4200000001,
+                "192.0.2.1".parse().unwrap(),
+            )));
+        }
+        Update::new(vec![], attributes, vec![])
+    };
+
+    let as_path = |update: Update| update.effective_as_path().unwrap().sequence();
+    assert_eq!(as_path(attributes(None)), Some(vec![100, 4200000000]));
+    assert_eq!(
+        as_path(attributes(Some(AS_TRANS))),
+        Some(vec![100, 4200000000])
+    );
+    // Aggregated by a 2-byte ASN speaker, AS4_PATH is ignored (RFC6793 4.2.3)
+    assert_eq!(as_path(attributes(Some(65000))), Some(vec![100, AS_TRANS]));
+}
+
+#[test]
+fn test_encode_into() {
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+        vec![
+            PathAttribute::LOCAL_PREF(100),
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![100, 4200000000])],
+            }),
+        ],
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    );
+    let message = Message::Update(update);
+    let capabilities = Capabilities::default();
+
+    let mut buf = vec![0xff];
+    let length = message.encode_into(&mut buf, None).unwrap();
+    assert_eq!(&buf[1..], &encode_as_message(message.clone())[..]);
+    assert_eq!(length, buf.len() - 1);
+
+    buf.clear();
+    message.encode_into(&mut buf, Some(&capabilities)).unwrap();
+    let mut data = vec![];
+    message
+        .encode_with_capabilities(&mut data, &capabilities)
+        .unwrap();
+    assert_eq!(buf, data);
+
+    // Nothing is appended when encoding fails
+    let message = Message::Update(Update::new(
+        vec![],
+        vec![
+            PathAttribute::LOCAL_PREF(100),
+            PathAttribute::LOCAL_PREF(200),
+        ],
+        vec![],
+    ));
+    assert!(message.encode_into(&mut buf, None).is_err());
+    assert_eq!(buf, data);
+}
+
 #[test]
 fn test_encode_update_withdraw() {
-    let update = Update {
-        withdrawn_routes: vec![
+    let update = Update::new(
+        vec![
             NLRIEncoding::IP(("5.5.5.5".parse().unwrap(), 32).into()),
             NLRIEncoding::IP(("192.168.1.5".parse().unwrap(), 32).into()),
         ],
-        attributes: vec![
+        vec![
             PathAttribute::ORIGIN(Origin::IGP),
             PathAttribute::AS_PATH(ASPath {
                 segments: vec![Segment::AS_SEQUENCE(vec![64511])],
@@ -240,17 +464,17 @@ fn test_encode_update_withdraw() {
             PathAttribute::MULTI_EXIT_DISC(0),
             PathAttribute::LOCAL_PREF(100),
             // IPv6 withdraw
-            PathAttribute::MP_UNREACH_NLRI(MPUnreachNLRI {
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
                 afi: AFI::IPV6,
                 safi: SAFI::Unicast,
                 withdrawn_routes: vec![
                     NLRIEncoding::IP(("3001:10:10::".parse().unwrap(), 56).into()),
                     NLRIEncoding::IP(("2620:20:20::".parse().unwrap(), 48).into()),
                 ],
-            }),
+            })),
         ],
-        announced_routes: vec![],
-    };
+        vec![],
+    );
 
     let mut data: Vec<u8> = vec![];
     update.encode(&mut data).expect("Encoding Update");
@@ -273,6 +497,62 @@ fn test_encode_update_withdraw() {
     );
 }
 
+#[test]
+fn test_encode_update_withdraw_merged() {
+    let mp_unreach = |routes: &[&str]| {
+        PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+            afi: AFI::IPV6,
+            safi: SAFI::Unicast,
+            withdrawn_routes: routes
+                .iter()
+                .map(|route| NLRIEncoding::IP(route.parse().unwrap()))
+                .collect(),
+        }))
+    };
+    // IPv6 withdrawals both in an MP_UNREACH_NLRI and in the withdrawn routes
+    let update = Update::new(
+        vec![NLRIEncoding::IP("2620:20:20::/48".parse().unwrap())],
+        vec![mp_unreach(&["3001:10:10::/56"])],
+        vec![],
+    );
+    let mut data: Vec<u8> = vec![];
+    update.encode(&mut data).unwrap();
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        vec![
+            0, 0, // Withdrawn Routes Length
+            0, 21, // Path Attribute Length
+            // A single MPUnreachNlri
+            128, 15, 18, 0, 2, 1,
+            56, 48, 1, 0, 16, 0, 16, 0,
+            48, 38, 32, 0, 32, 0, 32,
+        ]
+    );
+    let expected = Update::new(
+        vec![],
+        vec![mp_unreach(&["3001:10:10::/56", "2620:20:20::/48"])],
+        vec![],
+    );
+    let bytes = encode_message(&Message::Update(update), None).unwrap();
+    assert_eq!(
+        decode_message(&bytes, &Capabilities::default()).unwrap(),
+        Message::Update(expected)
+    );
+
+    // An MP_UNREACH_NLRI can't hold the withdrawals of another family
+    let update = Update::new(
+        vec![NLRIEncoding::IP("2620:20:20::/48".parse().unwrap())],
+        vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+            afi: AFI::IPV6,
+            safi: SAFI::Multicast,
+            withdrawn_routes: vec![],
+        }))],
+        vec![],
+    );
+    assert!(update.encode(&mut vec![]).is_err());
+}
+
 #[test]
 fn test_encode_nlri_ip_vpn_mpls() {
     let nlri = NLRIEncoding::IP_VPN_MPLS((100, ("5.5.5.5".parse().unwrap(), 32).into(), 3200));
@@ -280,10 +560,21 @@ fn test_encode_nlri_ip_vpn_mpls() {
     nlri.encode(&mut data).unwrap();
     assert_eq!(
         data,
-        vec![0, 0, 12, 128, 0, 0, 0, 0, 0, 0, 0, 100, 5, 5, 5, 5]
+        vec![120, 0x00, 0xc8, 0x01, 0, 0, 0, 0, 0, 0, 0, 100, 5, 5, 5, 5]
     );
 }
 
+#[test]
+fn test_encode_nlri_ip_mpls() {
+    let nlri = NLRIEncoding::IP_MPLS((("10.10.128.0".parse().unwrap(), 17).into(), 16));
+    let mut data: Vec<u8> = vec![];
+    nlri.encode(&mut data).unwrap();
+    assert_eq!(data, vec![41, 0, 1, 1, 10, 10, 128]);
+
+    let nlri = NLRIEncoding::IP_MPLS((("10.0.0.0".parse().unwrap(), 8).into(), 1 << 20));
+    assert!(nlri.encode(&mut vec![]).is_err());
+}
+
 #[test]
 fn test_encode_keepalive() {
     let keepalive = Message::KeepAlive;
@@ -310,8 +601,7 @@ fn test_encode_keepalive() {
 #[test]
 fn test_encode_notification() {
     let notification = Notification {
-        major_err_code: 6,
-        minor_err_code: 3,
+        code: NotificationCode::Cease(CeaseSubcode::PeerDeconfigured),
         data: vec![],
     };
     let mut data: Vec<u8> = vec![];
@@ -322,8 +612,7 @@ fn test_encode_notification() {
 
     let msg = "Peer De-Configured".to_string();
     let notification = Notification {
-        major_err_code: 6,
-        minor_err_code: 3,
+        code: NotificationCode::Cease(CeaseSubcode::PeerDeconfigured),
         data: msg.into_bytes(),
     };
     let mut data: Vec<u8> = vec![];
@@ -346,6 +635,23 @@ fn test_encode_notification() {
     );
 }
 
+#[test]
+fn test_encode_notification_unknown_code() {
+    let notification = Notification::new(6, 200);
+    assert_eq!(
+        notification.code,
+        NotificationCode::Unknown {
+            major: 6,
+            minor: 200
+        }
+    );
+    let mut data: Vec<u8> = vec![];
+    notification
+        .encode(&mut data)
+        .expect("Encoding Notification");
+    assert_eq!(data, vec![6, 200]);
+}
+
 #[cfg(feature = "flowspec")]
 #[test]
 fn test_encode_flowspec_filter_prefix() {
@@ -400,3 +706,381 @@ fn test_encode_flowspec_filter_ports() {
         ]
     );
 }
+
+#[test]
+fn test_encode_update_add_path_families() {
+    let update = Update::new(
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+        vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+            AFI::IPV4,
+            SAFI::Mpls,
+            "192.0.2.1".parse().unwrap(),
+            vec![
+                NLRIEncoding::IP_MPLS((("10.1.0.0".parse().unwrap(), 16).into(), 16)),
+                NLRIEncoding::IP_MPLS_WITH_PATH_ID((
+                    ("10.2.0.0".parse().unwrap(), 16).into(),
+                    16,
+                    7,
+                )),
+            ],
+        )))],
+        vec![],
+    );
+
+    // ADD-PATH is only negotiated for sending IPv4 labeled unicast
+    let mut capabilities = Capabilities::default();
+    capabilities
+        .ADD_PATH_SUPPORT
+        .insert((AFI::IPV4, SAFI::Mpls), AddPathDirection::SendPaths);
+    let mut data: Vec<u8> = vec![];
+    update
+        .encode_with_capabilities(&mut data, &capabilities)
+        .expect("Encoding Update");
+    #[rustfmt::skip]
+    assert_eq!(
+        data,
+        vec![
+            0, 2, 8, 10, // Withdrawn Routes
+            0, 32, // Path Attribute Length
+            0x80, 14, 29, 0, 1, 4, 4, 192, 0, 2, 1, 0, // MP_REACH_NLRI
+            0, 0, 0, 0, 40, 0, 1, 1, 10, 1, // Path ID 0, 10.1.0.0/16 label 16
+            0, 0, 0, 7, 40, 0, 1, 1, 10, 2, // Path ID 7, 10.2.0.0/16 label 16
+        ]
+    );
+
+    // The receiving side reads the Path Identifiers back
+    let mut message_data = vec![];
+    Message::Update(update)
+        .encode_with_capabilities(&mut message_data, &capabilities)
+        .expect("Encoding Update");
+    let mut reader = Reader::new(std::io::Cursor::new(message_data));
+    match reader.read().expect("Decoding Update") {
+        (_, Message::Update(update)) => match update.get(Identifier::MP_REACH_NLRI) {
+            Some(PathAttribute::MP_REACH_NLRI(mp_reach)) => {
+                let path_ids: Vec<_> = mp_reach
+                    .announced_routes
+                    .iter()
+                    .map(|route| route.path_id())
+                    .collect();
+                assert_eq!(path_ids, vec![Some(0), Some(7)]);
+            }
+            _ => panic!("MP_REACH_NLRI not present"),
+        },
+        (_, message) => panic!("Unexpected {:?}", message),
+    }
+}
+
+#[test]
+fn test_update_split() {
+    let ipv4 = |i: u32| NLRIEncoding::IP((std::net::Ipv4Addr::from(i << 8).into(), 24).into());
+    let ipv6 = |i: u32| {
+        let address = std::net::Ipv6Addr::new(0x2001, 0xdb8, i as u16, 0, 0, 0, 0, 0);
+        NLRIEncoding::IP((address.into(), 48).into())
+    };
+    let update = Update::new(
+        (0..1500).map(|i| ipv4(0x0a_0000 + i)).collect(),
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000, 4_200_000_000])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                (0..1000).map(ipv6).collect(),
+            ))),
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: (1000..1500).map(ipv6).collect(),
+            })),
+        ],
+        (0..2000).map(|i| ipv4(0x0b_0000 + i)).collect(),
+    );
+    let mut capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: false,
+        ..Capabilities::default()
+    };
+    capabilities
+        .ADD_PATH_SUPPORT
+        .insert((AFI::IPV6, SAFI::Unicast), AddPathDirection::SendPaths);
+
+    let updates = update.split(4096, &capabilities);
+    let mut withdrawn = vec![];
+    let mut announced = vec![];
+    for split in updates {
+        let mut data = vec![];
+        Message::Update(split.clone())
+            .encode_with_capabilities(&mut data, &capabilities)
+            .expect("Encoding split Update");
+        assert!(data.len() <= 4096);
+
+        let mut split = split;
+        split.normalize();
+        if split.announced_routes.is_empty() {
+            assert!(split.get(Identifier::AS_PATH).is_none());
+        } else {
+            assert!(split.get(Identifier::AS_PATH).is_some());
+        }
+        withdrawn.extend(split.withdrawn_routes);
+        announced.extend(split.announced_routes);
+    }
+
+    let mut expected = update.clone();
+    expected.normalize();
+    assert_eq!(withdrawn.len(), expected.withdrawn_routes.len());
+    assert_eq!(announced.len(), expected.announced_routes.len());
+    for route in &expected.withdrawn_routes {
+        assert!(withdrawn.contains(route));
+    }
+    for route in &expected.announced_routes {
+        assert!(announced.contains(route));
+    }
+
+    // UPDATEs that fit are left alone
+    let update = Update::new(
+        vec![],
+        vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+        vec![ipv4(1)],
+    );
+    assert_eq!(update.split(4096, &capabilities).len(), 1);
+}
+
+#[test]
+fn test_update_split_withdrawal_families() {
+    let ipv6 = |i: u32| {
+        let address = std::net::Ipv6Addr::new(0x2001, 0xdb8, i as u16, 0, 0, 0, 0, 0);
+        NLRIEncoding::IP((address.into(), 48).into())
+    };
+    let labeled = |i: u32| {
+        let prefix = (std::net::Ipv4Addr::from((0x0a_0000 + i) << 8).into(), 24).into();
+        NLRIEncoding::IP_MPLS((prefix, 16))
+    };
+    let capabilities = Capabilities::default();
+    for &count in &[3, 600] {
+        let update = Update::new(
+            vec![],
+            vec![
+                PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                    afi: AFI::IPV6,
+                    safi: SAFI::Unicast,
+                    withdrawn_routes: (0..count).map(ipv6).collect(),
+                })),
+                PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                    afi: AFI::IPV4,
+                    safi: SAFI::Mpls,
+                    withdrawn_routes: (0..count).map(labeled).collect(),
+                })),
+            ],
+            vec![],
+        );
+        let updates = update.split(4096, &capabilities);
+        assert!(updates.len() >= 2);
+        let mut withdrawn = 0;
+        for split in updates {
+            let mut data = vec![];
+            Message::Update(split.clone())
+                .encode_with_capabilities(&mut data, &capabilities)
+                .expect("Encoding split Update");
+            assert!(data.len() <= 4096);
+            assert_eq!(split.attributes.len(), 1);
+            withdrawn += split.withdrawn().count();
+        }
+        assert_eq!(withdrawn, 2 * count as usize);
+    }
+}
+
+#[test]
+fn test_normalize_multicast_withdrawal() {
+    let prefix = (
+        std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).into(),
+        32,
+    )
+        .into();
+    let mut update = Update::new(
+        vec![],
+        vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+            afi: AFI::IPV6,
+            safi: SAFI::Multicast,
+            withdrawn_routes: vec![NLRIEncoding::IP(prefix)],
+        }))],
+        vec![],
+    );
+    let capabilities = Capabilities::default();
+    let mut data = vec![];
+    Message::Update(update.clone())
+        .encode_with_capabilities(&mut data, &capabilities)
+        .expect("Encoding Update");
+
+    // The SAFI of the route can't be derived from its encoding, so it stays in MP_UNREACH_NLRI
+    update.normalize();
+    assert!(update.withdrawn_routes.is_empty());
+    let withdrawn: Vec<_> = update.withdrawn().collect();
+    assert_eq!(withdrawn.len(), 1);
+    assert_eq!(
+        (withdrawn[0].0, withdrawn[0].1),
+        (AFI::IPV6, SAFI::Multicast)
+    );
+
+    let mut normalized = vec![];
+    Message::Update(update)
+        .encode_with_capabilities(&mut normalized, &capabilities)
+        .expect("Encoding normalized Update");
+    assert_eq!(data, normalized);
+}
+
+#[cfg(feature = "flowspec")]
+#[test]
+fn test_normalize_flowspec_without_prefix() {
+    let filters = vec![FlowspecFilter::Port(vec![(NumericOperator::EQ, 80)])];
+    let mut update = Update::new(
+        vec![],
+        vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+            afi: AFI::IPV6,
+            safi: SAFI::Flowspec,
+            withdrawn_routes: vec![NLRIEncoding::FLOWSPEC(filters)],
+        }))],
+        vec![],
+    );
+    // The AFI of a rule not filtering on prefixes can't be derived from its encoding
+    update.normalize();
+    assert!(update.withdrawn_routes.is_empty());
+    let withdrawn: Vec<_> = update.withdrawn().collect();
+    assert_eq!(withdrawn.len(), 1);
+    assert_eq!(
+        (withdrawn[0].0, withdrawn[0].1),
+        (AFI::IPV6, SAFI::Flowspec)
+    );
+}
+
+#[test]
+fn test_pack_updates() {
+    let attributes = |local_pref: u32| {
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::LOCAL_PREF(local_pref),
+        ]
+    };
+    // Interleaved routes of two attribute sets, the second one listed in another order
+    let routes = (0..3000u32).map(|i| {
+        let prefix = (std::net::Ipv4Addr::from(i << 8).into(), 24).into();
+        if i % 2 == 0 {
+            (prefix, attributes(100))
+        } else {
+            let mut attributes = attributes(200);
+            attributes.reverse();
+            (prefix, attributes)
+        }
+    });
+    let capabilities = Capabilities::default();
+    let updates = pack_updates(routes, 4096, &capabilities);
+
+    // 1500 routes of 4 bytes per attribute set, 2 messages each
+    assert_eq!(updates.len(), 4);
+    let mut announced = 0;
+    for (i, update) in updates.iter().enumerate() {
+        match update.get(Identifier::LOCAL_PREF) {
+            Some(PathAttribute::LOCAL_PREF(local_pref)) => {
+                assert_eq!(*local_pref, if i < 2 { 100 } else { 200 })
+            }
+            _ => panic!("LOCAL_PREF not present"),
+        }
+        let mut data = vec![];
+        Message::Update(update.clone())
+            .encode_with_capabilities(&mut data, &capabilities)
+            .expect("Encoding packed Update");
+        assert!(data.len() <= 4096);
+        announced += update.announced_routes.len();
+    }
+    assert_eq!(announced, 3000);
+
+    // IPv6 prefixes without an MP_REACH_NLRI of their AFI are left out of the NLRI
+    let routes: Vec<(Prefix, Vec<PathAttribute>)> = vec![
+        ("2001:db8::/32".parse().unwrap(), attributes(100)),
+        ("10.0.0.0/8".parse().unwrap(), attributes(100)),
+    ];
+    let updates = pack_updates(routes, 4096, &capabilities);
+    assert_eq!(updates.len(), 1);
+    assert_eq!(
+        updates[0].announced_routes,
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())]
+    );
+    let updates = pack_updates(
+        vec![("2001:db8::/32".parse().unwrap(), attributes(100))],
+        4096,
+        &capabilities,
+    );
+    assert!(updates.is_empty());
+
+    // Nor are they added to an MP_REACH_NLRI of labeled routes
+    let mut labeled = attributes(100);
+    labeled.push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+        AFI::IPV6,
+        SAFI::Mpls,
+        "2001:db8::1".parse().unwrap(),
+        vec![],
+    ))));
+    let updates = pack_updates(
+        vec![("2001:db8::/32".parse().unwrap(), labeled)],
+        4096,
+        &capabilities,
+    );
+    assert!(updates.is_empty());
+}
+
+#[test]
+fn test_update_graceful_shutdown() {
+    let mut update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::COMMUNITY(vec![0xfde8_0064]),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![NLRIEncoding::IP("2001:db8::/32".parse().unwrap())],
+            ))),
+        ],
+        vec![],
+    );
+    assert!(!update.is_graceful_shutdown());
+    update.apply_graceful_shutdown();
+    update.apply_graceful_shutdown();
+    assert!(update.is_graceful_shutdown());
+    match update.get(Identifier::COMMUNITY) {
+        Some(PathAttribute::COMMUNITY(communities)) => {
+            assert_eq!(communities, &vec![0xfde8_0064, GRACEFUL_SHUTDOWN])
+        }
+        _ => panic!("COMMUNITY not present"),
+    }
+
+    // Detected on receipt
+    let mut data = vec![];
+    Message::Update(update)
+        .encode(&mut data)
+        .expect("Encoding Update");
+    let mut reader = Reader::new(&data[..]);
+    match reader.read().unwrap().1 {
+        Message::Update(update) => {
+            assert!(update.is_graceful_shutdown());
+            match update.get(Identifier::LOCAL_PREF) {
+                Some(PathAttribute::LOCAL_PREF(local_pref)) => assert_eq!(*local_pref, 0),
+                _ => panic!("LOCAL_PREF not present"),
+            }
+        }
+        message => panic!("Unexpected message {:?}", message),
+    }
+
+    // Withdrawals are not modified
+    let mut update = Update::new(
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+        vec![],
+        vec![],
+    );
+    update.apply_graceful_shutdown();
+    assert!(update.attributes.is_empty());
+}
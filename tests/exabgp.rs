@@ -0,0 +1,120 @@
+#![cfg(feature = "json")]
+use bgp_rs::*;
+
+const UPDATE: &str = r#"{"exabgp": "4.0.1", "time": 1561601637.93, "host": "lab", "pid": 1234,
+    "ppid": 1, "counter": 7, "type": "update",
+    "neighbor": {
+        "address": {"local": "2001:db8::2", "peer": "2001:db8::1"},
+        "asn": {"local": 65000, "peer": 4200000000},
+        "direction": "receive",
+        "message": {"update": {
+            "attribute": {
+                "origin": "igp", "as-path": [4200000000, 64497, [64498, 64499]], "med": 10,
+                "local-preference": 100, "atomic-aggregate": true, "aggregator": "64497:192.0.2.1",
+                "community": [[64496, 100]], "large-community": [[4200000000, 1, 2]],
+                "originator-id": "192.0.2.9", "cluster-list": ["192.0.2.10"]
+            },
+            "announce": {
+                "ipv4 unicast": {"192.0.2.1": [{"nlri": "198.51.100.0/24"}]},
+                "ipv6 unicast": {"2001:db8::1": [{"nlri": "2001:db8:1::/48"}]}
+            },
+            "withdraw": {"ipv6 unicast": [{"nlri": "2001:db8:2::/48"}]}
+        }}
+    }}"#;
+
+#[test]
+fn test_exabgp_update() {
+    let message = ExaBgpMessage::from_json(UPDATE).unwrap();
+    assert_eq!(message.peer_asn, 4_200_000_000);
+    assert_eq!(message.local_address.to_string(), "2001:db8::2");
+    let update = match &message.message {
+        Message::Update(update) => update,
+        message => panic!("Unexpected message: {:?}", message),
+    };
+    let identifiers: Vec<Identifier> = update.attributes.iter().map(PathAttribute::id).collect();
+    assert_eq!(
+        identifiers,
+        vec![
+            Identifier::ORIGIN,
+            Identifier::AS_PATH,
+            Identifier::NEXT_HOP,
+            Identifier::MULTI_EXIT_DISC,
+            Identifier::LOCAL_PREF,
+            Identifier::ATOMIC_AGGREGATOR,
+            Identifier::AGGREGATOR,
+            Identifier::COMMUNITY,
+            Identifier::ORIGINATOR_ID,
+            Identifier::CLUSTER_LIST,
+            Identifier::MP_REACH_NLRI,
+            Identifier::MP_UNREACH_NLRI,
+            Identifier::LARGE_COMMUNITY,
+        ]
+    );
+    assert_eq!(update.announced_routes.len(), 1);
+
+    let commands = message.to_commands().unwrap();
+    assert_eq!(commands.len(), 3);
+    assert_eq!(
+        commands[1],
+        "neighbor 2001:db8::1 announce route 2001:db8:1::/48 next-hop 2001:db8::1 origin igp \
+         as-path [ 4200000000 64497 ( 64498 64499 ) ] med 10 local-preference 100 \
+         atomic-aggregate aggregator ( 64497:192.0.2.1 ) community [ 64496:100 ] \
+         large-community [ 4200000000:1:2 ]"
+    );
+    assert_eq!(
+        commands[2],
+        "neighbor 2001:db8::1 withdraw route 2001:db8:2::/48"
+    );
+
+    // Converting back gives the same JSON
+    let json = message.to_json().unwrap();
+    let roundtrip = ExaBgpMessage::from_json(&json).unwrap();
+    assert_eq!(
+        format!("{:?}", roundtrip.message),
+        format!("{:?}", message.message)
+    );
+    assert_eq!(roundtrip.to_json().unwrap(), json);
+}
+
+#[test]
+fn test_exabgp_open_notification() {
+    let json = r#"{"exabgp": "4.0.1", "time": 1561601637.93, "host": "lab", "type": "open",
+        "neighbor": {
+            "address": {"local": "192.0.2.2", "peer": "192.0.2.1"},
+            "asn": {"local": 65000, "peer": 65001},
+            "direction": "receive",
+            "open": {
+                "version": 4, "asn": 65001, "hold_time": 90, "router_id": "192.0.2.1",
+                "capabilities": {
+                    "1": {"name": "multiprotocol", "families": ["ipv4 unicast", "ipv6 unicast"]},
+                    "65": {"name": "asn4", "asn4": 65001}
+                }
+            }
+        }}"#;
+    let message = ExaBgpMessage::from_json(json).unwrap();
+    match &message.message {
+        Message::Open(open) => {
+            assert_eq!(open.hold_timer, 90);
+            let capabilities = Capabilities::from_parameters(open.parameters.clone());
+            assert!(capabilities.FOUR_OCTET_ASN_SUPPORT);
+            assert_eq!(capabilities.MP_BGP_SUPPORT.len(), 2);
+        }
+        message => panic!("Unexpected message: {:?}", message),
+    }
+    assert!(message.to_json().unwrap().contains(r#""ipv6 unicast""#));
+    assert!(message.to_commands().is_err());
+
+    let json = json
+        .replace(r#""type": "open""#, r#""type": "notification""#)
+        .replace(
+            r#""direction": "receive","#,
+            r#""direction": "receive", "notification": {"code": 6, "subcode": 4, "data": ""},"#,
+        );
+    match ExaBgpMessage::from_json(&json).unwrap().message {
+        Message::Notification(notification) => {
+            assert_eq!(notification.major_err_code(), 6);
+            assert_eq!(notification.minor_err_code(), 4);
+        }
+        message => panic!("Unexpected message: {:?}", message),
+    }
+}
@@ -0,0 +1,136 @@
+#![cfg(feature = "export")]
+
+use arrow_array::{Array, StringArray, UInt32Array};
+use bgp_rs::*;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+fn update() -> Update {
+    Update::new(
+        vec![],
+        vec![
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![65000, 23456]),
+                    Segment::AS_SET(vec![65002, 65003]),
+                ],
+            }),
+            PathAttribute::AS4_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![4_200_000_000]),
+                    Segment::AS_SET(vec![65002, 65003]),
+                ],
+            }),
+            PathAttribute::COMMUNITY(vec![(65000 << 16) + 100, (65000 << 16) + 200]),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![NLRIEncoding::IP("2001:db8:1::/48".parse().unwrap())],
+            ))),
+            PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: vec![NLRIEncoding::IP("2001:db8:2::/48".parse().unwrap())],
+            })),
+        ],
+        vec![],
+    )
+}
+
+#[test]
+fn test_route_rows() {
+    let rows = RouteRow::from_update(&update(), 1_600_000_000);
+    assert_eq!(
+        rows,
+        vec![
+            RouteRow {
+                timestamp: 1_600_000_000,
+                prefix: "2001:db8:1::/48".to_string(),
+                withdrawn: false,
+                origin_asn: None,
+                as_path: "65000 4200000000 {65002,65003}".to_string(),
+                communities: "65000:100 65000:200".to_string(),
+                next_hop: Some("2001:db8::1".parse().unwrap()),
+            },
+            RouteRow {
+                timestamp: 1_600_000_000,
+                prefix: "2001:db8:2::/48".to_string(),
+                withdrawn: true,
+                origin_asn: None,
+                as_path: String::new(),
+                communities: String::new(),
+                next_hop: None,
+            },
+        ]
+    );
+
+    // RIB entries are flattened the same way
+    let update = update();
+    let row = RouteRow::new(
+        1_600_000_000,
+        &"2001:db8:1::/48".parse().unwrap(),
+        &update.attributes,
+    );
+    assert_eq!(row, rows[0]);
+}
+
+#[test]
+fn test_csv_export() {
+    let mut writer = CsvRouteWriter::new(vec![]);
+    for row in RouteRow::from_update(&update(), 1_600_000_000) {
+        writer.write(&row).unwrap();
+    }
+    let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    assert_eq!(
+        csv,
+        "timestamp,prefix,withdrawn,origin_asn,as_path,communities,next_hop\n\
+         1600000000,2001:db8:1::/48,false,,\"65000 4200000000 {65002,65003}\",65000:100 65000:200,2001:db8::1\n\
+         1600000000,2001:db8:2::/48,true,,,,\n"
+    );
+}
+
+#[test]
+fn test_parquet_export() {
+    let rows = RouteRow::from_update(&update(), 1_600_000_000);
+    let batch = route_batch(&rows).unwrap();
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(batch.schema(), route_schema());
+
+    let path = std::env::temp_dir().join(format!("bgp-rs-export-{}.parquet", std::process::id()));
+    let mut writer = ParquetRouteWriter::new(std::fs::File::create(&path).unwrap()).unwrap();
+    writer.write(&rows).unwrap();
+    writer.write(&rows[..1]).unwrap();
+    writer.close().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+        3
+    );
+
+    let batch = &batches[0];
+    let prefixes = batch
+        .column(1)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(prefixes.value(0), "2001:db8:1::/48");
+    let origins = batch
+        .column(3)
+        .as_any()
+        .downcast_ref::<UInt32Array>()
+        .unwrap();
+    assert!(origins.is_null(0));
+    let next_hops = batch
+        .column(6)
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(next_hops.value(0), "2001:db8::1");
+    assert!(next_hops.is_null(1));
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "ffi")]
+use bgp_rs::*;
+
+use std::ffi::CStr;
+use std::ptr;
+
+#[test]
+fn test_ffi_update() {
+    let update = Message::Update(Update::new(
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![64496, 64497]),
+                    Segment::AS_SET(vec![64498]),
+                ],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::COMMUNITY(vec![0xfbf0_0001]),
+        ],
+        vec![NLRIEncoding::IP("198.51.100.0/24".parse().unwrap())],
+    ));
+    let data = encode_message(&update, None).unwrap();
+
+    unsafe {
+        let message = bgp_message_parse(data.as_ptr(), data.len(), false, ptr::null_mut());
+        assert!(!message.is_null());
+        assert_eq!(bgp_message_type(message), 2);
+
+        assert_eq!(bgp_message_prefix_count(message, false), 1);
+        let mut prefix = BgpPrefix {
+            afi: 0,
+            safi: 0,
+            length: 0,
+            address: [0; 16],
+            path_id: 0,
+        };
+        assert!(bgp_message_prefix(message, false, 0, &mut prefix));
+        assert_eq!((prefix.afi, prefix.safi, prefix.length), (1, 1, 24));
+        assert_eq!(&prefix.address[..4], &[198, 51, 100, 0]);
+        assert!(!bgp_message_prefix(message, false, 1, &mut prefix));
+        assert!(bgp_message_prefix(message, true, 0, &mut prefix));
+        assert_eq!(prefix.length, 8);
+
+        // The number of ASNs is returned even when the buffer is too small
+        let mut asns = [0u32; 2];
+        assert_eq!(bgp_message_as_path(message, asns.as_mut_ptr(), 2), 3);
+        assert_eq!(asns, [64496, 64497]);
+        let mut communities = [0u32; 4];
+        assert_eq!(
+            bgp_message_communities(message, communities.as_mut_ptr(), 4),
+            1
+        );
+        assert_eq!(communities[0], 0xfbf0_0001);
+
+        let json = bgp_message_to_json(message);
+        let value = CStr::from_ptr(json).to_str().unwrap().to_string();
+        assert!(value.contains(r#""type":"UPDATE""#), "{}", value);
+        assert!(value.contains("198.51.100.0/24"), "{}", value);
+        bgp_string_free(json);
+        bgp_message_free(message);
+    }
+}
+
+#[test]
+fn test_ffi_parse_error() {
+    let data = [0xff; 18];
+    let mut error = ptr::null_mut();
+    unsafe {
+        let message = bgp_message_parse(data.as_ptr(), data.len(), true, &mut error);
+        assert!(message.is_null());
+        assert_eq!(
+            CStr::from_ptr(error).to_str().unwrap(),
+            "failed to fill whole buffer"
+        );
+        bgp_string_free(error);
+        assert_eq!(bgp_message_type(message), 0);
+    }
+}
@@ -0,0 +1,56 @@
+use bgp_rs::*;
+
+#[test]
+fn test_community_filter_parse() {
+    for filter in &[
+        "65000:*",
+        "*:666",
+        "*:*",
+        "65000:666",
+        "4200000000:*:100",
+        "*:*:*",
+    ] {
+        let parsed: CommunityFilter = filter.parse().unwrap();
+        assert_eq!(&parsed.to_string(), filter);
+    }
+    assert_eq!(
+        "65000:*".parse::<CommunityFilter>().unwrap(),
+        CommunityFilter::Standard {
+            asn: Some(65000),
+            value: None
+        }
+    );
+    assert!("65536:*".parse::<CommunityFilter>().is_err());
+    assert!("65000".parse::<CommunityFilter>().is_err());
+    assert!("65000:**".parse::<CommunityFilter>().is_err());
+    assert!("1:2:3:4".parse::<CommunityFilter>().is_err());
+}
+
+#[test]
+fn test_community_filter_update() {
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::COMMUNITY(vec![0xfde8_0064, 0xfde9_029a]),
+            PathAttribute::LARGE_COMMUNITY(vec![(65002, 1, 100)]),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    );
+    let matches = |filter: &str| {
+        filter
+            .parse::<CommunityFilter>()
+            .unwrap()
+            .matches_update(&update)
+    };
+    assert!(matches("65000:*"));
+    assert!(matches("*:666"));
+    assert!(matches("65001:666"));
+    assert!(!matches("65000:666"));
+    assert!(!matches("65003:*"));
+    assert!(matches("65002:*:*"));
+    assert!(matches("*:1:100"));
+    assert!(!matches("*:2:*"));
+    // Standard filters only match standard communities
+    assert!(!matches("65002:*"));
+}
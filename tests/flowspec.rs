@@ -256,6 +256,12 @@ fn test_filter_roundtrips() {
     ];
 
     for filter in filters {
-        _filter_roundtrip(&filter, AFI::IPV4);
+        let afi = match &filter {
+            FlowspecFilter::DestinationPrefix(prefix) | FlowspecFilter::SourcePrefix(prefix) => {
+                prefix.protocol
+            }
+            _ => AFI::IPV4,
+        };
+        _filter_roundtrip(&filter, afi);
     }
 }
@@ -0,0 +1,73 @@
+#![cfg(feature = "fuzz")]
+use arbitrary::{Arbitrary, Unstructured};
+use bgp_rs::{roundtrip, Message, Prefix};
+
+// Deterministic pseudo-random input (xorshift), standing in for the data of a fuzzer
+fn random_bytes(seed: u64, length: usize) -> Vec<u8> {
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn test_arbitrary_message_roundtrip() {
+    let mut updates = 0;
+    for seed in 0..2000 {
+        let data = random_bytes(seed, 1024);
+        let message = Message::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        if let Message::Update(_) = message {
+            updates += 1;
+        }
+        if let Err(error) = roundtrip(&message) {
+            panic!("{:?}: {}", message, error);
+        }
+    }
+    assert!(updates > 100);
+}
+
+#[test]
+fn test_arbitrary_prefix() {
+    for seed in 0..200 {
+        let data = random_bytes(seed, 32);
+        let prefix = Prefix::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        let octets = prefix.masked_octets();
+        // Bits beyond the mask are cleared
+        if prefix.length % 8 != 0 {
+            let last = octets[octets.len() - 1];
+            assert_eq!(last & (0xff >> (prefix.length % 8)), 0, "{:?}", prefix);
+        }
+        assert!(prefix.prefix[octets.len()..]
+            .iter()
+            .all(|octet| *octet == 0));
+    }
+}
+
+#[test]
+#[cfg(feature = "flowspec")]
+fn test_arbitrary_flowspec_filter() {
+    use bgp_rs::{FlowspecFilter, AFI};
+    use std::io::Cursor;
+
+    for seed in 0..500 {
+        let data = random_bytes(seed, 256);
+        let filter = FlowspecFilter::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        let afi = match &filter {
+            FlowspecFilter::DestinationPrefix(prefix) | FlowspecFilter::SourcePrefix(prefix) => {
+                prefix.protocol
+            }
+            _ => AFI::IPV4,
+        };
+        let mut encoded = vec![];
+        filter.encode(&mut encoded).unwrap();
+        let decoded = FlowspecFilter::parse(&mut Cursor::new(&encoded), afi).unwrap();
+        let mut reencoded = vec![];
+        decoded.encode(&mut reencoded).unwrap();
+        assert_eq!(encoded, reencoded, "{:?}", filter);
+    }
+}
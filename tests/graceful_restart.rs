@@ -0,0 +1,101 @@
+use bgp_rs::*;
+use std::time::Duration;
+
+fn update(routes: &[&str]) -> Update {
+    let (ipv4, ipv6): (Vec<NLRIEncoding>, Vec<NLRIEncoding>) = routes
+        .iter()
+        .map(|route| NLRIEncoding::IP(route.parse().unwrap()))
+        .partition(|route| match route {
+            NLRIEncoding::IP(prefix) => prefix.protocol == AFI::IPV4,
+            _ => false,
+        });
+    let mut attributes = vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())];
+    if !ipv6.is_empty() {
+        attributes.push(PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+            AFI::IPV6,
+            SAFI::Unicast,
+            "2001:db8::1".parse().unwrap(),
+            ipv6,
+        ))));
+    }
+    Update::new(vec![], attributes, ipv4)
+}
+
+fn key(route: &str) -> RouteKey {
+    let prefix: Prefix = route.parse().unwrap();
+    RouteKey::new(prefix.protocol, SAFI::Unicast, &NLRIEncoding::IP(prefix)).unwrap()
+}
+
+#[test]
+fn test_graceful_restart_end_of_rib() {
+    let families = vec![(AFI::IPV4, SAFI::Unicast), (AFI::IPV6, SAFI::Unicast)]
+        .into_iter()
+        .collect();
+    let mut state =
+        GracefulRestartState::new(families, Duration::from_secs(120), Duration::from_secs(360));
+    let mut rib = Rib::new();
+    state.apply(
+        &mut rib,
+        &update(&["10.0.0.0/8", "10.1.0.0/16", "2001:db8::/32"]),
+        1,
+    );
+    state.apply(&mut rib, &Update::end_of_rib(AFI::IPV4, SAFI::Unicast), 1);
+    assert!(state.end_of_rib_received(AFI::IPV4, SAFI::Unicast));
+    assert!(!state.end_of_rib_received(AFI::IPV6, SAFI::Unicast));
+    assert!(!state.is_restarting());
+    assert_eq!(rib.len(), 3);
+
+    state.session_down(&mut rib, &1);
+    assert!(state.is_restarting());
+    assert!(!state.end_of_rib_received(AFI::IPV4, SAFI::Unicast));
+    assert!(rib.is_stale(&1, &key("10.0.0.0/8")));
+    assert_eq!(state.expire(&mut rib, &1), 0);
+    state.session_up();
+
+    // Routes announced again are no longer stale
+    state.apply(&mut rib, &update(&["10.0.0.0/8"]), 1);
+    assert!(!rib.is_stale(&1, &key("10.0.0.0/8")));
+    assert!(rib.is_stale(&1, &key("10.1.0.0/16")));
+
+    state.apply(&mut rib, &Update::end_of_rib(AFI::IPV4, SAFI::Unicast), 1);
+    assert!(rib.get(&1, &key("10.0.0.0/8")).is_some());
+    assert!(rib.get(&1, &key("10.1.0.0/16")).is_none());
+    assert!(rib.is_stale(&1, &key("2001:db8::/32")));
+    assert!(state.is_restarting());
+
+    state.apply(&mut rib, &Update::end_of_rib(AFI::IPV6, SAFI::Unicast), 1);
+    assert!(!state.is_restarting());
+    assert_eq!(rib.len(), 1);
+    assert_eq!(
+        rib.longest_match(AFI::IPV4, SAFI::Unicast, &"10.1.1.1/32".parse().unwrap())
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_graceful_restart_timers() {
+    // The routes of families not preserved by the peer are removed right away
+    let families = vec![(AFI::IPV4, SAFI::Unicast)].into_iter().collect();
+    let mut state =
+        GracefulRestartState::new(families, Duration::from_secs(0), Duration::from_secs(0));
+    let mut rib = Rib::new();
+    state.apply(&mut rib, &update(&["10.0.0.0/8", "2001:db8::/32"]), 1);
+    state.session_down(&mut rib, &1);
+    assert_eq!(rib.len(), 1);
+
+    // The session was not re-established within the restart time
+    assert_eq!(state.expire(&mut rib, &1), 1);
+    assert!(rib.is_empty());
+    assert!(!state.is_restarting());
+
+    // End-of-RIB was not received within the stale time
+    state.apply(&mut rib, &update(&["10.0.0.0/8"]), 1);
+    state.session_down(&mut rib, &1);
+    state.restart_time = Duration::from_secs(120);
+    assert_eq!(state.expire(&mut rib, &1), 0);
+    state.session_up();
+    assert_eq!(state.expire(&mut rib, &1), 1);
+    assert!(rib.is_empty());
+    assert_eq!(state.expire(&mut rib, &1), 0);
+}
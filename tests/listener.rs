@@ -0,0 +1,74 @@
+#![cfg(feature = "async")]
+use bgp_rs::*;
+use std::net::TcpStream;
+use std::thread;
+
+fn runtime() -> tokio::runtime::Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_listener_receives_updates() {
+    runtime().block_on(async {
+        let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+        config.peer_asn = Some(65001);
+        let listener = BgpListener::bind("127.0.0.1:0", config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let config = PeerConfig::new(65001, "2.2.2.2".parse().unwrap());
+            let mut session = BgpSession::connect(stream, config).unwrap();
+            session
+                .send_update(&Update::end_of_rib(AFI::IPV4, SAFI::Unicast))
+                .unwrap();
+            // Wait for the Cease sent by the listener
+            match session.recv() {
+                Ok(Message::Notification(notification)) => notification,
+                result => panic!("Expected NOTIFICATION, got {:?}", result),
+            }
+        });
+
+        let (mut peer, _addr) = listener.accept().await.unwrap();
+        assert_eq!(peer.peer_open().asn(), 65001);
+        assert_eq!(peer.capabilities().hold_time, Some(90));
+        let update = peer.next_update().await.unwrap().unwrap();
+        assert!(update.is_end_of_rib());
+        peer.close(CeaseSubcode::AdministrativeShutdown)
+            .await
+            .unwrap();
+
+        let notification = client.join().unwrap();
+        assert_eq!(
+            notification.code,
+            NotificationCode::Cease(CeaseSubcode::AdministrativeShutdown)
+        );
+    });
+}
+
+#[test]
+fn test_listener_rejects_peer() {
+    runtime().block_on(async {
+        let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+        config.peer_asn = Some(65002);
+        let listener = BgpListener::bind("127.0.0.1:0", config).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(addr).unwrap();
+            let config = PeerConfig::new(65001, "2.2.2.2".parse().unwrap());
+            BgpSession::connect(stream, config).err().unwrap()
+        });
+
+        let error = listener.accept().await.err().unwrap();
+        assert_eq!(
+            error.to_notification().code,
+            NotificationCode::OpenMessage(OpenMessageSubcode::BadPeerAS)
+        );
+        // The client receives the Notification instead of a KEEPALIVE
+        assert!(client.join().unwrap().to_string().contains("Bad Peer AS"));
+    });
+}
@@ -0,0 +1,98 @@
+use bgp_rs::*;
+use std::io::Cursor;
+
+fn update() -> Update {
+    Update::new(
+        vec![NLRIEncoding::IP("10.1.0.0/16".parse().unwrap())],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![
+                    NLRIEncoding::IP("2001:db8:1::/48".parse().unwrap()),
+                    NLRIEncoding::IP("2001:db8:2::/48".parse().unwrap()),
+                ],
+            ))),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    )
+}
+
+#[test]
+fn test_metrics() {
+    let mut data = vec![];
+    Message::Update(update()).encode(&mut data).unwrap();
+    Message::KeepAlive.encode(&mut data).unwrap();
+    let length = data.len() as u64;
+    // An UPDATE holding a truncated attribute
+    data.extend_from_slice(&[0xff; 16]);
+    data.extend_from_slice(&[0, 27, 2, 0, 0, 0, 4, 0x40, 1, 2, 0]);
+
+    let mut metrics = Metrics::new();
+    assert_eq!(metrics.error_rate(), 0.0);
+    let mut reader = Reader::new(Cursor::new(data));
+    for _ in 0..3 {
+        metrics.record(&reader.read());
+    }
+    assert_eq!(metrics.message_count(), 2);
+    assert_eq!(metrics.messages[&MessageType::Update], 1);
+    assert_eq!(metrics.messages[&MessageType::KeepAlive], 1);
+    assert_eq!(metrics.bytes, length);
+    assert_eq!(metrics.announced[&(AFI::IPV4, SAFI::Unicast)], 1);
+    assert_eq!(metrics.announced[&(AFI::IPV6, SAFI::Unicast)], 2);
+    assert_eq!(metrics.withdrawn[&(AFI::IPV4, SAFI::Unicast)], 1);
+    assert!(!metrics.withdrawn.contains_key(&(AFI::IPV6, SAFI::Unicast)));
+
+    // UPDATE Message Error
+    assert_eq!(metrics.errors[&3], 1);
+    assert_eq!(metrics.error_count(), 1);
+    assert!((metrics.error_rate() - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(metrics.session_state, SessionState::Idle);
+    assert_eq!(SessionState::Established.value(), 6);
+}
+
+#[cfg(feature = "prometheus")]
+#[test]
+fn test_prometheus_collector() {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    let metrics = Arc::new(Mutex::new(Metrics::new()));
+    let mut labels = HashMap::new();
+    labels.insert("peer".to_string(), "192.0.2.1".to_string());
+    let collector = MetricsCollector::new(metrics.clone(), labels).unwrap();
+    let registry = prometheus::Registry::new();
+    registry.register(Box::new(collector)).unwrap();
+
+    {
+        let mut metrics = metrics.lock().unwrap();
+        let update = update();
+        let mut data = vec![];
+        Message::Update(update.clone()).encode(&mut data).unwrap();
+        let header = Header::new(MessageType::Update, data.len() as u16);
+        metrics.record_message(&header, &Message::Update(update));
+        metrics.session_state = SessionState::Established;
+    }
+    let families = registry.gather();
+    let family = |name: &str| {
+        families
+            .iter()
+            .find(|family| family.get_name() == name)
+            .unwrap()
+    };
+    let messages = family("bgp_messages_total").get_metric();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].get_counter().get_value(), 1.0);
+    assert_eq!(messages[0].get_label()[0].get_name(), "peer");
+    assert_eq!(messages[0].get_label()[1].get_value(), "UPDATE");
+    // The announced IPv4 & IPv6 routes, and the withdrawn IPv4 routes
+    assert_eq!(family("bgp_routes_total").get_metric().len(), 3);
+    let state = family("bgp_session_state").get_metric();
+    assert_eq!(state[0].get_gauge().get_value(), 6.0);
+}
@@ -0,0 +1,304 @@
+#![cfg(feature = "mrt")]
+use bgp_rs::*;
+use libflate::gzip::Decoder;
+use mrt_rs::bgp4mp::BGP4MP;
+use mrt_rs::records::tabledump::{PeerEntry, RIBEntry, PEER_INDEX_TABLE, RIB_AFI, TABLE_DUMP_V2};
+use mrt_rs::Record;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+fn update() -> Update {
+    Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![4_200_000_000, 65001])],
+            }),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![NLRIEncoding::IP(("2001:db8::".parse().unwrap(), 32).into())],
+            ))),
+        ],
+        vec![],
+    )
+}
+
+#[test]
+fn test_write_bgp4mp_messages() {
+    let peering = MrtPeering {
+        peer_as: 4_200_000_000,
+        local_as: 65000,
+        interface: 0,
+        peer_address: "2001:db8::1".parse().unwrap(),
+        local_address: "2001:db8::2".parse().unwrap(),
+    };
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    let mut writer = MrtWriter::new(Vec::new());
+    writer
+        .write_message_as4(
+            1_577_836_800,
+            &peering,
+            &Message::Update(update()),
+            &capabilities,
+        )
+        .unwrap();
+    // MESSAGE only holds 2-byte ASNs
+    assert!(writer
+        .write_message(1, &peering, &Message::KeepAlive, &capabilities)
+        .is_err());
+
+    let mut stream = Cursor::new(writer.into_inner());
+    let (header, record) = mrt_rs::read(&mut stream).unwrap().unwrap();
+    assert_eq!(header.timestamp, 1_577_836_800);
+    let message = match record {
+        Record::BGP4MP(BGP4MP::MESSAGE_AS4(message)) => message,
+        record => panic!("Unexpected record: {:?}", record),
+    };
+    assert_eq!(message.peer_as, 4_200_000_000);
+    assert_eq!(message.local_as, 65000);
+    assert_eq!(message.peer_address, peering.peer_address);
+
+    let mut reader = Reader::with_capabilities(Cursor::new(message.message), capabilities);
+    match reader.read().unwrap().1 {
+        Message::Update(received) => {
+            assert_eq!(
+                format!("{:?}", received.attributes),
+                format!("{:?}", update().attributes)
+            );
+        }
+        message => panic!("Unexpected message: {:?}", message),
+    }
+    assert!(mrt_rs::read(&mut stream).unwrap().is_none());
+}
+
+#[test]
+fn test_write_table_dump_v2() {
+    let peers = vec![
+        MrtPeer {
+            bgp_id: "192.0.2.1".parse().unwrap(),
+            address: "192.0.2.1".parse().unwrap(),
+            asn: 65001,
+        },
+        MrtPeer {
+            bgp_id: "192.0.2.2".parse().unwrap(),
+            address: "2001:db8::2".parse().unwrap(),
+            asn: 4_200_000_000,
+        },
+    ];
+    let prefix: Prefix = ("2001:db8::".parse().unwrap(), 32).into();
+    let mut writer = MrtWriter::new(Vec::new());
+    writer
+        .write_peer_index_table(0, "10.0.0.1".parse().unwrap(), "rib", &peers)
+        .unwrap();
+    writer
+        .write_rib(
+            0,
+            0,
+            &prefix,
+            &[MrtRibEntry::from_update(1, 1_577_836_800, &update())],
+        )
+        .unwrap();
+
+    let mut stream = Cursor::new(writer.into_inner());
+    match mrt_rs::read(&mut stream).unwrap().unwrap().1 {
+        Record::TABLE_DUMP_V2(TABLE_DUMP_V2::PEER_INDEX_TABLE(table)) => {
+            assert_eq!(table.view_name, "rib");
+            assert_eq!(table.peer_entries.len(), 2);
+            assert_eq!(table.peer_entries[1].peer_as, 4_200_000_000);
+            assert_eq!(table.peer_entries[1].peer_ip_address, peers[1].address);
+        }
+        record => panic!("Unexpected record: {:?}", record),
+    }
+    let rib = match mrt_rs::read(&mut stream).unwrap().unwrap().1 {
+        Record::TABLE_DUMP_V2(TABLE_DUMP_V2::RIB_IPV6_UNICAST(rib)) => rib,
+        record => panic!("Unexpected record: {:?}", record),
+    };
+    assert_eq!(rib.prefix_length, 32);
+    assert_eq!(rib.prefix, vec![0x20, 0x01, 0x0d, 0xb8]);
+    assert_eq!(rib.entries.len(), 1);
+    assert_eq!(rib.entries[0].peer_index, 1);
+
+    // AS_PATH uses 4-byte ASNs, MP_REACH_NLRI only holds the next hop
+    let attributes = rib.entries[0].attributes.clone();
+    let mut cursor = Cursor::new(&attributes);
+    let capabilities = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    match PathAttribute::parse(&mut cursor, &capabilities).unwrap() {
+        PathAttribute::ORIGIN(Origin::IGP) => (),
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    match PathAttribute::parse(&mut cursor, &capabilities).unwrap() {
+        PathAttribute::AS_PATH(path) => assert_eq!(path.origin(), Some(65001)),
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    let mut next_hop = vec![0x80, 14, 17, 16];
+    next_hop.extend_from_slice(
+        &"2001:db8::1"
+            .parse::<std::net::Ipv6Addr>()
+            .unwrap()
+            .octets(),
+    );
+    assert_eq!(&attributes[cursor.position() as usize..], &next_hop[..]);
+}
+
+#[test]
+fn test_rib_decoder_roundtrip() {
+    let peers = vec![MrtPeer {
+        bgp_id: "192.0.2.1".parse().unwrap(),
+        address: "2001:db8::1".parse().unwrap(),
+        asn: 4_200_000_000,
+    }];
+    let prefix: Prefix = ("2001:db8::".parse().unwrap(), 32).into();
+    let mut writer = MrtWriter::new(Vec::new());
+    writer
+        .write_peer_index_table(0, "10.0.0.1".parse().unwrap(), "", &peers)
+        .unwrap();
+    writer
+        .write_rib(0, 0, &prefix, &[MrtRibEntry::from_update(0, 0, &update())])
+        .unwrap();
+
+    let mut stream = Cursor::new(writer.into_inner());
+    let mut decoder = RibDecoder::new();
+    let mut routes = vec![];
+    while let Some((_, record)) = mrt_rs::read(&mut stream).unwrap() {
+        if let Record::TABLE_DUMP_V2(record) = record {
+            routes.extend(decoder.decode(&record).unwrap());
+        }
+    }
+    assert_eq!(routes.len(), 1);
+    let (peer, route_prefix, attributes) = &routes[0];
+    assert_eq!(peer.asn, 4_200_000_000);
+    assert_eq!(route_prefix.to_string(), "2001:db8::/32");
+    match &attributes[1] {
+        PathAttribute::AS_PATH(path) => assert_eq!(path.origin(), Some(65001)),
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    match &attributes[2] {
+        PathAttribute::MP_REACH_NLRI(mp_reach) => {
+            assert_eq!(mp_reach.afi, AFI::IPV6);
+            assert_eq!(
+                mp_reach
+                    .next_hop_addresses(&Capabilities::default())
+                    .unwrap(),
+                vec!["2001:db8::1".parse::<std::net::IpAddr>().unwrap()]
+            );
+        }
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+}
+
+#[test]
+fn test_rib_decoder_two_byte_peer() {
+    // Peer Type 0: IPv4 address and 2-byte ASN
+    let table = PEER_INDEX_TABLE {
+        collector_id: 0,
+        view_name: String::new(),
+        peer_entries: vec![PeerEntry {
+            peer_type: 0,
+            peer_bgp_id: 1,
+            peer_ip_address: "192.0.2.1".parse().unwrap(),
+            peer_as: 65001,
+        }],
+    };
+    let rib = RIB_AFI {
+        sequence_number: 0,
+        prefix_length: 8,
+        prefix: vec![10],
+        entries: vec![RIBEntry {
+            peer_index: 0,
+            originated_time: 0,
+            // AS_PATH: AS_SEQUENCE of 65001 23456
+            attributes: vec![0x40, 2, 6, 2, 2, 0xfd, 0xe9, 0x5b, 0xa0],
+        }],
+    };
+    let mut decoder = RibDecoder::from_peer_index_table(&table);
+    let routes = decoder
+        .decode(&TABLE_DUMP_V2::RIB_IPV4_UNICAST(rib))
+        .unwrap();
+    match &routes[0].2[0] {
+        PathAttribute::AS_PATH(path) => assert_eq!(path.origin(), Some(23456)),
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+
+    let rib = RIB_AFI {
+        sequence_number: 1,
+        prefix_length: 8,
+        prefix: vec![10],
+        entries: vec![RIBEntry {
+            peer_index: 1,
+            originated_time: 0,
+            attributes: vec![],
+        }],
+    };
+    assert!(decoder
+        .decode(&TABLE_DUMP_V2::RIB_IPV4_UNICAST(rib))
+        .is_err());
+}
+
+#[test]
+fn test_rib_decoder_invalid_prefix() {
+    let table = PEER_INDEX_TABLE {
+        collector_id: 0,
+        view_name: String::new(),
+        peer_entries: vec![PeerEntry {
+            peer_type: 0,
+            peer_bgp_id: 1,
+            peer_ip_address: "192.0.2.1".parse().unwrap(),
+            peer_as: 65001,
+        }],
+    };
+    let mut decoder = RibDecoder::from_peer_index_table(&table);
+    let rib = |prefix_length: u8, prefix: Vec<u8>| RIB_AFI {
+        sequence_number: 0,
+        prefix_length,
+        prefix,
+        entries: vec![RIBEntry {
+            peer_index: 0,
+            originated_time: 0,
+            attributes: vec![],
+        }],
+    };
+
+    // Longer than an IPv4 address
+    assert!(decoder
+        .decode(&TABLE_DUMP_V2::RIB_IPV4_UNICAST(rib(
+            40,
+            vec![10, 0, 0, 0, 0]
+        )))
+        .is_err());
+    // More bytes than the length covers
+    assert!(decoder
+        .decode(&TABLE_DUMP_V2::RIB_IPV6_UNICAST(rib(8, vec![0x20, 1])))
+        .is_err());
+    let routes = decoder
+        .decode(&TABLE_DUMP_V2::RIB_IPV4_UNICAST(rib(32, vec![10, 0, 0, 1])))
+        .unwrap();
+    assert_eq!(routes[0].1.to_string(), "10.0.0.1/32");
+}
+
+#[test]
+fn test_rib_decoder_bview() {
+    let file = File::open("res/mrt/bview.20100101.0759.gz").unwrap();
+    let mut stream = Decoder::new(BufReader::new(file)).unwrap();
+    let mut decoder = RibDecoder::new();
+    let mut routes = 0;
+    for _ in 0..1000 {
+        match mrt_rs::read(&mut stream).unwrap() {
+            Some((_, Record::TABLE_DUMP_V2(record))) => {
+                routes += decoder.decode(&record).unwrap().len();
+            }
+            Some(_) => (),
+            None => break,
+        }
+    }
+    assert!(!decoder.peers().is_empty());
+    assert!(routes > 0);
+}
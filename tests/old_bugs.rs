@@ -21,3 +21,26 @@ fn unknown_attributes() {
     // Read the message.
     reader.read().unwrap();
 }
+
+#[test]
+fn fuzz_corpus() {
+    // Inputs that used to panic, in the format of fuzz/fuzz_targets/msg_target.rs:
+    // a byte of capabilities followed by the message
+    for entry in std::fs::read_dir("res/fuzz/msg_target").unwrap() {
+        let path = entry.unwrap().path();
+        let data = std::fs::read(&path).unwrap();
+        let capabilities = bgp_rs::Capabilities {
+            FOUR_OCTET_ASN_SUPPORT: (data[0] & 0b1) == 0b1,
+            EXTENDED_PATH_NLRI_SUPPORT: (data[0] & 0b10) == 0b10,
+            ..bgp_rs::Capabilities::default()
+        };
+        let mut reader = bgp_rs::Reader::with_capabilities(Cursor::new(&data[1..]), &capabilities);
+        // Messages that can be parsed must also be encoded (or fail to) without panicking
+        if let Ok((_header, message)) = reader.read() {
+            let _ = message.encode(&mut vec![]);
+            let _ = message.encode_with_capabilities(&mut vec![], &capabilities);
+        }
+        let mut reader = bgp_rs::Reader::with_capabilities(Cursor::new(&data[1..]), &capabilities);
+        let _ = reader.read_lossy();
+    }
+}
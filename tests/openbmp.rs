@@ -0,0 +1,128 @@
+use bgp_rs::*;
+use std::net::IpAddr;
+
+// Wraps a BMP message of the peer 192.0.2.1 AS65001 in an OpenBMP header of the router
+// 192.0.2.254
+fn kafka_message(bmp_type: u8, bgp_messages: &[Message]) -> Vec<u8> {
+    let mut body = vec![];
+    if bmp_type == 3 {
+        // Local address 192.0.2.254, local port 179 & remote port 50000
+        body.extend_from_slice(&[0; 12]);
+        body.extend_from_slice(&[192, 0, 2, 254, 0, 179, 0xc3, 0x50]);
+    }
+    for message in bgp_messages {
+        message.encode(&mut body).unwrap();
+    }
+
+    let mut bmp = vec![3];
+    bmp.extend_from_slice(&(48 + body.len() as u32).to_be_bytes());
+    bmp.push(bmp_type);
+    bmp.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]); // Peer type, flags & distinguisher
+    bmp.extend_from_slice(&[0; 12]);
+    bmp.extend_from_slice(&[192, 0, 2, 1]); // Peer address
+    bmp.extend_from_slice(&65001u32.to_be_bytes());
+    bmp.extend_from_slice(&[192, 0, 2, 1]); // Peer BGP ID
+    bmp.extend_from_slice(&[0x5f, 0x5e, 0x10, 0, 0, 0, 0, 7]); // Timestamp
+    bmp.extend_from_slice(&body);
+
+    let mut data = b"OBMP".to_vec();
+    data.extend_from_slice(&[1, 7, 0, 85]); // Version 1.7, header length
+    data.extend_from_slice(&(bmp.len() as u32).to_be_bytes());
+    data.extend_from_slice(&[0x80, 12]); // Router message, BMP_RAW
+    data.extend_from_slice(&[0x5f, 0x5e, 0x10, 1, 0, 0, 0, 8]); // Timestamp
+    data.extend_from_slice(&[1; 16]); // Collector hash
+    data.extend_from_slice(&[0, 3]);
+    data.extend_from_slice(b"rrc"); // Collector admin ID
+    data.extend_from_slice(&[2; 16]); // Router hash
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&[192, 0, 2, 254]); // Router address
+    data.extend_from_slice(&[0, 4]);
+    data.extend_from_slice(b"edge"); // Router group
+    data.extend_from_slice(&[0, 0, 0, 0]); // Row count
+    data.extend_from_slice(&bmp);
+    data
+}
+
+#[test]
+fn test_openbmp_route_monitoring() {
+    let update = Message::Update(Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65001])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    ));
+    let data = kafka_message(0, std::slice::from_ref(&update));
+    let message = OpenBmpMessage::parse(&data).unwrap();
+    assert_eq!(
+        message.header,
+        OpenBmpHeader {
+            version: (1, 7),
+            flags: 0x80,
+            message_type: 12,
+            timestamp: std::time::Duration::new(0x5f5e_1001, 8_000),
+            collector_hash: [1; 16],
+            collector_admin_id: "rrc".to_string(),
+            router_hash: [2; 16],
+            router_address: "192.0.2.254".parse().unwrap(),
+            router_group: "edge".to_string(),
+            row_count: 0,
+        }
+    );
+    assert_eq!(message.bmp_type().unwrap(), 0);
+
+    let (peer, bgp_messages) = message.bgp_messages().unwrap().unwrap();
+    assert_eq!(peer.address, "192.0.2.1".parse::<IpAddr>().unwrap());
+    assert_eq!(peer.asn, 65001);
+    assert_eq!(peer.timestamp, std::time::Duration::new(0x5f5e_1000, 7_000));
+    assert_eq!(bgp_messages.len(), 1);
+    let mut peers = PeerMap::new(Capabilities::default());
+    assert_eq!(
+        peers
+            .parse(peer.address, peer.asn, bgp_messages[0])
+            .unwrap(),
+        update
+    );
+
+    // Truncated messages
+    assert!(OpenBmpMessage::parse(&data[..data.len() - 1]).is_err());
+    assert!(OpenBmpMessage::parse(&data[..40]).is_err());
+    assert!(OpenBmpMessage::parse(&data[4..]).is_err());
+}
+
+#[test]
+fn test_openbmp_peer_up() {
+    let config = PeerConfig::new(65001, "192.0.2.1".parse().unwrap());
+    let received = Message::Open(Open::from_config(&config));
+    let sent = Message::Open(Open::from_config(&PeerConfig::new(
+        65000,
+        "192.0.2.254".parse().unwrap(),
+    )));
+    let data = kafka_message(3, &[sent, received]);
+    let message = OpenBmpMessage::parse(&data).unwrap();
+    let (peer, bgp_messages) = message.bgp_messages().unwrap().unwrap();
+    assert_eq!(bgp_messages.len(), 2);
+
+    // The OPEN messages establish the capabilities of the peer
+    let mut peers = PeerMap::new(Capabilities::default());
+    for bgp_message in &bgp_messages {
+        peers.parse(peer.address, peer.asn, bgp_message).unwrap();
+    }
+    assert!(peers.get(peer.address, peer.asn).FOUR_OCTET_ASN_SUPPORT);
+    match peers
+        .parse(peer.address, peer.asn, bgp_messages[1])
+        .unwrap()
+    {
+        Message::Open(open) => assert_eq!(open.asn(), 65001),
+        message => panic!("Unexpected {:?}", message),
+    }
+
+    // Other BMP messages (e.g. Initiation) wrap no BGP messages
+    let data = kafka_message(4, &[]);
+    let message = OpenBmpMessage::parse(&data).unwrap();
+    assert_eq!(message.bgp_messages().unwrap(), None);
+}
@@ -0,0 +1,82 @@
+use bgp_rs::*;
+use std::time::Duration;
+
+fn route(prefix: &str) -> NLRIEncoding {
+    NLRIEncoding::IP(prefix.parse().unwrap())
+}
+
+fn update(withdrawn: &[&str], next_hop: &str, announced: &[&str]) -> Update {
+    Update::new(
+        withdrawn.iter().map(|prefix| route(prefix)).collect(),
+        vec![PathAttribute::NEXT_HOP(next_hop.parse().unwrap())],
+        announced.iter().map(|prefix| route(prefix)).collect(),
+    )
+}
+
+fn strings(updates: &[Update]) -> Vec<String> {
+    updates.iter().map(|update| update.to_string()).collect()
+}
+
+#[test]
+fn test_pacer_coalesce() {
+    let mut pacer = Pacer::new(Duration::from_secs(3600));
+    pacer.queue(1, &update(&[], "192.0.2.1", &["10.0.0.0/8", "10.1.0.0/16"]));
+    pacer.queue(2, &update(&[], "192.0.2.1", &["10.0.0.0/8"]));
+    assert!(pacer.next_release().is_some());
+    let mut batches = pacer.poll();
+    batches.sort_by_key(|(peer, _)| *peer);
+    assert_eq!(batches.len(), 2);
+    assert_eq!(
+        strings(&batches[0].1),
+        vec!["10.0.0.0/8 10.1.0.0/16 via 192.0.2.1"]
+    );
+    assert!(pacer.next_release().is_none());
+
+    // Queued until the interval elapsed
+    pacer.queue(1, &update(&[], "192.0.2.2", &["10.0.0.0/8", "10.2.0.0/16"]));
+    pacer.queue(
+        1,
+        &update(&["10.1.0.0/16", "10.2.0.0/16"], "192.0.2.3", &[]),
+    );
+    pacer.queue(
+        1,
+        &update(&[], "192.0.2.3", &["10.1.0.0/16", "10.3.0.0/16"]),
+    );
+    pacer.queue(1, &Update::end_of_rib(AFI::IPV4, SAFI::Unicast));
+    pacer.queue(1, &update(&["10.3.0.0/16"], "192.0.2.3", &[]));
+    assert_eq!(pacer.pending(&1), 5);
+    assert!(pacer.poll().is_empty());
+    assert!(pacer.release(&1).is_empty());
+    assert!(pacer.next_release().unwrap() > std::time::Instant::now());
+
+    assert_eq!(
+        strings(&pacer.flush(&1)),
+        vec![
+            "withdrawn 10.2.0.0/16 10.3.0.0/16",
+            "10.0.0.0/8 via 192.0.2.2",
+            "10.1.0.0/16 via 192.0.2.3",
+            "End-of-RIB IPv4 Unicast",
+        ]
+    );
+    assert_eq!(pacer.pending(&1), 0);
+    assert!(pacer.flush(&1).is_empty());
+    assert!(pacer.flush(&3).is_empty());
+}
+
+#[test]
+fn test_pacer_interval() {
+    let mut pacer = Pacer::new(Duration::from_secs(0));
+    pacer.queue("peer", &update(&[], "192.0.2.1", &["10.0.0.0/8"]));
+    assert_eq!(pacer.release(&"peer").len(), 1);
+    pacer.queue("peer", &update(&["10.0.0.0/8"], "192.0.2.1", &[]));
+    assert_eq!(
+        strings(&pacer.release(&"peer")),
+        vec!["withdrawn 10.0.0.0/8"]
+    );
+    assert!(pacer.release(&"peer").is_empty());
+
+    pacer.queue("peer", &update(&[], "192.0.2.1", &["10.0.0.0/8"]));
+    pacer.remove_peer(&"peer");
+    assert_eq!(pacer.pending(&"peer"), 0);
+    assert!(pacer.poll().is_empty());
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "parallel")]
+use bgp_rs::*;
+
+#[test]
+fn test_parse_parallel() {
+    let update = Message::Update(Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65000, 4200000000])],
+            }),
+        ],
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    ));
+    let two_byte = Capabilities::default();
+    let four_byte = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+
+    let mut messages = vec![];
+    for i in 0..100 {
+        let capabilities = if i % 2 == 0 { &two_byte } else { &four_byte };
+        let mut data = vec![];
+        if i % 10 == 0 {
+            Message::KeepAlive.encode(&mut data).unwrap();
+        } else {
+            update
+                .encode_with_capabilities(&mut data, capabilities)
+                .unwrap();
+        }
+        if i == 99 {
+            data.push(0);
+        }
+        messages.push((data, capabilities));
+    }
+
+    let results = parse_parallel(&messages);
+    assert_eq!(results.len(), 100);
+    for (i, result) in results.iter().enumerate().take(99) {
+        let (_header, message) = result.as_ref().unwrap();
+        match message {
+            Message::KeepAlive => assert_eq!(i % 10, 0),
+            Message::Update(parsed) => {
+                // Each message is parsed with the capabilities of its peer
+                let as_path = parsed.effective_as_path().unwrap();
+                assert_eq!(as_path.sequence(), Some(vec![65000, 4200000000]));
+            }
+            message => panic!("Unexpected message {:?}", message),
+        }
+    }
+    assert!(results[99].is_err());
+}
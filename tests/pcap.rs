@@ -1,9 +1,11 @@
+use bgp_rs::{Capabilities, Identifier, Message, NegotiatedCapabilities, ParseOptions, Reader};
 use etherparse::PacketHeaders;
 
 mod common;
 use common::parse::{
-    parse_pcap_message_bytes, parse_u16, parse_u32, parse_u32_with_path_id, test_message_roundtrip,
-    test_pcap_roundtrip,
+    parse_pcap_message_bytes, parse_pcap_messages, parse_u16, parse_u32, parse_u32_with_path_id,
+    test_message_roundtrip, test_message_roundtrip_with_capabilities,
+    test_message_roundtrip_with_options, test_pcap_roundtrip,
 };
 
 #[test]
@@ -64,6 +66,112 @@ fn pcap_roundtrip1() {
     test_pcap_roundtrip("res/pcap/bgp_withdraw.cap").unwrap();
 }
 
+#[test]
+fn pcap_roundtrip_capabilities() {
+    let messages = parse_pcap_messages("res/pcap/bgp-add-path.cap").unwrap();
+    let capabilities = match &messages[0] {
+        bgp_rs::Message::Open(open) => Capabilities::from_parameters(open.parameters.clone()),
+        _ => panic!("First message should be an OPEN"),
+    };
+    assert!(capabilities.FOUR_OCTET_ASN_SUPPORT);
+
+    // Message 6 uses 4-byte AS_PATH even when AS < 65535, following the capabilities
+    parse_pcap_message_bytes("res/pcap/bgp-add-path.cap")
+        .unwrap()
+        .into_iter()
+        .try_for_each(|message_bytes| {
+            test_message_roundtrip_with_capabilities(&message_bytes, &capabilities)
+        })
+        .unwrap();
+}
+
+#[test]
+fn pcap_read_negotiated() {
+    let mut stream = vec![];
+    for message in parse_pcap_message_bytes("res/pcap/bgp-add-path.cap").unwrap() {
+        stream.extend(message);
+    }
+    let mut reader = Reader::with_capabilities(
+        std::io::Cursor::new(stream),
+        NegotiatedCapabilities::default(),
+    );
+    let mut updates = 0;
+    loop {
+        match reader.read_negotiated() {
+            Ok((_header, Message::Update(_))) => updates += 1,
+            Ok(_) => (),
+            Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => panic!("{}", e),
+        }
+    }
+    assert!(updates > 0);
+    assert!(reader.capabilities.capabilities.FOUR_OCTET_ASN_SUPPORT);
+    assert!(reader.capabilities.capabilities.EXTENDED_PATH_NLRI_SUPPORT);
+}
+
+#[test]
+fn pcap_roundtrip_preserve_wire_format() {
+    let options = ParseOptions {
+        preserve_wire_format: true,
+        ..ParseOptions::default()
+    };
+    // These files don't roundtrip without the original attribute encoding
+    for filename in &[
+        "res/pcap/4-byte_AS_numbers_Mixed_Scenario.cap",
+        "res/pcap/BGP_redist.cap",
+        "res/pcap/bgp-add-path.cap",
+    ] {
+        parse_pcap_message_bytes(filename)
+            .unwrap()
+            .into_iter()
+            .try_for_each(|message_bytes| {
+                test_message_roundtrip_with_options(
+                    &message_bytes,
+                    &Capabilities::default(),
+                    options.clone(),
+                )
+            })
+            .unwrap();
+    }
+}
+
+#[test]
+fn pcap_preserve_wire_format_modified() {
+    let options = ParseOptions {
+        preserve_wire_format: true,
+        ..ParseOptions::default()
+    };
+    let message_bytes = parse_pcap_message_bytes("res/pcap/BGP_redist.cap")
+        .unwrap()
+        .into_iter()
+        .find(|message_bytes| message_bytes[18] == 2)
+        .unwrap();
+    let mut reader = Reader::with_options(std::io::Cursor::new(&message_bytes), options);
+    let mut update = match reader.read().unwrap().1 {
+        Message::Update(update) => update,
+        message => panic!("Unexpected {:?}", message),
+    };
+    assert!(update.raw_attributes().is_some());
+
+    // Modifying the attributes directly is not ignored when encoding
+    update
+        .attributes
+        .retain(|attribute| attribute.id() != Identifier::LOCAL_PREF);
+    assert!(update.raw_attributes().is_none());
+    let mut encoded = vec![];
+    Message::Update(update.clone())
+        .encode(&mut encoded)
+        .unwrap();
+    assert_ne!(encoded, message_bytes);
+    match Reader::new(std::io::Cursor::new(encoded)).read().unwrap().1 {
+        Message::Update(parsed) => {
+            assert!(parsed.get(Identifier::LOCAL_PREF).is_none());
+            assert_eq!(parsed.attributes.len(), update.attributes.len());
+        }
+        message => panic!("Unexpected {:?}", message),
+    }
+}
+
 #[cfg(feature = "flowspec")]
 #[test]
 fn pcap_roundtrip_flowspec() {
@@ -76,6 +184,25 @@ fn pcap_roundtrip_flowspec() {
         .unwrap();
 }
 
+#[test]
+fn pcap_effective_as_path() {
+    let paths: Vec<_> = parse_pcap_messages("res/pcap/4-byte_AS_numbers_Mixed_Scenario.cap")
+        .unwrap()
+        .into_iter()
+        .filter_map(|message| match message {
+            bgp_rs::Message::Update(update) => update.effective_as_path(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(paths.len(), 2);
+    // AS_PATH [23456, 23456] + AS4_PATH [655361, 2621441]
+    assert_eq!(paths[0].sequence(), Some(vec![655361, 2621441]));
+    assert_eq!(paths[0].origin(), Some(2621441));
+    // AS_PATH [2, 23456] + AS4_PATH [2621441]
+    assert_eq!(paths[1].sequence(), Some(vec![2, 2621441]));
+    assert_eq!(paths[1].origin(), Some(2621441));
+}
+
 fn parse_pcap(filename: &str) {
     use pcap_file::PcapReader;
     use std::fs::File;
@@ -109,3 +236,40 @@ fn parse_pcap(filename: &str) {
         }
     }
 }
+
+#[cfg(feature = "pcap")]
+#[test]
+fn test_read_bgp_messages_from_pcap() {
+    for entry in std::fs::read_dir("res/pcap").unwrap() {
+        let path = entry.unwrap().path();
+        let filename = path.to_str().unwrap();
+        let messages = match bgp_rs::read_bgp_messages_from_pcap(filename) {
+            Ok(messages) => messages,
+            Err(error) => panic!("{}: {}", filename, error),
+        };
+        println!("{}: {} messages", filename, messages.len());
+        for message in &messages {
+            assert_eq!(&message.data[..16], &[0xff; 16][..]);
+            let length = usize::from(message.data[16]) << 8 | usize::from(message.data[17]);
+            assert_eq!(message.data.len(), length);
+        }
+    }
+
+    // Matches the messages found by scanning the packets for markers
+    let messages = bgp_rs::read_bgp_messages_from_pcap("res/pcap/BGP_MP_NLRI.cap").unwrap();
+    let message_bytes = parse_pcap_message_bytes("res/pcap/BGP_MP_NLRI.cap").unwrap();
+    assert_eq!(messages.len(), message_bytes.len());
+    for (message, bytes) in messages.iter().zip(message_bytes.iter()) {
+        assert_eq!(&message.data, bytes);
+    }
+    assert_eq!(messages[0].source, "[2001:db8::1]:42037".parse().unwrap());
+    assert_eq!(
+        messages[0].destination,
+        "[2001:db8::2]:179".parse().unwrap()
+    );
+    assert_eq!(messages[0].timestamp.as_secs(), 1_277_757_397);
+    match messages[0].parse(&Capabilities::default()).unwrap() {
+        Message::Open(open) => assert_eq!(open.peer_asn, 65001),
+        message => panic!("Unexpected message: {:?}", message),
+    }
+}
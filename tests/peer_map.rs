@@ -0,0 +1,68 @@
+use bgp_rs::*;
+use std::net::IpAddr;
+
+fn open(asn: u32) -> Vec<u8> {
+    let config = PeerConfig::new(asn, "192.0.2.1".parse().unwrap());
+    let mut data = vec![];
+    Message::Open(Open::from_config(&config))
+        .encode(&mut data)
+        .unwrap();
+    data
+}
+
+#[test]
+fn test_peer_map() {
+    let options = ParseOptions {
+        preserve_wire_format: true,
+        ..ParseOptions::default()
+    };
+    let mut peers = PeerMap::with_options(Capabilities::default(), options);
+    let four_byte: IpAddr = "192.0.2.1".parse().unwrap();
+    let two_byte: IpAddr = "192.0.2.2".parse().unwrap();
+
+    // Only the session of the first peer advertised 4-byte ASN support in both directions
+    peers.parse(four_byte, 65001, &open(65001)).unwrap();
+    peers.parse(four_byte, 65001, &open(65000)).unwrap();
+    peers.insert(two_byte, 65002, Capabilities::default());
+    assert_eq!(peers.len(), 2);
+    assert!(peers.get(four_byte, 65001).FOUR_OCTET_ASN_SUPPORT);
+    assert_eq!(peers.get_mut(four_byte, 65001).hold_time, Some(90));
+
+    let update = Message::Update(Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![65001, 65536])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    ));
+    let four_byte_data = encode_message(&update, Some(peers.get(four_byte, 65001))).unwrap();
+    let two_byte_data = encode_message(&update, Some(peers.get(two_byte, 65002))).unwrap();
+    assert_ne!(four_byte_data, two_byte_data);
+
+    let as_path = |message: Message| match message {
+        Message::Update(update) => update.effective_as_path().unwrap().to_string(),
+        message => panic!("Unexpected {:?}", message),
+    };
+    let parsed = peers.parse(four_byte, 65001, &four_byte_data).unwrap();
+    // The options of the map apply to every peer
+    match &parsed {
+        Message::Update(update) => assert_eq!(update.raw_attributes().unwrap().len(), 3),
+        message => panic!("Unexpected {:?}", message),
+    }
+    assert_eq!(as_path(parsed), "65001 65536");
+    let parsed = peers.parse(two_byte, 65002, &two_byte_data).unwrap();
+    assert_eq!(as_path(parsed), "65001 65536");
+
+    // Unknown peers are added with the initial capabilities
+    let other: IpAddr = "192.0.2.3".parse().unwrap();
+    assert!(peers.parse(other, 65003, &two_byte_data).is_ok());
+    assert_eq!(peers.len(), 3);
+    assert!(peers.remove(other, 65003).is_some());
+    let mut known: Vec<(IpAddr, u32)> = peers.peers().collect();
+    known.sort();
+    assert_eq!(known, vec![(four_byte, 65001), (two_byte, 65002)]);
+}
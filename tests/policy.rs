@@ -0,0 +1,184 @@
+#![cfg(feature = "policy")]
+use bgp_rs::policy::{Action, Community, Condition, Policy, PrefixListEntry, Term};
+use bgp_rs::*;
+
+fn route(prefix: &str) -> NLRIEncoding {
+    NLRIEncoding::IP(prefix.parse().unwrap())
+}
+
+fn key(prefix: &str) -> RouteKey {
+    RouteKey::new(AFI::IPV4, SAFI::Unicast, &route(prefix)).unwrap()
+}
+
+fn as_path(asns: Vec<u32>) -> PathAttribute {
+    PathAttribute::AS_PATH(ASPath {
+        segments: vec![Segment::AS_SEQUENCE(asns)],
+    })
+}
+
+#[test]
+fn test_prefix_list_entry() {
+    let entry: PrefixListEntry = "10.0.0.0/8 le 16".parse().unwrap();
+    assert!(entry.matches(&"10.0.0.0/8".parse().unwrap()));
+    assert!(entry.matches(&"10.128.0.0/9".parse().unwrap()));
+    assert!(!entry.matches(&"10.1.1.0/24".parse().unwrap()));
+    assert!(!entry.matches(&"2001:db8::/32".parse().unwrap()));
+
+    let entry: PrefixListEntry = "2001:db8::/32 ge 48".parse().unwrap();
+    assert!(entry.matches(&"2001:db8:1::/48".parse().unwrap()));
+    assert!(entry.matches(&"2001:db8::1/128".parse().unwrap()));
+    assert!(!entry.matches(&"2001:db8::/32".parse().unwrap()));
+
+    assert!("10.0.0.0/8 le".parse::<PrefixListEntry>().is_err());
+    assert!("10.0.0.0/8 le 16 le 24".parse::<PrefixListEntry>().is_err());
+    assert!("10.0.0.0/8 eq 16".parse::<PrefixListEntry>().is_err());
+    assert!("10.0.0.0 le 16".parse::<PrefixListEntry>().is_err());
+}
+
+#[test]
+fn test_policy_conditions() {
+    let attributes = vec![
+        PathAttribute::ORIGIN(Origin::IGP),
+        as_path(vec![65001, 65002]),
+        PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        PathAttribute::COMMUNITY(vec![0xfde9_0001]),
+        PathAttribute::LARGE_COMMUNITY(vec![(65001, 1, 2)]),
+    ];
+    let matches = |condition: Condition| {
+        let policy = Policy {
+            terms: vec![Term {
+                conditions: vec![condition],
+                actions: vec![Action::Accept],
+            }],
+            accept_by_default: false,
+        };
+        policy.apply_route(&key("10.0.0.0/8"), &mut attributes.clone())
+    };
+
+    assert!(matches(Condition::Origin(Origin::IGP)));
+    assert!(!matches(Condition::Origin(Origin::EGP)));
+    assert!(matches(Condition::AsPath("^65001 ".parse().unwrap())));
+    assert!(matches(Condition::AsPath(" 65002$".parse().unwrap())));
+    assert!(!matches(Condition::AsPath("^65002".parse().unwrap())));
+    assert!(matches(Condition::NextHop("192.0.2.1".parse().unwrap())));
+    assert!(!matches(Condition::NextHop("192.0.2.2".parse().unwrap())));
+    assert!(matches(Condition::Community(Community::Standard(
+        0xfde9_0001
+    ))));
+    assert!(matches(Condition::Community(Community::Large((
+        65001, 1, 2
+    )))));
+    assert!(!matches(Condition::Community(Community::Extended(1))));
+    assert!(matches(Condition::Not(Box::new(Condition::Community(
+        Community::Extended(1)
+    )))));
+    assert!(matches(Condition::CommunityFilter(
+        "65001:*".parse().unwrap()
+    )));
+    assert!(!matches(Condition::CommunityFilter("*:2".parse().unwrap())));
+    assert!(matches(Condition::CommunityFilter(
+        "*:*:2".parse().unwrap()
+    )));
+    assert!(matches(Condition::Prefix(vec![
+        "192.168.0.0/16 le 24".parse().unwrap(),
+        "10.0.0.0/8".parse().unwrap(),
+    ])));
+    assert!(!matches(Condition::Prefix(vec!["10.0.0.0/8 ge 9"
+        .parse()
+        .unwrap()])));
+}
+
+#[test]
+fn test_policy_actions() {
+    let policy = Policy {
+        terms: vec![
+            Term {
+                conditions: vec![],
+                actions: vec![
+                    Action::Prepend {
+                        asn: 65000,
+                        count: 2,
+                    },
+                    Action::Set(PathAttribute::LOCAL_PREF(50)),
+                    Action::Strip(Identifier::MULTI_EXIT_DISC),
+                    Action::SetNextHop("198.51.100.1".parse().unwrap()),
+                    Action::AddCommunity(Community::Standard(1)),
+                    Action::AddCommunity(Community::Standard(2)),
+                    Action::RemoveCommunity(Community::Standard(0xfde9_0001)),
+                    Action::RemoveCommunity(Community::Large((65001, 1, 2))),
+                ],
+            },
+            // Terms are evaluated until a route is accepted or rejected
+            Term {
+                conditions: vec![Condition::Community(Community::Standard(2))],
+                actions: vec![Action::Set(PathAttribute::LOCAL_PREF(100))],
+            },
+        ],
+        accept_by_default: true,
+    };
+    let mut attributes = vec![
+        as_path(vec![65001]),
+        PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        PathAttribute::MULTI_EXIT_DISC(10),
+        PathAttribute::COMMUNITY(vec![0xfde9_0001]),
+        PathAttribute::LARGE_COMMUNITY(vec![(65001, 1, 2)]),
+    ];
+    assert!(policy.apply_route(&key("10.0.0.0/8"), &mut attributes));
+    let update = Update::new(vec![], attributes, vec![]);
+    assert_eq!(
+        update.to_string(),
+        "AS path 65000 65000 65001, next hop 198.51.100.1, communities 0:1 0:2, local pref 100"
+    );
+}
+
+#[test]
+fn test_policy_apply_update() {
+    let policy = Policy {
+        terms: vec![
+            Term {
+                conditions: vec![Condition::Prefix(vec!["2001:db8::/32 le 48"
+                    .parse()
+                    .unwrap()])],
+                actions: vec![Action::Accept],
+            },
+            Term {
+                conditions: vec![Condition::Prefix(vec!["10.0.0.0/8 le 24".parse().unwrap()])],
+                actions: vec![Action::AddCommunity(Community::Standard(1)), Action::Accept],
+            },
+        ],
+        accept_by_default: false,
+    };
+    let update = Update::new(
+        vec![route("10.9.0.0/16")],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Unicast,
+                "2001:db8::1".parse().unwrap(),
+                vec![route("2001:db8:1::/48"), route("2001:db8:1::/64")],
+            ))),
+        ],
+        vec![route("10.1.0.0/16"), route("10.1.1.1/32")],
+    );
+    let updates = policy.apply_update(&update);
+    let updates: Vec<String> = updates.iter().map(|update| update.to_string()).collect();
+    assert_eq!(
+        updates,
+        vec![
+            "withdrawn 10.9.0.0/16 10.1.1.1/32",
+            "withdrawn 2001:db8:1::/64",
+            "10.1.0.0/16 via 192.0.2.1, origin IGP, communities 0:1",
+            "2001:db8:1::/48 via 2001:db8::1, origin IGP",
+        ]
+    );
+
+    // The filtered UPDATE messages keep a RIB consistent
+    let mut rib = Rib::new();
+    rib.apply(&update, 1);
+    for update in policy.apply_update(&update) {
+        rib.apply(&update, 1);
+    }
+    assert_eq!(rib.len(), 2);
+}
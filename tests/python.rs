@@ -0,0 +1,86 @@
+#![cfg(feature = "python")]
+use bgp_rs::*;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::wrap_pymodule;
+
+// Runs the Python code with the module imported as `bgp_rs` and the message as `data`
+fn run_python(message: &Message, code: &str) {
+    let data = encode_message(message, None).unwrap();
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let globals = PyDict::new_bound(py);
+        globals
+            .set_item("bgp_rs", wrap_pymodule!(bgp_rs::python::python_module)(py))
+            .unwrap();
+        globals
+            .set_item("data", pyo3::types::PyBytes::new_bound(py, &data))
+            .unwrap();
+        py.run_bound(code, Some(&globals), None)
+            .map_err(|e| e.print(py))
+            .unwrap();
+    });
+}
+
+#[test]
+fn test_python_update() {
+    let update = Message::Update(Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![
+                    Segment::AS_SEQUENCE(vec![64496, 64497]),
+                    Segment::AS_SET(vec![64498]),
+                ],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+            PathAttribute::COMMUNITY(vec![0xfbf0_0001]),
+        ],
+        vec![NLRIEncoding::IP("198.51.100.0/24".parse().unwrap())],
+    ));
+    run_python(
+        &update,
+        r#"
+capabilities = bgp_rs.Capabilities(add_path=False)
+assert not capabilities.four_octet_asn
+message = bgp_rs.parse_message(data, capabilities)
+assert message["type"] == "UPDATE", message
+assert message["path"] == [64496, 64497, [64498]], message
+assert message["announcements"] == [{"next_hop": "192.0.2.1", "prefixes": ["198.51.100.0/24"]}]
+
+update = bgp_rs.Update.from_bytes(data, capabilities)
+assert update.announced == ["198.51.100.0/24"]
+assert update.as_path == [64496, 64497, [64498]]
+assert update.communities == [(64496, 1)]
+assert update.next_hop == "192.0.2.1"
+assert update.to_dict() == message
+
+try:
+    bgp_rs.Open.from_bytes(data)
+    assert False
+except ValueError as e:
+    assert str(e) == "Unexpected UPDATE message", e
+"#,
+    );
+}
+
+#[test]
+fn test_python_notification() {
+    let notification = Message::Notification(Notification::from_data(6, 2, vec![1]));
+    run_python(
+        &notification,
+        r#"
+notification = bgp_rs.Notification.from_bytes(data)
+assert (notification.code, notification.subcode, notification.data) == (6, 2, b"\x01")
+assert bgp_rs.parse_message(data)["type"] == "NOTIFICATION"
+
+try:
+    bgp_rs.parse_message(data[:18])
+    assert False
+except ValueError as e:
+    assert str(e) == "failed to fill whole buffer", e
+"#,
+    );
+}
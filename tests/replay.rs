@@ -0,0 +1,96 @@
+#![cfg(feature = "mrt")]
+use bgp_rs::*;
+use libflate::gzip::Decoder;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::time::{Duration, Instant};
+
+fn archive() -> Vec<u8> {
+    let peering = |peer_as: u32, peer_address: &str| MrtPeering {
+        peer_as,
+        local_as: 65000,
+        interface: 0,
+        peer_address: peer_address.parse().unwrap(),
+        local_address: "192.0.2.254".parse().unwrap(),
+    };
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![4_200_000_000])],
+            }),
+            PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap()),
+        ],
+        vec![NLRIEncoding::IP("10.0.0.0/8".parse().unwrap())],
+    );
+    let four_octet = Capabilities {
+        FOUR_OCTET_ASN_SUPPORT: true,
+        ..Capabilities::default()
+    };
+    let mut writer = MrtWriter::new(Vec::new());
+    writer
+        .write_message_as4(
+            100,
+            &peering(4_200_000_000, "192.0.2.1"),
+            &Message::Update(update),
+            &four_octet,
+        )
+        .unwrap();
+    // Records other than BGP4MP messages are skipped
+    writer
+        .write_peer_index_table(100, "192.0.2.254".parse().unwrap(), "", &[])
+        .unwrap();
+    writer
+        .write_message(
+            101,
+            &peering(65002, "192.0.2.2"),
+            &Message::KeepAlive,
+            &Capabilities::default(),
+        )
+        .unwrap();
+    writer.into_inner()
+}
+
+#[test]
+fn test_mrt_replay() {
+    let mut replay = MrtReplay::new(Cursor::new(archive()));
+    // The second message is due 1 second after the first one, 50ms at 20 times the speed
+    replay.speed = Some(20.0);
+    let started = Instant::now();
+    let messages: Vec<TimedMessage> = replay.map(|message| message.unwrap()).collect();
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(messages.len(), 2);
+
+    assert_eq!(messages[0].timestamp, Duration::from_secs(100));
+    assert_eq!(
+        messages[0].peer,
+        ("192.0.2.1".parse().unwrap(), 4_200_000_000)
+    );
+    match &messages[0].message {
+        Message::Update(update) => assert_eq!(
+            update.effective_as_path().unwrap().origin(),
+            Some(4_200_000_000)
+        ),
+        message => panic!("Unexpected message: {:?}", message),
+    }
+    assert_eq!(messages[1].timestamp, Duration::from_secs(101));
+    assert_eq!(messages[1].peer, ("192.0.2.2".parse().unwrap(), 65002));
+    assert_eq!(messages[1].message, Message::KeepAlive);
+}
+
+#[test]
+fn test_mrt_replay_archive() {
+    let file = File::open("res/mrt/updates.20190101.0000.gz").unwrap();
+    let mut replay = MrtReplay::new(BufReader::new(Decoder::new(file).unwrap()));
+    // The archive spans 15 minutes, `read` does not wait
+    let mut count = 0;
+    let mut last = Duration::from_secs(0);
+    while let Some(message) = replay.read().unwrap() {
+        assert!(message.timestamp >= last);
+        last = message.timestamp;
+        count += 1;
+    }
+    assert_eq!(count, 54471);
+    assert!(replay.peers().len() > 1);
+}
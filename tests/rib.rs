@@ -0,0 +1,295 @@
+use bgp_rs::*;
+use std::net::IpAddr;
+
+fn prefix(address: &str, length: u8) -> Prefix {
+    (address.parse().unwrap(), length).into()
+}
+
+#[test]
+fn test_rib_announce_withdraw() {
+    let peer_a: IpAddr = "192.0.2.1".parse().unwrap();
+    let peer_b: IpAddr = "192.0.2.2".parse().unwrap();
+    let route = NLRIEncoding::IP(prefix("10.0.0.0", 8));
+    let key = RouteKey::new(AFI::IPV4, SAFI::Unicast, &route).unwrap();
+
+    let mut rib = Rib::new();
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP(peer_a)],
+            vec![route.clone(), NLRIEncoding::IP(prefix("10.1.0.0", 16))],
+        ),
+        peer_a,
+    );
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP(peer_b)],
+            vec![route.clone()],
+        ),
+        peer_b,
+    );
+    assert_eq!(rib.len(), 3);
+    assert_eq!(rib.lookup(&key).count(), 2);
+    assert_eq!(rib.routes(&peer_a).count(), 2);
+
+    // A newer announcement replaces the attributes of the route
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.3".parse().unwrap())],
+            vec![route.clone()],
+        ),
+        peer_a,
+    );
+    match rib.get(&peer_a, &key) {
+        Some([PathAttribute::NEXT_HOP(next_hop)]) => {
+            assert_eq!(next_hop.to_string(), "192.0.2.3")
+        }
+        attributes => panic!("Unexpected attributes {:?}", attributes),
+    }
+
+    rib.apply(&Update::new(vec![route.clone()], vec![], vec![]), peer_b);
+    assert!(rib.get(&peer_b, &key).is_none());
+    assert_eq!(rib.lookup(&key).count(), 1);
+    assert_eq!(rib.len(), 2);
+
+    rib.remove_peer(&peer_a);
+    assert!(rib.is_empty());
+    assert_eq!(rib.peers().count(), 1);
+}
+
+#[test]
+fn test_rib_add_path() {
+    let mut rib = Rib::new();
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+            vec![
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.0.0.0", 8), 1)),
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.0.0.0", 8), 2)),
+            ],
+        ),
+        1,
+    );
+    assert_eq!(rib.len(), 2);
+
+    rib.apply(
+        &Update::new(
+            vec![NLRIEncoding::IP_WITH_PATH_ID((prefix("10.0.0.0", 8), 1))],
+            vec![],
+            vec![],
+        ),
+        1,
+    );
+    let keys: Vec<_> = rib.routes(&1).map(|(key, _)| key.path_id).collect();
+    assert_eq!(keys, vec![2]);
+}
+
+#[test]
+fn test_rib_mp_routes() {
+    let route = NLRIEncoding::IP(prefix("2001:db8::", 32));
+    let key = RouteKey::new(AFI::IPV6, SAFI::Unicast, &route).unwrap();
+    let mut rib = Rib::new();
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![
+                PathAttribute::ORIGIN(Origin::IGP),
+                PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                    AFI::IPV6,
+                    SAFI::Unicast,
+                    "2001:db8::1".parse().unwrap(),
+                    vec![route.clone()],
+                ))),
+            ],
+            vec![],
+        ),
+        "peer",
+    );
+    let attributes = rib.get(&"peer", &key).unwrap();
+    assert_eq!(attributes.len(), 2);
+    match &attributes[1] {
+        PathAttribute::MP_REACH_NLRI(mp_reach) => {
+            assert_eq!(mp_reach.next_hop.len(), 16);
+            assert!(mp_reach.announced_routes.is_empty());
+        }
+        attribute => panic!("Unexpected attribute {:?}", attribute),
+    }
+
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::MP_UNREACH_NLRI(Box::new(MPUnreachNLRI {
+                afi: AFI::IPV6,
+                safi: SAFI::Unicast,
+                withdrawn_routes: vec![route],
+            }))],
+            vec![],
+        ),
+        "peer",
+    );
+    assert!(rib.is_empty());
+}
+
+#[test]
+fn test_rib_longest_match() {
+    let mut rib = Rib::new();
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+            vec![
+                NLRIEncoding::IP(prefix("10.0.0.0", 8)),
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.1.0.0", 16), 1)),
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.1.0.0", 16), 2)),
+            ],
+        ),
+        1,
+    );
+    rib.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.2".parse().unwrap())],
+            vec![NLRIEncoding::IP(prefix("10.1.0.0", 16))],
+        ),
+        2,
+    );
+
+    let address = prefix("10.1.2.3", 32);
+    let mut routes: Vec<_> = rib
+        .longest_match(AFI::IPV4, SAFI::Unicast, &address)
+        .map(|(peer, key, _)| (*peer, key.prefix.to_string(), key.path_id))
+        .collect();
+    routes.sort();
+    assert_eq!(
+        routes,
+        vec![
+            (1, "10.1.0.0/16".to_string(), 1),
+            (1, "10.1.0.0/16".to_string(), 2),
+            (2, "10.1.0.0/16".to_string(), 0),
+        ]
+    );
+    assert_eq!(
+        rib.longest_match(AFI::IPV4, SAFI::Multicast, &address)
+            .count(),
+        0
+    );
+
+    // Once all routes of the /16 are gone, the /8 matches
+    rib.remove_peer(&2);
+    rib.apply(
+        &Update::new(
+            vec![
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.1.0.0", 16), 1)),
+                NLRIEncoding::IP_WITH_PATH_ID((prefix("10.1.0.0", 16), 2)),
+            ],
+            vec![],
+            vec![],
+        ),
+        1,
+    );
+    let routes: Vec<_> = rib
+        .longest_match(AFI::IPV4, SAFI::Unicast, &address)
+        .map(|(_, key, _)| key.prefix.to_string())
+        .collect();
+    assert_eq!(routes, vec!["10.0.0.0/8"]);
+}
+
+#[test]
+fn test_rib_diff() {
+    let mut old = Rib::new();
+    old.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+            vec![
+                NLRIEncoding::IP(prefix("10.0.0.0", 8)),
+                NLRIEncoding::IP(prefix("10.1.0.0", 16)),
+            ],
+        ),
+        1,
+    );
+    old.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Mpls,
+                "2001:db8::1".parse().unwrap(),
+                vec![
+                    NLRIEncoding::IP_MPLS((prefix("2001:db8:1::", 48), 100)),
+                    NLRIEncoding::IP_MPLS((prefix("2001:db8:2::", 48), 200)),
+                ],
+            )))],
+            vec![],
+        ),
+        1,
+    );
+
+    // Built separately: equal attributes are not shared
+    let mut new = Rib::new();
+    new.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.1".parse().unwrap())],
+            vec![NLRIEncoding::IP(prefix("10.0.0.0", 8))],
+        ),
+        1,
+    );
+    new.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::MP_REACH_NLRI(Box::new(MPReachNLRI::new(
+                AFI::IPV6,
+                SAFI::Mpls,
+                "2001:db8::1".parse().unwrap(),
+                vec![
+                    NLRIEncoding::IP_MPLS((prefix("2001:db8:1::", 48), 100)),
+                    // A new label is announced again
+                    NLRIEncoding::IP_MPLS((prefix("2001:db8:2::", 48), 300)),
+                ],
+            )))],
+            vec![],
+        ),
+        1,
+    );
+    new.apply(
+        &Update::new(
+            vec![],
+            vec![PathAttribute::NEXT_HOP("192.0.2.2".parse().unwrap())],
+            vec![
+                NLRIEncoding::IP(prefix("10.2.0.0", 16)),
+                NLRIEncoding::IP(prefix("10.3.0.0", 16)),
+            ],
+        ),
+        1,
+    );
+
+    let updates = old.diff(&new);
+    let strings: Vec<String> = updates.iter().map(|update| update.to_string()).collect();
+    assert_eq!(
+        strings,
+        vec![
+            "withdrawn 10.1.0.0/16",
+            "10.2.0.0/16 10.3.0.0/16 via 192.0.2.2",
+            "2001:db8:2::/48 label 300 via 2001:db8::1",
+        ]
+    );
+
+    // Replaying the messages turns the old RIB into the new one
+    for update in &updates {
+        old.apply(update, 1);
+    }
+    assert!(old.diff(&new).is_empty());
+    assert!(new.diff(&old).is_empty());
+
+    // All routes of a missing peer are withdrawn
+    let updates = new.diff(&Rib::new());
+    let withdrawn: usize = updates
+        .iter()
+        .map(|update| update.withdrawn().count())
+        .sum();
+    assert_eq!(withdrawn, 5);
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "json")]
+use bgp_rs::*;
+
+#[test]
+fn test_ris_live_update() {
+    let json = r#"{"type": "ris_message", "data": {
+        "timestamp": 1568279796.63, "peer": "2001:db8::1", "peer_asn": "4200000000",
+        "id": "21-2001-db8--1-1234", "host": "rrc21", "type": "UPDATE",
+        "path": [4200000000, 64497, [64498, 64499]], "community": [[64496, 100]],
+        "origin": "incomplete", "med": 10, "aggregator": "64497:192.0.2.1",
+        "announcements": [
+            {"next_hop": "2001:db8::1,fe80::1", "prefixes": ["2001:db8:1::/48", "2001:db8:2::/48"]}
+        ],
+        "withdrawals": ["2001:db8:3::/48", "198.51.100.0/24"]
+    }}"#;
+    let message = RisLiveMessage::from_json(json).unwrap();
+    assert_eq!(message.peer_asn, 4_200_000_000);
+    assert_eq!(message.host, "rrc21");
+    let update = match &message.message {
+        Message::Update(update) => update,
+        message => panic!("Unexpected message: {:?}", message),
+    };
+    assert_eq!(update.withdrawn_routes.len(), 1);
+    match update.get(Identifier::AS_PATH) {
+        Some(PathAttribute::AS_PATH(path)) => {
+            assert_eq!(path.segments.len(), 2);
+            assert_eq!(path.origin(), None);
+        }
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    match update.get(Identifier::COMMUNITY) {
+        Some(PathAttribute::COMMUNITY(communities)) => {
+            assert_eq!(communities, &vec![64496 << 16 | 100])
+        }
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    match update.get(Identifier::MP_REACH_NLRI) {
+        Some(PathAttribute::MP_REACH_NLRI(mp_reach)) => {
+            assert_eq!(mp_reach.next_hop.len(), 32);
+            assert_eq!(mp_reach.announced_routes.len(), 2);
+        }
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+    match update.get(Identifier::MP_UNREACH_NLRI) {
+        Some(PathAttribute::MP_UNREACH_NLRI(mp_unreach)) => {
+            assert_eq!(mp_unreach.withdrawn_routes.len(), 1)
+        }
+        attribute => panic!("Unexpected attribute: {:?}", attribute),
+    }
+
+    // The raw message is included, and preferred when converting back
+    let json = message.to_json().unwrap();
+    assert!(json.contains(r#""aggregator":"64497:192.0.2.1""#));
+    assert!(json.contains(r#""next_hop":"2001:db8::1,fe80::1""#));
+    assert!(json.contains(r#""path":[4200000000,64497,[64498,64499]]"#));
+    let roundtrip = RisLiveMessage::from_json(&json).unwrap();
+    assert_eq!(
+        format!("{:?}", roundtrip.message),
+        format!("{:?}", message.message)
+    );
+}
+
+#[test]
+fn test_ris_live_open_notification() {
+    let json = r#"{"timestamp": 1568279796.63, "peer": "192.0.2.1", "peer_asn": 64496,
+        "host": "rrc00", "type": "OPEN", "direction": "received", "version": 4,
+        "asn": 4200000000, "hold_time": 180, "router_id": "192.0.2.1",
+        "capabilities": {
+            "1": {"name": "multiprotocol", "families": ["ipv4/unicast", "ipv6/unicast"]},
+            "2": {"name": "route-refresh"},
+            "65": {"name": "asn4", "asn": 4200000000}
+        }}"#;
+    let message = RisLiveMessage::from_json(json).unwrap();
+    let open = match &message.message {
+        Message::Open(open) => open,
+        message => panic!("Unexpected message: {:?}", message),
+    };
+    assert_eq!(open.peer_asn, 23456);
+    assert_eq!(open.asn(), 4_200_000_000);
+    let capabilities = Capabilities::from_parameters(open.parameters.clone());
+    assert!(capabilities.FOUR_OCTET_ASN_SUPPORT);
+    assert!(capabilities.ROUTE_REFRESH_SUPPORT);
+    assert_eq!(capabilities.MP_BGP_SUPPORT.len(), 2);
+
+    let json = r#"{"timestamp": 1568279796.63, "peer": "192.0.2.1", "peer_asn": "64496",
+        "host": "rrc00", "type": "NOTIFICATION",
+        "notification": {"code": 6, "subcode": 2, "data": "FF"}}"#;
+    let message = RisLiveMessage::from_json(json).unwrap();
+    match &message.message {
+        Message::Notification(notification) => {
+            assert_eq!(notification.major_err_code(), 6);
+            assert_eq!(notification.minor_err_code(), 2);
+            assert_eq!(notification.data, vec![0xff]);
+        }
+        message => panic!("Unexpected message: {:?}", message),
+    }
+    assert!(message.to_json().unwrap().contains(r#""subcode":2"#));
+
+    let json = r#"{"peer": "192.0.2.1", "peer_asn": "64496", "type": "RIS_PEER_STATE"}"#;
+    assert!(RisLiveMessage::from_json(json).is_err());
+}
@@ -0,0 +1,141 @@
+use bgp_rs::*;
+use std::io::{self, Cursor, Read, Write};
+
+/// A stream replaying the messages of a peer while recording what is sent to it
+struct MockStream {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl MockStream {
+    fn new(messages: &[Message]) -> Self {
+        let mut input = vec![];
+        for message in messages {
+            message.encode(&mut input).unwrap();
+        }
+        MockStream {
+            input: Cursor::new(input),
+            output: vec![],
+        }
+    }
+}
+
+impl Read for MockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for MockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn sent_messages(output: &[u8]) -> Vec<Message> {
+    let mut reader = Reader::new(Cursor::new(output));
+    let mut messages = vec![];
+    while reader.stream.position() < output.len() as u64 {
+        messages.push(reader.read().unwrap().1);
+    }
+    messages
+}
+
+#[test]
+fn test_session_connect() {
+    let mut peer = PeerConfig::new(4200000000, "2.2.2.2".parse().unwrap());
+    peer.hold_time = 30;
+    let update = Update::new(
+        vec![],
+        vec![
+            PathAttribute::ORIGIN(Origin::IGP),
+            PathAttribute::AS_PATH(ASPath {
+                segments: vec![Segment::AS_SEQUENCE(vec![4200000000])],
+            }),
+            PathAttribute::NEXT_HOP("2.2.2.2".parse().unwrap()),
+        ],
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    );
+    let stream = MockStream::new(&[
+        Message::Open(Open::from_config(&peer)),
+        Message::KeepAlive,
+        Message::Update(update),
+    ]);
+
+    let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+    config.peer_asn = Some(4200000000);
+    let mut session = BgpSession::connect(stream, config).unwrap();
+    assert_eq!(session.peer_open().asn(), 4200000000);
+    assert_eq!(session.capabilities().hold_time, Some(30));
+    assert!(session.capabilities().capabilities.FOUR_OCTET_ASN_SUPPORT);
+    assert!(!session.hold_timer_expired());
+    assert!(!session.keepalive_due());
+
+    // The 4-byte AS_PATH is parsed following the negotiated capabilities
+    match session.recv().unwrap() {
+        Message::Update(update) => {
+            let as_path = update.get(Identifier::AS_PATH).unwrap();
+            match as_path {
+                PathAttribute::AS_PATH(as_path) => assert_eq!(as_path.origin(), Some(4200000000)),
+                _ => unreachable!(),
+            }
+        }
+        message => panic!("Expected UPDATE, got {:?}", message),
+    }
+    session.tick().unwrap();
+
+    let metrics = session.metrics();
+    assert_eq!(metrics.session_state, SessionState::Established);
+    assert_eq!(metrics.messages[&MessageType::Update], 1);
+    assert_eq!(metrics.announced[&(AFI::IPV4, SAFI::Unicast)], 1);
+}
+
+#[test]
+fn test_session_bad_peer_as() {
+    let peer = PeerConfig::new(65001, "2.2.2.2".parse().unwrap());
+    let stream = MockStream::new(&[Message::Open(Open::from_config(&peer))]);
+
+    let mut config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+    config.peer_asn = Some(65002);
+    let mut output = vec![];
+    {
+        let mut stream = stream;
+        let result = BgpSession::connect(&mut stream, config);
+        let error = result.err().unwrap();
+        assert_eq!(
+            error.to_notification().code,
+            NotificationCode::OpenMessage(OpenMessageSubcode::BadPeerAS)
+        );
+        output.extend(stream.output);
+    }
+
+    let messages = sent_messages(&output);
+    assert_eq!(messages.len(), 2);
+    match &messages[1] {
+        Message::Notification(notification) => {
+            assert_eq!(
+                notification.code,
+                NotificationCode::OpenMessage(OpenMessageSubcode::BadPeerAS)
+            );
+            assert_eq!(notification.data, vec![0xfd, 0xe9]);
+        }
+        message => panic!("Expected NOTIFICATION, got {:?}", message),
+    }
+}
+
+#[test]
+fn test_session_unexpected_message() {
+    let stream = MockStream::new(&[Message::KeepAlive]);
+    let config = PeerConfig::new(65000, "1.1.1.1".parse().unwrap());
+    let error = BgpSession::connect(stream, config).err().unwrap();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::FiniteStateMachine(
+            FiniteStateMachineSubcode::UnexpectedMessageInOpenSent
+        )
+    );
+}
@@ -0,0 +1,40 @@
+use bgp_rs::*;
+
+#[test]
+fn test_decode_messages() {
+    let capabilities = Capabilities::default();
+    let update = Message::Update(Update::new(
+        vec![],
+        vec![PathAttribute::ORIGIN(Origin::IGP)],
+        vec![NLRIEncoding::IP(("10.0.0.0".parse().unwrap(), 8).into())],
+    ));
+    let mut data = encode_message(&update, None).unwrap();
+    data.extend(encode_message(&Message::KeepAlive, None).unwrap());
+    assert_eq!(
+        decode_messages(&data, &capabilities).unwrap(),
+        vec![update, Message::KeepAlive]
+    );
+
+    // A single message must fill the whole slice
+    let error = decode_message(&data, &capabilities).unwrap_err();
+    assert_eq!(error.message, "Chunk holds 19 bytes after the message");
+}
+
+#[test]
+fn test_message_error_notification() {
+    #[rustfmt::skip]
+    let message = vec![
+        0, 26, 2, // Header
+        0, 0, // Withdrawn Routes Length
+        0, 0, // Path Attribute Length
+        24, 10, 0, // A truncated prefix
+    ];
+    let mut data = vec![0xff; 16];
+    data.extend(message);
+    let error = decode_message(&data, &Capabilities::default()).unwrap_err();
+    assert_eq!(
+        error.to_notification().code,
+        NotificationCode::UpdateMessage(UpdateMessageSubcode::InvalidNetworkField)
+    );
+    assert_eq!(error.clone(), error);
+}
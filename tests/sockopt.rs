@@ -0,0 +1,34 @@
+#![cfg(all(feature = "sockopt", target_os = "linux"))]
+use bgp_rs::*;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+#[test]
+fn test_socket_options_connect() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let options = SocketOptions {
+        md5_key: Some(b"secret".to_vec()),
+        gtsm_hops: Some(1),
+    };
+    options.apply(&listener, addr.ip()).unwrap();
+
+    let mut stream = options.connect(addr).unwrap();
+    let (mut accepted, _) = listener.accept().unwrap();
+    stream.write_all(b"bgp").unwrap();
+    let mut buf = [0; 3];
+    accepted.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"bgp");
+}
+
+#[test]
+fn test_socket_options_errors() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    assert!(set_md5_key(&listener, addr.ip(), &[0; 81]).is_err());
+    // The address family of the peer does not match the socket
+    assert!(set_md5_key(&listener, "::1".parse().unwrap(), b"secret").is_err());
+    // An empty key removes it
+    set_md5_key(&listener, addr.ip(), b"secret").unwrap();
+    set_md5_key(&listener, addr.ip(), b"").unwrap();
+}
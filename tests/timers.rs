@@ -0,0 +1,52 @@
+use bgp_rs::*;
+use std::time::Duration;
+
+#[test]
+fn test_hold_timer() {
+    let timer = HoldTimer::negotiate(90, 180).unwrap();
+    assert_eq!(timer.hold_time(), Duration::from_secs(90));
+    assert!(!timer.is_expired());
+    let deadline = timer.deadline().unwrap();
+    let mut reset = timer.clone();
+    reset.reset();
+    assert!(reset.deadline().unwrap() >= deadline);
+
+    // Unacceptable proposals of the peer
+    for hold_time in &[1, 2] {
+        let error = HoldTimer::negotiate(90, *hold_time).unwrap_err();
+        assert_eq!(
+            error.to_notification().minor_err_code(),
+            OpenMessageSubcode::UnacceptableHoldTime as u8
+        );
+    }
+
+    // Disabled by a Hold Time of zero
+    let timer = HoldTimer::negotiate(90, 0).unwrap();
+    assert_eq!(timer.hold_time(), Duration::from_secs(0));
+    assert!(timer.deadline().is_none());
+    assert!(!timer.is_expired());
+
+    let notification = timer.expired_error().to_notification();
+    assert_eq!(notification.major_err_code(), 4);
+    assert_eq!(notification.minor_err_code(), 0);
+    let notification = HoldTimer::expired_notification();
+    assert_eq!(notification.major_err_code(), 4);
+    assert!(notification.data.is_empty());
+}
+
+#[test]
+fn test_keepalive_timer() {
+    assert_eq!(keepalive_interval(90), Some(30));
+    assert_eq!(keepalive_interval(3), Some(1));
+    assert_eq!(keepalive_interval(0), None);
+
+    let timer = HoldTimer::new(90).keepalive_timer();
+    assert_eq!(timer.interval(), Some(Duration::from_secs(30)));
+    assert!(!timer.is_due());
+    assert!(timer.deadline().is_some());
+
+    let timer = KeepaliveTimer::new(0);
+    assert_eq!(timer.interval(), None);
+    assert!(timer.deadline().is_none());
+    assert!(!timer.is_due());
+}
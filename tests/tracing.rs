@@ -0,0 +1,88 @@
+#![cfg(feature = "tracing")]
+use bgp_rs::*;
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+// Collects the messages of events, and the names of spans
+#[derive(Clone, Default)]
+struct Collector {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+
+impl<'a> Visit for MessageVisitor<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0.push_str(&format!("{:?}", value));
+        }
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.lines
+            .lock()
+            .unwrap()
+            .push(format!("span {}", span.metadata().name()));
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = String::new();
+        event.record(&mut MessageVisitor(&mut message));
+        self.lines.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn test_trace_parse_decisions() {
+    #[rustfmt::skip]
+    let message = vec![
+        0, 55, 2, // Header
+        0, 0, // Withdrawn Routes Length
+        0, 24, // Path Attribute Length
+        0x40, 1, 1, 5, // ORIGIN with an invalid value
+        0x40, 2, 10, 2, 2, 0, 0, 0xfd, 0xe8, 0, 0, 0xfd, 0xe9, // AS_PATH of 4-byte ASNs
+        0x40, 3, 4, 10, 0, 0, 1, // NEXT_HOP
+        0, 0, 0, 1, 24, 10, 0, 0, // 10.0.0.0/24 with a Path Identifier
+    ];
+    let mut data = vec![0xff; 16];
+    data.extend(message);
+
+    let collector = Collector::default();
+    let (update, warnings) = tracing::subscriber::with_default(collector.clone(), || {
+        let mut reader = Reader::new(std::io::Cursor::new(data));
+        match reader.read_lossy().unwrap() {
+            (_, Message::Update(update), warnings) => (update, warnings),
+            (_, message, _) => panic!("Unexpected message {:?}", message),
+        }
+    });
+    assert_eq!(update.announced_routes.len(), 1);
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        *collector.lines.lock().unwrap(),
+        vec![
+            "span message",
+            "Skipped attribute",
+            "AS_PATH holds ASNs of the other width",
+            "ADD-PATH detected contrary to the capabilities",
+        ]
+    );
+}
@@ -0,0 +1,165 @@
+use bgp_rs::{Prefix, PrefixTrie};
+
+fn prefix(prefix: &str) -> Prefix {
+    prefix.parse().unwrap()
+}
+
+fn prefixes(trie: &PrefixTrie<u32>) -> Vec<String> {
+    trie.iter().map(|(prefix, _)| prefix.to_string()).collect()
+}
+
+#[test]
+fn test_trie_insert_remove() {
+    let mut trie = PrefixTrie::new();
+    assert!(trie.is_empty());
+    assert_eq!(trie.insert(&prefix("10.0.0.0/8"), 1), None);
+    assert_eq!(trie.insert(&prefix("10.128.0.0/9"), 2), None);
+    assert_eq!(trie.insert(&prefix("10.0.0.0/9"), 3), None);
+    assert_eq!(trie.insert(&prefix("0.0.0.0/0"), 4), None);
+    assert_eq!(trie.insert(&prefix("2001:db8::/32"), 5), None);
+    assert_eq!(trie.insert(&prefix("10.0.0.0/8"), 6), Some(1));
+    assert_eq!(trie.len(), 5);
+
+    assert_eq!(trie.get(&prefix("10.0.0.0/8")), Some(&6));
+    assert_eq!(trie.get(&prefix("10.0.0.0/16")), None);
+    // Host bits are ignored
+    assert_eq!(trie.get(&prefix("10.1.2.3/8")), Some(&6));
+    *trie.get_mut(&prefix("2001:db8::/32")).unwrap() += 1;
+    assert_eq!(trie.get(&prefix("2001:db8::/32")), Some(&6));
+
+    assert_eq!(
+        prefixes(&trie),
+        vec![
+            "0.0.0.0/0",
+            "10.0.0.0/8",
+            "10.0.0.0/9",
+            "10.128.0.0/9",
+            "2001:db8::/32"
+        ]
+    );
+
+    assert_eq!(trie.remove(&prefix("10.0.0.0/16")), None);
+    assert_eq!(trie.remove(&prefix("10.0.0.0/8")), Some(6));
+    assert_eq!(trie.remove(&prefix("10.0.0.0/8")), None);
+    assert_eq!(trie.get(&prefix("10.0.0.0/9")), Some(&3));
+    assert_eq!(trie.remove(&prefix("0.0.0.0/0")), Some(4));
+    assert_eq!(
+        prefixes(&trie),
+        vec!["10.0.0.0/9", "10.128.0.0/9", "2001:db8::/32"]
+    );
+    assert_eq!(trie.len(), 3);
+}
+
+#[test]
+fn test_trie_longest_match() {
+    let trie: PrefixTrie<u32> = vec![
+        (prefix("10.0.0.0/8"), 8),
+        (prefix("10.1.0.0/16"), 16),
+        (prefix("10.1.1.0/24"), 24),
+        (prefix("::/0"), 0),
+    ]
+    .into_iter()
+    .collect();
+
+    let matched = |query: &str| {
+        trie.longest_match(&prefix(query))
+            .map(|(prefix, value)| (prefix.to_string(), *value))
+    };
+    assert_eq!(matched("10.1.1.1/32"), Some(("10.1.1.0/24".into(), 24)));
+    assert_eq!(matched("10.1.2.1/32"), Some(("10.1.0.0/16".into(), 16)));
+    assert_eq!(matched("10.1.0.0/16"), Some(("10.1.0.0/16".into(), 16)));
+    assert_eq!(matched("10.1.0.0/15"), Some(("10.0.0.0/8".into(), 8)));
+    assert_eq!(matched("10.2.0.0/16"), Some(("10.0.0.0/8".into(), 8)));
+    assert_eq!(matched("11.0.0.0/8"), None);
+    assert_eq!(matched("0.0.0.0/0"), None);
+    assert_eq!(matched("2001:db8::1/128"), Some(("::/0".into(), 0)));
+}
+
+#[test]
+fn test_trie_subtree() {
+    let trie: PrefixTrie<u32> = vec![
+        "10.0.0.0/8",
+        "10.1.0.0/16",
+        "10.1.1.0/24",
+        "10.2.0.0/16",
+        "11.0.0.0/8",
+    ]
+    .into_iter()
+    .map(|value| (prefix(value), 0))
+    .collect();
+    let subtree = |query: &str| -> Vec<String> {
+        trie.subtree(&prefix(query))
+            .map(|(prefix, _)| prefix.to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        subtree("10.0.0.0/8"),
+        vec!["10.0.0.0/8", "10.1.0.0/16", "10.1.1.0/24", "10.2.0.0/16"]
+    );
+    assert_eq!(subtree("10.1.0.0/16"), vec!["10.1.0.0/16", "10.1.1.0/24"]);
+    // Prefixes without a value of their own
+    assert_eq!(
+        subtree("10.0.0.0/14"),
+        vec!["10.1.0.0/16", "10.1.1.0/24", "10.2.0.0/16"]
+    );
+    assert_eq!(subtree("10.0.0.0/7").len(), 5);
+    assert!(subtree("10.3.0.0/16").is_empty());
+    assert!(subtree("2001:db8::/32").is_empty());
+    assert_eq!(subtree("0.0.0.0/0").len(), 5);
+}
+
+#[test]
+fn test_trie_longest_match_linear_search() {
+    // Pseudo-random prefixes, checked against a linear search
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut trie = PrefixTrie::new();
+    let mut inserted: Vec<Prefix> = vec![];
+    for _ in 0..500 {
+        let value = random();
+        // Keep the addresses close to each other for nested prefixes
+        let address = std::net::Ipv4Addr::from(0x0a00_0000 | (value as u32 & 0x00ff_ffff));
+        let length = (value >> 32) as u8 % 25 + 8;
+        let new = Prefix::from((address.into(), length));
+        if !inserted.iter().any(|prefix| same(prefix, &new)) {
+            inserted.push(new.clone());
+        }
+        trie.insert(&new, ());
+    }
+    assert_eq!(trie.len(), inserted.len());
+
+    for _ in 0..500 {
+        let address = std::net::Ipv4Addr::from(0x0a00_0000 | (random() as u32 & 0x00ff_ffff));
+        let query = Prefix::from((address.into(), 32));
+        let expected = inserted
+            .iter()
+            .filter(|prefix| covers(prefix, &query))
+            .max_by_key(|prefix| prefix.length);
+        let matched = trie.longest_match(&query).map(|(prefix, _)| prefix);
+        assert_eq!(matched.map(|p| p.length), expected.map(|p| p.length));
+    }
+}
+
+fn bits(prefix: &Prefix) -> u32 {
+    let mut octets = [0; 4];
+    octets.copy_from_slice(&prefix.prefix[..4]);
+    u32::from_be_bytes(octets)
+        & (!0u32)
+            .checked_shl(32 - u32::from(prefix.length))
+            .unwrap_or(0)
+}
+
+fn covers(prefix: &Prefix, other: &Prefix) -> bool {
+    prefix.length <= other.length
+        && bits(prefix) == bits(other) & (!0u32 << (32 - u32::from(prefix.length)))
+}
+
+fn same(prefix: &Prefix, other: &Prefix) -> bool {
+    prefix.length == other.length && bits(prefix) == bits(other)
+}